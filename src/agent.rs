@@ -0,0 +1,668 @@
+//! Agent loop for claWasm
+//!
+//! One user turn: filter and redact the conversation, ask the provider,
+//! execute every tool call in the reply (subject to the security manager),
+//! feed the results back, and repeat until the model answers without tools
+//! or the iteration budget runs out. A model that only repeats calls it
+//! already made is told to answer with what it has; if it keeps looping,
+//! or is still calling tools at the end, the turn ends with a best-effort
+//! answer built from the results so far. With the strict tool format,
+//! a reply whose calls break the grammar is sent back for correction
+//! first (see `tool_grammar`). The browser bindings and the
+//! native build share this loop; only the `ToolRunner` differs.
+
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use crate::audit::{self, AuditKind};
+use crate::chat::{Message, Role};
+use crate::config::Config;
+use crate::content_filter::{self, FilterStage};
+use crate::error::Result;
+use crate::memory::{self, MemorySystem};
+use crate::logger;
+use crate::providers::ChatModel;
+use crate::rag;
+use crate::reasoning::{self, ReasoningMode};
+use crate::security::{SecurityDecision, SecurityManager};
+use crate::tool_grammar::{self, ToolFormat};
+use crate::tool_xml;
+use crate::tools;
+use crate::trace::SpanHandle;
+
+/// Characters per tool-result part sent back to the model
+const RESULT_BATCH_CHARS: usize = 800;
+/// Trim the context beyond this many messages...
+const MAX_CONTEXT_MESSAGES: usize = 20;
+/// ...or this many characters
+const MAX_CONTEXT_CHARS: usize = 100_000;
+/// Size the trimmed context is cut down to
+const TRIMMED_CONTEXT_CHARS: usize = 80_000;
+/// Corrections sent to a model repeating its calls before giving up on it
+const LOOP_NUDGES: usize = 2;
+/// Characters of each tool result quoted in a best-effort answer
+const BEST_EFFORT_CHARS: usize = 600;
+/// Sent after an iteration made of repeated calls only
+const LOOP_CORRECTION: &str = "You are repeating tool calls you already made, and their results are above. \
+    Do not call those tools again: answer the user now with what you have, and say what is still missing, if anything.";
+
+thread_local! {
+    /// Set while `consolidate_if_due` waits on the provider
+    static CONSOLIDATING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Tool call structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// Future returned by a `ToolRunner`
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + 'a>>;
+
+/// Executes tool calls that the security manager allowed
+pub trait ToolRunner {
+    fn run<'a>(&'a self, name: &'a str, args: &'a serde_json::Value) -> ToolFuture<'a>;
+}
+
+/// What one turn produced
+#[derive(Debug, Clone)]
+pub struct TurnOutput {
+    pub response: String,
+    /// Every tool call made along the way, in order
+    pub tool_calls: Vec<ToolCall>,
+    /// The model's thinking for each reply that had any, with
+    /// `reasoning: "expose"`; empty otherwise
+    pub reasoning: Vec<String>,
+}
+
+/// State a turn runs against; cheap to clone into a future
+#[derive(Clone)]
+pub struct Agent {
+    /// Shared with the instance that started the turn, not copied per turn
+    pub config: Rc<Config>,
+    pub provider: Rc<dyn ChatModel>,
+    pub memory: Rc<RefCell<MemorySystem>>,
+    pub security: Rc<RefCell<SecurityManager>>,
+    /// Who the agent is talking to, for permission profiles
+    pub subject: Option<String>,
+}
+
+impl Agent {
+    /// Run one turn over `history` (the last message being the user's)
+    ///
+    /// The turn works on `history` in place rather than on a copy: replies
+    /// and tool results are appended as it goes and taken off again at the
+    /// end, whether it succeeded or not. The last message is left as sent
+    /// (content-filtered, without the retrieved knowledge), and secrets
+    /// stay redacted throughout.
+    pub async fn run_turn(&self, history: &mut Vec<Message>, tools: &dyn ToolRunner) -> Result<TurnOutput> {
+        let mut span = SpanHandle::root("agent.turn");
+        span.attr("provider", self.config.provider.active.as_str());
+        span.attr("model", self.config.provider.model.as_str());
+        if let Some(subject) = &self.subject {
+            span.attr("subject", subject.as_str());
+        }
+        let len = history.len();
+        let mut sent = None;
+        let result = self.traced_turn(history, &mut sent, tools, &mut span).await;
+        history.truncate(len);
+        if let (Some(last), Some(sent)) = (history.last_mut(), sent) {
+            last.content = sent;
+        }
+        span.finish(&result);
+        result
+    }
+
+    /// `sent` gets the user's message as it goes out, before knowledge is
+    /// added to it
+    async fn traced_turn(&self, history: &mut Vec<Message>, sent: &mut Option<String>, tools: &dyn ToolRunner, span: &mut SpanHandle) -> Result<TurnOutput> {
+        let config = &self.config;
+        let provider = self.provider.as_ref();
+        let policy = self.security.borrow().get_config().clone();
+        let profile = policy.resolve_profile(self.subject.as_deref()).cloned();
+        let max_iterations = profile.as_ref().map(|p| p.max_iterations).unwrap_or(10);
+
+        if let Some(last) = history.last_mut() {
+            last.content = content_filter::apply(FilterStage::PreSend, &last.content, &policy.content_filter, config).await?;
+        }
+        policy.redact_messages(history);
+        *sent = history.last().map(|m| m.content.clone());
+        if config.rag.enabled {
+            match rag::inject(&self.memory, &config.rag, history).await {
+                Ok(passages) => {
+                    span.attr("rag_passages", passages);
+                    let last = history.len().saturating_sub(1);
+                    policy.redact_messages(&mut history[last..]);
+                }
+                Err(e) => logger::warn("agent", &format!("Knowledge retrieval failed: {}", e)),
+            }
+        }
+        // The history with the turn appended, until it outgrows the limits
+        // and a trimmed copy takes over
+        let mut trimmed: Option<Vec<Message>> = None;
+        let mut reasoning = Vec::new();
+        let mut response = traced_complete(provider, history, config, span, &mut reasoning).await?;
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        // Results of the calls run so far, by name and arguments
+        let mut executed: HashMap<(String, String), String> = HashMap::new();
+        // Every call asked for so far, whatever came of it
+        let mut seen: HashSet<(String, String)> = HashSet::new();
+        // Successful results in order, for a best-effort answer
+        let mut results: Vec<(String, String)> = Vec::new();
+        let mut repeats = 0;
+        let mut nudges = 0;
+        let mut reasks = 0;
+        
+        // Loop: if AI calls tools, execute ALL of them and send results back
+        let mut iterations = 0;
+        while iterations < max_iterations {
+            iterations += 1;
+            
+            let calls = match config.tool_format {
+                ToolFormat::Loose => parse_all_tool_calls(&response),
+                // Malformed calls go back to the model until it gets them right
+                ToolFormat::Strict => loop {
+                    match tool_grammar::parse(&response, &tools::get_tool_definitions()) {
+                        Ok(calls) => break calls,
+                        Err(errors) if reasks < tool_grammar::MAX_REASKS => {
+                            reasks += 1;
+                            logger::debug("agent", &format!("Malformed tool call, asking again: {}", errors.join("; ")));
+                            let context = trimmed.as_mut().unwrap_or(&mut *history);
+                            context.push(Message { role: Role::Assistant, content: std::mem::take(&mut response) });
+                            context.push(Message::user(&tool_grammar::reask(&errors)));
+                            response = traced_complete(provider, context, config, span, &mut reasoning).await?;
+                        }
+                        Err(errors) => {
+                            logger::warn("agent", &format!("Tool call still malformed after {} corrections: {}", reasks, errors.join("; ")));
+                            break Vec::new();
+                        }
+                    }
+                },
+            };
+            if calls.is_empty() {
+                // No tool calls, we have a final response
+                break;
+            }
+            let mut iteration_span = span.child("agent.iteration");
+            iteration_span.attr("iteration", iterations);
+            iteration_span.attr("tool_calls", calls.len());
+            
+            // Execute ALL tool calls found
+            let mut tool_results = Vec::new();
+            let mut fresh = 0;
+            for tool_call in calls {
+                let tier = tools::tool_tier(&tool_call.name);
+                let decision = self.security.borrow_mut().authorize_tool(&tool_call.name, &tool_call.arguments, tier, profile.as_ref());
+                let mut tool_span = iteration_span.child("tool.execute");
+                tool_span.attr("tool", tool_call.name.as_str());
+                tool_span.attr("arguments", tool_call.arguments.to_string());
+                let key = (tool_call.name.clone(), tool_call.arguments.to_string());
+                // A denied or failing call asked for again is a repeat too
+                if seen.insert(key.clone()) {
+                    fresh += 1;
+                }
+                let tool_result = match decision {
+                    SecurityDecision::Allow if executed.contains_key(&key) => {
+                        // A looping model asks again; answer from the first run
+                        tool_span.attr("decision", "allow");
+                        tool_span.attr("repeat", true);
+                        repeats += 1;
+                        logger::debug("agent", &format!("Repeated call to {} answered from the earlier result", tool_call.name));
+                        repeat_notice(&tool_call.name, &executed[&key])
+                    }
+                    SecurityDecision::Allow => {
+                        tool_span.attr("decision", "allow");
+                        let result = tools.run(&tool_call.name, &tool_call.arguments).await;
+                        tool_span.finish(&result);
+                        match result {
+                            Ok(result) => {
+                                executed.insert(key, result.clone());
+                                results.push((tool_call.name.clone(), result.clone()));
+                                result
+                            }
+                            // Errors may be transient, so a retry runs again
+                            Err(e) => format!("Error: {}", e),
+                        }
+                    }
+                    SecurityDecision::Deny { reason } => {
+                        tool_span.attr("decision", "deny");
+                        audit::record(AuditKind::SecurityDecision, &tool_call.name, "deny", &reason);
+                        format!("⛔ {}", reason)
+                    }
+                    SecurityDecision::RequireApproval { message } => {
+                        tool_span.attr("decision", "require_approval");
+                        audit::record(AuditKind::SecurityDecision, &tool_call.name, "require_approval", &message);
+                        format!("⛔ {}", message)
+                    }
+                };
+                tool_span.attr("result_chars", tool_result.chars().count());
+                drop(tool_span);
+                tool_results.extend(tool_result_parts(&tool_call.name, &tool_result));
+                tool_calls.push(tool_call);
+            }
+            
+            // Nothing new this time: the model is going round in circles
+            let looping = fresh == 0;
+            if looping {
+                nudges += 1;
+                iteration_span.attr("loop_detected", true);
+                if nudges > LOOP_NUDGES {
+                    logger::info("agent", "Tool loop persists after corrections; answering with what was found");
+                    break;
+                }
+            }
+            
+            // Add assistant's response to messages; a new one replaces it below
+            let context = trimmed.as_mut().unwrap_or(&mut *history);
+            context.push(Message { role: Role::Assistant, content: std::mem::take(&mut response) });
+            
+            // Add all tool results as one message, redacting only that one
+            let start = context.len();
+            context.push(Message { role: Role::User, content: tool_results.join("\n\n---\n\n") });
+            policy.redact_messages(&mut context[start..]);
+            if looping {
+                // The latest correction replaces any earlier one
+                context.retain(|m| !(matches!(m.role, Role::System) && m.content == LOOP_CORRECTION));
+                context.push(Message::system(LOOP_CORRECTION));
+            }
+            
+            if let Some(smaller) = trim_context(context) {
+                iteration_span.attr("context_trimmed", true);
+                logger::debug("agent", &format!(
+                    "Context trimmed: {} messages, {} chars",
+                    smaller.len(),
+                    smaller.iter().map(|m| m.content.chars().count()).sum::<usize>()
+                ));
+                trimmed = Some(smaller);
+            }
+            
+            // Get AI's response to tool results
+            let context = trimmed.as_deref().unwrap_or(&history[..]);
+            response = traced_complete(provider, context, config, &iteration_span, &mut reasoning).await?;
+        }
+        span.attr("iterations", iterations);
+        span.attr("tool_calls", tool_calls.len());
+        if repeats > 0 {
+            span.attr("repeated_tool_calls", repeats);
+        }
+        if nudges > 0 {
+            span.attr("loop_corrections", nudges.min(LOOP_NUDGES));
+        }
+        if reasks > 0 {
+            span.attr("format_corrections", reasks);
+        }
+        let still_calling = match config.tool_format {
+            ToolFormat::Loose => !parse_all_tool_calls(&response).is_empty(),
+            ToolFormat::Strict => response.contains(tool_grammar::OPEN) || tool_grammar::parse(&response, &tools::get_tool_definitions()).is_err(),
+        };
+        if still_calling {
+            // Still calling tools when the budget or the corrections ran out
+            if iterations >= max_iterations {
+                span.attr("iteration_limit_reached", true);
+            }
+            span.attr("best_effort", true);
+            response = best_effort(&response, &results);
+        }
+        
+        let response = content_filter::apply(FilterStage::PostReceive, &response, &policy.content_filter, config).await?;
+
+        Ok(TurnOutput { response, tool_calls, reasoning })
+    }
+
+    /// Fold expired memories into summaries, if it is time to
+    ///
+    /// This asks the provider once more, so it is not part of a turn: the
+    /// caller runs it once the answer is out (the browser in the
+    /// background, the CLI before its next prompt). Failures are only
+    /// logged, and a run already under way makes this one a no-op.
+    pub async fn consolidate_if_due(&self) {
+        if CONSOLIDATING.with(Cell::get) || !self.memory.borrow().is_consolidation_due(chrono::Utc::now().timestamp()) {
+            return;
+        }
+        CONSOLIDATING.with(|c| c.set(true));
+        let policy = self.security.borrow().get_config().clone();
+        let result = memory::consolidate_with_provider(&self.memory, self.provider.as_ref(), &self.config, &policy).await;
+        CONSOLIDATING.with(|c| c.set(false));
+        if let Err(e) = result {
+            logger::warn("agent", &format!("Memory consolidation failed: {}", e));
+        }
+    }
+}
+
+/// Ask the model, recording a `provider.chat` span under `parent`
+///
+/// The reply comes back without its thinking, which is dropped, logged or
+/// added to `reasoning` as `config.reasoning` says.
+async fn traced_complete(provider: &dyn ChatModel, messages: &[Message], config: &Config, parent: &SpanHandle, reasoning: &mut Vec<String>) -> Result<String> {
+    let mut span = parent.child("provider.chat");
+    span.attr("messages", messages.len());
+    span.attr("prompt_chars", messages.iter().map(|m| m.content.chars().count()).sum::<usize>());
+    let result = provider.complete(messages, config).await
+        .map(|response| reasoning::split(&response));
+    if let Ok((answer, thinking)) = &result {
+        span.attr("response_chars", answer.chars().count());
+        if let Some(thinking) = thinking {
+            span.attr("reasoning_chars", thinking.chars().count());
+        }
+    }
+    span.finish(&result);
+    let (answer, thinking) = result?;
+    match (thinking, config.reasoning) {
+        (Some(thinking), ReasoningMode::Log) => logger::info("reasoning", &thinking),
+        (Some(thinking), ReasoningMode::Expose) => reasoning.push(thinking),
+        _ => {}
+    }
+    Ok(answer)
+}
+
+/// An answer for a turn that ended still calling tools: the reply's text
+/// without its tool calls, or else excerpts of what the tools returned
+fn best_effort(response: &str, results: &[(String, String)]) -> String {
+    let text = strip_tool_calls(response);
+    if !text.is_empty() {
+        return text;
+    }
+    if results.is_empty() {
+        return "I couldn't finish this: I kept calling tools without getting anywhere. \
+            Try rephrasing or narrowing the request.".to_string();
+    }
+    let found: Vec<String> = results.iter()
+        .map(|(name, result)| {
+            let mut excerpt: String = result.chars().take(BEST_EFFORT_CHARS).collect();
+            if result.chars().count() > BEST_EFFORT_CHARS {
+                excerpt.push('…');
+            }
+            format!("**{}**:\n{}", name, excerpt.trim())
+        })
+        .collect();
+    format!("I kept calling tools without reaching an answer, so here is what they returned:\n\n{}", found.join("\n\n"))
+}
+
+/// `response` without ```tool blocks, strict and XML calls and bare JSON
+/// tool calls
+fn strip_tool_calls(response: &str) -> String {
+    let response = tool_xml::strip(&tool_grammar::strip(response));
+    let mut text = String::new();
+    let mut rest = response.as_str();
+    while let Some(start) = rest.find("```tool") {
+        text.push_str(&rest[..start]);
+        let block = &rest[start + 7..];
+        rest = block.find("```").map(|end| &block[end + 3..]).unwrap_or("");
+    }
+    text.push_str(rest);
+
+    let is_call = |json: &str| serde_json::from_str::<serde_json::Value>(json)
+        .is_ok_and(|value| value.get("name").is_some_and(|name| name.is_string()));
+    let mut kept = String::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 && !is_call(&text[start..=i]) {
+                    kept.push_str(&text[start..=i]);
+                }
+            }
+            _ if depth == 0 => kept.push(c),
+            _ => {}
+        }
+    }
+    // A truncated call at the end is dropped with the rest of it
+    if depth > 0 && !text[start..].contains("\"name\"") {
+        kept.push_str(&text[start..]);
+    }
+    kept.trim().to_string()
+}
+
+/// What a repeated call gets instead of running again
+fn repeat_notice(name: &str, result: &str) -> String {
+    format!(
+        "[Already called '{}' with these exact arguments in this turn; this is the earlier result. \
+        Use it, or call with different arguments, instead of repeating the call.]\n{}",
+        name, result
+    )
+}
+
+/// A tool result as sent back to the model, split into parts if long
+fn tool_result_parts(name: &str, result: &str) -> Vec<String> {
+    let result_len = result.chars().count();
+    if result_len <= RESULT_BATCH_CHARS {
+        return vec![format!("Tool '{}' returned:\n{}", name, result)];
+    }
+
+    let chars: Vec<char> = result.chars().collect();
+    let batch_num = result_len.div_ceil(RESULT_BATCH_CHARS);
+    chars.chunks(RESULT_BATCH_CHARS)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let batch = format!("[Part {}/{}]\n{}", idx + 1, batch_num, chunk.iter().collect::<String>());
+            if idx == 0 {
+                format!("Tool '{}' (split into {} parts):\n{}", name, batch_num, batch)
+            } else {
+                batch
+            }
+        })
+        .collect()
+}
+
+/// Trim the context if it has too many messages or characters
+///
+/// System messages are kept; the most recent messages that fit the size
+/// limit follow them. Returns the trimmed context, None when `messages`
+/// is within the limits.
+fn trim_context(messages: &[Message]) -> Option<Vec<Message>> {
+    let total_size: usize = messages.iter().map(|m| m.content.chars().count()).sum();
+    if messages.len() <= MAX_CONTEXT_MESSAGES && total_size <= MAX_CONTEXT_CHARS {
+        return None;
+    }
+
+    // Count back from the most recent message while the size limit allows
+    let mut recent = 0;
+    let mut current_size = 0;
+    for msg in messages.iter().rev().filter(|m| !matches!(m.role, Role::System)) {
+        let msg_size = msg.content.chars().count();
+        if current_size + msg_size > TRIMMED_CONTEXT_CHARS {
+            break;
+        }
+        current_size += msg_size;
+        recent += 1;
+    }
+    
+    // System messages first, then the recent ones
+    let older = messages.iter().filter(|m| !matches!(m.role, Role::System)).count() - recent;
+    let system = messages.iter().filter(|m| matches!(m.role, Role::System));
+    let rest = messages.iter().filter(|m| !matches!(m.role, Role::System)).skip(older);
+    Some(system.chain(rest).cloned().collect())
+}
+
+/// Parse ALL tool calls from response
+pub fn parse_all_tool_calls(response: &str) -> Vec<ToolCall> {
+    // XML markup (GLM and other local models) may wrap JSON of its own
+    if tool_xml::has_markup(response) {
+        let calls = tool_xml::parse(response);
+        if !calls.is_empty() {
+            return calls;
+        }
+    }
+
+    let mut calls = Vec::new();
+    
+    // Check for incomplete JSON (response ends with incomplete JSON)
+    let open_braces = response.matches('{').count();
+    let close_braces = response.matches('}').count();
+    let open_brackets = response.matches('[').count();
+    let close_brackets = response.matches(']').count();
+    
+    if open_braces > close_braces || open_brackets > close_brackets {
+        // Incomplete JSON detected - try to find complete JSONs only
+        // This means the response was truncated
+        logger::warn("agent", &format!(
+            "Incomplete JSON detected ({{:{}/}}:{}, [:{}/]:{})", 
+            open_braces, close_braces, open_brackets, close_brackets
+        ));
+    }
+    
+    // Find all ```tool ... ``` blocks
+    let mut search_start = 0;
+    while let Some(start) = response[search_start..].find("```tool") {
+        let rest = &response[search_start + start + 7..];
+        if let Some(end_relative) = rest.find("```") {
+            let tool_json = rest[..end_relative].trim();
+            if let Ok(call) = serde_json::from_str::<ToolCall>(tool_json) {
+                calls.push(call);
+            }
+        }
+        search_start += start + 7;
+    }
+    
+    // Find all JSON objects with "name" field
+    let mut depth = 0;
+    let mut start_idx = None;
+    
+    for (i, c) in response.char_indices() {
+        if c == '{' {
+            if depth == 0 {
+                start_idx = Some(i);
+            }
+            depth += 1;
+        } else if c == '}' {
+            depth -= 1;
+            if depth == 0 {
+                if let Some(start) = start_idx {
+                    let json_str = &response[start..i+1];
+                    // Try to parse as ToolCall with arguments
+                    if let Ok(call) = serde_json::from_str::<ToolCall>(json_str) {
+                        // Avoid duplicates
+                        if !calls.iter().any(|c| c.name == call.name && c.arguments == call.arguments) {
+                            calls.push(call);
+                        }
+                    } else if let Ok(obj) = serde_json::from_str::<serde_json::Value>(json_str) {
+                        if let Some(name) = obj.get("name").and_then(|n| n.as_str()) {
+                            let mut args = serde_json::Map::new();
+                            for (key, value) in obj.as_object().unwrap_or(&serde_json::Map::new()) {
+                                if key != "name" {
+                                    args.insert(key.clone(), value.clone());
+                                }
+                            }
+                            let call = ToolCall {
+                                name: name.to_string(),
+                                arguments: serde_json::Value::Object(args),
+                            };
+                            // Avoid duplicates
+                            if !calls.iter().any(|c| c.name == call.name && c.arguments == call.arguments) {
+                                calls.push(call);
+                            }
+                        }
+                    }
+                }
+                start_idx = None;
+            }
+        }
+    }
+    
+    calls
+}
+
+/// Parse XML-style tool calls produced by some models (e.g. GLM); the
+/// first of them, see `tool_xml::parse` for all
+pub fn parse_xml_tool_call(response: &str) -> Option<ToolCall> {
+    tool_xml::parse(response).into_iter().next()
+}
+
+/// Parse single tool call (for backwards compatibility)
+pub fn parse_tool_call(response: &str) -> Option<ToolCall> {
+    parse_all_tool_calls(response).first().cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_calls() {
+        let response = "Let me check.\n```tool\n{\"name\": \"web_search\", \"arguments\": {\"query\": \"rust\"}}\n```\nAlso {\"name\": \"get_current_time\", \"timezone\": \"UTC\"}";
+        let calls = parse_all_tool_calls(response);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].name, "web_search");
+        assert_eq!(calls[0].arguments["query"], "rust");
+        assert_eq!(calls[1].name, "get_current_time");
+        assert_eq!(calls[1].arguments, serde_json::json!({"timezone": "UTC"}));
+
+        assert!(parse_all_tool_calls("Just an answer.").is_empty());
+
+        let glm = "{\"arguments\":{\"query\":\"wasm\"}}<arg_key>name</arg_key><arg_value>web_search</tool_call>";
+        let calls = parse_all_tool_calls(glm);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "web_search");
+        assert_eq!(calls[0].arguments["query"], "wasm");
+
+        // JSON inside an XML argument is not read as a call of its own
+        let glm = "<tool_call>save_note\n<arg_key>title</arg_key>\n<arg_value>cfg</arg_value>\n<arg_key>content</arg_key>\n<arg_value>{\"name\": \"app\", \"port\": 80}</arg_value>\n</tool_call>";
+        let calls = parse_all_tool_calls(glm);
+        assert_eq!(calls.len(), 1);
+        assert_eq!((calls[0].name.as_str(), &calls[0].arguments["content"]), ("save_note", &serde_json::json!("{\"name\": \"app\", \"port\": 80}")));
+    }
+
+    #[test]
+    fn test_best_effort_answer() {
+        let reply = "Here is the summary so far.\n```tool\n{\"name\": \"web_search\", \"arguments\": {\"query\": \"x\"}}\n```\n{\"name\": \"fetch_url\", \"arguments\": {}} Config is {\"a\": 1}.";
+        assert_eq!(strip_tool_calls(reply), "Here is the summary so far.\n\n Config is {\"a\": 1}.");
+        assert_eq!(strip_tool_calls("Checking.\n<tool_call>web_search\n<arg_key>query</arg_key>\n<arg_value>x</arg_value>\n</tool_call>"), "Checking.");
+        assert_eq!(best_effort("{\"name\": \"calculate\", \"arguments\": {\"expression\": \"6*7\"}}", &[]),
+            "I couldn't finish this: I kept calling tools without getting anywhere. Try rephrasing or narrowing the request.");
+
+        let results = vec![("calculate".to_string(), "Result: 42".to_string()), ("fetch_url".to_string(), "y".repeat(700))];
+        let answer = best_effort("```tool\n{\"name\": \"calculate\", \"arguments\": {}}\n```", &results);
+        assert!(answer.starts_with("I kept calling tools without reaching an answer"));
+        assert!(answer.contains("**calculate**:\nResult: 42") && answer.ends_with(&format!("{}…", "y".repeat(BEST_EFFORT_CHARS))));
+    }
+
+    #[test]
+    fn test_tool_result_parts() {
+        assert_eq!(tool_result_parts("calculate", "4"), vec!["Tool 'calculate' returned:\n4"]);
+
+        let parts = tool_result_parts("fetch_url", &"x".repeat(RESULT_BATCH_CHARS * 2 + 1));
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].starts_with("Tool 'fetch_url' (split into 3 parts):\n[Part 1/3]"));
+        assert_eq!(parts[2], "[Part 3/3]\nx");
+    }
+
+    #[test]
+    fn test_trim_context_keeps_system_and_recent() {
+        let mut messages = vec![Message::system("You are helpful")];
+        for i in 0..25 {
+            messages.push(Message::user(&format!("message {}", i)));
+        }
+        let trimmed = trim_context(&messages).unwrap();
+        assert_eq!(trimmed[0].role, Role::System);
+        assert_eq!(trimmed.last().unwrap().content, "message 24");
+        assert_eq!(messages.len(), 26, "the history is left alone");
+
+        let short = vec![Message::system("s"), Message::user("hi")];
+        assert!(trim_context(&short).is_none());
+
+        let mut large = vec![Message::system("s")];
+        large.extend((0..3).map(|_| Message::user(&"y".repeat(40_000))));
+        let mut large = trim_context(&large).unwrap();
+        assert_eq!(large.len(), 3);
+
+        // Later system messages move up front; the rest keep their order
+        large.push(Message::system("correction"));
+        large.push(Message::assistant(&"z".repeat(30_000)));
+        let large = trim_context(&large).unwrap();
+        let kept: Vec<(Role, usize)> = large.iter().map(|m| (m.role.clone(), m.content.len())).collect();
+        assert_eq!(kept, [(Role::System, 1), (Role::System, 10), (Role::User, 40_000), (Role::Assistant, 30_000)]);
+    }
+}
@@ -0,0 +1,356 @@
+//! Audit log for claWasm
+//!
+//! Persistent, size-capped record of tool calls, provider requests,
+//! security decisions, and approvals so users can review what the
+//! assistant did. Entries are stored in localStorage (sealed when
+//! encryption at rest is enabled) in pages of `PAGE_ENTRIES`, listed by an
+//! index under `AUDIT_LOG_KEY`, so an event rewrites one page rather than
+//! the whole log, and the oldest page goes once the log is full. A log
+//! kept whole under `AUDIT_LOG_KEY`, as it was before paging, is split on
+//! first use. A page or index that no longer parses is moved aside to
+//! "<key>_corrupt_<ms>" and reported, never silently overwritten, and a
+//! lost index is rebuilt from the pages still stored. Recording never
+//! fails the caller.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::logger;
+use crate::platform::storage;
+use crate::vault;
+
+/// localStorage key of the audit log's index; page n is "clawasm_audit_<n>"
+pub const AUDIT_LOG_KEY: &str = "clawasm_audit";
+/// Maximum number of entries kept; the oldest page of them goes first
+const MAX_AUDIT_ENTRIES: usize = 1000;
+/// Entries per stored page
+const PAGE_ENTRIES: usize = 100;
+const MAX_PAGES: u64 = (MAX_AUDIT_ENTRIES / PAGE_ENTRIES) as u64;
+/// Maximum length of the recorded details text
+const MAX_DETAIL_CHARS: usize = 500;
+
+/// Kind of audited event
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    ToolCall,
+    ProviderRequest,
+    SecurityDecision,
+    Approval,
+    ScheduledScan,
+}
+
+/// Audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix timestamp in milliseconds
+    pub timestamp: i64,
+    pub kind: AuditKind,
+    /// What was acted on (tool name, provider, domain, action ID)
+    pub subject: String,
+    /// Result, e.g. "ok", "error", "allow", "deny", "approved", "drift"
+    pub outcome: String,
+    /// Arguments or error message, truncated
+    #[serde(default)]
+    pub details: String,
+}
+
+/// Filter for `getAuditLog`; all fields optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub kind: Option<AuditKind>,
+    /// Only entries at or after this timestamp (ms)
+    pub since: Option<i64>,
+    /// Only entries at or before this timestamp (ms)
+    pub until: Option<i64>,
+    pub outcome: Option<String>,
+    /// Case-insensitive text match on subject and details
+    pub search: Option<String>,
+    /// Return at most this many of the newest matches
+    pub limit: Option<usize>,
+}
+
+/// Numbers of the first and last pages of the log
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Pages {
+    first: u64,
+    last: u64,
+}
+
+/// What `AUDIT_LOG_KEY` holds
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Index {
+    Pages(Pages),
+    /// The whole log, as stored before paging
+    Unpaged(Vec<AuditEntry>),
+}
+
+/// Record an event (errors are logged, never returned)
+pub fn record(kind: AuditKind, subject: &str, outcome: &str, details: &str) {
+    let entry = AuditEntry {
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        kind,
+        subject: subject.to_string(),
+        outcome: outcome.to_string(),
+        details: truncate(details, MAX_DETAIL_CHARS),
+    };
+
+    let result = pages().and_then(|stored| {
+        let current = stored.unwrap_or(Pages { first: 0, last: 0 });
+        let page = read(&page_key(current.last))?.unwrap_or_default();
+        let (pages, page) = append(current, page, entry);
+        write(&page_key(pages.last), &page)?;
+        for dropped in current.first..pages.first {
+            storage::remove(&page_key(dropped))?;
+        }
+        if stored != Some(pages) {
+            write(AUDIT_LOG_KEY, &pages)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        logger::error("audit", &format!("Audit log write failed: {}", e));
+    }
+}
+
+/// Read entries matching a filter, oldest first
+pub fn query(filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+    Ok(filter_entries(load()?, filter))
+}
+
+/// Export the full log as JSON
+pub fn export_json() -> Result<String> {
+    serde_json::to_string(&serde_json::json!({
+        "exported_at": chrono::Utc::now().timestamp_millis(),
+        "entries": load()?,
+    }))
+    .map_err(|e| Error::new(format!("Serialize error: {}", e)))
+}
+
+/// Storage keys holding the log, for the vault to seal: the index, every
+/// page, listed or not, and whatever was moved aside
+pub fn storage_keys() -> Vec<String> {
+    let page_prefix = format!("{}_", AUDIT_LOG_KEY);
+    let mut keys = vec![AUDIT_LOG_KEY.to_string()];
+    match storage::keys() {
+        Ok(stored) => keys.extend(stored.into_iter().filter(|key| key.starts_with(&page_prefix))),
+        Err(e) => logger::error("audit", &format!("Can't list the audit log's pages: {}", e)),
+    }
+    keys
+}
+
+fn load() -> Result<Vec<AuditEntry>> {
+    let Some(pages) = pages()? else { return Ok(Vec::new()) };
+    let mut entries = Vec::new();
+    for page in pages.first..=pages.last {
+        entries.extend(read::<Vec<AuditEntry>>(&page_key(page))?.unwrap_or_default());
+    }
+    Ok(entries)
+}
+
+fn page_key(page: u64) -> String {
+    format!("{}_{}", AUDIT_LOG_KEY, page)
+}
+
+/// The log's pages, splitting a log stored whole and rebuilding a lost
+/// index; None before the first event
+fn pages() -> Result<Option<Pages>> {
+    match read::<Index>(AUDIT_LOG_KEY)? {
+        Some(Index::Pages(pages)) => Ok(Some(pages)),
+        Some(Index::Unpaged(entries)) => {
+            let skip = entries.len().saturating_sub(MAX_AUDIT_ENTRIES);
+            let chunks: Vec<&[AuditEntry]> = entries[skip..].chunks(PAGE_ENTRIES).collect();
+            for (page, chunk) in chunks.iter().enumerate() {
+                write(&page_key(page as u64), chunk)?;
+            }
+            let pages = Pages { first: 0, last: chunks.len().saturating_sub(1) as u64 };
+            write(AUDIT_LOG_KEY, &pages)?;
+            Ok(Some(pages))
+        }
+        None => rebuild_index(),
+    }
+}
+
+/// An index for the pages stored without one, after moving them into place
+fn rebuild_index() -> Result<Option<Pages>> {
+    let found: Vec<u64> = storage::keys()?.iter()
+        .filter_map(|key| key.strip_prefix(AUDIT_LOG_KEY)?.strip_prefix('_'))
+        .filter(|page| page.bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|page| page.parse().ok())
+        .collect();
+    let (Some(pages), moves) = repack(found) else { return Ok(None) };
+    for (page, to) in &moves {
+        match to {
+            Some(to) if to == page => {}
+            Some(to) => {
+                if let Some(stored) = storage::get(&page_key(*page))? {
+                    storage::set(&page_key(*to), &stored)?;
+                }
+                storage::remove(&page_key(*page))?;
+            }
+            None => storage::remove(&page_key(*page))?,
+        }
+    }
+    write(AUDIT_LOG_KEY, &pages)?;
+    logger::warn("audit", &format!("Rebuilt the audit log's index from the {} pages found", moves.len()));
+    Ok(Some(pages))
+}
+
+/// Where each of the `found` pages goes in a rebuilt index, newest first,
+/// the order to move them in: the newest `MAX_PAGES` packed together,
+/// ending at the highest, and the rest dropped (None)
+fn repack(mut found: Vec<u64>) -> (Option<Pages>, Vec<(u64, Option<u64>)>) {
+    found.sort_unstable_by(|a, b| b.cmp(a));
+    found.dedup();
+    let Some(&last) = found.first() else { return (None, Vec::new()) };
+    let kept = found.len().min(MAX_PAGES as usize) as u64;
+    // Each page moves up, if at all, so none lands on one not yet moved
+    let moves = found.iter().enumerate()
+        .map(|(i, page)| (*page, ((i as u64) < kept).then(|| last - i as u64)))
+        .collect();
+    (Some(Pages { first: last + 1 - kept, last }), moves)
+}
+
+/// The value under `key`, None when there is none or it was set aside as
+/// corrupt; a vault that can't open it is an error, and nothing is moved
+fn read<T: DeserializeOwned>(key: &str) -> Result<Option<T>> {
+    let Some(stored) = storage::get(key)? else { return Ok(None) };
+    match serde_json::from_str(&vault::open(&stored)?) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            let backup = format!("{}_corrupt_{}", key, chrono::Utc::now().timestamp_millis());
+            storage::set(&backup, &stored)?;
+            storage::remove(key)?;
+            logger::error("audit", &format!("{} was unreadable ({}); it is kept as {}", key, e, backup));
+            Ok(None)
+        }
+    }
+}
+
+fn write<T: Serialize + ?Sized>(key: &str, value: &T) -> Result<()> {
+    let json = serde_json::to_string(value)
+        .map_err(|e| Error::new(format!("Serialize error: {}", e)))?;
+    storage::set(key, &vault::seal(&json)?)
+}
+
+/// Add `entry` to `page`, the last of `pages`, starting a new page when it
+/// is full and dropping the first once there are more than `MAX_PAGES`
+fn append(mut pages: Pages, mut page: Vec<AuditEntry>, entry: AuditEntry) -> (Pages, Vec<AuditEntry>) {
+    if page.len() >= PAGE_ENTRIES {
+        pages.last += 1;
+        page.clear();
+    }
+    page.push(entry);
+    pages.first = pages.first.max((pages.last + 1).saturating_sub(MAX_PAGES));
+    (pages, page)
+}
+
+fn filter_entries(entries: Vec<AuditEntry>, filter: &AuditFilter) -> Vec<AuditEntry> {
+    let search = filter.search.as_ref().map(|s| s.to_lowercase());
+    let mut matched: Vec<AuditEntry> = entries.into_iter()
+        .filter(|e| filter.kind.is_none_or(|k| e.kind == k))
+        .filter(|e| filter.since.is_none_or(|t| e.timestamp >= t))
+        .filter(|e| filter.until.is_none_or(|t| e.timestamp <= t))
+        .filter(|e| filter.outcome.as_ref().is_none_or(|o| &e.outcome == o))
+        .filter(|e| search.as_ref().is_none_or(|s| {
+            e.subject.to_lowercase().contains(s) || e.details.to_lowercase().contains(s)
+        }))
+        .collect();
+
+    if let Some(limit) = filter.limit {
+        let skip = matched.len().saturating_sub(limit);
+        matched.drain(..skip);
+    }
+    matched
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        format!("{}...", text.chars().take(max_chars).collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64, kind: AuditKind, subject: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            kind,
+            subject: subject.to_string(),
+            outcome: "ok".to_string(),
+            details: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_append_fills_pages_and_drops_the_oldest() {
+        let (mut pages, mut page) = (Pages { first: 7, last: 7 }, Vec::new());
+        for i in 0..PAGE_ENTRIES as i64 {
+            (pages, page) = append(pages, page, entry(i, AuditKind::ToolCall, "calculate"));
+        }
+        assert_eq!((pages, page.len()), (Pages { first: 7, last: 7 }, PAGE_ENTRIES));
+        (pages, page) = append(pages, page, entry(100, AuditKind::ToolCall, "calculate"));
+        assert_eq!(pages, Pages { first: 7, last: 8 });
+        assert_eq!(page.iter().map(|e| e.timestamp).collect::<Vec<_>>(), vec![100], "only the new page is written");
+
+        let full = Pages { first: 7, last: 7 + MAX_PAGES - 1 };
+        let (pages, _) = append(full, vec![entry(0, AuditKind::ToolCall, "x"); PAGE_ENTRIES], entry(1, AuditKind::ToolCall, "x"));
+        assert_eq!(pages, Pages { first: 8, last: 7 + MAX_PAGES });
+    }
+
+    #[test]
+    fn test_repack_after_the_index_is_lost() {
+        assert_eq!(repack(Vec::new()), (None, Vec::new()));
+        assert_eq!(repack(vec![4, 3, 5]).0, Some(Pages { first: 3, last: 5 }));
+        assert!(repack(vec![4, 3, 5]).1.iter().all(|(page, to)| Some(*page) == *to), "a whole run stays where it is");
+
+        // A run the old index listed, and one started after it was lost
+        let (pages, moves) = repack(vec![0, 1, 2, 1_700_000_000_000, 1_700_000_000_001]);
+        assert_eq!(pages, Some(Pages { first: 1_699_999_999_997, last: 1_700_000_000_001 }));
+        assert_eq!(moves, vec![
+            (1_700_000_000_001, Some(1_700_000_000_001)),
+            (1_700_000_000_000, Some(1_700_000_000_000)),
+            (2, Some(1_699_999_999_999)),
+            (1, Some(1_699_999_999_998)),
+            (0, Some(1_699_999_999_997)),
+        ]);
+
+        let (pages, moves) = repack((0..MAX_PAGES + 2).collect());
+        assert_eq!(pages, Some(Pages { first: 2, last: MAX_PAGES + 1 }));
+        assert_eq!(moves[moves.len() - 2..], [(1, None), (0, None)], "the oldest pages past the cap go");
+    }
+
+    #[test]
+    fn test_index_formats() {
+        let paged: Index = serde_json::from_str(r#"{"first": 3, "last": 5}"#).unwrap();
+        assert!(matches!(paged, Index::Pages(Pages { first: 3, last: 5 })));
+        let whole = serde_json::to_string(&vec![entry(1, AuditKind::Approval, "action_1")]).unwrap();
+        assert!(matches!(serde_json::from_str(&whole).unwrap(), Index::Unpaged(entries) if entries.len() == 1));
+        assert!(serde_json::from_str::<Index>(r#"{"first": "#).is_err());
+    }
+
+    #[test]
+    fn test_filter_entries() {
+        let entries = vec![
+            entry(1, AuditKind::ToolCall, "web_search"),
+            entry(2, AuditKind::ProviderRequest, "openai"),
+            entry(3, AuditKind::ToolCall, "fetch_url"),
+            entry(4, AuditKind::ToolCall, "web_search"),
+        ];
+        let filter: AuditFilter = serde_json::from_str(r#"{"kind": "tool_call", "since": 2, "search": "WEB"}"#).unwrap();
+        let matched = filter_entries(entries.clone(), &filter);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].timestamp, 4);
+
+        let filter = AuditFilter { limit: Some(2), ..Default::default() };
+        let matched = filter_entries(entries, &filter);
+        assert_eq!(matched.iter().map(|e| e.timestamp).collect::<Vec<_>>(), vec![3, 4]);
+    }
+}
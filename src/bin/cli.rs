@@ -0,0 +1,332 @@
+//! claWasm terminal client
+//!
+//! Runs the same agent loop as the browser build, with tools executed
+//! natively over reqwest and state kept in `~/.clawasm`. Build with
+//!
+//! ```text
+//! cargo run --bin cli --no-default-features --features cli -- --provider openai
+//! ```
+//!
+//! Without a prompt it opens an interactive session; with `-p` (or when
+//! stdin is not a terminal) it answers once and exits, for use in scripts.
+
+#[cfg(feature = "web")]
+compile_error!("the CLI is a native build: use `--no-default-features --features cli`");
+
+use std::cell::RefCell;
+use std::io::{IsTerminal, Read};
+use std::rc::Rc;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use clawasm::agent::Agent;
+use clawasm::chat::Chat;
+use clawasm::config::{self, Config};
+use clawasm::logger::{self, Level};
+use clawasm::memory::{MemoryConfig, MemorySystem};
+use clawasm::platform::storage;
+use clawasm::providers::Provider;
+use clawasm::security::{SecurityConfig, SecurityManager};
+use clawasm::tool_grammar::{self, ToolFormat};
+use clawasm::tools::{registry, NativeTools};
+use clawasm::trace;
+
+const USAGE: &str = "\
+Usage: cli [OPTIONS]
+
+Options:
+  --provider <NAME>   openai, anthropic, ollama, openrouter, ... (default: openai)
+  --model <MODEL>     Model name (default: the provider's default)
+  --api-key <KEY>     API key (default: $CLAWASM_API_KEY)
+  --base-url <URL>    Custom API endpoint
+  --config <FILE>     Load a JSON config (same schema as withConfig)
+  --system <PROMPT>   System prompt
+  -p, --prompt <TEXT> Answer one prompt and exit
+  -v, --verbose       Print tool calls to stderr
+  --trace <FILE>      Record spans and write them as OTLP/JSON on exit
+  --log-level <LEVEL> error, warn, info or debug (default: info)
+  -h, --help          Show this help
+
+Reads the prompt from stdin when it is not a terminal.";
+
+const HELP: &str = "\
+/help              Show this help
+/tools             List the tools available natively
+/pending           List actions waiting for approval
+/approve <id>      Approve a pending action
+/deny <id>         Deny a pending action
+/reset             Start a new conversation
+/quit              Exit";
+
+const DEFAULT_PERSONA: &str = "You are claWasm, a helpful AI assistant running in a terminal. \
+    You are fast, private, and ready to help with any task.";
+
+#[derive(Default)]
+struct Options {
+    provider: Option<String>,
+    model: Option<String>,
+    api_key: Option<String>,
+    base_url: Option<String>,
+    config: Option<String>,
+    system: Option<String>,
+    prompt: Option<String>,
+    verbose: bool,
+    trace: Option<String>,
+    log_level: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--provider" => options.provider = Some(value(&arg)?),
+            "--model" => options.model = Some(value(&arg)?),
+            "--api-key" => options.api_key = Some(value(&arg)?),
+            "--base-url" => options.base_url = Some(value(&arg)?),
+            "--config" => options.config = Some(value(&arg)?),
+            "--system" => options.system = Some(value(&arg)?),
+            "-p" | "--prompt" => options.prompt = Some(value(&arg)?),
+            "-v" | "--verbose" => options.verbose = true,
+            "--trace" => options.trace = Some(value(&arg)?),
+            "--log-level" => options.log_level = Some(value(&arg)?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other => return Err(format!("Unknown option: {}\n\n{}", other, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+fn build_config(options: &Options) -> Result<Config, String> {
+    let mut config = match &options.config {
+        Some(path) => {
+            let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+            Config::from_json(&json).map_err(|errors| config::describe_errors(&errors))?
+        }
+        None => Config { system_prompt: DEFAULT_PERSONA.to_string(), ..Config::default() },
+    };
+    if let Some(prompt) = config::load_system_prompt() {
+        config.system_prompt = prompt;
+    }
+    if let Some(name) = &options.provider {
+        config.provider.switch_to(name);
+    }
+    if let Some(model) = &options.model {
+        config.provider.model = model.clone();
+    }
+    if let Some(url) = &options.base_url {
+        config.provider.base_url = Some(url.clone());
+    }
+    config.provider.api_key = options.api_key.clone()
+        .or_else(|| std::env::var("CLAWASM_API_KEY").ok())
+        .or(config.provider.api_key.take());
+    if let Some(system) = &options.system {
+        config.system_prompt = system.clone();
+    }
+    if let Some(level) = &options.log_level {
+        config.log_level = Level::parse(level).ok_or_else(|| format!("Unknown log level: {}", level))?;
+    }
+    Ok(config)
+}
+
+/// Persona plus the tools this build can run and how to call them
+fn system_prompt(persona: &str, format: ToolFormat) -> String {
+    format!(
+        "{}\n\n\
+        You have access to the following tools:{}\n\
+        {}\n\n\
+        After using a tool, you will receive its result and can continue helping the user.\n\
+        Only run scan_* tools against targets the user owns or is authorized to test.",
+        persona.trim(),
+        registry::with(|r| r.catalog()),
+        match format {
+            ToolFormat::Loose => "To use a tool, respond with a JSON object in this format:\n```tool\n{\"name\": \"tool_name\", \"arguments\": {...}}\n```",
+            ToolFormat::Strict => tool_grammar::INSTRUCTIONS,
+        }
+    )
+}
+
+struct Session {
+    agent: Agent,
+    chat: Chat,
+    verbose: bool,
+}
+
+impl Session {
+    fn new(config: Config, verbose: bool) -> Self {
+        let chat = Chat::with_system_prompt(&system_prompt(&config.system_prompt, config.tool_format));
+        let provider = Provider::from_name(&config.provider.active, config.provider.base_url.as_deref());
+        let agent = Agent {
+            config: Rc::new(config),
+            provider: Rc::new(provider),
+            memory: Rc::new(RefCell::new(MemorySystem::new(MemoryConfig::default()))),
+            security: Rc::new(RefCell::new(SecurityManager::new(SecurityConfig::load()))),
+            subject: Some("cli".to_string()),
+        };
+        Session { agent, chat, verbose }
+    }
+
+    async fn send(&mut self, message: &str) -> clawasm::error::Result<String> {
+        self.chat.add_user(message);
+        let turn = match self.agent.run_turn(&mut self.chat.messages, &NativeTools).await {
+            Ok(turn) => turn,
+            Err(e) => {
+                // Drop the unanswered message so a retry does not send it twice
+                self.chat.messages.pop();
+                return Err(e);
+            }
+        };
+        if self.verbose {
+            for thinking in &turn.reasoning {
+                eprintln!("[thinking] {}", thinking);
+            }
+            for call in &turn.tool_calls {
+                eprintln!("[tool] {} {}", call.name, call.arguments);
+            }
+        }
+        self.chat.add_assistant(&turn.response);
+        Ok(turn.response)
+    }
+
+    /// Handle a `/command`; returns false to quit
+    fn command(&mut self, line: &str) -> bool {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or_default();
+        let arg = parts.next();
+        let mut security = self.agent.security.borrow_mut();
+        match (command, arg) {
+            ("/quit" | "/exit", _) => return false,
+            ("/help", _) => println!("{}", HELP),
+            ("/tools", _) => println!("{}", registry::with(|r| r.names().join(", "))),
+            ("/reset", _) => {
+                self.chat.clear(&system_prompt(&self.agent.config.system_prompt, self.agent.config.tool_format));
+                println!("Conversation cleared.");
+            }
+            ("/pending", _) => {
+                let pending = security.pending_actions();
+                if pending.is_empty() {
+                    println!("No pending actions.");
+                }
+                for (id, action) in pending {
+                    println!("{}  {:?}", id, action);
+                }
+            }
+            ("/approve", Some(id)) => match security.approve_action(id) {
+                Ok(()) => println!("Approved {}. Ask again to run it.", id),
+                Err(e) => eprintln!("{}", e),
+            },
+            ("/deny", Some(id)) => match security.deny_action(id) {
+                Ok(()) => println!("Denied {}.", id),
+                Err(e) => eprintln!("{}", e),
+            },
+            ("/approve" | "/deny", None) => eprintln!("Usage: {} <id>", command),
+            _ => eprintln!("Unknown command: {} (try /help)", command),
+        }
+        true
+    }
+}
+
+async fn repl(session: &mut Session) -> rustyline::Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = storage::home().join("history.txt");
+    // No history yet on first run
+    let _ = editor.load_history(&history);
+    println!("claWasm {} ({}). Type /help for commands.", session.agent.config.provider.active, session.agent.config.provider.model);
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        if line.starts_with('/') {
+            if !session.command(line) {
+                break;
+            }
+            continue;
+        }
+        match session.send(line).await {
+            Ok(response) => println!("{}\n", response),
+            Err(e) => eprintln!("Error: {}\n", e),
+        }
+        session.agent.consolidate_if_due().await;
+    }
+
+    if let Some(dir) = history.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    editor.save_history(&history)
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let config = match build_config(&options) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    logger::set_level(config.log_level);
+    let mut session = Session::new(config, options.verbose);
+    if options.trace.is_some() {
+        trace::set_enabled(true);
+    }
+
+    let prompt = match options.prompt {
+        Some(prompt) => Some(prompt),
+        None if !std::io::stdin().is_terminal() => {
+            let mut input = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+                eprintln!("Cannot read stdin: {}", e);
+                std::process::exit(1);
+            }
+            Some(input)
+        }
+        None => None,
+    };
+
+    let status = match prompt {
+        Some(prompt) => match session.send(prompt.trim()).await {
+            Ok(response) => {
+                println!("{}", response);
+                session.agent.consolidate_if_due().await;
+                0
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        },
+        None => match repl(&mut session).await {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        },
+    };
+
+    if let Some(path) = &options.trace {
+        let json = trace::to_otlp(&trace::spans()).to_string();
+        if let Err(e) = std::fs::write(path, json) {
+            eprintln!("Cannot write trace to {}: {}", path, e);
+        }
+    }
+    std::process::exit(status);
+}
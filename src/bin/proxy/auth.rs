@@ -0,0 +1,84 @@
+//! Shared-secret access to the proxy
+//!
+//! With a token configured, every route answers 401 unless the
+//! request carries the same value in `X-Proxy-Token`. A custom header,
+//! because `Authorization` is forwarded to upstream APIs. CORS preflights
+//! are answered by the CORS middleware before this check runs. Files of the
+//! web UI (GETs that match no route) stay public: a browser loading the
+//! page cannot add the header, and they hold no secrets. Nor can a browser
+//! set headers on a WebSocket, so `/ws` also takes the token as `?token=`.
+//! Webhooks called by a chat service check that service's own secret.
+//! Shared conversations are read without it: whoever has the link has the
+//! key, and the proxy only holds their ciphertext.
+
+use std::collections::HashMap;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+
+pub const TOKEN_HEADER: &str = "X-Proxy-Token";
+/// Called by third parties that cannot send the token
+const WEBHOOKS: &[&str] = &["/channel/telegram/webhook", "/channel/discord/interactions", "/channel/slack/events", "/channel/slack/command", "/channel/whatsapp/webhook"];
+/// Opened by anyone with a share link
+const SHARED: &str = "/paste/{id}";
+
+/// The token clients must present, if any
+#[derive(Clone)]
+pub struct ProxyToken(pub Option<String>);
+
+impl ProxyToken {
+    pub fn accepts(&self, presented: Option<&str>) -> bool {
+        match (&self.0, presented) {
+            (None, _) => true,
+            (Some(expected), Some(presented)) => constant_time_eq(expected.as_bytes(), presented.as_bytes()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Compare without leaking the position of the first difference
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub async fn require_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let token = req.app_data::<actix_web::web::Data<ProxyToken>>().map(|t| t.get_ref().clone());
+    let query = (req.path() == "/ws")
+        .then(|| web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok())
+        .flatten();
+    let presented = req.headers().get(TOKEN_HEADER).and_then(|v| v.to_str().ok())
+        .or_else(|| query.as_ref().and_then(|q| q.get("token")).map(String::as_str));
+    let static_file = matches!(*req.method(), Method::GET | Method::HEAD) && req.match_pattern().is_none();
+    let webhook = WEBHOOKS.contains(&req.path());
+    let shared = *req.method() == Method::GET && req.match_pattern().as_deref() == Some(SHARED);
+    if !static_file && !webhook && !shared && token.is_some_and(|token| !token.accepts(presented)) {
+        eprintln!("⛔ Rejected {} {}: missing or wrong {}", req.method(), req.path(), TOKEN_HEADER);
+        let response = HttpResponse::Unauthorized()
+            .body(format!("Missing or invalid {} header", TOKEN_HEADER));
+        return Ok(req.into_response(response));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_check() {
+        let open = ProxyToken(None);
+        assert!(open.accepts(None) && open.accepts(Some("anything")));
+
+        let locked = ProxyToken(Some("s3cret".to_string()));
+        assert!(locked.accepts(Some("s3cret")));
+        assert!(!locked.accepts(Some("s3cres")));
+        assert!(!locked.accepts(Some("s3cret2")));
+        assert!(!locked.accepts(None));
+    }
+}
@@ -0,0 +1,505 @@
+//! Bluesky bot on an app password
+//!
+//! Bluesky has no stream of one account's notifications short of the whole
+//! firehose, so the proxy polls `listNotifications` every `poll_secs`
+//! instead and marks what it handled as seen. A mention of the bot, or a
+//! reply to one of its posts, from an allowed handle becomes a `mention`
+//! event on `/ws`:
+//!
+//! ```json
+//! {"channel": "bluesky", "type": "mention", "data": {"uri": "at://did:plc:.../app.bsky.feed.post/3k...",
+//!   "handle": "ann.bsky.social", "name": "Ann", "reason": "mention", "text": "...",
+//!   "history": [{"role": "user", "content": "..."}]}}
+//! ```
+//!
+//! The browser answers with POST /channel/bluesky/send `{"text",
+//! "reply_to"}`, posted as a thread of replies under that post, split at
+//! 300 characters with links made clickable. Without `reply_to` the same
+//! route publishes a new post.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Hub};
+use crate::telegram::{chunks, Turn};
+
+pub const MAX_POST_CHARS: usize = 300;
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Older notifications (a backlog after downtime) are not answered
+const MAX_AGE_SECS: i64 = 600;
+/// Posts remembered so their replies can be threaded
+const PENDING_POSTS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlueskyConfig {
+    /// The bot's handle or DID; the bot is off without `app_password`
+    pub identifier: Option<String>,
+    /// From Settings → App passwords, never the account password
+    pub app_password: Option<String>,
+    /// The account's PDS
+    pub service: String,
+    /// Handles the bot answers; empty answers anyone
+    pub allowed_handles: Vec<String>,
+    pub poll_secs: u64,
+    /// Messages per handle kept as context
+    pub history: usize,
+    /// Sent when no browser is connected to answer
+    pub offline_reply: Option<String>,
+}
+
+impl Default for BlueskyConfig {
+    fn default() -> Self {
+        BlueskyConfig {
+            identifier: None,
+            app_password: None,
+            service: "https://bsky.social".to_string(),
+            allowed_handles: Vec::new(),
+            poll_secs: 15,
+            history: 20,
+            offline_reply: Some("The assistant is offline right now. Please try again later.".to_string()),
+        }
+    }
+}
+
+impl BlueskyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.app_password.is_none() {
+            return Ok(());
+        }
+        if self.identifier.as_deref().is_none_or(|i| i.trim().is_empty()) {
+            return Err("bluesky needs identifier (the bot's handle) with app_password".to_string());
+        }
+        match url::Url::parse(&self.service) {
+            Ok(url) if url.scheme() == "https" && url.host_str().is_some() => {}
+            _ => return Err(format!("bluesky service must be an https:// URL, not {:?}", self.service)),
+        }
+        if self.poll_secs < 5 {
+            return Err("bluesky poll_secs must be at least 5".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn handle(handle: &str) -> String {
+    handle.trim().trim_start_matches('@').to_lowercase()
+}
+
+/// A post for the bot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    pub uri: String,
+    #[serde(skip)]
+    pub cid: String,
+    /// The thread's first post; the post itself when it starts one
+    #[serde(skip)]
+    pub root: Value,
+    pub handle: String,
+    pub name: String,
+    /// `mention` or `reply`
+    pub reason: String,
+    pub text: String,
+    #[serde(skip)]
+    pub indexed_at: String,
+}
+
+/// The post behind a notification, if it mentions or answers the bot
+pub fn incoming(notification: &Value) -> Option<Incoming> {
+    let reason = notification["reason"].as_str()?;
+    if !matches!(reason, "mention" | "reply") {
+        return None;
+    }
+    let author = &notification["author"];
+    let record = &notification["record"];
+    let uri = notification["uri"].as_str()?.to_string();
+    let cid = notification["cid"].as_str()?.to_string();
+    let root = match &record["reply"]["root"] {
+        root if root["uri"].is_string() => json!({ "uri": root["uri"], "cid": root["cid"] }),
+        _ => json!({ "uri": uri, "cid": cid }),
+    };
+    let handle = author["handle"].as_str()?.to_string();
+    // A mention's text starts with the bot's @handle
+    let text = record["text"].as_str()?.trim();
+    let text = match text.starts_with('@') {
+        true => text.split_once(char::is_whitespace).map_or("", |(_, rest)| rest).trim(),
+        false => text,
+    };
+    Some(Incoming {
+        uri,
+        cid,
+        root,
+        name: author["displayName"].as_str().filter(|n| !n.is_empty()).unwrap_or(&handle).to_string(),
+        handle,
+        reason: reason.to_string(),
+        text: text.to_string(),
+        indexed_at: notification["indexedAt"].as_str().unwrap_or("").to_string(),
+    })
+}
+
+/// Link facets for the URLs in `text`, which Bluesky shows as plain text
+/// otherwise; offsets are in UTF-8 bytes
+pub fn facets(text: &str) -> Vec<Value> {
+    let mut facets = Vec::new();
+    let mut offset = 0;
+    for word in text.split_inclusive(char::is_whitespace) {
+        let start = offset;
+        offset += word.len();
+        let link = word.trim_end().trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+        if link.starts_with("https://") || link.starts_with("http://") {
+            facets.push(json!({
+                "index": { "byteStart": start, "byteEnd": start + link.len() },
+                "features": [{ "$type": "app.bsky.richtext.facet#link", "uri": link }],
+            }));
+        }
+    }
+    facets
+}
+
+/// The web address of the post at `uri`
+pub fn web_url(uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix("at://")?;
+    let (did, rest) = rest.split_once('/')?;
+    let rkey = rest.strip_prefix("app.bsky.feed.post/")?;
+    Some(format!("https://bsky.app/profile/{}/post/{}", did, rkey))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub text: String,
+    /// The mention's `uri`; a new post without it
+    pub reply_to: Option<String>,
+}
+
+/// A post replies can hang under
+#[derive(Debug, Clone)]
+struct Pending {
+    uri: String,
+    cid: String,
+    root: Value,
+    /// Who wrote it
+    handle: String,
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    did: String,
+    access_jwt: String,
+}
+
+/// The bot's settings and session, the recent turns with every handle,
+/// and the posts waiting for an answer
+pub struct Bluesky {
+    pub config: BlueskyConfig,
+    session: Mutex<Option<Session>>,
+    chats: Mutex<HashMap<String, VecDeque<Turn>>>,
+    pending: Mutex<VecDeque<Pending>>,
+    /// Notifications handled, in case marking them seen failed
+    seen: Mutex<VecDeque<String>>,
+}
+
+impl Bluesky {
+    pub fn new(config: &BlueskyConfig) -> Self {
+        Bluesky {
+            config: config.clone(),
+            session: Mutex::new(None),
+            chats: Mutex::new(HashMap::new()),
+            pending: Mutex::new(VecDeque::new()),
+            seen: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.app_password.is_some()
+    }
+
+    /// The PDS's host name, for the per-host limits
+    pub fn host(&self) -> String {
+        url::Url::parse(&self.config.service).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default()
+    }
+
+    pub fn allows(&self, who: &str) -> bool {
+        self.config.allowed_handles.is_empty() || self.config.allowed_handles.iter().any(|allowed| handle(allowed) == handle(who))
+    }
+
+    /// Whether `reply_to` is a post the bot can answer
+    pub fn may_answer(&self, req: &SendRequest) -> bool {
+        req.reply_to.as_ref().is_none_or(|uri| self.pending.lock().unwrap().iter().any(|p| &p.uri == uri))
+    }
+
+    pub fn history(&self, who: &str) -> Vec<Turn> {
+        self.chats.lock().unwrap().get(&handle(who)).map(|turns| turns.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn remember(&self, who: &str, role: &'static str, content: &str) {
+        let mut chats = self.chats.lock().unwrap();
+        let turns = chats.entry(handle(who)).or_default();
+        turns.push_back(Turn { role, content: content.to_string() });
+        while turns.len() > self.config.history {
+            turns.pop_front();
+        }
+    }
+
+    /// The handle that wrote the post at `uri`
+    pub fn author(&self, uri: &str) -> Option<String> {
+        self.pending.lock().unwrap().iter().find(|p| p.uri == uri).map(|p| p.handle.clone())
+    }
+
+    fn forget(&self, who: &str) {
+        self.chats.lock().unwrap().remove(&handle(who));
+    }
+
+    async fn login(&self, client: &reqwest::Client) -> Result<Session, String> {
+        let body = json!({ "identifier": self.config.identifier, "password": self.config.app_password });
+        let url = format!("{}/xrpc/com.atproto.server.createSession", self.config.service.trim_end_matches('/'));
+        let response = client.post(url).json(&body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let reply: Value = response.json().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{} {}", status.as_u16(), reply["message"].as_str().unwrap_or("login refused")));
+        }
+        let session = Session {
+            did: reply["did"].as_str().unwrap_or("").to_string(),
+            access_jwt: reply["accessJwt"].as_str().unwrap_or("").to_string(),
+        };
+        *self.session.lock().unwrap() = Some(session.clone());
+        Ok(session)
+    }
+
+    /// Call an XRPC method: a query without `body`, a procedure with one.
+    /// An expired session is renewed once
+    async fn call(&self, client: &reqwest::Client, method: &str, query: &[(&str, &str)], body: Option<&Value>) -> Result<Value, String> {
+        let url = format!("{}/xrpc/{}", self.config.service.trim_end_matches('/'), method);
+        let mut renewed = false;
+        loop {
+            let cached = self.session.lock().unwrap().clone();
+            let session = match cached {
+                Some(session) => session,
+                None => self.login(client).await?,
+            };
+            let request = match body {
+                Some(body) => client.post(&url).json(body),
+                None => client.get(&url).query(query),
+            };
+            let response = request.bearer_auth(&session.access_jwt).send().await.map_err(|e| e.to_string())?;
+            let status = response.status();
+            let text = response.text().await.map_err(|e| e.to_string())?;
+            let reply: Value = serde_json::from_str(&text).unwrap_or_default();
+            if status.is_success() {
+                return Ok(reply);
+            }
+            if !renewed && (status.as_u16() == 401 || reply["error"] == "ExpiredToken") {
+                *self.session.lock().unwrap() = None;
+                renewed = true;
+                continue;
+            }
+            return Err(format!("{} {}", status.as_u16(), reply["message"].as_str().or(reply["error"].as_str()).unwrap_or("request refused")));
+        }
+    }
+
+    /// Post `req` as a thread; the answer is the number of posts and the
+    /// first one's web address
+    pub async fn send(&self, client: &reqwest::Client, req: &SendRequest) -> Result<(usize, Option<String>), String> {
+        if req.text.trim().is_empty() {
+            return Err("Missing 'text'".to_string());
+        }
+        let mut parent = match &req.reply_to {
+            Some(uri) => Some(
+                self.pending.lock().unwrap().iter().find(|p| &p.uri == uri).cloned()
+                    .ok_or_else(|| format!("No recent mention {}", uri))?,
+            ),
+            None => None,
+        };
+        let parts = chunks(&req.text, MAX_POST_CHARS);
+        let mut url = None;
+        for part in &parts {
+            let mut record = json!({
+                "$type": "app.bsky.feed.post",
+                "text": part,
+                "createdAt": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            });
+            let links = facets(part);
+            if !links.is_empty() {
+                record["facets"] = json!(links);
+            }
+            if let Some(parent) = &parent {
+                record["reply"] = json!({ "root": parent.root, "parent": { "uri": parent.uri, "cid": parent.cid } });
+            }
+            let did = self.session.lock().unwrap().as_ref().map(|s| s.did.clone());
+            let did = match did {
+                Some(did) => did,
+                None => self.login(client).await?.did,
+            };
+            let body = json!({ "repo": did, "collection": "app.bsky.feed.post", "record": record });
+            let created = self.call(client, "com.atproto.repo.createRecord", &[], Some(&body)).await?;
+            let (uri, cid) = (created["uri"].as_str().unwrap_or("").to_string(), created["cid"].as_str().unwrap_or("").to_string());
+            url = url.or_else(|| web_url(&uri));
+            // The next part answers this one, in the same thread
+            let root = parent.as_ref().map(|p| p.root.clone()).unwrap_or_else(|| json!({ "uri": uri, "cid": cid }));
+            let handle = self.config.identifier.clone().unwrap_or_default();
+            parent = Some(Pending { uri, cid, root, handle });
+        }
+        Ok((parts.len(), url))
+    }
+
+    async fn reply(&self, client: &reqwest::Client, metrics: &Metrics, post: &Incoming, text: &str) {
+        let req = SendRequest { text: text.to_string(), reply_to: Some(post.uri.clone()) };
+        if let Err(e) = metrics.upstream("bluesky:createRecord", self.send(client, &req)).await {
+            eprintln!("❌ Bluesky reply: {}", e);
+        }
+    }
+
+    /// Relay one post to the browsers on /ws, or answer it here
+    async fn handle(&self, post: Incoming, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        if !self.allows(&post.handle) {
+            eprintln!("⛔ Bluesky: ignored a {} by {} (not in allowed_handles)", post.reason, post.handle);
+            return;
+        }
+        {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push_back(Pending { uri: post.uri.clone(), cid: post.cid.clone(), root: post.root.clone(), handle: post.handle.clone() });
+            if pending.len() > PENDING_POSTS {
+                pending.pop_front();
+            }
+        }
+        if matches!(post.text.as_str(), "/new" | "/reset") {
+            self.forget(&post.handle);
+            self.reply(client, metrics, &post, "Started a new conversation.").await;
+            return;
+        }
+        if hub.listeners(Channel::Bluesky) == 0 {
+            eprintln!("→ Bluesky {} by {} with no browser connected", post.reason, post.handle);
+            if let Some(offline) = &self.config.offline_reply {
+                self.reply(client, metrics, &post, offline).await;
+            }
+            return;
+        }
+
+        eprintln!("→ Bluesky {} by {}", post.reason, post.handle);
+        let mut data = json!(post);
+        data["history"] = json!(self.history(&post.handle));
+        self.remember(&post.handle, "user", &post.text);
+        hub.relay(Channel::Bluesky, "mention", data);
+    }
+
+    /// Handle the unread mentions and replies, then mark them seen
+    async fn poll_once(&self, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) -> Result<(), String> {
+        let listed = self.call(client, "app.bsky.notification.listNotifications", &[("limit", "50")], None);
+        let listed = metrics.upstream("bluesky:listNotifications", listed).await?;
+        let now = chrono::Utc::now();
+        let mut newest: Option<String> = None;
+        let notifications = listed["notifications"].as_array().cloned().unwrap_or_default();
+        // Newest first as listed; answered oldest first
+        for notification in notifications.iter().rev().filter(|n| n["isRead"] == false) {
+            let Some(post) = incoming(notification) else {
+                continue;
+            };
+            newest = Some(post.indexed_at.clone()).max(newest);
+            {
+                let mut seen = self.seen.lock().unwrap();
+                if seen.contains(&post.uri) {
+                    continue;
+                }
+                seen.push_back(post.uri.clone());
+                if seen.len() > PENDING_POSTS {
+                    seen.pop_front();
+                }
+            }
+            let age = chrono::DateTime::parse_from_rfc3339(&post.indexed_at).map_or(0, |at| (now - at.with_timezone(&chrono::Utc)).num_seconds());
+            if age > MAX_AGE_SECS {
+                eprintln!("⏭️  Bluesky: skipped a {} by {} from {}s ago", post.reason, post.handle, age);
+                continue;
+            }
+            self.handle(post, hub, client, metrics).await;
+        }
+        if let Some(seen_at) = newest {
+            self.call(client, "app.bsky.notification.updateSeen", &[], Some(&json!({ "seenAt": seen_at }))).await?;
+        }
+        Ok(())
+    }
+
+    /// Poll for the life of the process
+    pub async fn run(self: Arc<Self>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>) {
+        match metrics.upstream("bluesky:createSession", self.login(&client)).await {
+            Ok(session) => println!("   Bluesky bot: {} ({})", self.config.identifier.as_deref().unwrap_or(""), session.did),
+            Err(e) if e.starts_with("401") => {
+                eprintln!("❌ Bluesky: the app password was refused: {}", e);
+                hub.set_status(Channel::Bluesky, false, Some(e));
+                return;
+            }
+            Err(e) => eprintln!("⚠️  Bluesky login: {}", e),
+        }
+        let interval = Duration::from_secs(self.config.poll_secs);
+        let mut backoff = interval;
+        loop {
+            match self.poll_once(&hub, &client, &metrics).await {
+                Ok(()) => {
+                    hub.set_status(Channel::Bluesky, true, None);
+                    backoff = interval;
+                }
+                Err(e) => {
+                    eprintln!("⚠️  Bluesky notifications: {}; retrying in {}s", e, backoff.as_secs());
+                    hub.set_status(Channel::Bluesky, false, Some(e));
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming() {
+        let notification = json!({"uri": "at://did:plc:ann/app.bsky.feed.post/3k2", "cid": "bafy2", "reason": "reply",
+            "isRead": false, "indexedAt": "2024-05-01T10:00:00.000Z",
+            "author": {"did": "did:plc:ann", "handle": "ann.bsky.social", "displayName": ""},
+            "record": {"text": "and the second one?", "reply": {"root": {"uri": "at://did:plc:bot/app.bsky.feed.post/3k1", "cid": "bafy1"},
+                "parent": {"uri": "at://did:plc:bot/app.bsky.feed.post/3k1", "cid": "bafy1"}}}});
+        let post = incoming(&notification).unwrap();
+        assert_eq!((post.name.as_str(), post.text.as_str()), ("ann.bsky.social", "and the second one?"));
+        assert_eq!(post.root["cid"], "bafy1");
+
+        let mention = json!({"uri": "at://did:plc:ann/app.bsky.feed.post/3k3", "cid": "bafy3", "reason": "mention",
+            "author": {"handle": "ann.bsky.social"}, "record": {"text": "@bot.bsky.social summarize this"}});
+        let post = incoming(&mention).unwrap();
+        assert_eq!((post.text.as_str(), post.root["uri"].as_str()), ("summarize this", Some("at://did:plc:ann/app.bsky.feed.post/3k3")));
+        assert!(incoming(&json!({"reason": "like", "uri": "at://x", "cid": "c"})).is_none());
+    }
+
+    #[test]
+    fn test_facets_and_urls() {
+        let facets = facets("Read é https://example.com/a. Then (http://x.org)");
+        assert_eq!(facets.len(), 1, "the parenthesized link starts with '('");
+        assert_eq!(facets[0]["index"], json!({"byteStart": 8, "byteEnd": 29}));
+        assert_eq!(facets[0]["features"][0]["uri"], "https://example.com/a");
+        assert_eq!(web_url("at://did:plc:ann/app.bsky.feed.post/3k2").as_deref(), Some("https://bsky.app/profile/did:plc:ann/post/3k2"));
+        assert_eq!(web_url("at://did:plc:ann/app.bsky.feed.like/3k2"), None);
+    }
+
+    #[test]
+    fn test_config() {
+        let config = BlueskyConfig {
+            identifier: Some("bot.bsky.social".to_string()),
+            app_password: Some("abcd-efgh-ijkl-mnop".to_string()),
+            allowed_handles: vec!["@Ann.bsky.social".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(BlueskyConfig { identifier: None, ..config.clone() }.validate().is_err());
+        assert!(BlueskyConfig { service: "http://pds.local".to_string(), ..config.clone() }.validate().is_err());
+        let bluesky = Bluesky::new(&config);
+        assert_eq!(bluesky.host(), "bsky.social");
+        assert!(bluesky.allows("ann.bsky.social") && !bluesky.allows("bob.bsky.social"));
+        assert!(bluesky.may_answer(&SendRequest::default()));
+        assert!(!bluesky.may_answer(&SendRequest { reply_to: Some("at://x".to_string()), ..Default::default() }));
+    }
+}
@@ -0,0 +1,261 @@
+//! Response cache for GET /proxy requests and /search
+//!
+//! Research loops fetch the same pages and queries over and over; serving
+//! repeats from memory cuts latency and keeps upstream rate limits at bay.
+//! Only successful responses are kept. Entries are keyed by URL plus the
+//! request headers (so differently authorized requests never share one)
+//! and expire after the TTL; past `max_entries` the oldest go. With `dir`
+//! set they survive restarts, one file each, and an entry's file goes with
+//! it whichever way it leaves. Clients skip the cache with
+//! `X-Proxy-Cache: bypass` or `Cache-Control: no-cache`; responses say
+//! `X-Proxy-Cache: HIT`, `MISS` or `BYPASS`. Configured in the `[cache]`
+//! section (see `config`).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::HttpRequest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::CacheConfig;
+
+pub const CACHE_HEADER: &str = "X-Proxy-Cache";
+
+/// A finished proxy response, as sent to the browser
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub status: u16,
+    pub content_type: String,
+    /// Encoded `X-Upstream-Headers` value, empty for routes without one
+    pub upstream_headers: String,
+    #[serde(with = "base64_body")]
+    pub body: Vec<u8>,
+}
+
+mod base64_body {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Unix seconds
+    stored_at: u64,
+    response: CachedResponse,
+}
+
+pub struct ResponseCache {
+    ttl_secs: u64,
+    max_entries: usize,
+    dir: Option<PathBuf>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Keys are SHA-256 in hex; no other file in `dir` is read or removed
+fn is_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl ResponseCache {
+    /// The cache, with the unexpired entries of `dir`
+    pub fn new(ttl_secs: u64, max_entries: usize, dir: Option<PathBuf>) -> Self {
+        let cache = ResponseCache { ttl_secs, max_entries, dir, entries: Mutex::new(HashMap::new()) };
+        let Some(files) = cache.dir.as_ref().filter(|_| cache.enabled()).and_then(|dir| std::fs::read_dir(dir).ok()) else { return cache };
+        let mut entries = cache.entries.lock().unwrap();
+        for file in files.flatten() {
+            let path = file.path();
+            let Some(key) = path.file_stem().and_then(|s| s.to_str()).filter(|key| is_key(key)).map(str::to_string) else { continue };
+            match cache.read_disk(&key) {
+                Some(entry) => {
+                    entries.insert(key, entry);
+                }
+                None => cache.remove_disk(&key),
+            }
+        }
+        cache.evict(&mut entries);
+        drop(entries);
+        cache
+    }
+
+    pub fn from_config(config: &CacheConfig) -> Self {
+        ResponseCache::new(config.ttl_secs, config.max_entries, config.dir.clone())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.ttl_secs > 0 && self.max_entries > 0
+    }
+
+    pub fn ttl_secs(&self) -> u64 {
+        self.ttl_secs
+    }
+
+    /// Cache key for a request: hash of its identifying parts
+    pub fn key(parts: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Whether the client asked to skip the cache
+    pub fn bypass_requested(req: &HttpRequest) -> bool {
+        let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or("").to_lowercase();
+        header(CACHE_HEADER) == "bypass" || header("cache-control").contains("no-cache")
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if !self.expired(entry) => Some(entry.response.clone()),
+            Some(_) => {
+                entries.remove(key);
+                self.remove_disk(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Keep a successful response
+    pub fn put(&self, key: &str, response: &CachedResponse) {
+        if !self.enabled() || !(200..300).contains(&response.status) {
+            return;
+        }
+        let entry = Entry { stored_at: now(), response: response.clone() };
+        self.write_disk(key, &entry);
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key.to_string(), entry);
+        self.evict(&mut entries);
+    }
+
+    fn expired(&self, entry: &Entry) -> bool {
+        now() >= entry.stored_at + self.ttl_secs
+    }
+
+    /// Drop expired entries, then the oldest past `max_entries`, files and all
+    fn evict(&self, entries: &mut HashMap<String, Entry>) {
+        let expired: Vec<String> = entries.iter().filter(|(_, e)| self.expired(e)).map(|(k, _)| k.clone()).collect();
+        for key in expired {
+            entries.remove(&key);
+            self.remove_disk(&key);
+        }
+        while entries.len() > self.max_entries {
+            let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.stored_at).map(|(k, _)| k.clone()) else { break };
+            entries.remove(&oldest);
+            self.remove_disk(&oldest);
+        }
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.json", key)))
+    }
+
+    fn read_disk(&self, key: &str) -> Option<Entry> {
+        let json = std::fs::read(self.path(key)?).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    fn write_disk(&self, key: &str, entry: &Entry) {
+        let Some(path) = self.path(key) else { return };
+        let written = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, serde_json::to_vec(entry).unwrap_or_default()));
+        if let Err(e) = written {
+            eprintln!("⚠️  Cache write failed for {}: {}", path.display(), e);
+        }
+    }
+
+    fn remove_disk(&self, key: &str) {
+        if let Some(path) = self.path(key) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, body: &str) -> CachedResponse {
+        CachedResponse { status, content_type: "text/plain".to_string(), upstream_headers: String::new(), body: body.as_bytes().to_vec() }
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = ResponseCache::new(60, 2, None);
+        let (a, b, c) = (ResponseCache::key(&["GET", "https://a.test"]), ResponseCache::key(&["GET", "https://b.test"]), ResponseCache::key(&["GET", "https://c.test"]));
+        assert_ne!(a, ResponseCache::key(&["GET", "https://a.test", "authorization: x"]));
+
+        cache.put(&a, &response(200, "a"));
+        cache.put(&b, &response(500, "error"));
+        assert_eq!(cache.get(&a).unwrap().body, b"a");
+        assert!(cache.get(&b).is_none(), "errors are not cached");
+
+        cache.entries.lock().unwrap().get_mut(&a).unwrap().stored_at -= 61;
+        assert!(cache.get(&a).is_none(), "expired");
+
+        cache.put(&a, &response(200, "a"));
+        cache.entries.lock().unwrap().get_mut(&a).unwrap().stored_at -= 10;
+        cache.put(&b, &response(200, "b"));
+        cache.put(&c, &response(200, "c"));
+        assert!(cache.get(&a).is_none(), "oldest evicted");
+        assert!(cache.get(&b).is_some() && cache.get(&c).is_some());
+
+        assert!(ResponseCache::new(0, 10, None).get(&a).is_none());
+    }
+
+    #[test]
+    fn test_disk_cache_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("clawasm-cache-test-{}", std::process::id()));
+        let key = ResponseCache::key(&["search", "rust"]);
+        ResponseCache::new(60, 10, Some(dir.clone())).put(&key, &response(200, "results"));
+
+        let restarted = ResponseCache::new(60, 10, Some(dir.clone()));
+        assert_eq!(restarted.get(&key).unwrap().body, b"results");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_evicted_entries_leave_the_disk() {
+        let dir = std::env::temp_dir().join(format!("clawasm-cache-evict-test-{}", std::process::id()));
+        let files = || std::fs::read_dir(&dir).unwrap().count();
+        let keys: Vec<String> = (0..4).map(|i| ResponseCache::key(&["GET", &format!("https://{}.test", i)])).collect();
+        let cache = ResponseCache::new(60, 2, Some(dir.clone()));
+        for (age, key) in keys.iter().enumerate() {
+            cache.put(key, &response(200, key));
+            cache.entries.lock().unwrap().get_mut(key).unwrap().stored_at -= 10 - age as u64;
+        }
+        assert_eq!(files(), 2, "capacity");
+        assert!(cache.get(&keys[0]).is_none() && cache.get(&keys[3]).is_some());
+
+        cache.entries.lock().unwrap().get_mut(&keys[2]).unwrap().stored_at -= 60;
+        cache.put(&keys[0], &response(200, "again"));
+        assert_eq!(files(), 2, "the expired entry went, unread");
+        assert!(cache.get(&keys[2]).is_none());
+
+        std::fs::write(dir.join("notes.txt"), "not the cache's").unwrap();
+        assert_eq!(ResponseCache::new(60, 1, Some(dir.clone())).entries.lock().unwrap().len(), 1);
+        assert_eq!(files(), 2, "capacity on restart, other files kept");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
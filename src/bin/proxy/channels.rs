@@ -0,0 +1,323 @@
+//! Named channel instances behind POST /channels/{id}/send and POST /notify
+//!
+//! Credentials for outgoing messages stay in the proxy config: each
+//! `[channels.<id>]` names a destination, a Telegram chat, a Slack or
+//! Discord channel, or any webhook, and the browser refers to it by id
+//! alone. GET /channels lists the ids, never their secrets.
+//!
+//! ```toml
+//! [channels.alerts]
+//! kind = "telegram"
+//! target = "-1001234567890"   # the token defaults to [telegram] bot_token
+//!
+//! [channels.ops]
+//! kind = "slack"
+//! url = "https://hooks.slack.com/services/..."   # or a target with a bot token
+//! ```
+//!
+//! `CLAWASM_PROXY_CHANNEL_<ID>_TOKEN` and `CLAWASM_PROXY_CHANNEL_<ID>_URL`
+//! (the id upper-cased, `-` as `_`) set the secrets of a configured id, so
+//! the file can leave them out.
+//!
+//! POST /notify broadcasts one notification, a title and message marked
+//! with its level, to several instances at once:
+//!
+//! ```json
+//! {"channels": ["alerts", "ops"], "title": "Scan of example.com", "message": "2 new issues", "level": "warning"}
+//! ```
+//!
+//! Each instance is tried on its own; the answer lists where it was `sent`
+//! and where it `failed`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::telegram::chunks;
+use crate::{discord, slack, telegram};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Telegram,
+    Slack,
+    Discord,
+    /// Any URL taking `{"text", "content"}`
+    Webhook,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    pub kind: Kind,
+    /// Bot token; Telegram, Slack and Discord fall back to their bot's
+    pub token: Option<String>,
+    /// Incoming webhook of Slack or Discord, or the `webhook` to post to
+    pub url: Option<String>,
+    /// Chat or channel ID the bot posts to
+    pub target: Option<String>,
+    /// Shown in GET /channels
+    pub description: Option<String>,
+}
+
+impl ChannelConfig {
+    pub fn validate(&self, id: &str) -> Result<(), String> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+            return Err(format!("channel id {:?}: use lowercase letters, digits, - and _", id));
+        }
+        let prefixes: &[&str] = match self.kind {
+            Kind::Slack => &["https://hooks.slack.com/"],
+            Kind::Discord => &["https://discord.com/api/webhooks/", "https://discordapp.com/api/webhooks/"],
+            Kind::Telegram | Kind::Webhook => &["https://", "http://"],
+        };
+        match (&self.url, &self.target) {
+            (Some(_), Some(_)) => Err(format!("channel {}: set either url or target", id)),
+            (Some(_), None) if self.kind == Kind::Telegram => Err(format!("channel {}: Telegram takes a target chat, not a url", id)),
+            (Some(url), None) if !prefixes.iter().any(|prefix| url.starts_with(prefix)) => {
+                Err(format!("channel {}: url must start with {}", id, prefixes[0]))
+            }
+            (Some(_), None) => Ok(()),
+            (None, _) if self.kind == Kind::Webhook => Err(format!("channel {} needs url", id)),
+            (None, None) => Err(format!("channel {} needs target (or url)", id)),
+            (None, Some(_)) if self.token.is_none() => Err(format!("channel {} needs a token: set token or the bot's", id)),
+            (None, Some(_)) => Ok(()),
+        }
+    }
+
+    /// The host messages go to, for the per-host rate limit
+    pub fn host(&self) -> String {
+        match (&self.url, self.kind) {
+            (Some(url), _) => crate::limits::host_of(url),
+            (None, Kind::Telegram) => telegram::HOST.to_string(),
+            (None, Kind::Slack) => slack::HOST.to_string(),
+            (None, Kind::Discord) => discord::HOST.to_string(),
+            (None, Kind::Webhook) => String::new(),
+        }
+    }
+
+    /// Post `text`, split as the destination needs; the answer is the
+    /// number of messages sent
+    pub async fn send(&self, client: &reqwest::Client, text: &str) -> Result<usize, String> {
+        let limit = match self.kind {
+            Kind::Telegram => telegram::MAX_MESSAGE_CHARS,
+            Kind::Slack => slack::MAX_MESSAGE_CHARS,
+            Kind::Discord => discord::MAX_MESSAGE_CHARS,
+            Kind::Webhook => usize::MAX,
+        };
+        let chunks = chunks(text, limit);
+        if chunks.is_empty() {
+            return Err("Missing 'text'".to_string());
+        }
+        let token = self.token.as_deref().unwrap_or("");
+        let target = self.target.as_deref().unwrap_or("");
+        for chunk in &chunks {
+            match (self.kind, &self.url) {
+                (Kind::Telegram, _) => {
+                    let url = format!("https://{}/bot{}/sendMessage", telegram::HOST, token);
+                    telegram::result(client.post(url).json(&json!({ "chat_id": target, "text": chunk }))).await?;
+                }
+                (Kind::Slack, None) => {
+                    let url = format!("https://{}/api/chat.postMessage", slack::HOST);
+                    let reply = checked(client.post(url).bearer_auth(token).json(&json!({ "channel": target, "text": chunk }))).await?;
+                    if reply["ok"] != true {
+                        return Err(reply["error"].as_str().unwrap_or("Slack refused the message").to_string());
+                    }
+                }
+                (Kind::Discord, None) => {
+                    let url = format!("https://{}/api/v10/channels/{}/messages", discord::HOST, target);
+                    let body = json!({ "content": chunk, "allowed_mentions": { "parse": [] } });
+                    checked(client.post(url).header("Authorization", format!("Bot {}", token.trim_start_matches("Bot "))).json(&body)).await?;
+                }
+                (Kind::Discord, Some(url)) => {
+                    let body = json!({ "content": chunk, "allowed_mentions": { "parse": [] } });
+                    checked(client.post(url).json(&body)).await?;
+                }
+                (_, Some(url)) => {
+                    // Slack reads "text", Discord and others "content"
+                    checked(client.post(url).json(&json!({ "text": chunk, "content": chunk }))).await?;
+                }
+                (Kind::Webhook, None) => return Err("The webhook has no url".to_string()),
+            }
+        }
+        Ok(chunks.len())
+    }
+}
+
+/// Send `request`, turning error statuses into their message; errors never
+/// carry the URL, which may hold a token
+async fn checked(request: reqwest::RequestBuilder) -> Result<Value, String> {
+    let response = request.send().await.map_err(|e| e.without_url().to_string())?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.without_url().to_string())?;
+    let reply: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+    match status.is_success() {
+        true => Ok(reply),
+        false => {
+            let message = reply["message"].as_str().or(reply["error"].as_str()).map(str::to_string)
+                .unwrap_or_else(|| text.chars().take(200).collect());
+            Err(format!("{} {}", status.as_u16(), message.trim()))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    fn icon(self) -> &'static str {
+        match self {
+            Level::Info => "ℹ️",
+            Level::Success => "✅",
+            Level::Warning => "⚠️",
+            Level::Critical => "🚨",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyRequest {
+    /// Channel instance ids; `["*"]` is every one
+    pub channels: Vec<String>,
+    pub title: Option<String>,
+    pub message: String,
+    #[serde(default)]
+    pub level: Level,
+}
+
+impl NotifyRequest {
+    /// The text every instance gets: the level's icon and the title over
+    /// the message
+    pub fn text(&self) -> String {
+        match self.title.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            Some(title) => format!("{} {}\n\n{}", self.level.icon(), title, self.message.trim()),
+            None => format!("{} {}", self.level.icon(), self.message.trim()),
+        }
+    }
+}
+
+/// The configured channel instances, by id
+pub struct Channels {
+    channels: BTreeMap<String, ChannelConfig>,
+}
+
+impl Channels {
+    pub fn new(channels: &BTreeMap<String, ChannelConfig>) -> Self {
+        Channels { channels: channels.clone() }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ChannelConfig> {
+        self.channels.get(id)
+    }
+
+    /// The ids `requested` names, `*` standing for all; unknown ids are
+    /// an error
+    pub fn select(&self, requested: &[String]) -> Result<Vec<String>, String> {
+        let mut ids: Vec<String> = Vec::new();
+        for id in requested.iter().map(|id| id.trim()) {
+            let more: Vec<String> = match id {
+                "*" => self.channels.keys().cloned().collect(),
+                id if self.channels.contains_key(id) => vec![id.to_string()],
+                id => return Err(format!("No channel '{}': add it as [channels.{}]", id, id)),
+            };
+            ids.extend(more.into_iter().filter(|id| !ids.contains(id)).collect::<Vec<_>>());
+        }
+        match ids.is_empty() {
+            true => Err("Missing 'channels'".to_string()),
+            false => Ok(ids),
+        }
+    }
+
+    /// Ids, kinds and descriptions, without secrets
+    pub fn list(&self) -> Vec<Value> {
+        self.channels.iter()
+            .map(|(id, channel)| json!({ "id": id, "kind": channel.kind, "description": channel.description }))
+            .collect()
+    }
+}
+
+/// Fill in the tokens left out with the bots' own
+pub fn resolve(channels: &mut BTreeMap<String, ChannelConfig>, telegram: Option<&str>, slack: Option<&str>, discord: Option<&str>) {
+    for channel in channels.values_mut() {
+        let bot = match channel.kind {
+            Kind::Telegram => telegram,
+            Kind::Slack => slack,
+            Kind::Discord => discord,
+            Kind::Webhook => None,
+        };
+        if channel.token.is_none() {
+            channel.token = bot.map(str::to_string);
+        }
+    }
+}
+
+/// The environment variable holding `field` of channel `id`
+pub fn env_name(id: &str, field: &str) -> String {
+    format!("CLAWASM_PROXY_CHANNEL_{}_{}", id.to_uppercase().replace('-', "_"), field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(kind: Kind, url: Option<&str>, target: Option<&str>) -> ChannelConfig {
+        ChannelConfig { kind, token: None, url: url.map(str::to_string), target: target.map(str::to_string), description: None }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut channels = BTreeMap::from([
+            ("alerts".to_string(), channel(Kind::Telegram, None, Some("-100123"))),
+            ("ops".to_string(), channel(Kind::Slack, Some("https://hooks.slack.com/services/T/B/x"), None)),
+            ("dev".to_string(), channel(Kind::Discord, None, Some("81"))),
+        ]);
+        assert!(channels["alerts"].validate("alerts").unwrap_err().contains("token"));
+        resolve(&mut channels, Some("1:AA"), None, Some("Bot d"));
+        assert!(channels.iter().all(|(id, channel)| channel.validate(id).is_ok()));
+        assert_eq!(channels["alerts"].token.as_deref(), Some("1:AA"));
+        assert_eq!((channels["ops"].host().as_str(), channels["dev"].host().as_str()), ("hooks.slack.com", "discord.com"));
+
+        assert!(channel(Kind::Slack, Some("https://evil.example/"), None).validate("x").unwrap_err().contains("hooks.slack.com"));
+        assert!(channel(Kind::Webhook, None, None).validate("x").unwrap_err().contains("url"));
+        assert!(channel(Kind::Webhook, Some("https://example.com/hook"), None).validate("x").is_ok());
+        assert!(channel(Kind::Discord, None, None).validate("x").is_err());
+        assert!(channel(Kind::Webhook, Some("https://example.com/hook"), None).validate("Ops!").is_err());
+        assert_eq!(env_name("ops-team", "URL"), "CLAWASM_PROXY_CHANNEL_OPS_TEAM_URL");
+
+        let list = Channels::new(&channels).list();
+        assert_eq!(list[0], json!({"id": "alerts", "kind": "telegram", "description": null}));
+    }
+
+    #[test]
+    fn test_notify() {
+        let channels = Channels::new(&BTreeMap::from([
+            ("alerts".to_string(), channel(Kind::Telegram, None, Some("-100123"))),
+            ("ops".to_string(), channel(Kind::Webhook, Some("https://example.com/hook"), None)),
+        ]));
+        let ids = |requested: &[&str]| channels.select(&requested.iter().map(|id| id.to_string()).collect::<Vec<_>>());
+        assert_eq!(ids(&["ops", "*"]).unwrap(), ["ops", "alerts"]);
+        assert!(ids(&["ops", "nope"]).unwrap_err().contains("[channels.nope]"));
+        assert!(ids(&[]).unwrap_err().contains("channels"));
+
+        let req: NotifyRequest = serde_json::from_value(json!({
+            "channels": ["ops"], "title": "Scan of example.com", "message": "2 new issues\n", "level": "critical"
+        })).unwrap();
+        assert_eq!(req.text(), "🚨 Scan of example.com\n\n2 new issues");
+        let plain = NotifyRequest { title: None, level: Level::default(), ..req };
+        assert_eq!(plain.text(), "ℹ️ 2 new issues");
+    }
+}
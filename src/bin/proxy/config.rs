@@ -0,0 +1,907 @@
+//! Proxy settings: TOML file, environment and command-line flags
+//!
+//! Settings are layered, each overriding the one before: built-in
+//! defaults, the TOML file named by `--config` (or `CLAWASM_PROXY_CONFIG`),
+//! `CLAWASM_PROXY_*` environment variables, then flags. A file looks like:
+//!
+//! ```toml
+//! bind = "0.0.0.0"
+//! port = 3000
+//! token = "change-me"
+//! access_log = true
+//!
+//! [tls]
+//! cert = "/etc/clawasm/cert.pem"
+//! key = "/etc/clawasm/key.pem"
+//!
+//! [targets]
+//! allow_hosts = ["api.openai.com", "ollama.com"]
+//! deny_hosts = []
+//! allow_private = false
+//! schemes = ["http", "https"]
+//! max_redirects = 10
+//!
+//! [cache]
+//! ttl_secs = 300
+//! max_entries = 500
+//! dir = "/var/cache/clawasm-proxy"
+//!
+//! [limits]
+//! client_rate = 120
+//! host_rate = 60
+//! max_concurrent = 16
+//!
+//! [search]
+//! backend = "brave"     # duckduckgo, brave, searxng, google or bing
+//! api_key = "..."
+//!
+//! [images]
+//! sources = ["openverse", "wikimedia"]   # and bing, with bing_key
+//!
+//! [render]             # GET /render, feature "render"
+//! chrome = "/usr/bin/chromium"
+//!
+//! [transcribe]
+//! backend = "whispercpp"   # or openai (the default)
+//! url = "http://127.0.0.1:8080"
+//!
+//! [tts]
+//! backend = "piper"        # openai (the default), elevenlabs or piper
+//! url = "http://127.0.0.1:5000"
+//!
+//! [email]              # POST /channel/email/send
+//! host = "smtp.example.com"
+//! security = "starttls"    # tls (port 465) or none
+//! username = "bot@example.com"
+//! password = "..."
+//! from = "claWasm <bot@example.com>"
+//! allowed_recipients = ["me@example.com", "@example.com"]
+//!
+//! [imap]               # relays unread mail; replies go out through [email]
+//! host = "imap.example.com"
+//! username = "bot@example.com"
+//! password = "..."
+//! allowed_senders = ["@example.com"]
+//!
+//! [realtime]           # GET /ws and GET /inbox
+//! discord_token = "..."
+//! slack_app_token = "xapp-..."
+//! inbox_secs = 300
+//!
+//! [sessions]           # history, /model and rate budgets of the chat channels
+//! rate = 10            # messages per user, channel and minute
+//! models = ["gpt-4o", "claude-sonnet-4-5"]
+//!
+//! [discord]            # answers mentions and DMs over the gateway
+//! allowed_channels = ["81384788765712384"]
+//! public_key = "..."   # POST /channel/discord/interactions
+//!
+//! [slack]              # POST /channel/slack/events, or Socket Mode
+//! bot_token = "xoxb-..."
+//! signing_secret = "..."
+//!
+//! [telegram]           # POST /channel/telegram/webhook
+//! bot_token = "123456:AA..."
+//! webhook_secret = "long-random-string"
+//! public_url = "https://proxy.example.com"
+//! allowed_users = [12345678, "@me"]
+//! # mode = "polling"   # no public address: fetch updates instead
+//! # offset_file = "telegram-offset"
+//! # chat_rate = 5      # instead of the [sessions] rate
+//!
+//! [whatsapp]           # GET/POST /channel/whatsapp/webhook
+//! access_token = "EAAG..."
+//! phone_number_id = "106540352242922"
+//! verify_token = "long-random-string"
+//! app_secret = "..."
+//! allowed_numbers = ["+15551234567"]
+//!
+//! [mastodon]           # answers mentions over the streaming API
+//! instance = "mastodon.social"
+//! access_token = "..."
+//!
+//! [bluesky]            # polls notifications for mentions and replies
+//! identifier = "bot.bsky.social"
+//! app_password = "xxxx-xxxx-xxxx-xxxx"
+//!
+//! [channels.alerts]    # POST /channels/alerts/send; tokens stay here
+//! kind = "telegram"
+//! target = "-1001234567890"
+//!
+//! [paste]              # POST /paste and GET /paste/{id}: encrypted shares
+//! enabled = true       # off by default
+//! ttl_hours = 168
+//! max_bytes = 1048576
+//! dir = "/var/lib/clawasm-proxy/pastes"
+//!
+//! [web]                # the built frontend, served at /
+//! dir = "web"
+//!
+//! [upstream]
+//! timeout_secs = 120
+//! connect_timeout_secs = 30
+//! retries = 2
+//! insecure_tls = false
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::bluesky::BlueskyConfig;
+use crate::channels::{self, ChannelConfig};
+use crate::discord::DiscordConfig;
+use crate::email::EmailConfig;
+use crate::images::{ImageSource, ImagesConfig};
+use crate::imap::ImapConfig;
+use crate::mastodon::MastodonConfig;
+use crate::paste::PasteConfig;
+use crate::realtime::RealtimeConfig;
+use crate::search::{Backend, SearchConfig};
+use crate::sessions::SessionsConfig;
+use crate::slack::SlackConfig;
+use crate::telegram::{self, TelegramConfig};
+use crate::transcribe::{self, TranscribeConfig};
+use crate::tts::{self, TtsConfig};
+use crate::ssrf::TargetPolicy;
+use crate::whatsapp::WhatsAppConfig;
+
+/// claWasm CORS proxy
+#[derive(Debug, Default, Parser)]
+#[command(name = "proxy", version)]
+pub struct Args {
+    /// TOML config file
+    #[arg(short, long, env = "CLAWASM_PROXY_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on (default 127.0.0.1)
+    #[arg(long, value_name = "ADDR")]
+    pub bind: Option<String>,
+    /// Port to listen on (default 3000)
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// PEM certificate chain for HTTPS
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for HTTPS
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// Only reach these hosts and their subdomains (repeatable, or comma-separated)
+    #[arg(long = "allow-host", value_name = "HOST", value_delimiter = ',')]
+    pub allow_hosts: Vec<String>,
+    /// Never reach these hosts (repeatable, or comma-separated)
+    #[arg(long = "deny-host", value_name = "HOST", value_delimiter = ',')]
+    pub deny_hosts: Vec<String>,
+    /// Allow loopback, LAN and link-local targets
+    #[arg(long)]
+    pub allow_private: bool,
+    /// Seconds to cache GET responses; 0 disables the cache
+    #[arg(long, value_name = "SECS")]
+    pub cache_ttl: Option<u64>,
+    /// Also keep cached responses in this directory
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+    /// Requests per minute per client; 0 disables the limit
+    #[arg(long, value_name = "N")]
+    pub client_rate: Option<u32>,
+    /// Upstream requests per minute per target host; 0 disables the limit
+    #[arg(long, value_name = "N")]
+    pub host_rate: Option<u32>,
+    /// Requests handled at once; 0 disables the cap
+    #[arg(long, value_name = "N")]
+    pub max_concurrent: Option<usize>,
+    /// Do not write a JSON access log line per request to stdout
+    #[arg(long)]
+    pub no_access_log: bool,
+    /// Overall timeout for upstream requests, in seconds (streams excepted)
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+    /// Retries for failed idempotent upstream requests
+    #[arg(long, value_name = "N")]
+    pub retries: Option<u32>,
+    /// Accept invalid and self-signed upstream certificates (unsafe)
+    #[arg(long)]
+    pub insecure_tls: bool,
+    /// Web search backend: duckduckgo, brave, searxng, google or bing
+    #[arg(long, value_name = "NAME", value_parser = |name: &str| Backend::parse(name).ok_or("unknown backend"))]
+    pub search_backend: Option<Backend>,
+    /// Chromium or Chrome binary for /render (found automatically otherwise)
+    #[arg(long, value_name = "FILE")]
+    pub chrome: Option<PathBuf>,
+    /// Fetch Telegram updates with getUpdates instead of the webhook
+    #[arg(long)]
+    pub telegram_polling: bool,
+    /// Serve the web UI from this directory (default web)
+    #[arg(long, value_name = "DIR")]
+    pub web_dir: Option<PathBuf>,
+    /// Serve only the API, not the web UI
+    #[arg(long, conflicts_with = "web_dir")]
+    pub no_web: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Seconds to keep entries; 0 disables the cache
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    /// Persist entries here as well, surviving restarts
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { ttl_secs: 300, max_entries: 500, dir: None }
+    }
+}
+
+/// Per-minute rates and the concurrency cap; 0 turns each off
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub client_rate: u32,
+    pub host_rate: u32,
+    pub max_concurrent: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig { client_rate: 120, host_rate: 60, max_concurrent: 16 }
+    }
+}
+
+/// How the proxy talks to upstream servers
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// Extra attempts for idempotent requests
+    pub retries: u32,
+    /// Skip certificate verification, e.g. to scan hosts with broken TLS
+    pub insecure_tls: bool,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        UpstreamConfig { timeout_secs: 120, connect_timeout_secs: 30, retries: 2, insecure_tls: false }
+    }
+}
+
+/// Headless Chromium behind /render
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderConfig {
+    /// Browser binary; searched for on the PATH and in the usual places when unset
+    pub chrome: Option<PathBuf>,
+    /// Time for a page to load, on top of the requested wait
+    pub timeout_secs: u64,
+    /// Tabs rendering at once
+    pub max_pages: usize,
+    pub width: u32,
+    pub height: u32,
+    /// Needed to run Chromium as root, e.g. in most containers
+    pub no_sandbox: bool,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig { chrome: None, timeout_secs: 30, max_pages: 2, width: 1280, height: 800, no_sandbox: false }
+    }
+}
+
+/// The web UI and wasm bundle served at /
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebConfig {
+    pub enabled: bool,
+    /// Holds index.html and the wasm-bindgen output in pkg/; relative
+    /// paths start at the working directory
+    pub dir: PathBuf,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig { enabled: true, dir: PathBuf::from("web") }
+    }
+}
+
+impl WebConfig {
+    /// The directory to serve, if enabled and built
+    pub fn site(&self) -> Option<&Path> {
+        (self.enabled && self.dir.join("index.html").is_file()).then_some(self.dir.as_path())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    pub bind: String,
+    pub port: u16,
+    /// Required `X-Proxy-Token` value
+    pub token: Option<String>,
+    /// One JSON line per request on stdout
+    pub access_log: bool,
+    pub tls: TlsConfig,
+    pub targets: TargetPolicy,
+    pub cache: CacheConfig,
+    pub limits: LimitsConfig,
+    pub upstream: UpstreamConfig,
+    pub search: SearchConfig,
+    pub images: ImagesConfig,
+    pub render: RenderConfig,
+    pub transcribe: TranscribeConfig,
+    pub tts: TtsConfig,
+    pub email: EmailConfig,
+    pub imap: ImapConfig,
+    pub realtime: RealtimeConfig,
+    pub sessions: SessionsConfig,
+    pub discord: DiscordConfig,
+    pub slack: SlackConfig,
+    pub telegram: TelegramConfig,
+    pub whatsapp: WhatsAppConfig,
+    pub mastodon: MastodonConfig,
+    pub bluesky: BlueskyConfig,
+    /// Named destinations for POST /channels/{id}/send
+    pub channels: BTreeMap<String, ChannelConfig>,
+    pub paste: PasteConfig,
+    pub web: WebConfig,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            bind: "127.0.0.1".to_string(),
+            port: 3000,
+            token: None,
+            access_log: true,
+            tls: TlsConfig::default(),
+            targets: TargetPolicy::default(),
+            cache: CacheConfig::default(),
+            limits: LimitsConfig::default(),
+            upstream: UpstreamConfig::default(),
+            search: SearchConfig::default(),
+            images: ImagesConfig::default(),
+            render: RenderConfig::default(),
+            transcribe: TranscribeConfig::default(),
+            tts: TtsConfig::default(),
+            email: EmailConfig::default(),
+            imap: ImapConfig::default(),
+            realtime: RealtimeConfig::default(),
+            sessions: SessionsConfig::default(),
+            discord: DiscordConfig::default(),
+            slack: SlackConfig::default(),
+            telegram: TelegramConfig::default(),
+            whatsapp: WhatsAppConfig::default(),
+            mastodon: MastodonConfig::default(),
+            bluesky: BlueskyConfig::default(),
+            channels: BTreeMap::new(),
+            paste: PasteConfig::default(),
+            web: WebConfig::default(),
+        }
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn env_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env(name).and_then(|v| v.trim().parse().ok())
+}
+
+fn env_list(name: &str) -> Option<Vec<String>> {
+    std::env::var(name).ok().map(|v| v.split(',').map(str::to_string).collect())
+}
+
+impl ProxyConfig {
+    /// Defaults, then the config file, environment and flags
+    pub fn load(args: &Args) -> Result<Self, String> {
+        let mut config = match &args.config {
+            Some(path) => ProxyConfig::from_file(path)?,
+            None => ProxyConfig::default(),
+        };
+        config.apply_env();
+        config.apply_args(args);
+        config.targets.normalize();
+        config.token = config.token.filter(|t| !t.trim().is_empty());
+        if config.tls.cert.is_some() != config.tls.key.is_some() {
+            return Err("TLS needs both a certificate and a key".to_string());
+        }
+        config.search.validate()?;
+        config.images.validate()?;
+        config.transcribe.validate()?;
+        config.tts.validate()?;
+        config.email.validate()?;
+        config.imap.validate()?;
+        if config.imap.host.is_some() && config.email.host.is_none() {
+            return Err("imap replies go out through [email]: set its host and from".to_string());
+        }
+        config.realtime.validate()?;
+        config.sessions.validate()?;
+        config.discord.validate()?;
+        config.slack.validate()?;
+        config.telegram.validate()?;
+        config.whatsapp.validate()?;
+        config.mastodon.validate()?;
+        config.bluesky.validate()?;
+        config.paste.validate()?;
+        let discord_token = config.realtime.discord_token.as_deref();
+        channels::resolve(&mut config.channels, config.telegram.bot_token.as_deref(), config.slack.bot_token.as_deref(), discord_token);
+        for (id, channel) in &config.channels {
+            channel.validate(id)?;
+        }
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        ProxyConfig::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// `CLAWASM_PROXY_*` variables
+    fn apply_env(&mut self) {
+        if let Some(bind) = env("CLAWASM_PROXY_BIND") {
+            self.bind = bind;
+        }
+        if let Some(port) = env_number("CLAWASM_PROXY_PORT") {
+            self.port = port;
+        }
+        if let Some(token) = env("CLAWASM_PROXY_TOKEN") {
+            self.token = Some(token);
+        }
+        if let Some(log) = env("CLAWASM_PROXY_ACCESS_LOG") {
+            self.access_log = !matches!(log.trim(), "0" | "false" | "no" | "off");
+        }
+        if let Some(cert) = env("CLAWASM_PROXY_TLS_CERT") {
+            self.tls.cert = Some(cert.into());
+        }
+        if let Some(key) = env("CLAWASM_PROXY_TLS_KEY") {
+            self.tls.key = Some(key.into());
+        }
+
+        let targets = &mut self.targets;
+        if let Some(hosts) = env_list("CLAWASM_PROXY_ALLOW_HOSTS") {
+            targets.allow_hosts = hosts;
+        }
+        if let Some(hosts) = env_list("CLAWASM_PROXY_DENY_HOSTS") {
+            targets.deny_hosts = hosts;
+        }
+        if let Some(schemes) = env_list("CLAWASM_PROXY_SCHEMES") {
+            targets.schemes = schemes;
+        }
+        if let Some(allow) = env("CLAWASM_PROXY_ALLOW_PRIVATE") {
+            targets.allow_private = matches!(allow.trim(), "1" | "true" | "yes");
+        }
+        if let Some(max) = env_number("CLAWASM_PROXY_MAX_REDIRECTS") {
+            targets.max_redirects = max;
+        }
+
+        if let Some(ttl) = env_number("CLAWASM_PROXY_CACHE_TTL") {
+            self.cache.ttl_secs = ttl;
+        }
+        if let Some(max) = env_number("CLAWASM_PROXY_CACHE_MAX") {
+            self.cache.max_entries = max;
+        }
+        if let Some(dir) = env("CLAWASM_PROXY_CACHE_DIR") {
+            self.cache.dir = Some(dir.into());
+        }
+
+        if let Some(rate) = env_number("CLAWASM_PROXY_CLIENT_RATE") {
+            self.limits.client_rate = rate;
+        }
+        if let Some(rate) = env_number("CLAWASM_PROXY_HOST_RATE") {
+            self.limits.host_rate = rate;
+        }
+        if let Some(max) = env_number("CLAWASM_PROXY_MAX_CONCURRENT") {
+            self.limits.max_concurrent = max;
+        }
+
+        if let Some(timeout) = env_number("CLAWASM_PROXY_TIMEOUT") {
+            self.upstream.timeout_secs = timeout;
+        }
+        if let Some(retries) = env_number("CLAWASM_PROXY_RETRIES") {
+            self.upstream.retries = retries;
+        }
+        if let Some(insecure) = env("CLAWASM_PROXY_INSECURE_TLS") {
+            self.upstream.insecure_tls = matches!(insecure.trim(), "1" | "true" | "yes");
+        }
+
+        if let Some(backend) = env("CLAWASM_PROXY_SEARCH_BACKEND").and_then(|b| Backend::parse(&b)) {
+            self.search.backend = backend;
+        }
+        if let Some(key) = env("CLAWASM_PROXY_SEARCH_API_KEY") {
+            self.search.api_key = Some(key);
+        }
+        if let Some(url) = env("CLAWASM_PROXY_SEARCH_URL") {
+            self.search.url = Some(url);
+        }
+        if let Some(cx) = env("CLAWASM_PROXY_SEARCH_CX") {
+            self.search.cx = Some(cx);
+        }
+        if let Some(sources) = env_list("CLAWASM_PROXY_IMAGE_SOURCES") {
+            self.images.sources = sources.iter().filter_map(|s| ImageSource::parse(s)).collect();
+        }
+        if let Some(key) = env("CLAWASM_PROXY_BING_IMAGES_KEY") {
+            self.images.bing_key = Some(key);
+        }
+        if let Some(backend) = env("CLAWASM_PROXY_TRANSCRIBE_BACKEND").and_then(|b| transcribe::Backend::parse(&b)) {
+            self.transcribe.backend = backend;
+        }
+        if let Some(key) = env("CLAWASM_PROXY_TRANSCRIBE_API_KEY") {
+            self.transcribe.api_key = Some(key);
+        }
+        if let Some(url) = env("CLAWASM_PROXY_TRANSCRIBE_URL") {
+            self.transcribe.url = Some(url);
+        }
+        if let Some(backend) = env("CLAWASM_PROXY_TTS_BACKEND").and_then(|b| tts::Backend::parse(&b)) {
+            self.tts.backend = backend;
+        }
+        if let Some(key) = env("CLAWASM_PROXY_TTS_API_KEY") {
+            self.tts.api_key = Some(key);
+        }
+        if let Some(url) = env("CLAWASM_PROXY_TTS_URL") {
+            self.tts.url = Some(url);
+        }
+        if let Some(voice) = env("CLAWASM_PROXY_TTS_VOICE") {
+            self.tts.voice = Some(voice);
+        }
+        if let Some(host) = env("CLAWASM_PROXY_SMTP_HOST") {
+            self.email.host = Some(host);
+        }
+        if let Some(port) = env_number("CLAWASM_PROXY_SMTP_PORT") {
+            self.email.port = Some(port);
+        }
+        if let Some(username) = env("CLAWASM_PROXY_SMTP_USERNAME") {
+            self.email.username = Some(username);
+        }
+        if let Some(password) = env("CLAWASM_PROXY_SMTP_PASSWORD") {
+            self.email.password = Some(password);
+        }
+        if let Some(from) = env("CLAWASM_PROXY_EMAIL_FROM") {
+            self.email.from = Some(from);
+        }
+        if let Some(recipients) = env_list("CLAWASM_PROXY_EMAIL_RECIPIENTS") {
+            self.email.allowed_recipients = recipients;
+        }
+        if let Some(host) = env("CLAWASM_PROXY_IMAP_HOST") {
+            self.imap.host = Some(host);
+        }
+        if let Some(username) = env("CLAWASM_PROXY_IMAP_USERNAME") {
+            self.imap.username = Some(username);
+        }
+        if let Some(password) = env("CLAWASM_PROXY_IMAP_PASSWORD") {
+            self.imap.password = Some(password);
+        }
+        if let Some(senders) = env_list("CLAWASM_PROXY_IMAP_SENDERS") {
+            self.imap.allowed_senders = senders;
+        }
+        if let Some(token) = env("CLAWASM_PROXY_DISCORD_TOKEN") {
+            self.realtime.discord_token = Some(token);
+        }
+        if let Some(intents) = env_number("CLAWASM_PROXY_DISCORD_INTENTS") {
+            self.realtime.discord_intents = intents;
+        }
+        if let Some(token) = env("CLAWASM_PROXY_SLACK_APP_TOKEN") {
+            self.realtime.slack_app_token = Some(token);
+        }
+        if let Some(rate) = env_number("CLAWASM_PROXY_SESSION_RATE") {
+            self.sessions.rate = rate;
+        }
+        if let Some(models) = env_list("CLAWASM_PROXY_MODELS") {
+            self.sessions.models = models.iter().map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect();
+        }
+        if let Some(channels) = env_list("CLAWASM_PROXY_DISCORD_CHANNELS") {
+            self.discord.allowed_channels = channels.iter().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+        }
+        if let Some(users) = env_list("CLAWASM_PROXY_DISCORD_USERS") {
+            self.discord.allowed_users = users.iter().map(|u| u.trim().to_string()).filter(|u| !u.is_empty()).collect();
+        }
+        if let Some(key) = env("CLAWASM_PROXY_DISCORD_PUBLIC_KEY") {
+            self.discord.public_key = Some(key);
+        }
+        if let Some(token) = env("CLAWASM_PROXY_SLACK_BOT_TOKEN") {
+            self.slack.bot_token = Some(token);
+        }
+        if let Some(secret) = env("CLAWASM_PROXY_SLACK_SIGNING_SECRET") {
+            self.slack.signing_secret = Some(secret);
+        }
+        if let Some(channels) = env_list("CLAWASM_PROXY_SLACK_CHANNELS") {
+            self.slack.allowed_channels = channels.iter().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+        }
+        if let Some(token) = env("CLAWASM_PROXY_TELEGRAM_TOKEN") {
+            self.telegram.bot_token = Some(token);
+        }
+        if let Some(secret) = env("CLAWASM_PROXY_TELEGRAM_SECRET") {
+            self.telegram.webhook_secret = Some(secret);
+        }
+        if let Some(mode) = env("CLAWASM_PROXY_TELEGRAM_MODE") {
+            match mode.trim() {
+                "polling" => self.telegram.mode = telegram::Mode::Polling,
+                "webhook" => self.telegram.mode = telegram::Mode::Webhook,
+                other => eprintln!("⚠️  Ignoring CLAWASM_PROXY_TELEGRAM_MODE={:?}: use polling or webhook", other),
+            }
+        }
+        if let Some(url) = env("CLAWASM_PROXY_PUBLIC_URL") {
+            self.telegram.public_url = Some(url);
+        }
+        if let Some(chats) = env_list("CLAWASM_PROXY_TELEGRAM_CHATS") {
+            self.telegram.allowed_chats = chats.iter().filter_map(|c| c.trim().parse().ok()).collect();
+        }
+        if let Some(users) = env_list("CLAWASM_PROXY_TELEGRAM_USERS") {
+            self.telegram.allowed_users = users.iter().map(|u| telegram::User::parse(u)).collect();
+        }
+        if let Some(token) = env("CLAWASM_PROXY_WHATSAPP_TOKEN") {
+            self.whatsapp.access_token = Some(token);
+        }
+        if let Some(id) = env("CLAWASM_PROXY_WHATSAPP_PHONE_NUMBER_ID") {
+            self.whatsapp.phone_number_id = Some(id);
+        }
+        if let Some(token) = env("CLAWASM_PROXY_WHATSAPP_VERIFY_TOKEN") {
+            self.whatsapp.verify_token = Some(token);
+        }
+        if let Some(secret) = env("CLAWASM_PROXY_WHATSAPP_APP_SECRET") {
+            self.whatsapp.app_secret = Some(secret);
+        }
+        if let Some(numbers) = env_list("CLAWASM_PROXY_WHATSAPP_NUMBERS") {
+            self.whatsapp.allowed_numbers = numbers.iter().map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect();
+        }
+        if let Some(instance) = env("CLAWASM_PROXY_MASTODON_INSTANCE") {
+            self.mastodon.instance = Some(instance);
+        }
+        if let Some(token) = env("CLAWASM_PROXY_MASTODON_TOKEN") {
+            self.mastodon.access_token = Some(token);
+        }
+        if let Some(identifier) = env("CLAWASM_PROXY_BLUESKY_IDENTIFIER") {
+            self.bluesky.identifier = Some(identifier);
+        }
+        if let Some(password) = env("CLAWASM_PROXY_BLUESKY_APP_PASSWORD") {
+            self.bluesky.app_password = Some(password);
+        }
+        for (id, channel) in &mut self.channels {
+            if let Some(token) = env(&channels::env_name(id, "TOKEN")) {
+                channel.token = Some(token);
+            }
+            if let Some(url) = env(&channels::env_name(id, "URL")) {
+                channel.url = Some(url);
+            }
+        }
+        if let Some(dir) = env("CLAWASM_PROXY_WEB_DIR") {
+            self.web.dir = PathBuf::from(dir);
+        }
+        if let Some(web) = env("CLAWASM_PROXY_WEB") {
+            self.web.enabled = !matches!(web.trim(), "0" | "false" | "no" | "off");
+        }
+        if let Some(chrome) = env("CLAWASM_PROXY_CHROME") {
+            self.render.chrome = Some(PathBuf::from(chrome));
+        }
+        if let Some(no_sandbox) = env("CLAWASM_PROXY_CHROME_NO_SANDBOX") {
+            self.render.no_sandbox = matches!(no_sandbox.trim(), "1" | "true" | "yes");
+        }
+    }
+
+    fn apply_args(&mut self, args: &Args) {
+        if let Some(bind) = &args.bind {
+            self.bind = bind.clone();
+        }
+        if let Some(port) = args.port {
+            self.port = port;
+        }
+        if args.no_access_log {
+            self.access_log = false;
+        }
+        if args.tls_cert.is_some() {
+            self.tls = TlsConfig { cert: args.tls_cert.clone(), key: args.tls_key.clone() };
+        }
+        if !args.allow_hosts.is_empty() {
+            self.targets.allow_hosts = args.allow_hosts.clone();
+        }
+        if !args.deny_hosts.is_empty() {
+            self.targets.deny_hosts = args.deny_hosts.clone();
+        }
+        if args.allow_private {
+            self.targets.allow_private = true;
+        }
+        if let Some(ttl) = args.cache_ttl {
+            self.cache.ttl_secs = ttl;
+        }
+        if let Some(dir) = &args.cache_dir {
+            self.cache.dir = Some(dir.clone());
+        }
+        if let Some(rate) = args.client_rate {
+            self.limits.client_rate = rate;
+        }
+        if let Some(rate) = args.host_rate {
+            self.limits.host_rate = rate;
+        }
+        if let Some(max) = args.max_concurrent {
+            self.limits.max_concurrent = max;
+        }
+        if let Some(timeout) = args.timeout {
+            self.upstream.timeout_secs = timeout;
+        }
+        if let Some(retries) = args.retries {
+            self.upstream.retries = retries;
+        }
+        if args.insecure_tls {
+            self.upstream.insecure_tls = true;
+        }
+        if let Some(backend) = args.search_backend {
+            self.search.backend = backend;
+        }
+        if let Some(chrome) = &args.chrome {
+            self.render.chrome = Some(chrome.clone());
+        }
+        if args.telegram_polling {
+            self.telegram.mode = telegram::Mode::Polling;
+        }
+        if let Some(dir) = &args.web_dir {
+            self.web = WebConfig { enabled: true, dir: dir.clone() };
+        }
+        if args.no_web {
+            self.web.enabled = false;
+        }
+    }
+
+    /// `host:port` to listen on
+    pub fn address(&self) -> String {
+        if self.bind.contains(':') && !self.bind.starts_with('[') {
+            format!("[{}]:{}", self.bind, self.port)
+        } else {
+            format!("{}:{}", self.bind, self.port)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_file() {
+        let config = ProxyConfig::from_toml(r#"
+            bind = "0.0.0.0"
+            port = 8443
+
+            [tls]
+            cert = "cert.pem"
+            key = "key.pem"
+
+            [targets]
+            allow_hosts = ["*.Example.com"]
+
+            [cache]
+            ttl_secs = 0
+
+            [search]
+            backend = "searxng"
+            url = "https://searx.example.org"
+
+            [images]
+            sources = ["wikimedia"]
+
+            [email]
+            host = "smtp.example.com"
+            from = "bot@example.com"
+            allowed_recipients = ["@example.com"]
+
+            [imap]
+            host = "imap.example.com"
+            security = "starttls"
+            username = "bot@example.com"
+            password = "p"
+
+            [realtime]
+            slack_app_token = "xapp-1-A"
+            inbox_secs = 120
+
+            [sessions]
+            rate = 20
+            models = ["gpt-4o"]
+
+            [discord]
+            allowed_channels = ["81"]
+            dms = false
+            public_key = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c"
+            command_guild = "12"
+
+            [slack]
+            bot_token = "xoxb-1"
+            signing_secret = "abc"
+            allowed_users = ["U42"]
+
+            [telegram]
+            bot_token = "1:AA"
+            webhook_secret = "s3cret"
+            allowed_chats = [42, -100123]
+            mode = "polling"
+            chat_rate = 5
+
+            [whatsapp]
+            access_token = "EAAG"
+            phone_number_id = "100"
+            verify_token = "check"
+            app_secret = "s3cret"
+            allowed_numbers = ["+1 555 123 4567"]
+
+            [mastodon]
+            instance = "example.social"
+            access_token = "t"
+            visibility = "unlisted"
+
+            [bluesky]
+            identifier = "bot.bsky.social"
+            app_password = "abcd-efgh-ijkl-mnop"
+
+            [channels.ops]
+            kind = "slack"
+            url = "https://hooks.slack.com/services/T/B/x"
+            description = "Ops alerts"
+
+            [paste]
+            enabled = true
+            ttl_hours = 24
+            dir = "pastes"
+        "#).unwrap();
+        assert_eq!(config.address(), "0.0.0.0:8443");
+        assert_eq!(config.tls.cert.as_deref(), Some(Path::new("cert.pem")));
+        assert_eq!(config.cache, CacheConfig { ttl_secs: 0, ..Default::default() });
+        assert_eq!(config.limits, LimitsConfig::default());
+        assert!(!config.upstream.insecure_tls, "certificates are verified by default");
+        assert_eq!(config.search.backend, Backend::SearxNG);
+        assert!(config.search.validate().is_ok());
+        assert_eq!(config.images.sources, [ImageSource::Wikimedia]);
+        assert!(config.email.validate().is_ok() && config.email.allows("me@example.com"));
+        assert!(config.imap.validate().is_ok() && config.imap.mailbox == "INBOX" && config.imap.poll_secs == 60);
+        assert!(config.realtime.validate().is_ok() && config.realtime.discord_token.is_none() && config.realtime.inbox_secs == 120);
+        assert!(config.sessions.validate().is_ok() && config.sessions.rate == 20 && config.sessions.max_sessions == 10_000);
+        assert!(config.discord.validate().is_ok() && !config.discord.dms && config.discord.register_commands);
+        assert!(config.slack.validate().is_ok() && config.slack.dms && config.slack.allowed_users == ["U42"]);
+        assert!(config.telegram.validate().is_ok() && config.telegram.allowed_chats == [42, -100123]);
+        assert_eq!((config.telegram.mode, config.telegram.chat_rate), (telegram::Mode::Polling, Some(5)));
+        assert!(config.whatsapp.validate().is_ok() && config.whatsapp.api_version == "v21.0");
+        assert!(config.mastodon.validate().is_ok() && config.mastodon.visibility == crate::mastodon::Visibility::Unlisted);
+        assert!(config.bluesky.validate().is_ok() && config.bluesky.service == "https://bsky.social");
+        assert!(config.channels["ops"].validate("ops").is_ok() && config.channels["ops"].kind == channels::Kind::Slack);
+        assert!(config.paste.validate().is_ok() && config.paste.enabled && config.paste.ttl_hours == 24 && config.paste.max_bytes == 1024 * 1024);
+        assert_eq!(config.targets.schemes, ["http", "https"]);
+
+        assert!(ProxyConfig::from_toml("prot = 1").unwrap_err().contains("prot"));
+        assert!(ProxyConfig::from_toml("[cache]\nttl_secs = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn test_flags_override_file() {
+        let args = Args::try_parse_from([
+            "proxy", "--port", "9000", "--allow-host", "a.test,B.test", "--allow-host", "c.test",
+            "--client-rate", "0", "--tls-cert", "c.pem", "--tls-key", "k.pem", "--insecure-tls",
+        ]).unwrap();
+        let mut config = ProxyConfig::from_toml("port = 4000\n[limits]\nclient_rate = 30\nhost_rate = 5").unwrap();
+        config.apply_args(&args);
+        config.targets.normalize();
+
+        assert_eq!(config.address(), "127.0.0.1:9000");
+        assert_eq!(config.targets.allow_hosts, ["a.test", "b.test", "c.test"]);
+        assert_eq!(config.limits, LimitsConfig { client_rate: 0, host_rate: 5, max_concurrent: 16 });
+        assert!(config.tls.key.is_some());
+        assert!(config.upstream.insecure_tls);
+
+        assert!(Args::try_parse_from(["proxy", "--tls-cert", "c.pem"]).is_err(), "cert without key");
+        let mut api_only = ProxyConfig::default();
+        api_only.apply_args(&Args::try_parse_from(["proxy", "--no-web"]).unwrap());
+        assert_eq!(api_only.web.site(), None);
+        assert_eq!(ProxyConfig { bind: "::1".to_string(), ..Default::default() }.address(), "[::1]:3000");
+    }
+}
@@ -0,0 +1,636 @@
+//! Discord bot on the gateway connection of `realtime.rs`
+//!
+//! Every gateway dispatch already goes to `/ws` as is. On top of that, a
+//! message that mentions the bot (or replies to it) in an allowed channel,
+//! or a direct message, becomes a `message` event with the mention removed
+//! and the channel's recent turns, in the shape Telegram messages have:
+//!
+//! ```json
+//! {"channel": "discord", "type": "message", "data": {"channel_id": "81", "message_id": "93",
+//!   "guild_id": "12", "user_id": "42", "username": "ann", "name": "Ann", "dm": false,
+//!   "text": "...", "history": [{"role": "user", "content": "..."}], "model": null}}
+//! ```
+//!
+//! The browser answers with POST /channel/discord/send `{"channel_id",
+//! "text", "reply_to"}`, which posts through the REST API with the bot
+//! token, split at 2000 characters, and never pings @everyone or roles.
+//! Only channels the bot was talking in can be answered. `/new` after the
+//! mention clears a channel's history and `/model` picks one of the
+//! `[sessions]` models for it; each user gets the `[sessions]` rate.
+//!
+//! With `public_key` set, POST /channel/discord/interactions is the app's
+//! Interactions Endpoint URL. Requests are checked against their Ed25519
+//! signature, and the /ask, /research and /scan slash commands (registered
+//! when the gateway is ready) are answered at once with a deferred
+//! response, Discord's "thinking…" placeholder, then relayed as `message`
+//! events with `command` and `interaction_id`. Sending with that
+//! `interaction_id` replaces the placeholder; agent runs may take the
+//! fifteen minutes an interaction token lives.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Event, Hub, RealtimeConfig};
+use crate::sessions::{self, Sessions};
+use crate::telegram::{chunks, unix_now, Turn};
+
+pub const HOST: &str = "discord.com";
+const API: &str = "https://discord.com/api/v10";
+pub const MAX_MESSAGE_CHARS: usize = 2000;
+pub const SIGNATURE_HEADER: &str = "X-Signature-Ed25519";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+/// How long Discord accepts follow-ups to an interaction
+const INTERACTION_TTL: Duration = Duration::from_secs(15 * 60);
+/// Signed requests older than this are taken for replays
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+/// Response flag that shows a message to the invoking user only
+const EPHEMERAL: u64 = 1 << 6;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    /// Channel IDs where mentions are answered; empty answers in any channel
+    pub allowed_channels: Vec<String>,
+    /// Answer direct messages
+    pub dms: bool,
+    /// User IDs the bot answers; empty answers anyone
+    pub allowed_users: Vec<String>,
+    /// Messages per channel kept as context
+    pub history: usize,
+    /// Sent when no browser is connected to answer
+    pub offline_reply: Option<String>,
+    /// The application's public key (hex, from the Developer Portal);
+    /// turns on the interactions endpoint and slash commands
+    pub public_key: Option<String>,
+    /// Replace the application's slash commands with /ask, /research and
+    /// /scan when the gateway is ready
+    pub register_commands: bool,
+    /// Register the commands in this server only, where they show up at
+    /// once; global commands can take an hour to appear
+    pub command_guild: Option<String>,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        DiscordConfig {
+            allowed_channels: Vec::new(),
+            dms: true,
+            allowed_users: Vec::new(),
+            history: 20,
+            offline_reply: Some("The assistant is offline right now. Please try again later.".to_string()),
+            public_key: None,
+            register_commands: true,
+            command_guild: None,
+        }
+    }
+}
+
+impl DiscordConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for id in self.allowed_channels.iter().chain(&self.allowed_users).chain(&self.command_guild) {
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("discord IDs are numbers (turn on Developer Mode to copy them), not {:?}", id));
+            }
+        }
+        if let Some(key) = &self.public_key {
+            if hex(key).is_none_or(|key| key.len() != 32) {
+                return Err("discord public_key is the 64 hex digits shown in the Developer Portal".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A message for the bot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    pub channel_id: String,
+    pub message_id: String,
+    pub guild_id: Option<String>,
+    pub user_id: String,
+    pub username: String,
+    pub name: String,
+    /// A direct message rather than a mention in a server
+    pub dm: bool,
+    pub text: String,
+}
+
+/// The MESSAGE_CREATE payload as a message for the bot `bot_id`, if it is
+/// one: a direct message, a mention, or a reply to the bot
+pub fn incoming(message: &Value, bot_id: &str) -> Option<Incoming> {
+    let author = &message["author"];
+    if author["bot"].as_bool() == Some(true) || author["id"].as_str() == Some(bot_id) {
+        return None;
+    }
+    let dm = message["guild_id"].is_null();
+    let mentioned = message["mentions"].as_array().into_iter().flatten().any(|user| user["id"].as_str() == Some(bot_id))
+        || message["referenced_message"]["author"]["id"].as_str() == Some(bot_id);
+    if !dm && !mentioned {
+        return None;
+    }
+    let content = message["content"].as_str()?;
+    let text = content.replace(&format!("<@{}>", bot_id), "").replace(&format!("<@!{}>", bot_id), "");
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let username = author["username"].as_str()?.to_string();
+    Some(Incoming {
+        channel_id: message["channel_id"].as_str()?.to_string(),
+        message_id: message["id"].as_str()?.to_string(),
+        guild_id: message["guild_id"].as_str().map(str::to_string),
+        user_id: author["id"].as_str()?.to_string(),
+        name: message["member"]["nick"].as_str().or_else(|| author["global_name"].as_str()).unwrap_or(&username).to_string(),
+        username,
+        dm,
+        text: text.to_string(),
+    })
+}
+
+/// Decode hex digits, as Discord writes keys and signatures
+fn hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// The slash commands, as registered with Discord
+fn commands() -> Value {
+    let command = |name: &str, description: &str, option: &str, about: &str| json!({
+        "name": name,
+        "type": 1,
+        "description": description,
+        "options": [{ "type": 3, "name": option, "description": about, "required": true }],
+    });
+    json!([
+        command("ask", "Ask the assistant", "question", "What to ask"),
+        command("research", "Research a topic on the web and summarize it", "topic", "What to research"),
+        command("scan", "Check a site you own for security issues", "target", "Domain or URL"),
+    ])
+}
+
+/// What the agent is asked for a slash command and its option
+pub fn prompt(command: &str, input: &str) -> Option<String> {
+    match command {
+        "ask" => Some(input.to_string()),
+        "research" => Some(format!("Research this topic on the web and write a short summary that cites its sources: {}", input)),
+        "scan" => Some(format!("Run a security scan of {} and summarize the findings, most serious first.", input)),
+        _ => None,
+    }
+}
+
+/// An immediate reply only the invoking user sees
+fn ephemeral(text: &str) -> Value {
+    json!({ "type": 4, "data": { "content": text, "flags": EPHEMERAL } })
+}
+
+/// A deferred slash command, until its follow-up is sent
+#[derive(Debug, Clone)]
+struct Pending {
+    application_id: String,
+    token: String,
+    channel_id: String,
+    since: Instant,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub channel_id: String,
+    pub text: String,
+    /// Message the answer replies to
+    pub reply_to: Option<String>,
+    /// Slash command the answer completes, from the `message` event
+    pub interaction_id: Option<String>,
+}
+
+/// The bot's settings, its user ID once the gateway is ready, and the
+/// sessions of the channels it talks in
+pub struct Discord {
+    pub config: DiscordConfig,
+    token: Option<String>,
+    bot_id: Mutex<Option<String>>,
+    sessions: Arc<Sessions>,
+    public_key: Option<Vec<u8>>,
+    interactions: Mutex<HashMap<String, Pending>>,
+}
+
+impl Discord {
+    pub fn new(config: &DiscordConfig, realtime: &RealtimeConfig, sessions: Arc<Sessions>) -> Self {
+        Discord {
+            config: config.clone(),
+            token: realtime.discord_token.as_deref().map(|t| t.trim().trim_start_matches("Bot ").to_string()),
+            bot_id: Mutex::new(None),
+            sessions,
+            public_key: config.public_key.as_deref().and_then(hex),
+            interactions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    pub fn interactions_enabled(&self) -> bool {
+        self.public_key.is_some()
+    }
+
+    /// Whether `signature` is the application's over `timestamp` and
+    /// `body`, made recently
+    pub fn verify(&self, timestamp: &str, body: &[u8], signature: &str) -> bool {
+        let (Some(key), Some(signature)) = (&self.public_key, hex(signature)) else {
+            return false;
+        };
+        let fresh = timestamp.parse::<i64>().is_ok_and(|t| (unix_now() - t).abs() <= MAX_CLOCK_SKEW_SECS);
+        let message = [timestamp.as_bytes(), body].concat();
+        fresh && ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key).verify(&message, &signature).is_ok()
+    }
+
+    pub fn allows(&self, message: &Incoming) -> bool {
+        let channel = match message.dm {
+            true => self.config.dms,
+            false => self.config.allowed_channels.is_empty() || self.config.allowed_channels.contains(&message.channel_id),
+        };
+        channel && (self.config.allowed_users.is_empty() || self.config.allowed_users.contains(&message.user_id))
+    }
+
+    /// Whether the bot may post in `channel_id`: one of its conversations
+    /// or an allowed channel
+    pub fn may_send(&self, channel_id: &str) -> bool {
+        self.config.allowed_channels.iter().any(|c| c == channel_id) || self.sessions.active(Channel::Discord, channel_id)
+    }
+
+    /// Whether `req` may be sent: an answer to a deferred slash command in
+    /// its channel, or a message `may_send` allows
+    pub fn may_answer(&self, req: &SendRequest) -> bool {
+        match &req.interaction_id {
+            Some(id) => self.interactions.lock().unwrap().get(id)
+                .is_some_and(|pending| pending.channel_id == req.channel_id && pending.since.elapsed() < INTERACTION_TTL),
+            None => self.may_send(&req.channel_id),
+        }
+    }
+
+    pub fn history(&self, channel_id: &str) -> Vec<Turn> {
+        self.sessions.history(Channel::Discord, channel_id)
+    }
+
+    pub fn remember(&self, channel_id: &str, role: &'static str, content: &str) {
+        self.sessions.remember(Channel::Discord, channel_id, role, content, self.config.history);
+    }
+
+    /// Clear the history but keep the channel answerable
+    pub fn forget(&self, channel_id: &str) {
+        self.sessions.forget(Channel::Discord, channel_id);
+    }
+
+    /// Call a REST endpoint with the bot token
+    async fn call(&self, client: &reqwest::Client, method: reqwest::Method, path: &str, body: &Value) -> Result<Value, String> {
+        let token = self.token.as_deref().ok_or("Discord is not configured")?;
+        let response = client.request(method, format!("{}{}", API, path))
+            .header("Authorization", format!("Bot {}", token))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let reply: Value = match status {
+            reqwest::StatusCode::NO_CONTENT => Value::Null,
+            _ => response.json().await.map_err(|e| e.to_string())?,
+        };
+        match status.is_success() {
+            true => Ok(reply),
+            false if status == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                Err(format!("rate limited; retry in {}s", reply["retry_after"].as_f64().unwrap_or(1.0)))
+            }
+            false => Err(format!("{} {}", status.as_u16(), reply["message"].as_str().unwrap_or("request refused"))),
+        }
+    }
+
+    /// Post `text` to a channel, split as needed; the answer is the number
+    /// of messages sent
+    pub async fn send(&self, client: &reqwest::Client, channel_id: &str, text: &str, reply_to: Option<&str>) -> Result<usize, String> {
+        let chunks = chunks(text, MAX_MESSAGE_CHARS);
+        if chunks.is_empty() {
+            return Err("Missing 'text'".to_string());
+        }
+        let path = format!("/channels/{}/messages", channel_id);
+        for (i, chunk) in chunks.iter().enumerate() {
+            // Model output must not ping @everyone, roles or strangers
+            let mut body = json!({ "content": chunk, "allowed_mentions": { "parse": [], "replied_user": true } });
+            if let (0, Some(message_id)) = (i, reply_to) {
+                body["message_reference"] = json!({ "message_id": message_id, "fail_if_not_exists": false });
+            }
+            self.call(client, reqwest::Method::POST, &path, &body).await?;
+        }
+        Ok(chunks.len())
+    }
+
+    /// Replace the placeholder of a deferred slash command with `text`,
+    /// the rest of a long answer following as more messages
+    pub async fn complete(&self, client: &reqwest::Client, interaction_id: &str, text: &str) -> Result<usize, String> {
+        let pending = self.interactions.lock().unwrap().get(interaction_id).cloned()
+            .ok_or_else(|| format!("Unknown or expired interaction {}", interaction_id))?;
+        let chunks = chunks(text, MAX_MESSAGE_CHARS);
+        if chunks.is_empty() {
+            return Err("Missing 'text'".to_string());
+        }
+        let webhook = format!("/webhooks/{}/{}", pending.application_id, pending.token);
+        for (i, chunk) in chunks.iter().enumerate() {
+            let body = json!({ "content": chunk, "allowed_mentions": { "parse": [] } });
+            match i {
+                0 => self.call(client, reqwest::Method::PATCH, &format!("{}/messages/@original", webhook), &body).await?,
+                _ => self.call(client, reqwest::Method::POST, &webhook, &body).await?,
+            };
+        }
+        self.interactions.lock().unwrap().remove(interaction_id);
+        Ok(chunks.len())
+    }
+
+    /// Send `req`, to its interaction or its channel
+    pub async fn deliver(&self, client: &reqwest::Client, req: &SendRequest) -> Result<usize, String> {
+        match &req.interaction_id {
+            Some(id) => self.complete(client, id, &req.text).await,
+            None => self.send(client, &req.channel_id, &req.text, req.reply_to.as_deref()).await,
+        }
+    }
+
+    /// Answer a signed interaction: PING, or a slash command that is
+    /// deferred and relayed to the browsers on /ws
+    pub fn interaction(&self, interaction: &Value, hub: &Hub) -> Value {
+        match interaction["type"].as_u64() {
+            Some(1) => json!({ "type": 1 }),
+            Some(2) => self.command(interaction, hub),
+            _ => ephemeral("This kind of interaction is not supported."),
+        }
+    }
+
+    fn command(&self, interaction: &Value, hub: &Hub) -> Value {
+        let data = &interaction["data"];
+        let name = data["name"].as_str().unwrap_or("");
+        let input = data["options"][0]["value"].as_str().unwrap_or("").trim();
+        let Some(text) = prompt(name, input).filter(|_| !input.is_empty()) else {
+            return ephemeral(&format!("Unknown command /{}", name));
+        };
+        let user = match interaction["member"]["user"].is_object() {
+            true => &interaction["member"]["user"],
+            false => &interaction["user"],
+        };
+        let (Some(id), Some(token), Some(application_id), Some(channel_id), Some(user_id), Some(username)) = (
+            interaction["id"].as_str(), interaction["token"].as_str(), interaction["application_id"].as_str(),
+            interaction["channel_id"].as_str(), user["id"].as_str(), user["username"].as_str(),
+        ) else {
+            return ephemeral("This command is missing its channel or user.");
+        };
+        let message = Incoming {
+            channel_id: channel_id.to_string(),
+            message_id: id.to_string(),
+            guild_id: interaction["guild_id"].as_str().map(str::to_string),
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            name: interaction["member"]["nick"].as_str().or_else(|| user["global_name"].as_str()).unwrap_or(username).to_string(),
+            dm: interaction["guild_id"].is_null(),
+            text,
+        };
+        if !self.allows(&message) {
+            eprintln!("⛔ Discord: refused /{} from {} in channel {}", name, message.user_id, message.channel_id);
+            return ephemeral("Sorry, this bot is private.");
+        }
+        if let Err(wait) = self.sessions.spend(Channel::Discord, &message.user_id) {
+            eprintln!("🐢 Rate limit for Discord user {}: retry in {}s", message.user_id, sessions::wait_secs(wait));
+            return ephemeral(&sessions::slow_down(wait));
+        }
+        if hub.listeners(Channel::Discord) == 0 {
+            eprintln!("→ Discord /{} in channel {} with no browser connected", name, message.channel_id);
+            let offline = self.config.offline_reply.as_deref().unwrap_or("The assistant is offline right now.");
+            return ephemeral(offline);
+        }
+
+        eprintln!("→ Discord /{} in channel {}", name, message.channel_id);
+        {
+            let mut interactions = self.interactions.lock().unwrap();
+            interactions.retain(|_, pending| pending.since.elapsed() < INTERACTION_TTL);
+            interactions.insert(id.to_string(), Pending {
+                application_id: application_id.to_string(),
+                token: token.to_string(),
+                channel_id: message.channel_id.clone(),
+                since: Instant::now(),
+            });
+        }
+        let mut data = json!(message);
+        data["command"] = json!(name);
+        data["interaction_id"] = json!(id);
+        data["history"] = json!(self.history(&message.channel_id));
+        data["model"] = json!(self.sessions.model(Channel::Discord, &message.channel_id));
+        self.remember(&message.channel_id, "user", &message.text);
+        hub.relay(Channel::Discord, "message", data);
+        json!({ "type": 5 })
+    }
+
+    /// Put /ask, /research and /scan in place of the application's
+    /// commands
+    async fn register_commands(&self, client: &reqwest::Client, application_id: &str) -> Result<(), String> {
+        let path = match &self.config.command_guild {
+            Some(guild) => format!("/applications/{}/guilds/{}/commands", application_id, guild),
+            None => format!("/applications/{}/commands", application_id),
+        };
+        self.call(client, reqwest::Method::PUT, &path, &commands()).await.map(|_| ())
+    }
+
+    /// Reply to `message`, logging failures
+    async fn reply(&self, client: &reqwest::Client, metrics: &Metrics, message: &Incoming, text: &str) {
+        let sent = self.send(client, &message.channel_id, text, Some(&message.message_id));
+        if let Err(e) = metrics.upstream("discord:messages", sent).await {
+            eprintln!("❌ Discord message: {}", e);
+        }
+    }
+
+    /// Relay one MESSAGE_CREATE to the browsers on /ws, or answer it here
+    async fn handle(&self, payload: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        let Some(bot_id) = self.bot_id.lock().unwrap().clone() else {
+            return;
+        };
+        let Some(message) = incoming(payload, &bot_id) else {
+            return;
+        };
+        if !self.allows(&message) {
+            eprintln!("⛔ Discord: ignored {} in channel {}", message.user_id, message.channel_id);
+            return;
+        }
+        if let Err(wait) = self.sessions.spend(Channel::Discord, &message.user_id) {
+            eprintln!("🐢 Rate limit for Discord user {}: retry in {}s", message.user_id, sessions::wait_secs(wait));
+            if self.sessions.notice(Channel::Discord, &message.user_id) {
+                self.reply(client, metrics, &message, &sessions::slow_down(wait)).await;
+            }
+            return;
+        }
+        if matches!(message.text.as_str(), "/new" | "/reset") {
+            self.forget(&message.channel_id);
+            self.reply(client, metrics, &message, "Started a new conversation.").await;
+            return;
+        }
+        if let Some(args) = sessions::model_args(&message.text) {
+            let answer = self.sessions.model_command(Channel::Discord, &message.channel_id, &self.sessions.config.models, args);
+            self.reply(client, metrics, &message, &answer).await;
+            return;
+        }
+        if hub.listeners(Channel::Discord) == 0 {
+            eprintln!("→ Discord message in channel {} with no browser connected", message.channel_id);
+            if let Some(offline) = &self.config.offline_reply {
+                self.reply(client, metrics, &message, offline).await;
+            }
+            return;
+        }
+
+        eprintln!("→ Discord message in channel {}", message.channel_id);
+        let mut data = json!(message);
+        data["history"] = json!(self.history(&message.channel_id));
+        data["model"] = json!(self.sessions.model(Channel::Discord, &message.channel_id));
+        self.remember(&message.channel_id, "user", &message.text);
+        hub.relay(Channel::Discord, "message", data);
+        let _ = self.call(client, reqwest::Method::POST, &format!("/channels/{}/typing", message.channel_id), &json!({})).await;
+    }
+
+    /// Follow the gateway events the hub publishes, for the life of the
+    /// process; subscribed before the future first runs, so READY is not
+    /// missed
+    pub fn run(self: Arc<Self>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>) -> impl Future<Output = ()> {
+        let events = hub.subscribe();
+        self.follow(events, hub, client, metrics)
+    }
+
+    async fn follow(self: Arc<Self>, mut events: Receiver<Arc<Event>>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    eprintln!("⚠️  Discord bot fell behind and skipped {} events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if event.channel != Channel::Discord.name() {
+                continue;
+            }
+            match event.kind.as_str() {
+                "READY" => {
+                    let user = &event.data["user"];
+                    println!("   Discord bot: {} ({})", user["username"].as_str().unwrap_or(""), user["id"].as_str().unwrap_or(""));
+                    *self.bot_id.lock().unwrap() = user["id"].as_str().map(str::to_string);
+                    if let (true, true, Some(application_id)) =
+                        (self.interactions_enabled(), self.config.register_commands, event.data["application"]["id"].as_str())
+                    {
+                        let registered = self.register_commands(&client, application_id);
+                        match metrics.upstream("discord:commands", registered).await {
+                            Ok(()) => println!("   Discord slash commands: /ask, /research, /scan"),
+                            Err(e) => eprintln!("❌ Discord commands: {}", e),
+                        }
+                    }
+                }
+                "MESSAGE_CREATE" => self.handle(&event.data, &hub, &client, &metrics).await,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming() {
+        let mention = json!({"id": "93", "channel_id": "81", "guild_id": "12", "content": "<@7> what's up?",
+            "author": {"id": "42", "username": "ann", "global_name": "Ann"}, "mentions": [{"id": "7"}]});
+        let message = incoming(&mention, "7").unwrap();
+        assert_eq!((message.text.as_str(), message.name.as_str(), message.dm), ("what's up?", "Ann", false));
+        assert!(incoming(&mention, "8").is_none(), "not for this bot");
+
+        let dm = json!({"id": "94", "channel_id": "55", "content": "hi", "author": {"id": "42", "username": "ann"}});
+        assert!(incoming(&dm, "7").unwrap().dm);
+        let reply = json!({"id": "95", "channel_id": "81", "guild_id": "12", "content": "and then?",
+            "author": {"id": "42", "username": "ann"}, "referenced_message": {"author": {"id": "7"}}});
+        assert_eq!(incoming(&reply, "7").unwrap().text, "and then?");
+        let from_bot = json!({"id": "96", "channel_id": "55", "content": "hi", "author": {"id": "9", "username": "b", "bot": true}});
+        assert!(incoming(&from_bot, "7").is_none());
+    }
+
+    #[test]
+    fn test_allows() {
+        let config = DiscordConfig { allowed_channels: vec!["81".to_string()], dms: false, ..Default::default() };
+        assert!(config.validate().is_ok());
+        assert!(DiscordConfig { allowed_users: vec!["@ann".to_string()], ..Default::default() }.validate().is_err());
+        let discord = Discord::new(&config, &RealtimeConfig::default(), Default::default());
+        let message = |channel: &str, dm: bool| Incoming {
+            channel_id: channel.to_string(), message_id: "1".to_string(), guild_id: None, user_id: "42".to_string(),
+            username: "ann".to_string(), name: "Ann".to_string(), dm, text: "hi".to_string(),
+        };
+        assert!(discord.allows(&message("81", false)));
+        assert!(!discord.allows(&message("82", false)) && !discord.allows(&message("55", true)));
+
+        assert!(discord.may_send("81") && !discord.may_send("55"));
+        discord.remember("55", "user", "hi");
+        discord.forget("55");
+        assert!(discord.may_send("55") && discord.history("55").is_empty(), "forgetting keeps the conversation");
+    }
+
+    #[test]
+    fn test_verify() {
+        use ring::signature::KeyPair;
+        let pair = ring::signature::Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let public_key: String = pair.public_key().as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+        let config = DiscordConfig { public_key: Some(public_key), ..Default::default() };
+        assert!(config.validate().is_ok());
+        assert!(DiscordConfig { public_key: Some("abc".to_string()), ..Default::default() }.validate().is_err());
+        let discord = Discord::new(&config, &RealtimeConfig::default(), Default::default());
+
+        let sign = |timestamp: &str, body: &str| -> String {
+            pair.sign(format!("{}{}", timestamp, body).as_bytes()).as_ref().iter().map(|b| format!("{:02X}", b)).collect()
+        };
+        let now = unix_now().to_string();
+        let body = r#"{"type":1}"#;
+        assert!(discord.verify(&now, body.as_bytes(), &sign(&now, body)));
+        assert!(!discord.verify(&now, br#"{"type":2}"#, &sign(&now, body)), "signed another body");
+        let old = (unix_now() - 3600).to_string();
+        assert!(!discord.verify(&old, body.as_bytes(), &sign(&old, body)), "a replay");
+        assert!(!discord.verify(&now, body.as_bytes(), "zz"));
+    }
+
+    #[test]
+    fn test_interaction() {
+        let config = DiscordConfig { public_key: Some("00".repeat(32)), allowed_channels: vec!["81".to_string()], ..Default::default() };
+        let discord = Discord::new(&config, &RealtimeConfig::default(), Default::default());
+        let hub = Arc::new(Hub::new(&RealtimeConfig::default(), &[]));
+        assert_eq!(discord.interaction(&json!({"type": 1}), &hub), json!({"type": 1}));
+
+        let command = |channel: &str| json!({"type": 2, "id": "500", "token": "tok", "application_id": "9",
+            "channel_id": channel, "guild_id": "12", "member": {"user": {"id": "42", "username": "ann"}},
+            "data": {"name": "research", "options": [{"name": "topic", "value": " WebAssembly GC "}]}});
+        let offline = discord.interaction(&command("81"), &hub);
+        assert_eq!((offline["type"].as_u64(), offline["data"]["flags"].as_u64()), (Some(4), Some(EPHEMERAL)));
+        let listening = hub.clone().listen(vec![Channel::Discord]);
+        assert_eq!(discord.interaction(&command("82"), &hub)["data"]["content"], "Sorry, this bot is private.");
+
+        let mut events = hub.subscribe();
+        assert_eq!(discord.interaction(&command("81"), &hub), json!({"type": 5}), "deferred");
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.data["command"].as_str(), event.data["interaction_id"].as_str()), (Some("research"), Some("500")));
+        assert!(event.data["text"].as_str().unwrap().ends_with(": WebAssembly GC"));
+
+        let answer = |channel: &str, id: &str| SendRequest {
+            channel_id: channel.to_string(), text: "Done".to_string(), reply_to: None, interaction_id: Some(id.to_string()),
+        };
+        assert!(discord.may_answer(&answer("81", "500")));
+        assert!(!discord.may_answer(&answer("81", "501")) && !discord.may_answer(&answer("55", "500")));
+        drop(listening);
+    }
+}
@@ -0,0 +1,280 @@
+//! Email delivery behind POST /channel/email/send
+//!
+//! Reports and scheduled briefings leave the browser as mail sent through
+//! the SMTP server in `[email]`; the credentials never reach the client.
+//! The body is JSON:
+//!
+//! ```json
+//! {"to": ["me@example.com"], "cc": [], "subject": "Weekly briefing",
+//!  "text": "...", "html": "<p>...</p>",
+//!  "attachments": [{"filename": "report.pdf", "content_type": "application/pdf", "data": "<base64>"}]}
+//! ```
+//!
+//! At least one of `text` and `html` is required; with both, mail clients
+//! pick the one they can show. `allowed_recipients` keeps a leaked token
+//! from turning the proxy into an open relay.
+
+use std::time::Duration;
+
+use base64::Engine;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Plain connection upgraded with STARTTLS (port 587)
+    #[default]
+    StartTls,
+    /// TLS from the first byte (port 465)
+    Tls,
+    /// No encryption; only for a relay on the same machine
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    /// SMTP server; email is off without one
+    pub host: Option<String>,
+    /// Defaults to 587, 465 or 25 by `security`
+    pub port: Option<u16>,
+    pub security: Security,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender, e.g. `claWasm <bot@example.com>`
+    pub from: Option<String>,
+    /// Addresses (or `@domain`s) mail may go to; empty allows any
+    pub allowed_recipients: Vec<String>,
+    pub max_recipients: usize,
+    /// Largest total size of the decoded attachments
+    pub max_attachment_bytes: usize,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            host: None,
+            port: None,
+            security: Security::StartTls,
+            username: None,
+            password: None,
+            from: None,
+            allowed_recipients: Vec::new(),
+            max_recipients: 10,
+            max_attachment_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl EmailConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.is_none() {
+            return Ok(());
+        }
+        match &self.from {
+            Some(from) => from.parse::<Mailbox>().map(|_| ()).map_err(|e| format!("email from {:?}: {}", from, e))?,
+            None => return Err("email needs from (the sender address)".to_string()),
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return Err("email needs both username and password".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether `address` may receive mail
+    pub fn allows(&self, address: &str) -> bool {
+        listed(&self.allowed_recipients, address)
+    }
+}
+
+/// Whether `address` matches `list` of addresses and `@domain`s; an empty
+/// list matches any
+pub fn listed(list: &[String], address: &str) -> bool {
+    let address = address.trim().to_lowercase();
+    list.is_empty() || list.iter().any(|allowed| {
+        let allowed = allowed.trim().to_lowercase();
+        match allowed.strip_prefix('@') {
+            Some(domain) => address.rsplit_once('@').is_some_and(|(_, d)| d == domain),
+            None => address == allowed,
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: Option<String>,
+    /// Base64 content
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub text: Option<String>,
+    pub html: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<EmailAttachment>,
+}
+
+/// A message body part: the text, or the text/HTML pair, or an attachment
+enum Part {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+/// The configured SMTP transport, built once at startup
+pub struct Mailer {
+    pub config: EmailConfig,
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+}
+
+impl Mailer {
+    pub fn new(config: &EmailConfig) -> Result<Self, String> {
+        let Some(host) = &config.host else {
+            return Ok(Mailer { config: config.clone(), transport: None });
+        };
+        let mut builder = match config.security {
+            Security::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            Security::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            Security::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+        }
+        .map_err(|e| format!("Cannot use SMTP server {}: {}", host, e))?
+        .timeout(Some(Duration::from_secs(30)));
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(Mailer { config: config.clone(), transport: Some(builder.build()) })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    /// Check a request and build its message; errors are the client's
+    pub fn message(&self, req: &SendRequest) -> Result<Message, String> {
+        let from: Mailbox = self.config.from.as_deref().unwrap_or("").parse()
+            .map_err(|e| format!("Invalid sender: {}", e))?;
+        if req.to.is_empty() {
+            return Err("Missing 'to'".to_string());
+        }
+        if req.to.len() + req.cc.len() > self.config.max_recipients {
+            return Err(format!("{} recipients; the limit is {}", req.to.len() + req.cc.len(), self.config.max_recipients));
+        }
+        let mailbox = |address: &String| -> Result<Mailbox, String> {
+            let mailbox: Mailbox = address.parse().map_err(|e| format!("Invalid address {:?}: {}", address, e))?;
+            if !self.config.allows(mailbox.email.as_ref()) {
+                return Err(format!("{} is not in allowed_recipients", mailbox.email));
+            }
+            Ok(mailbox)
+        };
+
+        let mut builder = Message::builder().from(from).subject(req.subject.trim());
+        for address in &req.to {
+            builder = builder.to(mailbox(address)?);
+        }
+        for address in &req.cc {
+            builder = builder.cc(mailbox(address)?);
+        }
+        if let Some(reply_to) = &req.reply_to {
+            builder = builder.reply_to(reply_to.parse().map_err(|e| format!("Invalid reply_to: {}", e))?);
+        }
+
+        let mut parts = vec![match (&req.text, &req.html) {
+            (Some(text), Some(html)) => Part::Multi(MultiPart::alternative_plain_html(text.clone(), html.clone())),
+            (Some(text), None) => Part::Single(SinglePart::plain(text.clone())),
+            (None, Some(html)) => Part::Single(SinglePart::html(html.clone())),
+            (None, None) => return Err("Missing 'text' or 'html'".to_string()),
+        }];
+        let mut total = 0;
+        for attachment in &req.attachments {
+            let data = base64::engine::general_purpose::STANDARD.decode(attachment.data.trim())
+                .map_err(|e| format!("Attachment {}: {}", attachment.filename, e))?;
+            total += data.len();
+            if total > self.config.max_attachment_bytes {
+                return Err(format!("Attachments exceed {} bytes", self.config.max_attachment_bytes));
+            }
+            let content_type = ContentType::parse(attachment.content_type.as_deref().unwrap_or("application/octet-stream"))
+                .map_err(|e| format!("Attachment {}: {}", attachment.filename, e))?;
+            parts.push(Part::Single(Attachment::new(attachment.filename.clone()).body(data, content_type)));
+        }
+
+        let message = match parts.len() {
+            1 => match parts.remove(0) {
+                Part::Single(part) => builder.singlepart(part),
+                Part::Multi(part) => builder.multipart(part),
+            },
+            _ => builder.multipart(parts.into_iter().fold(MultiPart::mixed().build(), |mixed, part| match part {
+                Part::Single(part) => mixed.singlepart(part),
+                Part::Multi(part) => mixed.multipart(part),
+            })),
+        };
+        message.map_err(|e| e.to_string())
+    }
+
+    /// Hand the message to the SMTP server; the answer is its reply line
+    pub async fn send(&self, message: Message) -> Result<String, String> {
+        let transport = self.transport.as_ref().ok_or("Email is not configured")?;
+        let response = transport.send(message).await.map_err(|e| e.to_string())?;
+        Ok(format!("{} {}", response.code(), response.message().collect::<Vec<_>>().join(" ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailer(allowed: &[&str]) -> Mailer {
+        let config = EmailConfig {
+            host: Some("smtp.example.com".to_string()),
+            from: Some("claWasm <bot@example.com>".to_string()),
+            allowed_recipients: allowed.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        Mailer::new(&config).unwrap()
+    }
+
+    fn request(to: &str) -> SendRequest {
+        serde_json::from_value(serde_json::json!({
+            "to": [to], "subject": "Briefing", "text": "Hello", "html": "<p>Hello</p>",
+            "attachments": [{"filename": "a.txt", "content_type": "text/plain", "data": "aGk="}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_message() {
+        let message = mailer(&[]).message(&request("Ann <ann@example.org>")).unwrap();
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        assert!(raw.contains("To: Ann <ann@example.org>"));
+        assert!(raw.contains("multipart/alternative") && raw.contains("filename=\"a.txt\""));
+
+        let empty = SendRequest { text: None, html: None, ..request("ann@example.org") };
+        assert!(mailer(&[]).message(&empty).unwrap_err().contains("text"));
+    }
+
+    #[test]
+    fn test_recipients_and_config() {
+        let mailer = mailer(&["boss@example.com", "@team.example"]);
+        assert!(mailer.message(&request("dev@team.example")).is_ok());
+        assert!(mailer.message(&request("BOSS@example.com")).is_ok());
+        assert!(mailer.message(&request("someone@evil.example")).unwrap_err().contains("allowed_recipients"));
+
+        assert!(EmailConfig::default().validate().is_ok(), "off without a host");
+        let no_from = EmailConfig { host: Some("smtp.example.com".to_string()), ..Default::default() };
+        assert!(no_from.validate().unwrap_err().contains("from"));
+    }
+}
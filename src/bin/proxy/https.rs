@@ -0,0 +1,54 @@
+//! Serving the proxy over HTTPS
+//!
+//! A frontend served from https:// may not call an http:// proxy (mixed
+//! content), so a proxy on a public host needs TLS of its own. Given a PEM
+//! certificate chain and private key (`[tls]` in the config), the server
+//! terminates TLS with rustls and offers HTTP/2 and HTTP/1.1.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+
+/// Build the rustls server config from PEM files
+pub fn server_config(cert_path: &Path, key_path: &Path) -> Result<ServerConfig, String> {
+    let certs: Vec<CertificateDer<'static>> = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|e| format!("Cannot read certificate {}: {}", cert_path.display(), e))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid certificate {}: {}", cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", cert_path.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Cannot read private key {}: {}", key_path.display(), e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Certificate and key do not match: {}", e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bad_pem_files() {
+        let dir = std::env::temp_dir().join(format!("clawasm-https-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let empty = dir.join("empty.pem");
+        std::fs::write(&empty, "not a certificate\n").unwrap();
+
+        let missing = server_config(&dir.join("missing.pem"), &empty).unwrap_err();
+        assert!(missing.starts_with("Cannot read certificate"), "{}", missing);
+        assert!(server_config(&empty, &empty).unwrap_err().starts_with("No certificate found"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
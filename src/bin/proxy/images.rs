@@ -0,0 +1,255 @@
+//! Image search behind GET /images
+//!
+//! Openverse and Wikimedia Commons need no key and say how each image is
+//! licensed; Bing Images needs a key and does not. The configured sources
+//! are asked in order until enough images are found, and every answer is
+//! normalized to direct image URLs:
+//!
+//! ```json
+//! {"query": "...", "results": [{"title": "...", "url": "https://.../a.jpg", "thumbnail": "...",
+//!   "width": 1024, "height": 768, "license": "CC BY-SA 4.0", "license_url": "...",
+//!   "creator": "...", "page_url": "...", "source": "wikimedia"}]}
+//! ```
+
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::search::plain;
+
+/// Wikimedia refuses requests without a descriptive User-Agent
+const USER_AGENT: &str = "claWasm/0.1.0 (https://github.com/niyoseris/claWasm)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageSource {
+    Openverse,
+    Wikimedia,
+    Bing,
+}
+
+impl ImageSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageSource::Openverse => "openverse",
+            ImageSource::Wikimedia => "wikimedia",
+            ImageSource::Bing => "bing",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ImageSource> {
+        match name.trim().to_lowercase().as_str() {
+            "openverse" => Some(ImageSource::Openverse),
+            "wikimedia" | "commons" => Some(ImageSource::Wikimedia),
+            "bing" => Some(ImageSource::Bing),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImagesConfig {
+    /// Asked in this order until enough images are found
+    pub sources: Vec<ImageSource>,
+    /// Bing Image Search subscription key
+    pub bing_key: Option<String>,
+    /// Images per query unless the request asks for fewer
+    pub max_results: usize,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        ImagesConfig {
+            sources: vec![ImageSource::Openverse, ImageSource::Wikimedia],
+            bing_key: None,
+            max_results: 10,
+        }
+    }
+}
+
+impl ImagesConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sources.is_empty() {
+            return Err("images needs at least one source".to_string());
+        }
+        if self.sources.contains(&ImageSource::Bing) && self.bing_key.is_none() {
+            return Err("image source bing needs bing_key".to_string());
+        }
+        Ok(())
+    }
+
+    /// The upstream request for `query` at `source`
+    pub fn request(&self, client: &Client, source: ImageSource, query: &str, count: usize) -> RequestBuilder {
+        let count = count.to_string();
+        match source {
+            ImageSource::Openverse => client
+                .get("https://api.openverse.org/v1/images/")
+                .query(&[("q", query), ("page_size", &count), ("mature", "false")]),
+            ImageSource::Wikimedia => client
+                .get("https://commons.wikimedia.org/w/api.php")
+                .query(&[
+                    ("action", "query"), ("format", "json"), ("generator", "search"),
+                    ("gsrsearch", query), ("gsrnamespace", "6"), ("gsrlimit", &count),
+                    ("prop", "imageinfo"), ("iiprop", "url|size|mime|extmetadata"), ("iiurlwidth", "640"),
+                ]),
+            ImageSource::Bing => client
+                .get("https://api.bing.microsoft.com/v7.0/images/search")
+                .header("Ocp-Apim-Subscription-Key", self.bing_key.as_deref().unwrap_or(""))
+                .query(&[("q", query), ("count", &count), ("safeSearch", "Strict")]),
+        }
+        .header("User-Agent", USER_AGENT)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageResult {
+    pub title: String,
+    /// The image file itself
+    pub url: String,
+    pub thumbnail: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub license: Option<String>,
+    pub license_url: Option<String>,
+    pub creator: Option<String>,
+    /// The page the image appears on
+    pub page_url: Option<String>,
+    pub source: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImagesResponse {
+    pub query: String,
+    pub results: Vec<ImageResult>,
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(plain).filter(|s| !s.is_empty())
+}
+
+fn openverse(body: &Value) -> Vec<ImageResult> {
+    body["results"].as_array().into_iter().flatten()
+        .filter_map(|item| {
+            // "by-sa" + "4.0" -> "CC BY-SA 4.0"; "cc0" and "pdm" stand alone
+            let license = item["license"].as_str().map(|code| {
+                let version = item["license_version"].as_str().unwrap_or("");
+                match code {
+                    "cc0" => "CC0".to_string(),
+                    "pdm" => "Public Domain Mark".to_string(),
+                    _ => format!("CC {} {}", code.to_uppercase(), version).trim_end().to_string(),
+                }
+            });
+            Some(ImageResult {
+                title: text(&item["title"]).unwrap_or_else(|| "Untitled".to_string()),
+                url: item["url"].as_str()?.to_string(),
+                thumbnail: text(&item["thumbnail"]),
+                width: item["width"].as_u64(),
+                height: item["height"].as_u64(),
+                license,
+                license_url: text(&item["license_url"]),
+                creator: text(&item["creator"]),
+                page_url: text(&item["foreign_landing_url"]),
+                source: ImageSource::Openverse.name(),
+            })
+        })
+        .collect()
+}
+
+/// Pages come keyed by page ID; `index` is the search rank
+fn wikimedia(body: &Value) -> Vec<ImageResult> {
+    let mut pages: Vec<&Value> = body["query"]["pages"].as_object().into_iter().flat_map(|p| p.values()).collect();
+    pages.sort_by_key(|page| page["index"].as_u64().unwrap_or(u64::MAX));
+    pages.into_iter()
+        .filter_map(|page| {
+            let info = &page["imageinfo"][0];
+            // Search also finds PDFs, videos and audio in the File: namespace
+            if !info["mime"].as_str().unwrap_or("").starts_with("image/") {
+                return None;
+            }
+            let meta = &info["extmetadata"];
+            let title = page["title"].as_str().unwrap_or("");
+            Some(ImageResult {
+                title: title.trim_start_matches("File:").to_string(),
+                url: info["url"].as_str()?.to_string(),
+                thumbnail: text(&info["thumburl"]),
+                width: info["width"].as_u64(),
+                height: info["height"].as_u64(),
+                license: text(&meta["LicenseShortName"]["value"]),
+                license_url: text(&meta["LicenseUrl"]["value"]),
+                creator: text(&meta["Artist"]["value"]),
+                page_url: text(&info["descriptionurl"]),
+                source: ImageSource::Wikimedia.name(),
+            })
+        })
+        .collect()
+}
+
+fn bing(body: &Value) -> Vec<ImageResult> {
+    body["value"].as_array().into_iter().flatten()
+        .filter_map(|item| Some(ImageResult {
+            title: text(&item["name"]).unwrap_or_else(|| "Untitled".to_string()),
+            url: item["contentUrl"].as_str()?.to_string(),
+            thumbnail: text(&item["thumbnailUrl"]),
+            width: item["width"].as_u64(),
+            height: item["height"].as_u64(),
+            license: None,
+            license_url: None,
+            creator: None,
+            page_url: text(&item["hostPageUrl"]),
+            source: ImageSource::Bing.name(),
+        }))
+        .collect()
+}
+
+/// Normalize a source's JSON response
+pub fn parse(source: ImageSource, body: &Value) -> Vec<ImageResult> {
+    match source {
+        ImageSource::Openverse => openverse(body),
+        ImageSource::Wikimedia => wikimedia(body),
+        ImageSource::Bing => bing(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sources_normalize() {
+        let openverse = json!({"results": [{
+            "title": "Red fox", "url": "https://live.staticflickr.com/fox.jpg", "width": 1024, "height": 683,
+            "license": "by-sa", "license_version": "2.0", "license_url": "https://creativecommons.org/licenses/by-sa/2.0/",
+            "creator": "jdoe", "foreign_landing_url": "https://www.flickr.com/photos/jdoe/1"
+        }]});
+        let fox = &parse(ImageSource::Openverse, &openverse)[0];
+        assert_eq!(fox.license.as_deref(), Some("CC BY-SA 2.0"));
+        assert_eq!((fox.width, fox.height), (Some(1024), Some(683)));
+
+        let commons = json!({"query": {"pages": {
+            "7": {"title": "File:Fox.pdf", "index": 1, "imageinfo": [{"url": "https://upload.wikimedia.org/Fox.pdf", "mime": "application/pdf"}]},
+            "9": {"title": "File:Vulpes vulpes.jpg", "index": 2, "imageinfo": [{
+                "url": "https://upload.wikimedia.org/wikipedia/commons/a/a1/Vulpes_vulpes.jpg", "mime": "image/jpeg",
+                "width": 3000, "height": 2000,
+                "extmetadata": {"LicenseShortName": {"value": "CC BY 4.0"}, "Artist": {"value": "<a href=\"/wiki/User:X\">X</a>"}}
+            }]},
+            "3": {"title": "File:Fox den.png", "index": 3, "imageinfo": [{"url": "https://upload.wikimedia.org/Fox_den.png", "mime": "image/png"}]}
+        }}});
+        let images = parse(ImageSource::Wikimedia, &commons);
+        let titles: Vec<&str> = images.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, ["Vulpes vulpes.jpg", "Fox den.png"], "ranked, without the PDF");
+        assert_eq!(images[0].creator.as_deref(), Some("X"));
+
+        let bing = json!({"value": [{"name": "Fox", "contentUrl": "https://example.com/fox.png", "width": 800, "height": 600}]});
+        assert_eq!(parse(ImageSource::Bing, &bing)[0].license, None);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(ImagesConfig::default().validate().is_ok());
+        let bing = ImagesConfig { sources: vec![ImageSource::Bing], ..Default::default() };
+        assert!(bing.validate().unwrap_err().contains("bing_key"));
+        assert!(ImagesConfig { sources: vec![], ..Default::default() }.validate().is_err());
+    }
+}
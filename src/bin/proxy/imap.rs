@@ -0,0 +1,586 @@
+//! Email inbox on IMAP, answered through `[email]`
+//!
+//! With `[imap]` set, the proxy checks the mailbox every `poll_secs` and
+//! relays unread mail from allowed senders as `message` events on `/ws`,
+//! with the sender's recent turns, then marks it read:
+//!
+//! ```json
+//! {"channel": "email", "type": "message", "data": {"id": "<CAF1x@mail.example.com>",
+//!   "from": "ann@example.com", "name": "Ann", "subject": "Invoice 42", "date": "2026-10-16T09:30:00Z",
+//!   "text": "...", "attachments": ["invoice.pdf"], "history": [{"role": "user", "content": "..."}]}}
+//! ```
+//!
+//! The browser answers with POST /channel/email/reply `{"reply_to",
+//! "text", "html"}`, sent through the SMTP server in `[email]` as a reply
+//! in the same thread, to the sender only. Mail stays unread while no
+//! browser is connected and is picked up by the next check after one
+//! connects. Mail older than `max_age_hours` (a backlog from before the
+//! bot ran) and automatic mail (bounces, vacation notices, mailing lists)
+//! are left alone, so two responders cannot keep each other busy.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart, SinglePart};
+use lettre::Message;
+use mail_parser::{MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::email::{listed, Security};
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Hub};
+use crate::telegram::Turn;
+
+/// For connecting and for each command's answer
+const TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(900);
+/// Fetched per message; the rest of a larger one (attachments, mostly) is cut
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+/// Of the body relayed to the browser
+const MAX_TEXT_CHARS: usize = 20_000;
+/// Messages remembered so their replies can be threaded
+const PENDING_MESSAGES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImapConfig {
+    /// IMAP server; the inbox is off without one
+    pub host: Option<String>,
+    /// Defaults to 993 with `tls`, 143 otherwise
+    pub port: Option<u16>,
+    pub security: Security,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub mailbox: String,
+    /// Addresses (or `@domain`s) whose mail is answered; empty answers anyone
+    pub allowed_senders: Vec<String>,
+    pub poll_secs: u64,
+    /// Unread mail older than this is not answered
+    pub max_age_hours: u64,
+    /// Messages per sender kept as context
+    pub history: usize,
+}
+
+impl Default for ImapConfig {
+    fn default() -> Self {
+        ImapConfig {
+            host: None,
+            port: None,
+            security: Security::Tls,
+            username: None,
+            password: None,
+            mailbox: "INBOX".to_string(),
+            allowed_senders: Vec::new(),
+            poll_secs: 60,
+            max_age_hours: 24,
+            history: 20,
+        }
+    }
+}
+
+impl ImapConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.is_none() {
+            return Ok(());
+        }
+        if self.username.is_none() || self.password.is_none() {
+            return Err("imap needs username and password".to_string());
+        }
+        if self.mailbox.trim().is_empty() {
+            return Err("imap mailbox must not be empty".to_string());
+        }
+        if self.poll_secs < 10 {
+            return Err("imap poll_secs must be at least 10".to_string());
+        }
+        Ok(())
+    }
+
+    fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.security {
+            Security::Tls => 993,
+            Security::StartTls | Security::None => 143,
+        })
+    }
+}
+
+/// A message for the bot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    /// The Message-ID, or one made up from the UID without it
+    pub id: String,
+    pub from: String,
+    pub name: String,
+    pub subject: String,
+    pub date: Option<String>,
+    pub text: String,
+    pub attachments: Vec<String>,
+    /// Where replies go: Reply-To, else From
+    #[serde(skip)]
+    pub reply_to: String,
+    #[serde(skip)]
+    pub references: Vec<String>,
+    #[serde(skip)]
+    pub timestamp: Option<i64>,
+    /// Sent by a program rather than a person
+    #[serde(skip)]
+    pub automatic: bool,
+}
+
+/// A raw message as the bot sees it; `fallback_id` stands in for a
+/// missing Message-ID
+pub fn parse(raw: &[u8], fallback_id: &str) -> Option<Incoming> {
+    let message = MessageParser::default().parse(raw)?;
+    let sender = message.from()?.first()?;
+    let from = sender.address()?.trim().to_lowercase();
+    let reply_to = message.reply_to().and_then(|a| a.first()).and_then(|a| a.address()).map_or(from.clone(), str::to_lowercase);
+    let header = |name: &str| message.header_raw(name).map(|v| v.trim().to_lowercase());
+    let local = from.split('@').next().unwrap_or("");
+    let automatic = header("Auto-Submitted").is_some_and(|v| v != "no")
+        || header("Precedence").is_some_and(|v| matches!(v.as_str(), "bulk" | "junk" | "list"))
+        || header("List-Id").is_some()
+        || header("Return-Path").is_some_and(|v| v == "<>")
+        || ["mailer-daemon", "postmaster", "noreply", "no-reply", "donotreply", "do-not-reply"].contains(&local);
+    let mut text: String = message.body_text(0).unwrap_or_default().trim().to_string();
+    if let Some((cut, _)) = text.char_indices().nth(MAX_TEXT_CHARS) {
+        text.truncate(cut);
+    }
+    let id = message.message_id().map_or(fallback_id.to_string(), |id| format!("<{}>", id));
+    let references = message.references().as_text_list().unwrap_or_default().into_iter().map(|r| format!("<{}>", r)).collect();
+    Some(Incoming {
+        name: sender.name().filter(|n| !n.trim().is_empty()).unwrap_or(&from).to_string(),
+        subject: message.subject().unwrap_or("").trim().to_string(),
+        date: message.date().map(|d| d.to_rfc3339()),
+        timestamp: message.date().map(|d| d.to_timestamp()),
+        attachments: message.attachments().filter_map(|part| part.attachment_name().map(str::to_string)).collect(),
+        id,
+        from,
+        reply_to,
+        references,
+        text,
+        automatic,
+    })
+}
+
+/// The subject of a reply to `subject`
+fn re(subject: &str) -> String {
+    match subject.get(..3).is_some_and(|re| re.eq_ignore_ascii_case("re:")) {
+        true => subject.to_string(),
+        false => format!("Re: {}", subject),
+    }
+}
+
+/// `Auto-Submitted: auto-replied` (RFC 3834), which other responders heed
+#[derive(Debug, Clone)]
+struct AutoReplied;
+
+impl Header for AutoReplied {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("Auto-Submitted")
+    }
+
+    fn parse(_: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(AutoReplied)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "auto-replied".to_string())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplyRequest {
+    /// The message's `id`
+    pub reply_to: String,
+    pub text: Option<String>,
+    pub html: Option<String>,
+}
+
+/// What a reply needs from the message it answers
+#[derive(Debug, Clone)]
+struct Pending {
+    id: String,
+    from: String,
+    reply_to: String,
+    subject: String,
+    references: Vec<String>,
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// An untagged response, with the literals it carried
+struct Response {
+    line: String,
+    literals: Vec<Vec<u8>>,
+}
+
+/// The size of the literal announced at the end of `line`, `{123}`
+fn literal_size(line: &[u8]) -> Option<usize> {
+    let line = std::str::from_utf8(line).ok()?.trim_end();
+    let size = line.strip_suffix('}')?.rsplit_once('{')?.1;
+    size.parse().ok()
+}
+
+/// A string argument, quoted
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The number after `key` in a response code, e.g. `[UIDVALIDITY 3857529045]`
+fn code(line: &str, key: &str) -> Option<u32> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.trim_start().split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+/// One logged-in IMAP connection
+struct Session {
+    stream: BufReader<Box<dyn Io>>,
+    tag: u32,
+}
+
+impl Session {
+    async fn connect(config: &ImapConfig) -> Result<Self, String> {
+        let host = config.host.as_deref().unwrap_or("");
+        let tcp = tokio::time::timeout(TIMEOUT, TcpStream::connect((host, config.port())))
+            .await
+            .map_err(|_| format!("connection to {}:{} timed out", host, config.port()))?
+            .map_err(|e| format!("connection to {}:{} failed: {}", host, config.port(), e))?;
+        let stream: Box<dyn Io> = match config.security {
+            Security::Tls => Box::new(tls(host, Box::new(tcp)).await?),
+            Security::StartTls | Security::None => Box::new(tcp),
+        };
+        let mut session = Session { stream: BufReader::new(stream), tag: 0 };
+        let greeting = tokio::time::timeout(TIMEOUT, session.read()).await.map_err(|_| "no greeting from the server")??;
+        if !greeting.line.starts_with("* OK") && !greeting.line.starts_with("* PREAUTH") {
+            return Err(format!("refused: {}", greeting.line));
+        }
+        if config.security == Security::StartTls {
+            session.command("STARTTLS").await?;
+            // Nothing more comes before the handshake, so the buffer is empty
+            let Session { stream, tag } = session;
+            session = Session { stream: BufReader::new(Box::new(tls(host, stream.into_inner()).await?)), tag };
+        }
+        let (username, password) = (config.username.as_deref().unwrap_or(""), config.password.as_deref().unwrap_or(""));
+        session.command(&format!("LOGIN {} {}", quote(username), quote(password))).await
+            .map_err(|e| format!("login refused: {}", e))?;
+        Ok(session)
+    }
+
+    /// One response line, with any literals in it
+    async fn read(&mut self) -> Result<Response, String> {
+        let mut line = Vec::new();
+        let mut literals = Vec::new();
+        loop {
+            let start = line.len();
+            if self.stream.read_until(b'\n', &mut line).await.map_err(|e| e.to_string())? == 0 {
+                return Err("connection closed".to_string());
+            }
+            match literal_size(&line[start..]) {
+                Some(size) if size <= MAX_MESSAGE_BYTES + 4096 => {
+                    let mut literal = vec![0; size];
+                    self.stream.read_exact(&mut literal).await.map_err(|e| e.to_string())?;
+                    literals.push(literal);
+                }
+                Some(size) => return Err(format!("a {} byte literal is too large", size)),
+                None => break,
+            }
+        }
+        Ok(Response { line: String::from_utf8_lossy(&line).trim_end().to_string(), literals })
+    }
+
+    /// Send `command` and collect the untagged responses until its own;
+    /// an error is the server's reason
+    async fn command(&mut self, command: &str) -> Result<Vec<Response>, String> {
+        self.tag += 1;
+        let tag = format!("a{} ", self.tag);
+        let exchange = async {
+            self.stream.get_mut().write_all(format!("{}{}\r\n", tag, command).as_bytes()).await.map_err(|e| e.to_string())?;
+            self.stream.get_mut().flush().await.map_err(|e| e.to_string())?;
+            let mut responses = Vec::new();
+            loop {
+                let response = self.read().await?;
+                match response.line.strip_prefix(&tag) {
+                    Some(status) if status.starts_with("OK") => return Ok(responses),
+                    Some(status) => return Err(status.to_string()),
+                    None => responses.push(response),
+                }
+            }
+        };
+        let verb = command.split(' ').take(2).collect::<Vec<_>>().join(" ");
+        tokio::time::timeout(TIMEOUT, exchange).await.map_err(|_| format!("{} timed out", verb))?
+    }
+}
+
+async fn tls(host: &str, stream: Box<dyn Io>) -> Result<tokio_native_tls::TlsStream<Box<dyn Io>>, String> {
+    let connector = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    let connector = tokio_native_tls::TlsConnector::from(connector);
+    tokio::time::timeout(TIMEOUT, connector.connect(host, stream))
+        .await
+        .map_err(|_| "TLS handshake timed out".to_string())?
+        .map_err(|e| format!("TLS: {}", e))
+}
+
+/// The mailbox's settings, the recent turns with every sender, and the
+/// messages waiting for an answer
+pub struct Inbox {
+    pub config: ImapConfig,
+    chats: Mutex<HashMap<String, VecDeque<Turn>>>,
+    pending: Mutex<VecDeque<Pending>>,
+    /// The mailbox's UIDVALIDITY and the highest UID looked at in it
+    checked: Mutex<Option<(u32, u32)>>,
+}
+
+impl Inbox {
+    pub fn new(config: &ImapConfig) -> Self {
+        Inbox {
+            config: config.clone(),
+            chats: Mutex::new(HashMap::new()),
+            pending: Mutex::new(VecDeque::new()),
+            checked: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.host.is_some()
+    }
+
+    pub fn allows(&self, sender: &str) -> bool {
+        listed(&self.config.allowed_senders, sender)
+    }
+
+    pub fn history(&self, sender: &str) -> Vec<Turn> {
+        self.chats.lock().unwrap().get(sender).map(|turns| turns.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn remember(&self, sender: &str, role: &'static str, content: &str) {
+        let mut chats = self.chats.lock().unwrap();
+        let turns = chats.entry(sender.to_string()).or_default();
+        turns.push_back(Turn { role, content: content.to_string() });
+        while turns.len() > self.config.history {
+            turns.pop_front();
+        }
+    }
+
+    /// The sender of the message `id`
+    pub fn author(&self, id: &str) -> Option<String> {
+        self.pending.lock().unwrap().iter().find(|p| p.id == id).map(|p| p.from.clone())
+    }
+
+    /// The reply `req` asks for, from `sender` (the `from` in `[email]`),
+    /// and the address it goes to
+    pub fn reply(&self, sender: &str, req: &ReplyRequest) -> Result<(Message, String), String> {
+        let pending = self.pending.lock().unwrap().iter().find(|p| p.id == req.reply_to).cloned()
+            .ok_or_else(|| format!("No recent message {}", req.reply_to))?;
+        let from: Mailbox = sender.parse().map_err(|e| format!("Invalid sender: {}", e))?;
+        let to: Mailbox = pending.reply_to.parse().map_err(|e| format!("Invalid address {:?}: {}", pending.reply_to, e))?;
+        let mut builder = Message::builder().from(from).to(to).subject(re(&pending.subject)).header(AutoReplied);
+        // A made-up id is not in the sender's mailbox to thread under
+        if pending.id.starts_with('<') {
+            let mut references = pending.references.clone();
+            references.push(pending.id.clone());
+            builder = builder.in_reply_to(pending.id.clone()).references(references.join(" "));
+        }
+        let message = match (&req.text, &req.html) {
+            (Some(text), Some(html)) => builder.multipart(MultiPart::alternative_plain_html(text.clone(), html.clone())),
+            (Some(text), None) => builder.singlepart(SinglePart::plain(text.clone())),
+            (None, Some(html)) => builder.singlepart(SinglePart::html(html.clone())),
+            (None, None) => return Err("Missing 'text' or 'html'".to_string()),
+        };
+        Ok((message.map_err(|e| e.to_string())?, pending.reply_to))
+    }
+
+    /// Relay one message to the browsers on /ws
+    fn handle(&self, message: Incoming, hub: &Hub) {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push_back(Pending {
+                id: message.id.clone(),
+                from: message.from.clone(),
+                reply_to: message.reply_to.clone(),
+                subject: message.subject.clone(),
+                references: message.references.clone(),
+            });
+            if pending.len() > PENDING_MESSAGES {
+                pending.pop_front();
+            }
+        }
+        eprintln!("→ Email from {}: {}", message.from, message.subject);
+        let mut data = json!(message);
+        data["history"] = json!(self.history(&message.from));
+        self.remember(&message.from, "user", &format!("Subject: {}\n\n{}", message.subject, message.text));
+        hub.relay(Channel::Email, "message", data);
+    }
+
+    /// Relay the unread mail that came since the last check, if a browser
+    /// is there to answer it
+    async fn check(&self, session: &mut Session, hub: &Hub) -> Result<(), String> {
+        let selected = session.command(&format!("SELECT {}", quote(&self.config.mailbox))).await?;
+        let validity = selected.iter().find_map(|r| code(&r.line, "[UIDVALIDITY")).unwrap_or(0);
+        let last = match *self.checked.lock().unwrap() {
+            Some((v, last)) if v == validity => last,
+            _ => 0,
+        };
+        if hub.listeners(Channel::Email) == 0 {
+            return Ok(());
+        }
+        // `n:*` always matches the newest message, even below n
+        let found = session.command(&format!("UID SEARCH UID {}:* UNSEEN", last + 1)).await?;
+        let mut uids: Vec<u32> = found.iter()
+            .filter_map(|r| r.line.strip_prefix("* SEARCH"))
+            .flat_map(|rest| rest.split_whitespace().filter_map(|uid| uid.parse().ok()))
+            .filter(|uid| *uid > last)
+            .collect();
+        uids.sort_unstable();
+        let now = chrono::Utc::now().timestamp();
+        let max_age = self.config.max_age_hours.saturating_mul(3600) as i64;
+        let host = self.config.host.as_deref().unwrap_or("");
+        for uid in uids {
+            if hub.listeners(Channel::Email) == 0 {
+                break;
+            }
+            let fetched = session.command(&format!("UID FETCH {} (BODY.PEEK[]<0.{}>)", uid, MAX_MESSAGE_BYTES)).await?;
+            *self.checked.lock().unwrap() = Some((validity, uid));
+            let raw = fetched.iter().find(|r| r.line.contains("FETCH")).and_then(|r| r.literals.first());
+            let Some(message) = raw.and_then(|raw| parse(raw, &format!("{}.{}@{}", uid, validity, host))) else {
+                eprintln!("⚠️  Email: could not read message {}", uid);
+                continue;
+            };
+            if message.timestamp.is_some_and(|at| now - at > max_age) {
+                eprintln!("⏭️  Email: left mail from {} unread (older than {}h)", message.from, self.config.max_age_hours);
+                continue;
+            }
+            if message.automatic {
+                eprintln!("⏭️  Email: left automatic mail from {} unread", message.from);
+                continue;
+            }
+            if !self.allows(&message.from) {
+                eprintln!("⛔ Email: left mail from {} unread (not in allowed_senders)", message.from);
+                continue;
+            }
+            self.handle(message, hub);
+            session.command(&format!("UID STORE {} +FLAGS.SILENT (\\Seen)", uid)).await?;
+        }
+        Ok(())
+    }
+
+    async fn check_once(&self, hub: &Hub) -> Result<(), String> {
+        let mut session = Session::connect(&self.config).await?;
+        let result = self.check(&mut session, hub).await;
+        let _ = session.command("LOGOUT").await;
+        result
+    }
+
+    /// Check the mailbox for the life of the process
+    pub async fn run(self: Arc<Self>, hub: Arc<Hub>, metrics: Arc<Metrics>) {
+        let interval = Duration::from_secs(self.config.poll_secs);
+        let mut backoff = interval;
+        let mut connected = false;
+        loop {
+            match metrics.upstream("imap:check", self.check_once(&hub)).await {
+                Ok(()) => {
+                    if !connected {
+                        println!("   Email inbox: {} on {}", self.config.mailbox, self.config.host.as_deref().unwrap_or(""));
+                        connected = true;
+                    }
+                    hub.set_status(Channel::Email, true, None);
+                    backoff = interval;
+                }
+                Err(e) => {
+                    eprintln!("⚠️  Email inbox: {}; retrying in {}s", e, backoff.as_secs());
+                    hub.set_status(Channel::Email, false, Some(e));
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &str = "From: Ann Example <Ann@Example.com>\r\n\
+        To: bot@example.org\r\n\
+        Subject: =?UTF-8?Q?Invoice_42_=E2=80=93_due?=\r\n\
+        Date: Fri, 16 Oct 2026 09:30:00 +0000\r\n\
+        Message-ID: <CAF1x@mail.example.com>\r\n\
+        References: <a@x> <b@x>\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+        \r\n\
+        --b\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        \r\n\
+        Please check the attached invoice.\r\n\
+        --b\r\n\
+        Content-Type: application/pdf; name=\"invoice.pdf\"\r\n\
+        Content-Disposition: attachment; filename=\"invoice.pdf\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        JVBERi0=\r\n\
+        --b--\r\n";
+
+    #[test]
+    fn test_parse() {
+        let message = parse(MAIL.as_bytes(), "1.1@imap").unwrap();
+        assert_eq!((message.from.as_str(), message.name.as_str()), ("ann@example.com", "Ann Example"));
+        assert_eq!(message.subject, "Invoice 42 – due");
+        assert_eq!((message.id.as_str(), message.references.len()), ("<CAF1x@mail.example.com>", 2));
+        assert_eq!(message.text, "Please check the attached invoice.");
+        assert_eq!(message.attachments, ["invoice.pdf"]);
+        assert_eq!(message.date.as_deref(), Some("2026-10-16T09:30:00Z"));
+        assert!(!message.automatic);
+
+        let vacation = format!("Auto-Submitted: auto-replied\r\n{}", MAIL);
+        assert!(parse(vacation.as_bytes(), "").unwrap().automatic);
+        let list = MAIL.replace("To: bot@example.org", "List-Id: <dev.lists.example.com>");
+        assert!(parse(list.as_bytes(), "").unwrap().automatic);
+        let bare = "From: mailer-daemon@example.com\r\nSubject: Undelivered\r\n\r\nSorry";
+        let bounce = parse(bare.as_bytes(), "7.1@imap").unwrap();
+        assert!(bounce.automatic && bounce.id == "7.1@imap");
+    }
+
+    #[test]
+    fn test_protocol_helpers() {
+        assert_eq!(literal_size(b"* 3 FETCH (UID 9 BODY[]<0> {1234}\r\n"), Some(1234));
+        assert_eq!(literal_size(b"* 3 FETCH (FLAGS (\\Seen))\r\n"), None);
+        assert_eq!(quote("p\"a\\ss"), "\"p\\\"a\\\\ss\"");
+        assert_eq!(code("* OK [UIDVALIDITY 3857529045] UIDs valid", "[UIDVALIDITY"), Some(3857529045));
+    }
+
+    #[test]
+    fn test_reply_and_config() {
+        let config = ImapConfig {
+            host: Some("imap.example.org".to_string()),
+            username: Some("bot@example.org".to_string()),
+            password: Some("p".to_string()),
+            allowed_senders: vec!["@example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok() && config.port() == 993);
+        assert!(ImapConfig { password: None, ..config.clone() }.validate().is_err());
+        let inbox = Inbox::new(&config);
+        assert!(inbox.allows("ann@example.com") && !inbox.allows("eve@example.net"));
+
+        let hub = Hub::new(&Default::default(), &[Channel::Email]);
+        inbox.handle(parse(MAIL.as_bytes(), "").unwrap(), &hub);
+        assert_eq!(inbox.author("<CAF1x@mail.example.com>").as_deref(), Some("ann@example.com"));
+        assert_eq!(inbox.history("ann@example.com").len(), 1);
+        let req = ReplyRequest { reply_to: "<CAF1x@mail.example.com>".to_string(), text: Some("Paid.".to_string()), html: None };
+        let (reply, to) = inbox.reply("claWasm <bot@example.org>", &req).unwrap();
+        let raw = String::from_utf8(reply.formatted()).unwrap();
+        assert_eq!(to, "ann@example.com");
+        assert!(raw.contains("In-Reply-To: <CAF1x@mail.example.com>") && raw.contains("References: <a@x> <b@x> <CAF1x@mail.example.com>"));
+        assert!(raw.contains("Auto-Submitted: auto-replied"));
+        assert_eq!((re("Invoice 42").as_str(), re("RE: Invoice 42").as_str()), ("Re: Invoice 42", "RE: Invoice 42"));
+        assert!(inbox.reply("bot@example.org", &ReplyRequest { reply_to: "<other@x>".to_string(), ..req }).unwrap_err().contains("No recent"));
+    }
+}
@@ -0,0 +1,159 @@
+//! Queued channel messages behind GET /inbox (long-polling)
+//!
+//! `/ws` only reaches browsers connected when a message arrives. The inbox
+//! keeps the messages the bots hand to the agent (Telegram and WhatsApp
+//! messages, Discord and Slack mentions and commands, button presses,
+//! Mastodon and Bluesky mentions, mail) for `inbox_secs`, and
+//! `GET /inbox?channels=telegram,slack&wait=25` takes them:
+//!
+//! ```json
+//! {"events": [{"channel": "telegram", "type": "message", "data": {...}}]}
+//! ```
+//!
+//! The data is what `/ws` carries. When nothing is queued the request waits
+//! up to `wait` seconds (at most 60) for a message. Each message goes to one
+//! poller, so several tabs share the work instead of all answering. A
+//! browser counts as listening for `inbox_secs` after its last poll; the
+//! bots only send their offline replies once no browser has polled for
+//! that long and none is connected to `/ws`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::realtime::{Channel, Event};
+
+/// Longest a poll may wait
+pub const MAX_WAIT: Duration = Duration::from_secs(60);
+
+pub struct Inbox {
+    /// How long messages wait, and pollers count as listening
+    ttl: Duration,
+    capacity: usize,
+    queue: Mutex<VecDeque<(Instant, Arc<Event>)>>,
+    /// Last poll per channel
+    polled: Mutex<BTreeMap<Channel, Instant>>,
+    arrived: tokio::sync::Notify,
+}
+
+impl Inbox {
+    /// A `ttl` of zero turns the inbox off
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Inbox {
+            ttl,
+            capacity: capacity.max(1),
+            queue: Mutex::new(VecDeque::new()),
+            polled: Mutex::new(BTreeMap::new()),
+            arrived: tokio::sync::Notify::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Queue a message, dropping the oldest past `capacity`
+    pub fn push(&self, event: Arc<Event>) {
+        if !self.enabled() {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        self.expire(&mut queue);
+        if queue.len() >= self.capacity {
+            if let Some((_, dropped)) = queue.pop_front() {
+                eprintln!("⚠️  Inbox full: dropped a {} {}", dropped.channel, dropped.kind);
+            }
+        }
+        queue.push_back((Instant::now(), event));
+        drop(queue);
+        self.arrived.notify_waiters();
+    }
+
+    fn expire(&self, queue: &mut VecDeque<(Instant, Arc<Event>)>) {
+        while queue.front().is_some_and(|(at, _)| at.elapsed() >= self.ttl) {
+            queue.pop_front();
+        }
+    }
+
+    /// Whether a browser polled for `channel` within `inbox_secs`
+    pub fn polling(&self, channel: Channel) -> bool {
+        self.polled.lock().unwrap().get(&channel).is_some_and(|at| at.elapsed() < self.ttl)
+    }
+
+    fn touch(&self, channels: &[Channel]) {
+        let mut polled = self.polled.lock().unwrap();
+        for channel in channels {
+            polled.insert(*channel, Instant::now());
+        }
+    }
+
+    /// Remove and return the queued messages of `channels`
+    fn take(&self, channels: &[Channel]) -> Vec<Arc<Event>> {
+        let mut queue = self.queue.lock().unwrap();
+        self.expire(&mut queue);
+        let mut taken = Vec::new();
+        queue.retain(|(_, event)| {
+            let wanted = channels.iter().any(|c| c.name() == event.channel);
+            if wanted {
+                taken.push(event.clone());
+            }
+            !wanted
+        });
+        taken
+    }
+
+    /// The queued messages of `channels`, waiting up to `wait` for one
+    pub async fn poll(&self, channels: &[Channel], wait: Duration) -> Vec<Arc<Event>> {
+        let deadline = tokio::time::Instant::now() + wait.min(MAX_WAIT);
+        self.touch(channels);
+        loop {
+            // Registered before looking, so a message pushed in between wakes it
+            let arrived = self.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+            let taken = self.take(channels);
+            if !taken.is_empty() || tokio::time::Instant::now() >= deadline {
+                self.touch(channels);
+                return taken;
+            }
+            let _ = tokio::time::timeout_at(deadline, arrived).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(channel: Channel, text: &str) -> Arc<Event> {
+        Arc::new(Event { channel: channel.name(), kind: "message".to_string(), data: json!({ "text": text }) })
+    }
+
+    #[tokio::test]
+    async fn test_poll() {
+        let inbox = Arc::new(Inbox::new(Duration::from_secs(300), 2));
+        assert!(!inbox.polling(Channel::Telegram));
+        for text in ["one", "two", "three"] {
+            inbox.push(event(Channel::Telegram, text));
+        }
+        inbox.push(event(Channel::Slack, "hi"));
+
+        let taken = inbox.poll(&[Channel::Telegram], Duration::ZERO).await;
+        assert_eq!(taken.iter().map(|e| e.data["text"].as_str().unwrap()).collect::<Vec<_>>(), ["three"], "the oldest went past capacity");
+        assert!(inbox.polling(Channel::Telegram) && !inbox.polling(Channel::Slack));
+        assert!(inbox.poll(&[Channel::Telegram], Duration::ZERO).await.is_empty(), "each message goes to one poller");
+
+        let waiting = tokio::spawn({
+            let inbox = inbox.clone();
+            async move { inbox.poll(&[Channel::Discord], Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        inbox.push(event(Channel::Discord, "late"));
+        assert_eq!(waiting.await.unwrap()[0].data["text"], "late");
+
+        let off = Inbox::new(Duration::ZERO, 10);
+        off.push(event(Channel::Slack, "hi"));
+        assert!(off.take(&[Channel::Slack]).is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+//! Rate limits and a concurrency cap
+//!
+//! A tool loop that goes wrong can fire hundreds of requests a minute;
+//! without limits the proxy would pass every one to the target site and
+//! get the user's IP banned. Each client (by peer address) and each target
+//! host gets a token bucket refilled at a steady rate per minute, and at
+//! most `max_concurrent` requests are handled at once; the rest wait for a
+//! slot. Over-limit requests get a 429 with `Retry-After`. Configured in
+//! the `[limits]` section (see `config`).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::HttpResponse;
+use tokio::sync::Semaphore;
+
+use crate::config::LimitsConfig;
+
+/// How long a request waits for a concurrency slot before giving up
+const SLOT_WAIT: Duration = Duration::from_secs(30);
+
+/// Buckets untouched this long are full again and can be dropped
+const IDLE: Duration = Duration::from_secs(600);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets keyed by client or host; a full bucket allows a burst of
+/// one minute's worth of requests
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        RateLimiter { per_minute, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.per_minute > 0
+    }
+
+    pub fn per_minute(&self) -> u32 {
+        self.per_minute
+    }
+
+    /// Take a token for `key`, or say how long until one is available
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let capacity = self.per_minute as f64;
+        let per_sec = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > 10_000 {
+            buckets.retain(|_, b| now.saturating_duration_since(b.updated) < IDLE);
+        }
+        let bucket = buckets.entry(key.to_string())
+            .or_insert(Bucket { tokens: capacity, updated: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+pub struct Limits {
+    pub per_client: RateLimiter,
+    pub per_host: RateLimiter,
+    pub max_concurrent: usize,
+    slots: Semaphore,
+}
+
+impl Limits {
+    pub fn new(config: &LimitsConfig) -> Self {
+        Limits {
+            per_client: RateLimiter::new(config.client_rate),
+            per_host: RateLimiter::new(config.host_rate),
+            max_concurrent: config.max_concurrent,
+            slots: Semaphore::new(config.max_concurrent.max(1)),
+        }
+    }
+
+    /// Take a token for an upstream request to `host`, answering 429 when
+    /// the host's budget is spent
+    pub fn check_host(&self, host: &str) -> Result<(), Box<HttpResponse>> {
+        self.per_host.check(&host.to_lowercase())
+            .map_err(|wait| Box::new(too_many_requests(&format!("target host {}", host), wait)))
+    }
+}
+
+/// Host part of a target URL, for the per-host limit
+pub fn host_of(url: &str) -> String {
+    url::Url::parse(url).ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn too_many_requests(what: &str, wait: Duration) -> HttpResponse {
+    let secs = wait.as_secs_f64().ceil().max(1.0) as u64;
+    eprintln!("🐢 Rate limit for {}: retry in {}s", what, secs);
+    HttpResponse::TooManyRequests()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .insert_header(("Access-Control-Expose-Headers", "Retry-After"))
+        .insert_header(("Retry-After", secs.to_string()))
+        .body(format!("Rate limit exceeded for {}; retry in {}s", what, secs))
+}
+
+/// Per-client limit and concurrency cap for every route but /health and
+/// /metrics
+pub async fn limit_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(limits) = req.app_data::<actix_web::web::Data<Limits>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if matches!(req.path(), "/health" | "/metrics") {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let client = req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default();
+    if let Err(wait) = limits.per_client.check(&client) {
+        let response = too_many_requests(&format!("client {}", client), wait);
+        return Ok(req.into_response(response));
+    }
+
+    if limits.max_concurrent == 0 {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let _slot = match tokio::time::timeout(SLOT_WAIT, limits.slots.acquire()).await {
+        Ok(Ok(slot)) => slot,
+        _ => {
+            eprintln!("🐢 All {} proxy slots busy for {}s", limits.max_concurrent, SLOT_WAIT.as_secs());
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .insert_header(("Retry-After", "5"))
+                .body("Proxy is busy; too many requests in flight");
+            return Ok(req.into_response(response));
+        }
+    };
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+        for _ in 0..60 {
+            assert!(limiter.check_at("127.0.0.1", start).is_ok());
+        }
+        let wait = limiter.check_at("127.0.0.1", start).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+        assert!(limiter.check_at("10.0.0.2", start).is_ok(), "clients have separate buckets");
+
+        // One token per second comes back
+        assert!(limiter.check_at("127.0.0.1", start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check_at("127.0.0.1", start + Duration::from_secs(1)).is_err());
+
+        let off = RateLimiter::new(0);
+        assert!((0..1000).all(|_| off.check("x").is_ok()));
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("https://API.example.com:8443/v1?q=1"), "api.example.com");
+        assert_eq!(host_of("not a url"), "");
+    }
+}
@@ -0,0 +1,2432 @@
+//! claWasm CORS Proxy Server
+//! 
+//! Usage: cargo run --bin proxy --features proxy [-- --config proxy.toml --port 3000]
+//! 
+//! This proxy bypasses CORS restrictions by acting as a middleman
+//! between the browser and external APIs.
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_cors::Cors;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use base64::Engine;
+
+mod auth;
+mod bluesky;
+mod cache;
+mod channels;
+mod config;
+mod discord;
+mod email;
+mod https;
+mod images;
+mod imap;
+mod inbox;
+mod limits;
+mod mastodon;
+mod metrics;
+mod osv;
+mod paste;
+mod realtime;
+#[cfg(feature = "render")]
+mod render;
+mod rss;
+mod search;
+mod sessions;
+mod slack;
+mod ssrf;
+mod telegram;
+mod tls;
+mod transcribe;
+mod tts;
+mod upstream;
+mod whatsapp;
+mod youtube;
+
+use auth::ProxyToken;
+use cache::{CachedResponse, ResponseCache};
+use clap::Parser;
+use config::ProxyConfig;
+use images::{ImagesConfig, ImagesResponse};
+use limits::Limits;
+use metrics::Metrics;
+use search::{SearchConfig, SearchResponse};
+use ssrf::TargetPolicy;
+use transcribe::TranscribeConfig;
+use tts::{TtsConfig, TtsRequest};
+use upstream::Upstream;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProxyRequest {
+    url: String,
+    #[serde(default)]
+    method: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+    /// "base64" wraps the response in JSON: {status, content_type, size, body}
+    /// with the body base64-encoded, for callers that can only read text
+    #[serde(default)]
+    response_format: Option<String>,
+}
+
+/// Whether a Content-Type carries text; everything else is passed on as bytes
+fn is_text_content(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("/json")
+        || essence.ends_with("+json")
+        || essence.ends_with("/xml")
+        || essence.ends_with("+xml")
+        || essence.contains("javascript")
+        || essence == "application/x-www-form-urlencoded"
+}
+
+/// Parse a `/proxy` or `/proxy-stream` body, answering 400 when malformed
+fn parse_proxy_request(body: &[u8]) -> Result<ProxyRequest, Box<HttpResponse>> {
+    serde_json::from_slice(body).map_err(|e| {
+        eprintln!("❌ Proxy: Failed to parse request body: {}", e);
+        eprintln!("   Raw body: {}", String::from_utf8_lossy(body));
+        Box::new(HttpResponse::BadRequest()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .body(format!("Invalid JSON body: {}", e)))
+    })
+}
+
+fn parse_method(method: &str) -> reqwest::Method {
+    match method.to_uppercase().as_str() {
+        "GET" => reqwest::Method::GET,
+        "POST" => reqwest::Method::POST,
+        "PUT" => reqwest::Method::PUT,
+        "DELETE" => reqwest::Method::DELETE,
+        "PATCH" => reqwest::Method::PATCH,
+        "HEAD" => reqwest::Method::HEAD,
+        _ => reqwest::Method::GET,
+    }
+}
+
+/// The upstream request described by a proxy request
+fn upstream_request(client: &reqwest::Client, req: &ProxyRequest) -> reqwest::RequestBuilder {
+    let mut request = client.request(parse_method(&req.method), &req.url);
+    // Add default User-Agent if not provided (required by Wikimedia)
+    let has_ua = req.headers.keys().any(|k| k.to_lowercase() == "user-agent");
+    if !has_ua {
+        request = request.header("User-Agent", "claWasm/0.1.0 (https://github.com/niyoseris/claWasm)");
+    }
+    for (key, value) in &req.headers {
+        // The client negotiates compression itself and hands back decoded bodies
+        if key.eq_ignore_ascii_case("accept-encoding") {
+            continue;
+        }
+        request = request.header(key, value);
+    }
+    if let Some(body) = &req.body {
+        request = request.body(body.clone());
+    }
+    request
+}
+
+/// Browsers only see the proxy's own headers, so the upstream ones travel
+/// as URL-encoded JSON (name -> list of values) in `X-Upstream-Headers`
+fn encode_upstream_headers(headers: &reqwest::header::HeaderMap) -> String {
+    let mut upstream: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, value) in headers.iter() {
+        if let Ok(value) = value.to_str() {
+            upstream.entry(name.as_str().to_string()).or_default().push(value.to_string());
+        }
+    }
+    urlencoding::encode(&serde_json::to_string(&upstream).unwrap_or_default()).into_owned()
+}
+
+/// 403 for targets the policy refuses
+fn forbidden(url: &str, reason: &str) -> HttpResponse {
+    eprintln!("⛔ Proxy blocked {}: {}", url, reason);
+    HttpResponse::Forbidden()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(format!("Blocked by proxy policy: {}", reason))
+}
+
+/// 500 for an upstream request that got no response, with the error's causes
+fn upstream_error(url: &str, e: &reqwest::Error) -> HttpResponse {
+    let mut message = format!("Proxy error: {}", upstream::error_chain(e).join(": "));
+    if upstream::is_certificate_error(e) {
+        message.push_str(" (the proxy verifies upstream certificates; set insecure_tls to accept this one)");
+    }
+    eprintln!("❌ {} for {}", message, url);
+    HttpResponse::InternalServerError()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(message)
+}
+
+/// Send a finished (fresh or cached) /proxy response
+fn proxy_reply(response: &CachedResponse, cache_status: &str) -> HttpResponse {
+    let status_code = actix_web::http::StatusCode::from_u16(response.status)
+        .unwrap_or(actix_web::http::StatusCode::OK);
+    let mut reply = HttpResponse::build(status_code);
+    reply
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .insert_header(("Access-Control-Expose-Headers", "X-Upstream-Headers, X-Proxy-Cache"))
+        .insert_header((cache::CACHE_HEADER, cache_status))
+        .insert_header(("Content-Type", response.content_type.as_str()));
+    if !response.upstream_headers.is_empty() {
+        reply.insert_header(("X-Upstream-Headers", response.upstream_headers.as_str()));
+    }
+    reply.body(response.body.clone())
+}
+
+/// Read an upstream response into what /proxy sends back
+async fn read_upstream(response: reqwest::Response, base64: bool) -> CachedResponse {
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    let upstream_headers = encode_upstream_headers(&headers);
+    let content_type = headers.get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    if base64 {
+        let bytes = response.bytes().await.unwrap_or_default();
+        eprintln!("← Proxy response: {} {} bytes ({}, base64)", status, bytes.len(), content_type);
+        let envelope = serde_json::json!({
+            "status": status,
+            "content_type": content_type,
+            "size": bytes.len(),
+            "body": base64::engine::general_purpose::STANDARD.encode(&bytes),
+        });
+        CachedResponse { status, content_type: "application/json".to_string(), upstream_headers, body: envelope.to_string().into_bytes() }
+    } else if is_text_content(&content_type) {
+        // Decoded with the upstream charset; browsers read text as UTF-8
+        let body = response.text().await.unwrap_or_default();
+        if status >= 400 {
+            let preview: String = body.chars().take(500).collect();
+            eprintln!("← Proxy response: {} {} bytes | body: {}", status, body.len(), preview);
+        } else {
+            eprintln!("← Proxy response: {} {} bytes", status, body.len());
+        }
+        let essence = content_type.split(';').next().unwrap_or("").trim();
+        CachedResponse { status, content_type: format!("{}; charset=utf-8", essence), upstream_headers, body: body.into_bytes() }
+    } else {
+        // Images, audio, PDFs, archives: the exact bytes
+        let bytes = response.bytes().await.unwrap_or_default();
+        eprintln!("← Proxy response: {} {} bytes ({})", status, bytes.len(), content_type);
+        CachedResponse { status, content_type, upstream_headers, body: bytes.to_vec() }
+    }
+}
+
+/// Cache key of a bodiless GET; None for anything else
+fn proxy_cache_key(req: &ProxyRequest) -> Option<String> {
+    let is_get = req.method.is_empty() || req.method.eq_ignore_ascii_case("GET");
+    if !is_get || req.body.is_some() {
+        return None;
+    }
+    let mut headers: Vec<String> = req.headers.iter()
+        .map(|(name, value)| format!("{}: {}", name.to_lowercase(), value))
+        .collect();
+    headers.sort();
+    let format = req.response_format.as_deref().unwrap_or("");
+    let mut parts = vec!["proxy", req.url.as_str(), format];
+    parts.extend(headers.iter().map(String::as_str));
+    Some(ResponseCache::key(&parts))
+}
+
+async fn proxy_handler(
+    req: actix_web::web::Bytes,
+    http_req: HttpRequest,
+    policy: web::Data<TargetPolicy>,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    let req = match parse_proxy_request(&req) {
+        Ok(r) => r,
+        Err(response) => return *response,
+    };
+    if let Err(reason) = policy.check(&req.url).await {
+        return forbidden(&req.url, &reason);
+    }
+
+    let cache_key = proxy_cache_key(&req).filter(|_| cache.enabled());
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    if let Some(key) = cache_key.as_deref().filter(|_| !bypass) {
+        if let Some(hit) = cache.get(key) {
+            eprintln!("← Proxy cache hit: {}", req.url);
+            return proxy_reply(&hit, "HIT");
+        }
+    }
+    if let Err(response) = limits.check_host(&limits::host_of(&req.url)) {
+        return *response;
+    }
+    
+    let body_size = req.body.as_ref().map(|b| b.len()).unwrap_or(0);
+    eprintln!("→ Proxy: {} {} (body: {} bytes)", req.method, req.url, body_size);
+    
+    let request = upstream_request(&upstream.targets, &req).timeout(upstream.timeout);
+    let result = metrics.upstream("/proxy", upstream.send(request)).await;
+
+    match result {
+        Ok(response) => {
+            let response = read_upstream(response, req.response_format.as_deref() == Some("base64")).await;
+            let cache_status = match &cache_key {
+                Some(key) => {
+                    cache.put(key, &response);
+                    if bypass { "BYPASS" } else { "MISS" }
+                }
+                None => "BYPASS",
+            };
+            proxy_reply(&response, cache_status)
+        }
+        Err(e) if e.is_redirect() => {
+            let reason = std::error::Error::source(&e).map(|s| s.to_string()).unwrap_or_else(|| e.to_string());
+            forbidden(&req.url, &reason)
+        }
+        Err(e) => upstream_error(&req.url, &e),
+    }
+}
+
+/// Like `/proxy`, but forwards the body as it arrives
+///
+/// For server-sent events and chunked responses (streaming completions):
+/// nothing is buffered, the upstream Content-Type is kept and there is no
+/// overall timeout, only one for connecting. Retries happen only before
+/// the response starts. Upstream headers come back the same way as from
+/// `/proxy`.
+async fn proxy_stream_handler(
+    body: web::Bytes,
+    policy: web::Data<TargetPolicy>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    let req = match parse_proxy_request(&body) {
+        Ok(r) => r,
+        Err(response) => return *response,
+    };
+    if let Err(reason) = policy.check(&req.url).await {
+        return forbidden(&req.url, &reason);
+    }
+    if let Err(response) = limits.check_host(&limits::host_of(&req.url)) {
+        return *response;
+    }
+    eprintln!("→ Proxy stream: {} {}", req.method, req.url);
+
+    let request = upstream_request(&upstream.targets, &req);
+    match metrics.upstream("/proxy-stream", upstream.send(request)).await {
+        Ok(response) => {
+            let status_code = actix_web::http::StatusCode::from_u16(response.status().as_u16())
+                .unwrap_or(actix_web::http::StatusCode::OK);
+            let upstream_headers = encode_upstream_headers(response.headers());
+            let content_type = response.headers().get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("application/octet-stream")
+                .to_string();
+            eprintln!("← Proxy stream: {} {}", status_code.as_u16(), content_type);
+
+            HttpResponse::build(status_code)
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .insert_header(("Access-Control-Expose-Headers", "X-Upstream-Headers"))
+                .insert_header(("X-Upstream-Headers", upstream_headers))
+                .insert_header(("Content-Type", content_type))
+                .insert_header(("Cache-Control", "no-cache"))
+                // Keep reverse proxies (nginx) from buffering the stream
+                .insert_header(("X-Accel-Buffering", "no"))
+                .streaming(response.bytes_stream())
+        }
+        Err(e) => upstream_error(&req.url, &e),
+    }
+}
+
+/// Handle CORS preflight requests
+async fn proxy_options() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .insert_header(("Access-Control-Allow-Methods", "POST, OPTIONS"))
+        .insert_header(("Access-Control-Allow-Headers", "Content-Type"))
+        .finish()
+}
+
+/// Server-side TLS handshake details for scan_ssl
+async fn tls_info_handler(
+    query: web::Query<HashMap<String, String>>,
+    policy: web::Data<TargetPolicy>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+) -> HttpResponse {
+    let host = query.get("host").map(|h| h.trim().to_string()).unwrap_or_default();
+    let port: u16 = query.get("port").and_then(|p| p.parse().ok()).unwrap_or(443);
+    if host.is_empty() {
+        return HttpResponse::BadRequest()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "Missing 'host' parameter" }));
+    }
+    
+    if let Err(reason) = policy.check_host(&host) {
+        return forbidden(&host, &reason);
+    }
+    // Connect to exactly what was checked; a second lookup could rebind
+    let addrs = match policy.resolve(&host, port).await {
+        Ok(addrs) => addrs,
+        Err(reason) => return forbidden(&host, &reason),
+    };
+    if let Err(response) = limits.check_host(&host) {
+        return *response;
+    }
+    
+    eprintln!("→ TLS info: {}:{}", host, port);
+    match metrics.upstream("/tls-info", tls::inspect(&host, port, &addrs)).await {
+        Ok(info) => HttpResponse::Ok()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(info),
+        Err(e) => {
+            eprintln!("❌ TLS info error for {}:{}: {}", host, port, e);
+            HttpResponse::BadGateway()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "error": e }))
+        }
+    }
+}
+
+/// GET /search?q=...&count=N with the configured backend
+async fn web_search_handler(
+    query: web::Query<HashMap<String, String>>,
+    http_req: HttpRequest,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    search: web::Data<SearchConfig>,
+) -> HttpResponse {
+    let search_query = query.get("q").map(|q| q.trim().to_string()).unwrap_or_default();
+    if search_query.is_empty() {
+        return HttpResponse::BadRequest()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "Missing 'q' parameter" }));
+    }
+    let count = query.get("count").and_then(|c| c.parse().ok())
+        .unwrap_or(search.max_results)
+        .clamp(1, search.max_results.max(1));
+    let backend = search.backend.name();
+    let cache_key = ResponseCache::key(&["search", backend, &search_query, &count.to_string()]);
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    if !bypass {
+        if let Some(hit) = cache.get(&cache_key) {
+            eprintln!("← Search cache hit: {}", search_query);
+            return proxy_reply(&hit, "HIT");
+        }
+    }
+
+    let request = search.request(&upstream.fixed, &search_query, count);
+    let host = request.try_clone()
+        .and_then(|r| r.build().ok())
+        .and_then(|r| r.url().host_str().map(str::to_string))
+        .unwrap_or_default();
+    if let Err(response) = limits.check_host(&host) {
+        return *response;
+    }
+    eprintln!("→ Search ({}): {}", backend, search_query);
+
+    let response = match metrics.upstream("/search", upstream.send(request)).await {
+        Ok(response) => response,
+        Err(e) => return upstream_error(&host, &e),
+    };
+    let status = response.status().as_u16();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if !(200..300).contains(&status) {
+        let detail = body.to_string().chars().take(300).collect::<String>();
+        eprintln!("❌ Search backend {} answered {}: {}", backend, status, detail);
+        return HttpResponse::BadGateway()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": format!("Search backend {} answered HTTP {}", backend, status), "detail": detail }));
+    }
+
+    let mut results = search::parse(search.backend, &body);
+    results.truncate(count);
+    let reply = SearchResponse { query: search_query, backend, results };
+    let response = CachedResponse {
+        status: 200,
+        content_type: "application/json".to_string(),
+        upstream_headers: String::new(),
+        body: serde_json::to_vec(&reply).unwrap_or_default(),
+    };
+    cache.put(&cache_key, &response);
+    proxy_reply(&response, if bypass { "BYPASS" } else { "MISS" })
+}
+
+/// GET /images?q=...&count=N from the configured sources, in order
+async fn images_handler(
+    query: web::Query<HashMap<String, String>>,
+    http_req: HttpRequest,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    images: web::Data<ImagesConfig>,
+) -> HttpResponse {
+    let search_query = query.get("q").map(|q| q.trim().to_string()).unwrap_or_default();
+    if search_query.is_empty() {
+        return HttpResponse::BadRequest()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "Missing 'q' parameter" }));
+    }
+    let count = query.get("count").and_then(|c| c.parse().ok())
+        .unwrap_or(images.max_results)
+        .clamp(1, images.max_results.max(1));
+    let cache_key = ResponseCache::key(&["images", &search_query, &count.to_string()]);
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    if !bypass {
+        if let Some(hit) = cache.get(&cache_key) {
+            eprintln!("← Image search cache hit: {}", search_query);
+            return proxy_reply(&hit, "HIT");
+        }
+    }
+
+    // A failing source falls through to the next one
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+    let mut limited = None;
+    for &source in &images.sources {
+        if results.len() >= count {
+            break;
+        }
+        let request = images.request(&upstream.fixed, source, &search_query, count - results.len());
+        let host = request.try_clone()
+            .and_then(|r| r.build().ok())
+            .and_then(|r| r.url().host_str().map(str::to_string))
+            .unwrap_or_default();
+        if let Err(response) = limits.check_host(&host) {
+            failures.push(format!("{}: rate limited", source.name()));
+            limited = Some(response);
+            continue;
+        }
+        eprintln!("→ Image search ({}): {}", source.name(), search_query);
+        match metrics.upstream("/images", upstream.send(request)).await {
+            Ok(response) if response.status().is_success() => {
+                let body: serde_json::Value = response.json().await.unwrap_or_default();
+                results.extend(images::parse(source, &body));
+            }
+            Ok(response) => {
+                eprintln!("❌ Image source {} answered {}", source.name(), response.status().as_u16());
+                failures.push(format!("{}: HTTP {}", source.name(), response.status().as_u16()));
+            }
+            Err(e) => {
+                eprintln!("❌ Image source {} failed: {}", source.name(), e);
+                failures.push(format!("{}: {}", source.name(), e));
+            }
+        }
+    }
+    if results.is_empty() && failures.len() == images.sources.len() {
+        if let Some(response) = limited {
+            return *response;
+        }
+        return HttpResponse::BadGateway()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "Every image source failed", "detail": failures }));
+    }
+
+    results.truncate(count);
+    let reply = ImagesResponse { query: search_query, results };
+    let response = CachedResponse {
+        status: 200,
+        content_type: "application/json".to_string(),
+        upstream_headers: String::new(),
+        body: serde_json::to_vec(&reply).unwrap_or_default(),
+    };
+    // Partial answers are not worth keeping
+    if failures.is_empty() {
+        cache.put(&cache_key, &response);
+    }
+    proxy_reply(&response, if bypass { "BYPASS" } else { "MISS" })
+}
+
+/// GET /render?url=...&format=html|png&wait_ms=N: the page after its scripts ran
+#[cfg(feature = "render")]
+async fn render_handler(
+    query: web::Query<HashMap<String, String>>,
+    http_req: HttpRequest,
+    policy: web::Data<TargetPolicy>,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    renderer: web::Data<render::Renderer>,
+) -> HttpResponse {
+    let Some(target) = query.get("url").map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) else {
+        return HttpResponse::BadRequest()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "Missing 'url' parameter" }));
+    };
+    let Some(format) = render::Format::parse(query.get("format").map(String::as_str).unwrap_or("html")) else {
+        return HttpResponse::BadRequest()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "'format' must be html or png" }));
+    };
+    let wait = std::time::Duration::from_millis(query.get("wait_ms").and_then(|w| w.parse().ok()).unwrap_or(0))
+        .min(render::MAX_WAIT);
+    if let Err(reason) = policy.check(&target).await {
+        return forbidden(&target, &reason);
+    }
+
+    let cache_key = ResponseCache::key(&["render", &target, format.content_type(), &wait.as_millis().to_string()]);
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    if !bypass {
+        if let Some(hit) = cache.get(&cache_key) {
+            eprintln!("← Render cache hit: {}", target);
+            return proxy_reply(&hit, "HIT");
+        }
+    }
+    if let Err(response) = limits.check_host(&limits::host_of(&target)) {
+        return *response;
+    }
+    eprintln!("→ Render: {}", target);
+
+    match metrics.upstream("/render", renderer.render(&target, format, wait)).await {
+        Ok(body) => {
+            let response = CachedResponse {
+                status: 200,
+                content_type: format.content_type().to_string(),
+                upstream_headers: String::new(),
+                body,
+            };
+            cache.put(&cache_key, &response);
+            proxy_reply(&response, if bypass { "BYPASS" } else { "MISS" })
+        }
+        Err(e) => {
+            eprintln!("❌ Render failed: {}", e);
+            HttpResponse::BadGateway()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "error": e }))
+        }
+    }
+}
+
+#[cfg(not(feature = "render"))]
+async fn render_handler() -> HttpResponse {
+    HttpResponse::NotImplemented()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .json(serde_json::json!({ "error": "This proxy was built without headless rendering; rebuild it with --features render" }))
+}
+
+/// POST /transcribe?language=xx with audio as the body, or with `?url=` of
+/// an audio file for the proxy to fetch
+#[allow(clippy::too_many_arguments)]
+async fn transcribe_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    query: web::Query<HashMap<String, String>>,
+    policy: web::Data<TargetPolicy>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    transcribe: web::Data<TranscribeConfig>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Transcribe: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    let (audio, content_type) = match query.get("url").filter(|_| body.is_empty()) {
+        Some(url) => {
+            if let Err(reason) = policy.check(url).await {
+                return forbidden(url, &reason);
+            }
+            if let Err(response) = limits.check_host(&limits::host_of(url)) {
+                return *response;
+            }
+            eprintln!("→ Fetching audio: {}", url);
+            let response = match upstream.send(upstream.targets.get(url).timeout(upstream.timeout)).await {
+                Ok(response) if response.status().is_success() => response,
+                Ok(response) => return error(StatusCode::BAD_GATEWAY, format!("{} answered HTTP {}", url, response.status().as_u16())),
+                Err(e) => return upstream_error(url, &e),
+            };
+            let content_type = response.headers().get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("audio/mpeg")
+                .to_string();
+            match response.bytes().await {
+                Ok(bytes) => (bytes.to_vec(), content_type),
+                Err(e) => return upstream_error(url, &e),
+            }
+        }
+        None => {
+            let content_type = http_req.headers().get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("audio/mpeg")
+                .to_string();
+            (body.to_vec(), content_type)
+        }
+    };
+    if audio.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Send audio as the body or an audio file's 'url'".to_string());
+    }
+    if audio.len() > transcribe.max_bytes {
+        return error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Audio is {} bytes; the limit is {}", audio.len(), transcribe.max_bytes),
+        );
+    }
+
+    let size = audio.len();
+    let authorization = http_req.headers().get("Authorization").and_then(|v| v.to_str().ok());
+    let language = query.get("language").map(String::as_str).filter(|l| !l.is_empty());
+    let request = match transcribe.request(&upstream.fixed, audio, &content_type, language, authorization) {
+        Ok(request) => request,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let host = request.try_clone()
+        .and_then(|r| r.build().ok())
+        .and_then(|r| r.url().host_str().map(str::to_string))
+        .unwrap_or_default();
+    if let Err(response) = limits.check_host(&host) {
+        return *response;
+    }
+    eprintln!("→ Transcribe ({}): {} bytes of {}", transcribe.backend.name(), size, content_type);
+
+    let response = match metrics.upstream("/transcribe", upstream.send(request)).await {
+        Ok(response) => response,
+        Err(e) => return upstream_error(&host, &e),
+    };
+    let status = response.status();
+    let reply: serde_json::Value = response.json().await.unwrap_or_default();
+    if !status.is_success() {
+        // A rejected key or unsupported file is the caller's to fix; anything
+        // else is the backend's fault
+        let status = if status.is_client_error() { status.as_u16() } else { 502 };
+        let detail = reply["error"]["message"].as_str().map(str::to_string).unwrap_or_else(|| reply.to_string());
+        return error(
+            StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY),
+            format!("{} answered HTTP {}: {}", transcribe.backend.name(), status, detail),
+        );
+    }
+    HttpResponse::Ok()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .json(transcribe::parse(transcribe.backend, &reply))
+}
+
+/// POST /tts {"text", "voice", "format": "mp3"|"ogg"|"wav", "speed"}: audio,
+/// streamed chunk by chunk for long text
+async fn tts_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    tts: web::Data<TtsConfig>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: u16, message: String| {
+        eprintln!("❌ TTS: {}", message);
+        HttpResponse::build(StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY))
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    let req: TtsRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(400, format!("Invalid request: {}", e)),
+    };
+    let length = req.text.chars().count();
+    if req.text.trim().is_empty() {
+        return error(400, "Missing 'text'".to_string());
+    }
+    if length > tts.max_chars {
+        return error(413, format!("Text is {} characters; the limit is {}", length, tts.max_chars));
+    }
+    let authorization = http_req.headers().get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let chunks = tts::split_text(&req.text, tts.chunk_chars);
+    let host = tts.request(&upstream.fixed, "", &req, authorization.as_deref()).ok()
+        .and_then(|r| r.build().ok())
+        .and_then(|r| r.url().host_str().map(str::to_string))
+        .unwrap_or_default();
+    if let Err(response) = limits.check_host(&host) {
+        return *response;
+    }
+    eprintln!("→ TTS ({}): {} characters in {} chunk(s) as {:?}", tts.backend.name(), length, chunks.len(), req.format);
+
+    // The first chunk decides the status; later failures cut the stream
+    let first = match tts.synthesize(&upstream, &metrics, &chunks[0], &req, authorization.as_deref()).await {
+        Ok(audio) => audio,
+        Err((status, message)) => return error(status, message),
+    };
+    let content_type = req.format.content_type();
+    if req.format == tts::Format::Wav {
+        let mut parts = vec![first];
+        for chunk in &chunks[1..] {
+            match tts.synthesize(&upstream, &metrics, chunk, &req, authorization.as_deref()).await {
+                Ok(audio) => parts.push(audio),
+                Err((status, message)) => return error(status, message),
+            }
+        }
+        return match tts::merge_wav(&parts) {
+            Ok(wav) => HttpResponse::Ok().content_type(content_type).body(wav),
+            Err(message) => error(502, message),
+        };
+    }
+    if chunks.len() == 1 {
+        return HttpResponse::Ok().content_type(content_type).body(first);
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<web::Bytes, String>>(2);
+    let _ = tx.send(Ok(web::Bytes::from(first))).await;
+    tokio::spawn(async move {
+        for chunk in &chunks[1..] {
+            let next = match tts.synthesize(&upstream, &metrics, chunk, &req, authorization.as_deref()).await {
+                Ok(audio) => Ok(web::Bytes::from(audio)),
+                Err((_, message)) => {
+                    eprintln!("❌ TTS stopped mid-stream: {}", message);
+                    Err(message)
+                }
+            };
+            let failed = next.is_err();
+            if tx.send(next).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    // An error ends the response early, so a client cannot mistake the
+    // audio so far for all of it
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item.map_err(actix_web::error::ErrorBadGateway), rx))
+    });
+    HttpResponse::Ok().content_type(content_type).streaming(stream)
+}
+
+/// POST /osv/batch {"packages": [{"name", "ecosystem", "version"}]}: OSV
+/// advisories with their details for every package in one round trip
+async fn osv_batch_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    use futures::StreamExt;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ OSV: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+    let json = |value: &serde_json::Value| CachedResponse {
+        status: 200,
+        content_type: "application/json".to_string(),
+        upstream_headers: String::new(),
+        body: serde_json::to_vec(value).unwrap_or_default(),
+    };
+
+    let req: osv::BatchRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if let Err(message) = req.validate() {
+        return error(StatusCode::BAD_REQUEST, message);
+    }
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    let cached = |key: &str| if bypass { None } else { cache.get(key) };
+
+    // Advisory IDs per package, from the cache where possible
+    let package_key = |p: &osv::Package| ResponseCache::key(&["osv", &p.ecosystem, &p.name, &p.version]);
+    let mut ids: Vec<Option<Vec<String>>> = req.packages.iter()
+        .map(|p| cached(&package_key(p)).and_then(|hit| serde_json::from_slice(&hit.body).ok()))
+        .collect();
+    let hits = ids.iter().filter(|i| i.is_some()).count();
+    let misses: Vec<usize> = (0..ids.len()).filter(|&i| ids[i].is_none()).collect();
+    let mut checked = false;
+    if !misses.is_empty() {
+        if let Err(response) = limits.check_host(osv::HOST) {
+            return *response;
+        }
+        checked = true;
+        let packages: Vec<&osv::Package> = misses.iter().map(|&i| &req.packages[i]).collect();
+        eprintln!("→ OSV batch: {} of {} packages", packages.len(), req.packages.len());
+        let request = upstream.fixed.post(osv::BATCH_URL).json(&osv::batch_body(&packages));
+        let answer: serde_json::Value = match metrics.upstream("/osv/batch", upstream.send(request)).await {
+            Ok(response) if response.status().is_success() => response.json().await.unwrap_or_default(),
+            Ok(response) => return error(StatusCode::BAD_GATEWAY, format!("OSV answered HTTP {}", response.status().as_u16())),
+            Err(e) => return upstream_error(osv::BATCH_URL, &e),
+        };
+        let found = osv::parse_batch(&answer);
+        if found.len() != misses.len() {
+            return error(StatusCode::BAD_GATEWAY, format!("OSV answered {} results for {} queries", found.len(), misses.len()));
+        }
+        for (&i, found) in misses.iter().zip(found) {
+            cache.put(&package_key(&req.packages[i]), &json(&serde_json::json!(found)));
+            ids[i] = Some(found);
+        }
+    }
+
+    // Each advisory once, however many packages it affects
+    let mut unique: Vec<&String> = ids.iter().flatten().flatten().collect();
+    unique.sort();
+    unique.dedup();
+    let vuln_key = |id: &str| ResponseCache::key(&["osv-vuln", id]);
+    let mut details: HashMap<String, serde_json::Value> = HashMap::new();
+    let mut fetch = Vec::new();
+    for id in unique {
+        match cached(&vuln_key(id)).and_then(|hit| serde_json::from_slice(&hit.body).ok()) {
+            Some(vuln) => {
+                details.insert(id.clone(), vuln);
+            }
+            None => fetch.push(id.clone()),
+        }
+    }
+    if !fetch.is_empty() && !checked {
+        if let Err(response) = limits.check_host(osv::HOST) {
+            return *response;
+        }
+    }
+    if !fetch.is_empty() {
+        eprintln!("→ OSV details: {} advisories", fetch.len());
+    }
+    let fetched: Vec<(String, Option<serde_json::Value>)> = futures::stream::iter(fetch)
+        .map(|id| {
+            let request = upstream.fixed.get(osv::vuln_url(&id));
+            let (upstream, metrics) = (&upstream, &metrics);
+            async move {
+                let vuln = match metrics.upstream("/osv/batch", upstream.send(request)).await {
+                    Ok(response) if response.status().is_success() => response.json().await.ok(),
+                    _ => None,
+                };
+                (id, vuln)
+            }
+        })
+        .buffer_unordered(osv::DETAIL_CONCURRENCY)
+        .collect()
+        .await;
+    let mut missing = Vec::new();
+    for (id, vuln) in fetched {
+        match vuln {
+            Some(vuln) => {
+                cache.put(&vuln_key(&id), &json(&vuln));
+                details.insert(id, vuln);
+            }
+            None => missing.push(id),
+        }
+    }
+    missing.sort();
+
+    let results = req.packages.into_iter().zip(ids)
+        .map(|(package, ids)| osv::PackageResult {
+            package,
+            vulns: ids.unwrap_or_default().into_iter()
+                .map(|id| details.get(&id).cloned().unwrap_or_else(|| serde_json::json!({ "id": id })))
+                .collect(),
+        })
+        .collect();
+    let reply = osv::BatchResponse { results, cached: hits, missing };
+    HttpResponse::Ok()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .insert_header((cache::CACHE_HEADER, if bypass { "BYPASS" } else if misses.is_empty() { "HIT" } else { "MISS" }))
+        .json(reply)
+}
+
+/// GET /rss?url=...&count=20: an RSS or Atom feed as compact JSON
+async fn rss_handler(
+    query: web::Query<HashMap<String, String>>,
+    http_req: HttpRequest,
+    policy: web::Data<TargetPolicy>,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ RSS: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    let Some(url) = query.get("url").map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) else {
+        return error(StatusCode::BAD_REQUEST, "Missing 'url' parameter".to_string());
+    };
+    let count = query.get("count").and_then(|c| c.parse().ok()).unwrap_or(20).clamp(1, rss::MAX_ITEMS);
+    if let Err(reason) = policy.check(&url).await {
+        return forbidden(&url, &reason);
+    }
+    let cache_key = ResponseCache::key(&["rss", &url, &count.to_string()]);
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    if !bypass {
+        if let Some(hit) = cache.get(&cache_key) {
+            eprintln!("← Feed cache hit: {}", url);
+            return proxy_reply(&hit, "HIT");
+        }
+    }
+    if let Err(response) = limits.check_host(&limits::host_of(&url)) {
+        return *response;
+    }
+
+    eprintln!("→ Feed: {}", url);
+    let request = upstream.targets.get(&url)
+        .header("Accept", "application/rss+xml, application/atom+xml, application/xml;q=0.9, */*;q=0.8")
+        .timeout(upstream.timeout);
+    let xml = match metrics.upstream("/rss", upstream.send(request)).await {
+        Ok(response) if response.status().is_success() => match response.text().await {
+            Ok(xml) => xml,
+            Err(e) => return upstream_error(&url, &e),
+        },
+        Ok(response) => return error(StatusCode::BAD_GATEWAY, format!("{} answered HTTP {}", url, response.status().as_u16())),
+        Err(e) => return upstream_error(&url, &e),
+    };
+    let feed = match rss::parse(&xml, count) {
+        Ok(feed) => feed,
+        Err(message) => return error(StatusCode::UNPROCESSABLE_ENTITY, message),
+    };
+    let response = CachedResponse {
+        status: 200,
+        content_type: "application/json".to_string(),
+        upstream_headers: String::new(),
+        body: serde_json::to_vec(&feed).unwrap_or_default(),
+    };
+    cache.put(&cache_key, &response);
+    proxy_reply(&response, if bypass { "BYPASS" } else { "MISS" })
+}
+
+/// GET /youtube/transcript?id=...&lang=en: a video's captions as text
+async fn youtube_transcript_handler(
+    query: web::Query<HashMap<String, String>>,
+    http_req: HttpRequest,
+    cache: web::Data<ResponseCache>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ YouTube: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    let Some(id) = query.get("id").and_then(|id| youtube::video_id(id)) else {
+        return error(StatusCode::BAD_REQUEST, "'id' must be a YouTube video ID or URL".to_string());
+    };
+    let language = query.get("lang").map(|l| l.trim()).filter(|l| !l.is_empty());
+    let cache_key = ResponseCache::key(&["youtube", &id, language.unwrap_or("")]);
+    let bypass = ResponseCache::bypass_requested(&http_req);
+    if !bypass {
+        if let Some(hit) = cache.get(&cache_key) {
+            eprintln!("← Transcript cache hit: {}", id);
+            return proxy_reply(&hit, "HIT");
+        }
+    }
+    if let Err(response) = limits.check_host(youtube::HOST) {
+        return *response;
+    }
+
+    eprintln!("→ YouTube transcript: {}", id);
+    let get = |url: &str| {
+        upstream.fixed.get(url)
+            .header("Accept-Language", "en-US,en;q=0.9")
+            // Skips the cookie consent interstitial served to EU addresses
+            .header("Cookie", "CONSENT=YES+1")
+    };
+    let watch = youtube::watch_url(&id);
+    let html = match metrics.upstream("/youtube/transcript", upstream.send(get(&watch))).await {
+        Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+        Ok(response) => return error(StatusCode::BAD_GATEWAY, format!("YouTube answered HTTP {}", response.status().as_u16())),
+        Err(e) => return upstream_error(&watch, &e),
+    };
+    let Some(player) = youtube::player_response(&html) else {
+        return error(StatusCode::BAD_GATEWAY, "No player data in the video page".to_string());
+    };
+    if let Some(status) = player["playabilityStatus"]["status"].as_str().filter(|s| *s != "OK") {
+        let reason = player["playabilityStatus"]["reason"].as_str().unwrap_or(status);
+        return error(StatusCode::NOT_FOUND, format!("Video {} is unavailable: {}", id, reason));
+    }
+    let tracks = youtube::tracks(&player);
+    let mut languages: Vec<String> = tracks.iter().map(|t| t.language.clone()).collect();
+    languages.dedup();
+    let Some(track) = youtube::pick(&tracks, language) else {
+        return error(StatusCode::NOT_FOUND, match language {
+            Some(language) if !tracks.is_empty() => format!("No {} captions; available: {}", language, languages.join(", ")),
+            _ => format!("Video {} has no captions", id),
+        });
+    };
+    // The track URL comes from the page; never follow it elsewhere
+    if !track.url.starts_with("https://www.youtube.com/") {
+        return error(StatusCode::BAD_GATEWAY, format!("Unexpected caption URL {}", track.url));
+    }
+
+    let xml = match metrics.upstream("/youtube/transcript", upstream.send(get(&track.url))).await {
+        Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+        Ok(response) => return error(StatusCode::BAD_GATEWAY, format!("YouTube captions answered HTTP {}", response.status().as_u16())),
+        Err(e) => return upstream_error(&track.url, &e),
+    };
+    let segments = match youtube::parse_captions(&xml) {
+        Ok(segments) if !segments.is_empty() => segments,
+        Ok(_) => return error(StatusCode::BAD_GATEWAY, "YouTube sent empty captions".to_string()),
+        Err(message) => return error(StatusCode::BAD_GATEWAY, message),
+    };
+    let transcript = youtube::Transcript {
+        id,
+        title: player["videoDetails"]["title"].as_str().map(str::to_string),
+        language: track.language.clone(),
+        generated: track.generated,
+        text: segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" "),
+        segments,
+        languages,
+    };
+    let response = CachedResponse {
+        status: 200,
+        content_type: "application/json".to_string(),
+        upstream_headers: String::new(),
+        body: serde_json::to_vec(&transcript).unwrap_or_default(),
+    };
+    cache.put(&cache_key, &response);
+    proxy_reply(&response, if bypass { "BYPASS" } else { "MISS" })
+}
+
+/// POST /channel/email/send {"to", "cc", "subject", "text", "html",
+/// "attachments"}: mail through the configured SMTP server
+async fn email_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    mailer: web::Data<email::Mailer>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Email: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !mailer.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Email is not configured: set host and from in [email]".to_string());
+    }
+    let req: email::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    let message = match mailer.message(&req) {
+        Ok(message) => message,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    if let Err(response) = limits.check_host(mailer.config.host.as_deref().unwrap_or("")) {
+        return *response;
+    }
+
+    let recipients = req.to.len() + req.cc.len();
+    eprintln!("→ Email to {} recipient(s): {}", recipients, req.subject);
+    match metrics.upstream("/channel/email/send", mailer.send(message)).await {
+        Ok(reply) => HttpResponse::Ok()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "sent": true, "recipients": recipients, "response": reply })),
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("SMTP: {}", message)),
+    }
+}
+
+/// POST /channel/email/reply {"reply_to", "text", "html"}: the answer to a
+/// message relayed from the IMAP inbox, sent through the SMTP server
+async fn email_reply_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    mailer: web::Data<email::Mailer>,
+    inbox: web::Data<imap::Inbox>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Email reply: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !inbox.enabled() || !mailer.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "The inbox is not configured: set host in [imap] and [email]".to_string());
+    }
+    let req: imap::ReplyRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    let (message, to) = match inbox.reply(mailer.config.from.as_deref().unwrap_or(""), &req) {
+        Ok(reply) => reply,
+        Err(message) if message.starts_with("No recent") => return error(StatusCode::FORBIDDEN, message),
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    if let Err(response) = limits.check_host(mailer.config.host.as_deref().unwrap_or("")) {
+        return *response;
+    }
+
+    eprintln!("→ Email reply to {}", to);
+    match metrics.upstream("/channel/email/reply", mailer.send(message)).await {
+        Ok(reply) => {
+            if let Some(sender) = inbox.author(&req.reply_to) {
+                inbox.remember(&sender, "assistant", req.text.as_deref().or(req.html.as_deref()).unwrap_or(""));
+            }
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "to": to, "response": reply }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("SMTP: {}", message)),
+    }
+}
+
+/// POST /channel/bluesky/send {"text", "reply_to"}: the answer to a relayed
+/// mention, or a new post without `reply_to`
+async fn bluesky_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    bluesky: web::Data<bluesky::Bluesky>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Bluesky: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !bluesky.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Bluesky is not configured: set identifier and app_password in [bluesky]".to_string());
+    }
+    let req: bluesky::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if req.text.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Missing 'text'".to_string());
+    }
+    if !bluesky.may_answer(&req) {
+        return error(StatusCode::FORBIDDEN, format!("No recent mention {}", req.reply_to.as_deref().unwrap_or("")));
+    }
+    if let Err(response) = limits.check_host(&bluesky.host()) {
+        return *response;
+    }
+
+    let sent = bluesky.send(&upstream.fixed, &req);
+    match metrics.upstream("/channel/bluesky/send", sent).await {
+        Ok((posts, url)) => {
+            if let Some(recipient) = req.reply_to.as_deref().and_then(|id| bluesky.author(id)) {
+                bluesky.remember(&recipient, "assistant", &req.text);
+            }
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "posts": posts, "url": url }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("Bluesky: {}", message)),
+    }
+}
+
+/// GET /channels: the named channel instances, without their secrets
+async fn channels_handler(channels: web::Data<channels::Channels>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .json(serde_json::json!({ "channels": channels.list() }))
+}
+
+/// POST /channels/{id}/send {"text"}: a message to a channel instance of
+/// the config, whose credentials never reach the browser
+async fn channel_send_handler(
+    path: web::Path<String>,
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    channels: web::Data<channels::Channels>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let id = path.into_inner();
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Channel {}: {}", id, message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    let Some(channel) = channels.get(&id) else {
+        return error(StatusCode::NOT_FOUND, format!("No channel '{}': add it as [channels.{}]", id, id));
+    };
+    let req: channels::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if req.text.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Missing 'text'".to_string());
+    }
+    if let Err(response) = limits.check_host(&channel.host()) {
+        return *response;
+    }
+
+    match metrics.upstream("/channels/send", channel.send(&upstream.fixed, &req.text)).await {
+        Ok(messages) => HttpResponse::Ok()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "sent": true, "messages": messages })),
+        Err(message) => error(StatusCode::BAD_GATEWAY, message),
+    }
+}
+
+/// POST /notify {"channels", "title", "message", "level"}: one notification
+/// to several channel instances; it succeeds if any of them got it
+async fn notify_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    channels: web::Data<channels::Channels>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Notify: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    let req: channels::NotifyRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if req.message.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Missing 'message'".to_string());
+    }
+    let ids = match channels.select(&req.channels) {
+        Ok(ids) => ids,
+        Err(message) => return error(StatusCode::NOT_FOUND, message),
+    };
+
+    let text = req.text();
+    let (mut sent, mut failed) = (Vec::new(), Vec::new());
+    for id in ids {
+        let Some(channel) = channels.get(&id) else { continue };
+        let result = match limits.check_host(&channel.host()) {
+            Ok(()) => metrics.upstream("/notify", channel.send(&upstream.fixed, &text)).await,
+            Err(_) => Err(format!("rate limit for {}", channel.host())),
+        };
+        match result {
+            Ok(messages) => sent.push(serde_json::json!({ "channel": id, "messages": messages })),
+            Err(message) => {
+                eprintln!("❌ Notify {}: {}", id, message);
+                failed.push(serde_json::json!({ "channel": id, "error": message }));
+            }
+        }
+    }
+    let status = if sent.is_empty() { StatusCode::BAD_GATEWAY } else { StatusCode::OK };
+    HttpResponse::build(status)
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .json(serde_json::json!({ "sent": sent, "failed": failed }))
+}
+
+/// POST /channel/discord/interactions: a signed interaction from Discord,
+/// answered at once; slash commands are deferred and relayed to /ws
+async fn discord_interactions_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    discord: web::Data<discord::Discord>,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Discord interaction: {}", message);
+        HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+    };
+
+    if !discord.interactions_enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Discord interactions are not configured: set public_key in [discord]".to_string());
+    }
+    let header = |name: &str| http_req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+    // Discord checks the endpoint with bad signatures and expects a 401
+    if !discord.verify(&header(discord::TIMESTAMP_HEADER), &body, &header(discord::SIGNATURE_HEADER)) {
+        return error(StatusCode::UNAUTHORIZED, "Invalid request signature".to_string());
+    }
+    let interaction: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(interaction) => interaction,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid interaction: {}", e)),
+    };
+    HttpResponse::Ok().json(discord.interaction(&interaction, &hub))
+}
+
+/// POST /channel/discord/send {"channel_id", "text", "reply_to",
+/// "interaction_id"}: the answer to a relayed Discord message or slash
+/// command
+async fn discord_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    discord: web::Data<discord::Discord>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Discord: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !discord.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Discord is not configured: set discord_token in [realtime]".to_string());
+    }
+    let req: discord::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if req.text.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Missing 'text'".to_string());
+    }
+    if !discord.may_answer(&req) {
+        return error(StatusCode::FORBIDDEN, match &req.interaction_id {
+            Some(id) => format!("No pending interaction {} in channel {}", id, req.channel_id),
+            None => format!("The bot has no conversation in channel {}", req.channel_id),
+        });
+    }
+    if let Err(response) = limits.check_host(discord::HOST) {
+        return *response;
+    }
+
+    let sent = discord.deliver(&upstream.fixed, &req);
+    match metrics.upstream("/channel/discord/send", sent).await {
+        Ok(messages) => {
+            discord.remember(&req.channel_id, "assistant", &req.text);
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "messages": messages }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("Discord: {}", message)),
+    }
+}
+
+/// POST /channel/mastodon/send {"text", "reply_to", "visibility",
+/// "spoiler_text"}: the answer to a relayed mention, or a new post without
+/// `reply_to`
+async fn mastodon_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    mastodon: web::Data<mastodon::Mastodon>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Mastodon: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !mastodon.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Mastodon is not configured: set instance and access_token in [mastodon]".to_string());
+    }
+    let req: mastodon::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if req.text.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Missing 'text'".to_string());
+    }
+    if !mastodon.may_answer(&req) {
+        return error(StatusCode::FORBIDDEN, format!("No recent mention {}", req.reply_to.as_deref().unwrap_or("")));
+    }
+    if let Err(response) = limits.check_host(&mastodon.host()) {
+        return *response;
+    }
+
+    let sent = mastodon.send(&upstream.fixed, &req);
+    match metrics.upstream("/channel/mastodon/send", sent).await {
+        Ok((posts, url)) => {
+            if let Some(recipient) = req.reply_to.as_deref().and_then(|id| mastodon.author(id)) {
+                mastodon.remember(&recipient, "assistant", &req.text);
+            }
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "posts": posts, "url": url }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("Mastodon: {}", message)),
+    }
+}
+
+/// POST /channel/slack/events: a signed Events API request, acknowledged
+/// at once; event callbacks are published on /ws for the bot to handle
+async fn slack_events_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    slack: web::Data<slack::Slack>,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Slack event: {}", message);
+        HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+    };
+
+    if !slack.events_enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Slack events are not configured: set signing_secret in [slack]".to_string());
+    }
+    let header = |name: &str| http_req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+    if !slack.verify(&header(slack::TIMESTAMP_HEADER), &body, &header(slack::SIGNATURE_HEADER)) {
+        return error(StatusCode::UNAUTHORIZED, format!("Missing, stale or wrong {}", slack::SIGNATURE_HEADER));
+    }
+    let request: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid event: {}", e)),
+    };
+    match slack::callback(&request) {
+        slack::Callback::Challenge(challenge) => HttpResponse::Ok().json(serde_json::json!({ "challenge": challenge })),
+        // Slack wants an answer within three seconds, so the bot handles
+        // the event after this returns
+        slack::Callback::Event(kind) => {
+            hub.publish(realtime::Channel::Slack, &kind, request);
+            HttpResponse::Ok().finish()
+        }
+        slack::Callback::Ignore => HttpResponse::Ok().finish(),
+    }
+}
+
+/// POST /channel/slack/command: a signed slash command or button press,
+/// acknowledged at once and published on /ws for the bot to handle
+async fn slack_command_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    slack: web::Data<slack::Slack>,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Slack command: {}", message);
+        HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+    };
+
+    if !slack.events_enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Slack commands are not configured: set signing_secret in [slack]".to_string());
+    }
+    let header = |name: &str| http_req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+    if !slack.verify(&header(slack::TIMESTAMP_HEADER), &body, &header(slack::SIGNATURE_HEADER)) {
+        return error(StatusCode::UNAUTHORIZED, format!("Missing, stale or wrong {}", slack::SIGNATURE_HEADER));
+    }
+    let (kind, payload) = match slack::form(&body) {
+        Ok(form) => form,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    hub.publish(realtime::Channel::Slack, kind, payload);
+    match kind {
+        // Shows the command in the channel, where the answer will follow
+        "slash_commands" => HttpResponse::Ok().json(serde_json::json!({ "response_type": "in_channel" })),
+        _ => HttpResponse::Ok().finish(),
+    }
+}
+
+/// POST /channel/slack/send {"channel", "text", "thread_ts", "format",
+/// "buttons", "command_id"}: the answer to a relayed Slack message or
+/// command
+async fn slack_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    slack: web::Data<slack::Slack>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Slack: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !slack.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Slack is not configured: set bot_token in [slack]".to_string());
+    }
+    let req: slack::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if req.text.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Missing 'text'".to_string());
+    }
+    if !slack.may_answer(&req) {
+        return error(StatusCode::FORBIDDEN, match &req.command_id {
+            Some(id) => format!("No recent command {} in channel {}", id, req.channel),
+            None => format!("The bot has no conversation in channel {}", req.channel),
+        });
+    }
+    if let Err(response) = limits.check_host(slack::HOST) {
+        return *response;
+    }
+
+    let sent = slack.deliver(&upstream.fixed, &req);
+    match metrics.upstream("/channel/slack/send", sent).await {
+        Ok(messages) => {
+            slack.remember(&req.channel, req.thread_ts.as_deref(), "assistant", &req.text);
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "messages": messages }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("Slack: {}", message)),
+    }
+}
+
+/// POST /channel/telegram/webhook: an update from Telegram, relayed to the
+/// browsers listening on /ws
+async fn telegram_webhook_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    telegram: web::Data<telegram::Telegram>,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Telegram: {}", message);
+        HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+    };
+
+    if !telegram.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Telegram is not configured: set bot_token in [telegram]".to_string());
+    }
+    if telegram.polling() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Telegram is in polling mode; the webhook is off".to_string());
+    }
+    let presented = http_req.headers().get(telegram::SECRET_HEADER).and_then(|v| v.to_str().ok()).unwrap_or("");
+    let secret = telegram.config.webhook_secret.as_deref().unwrap_or("");
+    if !auth::constant_time_eq(secret.as_bytes(), presented.as_bytes()) {
+        return error(StatusCode::UNAUTHORIZED, format!("Missing or wrong {}", telegram::SECRET_HEADER));
+    }
+    let update: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(update) => update,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid update: {}", e)),
+    };
+    // Telegram resends anything not answered with a 2xx, so failures
+    // past this point are logged and answered 200 all the same
+    telegram.handle(&update, &hub, &upstream.fixed, &metrics, "/channel/telegram/webhook").await;
+    HttpResponse::Ok().json(serde_json::json!({ "ok": true }))
+}
+
+/// POST /channel/telegram/send {"chat_id", "text", "reply_to", "format",
+/// "files", "buttons"}: the answer to a relayed message
+async fn telegram_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    telegram: web::Data<telegram::Telegram>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Telegram: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !telegram.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Telegram is not configured: set bot_token in [telegram]".to_string());
+    }
+    let req: telegram::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    let messages = match req.outgoing() {
+        Ok(messages) => messages,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    if !telegram.allows(req.chat_id) {
+        return error(StatusCode::FORBIDDEN, format!("Chat {} is not in allowed_chats", req.chat_id));
+    }
+    if let Err(response) = limits.check_host(telegram::HOST) {
+        return *response;
+    }
+
+    let sent = telegram.deliver(&upstream.fixed, &messages);
+    match metrics.upstream("/channel/telegram/send", sent).await {
+        Ok(messages) => {
+            telegram.remember(req.chat_id, "assistant", &req.summary());
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "messages": messages }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("Telegram: {}", message)),
+    }
+}
+
+/// GET /channel/whatsapp/webhook?hub.mode=subscribe&hub.verify_token=...&hub.challenge=...:
+/// Meta's check of the callback URL
+async fn whatsapp_verify_handler(
+    query: web::Query<HashMap<String, String>>,
+    whatsapp: web::Data<whatsapp::WhatsApp>,
+) -> HttpResponse {
+    let param = |name: &str| query.get(name).map(String::as_str).unwrap_or("");
+    match whatsapp.challenge(param("hub.mode"), param("hub.verify_token"), param("hub.challenge")) {
+        Some(challenge) => HttpResponse::Ok().content_type("text/plain").body(challenge.to_string()),
+        None => {
+            eprintln!("❌ WhatsApp: webhook check with a wrong hub.verify_token");
+            HttpResponse::Forbidden().finish()
+        }
+    }
+}
+
+/// POST /channel/whatsapp/webhook: a signed notification from the Cloud
+/// API; messages are relayed to the browsers listening on /ws
+async fn whatsapp_webhook_handler(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    whatsapp: web::Data<whatsapp::WhatsApp>,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ WhatsApp: {}", message);
+        HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+    };
+
+    if !whatsapp.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "WhatsApp is not configured: set access_token in [whatsapp]".to_string());
+    }
+    let signature = http_req.headers().get(whatsapp::SIGNATURE_HEADER).and_then(|v| v.to_str().ok()).unwrap_or("");
+    if !whatsapp.verify(&body, signature) {
+        return error(StatusCode::UNAUTHORIZED, format!("Missing or wrong {}", whatsapp::SIGNATURE_HEADER));
+    }
+    let notification: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(notification) => notification,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid notification: {}", e)),
+    };
+    // Meta retries anything not answered with a 2xx for days, so failures
+    // past this point are logged and answered 200 all the same
+    whatsapp.handle(&notification, &hub, &upstream.fixed, &metrics).await;
+    HttpResponse::Ok().finish()
+}
+
+/// POST /channel/whatsapp/send {"to", "text", "reply_to", "buttons",
+/// "list", "template"}: the answer to a relayed message
+async fn whatsapp_send_handler(
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+    whatsapp: web::Data<whatsapp::WhatsApp>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ WhatsApp: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !whatsapp.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "WhatsApp is not configured: set access_token in [whatsapp]".to_string());
+    }
+    let req: whatsapp::SendRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    if let Err(message) = req.messages() {
+        return error(StatusCode::BAD_REQUEST, message);
+    }
+    if !whatsapp.may_send(&req.to) {
+        return error(StatusCode::FORBIDDEN, format!("{} has not written to the bot and is not in allowed_numbers", req.to));
+    }
+    if let Err(response) = limits.check_host(whatsapp::HOST) {
+        return *response;
+    }
+
+    let sent = whatsapp.send(&upstream.fixed, &req);
+    match metrics.upstream("/channel/whatsapp/send", sent).await {
+        Ok(messages) => {
+            let summary = req.template.as_ref().map(|t| format!("[template {}]", t.name)).unwrap_or_else(|| req.text.clone());
+            whatsapp.remember(&req.to, "assistant", &summary);
+            HttpResponse::Ok()
+                .insert_header(("Access-Control-Allow-Origin", "*"))
+                .json(serde_json::json!({ "sent": true, "messages": messages }))
+        }
+        Err(message) => error(StatusCode::BAD_GATEWAY, format!("WhatsApp: {}", message)),
+    }
+}
+
+/// GET /ws?channels=discord,slack,telegram,...: a WebSocket that receives the events of
+/// the realtime channels as they arrive
+async fn ws_handler(
+    query: web::Query<HashMap<String, String>>,
+    http_req: HttpRequest,
+    body: web::Payload,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ WebSocket: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if hub.channels().is_empty() {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Realtime channels are not configured: set discord_token or slack_app_token in [realtime], signing_secret in [slack], bot_token in [telegram], a [whatsapp], [mastodon] or [bluesky] bot, or an [imap] inbox".to_string(),
+        );
+    }
+    let channels = match hub.select(query.get("channels").map(String::as_str)) {
+        Ok(channels) => channels,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let (response, mut session, mut incoming) = match actix_ws::handle(&http_req, body) {
+        Ok(handshake) => handshake,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Not a WebSocket request: {}", e)),
+    };
+
+    let mut events = hub.subscribe();
+    let names: Vec<&str> = channels.iter().map(|c| c.name()).collect();
+    eprintln!("→ WebSocket client for {}", names.join(", "));
+    let listening = hub.clone().into_inner().listen(channels);
+    actix_web::rt::spawn(async move {
+        let text = |event: &realtime::Event| serde_json::to_string(event).unwrap_or_default();
+        for event in hub.status_events(&listening.channels) {
+            if session.text(text(&event)).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            tokio::select! {
+                event = events.recv() => {
+                    let message = match event {
+                        Ok(event) if names.contains(&event.channel) => text(&event),
+                        Ok(_) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                            serde_json::json!({"channel": "proxy", "type": "lagged", "data": {"missed": missed}}).to_string()
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    };
+                    if session.text(message).await.is_err() {
+                        return;
+                    }
+                }
+                message = incoming.recv() => match message {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(reason))) => {
+                        let _ = session.close(reason).await;
+                        return;
+                    }
+                    // The socket only carries events to the client
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => break,
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+    response
+}
+
+/// GET /inbox?channels=telegram,slack&wait=25: take the queued messages of
+/// the channels, waiting up to `wait` seconds for one
+async fn inbox_handler(
+    query: web::Query<HashMap<String, String>>,
+    hub: web::Data<realtime::Hub>,
+) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !hub.inbox.enabled() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "The inbox is off: set inbox_secs in [realtime]".to_string());
+    }
+    if hub.channels().is_empty() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Realtime channels are not configured".to_string());
+    }
+    let channels = match hub.select(query.get("channels").map(String::as_str)) {
+        Ok(channels) => channels,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let wait = match query.get("wait").map(|w| w.parse::<u64>()) {
+        None => std::time::Duration::from_secs(25),
+        Some(Ok(secs)) => std::time::Duration::from_secs(secs),
+        Some(Err(_)) => return error(StatusCode::BAD_REQUEST, "wait must be a number of seconds".to_string()),
+    };
+
+    let events = hub.inbox.poll(&channels, wait).await;
+    if !events.is_empty() {
+        eprintln!("→ Inbox: {} message(s) taken", events.len());
+    }
+    HttpResponse::Ok()
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .json(serde_json::json!({ "events": events.iter().map(AsRef::as_ref).collect::<Vec<&realtime::Event>>() }))
+}
+
+async fn ollama_search_handler(
+    req: HttpRequest,
+    body: web::Bytes,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    if let Err(response) = limits.check_host("ollama.com") {
+        return *response;
+    }
+    let client = &upstream.fixed;
+    
+    // Get Authorization header from request
+    let auth_header = req.headers().get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    
+    // Forward to Ollama Web Search API
+    let mut request = client.post("https://ollama.com/api/web_search")
+        .header("Content-Type", "application/json");
+    
+    if let Some(auth) = auth_header {
+        request = request.header("Authorization", auth);
+    }
+    
+    request = request.body(Vec::from(body));
+    
+    match metrics.upstream("/ollama-search", upstream.send(request)).await {
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            HttpResponse::build(
+                actix_web::http::StatusCode::from_u16(status.as_u16())
+                    .unwrap_or(actix_web::http::StatusCode::OK)
+            )
+            .content_type("application/json")
+            .body(body)
+        }
+        Err(e) => {
+            HttpResponse::InternalServerError().body(format!("Ollama search error: {}", e))
+        }
+    }
+}
+
+async fn reddit_search_handler(
+    query: web::Query<HashMap<String, String>>,
+    limits: web::Data<Limits>,
+    metrics: web::Data<Metrics>,
+    upstream: web::Data<Upstream>,
+) -> HttpResponse {
+    if let Err(response) = limits.check_host("www.reddit.com") {
+        return *response;
+    }
+    let search_query = query.get("q").cloned().unwrap_or_default();
+    let subreddit = query.get("subreddit").cloned().unwrap_or_else(|| "all".to_string());
+    let limit: usize = query.get("limit")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10);
+    
+    let client = &upstream.fixed;
+    
+    // Use Reddit's JSON API (no auth required for read-only)
+    let url = format!(
+        "https://www.reddit.com/r/{}/search.json?q={}&restrict_sr=on&limit={}&sort=relevance",
+        subreddit,
+        urlencoding::encode(&search_query),
+        limit
+    );
+    
+    // If subreddit is "all", use the regular search endpoint
+    let url = if subreddit == "all" {
+        format!(
+            "https://www.reddit.com/search.json?q={}&limit={}&sort=relevance",
+            urlencoding::encode(&search_query),
+            limit
+        )
+    } else {
+        url
+    };
+    
+    let request = client.get(&url)
+        .header("User-Agent", "claWasm/0.1.0")
+        .header("Accept", "application/json");
+    match metrics.upstream("/reddit/search", upstream.send(request)).await {
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            
+            // Parse Reddit response and format it
+            if let Ok(reddit_response) = serde_json::from_str::<RedditResponse>(&body) {
+                let posts: Vec<RedditPostFormatted> = reddit_response.data.children.iter()
+                    .filter_map(|c| {
+                        let d = &c.data;
+                        Some(RedditPostFormatted {
+                            title: d.title.clone(),
+                            subreddit: d.subreddit.clone(),
+                            selftext: d.selftext.clone().unwrap_or_default(),
+                            score: d.score.unwrap_or(0),
+                            num_comments: d.num_comments.unwrap_or(0),
+                            url: format!("https://reddit.com{}", d.permalink.clone().unwrap_or_default()),
+                        })
+                    })
+                    .take(limit)
+                    .collect();
+                
+                let result = RedditSearchResult { posts };
+                
+                return HttpResponse::build(
+                    actix_web::http::StatusCode::from_u16(status.as_u16())
+                        .unwrap_or(actix_web::http::StatusCode::OK)
+                )
+                .content_type("application/json")
+                .json(&result);
+            }
+            
+            HttpResponse::build(
+                actix_web::http::StatusCode::from_u16(status.as_u16())
+                    .unwrap_or(actix_web::http::StatusCode::OK)
+            )
+            .content_type("application/json")
+            .body(body)
+        }
+        Err(e) => {
+            HttpResponse::InternalServerError().body(format!("Reddit search error: {}", e))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditResponse {
+    data: RedditData,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditData {
+    children: Vec<RedditChild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditChild {
+    data: RedditPostData,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditPostData {
+    title: String,
+    subreddit: String,
+    selftext: Option<String>,
+    score: Option<i32>,
+    num_comments: Option<i32>,
+    permalink: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RedditSearchResult {
+    posts: Vec<RedditPostFormatted>,
+}
+
+#[derive(Debug, Serialize)]
+struct RedditPostFormatted {
+    title: String,
+    subreddit: String,
+    selftext: String,
+    score: i32,
+    num_comments: i32,
+    url: String,
+}
+
+/// Liveness check used by the WASM side's diagnose()
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "service": "clawasm-proxy",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+async fn index() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(r#"<!DOCTYPE html>
+<html>
+<head><title>claWasm Proxy</title></head>
+<body>
+    <h1>claWasm CORS Proxy</h1>
+    <p>Proxy is running!</p>
+    <h2>Endpoints:</h2>
+    <ul>
+        <li>POST /proxy - Generic proxy (JSON body: {"url": "...", "method": "GET", "headers": {}, "body": null}); binary responses keep their bytes and Content-Type, or add "response_format": "base64" for a JSON envelope</li>
+        <li>POST /proxy-stream - Same body as /proxy; forwards SSE and chunked responses as they arrive</li>
+        <li>GET /search?q=query&amp;count=10 - Web search with the configured backend: {"query", "backend", "results": [{"title", "url", "snippet"}]}</li>
+        <li>GET /images?q=query&amp;count=10 - Image search (Openverse, Wikimedia Commons, Bing): {"query", "results": [{"title", "url", "thumbnail", "width", "height", "license", "license_url", "creator", "page_url", "source"}]}</li>
+        <li>GET /render?url=...&amp;format=html|png&amp;wait_ms=0 - Load a page in headless Chromium and return the HTML after scripts ran, or a screenshot (feature "render")</li>
+        <li>POST /transcribe?language=en - Speech to text with OpenAI Whisper or whisper.cpp; audio as the body, or ?url= of an audio file: {"text", "language", "duration", "segments": [{"start", "end", "text"}]}</li>
+        <li>POST /tts - Text to speech with OpenAI, ElevenLabs or Piper (JSON body: {"text": "...", "voice": null, "format": "mp3|ogg|wav", "speed": 1.0}); long text is chunked and streamed</li>
+        <li>POST /osv/batch - OSV advisories for many packages at once (JSON body: {"packages": [{"name", "ecosystem", "version"}]}): {"results": [{"name", "ecosystem", "version", "vulns": [OSV records]}], "cached", "missing"}</li>
+        <li>GET /rss?url=...&amp;count=20 - RSS or Atom feed as JSON: {"title", "link", "description", "format", "items": [{"title", "link", "published", "author", "summary"}]}</li>
+        <li>GET /youtube/transcript?id=...&amp;lang=en - YouTube captions: {"id", "title", "language", "generated", "text", "segments": [{"start", "duration", "text"}], "languages"}</li>
+        <li>POST /channel/email/send - Send mail through the configured SMTP server (JSON body: {"to": [...], "cc": [], "subject", "text", "html", "attachments": [{"filename", "content_type", "data": base64}]})</li>
+        <li>POST /channel/email/reply - Answer mail relayed from the IMAP inbox, in its thread (JSON body: {"reply_to", "text", "html"})</li>
+        <li>POST /channel/discord/interactions - Discord Interactions Endpoint URL for the /ask, /research and /scan slash commands (Ed25519-signed by Discord, no proxy token)</li>
+        <li>POST /channel/discord/send - Answer a Discord mention, DM or slash command (JSON body: {"channel_id", "text", "reply_to", "interaction_id"})</li>
+        <li>POST /channel/slack/command - Slack slash command and interactivity Request URL (signed like the events); commands and button presses go to /ws</li>
+        <li>POST /channel/slack/events - Slack Events API Request URL (signed with the app's signing secret, no proxy token); mentions, DMs and thread replies go to /ws</li>
+        <li>POST /channel/slack/send - Answer a Slack mention, DM or command (JSON body: {"channel", "text", "thread_ts", "format", "buttons", "command_id"})</li>
+        <li>POST /channel/telegram/webhook - Telegram updates (checked against webhook_secret); text messages go to /ws listeners</li>
+        <li>POST /channel/telegram/send - Answer a Telegram chat (JSON body: {"chat_id", "text", "reply_to", "format", "files", "buttons"})</li>
+        <li>GET /channel/whatsapp/webhook - WhatsApp callback URL check (answers hub.challenge when hub.verify_token matches)</li>
+        <li>POST /channel/whatsapp/webhook - WhatsApp Cloud API notifications (signed with the app secret, no proxy token); messages and button taps go to /ws</li>
+        <li>POST /channel/whatsapp/send - Answer a WhatsApp message (JSON body: {"to", "text", "reply_to", "buttons", "list", "template"})</li>
+        <li>POST /channel/mastodon/send - Answer a Mastodon mention, or post (JSON body: {"text", "reply_to", "visibility", "spoiler_text"})</li>
+        <li>POST /channel/bluesky/send - Answer a Bluesky mention, or post (JSON body: {"text", "reply_to"})</li>
+        <li>GET /channels - Named channel instances of the config: {"channels": [{"id", "kind", "description"}]}</li>
+        <li>POST /channels/{id}/send - Message a named channel instance with its configured credentials (JSON body: {"text"})</li>
+        <li>POST /notify - One notification to several channel instances (JSON body: {"channels": ["id", ...] or ["*"], "title", "message", "level": "info" | "success" | "warning" | "critical"}); answers {"sent", "failed"}</li>
+        <li>POST /paste - With [paste] enabled, keep a conversation encrypted in the browser (JSON body: {"data": base64 ciphertext}); answers {"id", "ttl_hours"}</li>
+        <li>GET /paste/{id} - The ciphertext of a shared conversation, {"data"}; no token needed, the key stays in the link's fragment</li>
+        <li>GET /ws?channels=discord,slack,telegram,whatsapp,mastodon,bluesky,email - WebSocket with Discord gateway, Slack, Telegram, WhatsApp, Mastodon, Bluesky and inbox events as they happen: {"channel", "type", "data"} per message; the token may go in ?token=</li>
+        <li>GET /inbox?channels=telegram,slack&amp;wait=25 - Long poll for the queued messages of the chat channels, each handed to one poller: {"events": [{"channel", "type", "data"}]}</li>
+        <li>POST /ollama-search - Ollama Web Search API (JSON body: {"query": "...", "max_results": 5})</li>
+        <li>GET /reddit/search?q=query - Reddit search</li>
+        <li>GET /tls-info?host=example.com&amp;port=443 - TLS handshake, protocols and certificate chain</li>
+        <li>GET /health - Liveness check</li>
+        <li>GET /metrics - Prometheus metrics (request counts, latency, upstream errors)</li>
+        <li>GET /endpoints - This page; with a built web UI, / serves the app instead</li>
+    </ul>
+</body>
+</html>"#)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let args = config::Args::parse();
+    let config = match ProxyConfig::load(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+    let tls = match (&config.tls.cert, &config.tls.key) {
+        (Some(cert), Some(key)) => match https::server_config(cert, key) {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(2);
+            }
+        },
+        _ => None,
+    };
+
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    println!("🚀 claWasm CORS Proxy starting on {}://{}", scheme, config.address());
+    println!("   POST /proxy - Generic proxy endpoint");
+    println!("   POST /proxy-stream - Streaming proxy (SSE, chunked)");
+    println!("   GET /search?q=query - Web search (configured backend)");
+    println!("   GET /images?q=query - Image search with licenses and dimensions");
+    println!("   GET /render?url=... - Rendered HTML or screenshot (headless Chromium)");
+    println!("   POST /transcribe - Speech to text (Whisper)");
+    println!("   POST /tts - Text to speech");
+    println!("   POST /osv/batch - Vulnerabilities for many packages (OSV)");
+    println!("   GET /rss?url=... - RSS and Atom feeds as JSON");
+    println!("   GET /youtube/transcript?id=... - YouTube captions as text");
+    println!("   POST /channel/email/send - Email through SMTP");
+    println!("   POST /channel/email/reply - Replies to inbox mail");
+    println!("   POST /channel/discord/interactions - Discord slash commands");
+    println!("   POST /channel/discord/send - Discord replies");
+    println!("   POST /channel/slack/command - Slack slash commands and buttons");
+    println!("   POST /channel/slack/events - Slack Events API");
+    println!("   POST /channel/slack/send - Slack replies");
+    println!("   POST /channel/telegram/webhook - Telegram updates");
+    println!("   POST /channel/telegram/send - Telegram replies");
+    println!("   GET|POST /channel/whatsapp/webhook - WhatsApp Cloud API notifications");
+    println!("   POST /channel/whatsapp/send - WhatsApp replies");
+    println!("   POST /channel/mastodon/send - Mastodon replies and posts");
+    println!("   POST /channel/bluesky/send - Bluesky replies and posts");
+    println!("   GET /channels, POST /channels/{{id}}/send - Named channel instances");
+    println!("   POST /notify - Notifications to channel instances");
+    println!("   POST /paste, GET /paste/{{id}} - Encrypted conversation shares");
+    println!("   GET /ws - Discord, Slack, Telegram, WhatsApp, Mastodon, Bluesky and inbox events (WebSocket)");
+    println!("   GET /inbox - Queued channel messages (long poll)");
+    println!("   POST /ollama-search - Ollama Web Search API");
+    println!("   GET /reddit/search?q=query - Reddit search");
+    println!("   GET /tls-info?host=example.com - TLS certificate and protocol details");
+    println!("   GET /health - Liveness check");
+    println!("   GET /metrics - Prometheus metrics");
+    println!("   GET /endpoints - Endpoint list");
+    if let Some(path) = &args.config {
+        println!("   Config: {}", path.display());
+    }
+    
+    let token = ProxyToken(config.token.clone());
+    if token.0.is_some() {
+        println!("🔒 Requests must carry the {} header", auth::TOKEN_HEADER);
+    }
+    let policy = Arc::new(config.targets.clone());
+    let cache = web::Data::new(ResponseCache::from_config(&config.cache));
+    if cache.enabled() {
+        println!("   Caching GET responses for {}s", cache.ttl_secs());
+    }
+    let limits = web::Data::new(Limits::new(&config.limits));
+    println!(
+        "   Limits: {}/min per client, {}/min per target host, {} concurrent (0 = off)",
+        limits.per_client.per_minute(), limits.per_host.per_minute(), limits.max_concurrent,
+    );
+    let metrics = web::Data::new(Metrics::new(config.access_log));
+    let upstream = match Upstream::new(&config.upstream, &policy) {
+        Ok(upstream) => web::Data::new(upstream),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+    let search = web::Data::new(config.search.clone());
+    println!("   Search backend: {}", search.backend.name());
+    let images = web::Data::new(config.images.clone());
+    println!(
+        "   Image sources: {}",
+        images.sources.iter().map(|s| s.name()).collect::<Vec<_>>().join(", "),
+    );
+    let transcribe = web::Data::new(config.transcribe.clone());
+    println!("   Transcription: {}", transcribe.backend.name());
+    let tts = web::Data::new(config.tts.clone());
+    println!("   Text to speech: {}", tts.backend.name());
+    let mailer = match email::Mailer::new(&config.email) {
+        Ok(mailer) => web::Data::new(mailer),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Some(host) = &config.email.host {
+        let recipients = match config.email.allowed_recipients.is_empty() {
+            true => "any recipient".to_string(),
+            false => config.email.allowed_recipients.join(", "),
+        };
+        println!("   Email: {} via {} to {}", config.email.from.as_deref().unwrap_or(""), host, recipients);
+    }
+    let relayed: Vec<realtime::Channel> = [
+        (realtime::Channel::Bluesky, config.bluesky.app_password.is_some()),
+        (realtime::Channel::Email, config.imap.host.is_some()),
+        (realtime::Channel::Mastodon, config.mastodon.access_token.is_some()),
+        (realtime::Channel::Slack, config.slack.signing_secret.is_some()),
+        (realtime::Channel::Telegram, config.telegram.bot_token.is_some()),
+        (realtime::Channel::WhatsApp, config.whatsapp.access_token.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(channel, configured)| configured.then_some(channel))
+    .collect();
+    let hub = Arc::new(realtime::Hub::new(&config.realtime, &relayed));
+    let sessions = Arc::new(sessions::Sessions::new(&config.sessions).with_rate(realtime::Channel::Telegram, config.telegram.chat_rate));
+    let discord = web::Data::new(discord::Discord::new(&config.discord, &config.realtime, sessions.clone()));
+    if discord.enabled() {
+        let task = discord.clone().into_inner().run(hub.clone(), upstream.fixed.clone(), metrics.clone().into_inner());
+        tokio::spawn(task);
+    }
+    let slack = web::Data::new(slack::Slack::new(&config.slack, sessions.clone()));
+    if slack.enabled() {
+        let task = slack.clone().into_inner().run(hub.clone(), upstream.fixed.clone(), metrics.clone().into_inner());
+        tokio::spawn(task);
+    }
+    if slack.events_enabled() && config.realtime.slack_app_token.is_none() {
+        println!("   Slack: set the app's Request URL to <this proxy>/channel/slack/events");
+        hub.set_status(realtime::Channel::Slack, true, None);
+    }
+    let channels = hub.channels();
+    if !channels.is_empty() {
+        println!("   Realtime: {}", channels.iter().map(|c| c.name()).collect::<Vec<_>>().join(", "));
+        hub.start(upstream.fixed.clone());
+    }
+
+    let telegram = web::Data::new(telegram::Telegram::new(&config.telegram, sessions.clone()));
+    let (stop_polling, polling_stopped) = tokio::sync::watch::channel(false);
+    let mut poller = None;
+    if telegram.enabled() {
+        if telegram.is_open() {
+            println!("⚠️  Telegram: anyone who finds the bot can use your API keys; set allowed_users");
+        }
+        let (telegram, client) = (telegram.clone(), upstream.fixed.clone());
+        tokio::spawn(async move {
+            if let Err(e) = telegram.set_commands(&client).await {
+                eprintln!("⚠️  Telegram setMyCommands: {}", e);
+            }
+        });
+    }
+    if telegram.polling() {
+        let task = telegram.clone().into_inner().poll(hub.clone(), upstream.fixed.clone(), metrics.clone().into_inner(), polling_stopped);
+        poller = Some(tokio::spawn(task));
+    } else if telegram.enabled() {
+        match telegram.webhook_url() {
+            Some(url) => {
+                let (telegram, hub, client) = (telegram.clone(), hub.clone(), upstream.fixed.clone());
+                tokio::spawn(async move {
+                    match telegram.register(&client, &url).await {
+                        Ok(()) => {
+                            println!("   Telegram webhook: {}", url);
+                            hub.set_status(realtime::Channel::Telegram, true, None);
+                        }
+                        Err(e) => {
+                            eprintln!("❌ Telegram setWebhook: {}", e);
+                            hub.set_status(realtime::Channel::Telegram, false, Some(format!("setWebhook: {}", e)));
+                        }
+                    }
+                });
+            }
+            None => {
+                println!("   Telegram: set the webhook to <this proxy>/channel/telegram/webhook yourself, or set public_url");
+                hub.set_status(realtime::Channel::Telegram, true, None);
+            }
+        }
+    }
+    let mastodon = web::Data::new(mastodon::Mastodon::new(&config.mastodon));
+    if mastodon.enabled() {
+        let task = mastodon.clone().into_inner().run(hub.clone(), upstream.fixed.clone(), metrics.clone().into_inner());
+        tokio::spawn(task);
+    }
+    let bluesky = web::Data::new(bluesky::Bluesky::new(&config.bluesky));
+    if bluesky.enabled() {
+        let task = bluesky.clone().into_inner().run(hub.clone(), upstream.fixed.clone(), metrics.clone().into_inner());
+        tokio::spawn(task);
+    }
+    let inbox = web::Data::new(imap::Inbox::new(&config.imap));
+    if inbox.enabled() {
+        let task = inbox.clone().into_inner().run(hub.clone(), metrics.clone().into_inner());
+        tokio::spawn(task);
+    }
+    let whatsapp = web::Data::new(whatsapp::WhatsApp::new(&config.whatsapp, sessions));
+    let channels = web::Data::new(channels::Channels::new(&config.channels));
+    if whatsapp.enabled() {
+        println!("   WhatsApp: set the app's callback URL to <this proxy>/channel/whatsapp/webhook and subscribe to messages");
+        hub.set_status(realtime::Channel::WhatsApp, true, None);
+    }
+    let hub = web::Data::from(hub);
+    let pastes = web::Data::new(paste::Pastes::new(&config.paste));
+    #[cfg(feature = "render")]
+    let renderer = web::Data::new(render::Renderer::new(&config.render, &policy));
+    if config.upstream.insecure_tls {
+        println!("⚠️  Upstream certificates are not verified (insecure_tls)");
+    }
+    let loopback = config.bind.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+        || config.bind == "localhost";
+    if !loopback && token.0.is_none() {
+        println!("⚠️  Listening on {} without a token: anyone who can reach it can use the proxy", config.bind);
+    }
+    if policy.allow_private {
+        println!("⚠️  Private and loopback targets are reachable (allow_private)");
+    }
+    if !policy.allow_hosts.is_empty() {
+        println!("   Target allowlist: {}", policy.allow_hosts.join(", "));
+    }
+    
+    let site = config.web.site().map(Path::to_path_buf);
+    match &site {
+        Some(dir) => {
+            println!("   Web UI: {} at /", dir.display());
+            if !dir.join("pkg").is_dir() {
+                println!("   ⚠️  {} has no pkg/; see Build & Run in the README for the wasm-bindgen step", dir.display());
+            }
+        }
+        None if config.web.enabled => println!("   Web UI: {} not found; serving the API only", config.web.dir.join("index.html").display()),
+        None => {}
+    }
+
+    let server = HttpServer::new(move || {
+        let cors = Cors::default()
+            .allow_any_origin()
+            .allow_any_method()
+            .allow_any_header()
+            .max_age(3600);
+        
+        // Middleware runs in reverse order of wrapping. CORS answers
+        // preflights before anything else, metrics see every other request
+        // (rejected ones included), and rejected tokens never use up rate
+        // limit budget
+        let app = App::new()
+            .wrap(actix_web::middleware::from_fn(limits::limit_requests))
+            .wrap(actix_web::middleware::from_fn(auth::require_token))
+            .wrap(actix_web::middleware::from_fn(metrics::observe))
+            .wrap(cors)
+            .app_data(web::Data::new(token.clone()))
+            .app_data(web::Data::from(policy.clone()))
+            .app_data(cache.clone())
+            .app_data(limits.clone())
+            .app_data(metrics.clone())
+            .app_data(upstream.clone())
+            .app_data(search.clone())
+            .app_data(images.clone())
+            .app_data(transcribe.clone())
+            .app_data(tts.clone())
+            .app_data(mailer.clone())
+            .app_data(inbox.clone())
+            .app_data(hub.clone())
+            .app_data(discord.clone())
+            .app_data(slack.clone())
+            .app_data(telegram.clone())
+            .app_data(whatsapp.clone())
+            .app_data(mastodon.clone())
+            .app_data(bluesky.clone())
+            .app_data(channels.clone())
+            .app_data(pastes.clone())
+            .app_data(actix_web::web::JsonConfig::default().limit(52428800)) // 50MB
+            .app_data(actix_web::web::PayloadConfig::default().limit(52428800)) // 50MB
+            .route("/endpoints", web::get().to(index))
+            .route("/proxy", web::post().to(proxy_handler))
+            .route("/proxy", web::method(actix_web::http::Method::OPTIONS).to(proxy_options))
+            .route("/proxy-stream", web::post().to(proxy_stream_handler))
+            .route("/proxy-stream", web::method(actix_web::http::Method::OPTIONS).to(proxy_options))
+            .route("/search", web::get().to(web_search_handler))
+            .route("/images", web::get().to(images_handler))
+            .route("/transcribe", web::post().to(transcribe_handler))
+            .route("/tts", web::post().to(tts_handler))
+            .route("/osv/batch", web::post().to(osv_batch_handler))
+            .route("/rss", web::get().to(rss_handler))
+            .route("/youtube/transcript", web::get().to(youtube_transcript_handler))
+            .route("/channel/email/send", web::post().to(email_send_handler))
+            .route("/channel/email/reply", web::post().to(email_reply_handler))
+            .route("/channel/discord/interactions", web::post().to(discord_interactions_handler))
+            .route("/channel/discord/send", web::post().to(discord_send_handler))
+            .route("/channel/slack/command", web::post().to(slack_command_handler))
+            .route("/channel/slack/events", web::post().to(slack_events_handler))
+            .route("/channel/slack/send", web::post().to(slack_send_handler))
+            .route("/channel/telegram/webhook", web::post().to(telegram_webhook_handler))
+            .route("/channel/telegram/send", web::post().to(telegram_send_handler))
+            .route("/channel/whatsapp/webhook", web::get().to(whatsapp_verify_handler))
+            .route("/channel/whatsapp/webhook", web::post().to(whatsapp_webhook_handler))
+            .route("/channel/whatsapp/send", web::post().to(whatsapp_send_handler))
+            .route("/channel/mastodon/send", web::post().to(mastodon_send_handler))
+            .route("/channel/bluesky/send", web::post().to(bluesky_send_handler))
+            .route("/channels", web::get().to(channels_handler))
+            .route("/channels/{id}/send", web::post().to(channel_send_handler))
+            .route("/notify", web::post().to(notify_handler))
+            .route("/paste", web::post().to(paste::create_handler))
+            .route("/paste/{id}", web::get().to(paste::get_handler))
+            .route("/ws", web::get().to(ws_handler))
+            .route("/inbox", web::get().to(inbox_handler))
+            .route("/ollama-search", web::post().to(ollama_search_handler))
+            .route("/reddit/search", web::get().to(reddit_search_handler))
+            .route("/tls-info", web::get().to(tls_info_handler))
+            .route("/health", web::get().to(health))
+            .route("/metrics", web::get().to(metrics::metrics_handler))
+            .route("/render", web::get().to(render_handler));
+        #[cfg(feature = "render")]
+        let app = app.app_data(renderer.clone());
+        match &site {
+            Some(dir) => app.default_service(actix_files::Files::new("/", dir).index_file("index.html")),
+            None => app.route("/", web::get().to(index)),
+        }
+    });
+    let server = match tls {
+        Some(tls) => server.bind_rustls_0_23(config.address(), tls)?,
+        None => server.bind(config.address())?,
+    };
+    let result = server.run().await;
+    // Let the poller finish its batch and save the offset
+    let _ = stop_polling.send(true);
+    if let Some(poller) = poller {
+        if tokio::time::timeout(std::time::Duration::from_secs(5), poller).await.is_err() {
+            eprintln!("⚠️  Telegram polling did not stop in time");
+        }
+    }
+    result
+}
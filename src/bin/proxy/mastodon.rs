@@ -0,0 +1,479 @@
+//! Mastodon bot on the streaming API of any instance
+//!
+//! With `access_token` set, the proxy keeps the instance's
+//! `user:notification` stream open, reconnecting with backoff and catching
+//! up on mentions it missed meanwhile. A mention of the bot from an allowed
+//! account becomes a `mention` event on `/ws`, with the leading mentions
+//! removed and the account's recent turns:
+//!
+//! ```json
+//! {"channel": "mastodon", "type": "mention", "data": {"status_id": "1130", "acct": "ann@example.social",
+//!   "name": "Ann", "visibility": "public", "url": "https://...", "text": "...",
+//!   "history": [{"role": "user", "content": "..."}]}}
+//! ```
+//!
+//! The browser answers with POST /channel/mastodon/send `{"text",
+//! "reply_to"}`, posted as a thread of replies that mention the account,
+//! with the mention's visibility. Without `reply_to` the same route
+//! publishes a new post, which is how the `publish_post` tool shares
+//! summaries and digests.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Hub};
+use crate::search::plain;
+use crate::telegram::{chunks, Turn};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// The streaming server pings every 30 seconds; silence this long means
+/// the connection is gone
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Mentions remembered so their replies can be threaded
+const PENDING_MENTIONS: usize = 200;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    Unlisted,
+    /// Followers only
+    Private,
+    /// Mentioned accounts only
+    Direct,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MastodonConfig {
+    /// The bot account's instance, e.g. `mastodon.social`
+    pub instance: Option<String>,
+    /// Token with read:notifications and write:statuses; the bot is off without one
+    pub access_token: Option<String>,
+    /// Accounts (`user` on the instance, `user@domain` elsewhere) the bot answers; empty answers anyone
+    pub allowed_accounts: Vec<String>,
+    /// Of new posts; replies keep the mention's
+    pub visibility: Visibility,
+    /// The instance's post length limit
+    pub max_chars: usize,
+    /// Messages per account kept as context
+    pub history: usize,
+    /// Sent when no browser is connected to answer
+    pub offline_reply: Option<String>,
+}
+
+impl Default for MastodonConfig {
+    fn default() -> Self {
+        MastodonConfig {
+            instance: None,
+            access_token: None,
+            allowed_accounts: Vec::new(),
+            visibility: Visibility::Public,
+            max_chars: 500,
+            history: 20,
+            offline_reply: Some("The assistant is offline right now. Please try again later.".to_string()),
+        }
+    }
+}
+
+impl MastodonConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.access_token.is_none() {
+            return Ok(());
+        }
+        let Some(instance) = &self.instance else {
+            return Err("mastodon needs instance with access_token".to_string());
+        };
+        match url::Url::parse(&base(instance)) {
+            Ok(url) if url.host_str().is_some() && url.path() == "/" => {}
+            _ => return Err(format!("mastodon instance must be a host name like mastodon.social, not {:?}", instance)),
+        }
+        if self.max_chars < 100 {
+            return Err("mastodon max_chars must be at least 100".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `https://` and the instance, however it was written
+fn base(instance: &str) -> String {
+    let instance = instance.trim().trim_end_matches('/');
+    match instance.starts_with("https://") || instance.starts_with("http://") {
+        true => instance.to_string(),
+        false => format!("https://{}", instance),
+    }
+}
+
+/// An account as written in a mention, without the leading `@`
+fn account(acct: &str) -> String {
+    acct.trim().trim_start_matches('@').to_lowercase()
+}
+
+/// A status's HTML as plain text, paragraphs and line breaks kept
+pub fn text(content: &str) -> String {
+    let content = content.replace("<br />", "\n").replace("<br/>", "\n").replace("<br>", "\n").replace("</p>", "\n\n");
+    let lines: Vec<String> = content.lines().map(plain).collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// A mention of the bot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    #[serde(skip)]
+    pub notification_id: String,
+    pub status_id: String,
+    pub acct: String,
+    pub name: String,
+    pub visibility: Visibility,
+    pub url: Option<String>,
+    pub text: String,
+}
+
+/// The mention in a notification, if it is one
+pub fn incoming(notification: &Value) -> Option<Incoming> {
+    if notification["type"] != "mention" {
+        return None;
+    }
+    let status = &notification["status"];
+    let account = &notification["account"];
+    let words = text(status["content"].as_str()?);
+    // The leading @mentions address the bot and whoever else was in the thread
+    let rest = words.trim_start();
+    let mut start = 0;
+    for word in rest.split_inclusive(char::is_whitespace) {
+        if !word.starts_with('@') {
+            break;
+        }
+        start += word.len();
+    }
+    let acct = account["acct"].as_str()?.to_string();
+    Some(Incoming {
+        notification_id: notification["id"].as_str()?.to_string(),
+        status_id: status["id"].as_str()?.to_string(),
+        name: account["display_name"].as_str().filter(|n| !n.is_empty()).unwrap_or(&acct).to_string(),
+        acct,
+        visibility: serde_json::from_value(status["visibility"].clone()).unwrap_or_default(),
+        url: status["url"].as_str().map(str::to_string),
+        text: rest[start..].trim().to_string(),
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub text: String,
+    /// The mention's `status_id`; a new post without it
+    pub reply_to: Option<String>,
+    /// Of a new post; `visibility` in the config when unset
+    pub visibility: Option<Visibility>,
+    /// Content warning shown before the text
+    pub spoiler_text: Option<String>,
+}
+
+/// Where the answer to a mention goes
+#[derive(Debug, Clone)]
+struct Pending {
+    status_id: String,
+    acct: String,
+    visibility: Visibility,
+}
+
+/// The bot's settings, the recent turns with every account, and the
+/// mentions waiting for an answer
+pub struct Mastodon {
+    pub config: MastodonConfig,
+    chats: Mutex<HashMap<String, VecDeque<Turn>>>,
+    pending: Mutex<VecDeque<Pending>>,
+    /// Newest notification handled, to catch up from after a reconnect
+    last_seen: Mutex<Option<String>>,
+}
+
+impl Mastodon {
+    pub fn new(config: &MastodonConfig) -> Self {
+        Mastodon {
+            config: config.clone(),
+            chats: Mutex::new(HashMap::new()),
+            pending: Mutex::new(VecDeque::new()),
+            last_seen: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.access_token.is_some()
+    }
+
+    /// The instance's host name, for the per-host limits
+    pub fn host(&self) -> String {
+        let base = base(self.config.instance.as_deref().unwrap_or(""));
+        url::Url::parse(&base).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default()
+    }
+
+    pub fn allows(&self, acct: &str) -> bool {
+        self.config.allowed_accounts.is_empty() || self.config.allowed_accounts.iter().any(|allowed| account(allowed) == account(acct))
+    }
+
+    /// Whether `reply_to` is a mention the bot can answer
+    pub fn may_answer(&self, req: &SendRequest) -> bool {
+        req.reply_to.as_ref().is_none_or(|id| self.pending.lock().unwrap().iter().any(|p| &p.status_id == id))
+    }
+
+    pub fn history(&self, acct: &str) -> Vec<Turn> {
+        self.chats.lock().unwrap().get(&account(acct)).map(|turns| turns.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn remember(&self, acct: &str, role: &'static str, content: &str) {
+        let mut chats = self.chats.lock().unwrap();
+        let turns = chats.entry(account(acct)).or_default();
+        turns.push_back(Turn { role, content: content.to_string() });
+        while turns.len() > self.config.history {
+            turns.pop_front();
+        }
+    }
+
+    /// The account that wrote the mention `status_id`
+    pub fn author(&self, status_id: &str) -> Option<String> {
+        self.pending.lock().unwrap().iter().find(|p| p.status_id == status_id).map(|p| p.acct.clone())
+    }
+
+    fn forget(&self, acct: &str) {
+        self.chats.lock().unwrap().remove(&account(acct));
+    }
+
+    async fn call(&self, client: &reqwest::Client, method: reqwest::Method, path: &str, body: Option<&Value>) -> Result<Value, String> {
+        let url = format!("{}{}", base(self.config.instance.as_deref().unwrap_or("")), path);
+        let mut request = client.request(method, url).bearer_auth(self.config.access_token.as_deref().unwrap_or(""));
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let reply: Value = response.json().await.map_err(|e| e.to_string())?;
+        match status.is_success() {
+            true => Ok(reply),
+            false => Err(format!("{} {}", status.as_u16(), reply["error"].as_str().unwrap_or("request refused"))),
+        }
+    }
+
+    /// The statuses `req` becomes: replies open with the mention (so
+    /// direct ones stay visible to the account) and each part answers the
+    /// one before
+    fn parts(&self, req: &SendRequest) -> Result<(Vec<String>, Option<Pending>), String> {
+        if req.text.trim().is_empty() {
+            return Err("Missing 'text'".to_string());
+        }
+        let pending = match &req.reply_to {
+            Some(id) => Some(
+                self.pending.lock().unwrap().iter().find(|p| &p.status_id == id).cloned()
+                    .ok_or_else(|| format!("No recent mention {}", id))?,
+            ),
+            None => None,
+        };
+        let prefix = pending.as_ref().map(|p| format!("@{} ", p.acct)).unwrap_or_default();
+        let limit = self.config.max_chars.saturating_sub(prefix.chars().count());
+        let parts = chunks(&req.text, limit).into_iter().map(|part| format!("{}{}", prefix, part)).collect();
+        Ok((parts, pending))
+    }
+
+    /// Post `req`; the answer is the number of statuses and the first one's URL
+    pub async fn send(&self, client: &reqwest::Client, req: &SendRequest) -> Result<(usize, Option<String>), String> {
+        let (parts, pending) = self.parts(req)?;
+        let visibility = pending.as_ref().map(|p| p.visibility).or(req.visibility).unwrap_or(self.config.visibility);
+        let mut in_reply_to = pending.map(|p| p.status_id);
+        let mut url = None;
+        for part in &parts {
+            let body = json!({
+                "status": part,
+                "in_reply_to_id": in_reply_to,
+                "visibility": visibility,
+                "spoiler_text": req.spoiler_text.as_deref().unwrap_or(""),
+            });
+            let status = self.call(client, reqwest::Method::POST, "/api/v1/statuses", Some(&body)).await?;
+            in_reply_to = status["id"].as_str().map(str::to_string);
+            url = url.or_else(|| status["url"].as_str().map(str::to_string));
+        }
+        Ok((parts.len(), url))
+    }
+
+    async fn reply(&self, client: &reqwest::Client, metrics: &Metrics, mention: &Incoming, text: &str) {
+        let req = SendRequest { text: text.to_string(), reply_to: Some(mention.status_id.clone()), ..Default::default() };
+        if let Err(e) = metrics.upstream("mastodon:statuses", self.send(client, &req)).await {
+            eprintln!("❌ Mastodon reply: {}", e);
+        }
+    }
+
+    /// Relay one notification to the browsers on /ws, or answer it here
+    async fn handle(&self, notification: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        let Some(mention) = incoming(notification) else {
+            return;
+        };
+        *self.last_seen.lock().unwrap() = Some(mention.notification_id.clone());
+        if !self.allows(&mention.acct) {
+            eprintln!("⛔ Mastodon: ignored a mention by {} (not in allowed_accounts)", mention.acct);
+            return;
+        }
+        {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push_back(Pending { status_id: mention.status_id.clone(), acct: mention.acct.clone(), visibility: mention.visibility });
+            if pending.len() > PENDING_MENTIONS {
+                pending.pop_front();
+            }
+        }
+        if matches!(mention.text.as_str(), "/new" | "/reset") {
+            self.forget(&mention.acct);
+            self.reply(client, metrics, &mention, "Started a new conversation.").await;
+            return;
+        }
+        if hub.listeners(Channel::Mastodon) == 0 {
+            eprintln!("→ Mastodon mention by {} with no browser connected", mention.acct);
+            if let Some(offline) = &self.config.offline_reply {
+                self.reply(client, metrics, &mention, offline).await;
+            }
+            return;
+        }
+
+        eprintln!("→ Mastodon mention by {}", mention.acct);
+        let mut data = json!(mention);
+        data["history"] = json!(self.history(&mention.acct));
+        self.remember(&mention.acct, "user", &mention.text);
+        hub.relay(Channel::Mastodon, "mention", data);
+    }
+
+    /// Mentions since the last one handled, oldest first
+    async fn catch_up(&self, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) -> Result<(), String> {
+        let Some(since) = self.last_seen.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        let path = format!("/api/v1/notifications?types[]=mention&since_id={}&limit=30", since);
+        let missed = metrics.upstream("mastodon:notifications", self.call(client, reqwest::Method::GET, &path, None)).await?;
+        for notification in missed.as_array().into_iter().flatten().rev() {
+            self.handle(notification, hub, client, metrics).await;
+        }
+        Ok(())
+    }
+
+    /// One streaming connection, until it drops
+    async fn stream(&self, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) -> Result<(), String> {
+        let instance = base(self.config.instance.as_deref().unwrap_or(""));
+        let url = format!("{}/api/v1/streaming?stream=user:notification", instance.replacen("http", "ws", 1));
+        let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+        let authorization = format!("Bearer {}", self.config.access_token.as_deref().unwrap_or(""));
+        request.headers_mut().insert("Authorization", authorization.parse().map_err(|_| "access_token is not a valid header value")?);
+        let mut ws = match tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(request)).await {
+            Ok(Ok((ws, _))) => ws,
+            Ok(Err(e)) => return Err(e.to_string()),
+            Err(_) => return Err("connection timed out".to_string()),
+        };
+        hub.set_status(Channel::Mastodon, true, None);
+        if let Err(e) = self.catch_up(hub, client, metrics).await {
+            eprintln!("⚠️  Mastodon: could not fetch missed mentions: {}", e);
+        }
+        loop {
+            let message = match tokio::time::timeout(IDLE_TIMEOUT, ws.next()).await {
+                Ok(Some(Ok(message))) => message,
+                Ok(Some(Err(e))) => return Err(e.to_string()),
+                Ok(None) => return Err("connection closed".to_string()),
+                Err(_) => return Err("no ping from the streaming server".to_string()),
+            };
+            match message {
+                Message::Text(text) => {
+                    // The payload is itself JSON, as a string
+                    let event: Value = serde_json::from_str(&text).unwrap_or_default();
+                    if event["event"] != "notification" {
+                        continue;
+                    }
+                    match serde_json::from_str::<Value>(event["payload"].as_str().unwrap_or("")) {
+                        Ok(notification) => self.handle(&notification, hub, client, metrics).await,
+                        Err(e) => eprintln!("⚠️  Unreadable Mastodon notification: {}", e),
+                    }
+                }
+                Message::Ping(data) => {
+                    let _ = ws.send(Message::Pong(data)).await;
+                }
+                Message::Close(frame) => {
+                    let (code, reason) = frame.map(|f| (u16::from(f.code), f.reason.into_owned())).unwrap_or((1005, String::new()));
+                    return Err(format!("closed with {} {}", code, reason).trim_end().to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Stay connected to the stream for the life of the process
+    pub async fn run(self: Arc<Self>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>) {
+        match metrics.upstream("mastodon:verify_credentials", self.call(&client, reqwest::Method::GET, "/api/v1/accounts/verify_credentials", None)).await {
+            Ok(me) => println!("   Mastodon bot: @{}@{}", me["acct"].as_str().unwrap_or(""), self.host()),
+            Err(e) if e.starts_with("401") || e.starts_with("403") => {
+                eprintln!("❌ Mastodon: the access token was refused: {}", e);
+                hub.set_status(Channel::Mastodon, false, Some(e));
+                return;
+            }
+            Err(e) => eprintln!("⚠️  Mastodon verify_credentials: {}", e),
+        }
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            let started = tokio::time::Instant::now();
+            let ended = self.stream(&hub, &client, &metrics).await.err().unwrap_or_default();
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = Duration::from_secs(1);
+            }
+            eprintln!("⚠️  Mastodon stream lost: {}; retrying in {}s", ended, backoff.as_secs());
+            hub.set_status(Channel::Mastodon, false, Some(ended));
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming() {
+        let notification = json!({"id": "77", "type": "mention",
+            "account": {"acct": "ann@example.social", "display_name": "Ann"},
+            "status": {"id": "1130", "visibility": "direct", "url": "https://example.social/@ann/1130",
+                "content": "<p><span class=\"h-card\"><a href=\"https://m.s/@bot\">@<span>bot</span></a></span> summarize <a href=\"https://x.org\">x.org</a> &amp; more</p><p>thanks</p>"}});
+        let mention = incoming(&notification).unwrap();
+        assert_eq!((mention.status_id.as_str(), mention.acct.as_str(), mention.visibility), ("1130", "ann@example.social", Visibility::Direct));
+        assert_eq!(mention.text, "summarize x.org & more\n\nthanks");
+        assert!(incoming(&json!({"id": "78", "type": "favourite"})).is_none());
+        assert_eq!(text("<p>one<br>two</p>"), "one\ntwo");
+    }
+
+    #[test]
+    fn test_parts_and_config() {
+        let config = MastodonConfig {
+            instance: Some("https://example.social/".to_string()),
+            access_token: Some("t".to_string()),
+            allowed_accounts: vec!["@Ann@example.social".to_string()],
+            max_chars: 100,
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(MastodonConfig { instance: Some("example.social/@bot".to_string()), ..config.clone() }.validate().is_err());
+        let mastodon = Mastodon::new(&config);
+        assert_eq!(mastodon.host(), "example.social");
+        assert!(mastodon.allows("ann@example.social") && !mastodon.allows("bob"));
+
+        mastodon.pending.lock().unwrap().push_back(Pending { status_id: "1".to_string(), acct: "ann@example.social".to_string(), visibility: Visibility::Direct });
+        let reply = SendRequest { text: "word ".repeat(30), reply_to: Some("1".to_string()), ..Default::default() };
+        let (parts, pending) = mastodon.parts(&reply).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|p| p.starts_with("@ann@example.social ") && p.chars().count() <= 100));
+        assert_eq!(pending.unwrap().visibility, Visibility::Direct);
+        assert!(mastodon.may_answer(&reply) && !mastodon.may_answer(&SendRequest { reply_to: Some("2".to_string()), ..Default::default() }));
+        let post = SendRequest { text: "Daily digest".to_string(), ..Default::default() };
+        assert_eq!(mastodon.parts(&post).unwrap().0, ["Daily digest"]);
+    }
+}
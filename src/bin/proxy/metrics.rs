@@ -0,0 +1,232 @@
+//! Access log and Prometheus metrics
+//!
+//! Every request gets one JSON line on stdout (the human-readable progress
+//! messages stay on stderr), and `GET /metrics` serves counters in the
+//! Prometheus text format: requests by route, method and status, request
+//! and upstream latency histograms per route, upstream errors, cache
+//! results and requests in flight. Routes are the registered patterns, so
+//! scanning for random paths cannot blow up the label set.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+
+use crate::cache::CACHE_HEADER;
+
+/// Histogram bucket bounds in seconds
+const BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0, 30.0, 120.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// Per bucket, not cumulative; the last slot is +Inf
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let slot = BUCKETS.iter().position(|bound| secs <= *bound).unwrap_or(BUCKETS.len());
+        self.counts[slot] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, self.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    /// (route, method, status) -> requests
+    requests: BTreeMap<(String, String, u16), u64>,
+    durations: BTreeMap<String, Histogram>,
+    upstream: BTreeMap<String, Histogram>,
+    upstream_errors: BTreeMap<String, u64>,
+    /// HIT / MISS / BYPASS -> responses
+    cache: BTreeMap<String, u64>,
+}
+
+pub struct Metrics {
+    access_log: bool,
+    in_flight: AtomicI64,
+    counters: Mutex<Counters>,
+}
+
+/// One access log line
+#[derive(Serialize)]
+struct AccessRecord<'a> {
+    ts: String,
+    client: String,
+    method: &'a str,
+    path: &'a str,
+    route: &'a str,
+    status: u16,
+    duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<&'a str>,
+}
+
+impl Metrics {
+    pub fn new(access_log: bool) -> Self {
+        Metrics { access_log, in_flight: AtomicI64::new(0), counters: Mutex::new(Counters::default()) }
+    }
+
+    fn record(&self, route: &str, method: &str, status: u16, duration: Duration, cache: Option<&str>) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.requests.entry((route.to_string(), method.to_string(), status)).or_default() += 1;
+        counters.durations.entry(route.to_string()).or_default().observe(duration);
+        if let Some(result) = cache {
+            *counters.cache.entry(result.to_string()).or_default() += 1;
+        }
+    }
+
+    /// Time an upstream call made for `route`, counting failures
+    pub async fn upstream<T, E>(&self, route: &str, call: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+        let started = Instant::now();
+        let result = call.await;
+        let mut counters = self.counters.lock().unwrap();
+        counters.upstream.entry(route.to_string()).or_default().observe(started.elapsed());
+        if result.is_err() {
+            *counters.upstream_errors.entry(route.to_string()).or_default() += 1;
+        }
+        result
+    }
+
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP clawasm_proxy_requests_total Requests handled, by route, method and status.\n");
+        out.push_str("# TYPE clawasm_proxy_requests_total counter\n");
+        for ((route, method, status), count) in &counters.requests {
+            let _ = writeln!(out, "clawasm_proxy_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}", route, method, status, count);
+        }
+
+        out.push_str("# HELP clawasm_proxy_request_duration_seconds Time to answer a request, by route.\n");
+        out.push_str("# TYPE clawasm_proxy_request_duration_seconds histogram\n");
+        for (route, histogram) in &counters.durations {
+            histogram.render(&mut out, "clawasm_proxy_request_duration_seconds", &format!("route=\"{}\"", route));
+        }
+
+        out.push_str("# HELP clawasm_proxy_upstream_duration_seconds Time for the upstream server to respond, by route.\n");
+        out.push_str("# TYPE clawasm_proxy_upstream_duration_seconds histogram\n");
+        for (route, histogram) in &counters.upstream {
+            histogram.render(&mut out, "clawasm_proxy_upstream_duration_seconds", &format!("route=\"{}\"", route));
+        }
+
+        out.push_str("# HELP clawasm_proxy_upstream_errors_total Upstream requests that failed without a response, by route.\n");
+        out.push_str("# TYPE clawasm_proxy_upstream_errors_total counter\n");
+        for (route, count) in &counters.upstream_errors {
+            let _ = writeln!(out, "clawasm_proxy_upstream_errors_total{{route=\"{}\"}} {}", route, count);
+        }
+
+        out.push_str("# HELP clawasm_proxy_cache_responses_total Cacheable responses, by cache result.\n");
+        out.push_str("# TYPE clawasm_proxy_cache_responses_total counter\n");
+        for (result, count) in &counters.cache {
+            let _ = writeln!(out, "clawasm_proxy_cache_responses_total{{result=\"{}\"}} {}", result, count);
+        }
+
+        out.push_str("# HELP clawasm_proxy_in_flight Requests being handled right now.\n");
+        out.push_str("# TYPE clawasm_proxy_in_flight gauge\n");
+        let _ = writeln!(out, "clawasm_proxy_in_flight {}", self.in_flight.load(Ordering::Relaxed));
+        out
+    }
+}
+
+/// Access log line and counters for every request
+pub async fn observe(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(metrics) = req.app_data::<web::Data<Metrics>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let client = req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default();
+
+    metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+    let result = next.call(req).await;
+    metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+    let res = result?.map_into_boxed_body();
+
+    let duration = started.elapsed();
+    let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let status = res.status().as_u16();
+    let cache = res.headers().get(CACHE_HEADER).and_then(|v| v.to_str().ok());
+    metrics.record(&route, &method, status, duration, cache);
+
+    if metrics.access_log {
+        let record = AccessRecord {
+            ts: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            client,
+            method: &method,
+            path: &path,
+            route: &route,
+            status,
+            duration_ms: (duration.as_secs_f64() * 1000.0 * 10.0).round() / 10.0,
+            bytes: match res.response().body().size() {
+                BodySize::Sized(n) => Some(n),
+                _ => None,
+            },
+            cache,
+        };
+        if let Ok(line) = serde_json::to_string(&record) {
+            println!("{}", line);
+        }
+    }
+    Ok(res)
+}
+
+pub async fn metrics_handler(metrics: web::Data<Metrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prometheus_text() {
+        let metrics = Metrics::new(false);
+        metrics.record("/proxy", "POST", 200, Duration::from_millis(30), Some("MISS"));
+        metrics.record("/proxy", "POST", 200, Duration::from_millis(2), Some("HIT"));
+        metrics.record("/proxy", "POST", 429, Duration::from_millis(1), None);
+        let _ = metrics.upstream("/proxy", async { Err::<(), _>("timeout") }).await;
+
+        let text = metrics.render();
+        assert!(text.contains("clawasm_proxy_requests_total{route=\"/proxy\",method=\"POST\",status=\"200\"} 2"));
+        assert!(text.contains("clawasm_proxy_requests_total{route=\"/proxy\",method=\"POST\",status=\"429\"} 1"));
+        assert!(text.contains("clawasm_proxy_request_duration_seconds_bucket{route=\"/proxy\",le=\"0.005\"} 2"));
+        assert!(text.contains("clawasm_proxy_request_duration_seconds_bucket{route=\"/proxy\",le=\"+Inf\"} 3"));
+        assert!(text.contains("clawasm_proxy_request_duration_seconds_count{route=\"/proxy\"} 3"));
+        assert!(text.contains("clawasm_proxy_upstream_errors_total{route=\"/proxy\"} 1"));
+        assert!(text.contains("clawasm_proxy_cache_responses_total{result=\"HIT\"} 1"));
+        assert!(text.contains("clawasm_proxy_in_flight 0"));
+    }
+}
@@ -0,0 +1,120 @@
+//! Batched vulnerability lookups behind POST /osv/batch
+//!
+//! OSV's querybatch endpoint only returns advisory IDs, so a manifest scan
+//! used to cost one round trip for the batch plus one per advisory. Here the
+//! proxy does both halves: packages not seen within the cache TTL go to
+//! querybatch in one request, advisories not already cached are fetched a
+//! few at a time, and the answer carries the full OSV records:
+//!
+//! ```json
+//! {"results": [{"name": "lodash", "ecosystem": "npm", "version": "4.17.15",
+//!   "vulns": [{"id": "GHSA-...", "summary": "...", "severity": [...], ...}]}],
+//!  "cached": 12, "missing": []}
+//! ```
+//!
+//! `missing` lists advisories whose details could not be fetched; they
+//! still appear in `vulns` as `{"id": "..."}`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const HOST: &str = "api.osv.dev";
+pub const BATCH_URL: &str = "https://api.osv.dev/v1/querybatch";
+/// Most queries OSV accepts in one batch
+pub const MAX_PACKAGES: usize = 1000;
+/// Advisory details fetched at the same time
+pub const DETAIL_CONCURRENCY: usize = 8;
+
+/// URL of the full record for one vulnerability
+pub fn vuln_url(id: &str) -> String {
+    format!("https://api.osv.dev/v1/vulns/{}", urlencoding::encode(id))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub ecosystem: String,
+    pub version: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PackageResult {
+    #[serde(flatten)]
+    pub package: Package,
+    pub vulns: Vec<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub results: Vec<PackageResult>,
+    /// Packages answered from the cache without asking OSV
+    pub cached: usize,
+    pub missing: Vec<String>,
+}
+
+impl BatchRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.packages.is_empty() {
+            return Err("Missing 'packages'".to_string());
+        }
+        if self.packages.len() > MAX_PACKAGES {
+            return Err(format!("{} packages; the limit is {}", self.packages.len(), MAX_PACKAGES));
+        }
+        match self.packages.iter().find(|p| p.name.is_empty() || p.ecosystem.is_empty() || p.version.is_empty()) {
+            Some(p) => Err(format!("Package {:?} needs name, ecosystem and version", p.name)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// querybatch body for `packages`
+pub fn batch_body(packages: &[&Package]) -> Value {
+    let queries: Vec<Value> = packages.iter()
+        .map(|p| serde_json::json!({
+            "package": { "name": p.name, "ecosystem": p.ecosystem },
+            "version": p.version,
+        }))
+        .collect();
+    serde_json::json!({ "queries": queries })
+}
+
+/// Advisory IDs per query in a querybatch answer, in query order
+pub fn parse_batch(body: &Value) -> Vec<Vec<String>> {
+    body["results"].as_array().into_iter().flatten()
+        .map(|result| result["vulns"].as_array().into_iter().flatten()
+            .filter_map(|v| v["id"].as_str().map(str::to_string))
+            .collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_batch_round_trip() {
+        let lodash = Package { name: "lodash".to_string(), ecosystem: "npm".to_string(), version: "4.17.15".to_string() };
+        let body = batch_body(&[&lodash]);
+        assert_eq!(body["queries"][0]["package"]["name"], "lodash");
+        assert_eq!(body["queries"][0]["version"], "4.17.15");
+
+        let answer = json!({"results": [{"vulns": [{"id": "GHSA-1", "modified": "2024-01-01"}, {"id": "GHSA-2"}]}, {}]});
+        assert_eq!(parse_batch(&answer), vec![vec!["GHSA-1".to_string(), "GHSA-2".to_string()], vec![]]);
+
+        let flat = serde_json::to_value(PackageResult { package: lodash, vulns: vec![] }).unwrap();
+        assert_eq!(flat, json!({"name": "lodash", "ecosystem": "npm", "version": "4.17.15", "vulns": []}));
+    }
+
+    #[test]
+    fn test_validate() {
+        let request: BatchRequest = serde_json::from_value(json!({"packages": [{"name": "serde", "ecosystem": "crates.io", "version": ""}]})).unwrap();
+        assert!(request.validate().unwrap_err().contains("serde"));
+        assert!(BatchRequest { packages: vec![] }.validate().is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Encrypted conversation shares: POST /paste and GET /paste/{id}
+//!
+//! The browser encrypts a conversation with a key of its own, stores the
+//! ciphertext here and hands out a link with the key in its fragment,
+//! which browsers never send to a server. The proxy only ever holds
+//! base64 it cannot read: it checks the size and the alphabet, nothing
+//! else. Reading a paste needs no token, as its id is 128 random bits and
+//! its text is useless without the key, so a link works for whoever it is
+//! sent to; creating one does. Pastes expire after `ttl_hours`, past
+//! `max_pastes` the oldest goes, and with `dir` set they survive restarts,
+//! one file each. The store is off unless the `[paste]` section (see
+//! `config`) sets `enabled = true`; until then both routes answer 404.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{web, HttpResponse};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasteConfig {
+    /// Off by default: a proxy only keeps what its users share once asked to
+    pub enabled: bool,
+    /// Forget pastes this long after they were made; 0 keeps them
+    pub ttl_hours: u64,
+    /// Largest paste, in bytes of base64
+    pub max_bytes: usize,
+    pub max_pastes: usize,
+    /// Keep pastes here across restarts
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        PasteConfig { enabled: false, ttl_hours: 168, max_bytes: 1024 * 1024, max_pastes: 1000, dir: None }
+    }
+}
+
+impl PasteConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_bytes == 0 || self.max_pastes == 0 {
+            return Err("paste max_bytes and max_pastes must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// POST /paste body
+#[derive(Debug, Deserialize)]
+pub struct PasteRequest {
+    /// The ciphertext, base64
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Paste {
+    /// Unix seconds
+    created_at: u64,
+    data: String,
+}
+
+pub struct Pastes {
+    pub config: PasteConfig,
+    pastes: Mutex<HashMap<String, Paste>>,
+    random: SystemRandom,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Ids are 32 lowercase hex digits; anything else never reaches the disk
+fn is_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl Pastes {
+    /// The store, with the unexpired pastes of `dir`
+    pub fn new(config: &PasteConfig) -> Self {
+        let pastes = Pastes { config: config.clone(), pastes: Mutex::new(HashMap::new()), random: SystemRandom::new() };
+        let Some(dir) = &config.dir else { return pastes };
+        let Ok(files) = std::fs::read_dir(dir) else { return pastes };
+        let mut loaded = pastes.pastes.lock().unwrap();
+        for file in files.flatten() {
+            let path = file.path();
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()).filter(|id| is_id(id)).map(str::to_string) else { continue };
+            match std::fs::read(&path).ok().and_then(|json| serde_json::from_slice::<Paste>(&json).ok()) {
+                Some(paste) if !pastes.expired(&paste) => {
+                    loaded.insert(id, paste);
+                }
+                _ => pastes.remove_disk(&id),
+            }
+        }
+        drop(loaded);
+        pastes.evict();
+        pastes
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Keep `data`, answering with its id
+    pub fn put(&self, data: &str) -> Result<String, String> {
+        if !self.enabled() {
+            return Err("Sharing is off: set enabled = true in [paste]".to_string());
+        }
+        let data = data.trim();
+        if data.is_empty() {
+            return Err("Missing 'data'".to_string());
+        }
+        if data.len() > self.config.max_bytes {
+            return Err(format!("The paste is {} bytes, more than the {} allowed", data.len(), self.config.max_bytes));
+        }
+        if !data.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=')) {
+            return Err("'data' must be base64 ciphertext".to_string());
+        }
+        let mut bytes = [0u8; 16];
+        self.random.fill(&mut bytes).map_err(|_| "No random source for the paste id".to_string())?;
+        let id: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let paste = Paste { created_at: now(), data: data.to_string() };
+        self.write_disk(&id, &paste);
+        self.pastes.lock().unwrap().insert(id.clone(), paste);
+        self.evict();
+        Ok(id)
+    }
+
+    /// The ciphertext of paste `id`, unless it expired or never was
+    pub fn get(&self, id: &str) -> Option<String> {
+        if !self.enabled() || !is_id(id) {
+            return None;
+        }
+        let mut pastes = self.pastes.lock().unwrap();
+        match pastes.get(id) {
+            Some(paste) if !self.expired(paste) => Some(paste.data.clone()),
+            Some(_) => {
+                pastes.remove(id);
+                self.remove_disk(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn expired(&self, paste: &Paste) -> bool {
+        self.config.ttl_hours > 0 && now() >= paste.created_at + self.config.ttl_hours * 3600
+    }
+
+    /// Drop the oldest pastes past `max_pastes`
+    fn evict(&self) {
+        let mut pastes = self.pastes.lock().unwrap();
+        while pastes.len() > self.config.max_pastes {
+            let Some(oldest) = pastes.iter().min_by_key(|(_, p)| p.created_at).map(|(id, _)| id.clone()) else { break };
+            pastes.remove(&oldest);
+            self.remove_disk(&oldest);
+        }
+    }
+
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        self.config.dir.as_ref().map(|dir| dir.join(format!("{}.json", id)))
+    }
+
+    fn write_disk(&self, id: &str, paste: &Paste) {
+        let Some(path) = self.path(id) else { return };
+        let written = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, serde_json::to_vec(paste).unwrap_or_default()));
+        if let Err(e) = written {
+            eprintln!("⚠️  Paste write failed for {}: {}", path.display(), e);
+        }
+    }
+
+    fn remove_disk(&self, id: &str) {
+        if let Some(path) = self.path(id) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// POST /paste {"data"}: keep a conversation encrypted in the browser,
+/// answering {"id", "ttl_hours"}
+pub async fn create_handler(body: web::Bytes, pastes: web::Data<Pastes>) -> HttpResponse {
+    use actix_web::http::StatusCode;
+    let error = |status: StatusCode, message: String| {
+        eprintln!("❌ Paste: {}", message);
+        HttpResponse::build(status)
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": message }))
+    };
+
+    if !pastes.enabled() {
+        return error(StatusCode::NOT_FOUND, "Sharing is off: set enabled = true in [paste]".to_string());
+    }
+    let req: PasteRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)),
+    };
+    match pastes.put(&req.data) {
+        Ok(id) => HttpResponse::Ok()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "id": id, "ttl_hours": pastes.config.ttl_hours })),
+        Err(message) => error(StatusCode::BAD_REQUEST, message),
+    }
+}
+
+/// GET /paste/{id}: the ciphertext of a share, {"data"}
+pub async fn get_handler(path: web::Path<String>, pastes: web::Data<Pastes>) -> HttpResponse {
+    match pastes.get(&path.into_inner()) {
+        Some(data) => HttpResponse::Ok()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .insert_header(("Cache-Control", "no-store"))
+            .json(serde_json::json!({ "data": data })),
+        None => HttpResponse::NotFound()
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "No such share: it expired or the link is wrong" })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    fn enabled() -> PasteConfig {
+        PasteConfig { enabled: true, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_off_by_default() {
+        let pastes = web::Data::new(Pastes::new(&PasteConfig::default()));
+        assert!(!pastes.enabled());
+        let created = create_handler(web::Bytes::from_static(br#"{"data": "YWJj"}"#), pastes.clone()).await;
+        assert_eq!(created.status(), StatusCode::NOT_FOUND);
+        let read = get_handler(web::Path::from("0".repeat(32)), pastes).await;
+        assert_eq!(read.status(), StatusCode::NOT_FOUND);
+
+        let pastes = web::Data::new(Pastes::new(&enabled()));
+        let created = create_handler(web::Bytes::from_static(br#"{"data": "YWJj"}"#), pastes.clone()).await;
+        assert_eq!(created.status(), StatusCode::OK);
+        let id = pastes.pastes.lock().unwrap().keys().next().unwrap().clone();
+        assert_eq!(get_handler(web::Path::from(id), pastes).await.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_put_and_get() {
+        let pastes = Pastes::new(&PasteConfig { max_bytes: 16, max_pastes: 2, ..enabled() });
+        let a = pastes.put("YWJj").unwrap();
+        assert!(is_id(&a));
+        assert_eq!(pastes.get(&a).as_deref(), Some("YWJj"));
+        assert!(pastes.get("../../etc/passwd").is_none());
+
+        assert!(pastes.put("").is_err());
+        assert!(pastes.put("not base64!").unwrap_err().contains("base64"));
+        assert!(pastes.put(&"A".repeat(17)).unwrap_err().contains("more than the 16"));
+
+        pastes.pastes.lock().unwrap().get_mut(&a).unwrap().created_at -= 10;
+        let b = pastes.put("ZGVm").unwrap();
+        let c = pastes.put("Z2hp").unwrap();
+        assert!(pastes.get(&a).is_none(), "oldest evicted");
+        assert!(pastes.get(&b).is_some() && pastes.get(&c).is_some());
+
+        pastes.pastes.lock().unwrap().get_mut(&b).unwrap().created_at -= 168 * 3600;
+        assert!(pastes.get(&b).is_none(), "expired");
+    }
+
+    #[test]
+    fn test_disk_pastes_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("clawasm-paste-test-{}", std::process::id()));
+        let config = PasteConfig { dir: Some(dir.clone()), ..enabled() };
+        let id = Pastes::new(&config).put("c2VjcmV0").unwrap();
+        assert_eq!(Pastes::new(&config).get(&id).as_deref(), Some("c2VjcmV0"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
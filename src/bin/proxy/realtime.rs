@@ -0,0 +1,583 @@
+//! Realtime channel events behind GET /ws (WebSocket)
+//!
+//! The proxy holds the upstream connections itself, so events reach the
+//! browser without a public webhook: the Discord gateway with a bot token,
+//! and Slack Socket Mode with an app-level token. Both stay connected for
+//! the life of the process, reconnecting with backoff, and every event goes
+//! to each browser connected to `/ws` as one JSON text message:
+//!
+//! ```json
+//! {"channel": "discord", "type": "MESSAGE_CREATE", "data": {...}}
+//! {"channel": "slack", "type": "app_mention", "data": {"event": {...}, ...}}
+//! {"channel": "slack", "type": "status", "data": {"connected": true, "error": null}}
+//! ```
+//!
+//! Discord events carry the gateway's dispatch name and payload (a dropped
+//! session is resumed, so events in between are replayed); Slack
+//! events the inner event type (or `slash_commands`, `interactive`) and the
+//! envelope payload, already acknowledged; the Slack Events API endpoint
+//! (see `slack.rs`) publishes its callbacks the same way. Telegram and
+//! WhatsApp messages arrive through their webhooks instead (see
+//! `telegram.rs`, `whatsapp.rs`), Mastodon and Bluesky mentions from
+//! their own bots (`mastodon.rs`, `bluesky.rs`), and mail from an IMAP
+//! inbox (`imap.rs`); all are relayed the same way. A client that falls
+//! more than `buffer` events behind gets `{"channel": "proxy", "type":
+//! "lagged"}`. The messages meant for the agent are also queued for
+//! browsers that long-poll `/inbox` instead (see `inbox.rs`).
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::inbox::Inbox;
+
+pub const DISCORD_GATEWAY: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
+pub const SLACK_CONNECTIONS_OPEN: &str = "https://slack.com/api/apps.connections.open";
+/// GUILDS, GUILD_MESSAGES, DIRECT_MESSAGES and MESSAGE_CONTENT
+pub const DEFAULT_INTENTS: u64 = 1 | 1 << 9 | 1 << 12 | 1 << 15;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RealtimeConfig {
+    /// Bot token; connects to the Discord gateway when set
+    pub discord_token: Option<String>,
+    /// Gateway intents bitmask; privileged ones must be enabled for the bot
+    pub discord_intents: u64,
+    /// App-level `xapp-` token with connections:write; connects Socket Mode
+    pub slack_app_token: Option<String>,
+    /// Events held for a slow client before it misses some, and messages
+    /// held in the inbox
+    pub buffer: usize,
+    /// How long inbox messages wait for a poller, and a poller counts as
+    /// listening; 0 turns the inbox off
+    pub inbox_secs: u64,
+}
+
+impl Default for RealtimeConfig {
+    fn default() -> Self {
+        RealtimeConfig { discord_token: None, discord_intents: DEFAULT_INTENTS, slack_app_token: None, buffer: 256, inbox_secs: 300 }
+    }
+}
+
+impl RealtimeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.slack_app_token.as_deref().is_some_and(|t| !t.starts_with("xapp-")) {
+            return Err("slack_app_token must be an app-level token (xapp-...)".to_string());
+        }
+        if self.buffer == 0 {
+            return Err("realtime buffer must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Channel {
+    Bluesky,
+    Discord,
+    Email,
+    Mastodon,
+    Slack,
+    Telegram,
+    WhatsApp,
+}
+
+impl Channel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Bluesky => "bluesky",
+            Channel::Discord => "discord",
+            Channel::Email => "email",
+            Channel::Mastodon => "mastodon",
+            Channel::Slack => "slack",
+            Channel::Telegram => "telegram",
+            Channel::WhatsApp => "whatsapp",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "bluesky" => Some(Channel::Bluesky),
+            "discord" => Some(Channel::Discord),
+            "email" => Some(Channel::Email),
+            "mastodon" => Some(Channel::Mastodon),
+            "slack" => Some(Channel::Slack),
+            "telegram" => Some(Channel::Telegram),
+            "whatsapp" => Some(Channel::WhatsApp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub channel: &'static str,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub connected: bool,
+    pub error: Option<String>,
+}
+
+/// How an upstream session ended
+#[derive(Debug, PartialEq)]
+enum Ended {
+    /// The server asked for a fresh connection
+    Reconnect,
+    /// A failure worth retrying after a pause
+    Retry(String),
+    /// A failure retrying cannot fix, such as a rejected token
+    Stop(String),
+    /// The gateway dropped the session; retry with a fresh one
+    Restart(String),
+}
+
+/// What a dropped gateway session needs to resume where it stopped
+#[derive(Debug, Clone, PartialEq)]
+struct Resume {
+    session_id: String,
+    url: String,
+    sequence: Value,
+}
+
+/// Upstream connections and the browsers listening to them
+pub struct Hub {
+    pub config: RealtimeConfig,
+    /// Channels whose events arrive on a webhook (Telegram, the Slack
+    /// Events API, WhatsApp) rather than a connection held here
+    relayed: Vec<Channel>,
+    discord_session: Mutex<Option<Resume>>,
+    events: broadcast::Sender<Arc<Event>>,
+    status: Mutex<BTreeMap<Channel, Status>>,
+    /// Browsers connected per channel
+    listeners: Mutex<BTreeMap<Channel, usize>>,
+    pub inbox: Inbox,
+}
+
+/// A browser's subscription; counted as a listener until dropped
+pub struct Listening {
+    hub: Arc<Hub>,
+    pub channels: Vec<Channel>,
+}
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        let mut listeners = self.hub.listeners.lock().unwrap();
+        for channel in &self.channels {
+            if let Some(count) = listeners.get_mut(channel) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl Hub {
+    pub fn new(config: &RealtimeConfig, relayed: &[Channel]) -> Self {
+        let (events, _) = broadcast::channel(config.buffer.max(1));
+        Hub {
+            config: config.clone(),
+            relayed: relayed.to_vec(),
+            discord_session: Mutex::new(None),
+            events,
+            status: Mutex::new(BTreeMap::new()),
+            listeners: Mutex::new(BTreeMap::new()),
+            inbox: Inbox::new(Duration::from_secs(config.inbox_secs), config.buffer),
+        }
+    }
+
+    /// Channels with credentials, whether connected or not
+    pub fn channels(&self) -> Vec<Channel> {
+        let mut channels = self.relayed.clone();
+        if self.config.discord_token.is_some() {
+            channels.push(Channel::Discord);
+        }
+        if self.config.slack_app_token.is_some() {
+            channels.push(Channel::Slack);
+        }
+        channels.sort();
+        channels.dedup();
+        channels
+    }
+
+    /// The configured channels named in `?channels=` (all when absent)
+    pub fn select(&self, names: Option<&str>) -> Result<Vec<Channel>, String> {
+        let configured = self.channels();
+        let Some(names) = names.filter(|n| !n.trim().is_empty()) else {
+            return Ok(configured);
+        };
+        names.split(',')
+            .map(|name| match Channel::parse(name) {
+                Some(channel) if configured.contains(&channel) => Ok(channel),
+                Some(channel) => Err(format!("{} is not configured", channel.name())),
+                None => Err(format!("Unknown channel {:?}; use bluesky, discord, email, mastodon, slack, telegram or whatsapp", name.trim())),
+            })
+            .collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Event>> {
+        self.events.subscribe()
+    }
+
+    /// Count a browser as listening to `channels` while the guard lives
+    pub fn listen(self: Arc<Self>, channels: Vec<Channel>) -> Listening {
+        let mut listeners = self.listeners.lock().unwrap();
+        for channel in &channels {
+            *listeners.entry(*channel).or_default() += 1;
+        }
+        drop(listeners);
+        Listening { hub: self, channels }
+    }
+
+    /// Browsers connected for `channel`, counting those polling the inbox
+    /// as one
+    pub fn listeners(&self, channel: Channel) -> usize {
+        let connected = self.listeners.lock().unwrap().get(&channel).copied().unwrap_or(0);
+        connected + usize::from(self.inbox.polling(channel))
+    }
+
+    /// One status event per channel, for a client that just connected
+    pub fn status_events(&self, channels: &[Channel]) -> Vec<Event> {
+        let status = self.status.lock().unwrap();
+        channels.iter()
+            .map(|channel| Event {
+                channel: channel.name(),
+                kind: "status".to_string(),
+                data: json!(status.get(channel).cloned().unwrap_or_default()),
+            })
+            .collect()
+    }
+
+    pub fn publish(&self, channel: Channel, kind: &str, data: Value) {
+        // No receivers is fine: nobody is listening yet
+        let _ = self.events.send(Arc::new(Event { channel: channel.name(), kind: kind.to_string(), data }));
+    }
+
+    /// Publish a message for the agent and queue it in the inbox
+    pub fn relay(&self, channel: Channel, kind: &str, data: Value) {
+        let event = Arc::new(Event { channel: channel.name(), kind: kind.to_string(), data });
+        self.inbox.push(event.clone());
+        let _ = self.events.send(event);
+    }
+
+    pub fn set_status(&self, channel: Channel, connected: bool, error: Option<String>) {
+        let status = Status { connected, error };
+        self.status.lock().unwrap().insert(channel, status.clone());
+        self.publish(channel, "status", json!(status));
+    }
+
+    /// Connect every channel with an upstream connection in the background
+    pub fn start(self: &Arc<Self>, http: reqwest::Client) {
+        let connected = |channel: &Channel| match channel {
+            Channel::Discord => true,
+            Channel::Slack => self.config.slack_app_token.is_some(),
+            _ => false,
+        };
+        for channel in self.channels().into_iter().filter(connected) {
+            let hub = self.clone();
+            let http = http.clone();
+            tokio::spawn(async move { hub.keep_connected(channel, http).await });
+        }
+    }
+
+    async fn keep_connected(&self, channel: Channel, http: reqwest::Client) {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            let started = Instant::now();
+            let ended = match channel {
+                Channel::Discord => self.discord_session().await,
+                Channel::Slack => self.slack_session(&http).await,
+                _ => return,
+            };
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = Duration::from_secs(1);
+            }
+            match ended {
+                // Asked to reconnect: at once, unless it keeps happening
+                Ended::Reconnect if started.elapsed() > Duration::from_secs(5) => {
+                    self.set_status(channel, false, None);
+                    continue;
+                }
+                Ended::Reconnect => self.set_status(channel, false, None),
+                Ended::Retry(e) | Ended::Restart(e) => {
+                    eprintln!("⚠️  {} connection lost: {}; retrying in {}s", channel.name(), e, backoff.as_secs());
+                    self.set_status(channel, false, Some(e));
+                }
+                Ended::Stop(e) => {
+                    eprintln!("❌ {} disconnected for good: {}", channel.name(), e);
+                    self.set_status(channel, false, Some(e));
+                    return;
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    async fn discord_session(&self) -> Ended {
+        match self.discord_connection().await {
+            Ended::Restart(e) => {
+                *self.discord_session.lock().unwrap() = None;
+                Ended::Restart(e)
+            }
+            ended => ended,
+        }
+    }
+
+    async fn discord_connection(&self) -> Ended {
+        let token = self.config.discord_token.as_deref().unwrap_or("");
+        let resume = self.discord_session.lock().unwrap().clone();
+        let url = match &resume {
+            Some(resume) => format!("{}/?v=10&encoding=json", resume.url.trim_end_matches('/')),
+            None => DISCORD_GATEWAY.to_string(),
+        };
+        let mut ws = match connect(&url).await {
+            Ok(ws) => ws,
+            Err(e) => return Ended::Retry(e),
+        };
+        let interval = match next_json(&mut ws).await {
+            Ok(hello) if hello["op"] == 10 => hello["d"]["heartbeat_interval"].as_u64().unwrap_or(41_250),
+            Ok(other) => return Ended::Retry(format!("expected Hello, got op {}", other["op"])),
+            Err(ended) => return ended,
+        };
+        let start = match &resume {
+            Some(resume) => resume_payload(token, resume),
+            None => identify(token, self.config.discord_intents),
+        };
+        if let Err(e) = ws.send(Message::Text(start.to_string())).await {
+            return Ended::Retry(e.to_string());
+        }
+
+        let period = Duration::from_millis(interval);
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        let mut sequence = resume.map_or(Value::Null, |resume| resume.sequence);
+        let mut acknowledged = true;
+        loop {
+            tokio::select! {
+                _ = heartbeat.tick() => {
+                    // A zombie connection stops acknowledging; start over
+                    if !acknowledged {
+                        return Ended::Retry("heartbeat not acknowledged".to_string());
+                    }
+                    acknowledged = false;
+                    if let Err(e) = ws.send(Message::Text(json!({"op": 1, "d": sequence}).to_string())).await {
+                        return Ended::Retry(e.to_string());
+                    }
+                }
+                payload = next_json(&mut ws) => {
+                    let payload = match payload {
+                        Ok(payload) => payload,
+                        Err(ended) => return ended,
+                    };
+                    match payload["op"].as_u64() {
+                        Some(0) => {
+                            sequence = payload["s"].clone();
+                            let kind = payload["t"].as_str().unwrap_or("");
+                            let mut session = self.discord_session.lock().unwrap();
+                            if kind == "READY" {
+                                *session = Some(Resume {
+                                    session_id: payload["d"]["session_id"].as_str().unwrap_or("").to_string(),
+                                    url: payload["d"]["resume_gateway_url"].as_str().unwrap_or(DISCORD_GATEWAY).to_string(),
+                                    sequence: Value::Null,
+                                });
+                            }
+                            if let Some(session) = session.as_mut() {
+                                session.sequence = sequence.clone();
+                            }
+                            drop(session);
+                            if kind == "READY" || kind == "RESUMED" {
+                                self.set_status(Channel::Discord, true, None);
+                            }
+                            self.publish(Channel::Discord, kind, payload["d"].clone());
+                        }
+                        Some(1) => {
+                            if let Err(e) = ws.send(Message::Text(json!({"op": 1, "d": sequence}).to_string())).await {
+                                return Ended::Retry(e.to_string());
+                            }
+                        }
+                        Some(7) => return Ended::Reconnect,
+                        // d says whether the session can still be resumed
+                        Some(9) if payload["d"] == json!(true) => return Ended::Retry("session invalidated".to_string()),
+                        Some(9) => return Ended::Restart("session invalidated".to_string()),
+                        Some(11) => acknowledged = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn slack_session(&self, http: &reqwest::Client) -> Ended {
+        let token = self.config.slack_app_token.as_deref().unwrap_or("");
+        let opened: Value = match http.post(SLACK_CONNECTIONS_OPEN).bearer_auth(token).send().await {
+            Ok(response) => match response.json().await {
+                Ok(opened) => opened,
+                Err(e) => return Ended::Retry(e.to_string()),
+            },
+            Err(e) => return Ended::Retry(e.to_string()),
+        };
+        let url = match (opened["ok"].as_bool(), opened["url"].as_str()) {
+            (Some(true), Some(url)) => url.to_string(),
+            _ => {
+                let error = opened["error"].as_str().unwrap_or("no url").to_string();
+                return match error.as_str() {
+                    "invalid_auth" | "not_authed" | "account_inactive" | "token_revoked" | "missing_scope" => Ended::Stop(error),
+                    _ => Ended::Retry(error),
+                };
+            }
+        };
+        let mut ws = match connect(&url).await {
+            Ok(ws) => ws,
+            Err(e) => return Ended::Retry(e),
+        };
+        loop {
+            let envelope = match next_json(&mut ws).await {
+                Ok(envelope) => envelope,
+                Err(ended) => return ended,
+            };
+            // Unacknowledged envelopes are delivered again
+            if let Some(id) = envelope["envelope_id"].as_str() {
+                if let Err(e) = ws.send(Message::Text(json!({"envelope_id": id}).to_string())).await {
+                    return Ended::Retry(e.to_string());
+                }
+            }
+            match envelope["type"].as_str() {
+                Some("hello") => self.set_status(Channel::Slack, true, None),
+                Some("disconnect") if envelope["reason"] == "link_disabled" => {
+                    return Ended::Stop("Socket Mode was turned off for the app".to_string());
+                }
+                Some("disconnect") => return Ended::Reconnect,
+                _ => {
+                    if let Some((kind, data)) = slack_event(envelope) {
+                        self.publish(Channel::Slack, &kind, data);
+                    }
+                }
+            }
+        }
+    }
+}
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn connect(url: &str) -> Result<Socket, String> {
+    match tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(url)).await {
+        Ok(Ok((ws, _))) => Ok(ws),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("connection timed out".to_string()),
+    }
+}
+
+/// The next JSON text message; pings are answered by tungstenite itself
+async fn next_json(ws: &mut Socket) -> Result<Value, Ended> {
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                Ok(value) => return Ok(value),
+                Err(e) => eprintln!("⚠️  Unreadable realtime message: {}", e),
+            },
+            Some(Ok(Message::Close(frame))) => {
+                let (code, reason) = frame.map(|f| (u16::from(f.code), f.reason.into_owned())).unwrap_or((1005, String::new()));
+                let message = format!("closed with {} {}", code, reason).trim_end().to_string();
+                return Err(match code {
+                    _ if discord_fatal(code) => Ended::Stop(message),
+                    // Invalid sequence, session timed out
+                    4007 | 4009 => Ended::Restart(message),
+                    _ if CloseCode::from(code) == CloseCode::Normal => Ended::Reconnect,
+                    _ => Ended::Retry(message),
+                });
+            }
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(Ended::Retry(e.to_string())),
+            None => return Err(Ended::Retry("connection closed".to_string())),
+        }
+    }
+}
+
+/// Gateway Identify payload
+fn identify(token: &str, intents: u64) -> Value {
+    json!({"op": 2, "d": {
+        "token": token.trim().trim_start_matches("Bot "),
+        "intents": intents,
+        "properties": {"os": std::env::consts::OS, "browser": "clawasm-proxy", "device": "clawasm-proxy"},
+    }})
+}
+
+/// Gateway Resume payload
+fn resume_payload(token: &str, resume: &Resume) -> Value {
+    json!({"op": 6, "d": {
+        "token": token.trim().trim_start_matches("Bot "),
+        "session_id": resume.session_id,
+        "seq": resume.sequence,
+    }})
+}
+
+/// Gateway close codes that reconnecting cannot fix: a bad token, bad
+/// intents or a bad shard
+fn discord_fatal(code: u16) -> bool {
+    matches!(code, 4004 | 4010..=4014)
+}
+
+/// Event type and payload of a Socket Mode envelope, if it carries one
+fn slack_event(mut envelope: Value) -> Option<(String, Value)> {
+    let kind = match envelope["type"].as_str()? {
+        "events_api" => envelope["payload"]["event"]["type"].as_str().unwrap_or("events_api").to_string(),
+        other => other.to_string(),
+    };
+    Some((kind, envelope["payload"].take()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_channels() {
+        let hub = Arc::new(Hub::new(
+            &RealtimeConfig { slack_app_token: Some("xapp-1".to_string()), ..Default::default() },
+            &[],
+        ));
+        assert_eq!(hub.select(None).unwrap(), [Channel::Slack]);
+        assert_eq!(hub.select(Some(" Slack ")).unwrap(), [Channel::Slack]);
+        assert!(hub.select(Some("discord")).unwrap_err().contains("not configured"));
+        assert!(hub.select(Some("irc")).unwrap_err().contains("irc"));
+
+        let listening = hub.clone().listen(vec![Channel::Slack]);
+        assert_eq!(hub.listeners(Channel::Slack), 1);
+        drop(listening);
+        assert_eq!(hub.listeners(Channel::Slack), 0);
+
+        assert!(hub.config.validate().is_ok());
+        let bot_token = RealtimeConfig { slack_app_token: Some("xoxb-1".to_string()), ..Default::default() };
+        assert!(bot_token.validate().unwrap_err().contains("xapp-"));
+    }
+
+    #[test]
+    fn test_payloads() {
+        let identify = identify("Bot abc", DEFAULT_INTENTS);
+        assert_eq!(identify["d"]["token"], "abc");
+        assert_eq!(identify["d"]["intents"], 37377);
+        assert!(discord_fatal(4004) && !discord_fatal(4000));
+        let resume = Resume { session_id: "s1".to_string(), url: "wss://r.discord.gg".to_string(), sequence: json!(42) };
+        assert_eq!(resume_payload("abc", &resume), json!({"op": 6, "d": {"token": "abc", "session_id": "s1", "seq": 42}}));
+
+        let envelope = json!({"type": "events_api", "envelope_id": "e1",
+            "payload": {"event": {"type": "app_mention", "text": "hi"}}});
+        let (kind, data) = slack_event(envelope).unwrap();
+        assert_eq!((kind.as_str(), &data["event"]["text"]), ("app_mention", &json!("hi")));
+        assert_eq!(slack_event(json!({"type": "slash_commands", "payload": {}})).unwrap().0, "slash_commands");
+    }
+}
@@ -0,0 +1,178 @@
+//! Headless page rendering behind GET /render (feature "render")
+//!
+//! Single-page apps send an empty HTML shell and build the page in
+//! JavaScript, so /proxy gives fetch_url and scan_xss nothing to work with.
+//! /render loads the URL in headless Chromium over the DevTools protocol and
+//! returns the DOM after scripts ran, or a PNG screenshot.
+//!
+//! One browser is launched on first use and shared; each render gets its own
+//! tab. Every request the page makes (redirects, scripts, XHR) is paused and
+//! checked against the target policy before Chromium may send it, so a page
+//! cannot reach private addresses the proxy itself would refuse. Chromium
+//! resolves names on its own, so a host that changes its DNS answer between
+//! the check and the connection is not caught.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chromiumoxide::cdp::browser_protocol::fetch::{ContinueRequestParams, EventRequestPaused, FailRequestParams};
+use chromiumoxide::cdp::browser_protocol::network::ErrorReason;
+use chromiumoxide::page::ScreenshotParams;
+use chromiumoxide::{Browser, BrowserConfig, Page};
+use futures::StreamExt;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinHandle;
+
+use crate::config::RenderConfig;
+use crate::ssrf::TargetPolicy;
+
+/// Longest a page may keep running scripts after it loaded
+pub const MAX_WAIT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Png,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "html" => Some(Format::Html),
+            "png" | "screenshot" => Some(Format::Png),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Html => "text/html; charset=utf-8",
+            Format::Png => "image/png",
+        }
+    }
+}
+
+/// A running browser and the task driving its DevTools connection
+struct Running {
+    browser: Arc<Browser>,
+    handler: JoinHandle<()>,
+}
+
+pub struct Renderer {
+    config: RenderConfig,
+    policy: Arc<TargetPolicy>,
+    browser: Mutex<Option<Running>>,
+    tabs: Semaphore,
+}
+
+impl Renderer {
+    pub fn new(config: &RenderConfig, policy: &Arc<TargetPolicy>) -> Self {
+        Renderer {
+            config: config.clone(),
+            policy: policy.clone(),
+            browser: Mutex::new(None),
+            tabs: Semaphore::new(config.max_pages.max(1)),
+        }
+    }
+
+    /// The shared browser, launched on first use and again after a crash
+    async fn browser(&self) -> Result<Arc<Browser>, String> {
+        let mut running = self.browser.lock().await;
+        if let Some(current) = running.as_ref().filter(|r| !r.handler.is_finished()) {
+            return Ok(current.browser.clone());
+        }
+
+        let mut builder = BrowserConfig::builder()
+            .new_headless_mode()
+            .enable_request_intercept()
+            .window_size(self.config.width, self.config.height)
+            .request_timeout(Duration::from_secs(self.config.timeout_secs));
+        if let Some(chrome) = &self.config.chrome {
+            builder = builder.chrome_executable(chrome);
+        }
+        if self.config.no_sandbox {
+            builder = builder.no_sandbox();
+        }
+        let config = builder.build().map_err(|e| format!("Cannot launch Chromium: {} (set chrome in [render])", e))?;
+        let (browser, mut handler) = Browser::launch(config).await
+            .map_err(|e| format!("Cannot launch Chromium: {}", e))?;
+        eprintln!("🌐 Chromium started for /render");
+        let handler = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+        let browser = Arc::new(browser);
+        *running = Some(Running { browser: browser.clone(), handler });
+        Ok(browser)
+    }
+
+    /// Load `url` and return the rendered HTML or a screenshot
+    pub async fn render(&self, url: &str, format: Format, wait: Duration) -> Result<Vec<u8>, String> {
+        let _tab = self.tabs.acquire().await.map_err(|e| e.to_string())?;
+        let browser = self.browser().await?;
+        let page = browser.new_page("about:blank").await.map_err(|e| format!("Cannot open a tab: {}", e))?;
+        let guard = self.guard(&page).await;
+
+        let timeout = Duration::from_secs(self.config.timeout_secs) + wait;
+        let result = tokio::time::timeout(timeout, async {
+            page.goto(url).await.map_err(|e| format!("Cannot load {}: {}", url, e))?;
+            tokio::time::sleep(wait).await;
+            match format {
+                Format::Html => page.content().await.map(String::into_bytes),
+                Format::Png => page.screenshot(ScreenshotParams::builder().full_page(true).build()).await,
+            }
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|_| Err(format!("Rendering {} took longer than {:?}", url, timeout)));
+
+        guard.abort();
+        let _ = page.close().await;
+        result
+    }
+
+    /// Check every request the page makes against the target policy
+    async fn guard(&self, page: &Page) -> JoinHandle<()> {
+        let Ok(mut paused) = page.event_listener::<EventRequestPaused>().await else {
+            return tokio::spawn(async {});
+        };
+        let page = page.clone();
+        let policy = self.policy.clone();
+        tokio::spawn(async move {
+            while let Some(event) = paused.next().await {
+                let url = &event.request.url;
+                // Inline data never leaves the browser
+                let local = url.starts_with("data:") || url.starts_with("blob:") || url.starts_with("about:");
+                let verdict = if local { Ok(()) } else { policy.check(url).await.map(|_| ()) };
+                let sent = match verdict {
+                    Ok(()) => page.execute(ContinueRequestParams::new(event.request_id.clone())).await.map(|_| ()),
+                    Err(reason) => {
+                        eprintln!("🚫 /render blocked {}: {}", url, reason);
+                        page.execute(FailRequestParams::new(event.request_id.clone(), ErrorReason::BlockedByClient))
+                            .await
+                            .map(|_| ())
+                    }
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(Format::parse("html"), Some(Format::Html));
+        assert_eq!(Format::parse("screenshot"), Some(Format::Png));
+        assert_eq!(Format::parse("pdf"), None);
+        assert_eq!(Format::Png.content_type(), "image/png");
+    }
+}
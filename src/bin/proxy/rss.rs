@@ -0,0 +1,162 @@
+//! Feed reading behind GET /rss?url=...
+//!
+//! RSS 2.0, RSS 1.0 (RDF) and Atom are parsed by the proxy and answered as
+//! compact JSON, with summaries reduced to plain text of at most
+//! `SUMMARY_CHARS` characters:
+//!
+//! ```json
+//! {"title": "...", "link": "...", "description": "...", "format": "rss",
+//!  "items": [{"title": "...", "link": "...", "published": "...", "author": "...", "summary": "..."}]}
+//! ```
+//!
+//! Documents with a DTD are refused rather than expanded.
+
+use roxmltree::{Document, Node};
+use serde::Serialize;
+
+use crate::search::plain;
+
+/// Longest item summary, in characters
+pub const SUMMARY_CHARS: usize = 500;
+/// Most items one request may ask for
+pub const MAX_ITEMS: usize = 100;
+
+#[derive(Debug, Serialize)]
+pub struct FeedItem {
+    pub title: String,
+    pub link: Option<String>,
+    /// As the feed states it (RFC 822 for RSS, RFC 3339 for Atom)
+    pub published: Option<String>,
+    pub author: Option<String>,
+    pub summary: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Feed {
+    pub title: String,
+    pub link: Option<String>,
+    pub description: String,
+    /// "rss", "rdf" or "atom"
+    pub format: &'static str,
+    pub items: Vec<FeedItem>,
+}
+
+/// First child element called `name`, whatever its namespace
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+/// Text of the first of `names` present, as plain text
+fn text(node: Node, names: &[&str]) -> Option<String> {
+    names.iter()
+        .filter_map(|name| child(node, name))
+        .map(|n| plain(&n.descendants().filter(|d| d.is_text()).filter_map(|d| d.text()).collect::<String>()))
+        .find(|t| !t.is_empty())
+}
+
+/// Cut `text` to `max` characters on a word boundary
+fn shorten(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+    let cut: String = text.chars().take(max).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > max / 2 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Atom's `<link rel="alternate" href>`, or RSS's `<link>` text
+fn link(node: Node) -> Option<String> {
+    let atom = node.children()
+        .filter(|n| n.is_element() && n.tag_name().name() == "link")
+        .find(|n| n.attribute("href").is_some() && matches!(n.attribute("rel"), None | Some("alternate")))
+        .and_then(|n| n.attribute("href"))
+        .map(str::to_string);
+    atom.or_else(|| text(node, &["link"]))
+        .or_else(|| child(node, "guid")
+            .filter(|g| g.attribute("isPermaLink") != Some("false"))
+            .and_then(|g| g.text())
+            .map(|g| g.trim().to_string())
+            .filter(|g| g.starts_with("http")))
+}
+
+fn item(node: Node) -> FeedItem {
+    FeedItem {
+        title: text(node, &["title"]).unwrap_or_else(|| "Untitled".to_string()),
+        link: link(node),
+        published: text(node, &["pubDate", "published", "updated", "date"]),
+        author: child(node, "author")
+            .and_then(|a| text(a, &["name"]))
+            .or_else(|| text(node, &["author", "creator"])),
+        summary: shorten(text(node, &["description", "summary", "encoded", "content"]).unwrap_or_default(), SUMMARY_CHARS),
+    }
+}
+
+/// Parse a feed document, keeping the first `max_items` items
+pub fn parse(xml: &str, max_items: usize) -> Result<Feed, String> {
+    let doc = Document::parse(xml).map_err(|e| format!("Not valid XML: {}", e))?;
+    let root = doc.root_element();
+    let (format, channel, items): (&'static str, Node, Vec<Node>) = match root.tag_name().name() {
+        "rss" => {
+            let channel = child(root, "channel").ok_or("RSS feed without a channel")?;
+            ("rss", channel, channel.children().filter(|n| n.tag_name().name() == "item").collect())
+        }
+        // RSS 1.0 keeps items next to the channel, not inside it
+        "RDF" => {
+            let channel = child(root, "channel").ok_or("RDF feed without a channel")?;
+            ("rdf", channel, root.children().filter(|n| n.tag_name().name() == "item").collect())
+        }
+        "feed" => ("atom", root, root.children().filter(|n| n.tag_name().name() == "entry").collect()),
+        other => return Err(format!("Not an RSS or Atom feed (root element <{}>)", other)),
+    };
+    Ok(Feed {
+        title: text(channel, &["title"]).unwrap_or_default(),
+        link: link(channel),
+        description: text(channel, &["description", "subtitle"]).unwrap_or_default(),
+        format,
+        items: items.into_iter().take(max_items).map(item).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rss() {
+        let xml = r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/"><channel>
+              <title>Example News</title><link>https://example.com/</link><description>Daily</description>
+              <item><title>First &amp; foremost</title><link>https://example.com/1</link>
+                <pubDate>Tue, 10 Jun 2025 04:00:00 GMT</pubDate><dc:creator>Ann</dc:creator>
+                <description><![CDATA[<p>Hello <b>world</b></p>]]></description></item>
+              <item><title>Second</title><guid>https://example.com/2</guid></item>
+            </channel></rss>"#;
+        let feed = parse(xml, 10).unwrap();
+        assert_eq!((feed.title.as_str(), feed.format), ("Example News", "rss"));
+        assert_eq!(feed.items[0].title, "First & foremost");
+        assert_eq!(feed.items[0].summary, "Hello world");
+        assert_eq!(feed.items[0].author.as_deref(), Some("Ann"));
+        assert_eq!(feed.items[1].link.as_deref(), Some("https://example.com/2"));
+        assert_eq!(parse(xml, 1).unwrap().items.len(), 1);
+    }
+
+    #[test]
+    fn test_atom() {
+        let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title>
+              <link rel="self" href="https://example.com/feed.xml"/><link href="https://example.com/"/>
+              <entry><title>Post</title><link rel="alternate" href="https://example.com/post"/>
+                <updated>2025-06-10T04:00:00Z</updated><author><name>Bo</name></author>
+                <summary>Short</summary></entry></feed>"#;
+        let feed = parse(xml, 10).unwrap();
+        assert_eq!(feed.link.as_deref(), Some("https://example.com/"));
+        let post = &feed.items[0];
+        assert_eq!((post.link.as_deref(), post.author.as_deref()), (Some("https://example.com/post"), Some("Bo")));
+        assert_eq!(post.published.as_deref(), Some("2025-06-10T04:00:00Z"));
+
+        assert!(parse("<html><body/></html>", 10).unwrap_err().contains("<html>"));
+        assert!(shorten("word ".repeat(200), 20).ends_with('…'));
+    }
+}
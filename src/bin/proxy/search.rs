@@ -0,0 +1,237 @@
+//! Web search backends behind GET /search
+//!
+//! The DuckDuckGo Instant Answer API needs no key but only knows
+//! encyclopedia-style answers, not organic results. With a key (or a
+//! SearxNG instance) configured in `[search]`, /search asks a real search
+//! engine instead. Whatever the backend, the response has one shape:
+//!
+//! ```json
+//! {"query": "...", "backend": "brave", "results": [{"title": "...", "url": "...", "snippet": "..."}]}
+//! ```
+
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    DuckDuckGo,
+    Brave,
+    SearxNG,
+    Google,
+    Bing,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::DuckDuckGo => "duckduckgo",
+            Backend::Brave => "brave",
+            Backend::SearxNG => "searxng",
+            Backend::Google => "google",
+            Backend::Bing => "bing",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name.trim().to_lowercase().as_str() {
+            "duckduckgo" | "ddg" => Some(Backend::DuckDuckGo),
+            "brave" => Some(Backend::Brave),
+            "searxng" | "searx" => Some(Backend::SearxNG),
+            "google" => Some(Backend::Google),
+            "bing" => Some(Backend::Bing),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub backend: Backend,
+    /// Brave subscription token, Google API key or Bing subscription key
+    pub api_key: Option<String>,
+    /// SearxNG instance, e.g. `https://searx.example.org`; for the others,
+    /// overrides the API endpoint
+    pub url: Option<String>,
+    /// Google Programmable Search Engine ID
+    pub cx: Option<String>,
+    /// Results per query unless the request asks for fewer
+    pub max_results: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig { backend: Backend::DuckDuckGo, api_key: None, url: None, cx: None, max_results: 10 }
+    }
+}
+
+impl SearchConfig {
+    /// Settings the backend cannot work without
+    pub fn validate(&self) -> Result<(), String> {
+        let missing = |what: &str| Err(format!("search backend {} needs {}", self.backend.name(), what));
+        match self.backend {
+            Backend::DuckDuckGo => Ok(()),
+            Backend::SearxNG if self.url.is_none() => missing("url (the SearxNG instance)"),
+            Backend::Brave | Backend::Bing if self.api_key.is_none() => missing("api_key"),
+            Backend::Google if self.api_key.is_none() || self.cx.is_none() => missing("api_key and cx"),
+            _ => Ok(()),
+        }
+    }
+
+    fn endpoint(&self, default: &str) -> String {
+        self.url.clone().unwrap_or_else(|| default.to_string())
+    }
+
+    /// The upstream request for `query`
+    pub fn request(&self, client: &Client, query: &str, count: usize) -> RequestBuilder {
+        let key = self.api_key.as_deref().unwrap_or("");
+        // Google returns at most 10 per page
+        let google_count = count.min(10).to_string();
+        let count = count.to_string();
+        match self.backend {
+            Backend::DuckDuckGo => client
+                .get(self.endpoint("https://api.duckduckgo.com/"))
+                .query(&[("q", query), ("format", "json"), ("no_html", "1"), ("skip_disambig", "1")]),
+            Backend::Brave => client
+                .get(self.endpoint("https://api.search.brave.com/res/v1/web/search"))
+                .header("X-Subscription-Token", key)
+                .header("Accept", "application/json")
+                .query(&[("q", query), ("count", &count)]),
+            Backend::SearxNG => client
+                .get(format!("{}/search", self.endpoint("").trim_end_matches('/')))
+                .query(&[("q", query), ("format", "json")]),
+            Backend::Google => client
+                .get(self.endpoint("https://www.googleapis.com/customsearch/v1"))
+                .query(&[("key", key), ("cx", self.cx.as_deref().unwrap_or("")), ("q", query), ("num", &google_count)]),
+            Backend::Bing => client
+                .get(self.endpoint("https://api.bing.microsoft.com/v7.0/search"))
+                .header("Ocp-Apim-Subscription-Key", key)
+                .query(&[("q", query), ("count", &count), ("responseFilter", "Webpages")]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub query: String,
+    pub backend: &'static str,
+    pub results: Vec<SearchResult>,
+}
+
+/// Text without tags or common entities (Brave highlights with <strong>)
+pub fn plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("&amp;", "&").replace("&quot;", "\"").replace("&#39;", "'").replace("&lt;", "<").replace("&gt;", ">")
+        .split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Map `items` to results through the given field names
+fn collect(items: Option<&Vec<Value>>, title: &str, url: &str, snippet: &str) -> Vec<SearchResult> {
+    items.into_iter().flatten()
+        .filter_map(|item| Some(SearchResult {
+            title: plain(item[title].as_str()?),
+            url: item[url].as_str()?.to_string(),
+            snippet: plain(item[snippet].as_str().unwrap_or("")),
+        }))
+        .collect()
+}
+
+/// Instant answer plus related topics, flattening topic groups
+fn duckduckgo(body: &Value) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    if let (Some(text), Some(url)) = (body["Abstract"].as_str(), body["AbstractURL"].as_str()) {
+        if !text.is_empty() {
+            let title = body["Heading"].as_str().filter(|h| !h.is_empty())
+                .or(body["AbstractSource"].as_str())
+                .unwrap_or(url);
+            results.push(SearchResult { title: title.to_string(), url: url.to_string(), snippet: text.to_string() });
+        }
+    }
+    let topics = body["RelatedTopics"].as_array().into_iter().flatten()
+        .flat_map(|topic| match topic["Topics"].as_array() {
+            Some(group) => group.iter().collect::<Vec<_>>(),
+            None => vec![topic],
+        });
+    for topic in topics {
+        if let (Some(text), Some(url)) = (topic["Text"].as_str(), topic["FirstURL"].as_str()) {
+            // Topic text usually leads with its title: "Cargo - the package manager"
+            let title = text.split(" - ").next().unwrap_or(text);
+            results.push(SearchResult { title: title.to_string(), url: url.to_string(), snippet: text.to_string() });
+        }
+    }
+    results
+}
+
+/// Normalize a backend's JSON response
+pub fn parse(backend: Backend, body: &Value) -> Vec<SearchResult> {
+    match backend {
+        Backend::DuckDuckGo => duckduckgo(body),
+        Backend::Brave => collect(body["web"]["results"].as_array(), "title", "url", "description"),
+        Backend::SearxNG => collect(body["results"].as_array(), "title", "url", "content"),
+        Backend::Google => collect(body["items"].as_array(), "title", "link", "snippet"),
+        Backend::Bing => collect(body["webPages"]["value"].as_array(), "name", "url", "snippet"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_backends_normalize() {
+        let brave = json!({"web": {"results": [{"title": "The <strong>Rust</strong> Book", "url": "https://doc.rust-lang.org/book/", "description": "Learn &amp; build"}]}});
+        assert_eq!(parse(Backend::Brave, &brave), [SearchResult {
+            title: "The Rust Book".to_string(),
+            url: "https://doc.rust-lang.org/book/".to_string(),
+            snippet: "Learn & build".to_string(),
+        }]);
+
+        let google = json!({"items": [{"title": "Rust", "link": "https://rust-lang.org", "snippet": "A language"}]});
+        assert_eq!(parse(Backend::Google, &google)[0].url, "https://rust-lang.org");
+        let bing = json!({"webPages": {"value": [{"name": "Rust", "url": "https://rust-lang.org", "snippet": "A language"}]}});
+        assert_eq!(parse(Backend::Bing, &bing)[0].title, "Rust");
+        let searx = json!({"results": [{"title": "Rust", "url": "https://rust-lang.org", "content": "A language"}, {"title": "no url"}]});
+        assert_eq!(parse(Backend::SearxNG, &searx).len(), 1);
+        assert!(parse(Backend::Bing, &json!({"error": "quota"})).is_empty());
+
+        let ddg = json!({
+            "Heading": "Rust", "Abstract": "A language.", "AbstractURL": "https://en.wikipedia.org/wiki/Rust",
+            "RelatedTopics": [
+                {"Text": "Cargo - the package manager", "FirstURL": "https://duckduckgo.com/Cargo"},
+                {"Name": "See also", "Topics": [{"Text": "Ferris - the mascot", "FirstURL": "https://duckduckgo.com/Ferris"}]}
+            ]
+        });
+        let titles: Vec<String> = parse(Backend::DuckDuckGo, &ddg).into_iter().map(|r| r.title).collect();
+        assert_eq!(titles, ["Rust", "Cargo", "Ferris"]);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(SearchConfig::default().validate().is_ok());
+        let brave = SearchConfig { backend: Backend::Brave, ..Default::default() };
+        assert!(brave.validate().unwrap_err().contains("api_key"));
+        let google = SearchConfig { backend: Backend::Google, api_key: Some("k".to_string()), ..Default::default() };
+        assert!(google.validate().unwrap_err().contains("cx"));
+        assert_eq!(Backend::parse("SearX"), Some(Backend::SearxNG));
+    }
+}
@@ -0,0 +1,246 @@
+//! Conversation state shared by the chat channels
+//!
+//! Telegram, Discord, Slack and WhatsApp keep their conversations in one
+//! store, keyed by channel and conversation (a chat, a Discord channel, a
+//! Slack thread or a phone number). A session holds the recent turns sent
+//! to the browser as `history`, the model picked with `/model` (sent as
+//! `model`, `null` for the browser's own), and when it was last used, so
+//! every channel hands the agent the same state. Each user also gets a
+//! budget of `rate` messages a minute on every channel; a channel may set
+//! its own (Telegram's `chat_rate`). Sessions idle for `idle_hours` are
+//! dropped, and past `max_sessions` the least recently used one goes.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::limits::RateLimiter;
+use crate::realtime::Channel;
+use crate::telegram::Turn;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionsConfig {
+    /// Messages a user may send per channel and minute; 0 turns the limit off
+    pub rate: u32,
+    /// Models a conversation may pick with /model; empty turns it off
+    pub models: Vec<String>,
+    /// Forget conversations idle this long; 0 keeps them
+    pub idle_hours: u64,
+    pub max_sessions: usize,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        SessionsConfig { rate: 10, models: Vec::new(), idle_hours: 72, max_sessions: 10_000 }
+    }
+}
+
+impl SessionsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_sessions == 0 {
+            return Err("sessions max_sessions must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One conversation on one channel
+struct Session {
+    turns: VecDeque<Turn>,
+    /// Picked with /model
+    model: Option<String>,
+    used: Instant,
+}
+
+/// The sessions of every channel, and the rate budgets of their users
+pub struct Sessions {
+    pub config: SessionsConfig,
+    sessions: Mutex<HashMap<(Channel, String), Session>>,
+    rate: RateLimiter,
+    /// Channels with a rate of their own
+    rates: HashMap<Channel, RateLimiter>,
+    /// At most one "slow down" or "private bot" notice per key and minute
+    notices: RateLimiter,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions::new(&SessionsConfig::default())
+    }
+}
+
+impl Sessions {
+    pub fn new(config: &SessionsConfig) -> Self {
+        Sessions {
+            config: config.clone(),
+            sessions: Mutex::new(HashMap::new()),
+            rate: RateLimiter::new(config.rate),
+            rates: HashMap::new(),
+            notices: RateLimiter::new(1),
+        }
+    }
+
+    /// Give `channel` a rate of its own instead of `rate`
+    pub fn with_rate(mut self, channel: Channel, per_minute: Option<u32>) -> Self {
+        if let Some(per_minute) = per_minute {
+            self.rates.insert(channel, RateLimiter::new(per_minute));
+        }
+        self
+    }
+
+    /// Run `f` on the session, opening it if needed
+    fn with<T>(&self, channel: Channel, key: &str, f: impl FnOnce(&mut Session) -> T) -> T {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        let id = (channel, key.to_string());
+        if !sessions.contains_key(&id) {
+            self.prune(&mut sessions, now);
+            sessions.insert(id.clone(), Session { turns: VecDeque::new(), model: None, used: now });
+        }
+        let session = sessions.get_mut(&id).expect("session was just opened");
+        session.used = now;
+        f(session)
+    }
+
+    /// Drop idle sessions, and make room for one more
+    fn prune(&self, sessions: &mut HashMap<(Channel, String), Session>, now: Instant) {
+        if self.config.idle_hours > 0 {
+            let idle = Duration::from_secs(self.config.idle_hours * 3600);
+            sessions.retain(|_, session| now.saturating_duration_since(session.used) < idle);
+        }
+        while sessions.len() >= self.config.max_sessions {
+            let Some(oldest) = sessions.iter().min_by_key(|(_, session)| session.used).map(|(id, _)| id.clone()) else {
+                break;
+            };
+            sessions.remove(&oldest);
+        }
+    }
+
+    /// Whether the conversation exists, so the bot may write to it
+    pub fn active(&self, channel: Channel, key: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(&(channel, key.to_string()))
+    }
+
+    pub fn history(&self, channel: Channel, key: &str) -> Vec<Turn> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&(channel, key.to_string())).map(|s| s.turns.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Add a turn, keeping the last `keep`
+    pub fn remember(&self, channel: Channel, key: &str, role: &'static str, content: &str, keep: usize) {
+        self.with(channel, key, |session| {
+            session.turns.push_back(Turn { role, content: content.to_string() });
+            while session.turns.len() > keep {
+                session.turns.pop_front();
+            }
+        })
+    }
+
+    /// Clear the history but keep the conversation and its model
+    pub fn forget(&self, channel: Channel, key: &str) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&(channel, key.to_string())) {
+            session.turns.clear();
+        }
+    }
+
+    /// The model the conversation picked, if any
+    pub fn model(&self, channel: Channel, key: &str) -> Option<String> {
+        self.sessions.lock().unwrap().get(&(channel, key.to_string())).and_then(|s| s.model.clone())
+    }
+
+    /// Answer `/model [name|default]` from the choice of `models`
+    pub fn model_command(&self, channel: Channel, key: &str, models: &[String], args: &str) -> String {
+        if models.is_empty() {
+            return "Switching models is turned off for this bot.".to_string();
+        }
+        let available = format!("Available: {}", models.join(", "));
+        match args {
+            "" => format!("Model: {}\n{}", self.model(channel, key).as_deref().unwrap_or("default"), available),
+            "default" => {
+                self.with(channel, key, |session| session.model = None);
+                "Back to the default model.".to_string()
+            }
+            name => match models.iter().find(|m| m.as_str() == name) {
+                Some(model) => {
+                    self.with(channel, key, |session| session.model = Some(model.clone()));
+                    format!("Switched to {}.", model)
+                }
+                None => format!("Unknown model {}. {}", name, available),
+            },
+        }
+    }
+
+    /// Take one message from `user`'s budget on `channel`, or say how long
+    /// until there is one
+    pub fn spend(&self, channel: Channel, user: &str) -> Result<(), Duration> {
+        let rate = self.rates.get(&channel).unwrap_or(&self.rate);
+        rate.check(&format!("{}:{}", channel.name(), user))
+    }
+
+    /// Whether a notice may go to `key` on `channel` now
+    pub fn notice(&self, channel: Channel, key: &str) -> bool {
+        self.notices.check(&format!("{}:{}", channel.name(), key)).is_ok()
+    }
+}
+
+/// The arguments of a `/model` message, if it is one
+pub fn model_args(text: &str) -> Option<&str> {
+    let rest = text.strip_prefix("/model")?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+/// The "please wait" notice for a spent budget
+pub fn slow_down(wait: Duration) -> String {
+    format!("Too many messages at once. Please wait {}s before the next one.", wait_secs(wait))
+}
+
+pub fn wait_secs(wait: Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_and_models() {
+        let sessions = Sessions::new(&SessionsConfig { models: vec!["gpt-4o".to_string()], ..Default::default() });
+        for text in ["one", "two", "three"] {
+            sessions.remember(Channel::Slack, "C1:1.1", "user", text, 2);
+        }
+        let history = sessions.history(Channel::Slack, "C1:1.1");
+        assert_eq!(history.iter().map(|t| t.content.as_str()).collect::<Vec<_>>(), ["two", "three"]);
+        assert!(sessions.history(Channel::Discord, "C1:1.1").is_empty(), "channels do not share conversations");
+
+        assert_eq!(sessions.model_command(Channel::Slack, "C1:1.1", &sessions.config.models, "gpt-4o"), "Switched to gpt-4o.");
+        sessions.forget(Channel::Slack, "C1:1.1");
+        assert!(sessions.active(Channel::Slack, "C1:1.1") && sessions.history(Channel::Slack, "C1:1.1").is_empty());
+        assert_eq!(sessions.model(Channel::Slack, "C1:1.1").as_deref(), Some("gpt-4o"), "/new keeps the model");
+        assert!(sessions.model_command(Channel::WhatsApp, "1", &[], "x").contains("turned off"));
+
+        assert_eq!(model_args("/model"), Some(""));
+        assert_eq!(model_args("/model  gpt-4o "), Some("gpt-4o"));
+        assert_eq!(model_args("/models"), None);
+    }
+
+    #[test]
+    fn test_limits() {
+        let sessions = Sessions::new(&SessionsConfig { rate: 1, max_sessions: 2, ..Default::default() })
+            .with_rate(Channel::Telegram, Some(0));
+        assert!(sessions.spend(Channel::Discord, "42").is_ok());
+        assert!(sessions.spend(Channel::Discord, "42").is_err());
+        assert!(sessions.spend(Channel::Slack, "42").is_ok(), "each channel has its own budget");
+        assert!((0..5).all(|_| sessions.spend(Channel::Telegram, "42").is_ok()), "0 turns the limit off");
+        assert!(sessions.notice(Channel::Discord, "42") && !sessions.notice(Channel::Discord, "42"));
+
+        for key in ["a", "b", "c"] {
+            sessions.remember(Channel::WhatsApp, key, "user", "hi", 10);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(!sessions.active(Channel::WhatsApp, "a") && sessions.active(Channel::WhatsApp, "c"), "the oldest goes first");
+        assert!(SessionsConfig { max_sessions: 0, ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,893 @@
+//! Slack bot over the Events API or Socket Mode
+//!
+//! With `signing_secret` set, POST /channel/slack/events is the app's
+//! Request URL. Each request is checked against `X-Slack-Signature`, the
+//! `url_verification` challenge is answered, and event callbacks are
+//! acknowledged at once (Slack retries anything not answered within three
+//! seconds) and published on `/ws` the way Socket Mode publishes its
+//! envelopes. Either way, a mention of the bot (`app_mention`), a direct
+//! message, or a message in a thread the bot is answering becomes a
+//! `mention` event with the mention removed and the thread's recent turns:
+//!
+//! ```json
+//! {"channel": "slack", "type": "mention", "data": {"channel": "C1", "ts": "1700.1",
+//!   "thread_ts": "1700.1", "team": "T1", "user_id": "U42", "dm": false,
+//!   "text": "...", "history": [{"role": "user", "content": "..."}], "model": null}}
+//! ```
+//!
+//! The browser answers with POST /channel/slack/send `{"channel", "text",
+//! "thread_ts", "format", "buttons"}`, posted with `bot_token` in the
+//! thread; only threads the bot was talking in (or `allowed_channels`) can
+//! be answered. `format: "markdown"` converts the text to mrkdwn in Block
+//! Kit sections, and `buttons` go under it. `/new` after the mention clears
+//! a thread's history and `/model` picks one of the `[sessions]` models for
+//! it; each user gets the `[sessions]` rate.
+//!
+//! POST /channel/slack/command is the Request URL of slash commands and of
+//! interactivity. A command is acknowledged in the channel at once and
+//! relayed as a `command` event with `command` and `command_id`; the answer
+//! sent with that `command_id` goes through the command's response URL.
+//! /ask, /research and /scan get the prompts of the Discord commands. A
+//! pressed button replaces the buttons with who chose what and arrives as a
+//! `callback` event whose `text` is the button's `data`.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::auth::constant_time_eq;
+use crate::discord::prompt;
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Event, Hub};
+use crate::sessions::{self, Sessions};
+use crate::telegram::{chunks, markdown_chunks, unix_now, Turn};
+
+pub const HOST: &str = "slack.com";
+const API: &str = "https://slack.com/api";
+pub const SIGNATURE_HEADER: &str = "X-Slack-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Slack-Request-Timestamp";
+/// Slack shows longer messages truncated
+pub const MAX_MESSAGE_CHARS: usize = 4000;
+/// Signed requests older than this are taken for replays
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+/// Event IDs remembered to drop Slack's retries
+const SEEN_EVENTS: usize = 500;
+/// Block Kit limits: characters of a section, blocks of a message, buttons
+/// of an actions block
+const MAX_SECTION_CHARS: usize = 3000;
+const MAX_BLOCKS: usize = 50;
+const MAX_BUTTONS: usize = 25;
+/// How long Slack accepts answers through a response URL
+const RESPONSE_URL_TTL: Duration = Duration::from_secs(30 * 60);
+/// Where Slack's response URLs point; others are not followed
+const RESPONSE_URL_PREFIX: &str = "https://hooks.slack.com/";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlackConfig {
+    /// Bot `xoxb-` token with chat:write; the bot is off without one
+    pub bot_token: Option<String>,
+    /// From the app's Basic Information; turns on POST /channel/slack/events
+    pub signing_secret: Option<String>,
+    /// Channel IDs where mentions are answered; empty answers in any channel
+    pub allowed_channels: Vec<String>,
+    /// Answer direct messages
+    pub dms: bool,
+    /// User IDs the bot answers; empty answers anyone
+    pub allowed_users: Vec<String>,
+    /// Messages per thread kept as context
+    pub history: usize,
+    /// Sent when no browser is connected to answer
+    pub offline_reply: Option<String>,
+}
+
+impl Default for SlackConfig {
+    fn default() -> Self {
+        SlackConfig {
+            bot_token: None,
+            signing_secret: None,
+            allowed_channels: Vec::new(),
+            dms: true,
+            allowed_users: Vec::new(),
+            history: 20,
+            offline_reply: Some("The assistant is offline right now. Please try again later.".to_string()),
+        }
+    }
+}
+
+impl SlackConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bot_token.as_deref().is_some_and(|t| !t.starts_with("xoxb-")) {
+            return Err("slack bot_token must be a bot token (xoxb-...)".to_string());
+        }
+        if self.signing_secret.is_some() && self.bot_token.is_none() {
+            return Err("slack signing_secret needs bot_token to answer with".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A message for the bot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    pub channel: String,
+    /// The message; none for a slash command
+    pub ts: Option<String>,
+    /// Thread the answer goes in; none for a direct message outside one
+    pub thread_ts: Option<String>,
+    pub team: Option<String>,
+    pub user_id: String,
+    /// A direct message rather than a mention in a channel
+    pub dm: bool,
+    pub text: String,
+}
+
+impl Incoming {
+    /// The conversation the message belongs to
+    fn thread(&self) -> String {
+        thread_key(&self.channel, self.thread_ts.as_deref())
+    }
+}
+
+fn thread_key(channel: &str, thread_ts: Option<&str>) -> String {
+    format!("{}:{}", channel, thread_ts.unwrap_or(""))
+}
+
+/// The event of an Events API callback (or Socket Mode payload) as a
+/// message for the bot, if it is one; `active` tells whether the bot is
+/// answering a thread, so replies there need no mention
+pub fn incoming(payload: &Value, active: impl Fn(&str) -> bool) -> Option<Incoming> {
+    let event = &payload["event"];
+    // The bot's own messages, edits, joins and the like
+    if !event["bot_id"].is_null() || !event["subtype"].is_null() {
+        return None;
+    }
+    let bot_user = payload["authorizations"][0]["user_id"].as_str().unwrap_or("");
+    let text = event["text"].as_str()?;
+    let mention = format!("<@{}>", bot_user);
+    let channel = event["channel"].as_str()?;
+    let dm = event["channel_type"] == "im";
+    let thread_ts = event["thread_ts"].as_str();
+    let accepted = match event["type"].as_str()? {
+        "app_mention" => true,
+        // A mention also arrives as app_mention
+        "message" if !bot_user.is_empty() && text.contains(&mention) => false,
+        "message" => dm || thread_ts.is_some_and(|ts| active(&thread_key(channel, Some(ts)))),
+        _ => false,
+    };
+    if !accepted {
+        return None;
+    }
+    let text = match bot_user.is_empty() {
+        true => text.trim_start().strip_prefix("<@").and_then(|rest| rest.split_once('>')).map_or(text, |(_, rest)| rest).to_string(),
+        false => text.replace(&mention, ""),
+    };
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+    let ts = event["ts"].as_str()?;
+    Some(Incoming {
+        channel: channel.to_string(),
+        ts: Some(ts.to_string()),
+        // Mentions are answered in a thread; DMs stay flat unless threaded
+        thread_ts: thread_ts.or((!dm).then_some(ts)).map(str::to_string),
+        team: payload["team_id"].as_str().or_else(|| event["team"].as_str()).map(str::to_string),
+        user_id: event["user"].as_str()?.to_string(),
+        dm,
+        text,
+    })
+}
+
+/// Escape text for mrkdwn, where `<` starts mentions and links
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Common Markdown as Slack's mrkdwn: emphasis, code, fenced blocks, links,
+/// headings as bold lines and bullets. Everything else is escaped, so model
+/// output cannot ping anyone.
+pub fn mrkdwn(text: &str) -> String {
+    let pre = |lines: &[&str]| format!("```\n{}\n```", escape(&lines.join("\n")));
+    let mut out = Vec::new();
+    let mut block: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            match block.take() {
+                Some(lines) => out.push(pre(&lines)),
+                None => block = Some(Vec::new()),
+            }
+            continue;
+        }
+        if let Some(lines) = &mut block {
+            lines.push(line);
+            continue;
+        }
+        let indent = &line[..line.len() - trimmed.len()];
+        let heading = trimmed.trim_start_matches('#');
+        if (1..=6).contains(&(trimmed.len() - heading.len())) && heading.starts_with(' ') {
+            out.push(format!("*{}*", inline_mrkdwn(heading.trim())));
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|bullet| trimmed.strip_prefix(bullet)) {
+            out.push(format!("{}• {}", indent, inline_mrkdwn(item)));
+        } else {
+            out.push(inline_mrkdwn(line));
+        }
+    }
+    if let Some(lines) = block {
+        out.push(pre(&lines));
+    }
+    out.join("\n")
+}
+
+/// Markdown spans of one line as mrkdwn
+fn inline_mrkdwn(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let slice = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+    let mut out = String::new();
+    let mut i = 0;
+    'scan: while i < chars.len() {
+        let rest = &chars[i..];
+        if rest[0] == '`' {
+            if let Some(end) = rest[1..].iter().position(|&c| c == '`').filter(|&end| end > 0) {
+                out.push_str(&format!("`{}`", escape(&slice(i + 1, i + 1 + end))));
+                i += end + 2;
+                continue;
+            }
+        }
+        if rest[0] == '[' {
+            let label_end = rest.iter().position(|&c| c == ']');
+            if let Some(label_end) = label_end.filter(|&end| rest.get(end + 1) == Some(&'(')) {
+                if let Some(url_end) = rest[label_end + 2..].iter().position(|&c| c == ')') {
+                    let url = slice(i + label_end + 2, i + label_end + 2 + url_end);
+                    if ["https://", "http://", "mailto:"].iter().any(|scheme| url.starts_with(scheme)) && !url.contains(['|', '>']) {
+                        let label = inline_mrkdwn(&slice(i + 1, i + label_end)).replace('|', "¦");
+                        out.push_str(&format!("<{}|{}>", escape(&url), label));
+                        i += label_end + url_end + 3;
+                        continue;
+                    }
+                }
+            }
+        }
+        for (marker, symbol) in [("**", "*"), ("__", "*"), ("~~", "~"), ("*", "_"), ("_", "_")] {
+            let marker: Vec<char> = marker.chars().collect();
+            let n = marker.len();
+            // A single _ or * inside a word (snake_case, 2*3) is not emphasis
+            let word = |c: Option<&char>| n == 1 && c.is_some_and(|c| c.is_alphanumeric());
+            if !rest.starts_with(&marker) || word(i.checked_sub(1).map(|p| &chars[p]))
+                || rest.get(n).is_none_or(|c| c.is_whitespace() || *c == marker[0]) {
+                continue;
+            }
+            let close = (i + n + 1..=chars.len().saturating_sub(n)).find(|&k| {
+                chars[k..].starts_with(&marker) && !chars[k - 1].is_whitespace() && !word(chars.get(k + n))
+            });
+            if let Some(close) = close {
+                out.push_str(&format!("{symbol}{}{symbol}", inline_mrkdwn(&slice(i + n, close))));
+                i = close + n;
+                continue 'scan;
+            }
+        }
+        out.push_str(&escape(&rest[0].to_string()));
+        i += 1;
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Plain,
+    /// Converted to mrkdwn in Block Kit sections
+    Markdown,
+}
+
+/// A button under the answer: `data` comes back in a `callback` event,
+/// `url` opens a page
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Button {
+    pub text: String,
+    pub data: Option<String>,
+    pub url: Option<String>,
+    /// `primary` (green) or `danger` (red)
+    pub style: Option<String>,
+}
+
+impl Button {
+    /// The Block Kit element, `index` making its action ID unique
+    fn element(&self, index: usize) -> Result<Value, String> {
+        let text = self.text.trim();
+        if text.is_empty() || text.chars().count() > 75 {
+            return Err(format!("Button text must be 1 to 75 characters, not {:?}", self.text));
+        }
+        let mut element = json!({
+            "type": "button",
+            "action_id": format!("button-{}", index),
+            "text": { "type": "plain_text", "text": text, "emoji": true },
+        });
+        match (&self.data, &self.url) {
+            (Some(data), None) if !data.is_empty() && data.len() <= 2000 => element["value"] = json!(data),
+            (None, Some(url)) if url.starts_with("https://") || url.starts_with("http://") => element["url"] = json!(url),
+            _ => return Err(format!("Button {:?} needs 'data' (up to 2000 bytes) or an http(s) 'url'", text)),
+        }
+        match self.style.as_deref() {
+            None => {}
+            Some(style @ ("primary" | "danger")) => element["style"] = json!(style),
+            Some(other) => return Err(format!("Button style {:?}; use primary or danger", other)),
+        }
+        Ok(element)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub channel: String,
+    pub text: String,
+    /// Thread the answer goes in
+    pub thread_ts: Option<String>,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub buttons: Vec<Button>,
+    /// Slash command the answer completes, from the `command` event
+    pub command_id: Option<String>,
+}
+
+impl SendRequest {
+    /// The messages to post, without channel or thread: plain text split at
+    /// 4000 characters, or Block Kit sections with the buttons under the
+    /// last
+    pub fn messages(&self) -> Result<Vec<Value>, String> {
+        if self.text.trim().is_empty() {
+            return Err("Missing 'text'".to_string());
+        }
+        if self.buttons.len() > MAX_BUTTONS {
+            return Err(format!("{} buttons; the limit is {}", self.buttons.len(), MAX_BUTTONS));
+        }
+        let elements = self.buttons.iter().enumerate().map(|(i, button)| button.element(i)).collect::<Result<Vec<_>, _>>()?;
+        if self.format == Format::Plain && elements.is_empty() {
+            return Ok(chunks(&self.text, MAX_MESSAGE_CHARS).into_iter().map(|text| json!({ "text": text })).collect());
+        }
+        let (kind, sections) = match self.format {
+            Format::Plain => ("plain_text", chunks(&self.text, MAX_SECTION_CHARS)),
+            Format::Markdown => ("mrkdwn", markdown_chunks(&mrkdwn(&self.text), MAX_SECTION_CHARS)),
+        };
+        let mut messages: Vec<Value> = sections.chunks(MAX_BLOCKS - 1)
+            .map(|group| json!({
+                // What notifications show
+                "text": group[0].chars().take(150).collect::<String>(),
+                "blocks": group.iter().map(|text| json!({ "type": "section", "text": { "type": kind, "text": text } })).collect::<Vec<_>>(),
+            }))
+            .collect();
+        if let (Some(last), false) = (messages.last_mut(), elements.is_empty()) {
+            last["blocks"].as_array_mut().unwrap().push(json!({ "type": "actions", "elements": elements }));
+        }
+        Ok(messages)
+    }
+}
+
+/// A slash command or button press answered through its response URL
+#[derive(Debug, Clone)]
+struct Pending {
+    response_url: String,
+    channel: String,
+    since: Instant,
+}
+
+/// A form Slack posts to /channel/slack/command, as the event type and
+/// payload Socket Mode would publish
+pub fn form(body: &[u8]) -> Result<(&'static str, Value), String> {
+    let fields: serde_json::Map<String, Value> = url::form_urlencoded::parse(body)
+        .map(|(name, value)| (name.into_owned(), json!(value)))
+        .collect();
+    match (fields.get("payload").and_then(Value::as_str), fields.contains_key("command")) {
+        (Some(payload), _) => Ok(("interactive", serde_json::from_str(payload).map_err(|e| e.to_string())?)),
+        (None, true) => Ok(("slash_commands", Value::Object(fields))),
+        (None, false) => Err("neither a slash command nor an interaction".to_string()),
+    }
+}
+
+/// The bot's settings, the sessions of the threads it talks in, and the
+/// events already handled
+pub struct Slack {
+    pub config: SlackConfig,
+    sessions: Arc<Sessions>,
+    seen: Mutex<VecDeque<String>>,
+    commands: Mutex<HashMap<String, Pending>>,
+}
+
+impl Slack {
+    pub fn new(config: &SlackConfig, sessions: Arc<Sessions>) -> Self {
+        Slack {
+            config: config.clone(),
+            sessions,
+            seen: Mutex::new(VecDeque::new()),
+            commands: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.bot_token.is_some()
+    }
+
+    pub fn events_enabled(&self) -> bool {
+        self.config.signing_secret.is_some()
+    }
+
+    /// Whether `signature` is `v0=` and the HMAC-SHA256 of the request
+    /// under the signing secret, made recently
+    pub fn verify(&self, timestamp: &str, body: &[u8], signature: &str) -> bool {
+        let Some(secret) = &self.config.signing_secret else {
+            return false;
+        };
+        let fresh = timestamp.parse::<i64>().is_ok_and(|t| (unix_now() - t).abs() <= MAX_CLOCK_SKEW_SECS);
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+        let tag = ring::hmac::sign(&key, &[format!("v0:{}:", timestamp).as_bytes(), body].concat());
+        let expected: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+        fresh && constant_time_eq(format!("v0={}", expected).as_bytes(), signature.as_bytes())
+    }
+
+    /// Whether the event was seen before; Slack redelivers what it thinks
+    /// was not acknowledged
+    pub fn seen(&self, event_id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.iter().any(|id| id == event_id) {
+            return true;
+        }
+        seen.push_back(event_id.to_string());
+        if seen.len() > SEEN_EVENTS {
+            seen.pop_front();
+        }
+        false
+    }
+
+    pub fn allows(&self, message: &Incoming) -> bool {
+        let channel = match message.dm {
+            true => self.config.dms,
+            false => self.config.allowed_channels.is_empty() || self.config.allowed_channels.contains(&message.channel),
+        };
+        channel && (self.config.allowed_users.is_empty() || self.config.allowed_users.contains(&message.user_id))
+    }
+
+    fn active(&self, thread: &str) -> bool {
+        self.sessions.active(Channel::Slack, thread)
+    }
+
+    /// Whether the bot may post in `channel`'s `thread_ts`: one of its
+    /// conversations or an allowed channel
+    pub fn may_send(&self, channel: &str, thread_ts: Option<&str>) -> bool {
+        self.config.allowed_channels.iter().any(|c| c == channel) || self.active(&thread_key(channel, thread_ts))
+    }
+
+    /// Whether `req` may be sent: an answer to a recent slash command in
+    /// its channel, or a message `may_send` allows
+    pub fn may_answer(&self, req: &SendRequest) -> bool {
+        match &req.command_id {
+            Some(id) => self.commands.lock().unwrap().get(id)
+                .is_some_and(|pending| pending.channel == req.channel && pending.since.elapsed() < RESPONSE_URL_TTL),
+            None => self.may_send(&req.channel, req.thread_ts.as_deref()),
+        }
+    }
+
+    /// Keep `response_url` to answer `id` through
+    fn pending(&self, id: &str, response_url: &str, channel: &str) {
+        let mut commands = self.commands.lock().unwrap();
+        commands.retain(|_, pending| pending.since.elapsed() < RESPONSE_URL_TTL);
+        commands.insert(id.to_string(), Pending {
+            response_url: response_url.to_string(),
+            channel: channel.to_string(),
+            since: Instant::now(),
+        });
+    }
+
+    pub fn history(&self, thread: &str) -> Vec<Turn> {
+        self.sessions.history(Channel::Slack, thread)
+    }
+
+    pub fn remember(&self, channel: &str, thread_ts: Option<&str>, role: &'static str, content: &str) {
+        self.sessions.remember(Channel::Slack, &thread_key(channel, thread_ts), role, content, self.config.history);
+    }
+
+    /// Clear the history but keep the thread answerable
+    fn forget(&self, thread: &str) {
+        self.sessions.forget(Channel::Slack, thread);
+    }
+
+    /// The model the thread picked, if any
+    fn model(&self, thread: &str) -> Option<String> {
+        self.sessions.model(Channel::Slack, thread)
+    }
+
+    /// Call a Web API method with the bot token; Slack reports failures in
+    /// a 200 answer
+    async fn call(&self, client: &reqwest::Client, method: &str, body: &Value) -> Result<Value, String> {
+        let token = self.config.bot_token.as_deref().ok_or("Slack is not configured")?;
+        let response = client.post(format!("{}/{}", API, method))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry = response.headers().get("Retry-After").and_then(|v| v.to_str().ok()).unwrap_or("1").to_string();
+            return Err(format!("rate limited; retry in {}s", retry));
+        }
+        let reply: Value = response.json().await.map_err(|e| e.to_string())?;
+        match reply["ok"].as_bool() {
+            Some(true) => Ok(reply),
+            _ => Err(reply["error"].as_str().unwrap_or("request refused").to_string()),
+        }
+    }
+
+    /// Post to a response URL, which answers a plain `ok`
+    async fn respond(&self, client: &reqwest::Client, response_url: &str, body: &Value) -> Result<(), String> {
+        let response = client.post(response_url).json(body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        match status.is_success() {
+            true => Ok(()),
+            false => Err(format!("{} {}", status.as_u16(), response.text().await.unwrap_or_default().trim())),
+        }
+    }
+
+    /// Answer through a response URL, logging failures
+    async fn answer(&self, client: &reqwest::Client, metrics: &Metrics, response_url: &str, body: Value) {
+        if let Err(e) = metrics.upstream("slack:response_url", self.respond(client, response_url, &body)).await {
+            eprintln!("❌ Slack response: {}", e);
+        }
+    }
+
+    /// Post `messages` in a channel or thread
+    async fn post(&self, client: &reqwest::Client, channel: &str, thread_ts: Option<&str>, messages: Vec<Value>) -> Result<(), String> {
+        for mut message in messages {
+            message["channel"] = json!(channel);
+            message["unfurl_links"] = json!(false);
+            if let Some(ts) = thread_ts {
+                message["thread_ts"] = json!(ts);
+            }
+            self.call(client, "chat.postMessage", &message).await?;
+        }
+        Ok(())
+    }
+
+    /// Post `text` in a channel or thread, split as needed; the answer is
+    /// the number of messages sent
+    pub async fn send(&self, client: &reqwest::Client, channel: &str, text: &str, thread_ts: Option<&str>) -> Result<usize, String> {
+        let req = SendRequest { channel: channel.to_string(), text: text.to_string(), ..Default::default() };
+        let messages = req.messages()?;
+        let count = messages.len();
+        self.post(client, channel, thread_ts, messages).await?;
+        Ok(count)
+    }
+
+    /// Send `req`, through its command's response URL or in its thread
+    pub async fn deliver(&self, client: &reqwest::Client, req: &SendRequest) -> Result<usize, String> {
+        let messages = req.messages()?;
+        let count = messages.len();
+        match &req.command_id {
+            Some(id) => {
+                let response_url = self.commands.lock().unwrap().get(id).map(|pending| pending.response_url.clone())
+                    .ok_or_else(|| format!("Unknown or expired command {}", id))?;
+                for mut message in messages {
+                    message["response_type"] = json!("in_channel");
+                    self.respond(client, &response_url, &message).await?;
+                }
+            }
+            None => self.post(client, &req.channel, req.thread_ts.as_deref(), messages).await?,
+        }
+        Ok(count)
+    }
+
+    /// Reply to `message`, logging failures
+    async fn reply(&self, client: &reqwest::Client, metrics: &Metrics, message: &Incoming, text: &str) {
+        let sent = self.send(client, &message.channel, text, message.thread_ts.as_deref());
+        if let Err(e) = metrics.upstream("slack:chat.postMessage", sent).await {
+            eprintln!("❌ Slack message: {}", e);
+        }
+    }
+
+    /// Relay one event to the browsers on /ws, or answer it here
+    async fn handle(&self, payload: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        if payload["event_id"].as_str().is_some_and(|id| self.seen(id)) {
+            return;
+        }
+        let Some(message) = incoming(payload, |thread| self.active(thread)) else {
+            return;
+        };
+        if !self.allows(&message) {
+            eprintln!("⛔ Slack: ignored {} in channel {}", message.user_id, message.channel);
+            return;
+        }
+        if let Err(wait) = self.sessions.spend(Channel::Slack, &message.user_id) {
+            eprintln!("🐢 Rate limit for Slack user {}: retry in {}s", message.user_id, sessions::wait_secs(wait));
+            if self.sessions.notice(Channel::Slack, &message.user_id) {
+                self.reply(client, metrics, &message, &sessions::slow_down(wait)).await;
+            }
+            return;
+        }
+        let thread = message.thread();
+        if matches!(message.text.as_str(), "/new" | "/reset") {
+            self.forget(&thread);
+            self.reply(client, metrics, &message, "Started a new conversation.").await;
+            return;
+        }
+        if let Some(args) = sessions::model_args(&message.text) {
+            let answer = self.sessions.model_command(Channel::Slack, &thread, &self.sessions.config.models, args);
+            self.reply(client, metrics, &message, &answer).await;
+            return;
+        }
+        if hub.listeners(Channel::Slack) == 0 {
+            eprintln!("→ Slack message in channel {} with no browser connected", message.channel);
+            if let Some(offline) = &self.config.offline_reply {
+                self.reply(client, metrics, &message, offline).await;
+            }
+            return;
+        }
+
+        eprintln!("→ Slack message in channel {}", message.channel);
+        let mut data = json!(message);
+        data["history"] = json!(self.history(&thread));
+        data["model"] = json!(self.model(&thread));
+        self.remember(&message.channel, message.thread_ts.as_deref(), "user", &message.text);
+        hub.relay(Channel::Slack, "mention", data);
+    }
+
+    /// Relay a slash command to the browsers on /ws; refusals go to the
+    /// invoking user only
+    async fn command(&self, payload: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        let field = |name: &str| payload[name].as_str().unwrap_or("").to_string();
+        let (name, input, response_url, id) = (field("command"), field("text"), field("response_url"), field("trigger_id"));
+        let name = name.trim_start_matches('/');
+        if !response_url.starts_with(RESPONSE_URL_PREFIX) || id.is_empty() {
+            eprintln!("⚠️  Slack /{}: no usable response_url or trigger_id", name);
+            return;
+        }
+        let ephemeral = |text: &str| json!({ "response_type": "ephemeral", "text": text });
+        let input = input.trim();
+        if input.is_empty() {
+            return self.answer(client, metrics, &response_url, ephemeral(&format!("Usage: /{} <text>", name))).await;
+        }
+        let channel = field("channel_id");
+        let message = Incoming {
+            ts: None,
+            thread_ts: None,
+            team: payload["team_id"].as_str().map(str::to_string),
+            user_id: field("user_id"),
+            dm: channel.starts_with('D'),
+            // Commands other than /ask, /research and /scan pass their text on
+            text: prompt(name, input).unwrap_or_else(|| input.to_string()),
+            channel,
+        };
+        if !self.allows(&message) {
+            eprintln!("⛔ Slack: refused /{} from {} in channel {}", name, message.user_id, message.channel);
+            return self.answer(client, metrics, &response_url, ephemeral("Sorry, this bot is private.")).await;
+        }
+        if let Err(wait) = self.sessions.spend(Channel::Slack, &message.user_id) {
+            eprintln!("🐢 Rate limit for Slack user {}: retry in {}s", message.user_id, sessions::wait_secs(wait));
+            return self.answer(client, metrics, &response_url, ephemeral(&sessions::slow_down(wait))).await;
+        }
+        if hub.listeners(Channel::Slack) == 0 {
+            eprintln!("→ Slack /{} in channel {} with no browser connected", name, message.channel);
+            if let Some(offline) = &self.config.offline_reply {
+                self.answer(client, metrics, &response_url, ephemeral(offline)).await;
+            }
+            return;
+        }
+
+        eprintln!("→ Slack /{} in channel {}", name, message.channel);
+        self.pending(&id, &response_url, &message.channel);
+        let mut data = json!(message);
+        data["command"] = json!(name);
+        data["command_id"] = json!(id);
+        data["history"] = json!(self.history(&message.thread()));
+        data["model"] = json!(self.model(&message.thread()));
+        self.remember(&message.channel, None, "user", &message.text);
+        hub.relay(Channel::Slack, "command", data);
+    }
+
+    /// A pressed button: its buttons give way to who chose what, and its
+    /// `data` is relayed as a `callback` event
+    async fn press(&self, payload: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        let action = &payload["actions"][0];
+        let (Some("block_actions"), Some(data), Some(channel), Some(user_id), Some(response_url), Some(id)) = (
+            payload["type"].as_str(), action["value"].as_str(), payload["channel"]["id"].as_str(),
+            payload["user"]["id"].as_str(), payload["response_url"].as_str(), payload["trigger_id"].as_str(),
+        ) else {
+            return;
+        };
+        let response_url = Some(response_url).filter(|url| url.starts_with(RESPONSE_URL_PREFIX));
+        let message = Incoming {
+            channel: channel.to_string(),
+            ts: payload["message"]["ts"].as_str().map(str::to_string),
+            thread_ts: payload["message"]["thread_ts"].as_str().map(str::to_string),
+            team: payload["team"]["id"].as_str().map(str::to_string),
+            user_id: user_id.to_string(),
+            dm: channel.starts_with('D'),
+            text: data.to_string(),
+        };
+        if !self.allows(&message) {
+            eprintln!("⛔ Slack: ignored a button from {} in channel {}", message.user_id, message.channel);
+            return;
+        }
+        // One choice per set of buttons
+        if let (Some(url), Some(blocks)) = (response_url, payload["message"]["blocks"].as_array()) {
+            let label = action["text"]["text"].as_str().unwrap_or(data);
+            let mut blocks: Vec<Value> = blocks.iter().filter(|block| block["type"] != "actions").cloned().collect();
+            blocks.push(json!({ "type": "context", "elements": [{ "type": "mrkdwn", "text": format!("<@{}> chose *{}*", user_id, escape(label)) }] }));
+            let text = payload["message"]["text"].clone();
+            self.answer(client, metrics, url, json!({ "replace_original": true, "text": text, "blocks": blocks })).await;
+        }
+        if hub.listeners(Channel::Slack) == 0 {
+            return;
+        }
+
+        eprintln!("→ Slack button in channel {}", message.channel);
+        let mut event = json!(message);
+        // Outside a thread the answer goes back through the response URL
+        if let (None, Some(url)) = (&message.thread_ts, response_url) {
+            self.pending(id, url, &message.channel);
+            event["command_id"] = json!(id);
+        }
+        event["history"] = json!(self.history(&message.thread()));
+        event["model"] = json!(self.model(&message.thread()));
+        self.remember(&message.channel, message.thread_ts.as_deref(), "user", &message.text);
+        hub.relay(Channel::Slack, "callback", event);
+    }
+
+    /// Follow the Slack events the hub publishes, from Socket Mode or the
+    /// Events API, for the life of the process
+    pub fn run(self: Arc<Self>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>) -> impl Future<Output = ()> {
+        let events = hub.subscribe();
+        self.follow(events, hub, client, metrics)
+    }
+
+    async fn follow(self: Arc<Self>, mut events: Receiver<Arc<Event>>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    eprintln!("⚠️  Slack bot fell behind and skipped {} events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if event.channel != Channel::Slack.name() {
+                continue;
+            }
+            match event.kind.as_str() {
+                "app_mention" | "message" => self.handle(&event.data, &hub, &client, &metrics).await,
+                "slash_commands" => self.command(&event.data, &hub, &client, &metrics).await,
+                "interactive" => self.press(&event.data, &hub, &client, &metrics).await,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// What the Events API endpoint does with a verified request
+#[derive(Debug, PartialEq)]
+pub enum Callback {
+    /// Echo the challenge when the Request URL is set
+    Challenge(String),
+    /// Publish the event under its type
+    Event(String),
+    Ignore,
+}
+
+pub fn callback(body: &Value) -> Callback {
+    match body["type"].as_str() {
+        Some("url_verification") => Callback::Challenge(body["challenge"].as_str().unwrap_or("").to_string()),
+        Some("event_callback") => match body["event"]["type"].as_str() {
+            Some(kind) => Callback::Event(kind.to_string()),
+            None => Callback::Ignore,
+        },
+        _ => Callback::Ignore,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(event: Value) -> Value {
+        json!({"type": "event_callback", "event_id": "Ev1", "team_id": "T1",
+            "authorizations": [{"user_id": "UBOT"}], "event": event})
+    }
+
+    #[test]
+    fn test_incoming() {
+        let mention = payload(json!({"type": "app_mention", "channel": "C1", "ts": "1.1", "user": "U42", "text": "<@UBOT> what's up?"}));
+        let message = incoming(&mention, |_| false).unwrap();
+        assert_eq!((message.text.as_str(), message.thread_ts.as_deref(), message.dm), ("what's up?", Some("1.1"), false));
+        assert_eq!(callback(&mention), Callback::Event("app_mention".to_string()));
+
+        let dm = payload(json!({"type": "message", "channel_type": "im", "channel": "D1", "ts": "2.1", "user": "U42", "text": "hi"}));
+        let message = incoming(&dm, |_| false).unwrap();
+        assert!(message.dm && message.thread_ts.is_none());
+
+        let reply = payload(json!({"type": "message", "channel_type": "channel", "channel": "C1", "ts": "1.2",
+            "thread_ts": "1.1", "user": "U42", "text": "and then?"}));
+        assert!(incoming(&reply, |_| false).is_none(), "not a thread the bot is in");
+        assert_eq!(incoming(&reply, |thread| thread == "C1:1.1").unwrap().text, "and then?");
+        let echo = payload(json!({"type": "message", "channel_type": "channel", "channel": "C1", "ts": "1.3",
+            "user": "U42", "text": "<@UBOT> hi"}));
+        assert!(incoming(&echo, |_| true).is_none(), "handled as app_mention");
+        let from_bot = payload(json!({"type": "message", "channel_type": "im", "channel": "D1", "ts": "2.2", "bot_id": "B1", "text": "hi"}));
+        assert!(incoming(&from_bot, |_| true).is_none());
+
+        assert_eq!(callback(&json!({"type": "url_verification", "challenge": "c"})), Callback::Challenge("c".to_string()));
+    }
+
+    #[test]
+    fn test_verify_and_config() {
+        let config = SlackConfig { bot_token: Some("xoxb-1".to_string()), signing_secret: Some("8f742231b10e8888abcd99yyyzzz85a5".to_string()), ..Default::default() };
+        assert!(config.validate().is_ok());
+        assert!(SlackConfig { bot_token: None, ..config.clone() }.validate().is_err());
+        assert!(SlackConfig { bot_token: Some("xapp-1".to_string()), ..config.clone() }.validate().is_err());
+
+        // The example from Slack's documentation, at a fresh timestamp
+        let slack = Slack::new(&config, Default::default());
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J";
+        let timestamp = unix_now().to_string();
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"8f742231b10e8888abcd99yyyzzz85a5");
+        let tag = ring::hmac::sign(&key, &[format!("v0:{}:", timestamp).as_bytes(), &body[..]].concat());
+        let signature = format!("v0={}", tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect::<String>());
+        assert!(slack.verify(&timestamp, body, &signature));
+        assert!(!slack.verify(&timestamp, b"token=other", &signature));
+        assert!(!slack.verify("1531420618", body, &signature), "a replay");
+
+        assert!(!slack.seen("Ev1") && slack.seen("Ev1"));
+        assert!(!slack.may_send("C1", Some("1.1")));
+        slack.remember("C1", Some("1.1"), "user", "hi");
+        slack.forget("C1:1.1");
+        assert!(slack.may_send("C1", Some("1.1")) && slack.history("C1:1.1").is_empty());
+    }
+
+    #[test]
+    fn test_mrkdwn() {
+        let text = "# Findings\n- **High**: [CVE](https://example.com/a) in `a<b>`\n* _weak_ ~~gone~~ 2*3 snake_case\n```rust\nlet x = 1 < 2;\n```";
+        assert_eq!(mrkdwn(text), "*Findings*\n• *High*: <https://example.com/a|CVE> in `a&lt;b&gt;`\n• _weak_ ~gone~ 2*3 snake_case\n```\nlet x = 1 &lt; 2;\n```");
+        assert_eq!(mrkdwn("<!channel> hi"), "&lt;!channel&gt; hi", "no pings");
+    }
+
+    #[test]
+    fn test_messages_and_forms() {
+        let plain = SendRequest { channel: "C1".to_string(), text: "a\n".repeat(3000), ..Default::default() };
+        let messages = plain.messages().unwrap();
+        assert!(messages.len() == 2 && messages[0]["blocks"].is_null());
+
+        let buttons = vec![
+            Button { text: "Approve".to_string(), data: Some("approve:action_1".to_string()), url: None, style: Some("primary".to_string()) },
+            Button { text: "Docs".to_string(), data: None, url: Some("https://example.com".to_string()), style: None },
+        ];
+        let rich = SendRequest { text: "**Run it?**".to_string(), format: Format::Markdown, buttons, ..plain };
+        let messages = rich.messages().unwrap();
+        let blocks = messages[0]["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["text"], json!({"type": "mrkdwn", "text": "*Run it?*"}));
+        assert_eq!(blocks[1]["elements"][0]["value"], "approve:action_1");
+        assert_eq!(blocks[1]["elements"][1]["action_id"], "button-1");
+        let bad = SendRequest { buttons: vec![Button { text: "x".to_string(), data: None, url: None, style: None }], ..rich };
+        assert!(bad.messages().unwrap_err().contains("'data'"));
+
+        let (kind, command) = form(b"command=%2Fask&text=what+is+wasm%3F&channel_id=C1&trigger_id=t1").unwrap();
+        assert_eq!((kind, command["text"].as_str()), ("slash_commands", Some("what is wasm?")));
+        let (kind, press) = form(b"payload=%7B%22type%22%3A%22block_actions%22%7D").unwrap();
+        assert_eq!((kind, press["type"].as_str()), ("interactive", Some("block_actions")));
+        assert!(form(b"token=x").is_err());
+
+        let slack = Slack::new(&SlackConfig::default(), Default::default());
+        slack.pending("t1", "https://hooks.slack.com/commands/1", "C1");
+        let answer = |channel: &str, id: &str| SendRequest {
+            channel: channel.to_string(), text: "Done".to_string(), command_id: Some(id.to_string()), ..Default::default()
+        };
+        assert!(slack.may_answer(&answer("C1", "t1")));
+        assert!(!slack.may_answer(&answer("C2", "t1")) && !slack.may_answer(&answer("C1", "t2")));
+    }
+}
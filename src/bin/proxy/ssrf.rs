@@ -0,0 +1,242 @@
+//! Target policy for /proxy: which URLs the proxy may fetch
+//!
+//! Without it the proxy is an open door into the machine's network: cloud
+//! metadata (169.254.169.254), router admin pages, local databases. Every
+//! target is checked for scheme, host allow/deny lists and private
+//! addresses before the request, at DNS resolution (so a public name that
+//! rebinds to 127.0.0.1 still fails) and again on every redirect hop.
+//! Configured in the `[targets]` section (see `config`).
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::Deserialize;
+use url::{Host, Url};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetPolicy {
+    /// Reachable hosts; empty means any public host
+    pub allow_hosts: Vec<String>,
+    pub deny_hosts: Vec<String>,
+    pub allow_private: bool,
+    pub schemes: Vec<String>,
+    pub max_redirects: usize,
+}
+
+impl Default for TargetPolicy {
+    fn default() -> Self {
+        TargetPolicy {
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            allow_private: false,
+            schemes: vec!["http".to_string(), "https".to_string()],
+            max_redirects: 10,
+        }
+    }
+}
+
+/// Lowercase, trimmed, `*.` prefix dropped, empty entries removed
+fn normalize_list(items: &mut Vec<String>) {
+    *items = items.iter()
+        .map(|item| item.trim().trim_start_matches("*.").to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect();
+}
+
+/// `host` is `pattern` or one of its subdomains
+fn host_matches(host: &str, pattern: &str) -> bool {
+    host == pattern || host.strip_suffix(pattern).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Addresses a public proxy must never reach
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(embedded) => is_private_v4(embedded),
+            None => is_private_v6(ip),
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 one carries to the same host: IPv4-mapped
+/// `::ffff:a.b.c.d`, IPv4-compatible `::a.b.c.d`, NAT64 `64:ff9b::/96`
+/// and 6to4 `2002::/16`
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let v4 = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0 | 0xffff, high, low] => Some(v4(high, low)),
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] => Some(v4(high, low)),
+        [0x2002, high, low, ..] => Some(v4(high, low)),
+        _ => None,
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Carrier-grade NAT 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking 198.18.0.0/15
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved 240.0.0.0/4
+        || a >= 240
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local fe80::/10 and deprecated site-local fec0::/10
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+}
+
+impl TargetPolicy {
+    /// Clean up lists as written in config files and flags
+    pub fn normalize(&mut self) {
+        normalize_list(&mut self.allow_hosts);
+        normalize_list(&mut self.deny_hosts);
+        normalize_list(&mut self.schemes);
+    }
+
+    /// Check a host name (no DNS) against the allow and deny lists
+    pub fn check_host(&self, host: &str) -> Result<(), String> {
+        let host = host.trim_end_matches('.').to_lowercase();
+        if self.deny_hosts.iter().any(|pattern| host_matches(&host, pattern)) {
+            return Err(format!("host {} is denied", host));
+        }
+        if !self.allow_hosts.is_empty() && !self.allow_hosts.iter().any(|pattern| host_matches(&host, pattern)) {
+            return Err(format!("host {} is not in the allowlist", host));
+        }
+        Ok(())
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), String> {
+        if !self.allow_private && is_private(ip) {
+            return Err(format!("{} is a private or reserved address", ip));
+        }
+        Ok(())
+    }
+
+    /// Everything that can be checked without DNS: scheme, lists, literal IPs
+    pub fn check_url(&self, url: &Url) -> Result<(), String> {
+        if !self.schemes.iter().any(|s| s == url.scheme()) {
+            return Err(format!("scheme {} is not allowed", url.scheme()));
+        }
+        match url.host() {
+            None => Err("URL has no host".to_string()),
+            Some(Host::Domain(domain)) => self.check_host(domain),
+            Some(Host::Ipv4(ip)) => self.check_host(&ip.to_string()).and(self.check_ip(IpAddr::V4(ip))),
+            Some(Host::Ipv6(ip)) => self.check_host(&ip.to_string()).and(self.check_ip(IpAddr::V6(ip))),
+        }
+    }
+
+    /// Parse and check a target, resolving its host
+    ///
+    /// Gives callers a clear 403 up front; the resolver installed by
+    /// `client_builder` enforces the same rule on the connection itself.
+    pub async fn check(&self, target: &str) -> Result<Url, String> {
+        let url = Url::parse(target).map_err(|e| format!("invalid URL: {}", e))?;
+        self.check_url(&url)?;
+        if let Some(Host::Domain(domain)) = url.host() {
+            let port = url.port_or_known_default().unwrap_or(80);
+            self.resolve(domain, port).await?;
+        }
+        Ok(url)
+    }
+
+    /// Resolve `host`, keeping only addresses the policy allows
+    pub async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await
+            .map_err(|e| format!("cannot resolve {}: {}", host, e))?
+            .collect();
+        let allowed: Vec<SocketAddr> = addrs.iter().copied().filter(|a| self.check_ip(a.ip()).is_ok()).collect();
+        if allowed.is_empty() {
+            return Err(match addrs.first() {
+                Some(addr) => format!("{} resolves to {}, a private or reserved address", host, addr.ip()),
+                None => format!("{} has no addresses", host),
+            });
+        }
+        Ok(allowed)
+    }
+
+    /// A reqwest client builder that enforces this policy on every
+    /// connection and redirect
+    pub fn client_builder(self: &Arc<Self>) -> reqwest::ClientBuilder {
+        let redirects = self.clone();
+        reqwest::Client::builder()
+            .dns_resolver(Arc::new(GuardedResolver(self.clone())))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > redirects.max_redirects {
+                    return attempt.error(format!("more than {} redirects", redirects.max_redirects));
+                }
+                match redirects.check_url(attempt.url()) {
+                    Ok(()) => attempt.follow(),
+                    Err(reason) => attempt.error(format!("redirect blocked: {}", reason)),
+                }
+            }))
+    }
+}
+
+/// DNS resolver that drops addresses the policy forbids
+struct GuardedResolver(Arc<TargetPolicy>);
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.0.clone();
+        Box::pin(async move {
+            let addrs = policy.resolve(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_private_ranges() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1",
+            "0.0.0.0", "::", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+            "64:ff9b::127.0.0.1", "64:ff9b::a9fe:a9fe", "2002:7f00:1::", "2002:a9fe:a9fe::1", "::127.0.0.1", "::169.254.169.254"] {
+            assert!(is_private(ip.parse().unwrap()), "{} should be private", ip);
+        }
+        for ip in ["8.8.8.8", "1.1.1.1", "172.32.0.1", "2606:4700::1111", "::ffff:8.8.8.8", "64:ff9b::8.8.8.8", "2002:808:808::1", "::1.1.1.1"] {
+            assert!(!is_private(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn test_url_checks() {
+        let policy = TargetPolicy { deny_hosts: vec!["evil.test".to_string()], ..Default::default() };
+        assert!(policy.check_url(&url("https://example.com/a")).is_ok());
+        assert!(policy.check_url(&url("file:///etc/passwd")).unwrap_err().contains("scheme"));
+        assert!(policy.check_url(&url("http://169.254.169.254/latest/meta-data")).is_err());
+        assert!(policy.check_url(&url("http://[::1]:8080/")).is_err());
+        assert!(policy.check_url(&url("https://api.evil.test/")).unwrap_err().contains("denied"));
+        assert!(policy.check_url(&url("https://notevil.test/")).is_ok());
+
+        let policy = TargetPolicy { allow_hosts: vec!["ollama.com".to_string()], allow_private: true, ..Default::default() };
+        assert!(policy.check_url(&url("https://api.ollama.com/v1")).is_ok());
+        assert!(policy.check_url(&url("https://example.com/")).unwrap_err().contains("allowlist"));
+        assert!(policy.check_url(&url("http://127.0.0.1/")).unwrap_err().contains("allowlist"));
+    }
+}
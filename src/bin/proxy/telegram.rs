@@ -0,0 +1,1063 @@
+//! Telegram bot behind POST /channel/telegram/webhook
+//!
+//! Telegram posts every update to the webhook, which checks the
+//! `X-Telegram-Bot-Api-Secret-Token` header (Telegram cannot send the proxy
+//! token). Text messages go to the browsers listening on `/ws` as
+//! `telegram` events, together with the chat's recent turns so the agent
+//! answering has the conversation:
+//!
+//! ```json
+//! {"channel": "telegram", "type": "message", "data": {"chat_id": 42, "message_id": 7,
+//!   "user_id": 42, "username": "ann", "name": "Ann", "chat_type": "private", "text": "...", "date": 1700000000,
+//!   "history": [{"role": "user", "content": "..."}, {"role": "assistant", "content": "..."}]}}
+//! ```
+//!
+//! The browser answers with POST /channel/telegram/send, which sends the
+//! reply and adds it to the chat's history. `/reset` in a chat forgets its
+//! history. Replies may carry formatting, files and buttons:
+//!
+//! ```json
+//! {"chat_id": 42, "reply_to": 7, "text": "**Report** ready", "format": "markdown",
+//!  "files": [{"kind": "document", "filename": "report.pdf", "data": "<base64>", "caption": "Q3"},
+//!            {"kind": "photo", "url": "https://example.com/chart.png"}],
+//!  "buttons": [[{"text": "Approve", "data": "approve"}, {"text": "Deny", "data": "deny"}]]}
+//! ```
+//!
+//! Only `allowed_users` (IDs or @usernames) reach the agent, and with it
+//! the provider's API key; anyone else gets `denied_reply`. The proxy
+//! answers `/new` (a fresh conversation), `/model` (the chat's model, one
+//! of `models`) and `/help` itself. `/tools` goes to the browser as a
+//! `command` event, since only the agent knows its tools. Events carry the
+//! chat's `model`, `null` for the browser's own.
+//!
+//! Files go first, then the text, split at 4096 characters; the buttons
+//! hang under the last message. A pressed button arrives on `/ws` as a
+//! `callback` event shaped like a message whose text is the button's
+//! `data`, and the keyboard is removed so a prompt is answered once.
+//!
+//! Without a public address, `mode = "polling"` fetches updates with
+//! long-polling getUpdates instead. The offset of the last update handled
+//! is kept in `offset_file`, so a restart neither loses nor re-answers
+//! messages, and the poller stops cleanly when the proxy shuts down. Either
+//! way each chat gets `chat_rate` messages a minute (`[sessions]` `rate`
+//! unless set), and messages older than `max_age_secs` (a backlog after
+//! downtime) are skipped. Histories, models and rate budgets live in the
+//! store of `sessions.rs`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::watch;
+
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Hub};
+use crate::sessions::{self, Sessions};
+
+pub const HOST: &str = "api.telegram.org";
+/// Longest message Telegram accepts, counted after formatting is applied
+pub const MAX_MESSAGE_CHARS: usize = 4096;
+pub const MAX_CAPTION_CHARS: usize = 1024;
+/// Upload limits of sendPhoto and sendDocument
+const MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+const MAX_DOCUMENT_BYTES: usize = 50 * 1024 * 1024;
+pub const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+/// How long one getUpdates call waits for updates
+const POLL_SECS: u64 = 50;
+/// Bot API calls, long polls included
+const API_TIMEOUT: Duration = Duration::from_secs(POLL_SECS + 30);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Update types the bot handles
+const UPDATES: [&str; 3] = ["message", "edited_message", "callback_query"];
+/// The command menu Telegram shows
+const COMMANDS: [(&str, &str); 4] = [
+    ("new", "Start a new conversation"),
+    ("model", "Show or switch the model"),
+    ("tools", "List the assistant's tools"),
+    ("help", "List the commands"),
+];
+
+/// An allowed user: the numeric ID, which never changes, or the username
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum User {
+    Id(i64),
+    Name(String),
+}
+
+impl User {
+    pub fn parse(user: &str) -> Self {
+        let user = user.trim();
+        user.parse().map(User::Id).unwrap_or_else(|_| User::Name(user.to_string()))
+    }
+
+    fn matches(&self, message: &Incoming) -> bool {
+        match self {
+            User::Id(id) => message.user_id == Some(*id),
+            User::Name(name) => match name.parse::<i64>() {
+                Ok(id) => message.user_id == Some(id),
+                Err(_) => message.username.as_deref()
+                    .is_some_and(|username| username.eq_ignore_ascii_case(name.trim_start_matches('@'))),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Telegram posts updates to /channel/telegram/webhook
+    #[default]
+    Webhook,
+    /// The proxy fetches updates with getUpdates; no public address needed
+    Polling,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// From @BotFather; the bot is off without one
+    pub bot_token: Option<String>,
+    pub mode: Mode,
+    /// Required for the webhook: only requests carrying it are updates
+    pub webhook_secret: Option<String>,
+    /// Public https:// address of this proxy; the webhook is registered
+    /// there at startup when set
+    pub public_url: Option<String>,
+    /// Chats the bot answers; empty answers every chat
+    pub allowed_chats: Vec<i64>,
+    /// Users the bot answers, e.g. `[12345, "@ann"]`; empty answers anyone
+    pub allowed_users: Vec<User>,
+    /// Sent, at most once a minute, to users not in allowed_users
+    pub denied_reply: Option<String>,
+    /// Models a chat may pick with /model; empty uses `[sessions]` models
+    pub models: Vec<String>,
+    /// Messages per chat kept as context
+    pub history: usize,
+    /// Sent when no browser is connected to answer
+    pub offline_reply: Option<String>,
+    /// Messages a chat may send per minute, instead of `[sessions]` rate;
+    /// 0 turns the limit off
+    pub chat_rate: Option<u32>,
+    /// Skip messages older than this; 0 answers any
+    pub max_age_secs: u64,
+    /// Where polling keeps the update offset
+    pub offset_file: PathBuf,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        TelegramConfig {
+            bot_token: None,
+            mode: Mode::Webhook,
+            webhook_secret: None,
+            public_url: None,
+            allowed_chats: Vec::new(),
+            allowed_users: Vec::new(),
+            denied_reply: Some("Sorry, this bot is private.".to_string()),
+            models: Vec::new(),
+            history: 20,
+            offline_reply: Some("The assistant is offline right now. Please try again later.".to_string()),
+            chat_rate: None,
+            max_age_secs: 600,
+            offset_file: PathBuf::from("telegram-offset"),
+        }
+    }
+}
+
+impl TelegramConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bot_token.is_none() {
+            return Ok(());
+        }
+        match (self.mode, self.webhook_secret.as_deref()) {
+            // Telegram's own limits for secret_token
+            (_, Some(secret)) if !((1..=256).contains(&secret.len())
+                && secret.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')) => {
+                return Err("telegram webhook_secret must be 1-256 characters of A-Z, a-z, 0-9, _ and -".to_string());
+            }
+            (Mode::Webhook, None) => return Err("telegram needs webhook_secret (the webhook is public)".to_string()),
+            _ => {}
+        }
+        for user in &self.allowed_users {
+            if let User::Name(name) = user {
+                let name = name.trim_start_matches('@');
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(format!("telegram allowed_users: {:?} is neither a user ID nor a username", name));
+                }
+            }
+        }
+        if self.mode == Mode::Polling && self.public_url.is_some() {
+            return Err("telegram public_url registers a webhook; polling mode does not use one".to_string());
+        }
+        if self.public_url.as_deref().is_some_and(|url| !url.starts_with("https://")) {
+            return Err("telegram public_url must be https://; Telegram only calls HTTPS webhooks".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A text message from a chat
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub name: String,
+    pub chat_type: String,
+    pub text: String,
+    /// Unix time the message was sent
+    pub date: i64,
+}
+
+/// The text message in an update (new or edited, text or caption), if any
+pub fn incoming(update: &Value) -> Option<Incoming> {
+    let message = ["message", "edited_message"].iter().map(|k| &update[*k]).find(|m| m.is_object())?;
+    let text = message["text"].as_str().or_else(|| message["caption"].as_str())?.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(Incoming {
+        text: text.to_string(),
+        date: message["date"].as_i64().unwrap_or(0),
+        ..sender(message, &message["from"])?
+    })
+}
+
+/// A pressed inline button as the callback query ID and a message from the
+/// presser, whose text is the button's data
+pub fn button_press(update: &Value) -> Option<(String, Incoming)> {
+    let query = &update["callback_query"];
+    let press = Incoming {
+        text: query["data"].as_str()?.to_string(),
+        date: unix_now(),
+        ..sender(&query["message"], &query["from"])?
+    };
+    Some((query["id"].as_str()?.to_string(), press))
+}
+
+/// The chat and user fields of an Incoming, for a message sent by `from`
+fn sender(message: &Value, from: &Value) -> Option<Incoming> {
+    let name = [from["first_name"].as_str(), from["last_name"].as_str()]
+        .into_iter().flatten().collect::<Vec<_>>().join(" ");
+    Some(Incoming {
+        chat_id: message["chat"]["id"].as_i64()?,
+        message_id: message["message_id"].as_i64()?,
+        user_id: from["id"].as_i64(),
+        username: from["username"].as_str().map(str::to_string),
+        name: if name.is_empty() { message["chat"]["title"].as_str().unwrap_or("").to_string() } else { name },
+        chat_type: message["chat"]["type"].as_str().unwrap_or("private").to_string(),
+        text: String::new(),
+        date: 0,
+    })
+}
+
+/// The bot command in `text` and its arguments: `/model gpt-4o` or
+/// `/model@claw_bot gpt-4o` is `("model", "gpt-4o")`
+pub fn command(text: &str) -> Option<(&str, &str)> {
+    let rest = text.trim().strip_prefix('/')?;
+    let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let name = word.split('@').next().unwrap_or("");
+    (!name.is_empty()).then_some((name, args.trim()))
+}
+
+/// Split `text` into messages of at most `limit` characters, at line
+/// breaks where possible
+pub fn chunks(text: &str, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in text.split_inclusive('\n') {
+        if current.chars().count() + line.chars().count() > limit && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if line.chars().count() > limit {
+            let chars: Vec<char> = line.chars().collect();
+            for piece in chars.chunks(limit) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+        current.push_str(line);
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks.into_iter().map(|c| c.trim_end().to_string()).filter(|c| !c.is_empty()).collect()
+}
+
+/// `chunks` for Markdown: a code block cut in two is closed at the end of
+/// one chunk and opened again at the start of the next
+pub fn markdown_chunks(text: &str, limit: usize) -> Vec<String> {
+    let mut open: Option<String> = None;
+    chunks(text, limit).into_iter().map(|chunk| {
+        let reopened = open.clone();
+        for line in chunk.lines() {
+            let line = line.trim_start();
+            if line.starts_with("```") {
+                open = match open {
+                    Some(_) => None,
+                    None => Some(line.trim_end().to_string()),
+                };
+            }
+        }
+        let mut chunk = match reopened {
+            Some(fence) => format!("{}\n{}", fence, chunk),
+            None => chunk,
+        };
+        if open.is_some() {
+            chunk.push_str("\n```");
+        }
+        chunk
+    })
+    .collect()
+}
+
+/// Escape text for `parse_mode` HTML
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Common Markdown as the HTML subset Telegram renders: emphasis, code,
+/// fenced blocks, links, headings and bullets. Everything else is
+/// escaped, so any model output is safe to send.
+pub fn markdown_html(text: &str) -> String {
+    let pre = |language: &str, lines: &[&str]| {
+        let code = escape_html(&lines.join("\n"));
+        match language.chars().all(|c| c.is_ascii_alphanumeric() || "+-#_".contains(c)) && !language.is_empty() {
+            true => format!("<pre><code class=\"language-{}\">{}</code></pre>", language, code),
+            false => format!("<pre>{}</pre>", code),
+        }
+    };
+    let mut html = Vec::new();
+    let mut block: Option<(&str, Vec<&str>)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some(language) = trimmed.strip_prefix("```") {
+            match block.take() {
+                Some((language, lines)) => html.push(pre(language, &lines)),
+                None => block = Some((language.trim(), Vec::new())),
+            }
+            continue;
+        }
+        if let Some((_, lines)) = &mut block {
+            lines.push(line);
+            continue;
+        }
+        let indent = &line[..line.len() - trimmed.len()];
+        let heading = trimmed.trim_start_matches('#');
+        if (1..=6).contains(&(trimmed.len() - heading.len())) && heading.starts_with(' ') {
+            html.push(format!("<b>{}</b>", inline_html(heading.trim())));
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|bullet| trimmed.strip_prefix(bullet)) {
+            html.push(format!("{}• {}", indent, inline_html(item)));
+        } else {
+            html.push(inline_html(line));
+        }
+    }
+    if let Some((language, lines)) = block {
+        html.push(pre(language, &lines));
+    }
+    html.join("\n")
+}
+
+/// Markdown spans of one line as HTML
+fn inline_html(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let slice = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+    let mut html = String::new();
+    let mut i = 0;
+    'scan: while i < chars.len() {
+        let rest = &chars[i..];
+        if rest[0] == '`' {
+            if let Some(end) = rest[1..].iter().position(|&c| c == '`').filter(|&end| end > 0) {
+                html.push_str(&format!("<code>{}</code>", escape_html(&slice(i + 1, i + 1 + end))));
+                i += end + 2;
+                continue;
+            }
+        }
+        if rest[0] == '[' {
+            let label_end = rest.iter().position(|&c| c == ']');
+            if let Some(label_end) = label_end.filter(|&end| rest.get(end + 1) == Some(&'(')) {
+                if let Some(url_end) = rest[label_end + 2..].iter().position(|&c| c == ')') {
+                    let url = slice(i + label_end + 2, i + label_end + 2 + url_end);
+                    if ["https://", "http://", "tg://", "mailto:"].iter().any(|scheme| url.starts_with(scheme)) {
+                        let href = escape_html(&url).replace('"', "&quot;");
+                        html.push_str(&format!("<a href=\"{}\">{}</a>", href, inline_html(&slice(i + 1, i + label_end))));
+                        i += label_end + url_end + 3;
+                        continue;
+                    }
+                }
+            }
+        }
+        for (marker, tag) in [("**", "b"), ("__", "b"), ("~~", "s"), ("*", "i"), ("_", "i")] {
+            let marker: Vec<char> = marker.chars().collect();
+            let n = marker.len();
+            // A single _ or * inside a word (snake_case, 2*3) is not emphasis
+            let word = |c: Option<&char>| n == 1 && c.is_some_and(|c| c.is_alphanumeric());
+            if !rest.starts_with(&marker) || word(i.checked_sub(1).map(|p| &chars[p]))
+                || rest.get(n).is_none_or(|c| c.is_whitespace() || *c == marker[0]) {
+                continue;
+            }
+            let close = (i + n + 1..=chars.len().saturating_sub(n)).find(|&k| {
+                chars[k..].starts_with(&marker) && !chars[k - 1].is_whitespace() && !word(chars.get(k + n))
+            });
+            if let Some(close) = close {
+                html.push_str(&format!("<{tag}>{}</{tag}>", inline_html(&slice(i + n, close))));
+                i = close + n;
+                continue 'scan;
+            }
+        }
+        html.push_str(&escape_html(&rest[0].to_string()));
+        i += 1;
+    }
+    html
+}
+
+/// One message of a chat's history, in the shape `setHistory` takes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Turn {
+    pub role: &'static str,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Plain,
+    /// Common Markdown, converted to Telegram's HTML
+    Markdown,
+    /// Telegram's HTML subset, sent as is
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    /// Shown inline; compressed by Telegram
+    Photo,
+    /// Sent as a file, e.g. a generated PDF
+    Document,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendFile {
+    pub kind: FileKind,
+    /// Fetched by Telegram itself
+    pub url: Option<String>,
+    /// Base64 content, uploaded instead
+    pub data: Option<String>,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    /// In the reply's `format`
+    pub caption: Option<String>,
+}
+
+/// An inline keyboard button: `data` comes back as a `callback` event, a
+/// `url` opens a page
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Button {
+    pub text: String,
+    #[serde(rename(serialize = "callback_data"), skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub chat_id: i64,
+    #[serde(default)]
+    pub text: String,
+    /// Message the answer replies to
+    pub reply_to: Option<i64>,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub files: Vec<SendFile>,
+    /// Rows of buttons under the last message
+    #[serde(default)]
+    pub buttons: Vec<Vec<Button>>,
+}
+
+/// A file to upload with multipart/form-data
+#[derive(Debug)]
+struct Upload {
+    field: &'static str,
+    filename: String,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+/// One Bot API call of a reply
+#[derive(Debug)]
+pub struct Outgoing {
+    method: &'static str,
+    fields: Map<String, Value>,
+    /// The field holding formatted text and its source, sent as plain
+    /// text if Telegram cannot parse the markup
+    source: Option<(&'static str, String)>,
+    upload: Option<Upload>,
+}
+
+impl SendRequest {
+    /// Check the reply and break it into Bot API calls; errors are the
+    /// client's
+    pub fn outgoing(&self) -> Result<Vec<Outgoing>, String> {
+        if self.text.trim().is_empty() && self.files.is_empty() {
+            return Err("Missing 'text' or 'files'".to_string());
+        }
+        for button in self.buttons.iter().flatten() {
+            match (&button.data, &button.url) {
+                (Some(data), None) if (1..=64).contains(&data.len()) => {}
+                (Some(_), None) => return Err(format!("Button {:?}: data must be 1-64 bytes", button.text)),
+                (None, Some(url)) if url.starts_with("https://") || url.starts_with("http://") || url.starts_with("tg://") => {}
+                _ => return Err(format!("Button {:?} needs either 'data' or an http(s) 'url'", button.text)),
+            }
+        }
+        let format = |text: &str| match self.format {
+            Format::Plain => (text.to_string(), None),
+            Format::Markdown => (markdown_html(text), Some("HTML")),
+            Format::Html => (text.to_string(), Some("HTML")),
+        };
+
+        let mut messages = Vec::new();
+        for file in &self.files {
+            let (method, field, limit) = match file.kind {
+                FileKind::Photo => ("sendPhoto", "photo", MAX_PHOTO_BYTES),
+                FileKind::Document => ("sendDocument", "document", MAX_DOCUMENT_BYTES),
+            };
+            let mut fields = Map::new();
+            let upload = match (&file.url, &file.data) {
+                (Some(url), None) if url.starts_with("https://") || url.starts_with("http://") => {
+                    fields.insert(field.to_string(), json!(url));
+                    None
+                }
+                (None, Some(data)) => {
+                    let name = file.filename.as_deref().unwrap_or(field);
+                    let data = base64::engine::general_purpose::STANDARD.decode(data.trim())
+                        .map_err(|e| format!("File {}: {}", name, e))?;
+                    if data.len() > limit {
+                        return Err(format!("File {} is larger than Telegram's {} MB for a {}", name, limit >> 20, field));
+                    }
+                    Some(Upload {
+                        field,
+                        filename: file.filename.clone().unwrap_or_else(|| match file.kind {
+                            FileKind::Photo => "photo.jpg".to_string(),
+                            FileKind::Document => "file".to_string(),
+                        }),
+                        content_type: file.content_type.clone(),
+                        data,
+                    })
+                }
+                _ => return Err(format!("Each {} needs either an http(s) 'url' or base64 'data'", field)),
+            };
+            let mut source = None;
+            if let Some(caption) = file.caption.as_deref().filter(|c| !c.trim().is_empty()) {
+                if caption.chars().count() > MAX_CAPTION_CHARS {
+                    return Err(format!("Captions are limited to {} characters", MAX_CAPTION_CHARS));
+                }
+                let (text, parse_mode) = format(caption);
+                fields.insert("caption".to_string(), json!(text));
+                if let Some(parse_mode) = parse_mode {
+                    fields.insert("parse_mode".to_string(), json!(parse_mode));
+                    source = Some(("caption", caption.to_string()));
+                }
+            }
+            messages.push(Outgoing { method, fields, source, upload });
+        }
+        let texts = match self.format {
+            Format::Markdown => markdown_chunks(&self.text, MAX_MESSAGE_CHARS),
+            _ => chunks(&self.text, MAX_MESSAGE_CHARS),
+        };
+        for chunk in texts {
+            let (text, parse_mode) = format(&chunk);
+            let mut fields = Map::new();
+            fields.insert("text".to_string(), json!(text));
+            let source = parse_mode.map(|parse_mode| {
+                fields.insert("parse_mode".to_string(), json!(parse_mode));
+                ("text", chunk)
+            });
+            messages.push(Outgoing { method: "sendMessage", fields, source, upload: None });
+        }
+
+        for message in &mut messages {
+            message.fields.insert("chat_id".to_string(), json!(self.chat_id));
+        }
+        if let (Some(first), Some(message_id)) = (messages.first_mut(), self.reply_to) {
+            first.fields.insert("reply_parameters".to_string(), json!({"message_id": message_id, "allow_sending_without_reply": true}));
+        }
+        if let (Some(last), false) = (messages.last_mut(), self.buttons.is_empty()) {
+            last.fields.insert("reply_markup".to_string(), json!({"inline_keyboard": self.buttons}));
+        }
+        Ok(messages)
+    }
+
+    /// The reply as a history turn
+    pub fn summary(&self) -> String {
+        let mut summary = self.text.trim().to_string();
+        for file in &self.files {
+            let name = file.filename.as_deref().or(file.url.as_deref()).unwrap_or("");
+            let kind = match file.kind {
+                FileKind::Photo => "photo",
+                FileKind::Document => "document",
+            };
+            summary.push_str(&format!("\n[{} {}]", kind, name));
+        }
+        summary.trim().to_string()
+    }
+}
+
+/// A Bot API answer's `result`
+pub async fn result(request: reqwest::RequestBuilder) -> Result<Value, String> {
+    let response = request.timeout(API_TIMEOUT).send().await
+        // reqwest puts the URL, and so the bot token, in its errors
+        .map_err(|e| e.without_url().to_string())?;
+    let reply: Value = response.json().await.map_err(|e| e.without_url().to_string())?;
+    match reply["ok"].as_bool() {
+        Some(true) => Ok(reply["result"].clone()),
+        _ => Err(reply["description"].as_str().unwrap_or("Telegram refused the request").to_string()),
+    }
+}
+
+fn load_offset(path: &Path) -> Option<i64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Write through a temporary file, so a crash never leaves half an offset
+fn save_offset(path: &Path, offset: i64) -> std::io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, offset.to_string())?;
+    std::fs::rename(&temporary, path)
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// The bot's settings, and the sessions its chats keep
+pub struct Telegram {
+    pub config: TelegramConfig,
+    sessions: Arc<Sessions>,
+}
+
+impl Telegram {
+    pub fn new(config: &TelegramConfig, sessions: Arc<Sessions>) -> Self {
+        Telegram { config: config.clone(), sessions }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.bot_token.is_some()
+    }
+
+    pub fn polling(&self) -> bool {
+        self.enabled() && self.config.mode == Mode::Polling
+    }
+
+    pub fn allows(&self, chat_id: i64) -> bool {
+        self.config.allowed_chats.is_empty() || self.config.allowed_chats.contains(&chat_id)
+    }
+
+    pub fn allows_user(&self, message: &Incoming) -> bool {
+        self.config.allowed_users.is_empty() || self.config.allowed_users.iter().any(|user| user.matches(message))
+    }
+
+    /// Anyone who finds the bot may use it
+    pub fn is_open(&self) -> bool {
+        self.config.allowed_users.is_empty() && self.config.allowed_chats.is_empty()
+    }
+
+    /// The model the chat picked, if any
+    pub fn model(&self, chat_id: i64) -> Option<String> {
+        self.sessions.model(Channel::Telegram, &chat_id.to_string())
+    }
+
+    /// Answer `/model [name|default]`
+    pub fn model_command(&self, chat_id: i64, args: &str) -> String {
+        let models = match self.config.models.is_empty() {
+            true => &self.sessions.config.models,
+            false => &self.config.models,
+        };
+        self.sessions.model_command(Channel::Telegram, &chat_id.to_string(), models, args)
+    }
+
+    /// Bot API URL for `method`
+    pub fn api_url(&self, method: &str) -> String {
+        format!("https://api.telegram.org/bot{}/{}", self.config.bot_token.as_deref().unwrap_or(""), method)
+    }
+
+    pub fn webhook_url(&self) -> Option<String> {
+        let base = self.config.public_url.as_deref()?;
+        Some(format!("{}/channel/telegram/webhook", base.trim_end_matches('/')))
+    }
+
+    pub fn history(&self, chat_id: i64) -> Vec<Turn> {
+        self.sessions.history(Channel::Telegram, &chat_id.to_string())
+    }
+
+    pub fn remember(&self, chat_id: i64, role: &'static str, content: &str) {
+        self.sessions.remember(Channel::Telegram, &chat_id.to_string(), role, content, self.config.history);
+    }
+
+    pub fn forget(&self, chat_id: i64) {
+        self.sessions.forget(Channel::Telegram, &chat_id.to_string());
+    }
+
+    /// Call a Bot API method; the answer is its `result`
+    pub async fn call(&self, client: &reqwest::Client, method: &str, body: &Value) -> Result<Value, String> {
+        result(client.post(self.api_url(method)).json(body)).await
+    }
+
+    /// Make one call of a reply, uploading its file if it has one;
+    /// `plain` sends the unformatted source instead
+    async fn post(&self, client: &reqwest::Client, message: &Outgoing, plain: bool) -> Result<Value, String> {
+        let mut fields = message.fields.clone();
+        if let (true, Some((field, source))) = (plain, &message.source) {
+            fields.insert(field.to_string(), json!(source));
+            fields.remove("parse_mode");
+        }
+        let Some(upload) = &message.upload else {
+            return self.call(client, message.method, &Value::Object(fields)).await;
+        };
+        let mut form = reqwest::multipart::Form::new();
+        for (name, value) in fields {
+            form = form.text(name, match value {
+                Value::String(text) => text,
+                value => value.to_string(),
+            });
+        }
+        let mut part = reqwest::multipart::Part::bytes(upload.data.clone()).file_name(upload.filename.clone());
+        if let Some(content_type) = &upload.content_type {
+            part = part.mime_str(content_type).map_err(|e| format!("File {}: {}", upload.filename, e))?;
+        }
+        result(client.post(self.api_url(message.method)).multipart(form.part(upload.field, part))).await
+    }
+
+    /// Make the calls of a reply in order; the answer is the number of
+    /// messages sent
+    pub async fn deliver(&self, client: &reqwest::Client, messages: &[Outgoing]) -> Result<usize, String> {
+        for message in messages {
+            match self.post(client, message, false).await {
+                // Hand-written HTML, or Markdown cut mid-span, that
+                // Telegram rejects still gets through as plain text
+                Err(e) if message.source.is_some() && e.contains("can't parse entities") => {
+                    eprintln!("⚠️  Telegram {}: {}; sending plain text", message.method, e);
+                    self.post(client, message, true).await?;
+                }
+                sent => {
+                    sent?;
+                }
+            }
+        }
+        Ok(messages.len())
+    }
+
+    /// Send plain `text` to a chat, split as needed
+    pub async fn send(&self, client: &reqwest::Client, chat_id: i64, text: &str, reply_to: Option<i64>) -> Result<usize, String> {
+        let reply = SendRequest { chat_id, text: text.to_string(), reply_to, ..Default::default() };
+        self.deliver(client, &reply.outgoing()?).await
+    }
+
+    /// Reply to `message`, logging failures
+    async fn reply(&self, client: &reqwest::Client, metrics: &Metrics, route: &str, message: &Incoming, text: &str) {
+        let sent = self.send(client, message.chat_id, text, Some(message.message_id));
+        if let Err(e) = metrics.upstream(route, sent).await {
+            eprintln!("❌ Telegram sendMessage: {}", e);
+        }
+    }
+
+    /// Relay one update to the browsers on /ws, or answer it here: the
+    /// path shared by the webhook and polling
+    pub async fn handle(&self, update: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics, route: &str) {
+        let (message, press) = match button_press(update) {
+            Some((id, message)) => (message, Some(id)),
+            None => match incoming(update) {
+                Some(message) => (message, None),
+                None => return,
+            },
+        };
+        if !self.allows(message.chat_id) {
+            eprintln!("⛔ Telegram: ignored chat {} (not in allowed_chats)", message.chat_id);
+            return;
+        }
+        if let Some(id) = &press {
+            // Stop the button's spinner
+            let _ = self.call(client, "answerCallbackQuery", &json!({ "callback_query_id": id })).await;
+        }
+        if !self.allows_user(&message) {
+            let user = message.user_id.map_or("unknown".to_string(), |id| id.to_string());
+            eprintln!("⛔ Telegram: denied user {} (not in allowed_users)", user);
+            if let (Some(denied), None) = (&self.config.denied_reply, &press) {
+                if self.sessions.notice(Channel::Telegram, &format!("denied:{}", user)) {
+                    self.reply(client, metrics, route, &message, denied).await;
+                }
+            }
+            return;
+        }
+        if press.is_some() {
+            // Take the keyboard away, so a prompt is answered once
+            let keyboard = json!({ "chat_id": message.chat_id, "message_id": message.message_id });
+            let _ = self.call(client, "editMessageReplyMarkup", &keyboard).await;
+        }
+        if self.config.max_age_secs > 0 && unix_now() - message.date > self.config.max_age_secs as i64 {
+            eprintln!("⏭️  Telegram: skipped a message from chat {} sent {}s ago", message.chat_id, unix_now() - message.date);
+            return;
+        }
+        if let Err(wait) = self.sessions.spend(Channel::Telegram, &message.chat_id.to_string()) {
+            eprintln!("🐢 Rate limit for Telegram chat {}: retry in {}s", message.chat_id, sessions::wait_secs(wait));
+            if self.sessions.notice(Channel::Telegram, &message.chat_id.to_string()) {
+                self.reply(client, metrics, route, &message, &sessions::slow_down(wait)).await;
+            }
+            return;
+        }
+
+        let command = command(&message.text).filter(|_| press.is_none());
+        let answer = match command {
+            Some(("new" | "reset", _)) => {
+                self.forget(message.chat_id);
+                Some("Started a new conversation.".to_string())
+            }
+            Some(("model", args)) => Some(self.model_command(message.chat_id, args)),
+            Some(("start" | "help", _)) => Some(
+                COMMANDS.iter().map(|(name, about)| format!("/{} - {}", name, about)).collect::<Vec<_>>().join("\n"),
+            ),
+            _ => None,
+        };
+        if let Some(answer) = answer {
+            self.reply(client, metrics, route, &message, &answer).await;
+            return;
+        }
+        let kind = match (press.is_some(), command) {
+            (true, _) => "callback",
+            (false, Some(("tools", _))) => "command",
+            _ => "message",
+        };
+        if hub.listeners(Channel::Telegram) == 0 {
+            eprintln!("→ Telegram {} from chat {} with no browser connected", kind, message.chat_id);
+            if let Some(offline) = &self.config.offline_reply {
+                self.reply(client, metrics, route, &message, offline).await;
+            }
+            return;
+        }
+
+        eprintln!("→ Telegram {} from chat {}", kind, message.chat_id);
+        let mut data = json!(message);
+        data["history"] = json!(self.history(message.chat_id));
+        data["model"] = json!(self.model(message.chat_id));
+        if kind == "command" {
+            data["command"] = json!("tools");
+        } else {
+            self.remember(message.chat_id, "user", &message.text);
+        }
+        hub.relay(Channel::Telegram, kind, data);
+        let typing = json!({ "chat_id": message.chat_id, "action": "typing" });
+        let _ = self.call(client, "sendChatAction", &typing).await;
+    }
+
+    /// Fetch updates until `stop` turns true, saving the offset after each
+    /// batch and once more on the way out
+    pub async fn poll(self: Arc<Self>, hub: Arc<Hub>, client: reqwest::Client, metrics: Arc<Metrics>, mut stop: watch::Receiver<bool>) {
+        const ROUTE: &str = "telegram:getUpdates";
+        let path = self.config.offset_file.clone();
+        let mut offset = load_offset(&path);
+        let mut saved = offset;
+        let mut backoff = Duration::from_secs(1);
+        // getUpdates is refused while a webhook is set
+        if let Err(e) = self.call(&client, "deleteWebhook", &json!({})).await {
+            eprintln!("⚠️  Telegram deleteWebhook: {}", e);
+        }
+        println!("   Telegram: polling for updates (offset {})", offset.map_or("none".to_string(), |o| o.to_string()));
+
+        while !*stop.borrow() {
+            let body = json!({ "offset": offset, "timeout": POLL_SECS, "allowed_updates": UPDATES });
+            let updates = tokio::select! {
+                updates = metrics.upstream(ROUTE, self.call(&client, "getUpdates", &body)) => updates,
+                _ = stop.changed() => break,
+            };
+            match updates {
+                Ok(updates) => {
+                    backoff = Duration::from_secs(1);
+                    hub.set_status(Channel::Telegram, true, None);
+                    for update in updates.as_array().into_iter().flatten() {
+                        if let Some(id) = update["update_id"].as_i64() {
+                            offset = Some(id + 1);
+                        }
+                        self.handle(update, &hub, &client, &metrics, ROUTE).await;
+                    }
+                }
+                Err(e) if e.contains("Unauthorized") => {
+                    eprintln!("❌ Telegram polling stopped: the bot token was refused");
+                    hub.set_status(Channel::Telegram, false, Some(e));
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("⚠️  Telegram getUpdates: {}; retrying in {}s", e, backoff.as_secs());
+                    hub.set_status(Channel::Telegram, false, Some(e));
+                    tokio::select! {
+                        _ = tokio::time::sleep(backoff) => {}
+                        _ = stop.changed() => break,
+                    }
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+            if let (Some(current), true) = (offset, offset != saved) {
+                match save_offset(&path, current) {
+                    Ok(()) => saved = offset,
+                    Err(e) => eprintln!("⚠️  Cannot save the Telegram offset to {}: {}", path.display(), e),
+                }
+            }
+        }
+        if let (Some(current), true) = (offset, offset != saved) {
+            let _ = save_offset(&path, current);
+        }
+        eprintln!("Telegram polling stopped");
+    }
+
+    /// Fill the command menu Telegram shows next to the message box
+    pub async fn set_commands(&self, client: &reqwest::Client) -> Result<(), String> {
+        let commands: Vec<Value> = COMMANDS.iter().map(|(name, about)| json!({ "command": name, "description": about })).collect();
+        self.call(client, "setMyCommands", &json!({ "commands": commands })).await.map(|_| ())
+    }
+
+    /// Point Telegram at this proxy's webhook
+    pub async fn register(&self, client: &reqwest::Client, url: &str) -> Result<(), String> {
+        let body = json!({
+            "url": url,
+            "secret_token": self.config.webhook_secret,
+            "allowed_updates": UPDATES,
+        });
+        self.call(client, "setWebhook", &body).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming() {
+        let update = json!({"update_id": 1, "message": {"message_id": 7, "text": " hi bot ",
+            "from": {"id": 42, "first_name": "Ann", "last_name": "Lee", "username": "ann"},
+            "chat": {"id": -100, "type": "group", "title": "Team"}}});
+        let message = incoming(&update).unwrap();
+        assert_eq!((message.chat_id, message.message_id, message.user_id), (-100, 7, Some(42)));
+        assert_eq!((message.name.as_str(), message.text.as_str()), ("Ann Lee", "hi bot"));
+        assert!(incoming(&json!({"update_id": 2, "message": {"message_id": 8, "chat": {"id": 1}, "sticker": {}}})).is_none());
+        assert_eq!(command("/model@claw_bot  gpt-4o "), Some(("model", "gpt-4o")));
+        assert_eq!(command("/new"), Some(("new", "")));
+        assert_eq!(command("hi /new"), None);
+    }
+
+    #[test]
+    fn test_history_and_chunks() {
+        let telegram = Telegram::new(&TelegramConfig { history: 2, ..Default::default() }, Default::default());
+        for text in ["one", "two", "three"] {
+            telegram.remember(1, "user", text);
+        }
+        assert_eq!(telegram.history(1).iter().map(|t| t.content.as_str()).collect::<Vec<_>>(), ["two", "three"]);
+        telegram.forget(1);
+        assert!(telegram.history(1).is_empty());
+
+        let long = format!("{}\n{}", "a".repeat(3000), "b".repeat(9000));
+        let parts = chunks(&long, MAX_MESSAGE_CHARS);
+        assert_eq!(parts.iter().map(|p| p.chars().count()).collect::<Vec<_>>(), [3000, 4096, 4096, 808]);
+        assert_eq!(chunks("short", MAX_MESSAGE_CHARS), ["short"]);
+
+        let open = TelegramConfig { bot_token: Some("1:A".to_string()), ..Default::default() };
+        assert!(open.validate().unwrap_err().contains("webhook_secret"));
+        assert!(TelegramConfig { webhook_secret: Some("s3cret".to_string()), ..open.clone() }.validate().is_ok());
+        let polling = TelegramConfig { mode: Mode::Polling, ..open };
+        assert!(polling.validate().is_ok(), "polling needs no secret");
+        assert!(TelegramConfig { public_url: Some("https://x.example".to_string()), ..polling }.validate().is_err());
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            markdown_html("## Plan\n- **bold** and _it_ <x> & `a<b`\nsnake_case 2*3*4 [docs](https://e.com/?a=1&b=\"2\")"),
+            "<b>Plan</b>\n• <b>bold</b> and <i>it</i> &lt;x&gt; &amp; <code>a&lt;b</code>\n\
+             snake_case 2*3*4 <a href=\"https://e.com/?a=1&amp;b=&quot;2&quot;\">docs</a>"
+        );
+        assert_eq!(markdown_html("```rust\nlet x = 1 < 2;\n```\nok"), "<pre><code class=\"language-rust\">let x = 1 &lt; 2;</code></pre>\nok");
+        assert_eq!(markdown_html("[x](javascript:alert(1)) **open"), "[x](javascript:alert(1)) **open");
+
+        let long = format!("```\n{}\n```", "line\n".repeat(1000));
+        let parts = markdown_chunks(&long, MAX_MESSAGE_CHARS);
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].ends_with("\n```") && parts[1].starts_with("```\n"), "the split block is closed and reopened");
+    }
+
+    #[test]
+    fn test_outgoing() {
+        let reply: SendRequest = serde_json::from_value(json!({
+            "chat_id": 42, "reply_to": 7, "text": "Report **ready**", "format": "markdown",
+            "files": [{"kind": "document", "filename": "r.pdf", "data": "aGk=", "caption": "_Q3_"},
+                      {"kind": "photo", "url": "https://example.com/chart.png"}],
+            "buttons": [[{"text": "Approve", "data": "approve"}, {"text": "Docs", "url": "https://example.com"}]]
+        })).unwrap();
+        let messages = reply.outgoing().unwrap();
+        assert_eq!(messages.iter().map(|m| m.method).collect::<Vec<_>>(), ["sendDocument", "sendPhoto", "sendMessage"]);
+        let (document, text) = (&messages[0], &messages[2]);
+        assert_eq!(document.upload.as_ref().unwrap().data, b"hi");
+        assert_eq!(document.fields["caption"], json!("<i>Q3</i>"));
+        assert_eq!(document.fields["reply_parameters"]["message_id"], json!(7));
+        assert!(!messages[1].fields.contains_key("reply_parameters") && !messages[1].fields.contains_key("reply_markup"));
+        assert_eq!((&text.fields["text"], &text.fields["parse_mode"]), (&json!("Report <b>ready</b>"), &json!("HTML")));
+        assert_eq!(text.fields["reply_markup"]["inline_keyboard"][0][0], json!({"text": "Approve", "callback_data": "approve"}));
+        assert_eq!(reply.summary(), "Report **ready**\n[document r.pdf]\n[photo https://example.com/chart.png]");
+
+        let bad = |body: Value| serde_json::from_value::<SendRequest>(body).unwrap().outgoing().unwrap_err();
+        assert!(bad(json!({"chat_id": 1, "text": " "})).contains("text"));
+        assert!(bad(json!({"chat_id": 1, "files": [{"kind": "photo"}]})).contains("url"));
+        assert!(bad(json!({"chat_id": 1, "text": "?", "buttons": [[{"text": "x", "data": "d".repeat(65)}]]})).contains("1-64"));
+
+        let press = json!({"update_id": 3, "callback_query": {"id": "cb1", "data": "approve", "from": {"id": 42, "first_name": "Ann"},
+            "message": {"message_id": 9, "chat": {"id": 42, "type": "private"}}}});
+        let (id, message) = button_press(&press).unwrap();
+        assert_eq!((id.as_str(), message.chat_id, message.message_id, message.text.as_str()), ("cb1", 42, 9, "approve"));
+    }
+
+    #[test]
+    fn test_users_and_models() {
+        let config: TelegramConfig = toml::from_str(r#"
+            bot_token = "1:A"
+            mode = "polling"
+            allowed_users = [42, "@Ann", "77"]
+            models = ["gpt-4o", "claude-sonnet"]
+        "#).unwrap();
+        assert!(config.validate().is_ok());
+        let telegram = Telegram::new(&config, Default::default());
+        let user = |id: i64, username: Option<&str>| Incoming {
+            user_id: Some(id),
+            username: username.map(str::to_string),
+            ..incoming(&json!({"message": {"message_id": 1, "chat": {"id": 1}, "text": "hi"}})).unwrap()
+        };
+        assert!(telegram.allows_user(&user(42, None)) && telegram.allows_user(&user(77, None)));
+        assert!(telegram.allows_user(&user(5, Some("ann"))), "usernames ignore case");
+        assert!(!telegram.allows_user(&user(5, Some("bob"))) && !telegram.is_open());
+        assert!(TelegramConfig { allowed_users: vec![User::parse("not a name")], ..config.clone() }.validate().is_err());
+
+        assert!(telegram.model_command(1, "").contains("Model: default"));
+        assert_eq!(telegram.model_command(1, "claude-sonnet"), "Switched to claude-sonnet.");
+        assert_eq!(telegram.model(1).as_deref(), Some("claude-sonnet"));
+        assert!(telegram.model_command(1, "gpt-5").starts_with("Unknown model"));
+        telegram.model_command(1, "default");
+        assert_eq!(telegram.model(1), None);
+        assert!(Telegram::new(&TelegramConfig::default(), Default::default()).model_command(1, "x").contains("turned off"));
+    }
+
+    #[test]
+    fn test_offset_file() {
+        let path = std::env::temp_dir().join(format!("clawasm-telegram-offset-{}", std::process::id()));
+        assert_eq!(load_offset(&path), None);
+        save_offset(&path, 1234).unwrap();
+        assert_eq!(load_offset(&path), Some(1234));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,253 @@
+//! Server-side TLS inspection for scan_ssl
+//!
+//! Browsers never expose certificates or negotiated parameters to page
+//! scripts, so the proxy performs the handshake itself: one full handshake
+//! (rustls) for the negotiated protocol, cipher and certificate chain, then
+//! per-version probes. TLS 1.0/1.1 are probed with native-tls because
+//! rustls does not implement them. Every connection goes to the addresses
+//! the target policy already resolved and checked; the host name is only
+//! sent as SNI, so a name that rebinds between the check and the handshake
+//! cannot redirect it.
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme, SupportedProtocolVersion};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use x509_parser::prelude::*;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshake results returned by GET /tls-info
+#[derive(Debug, Serialize)]
+pub struct TlsInfo {
+    pub host: String,
+    pub port: u16,
+    /// Negotiated protocol of the default handshake, e.g. "TLSv1.3"
+    pub protocol: Option<String>,
+    pub cipher: Option<String>,
+    /// Protocol version -> accepted by the server
+    pub protocols: BTreeMap<String, bool>,
+    /// Whether the chain validates against the Mozilla root store for this host
+    pub trusted: bool,
+    pub verification_error: Option<String>,
+    /// Leaf first
+    pub chain: Vec<CertInfo>,
+}
+
+/// One certificate of the presented chain
+#[derive(Debug, Serialize)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    /// Unix timestamps (seconds)
+    pub not_before: i64,
+    pub not_after: i64,
+    pub san: Vec<String>,
+    pub signature_algorithm: String,
+    /// e.g. "RSA 2048", "EC 256"
+    pub key: String,
+    pub self_signed: bool,
+}
+
+/// Accepts any certificate so broken chains can still be inspected, but
+/// records what real WebPKI verification would have said
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    result: Mutex<Option<Result<(), String>>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now);
+        *self.result.lock().unwrap() = Some(result.map(|_| ()).map_err(|e| e.to_string()));
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Perform the handshakes with `host` at `addrs` (checked by the target
+/// policy) and collect everything scan_ssl reports on
+pub async fn inspect(host: &str, port: u16, addrs: &[SocketAddr]) -> Result<TlsInfo, String> {
+    let (protocol, cipher, chain, verification) = handshake(host, addrs, &[&rustls::version::TLS13, &rustls::version::TLS12]).await?;
+
+    let mut protocols = BTreeMap::new();
+    protocols.insert("TLSv1.3".to_string(), handshake(host, addrs, &[&rustls::version::TLS13]).await.is_ok());
+    protocols.insert("TLSv1.2".to_string(), handshake(host, addrs, &[&rustls::version::TLS12]).await.is_ok());
+    protocols.insert("TLSv1.1".to_string(), legacy_handshake(host, addrs, native_tls::Protocol::Tlsv11).await);
+    protocols.insert("TLSv1.0".to_string(), legacy_handshake(host, addrs, native_tls::Protocol::Tlsv10).await);
+
+    Ok(TlsInfo {
+        host: host.to_string(),
+        port,
+        protocol,
+        cipher,
+        protocols,
+        trusted: verification.is_ok(),
+        verification_error: verification.err(),
+        chain: chain.iter().filter_map(|der| describe(der)).collect(),
+    })
+}
+
+type Handshake = (Option<String>, Option<String>, Vec<CertificateDer<'static>>, Result<(), String>);
+
+async fn handshake(host: &str, addrs: &[SocketAddr], versions: &[&'static SupportedProtocolVersion]) -> Result<Handshake, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| e.to_string())?;
+    let verifier = Arc::new(RecordingVerifier { inner, result: Mutex::new(None) });
+
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(versions)
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+
+    let server_name = ServerName::try_from(host.to_string()).map_err(|e| format!("Invalid host: {}", e))?;
+    let stream = connect(host, addrs).await?;
+    let tls = tokio::time::timeout(
+        CONNECT_TIMEOUT,
+        tokio_rustls::TlsConnector::from(Arc::new(config)).connect(server_name, stream),
+    )
+    .await
+    .map_err(|_| "TLS handshake timed out".to_string())?
+    .map_err(|e| format!("TLS handshake failed: {}", e))?;
+
+    let (_, conn) = tls.get_ref();
+    let protocol = conn.protocol_version().map(|v| match v {
+        rustls::ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+        rustls::ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+        other => format!("{:?}", other),
+    });
+    let cipher = conn.negotiated_cipher_suite().map(|s| format!("{:?}", s.suite()));
+    let chain = conn.peer_certificates()
+        .map(|certs| certs.iter().map(|c| c.clone().into_owned()).collect())
+        .unwrap_or_default();
+    let verification = verifier.result.lock().unwrap().take()
+        .unwrap_or_else(|| Err("Certificate was not verified".to_string()));
+    Ok((protocol, cipher, chain, verification))
+}
+
+/// Whether the server completes a handshake pinned to a legacy version
+async fn legacy_handshake(host: &str, addrs: &[SocketAddr], version: native_tls::Protocol) -> bool {
+    let connector = native_tls::TlsConnector::builder()
+        .min_protocol_version(Some(version))
+        .max_protocol_version(Some(version))
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build();
+    let (Ok(connector), Ok(stream)) = (connector, connect(host, addrs).await) else {
+        return false;
+    };
+    let connector = tokio_native_tls::TlsConnector::from(connector);
+    matches!(tokio::time::timeout(CONNECT_TIMEOUT, connector.connect(host, stream)).await, Ok(Ok(_)))
+}
+
+/// A connection to the first of `addrs` that answers, without another
+/// DNS lookup
+async fn connect(host: &str, addrs: &[SocketAddr]) -> Result<TcpStream, String> {
+    let port = addrs.first().map(SocketAddr::port).ok_or_else(|| format!("{} has no checked addresses", host))?;
+    tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addrs))
+        .await
+        .map_err(|_| format!("Connection to {}:{} timed out", host, port))?
+        .map_err(|e| format!("Connection to {}:{} failed: {}", host, port, e))
+}
+
+fn describe(der: &CertificateDer<'_>) -> Option<CertInfo> {
+    let (_, cert) = parse_x509_certificate(der.as_ref()).ok()?;
+    let san = cert.subject_alternative_name().ok().flatten()
+        .map(|ext| ext.value.general_names.iter().filter_map(|name| match name {
+            GeneralName::DNSName(dns) => Some(dns.to_string()),
+            GeneralName::IPAddress(ip) => Some(format!("{:?}", ip)),
+            _ => None,
+        }).collect())
+        .unwrap_or_default();
+    let key = match cert.public_key().parsed() {
+        Ok(x509_parser::public_key::PublicKey::RSA(rsa)) => format!("RSA {}", rsa.key_size()),
+        Ok(x509_parser::public_key::PublicKey::EC(ec)) => format!("EC {}", ec.key_size()),
+        Ok(other) => format!("{:?}", other).split('(').next().unwrap_or("Unknown").to_string(),
+        Err(_) => "Unknown".to_string(),
+    };
+
+    Some(CertInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        serial: cert.raw_serial_as_string(),
+        not_before: cert.validity().not_before.timestamp(),
+        not_after: cert.validity().not_after.timestamp(),
+        san,
+        signature_algorithm: signature_name(&cert.signature_algorithm.algorithm.to_id_string()),
+        key,
+        self_signed: cert.subject() == cert.issuer(),
+    })
+}
+
+fn signature_name(oid: &str) -> String {
+    match oid {
+        "1.2.840.113549.1.1.4" => "md5WithRSAEncryption",
+        "1.2.840.113549.1.1.5" => "sha1WithRSAEncryption",
+        "1.2.840.113549.1.1.11" => "sha256WithRSAEncryption",
+        "1.2.840.113549.1.1.12" => "sha384WithRSAEncryption",
+        "1.2.840.113549.1.1.13" => "sha512WithRSAEncryption",
+        "1.2.840.113549.1.1.10" => "rsassaPss",
+        "1.2.840.10045.4.1" => "ecdsa-with-SHA1",
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256",
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384",
+        "1.2.840.10045.4.3.4" => "ecdsa-with-SHA512",
+        "1.3.101.112" => "Ed25519",
+        other => other,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connects_to_the_checked_addresses_only() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // The name doesn't resolve: the connection must not look it up
+        assert!(connect("rebinding.invalid", &[addr]).await.is_ok());
+        assert!(connect("rebinding.invalid", &[]).await.unwrap_err().contains("no checked addresses"));
+    }
+}
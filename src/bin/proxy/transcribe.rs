@@ -0,0 +1,203 @@
+//! Speech to text behind POST /transcribe
+//!
+//! The audio is either the request body (with its `Content-Type`) or fetched
+//! by the proxy from `?url=`. It goes to OpenAI's Whisper API, or to a
+//! whisper.cpp server (`whisper-server`) when `[transcribe]` says so; both
+//! speak the same multipart form and `verbose_json` answer, normalized to:
+//!
+//! ```json
+//! {"text": "...", "language": "english", "duration": 12.5, "backend": "openai",
+//!  "segments": [{"start": 0.0, "end": 4.2, "text": "..."}]}
+//! ```
+
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    OpenAI,
+    WhisperCpp,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::OpenAI => "openai",
+            Backend::WhisperCpp => "whispercpp",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name.trim().to_lowercase().replace(['-', '_', '.'], "").as_str() {
+            "openai" | "whisper" => Some(Backend::OpenAI),
+            "whispercpp" => Some(Backend::WhisperCpp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscribeConfig {
+    pub backend: Backend,
+    /// OpenAI key; without one, the caller's `Authorization` header is forwarded
+    pub api_key: Option<String>,
+    /// whisper.cpp server, e.g. `http://127.0.0.1:8080`; for OpenAI,
+    /// overrides the API endpoint (Azure, compatible servers)
+    pub url: Option<String>,
+    pub model: String,
+    /// Largest accepted audio file (OpenAI's own limit is 25 MB)
+    pub max_bytes: usize,
+}
+
+impl Default for TranscribeConfig {
+    fn default() -> Self {
+        TranscribeConfig {
+            backend: Backend::OpenAI,
+            api_key: None,
+            url: None,
+            model: "whisper-1".to_string(),
+            max_bytes: 25 * 1024 * 1024,
+        }
+    }
+}
+
+/// File name to upload under; OpenAI tells formats apart by extension
+pub fn file_name(content_type: &str) -> &'static str {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    match mime.as_str() {
+        "audio/mpeg" | "audio/mp3" => "audio.mp3",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "audio.wav",
+        "audio/ogg" | "audio/opus" => "audio.ogg",
+        "audio/webm" | "video/webm" => "audio.webm",
+        "audio/flac" | "audio/x-flac" => "audio.flac",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" => "audio.m4a",
+        "video/mp4" => "audio.mp4",
+        _ => "audio.mp3",
+    }
+}
+
+impl TranscribeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.backend == Backend::WhisperCpp && self.url.is_none() {
+            return Err("transcribe backend whispercpp needs url (the whisper.cpp server)".to_string());
+        }
+        Ok(())
+    }
+
+    /// The upstream request; `authorization` is the caller's header, used
+    /// when no key is configured
+    pub fn request(
+        &self,
+        client: &Client,
+        audio: Vec<u8>,
+        content_type: &str,
+        language: Option<&str>,
+        authorization: Option<&str>,
+    ) -> Result<RequestBuilder, String> {
+        let part = Part::bytes(audio)
+            .file_name(file_name(content_type))
+            .mime_str(content_type)
+            .map_err(|e| format!("Invalid Content-Type {}: {}", content_type, e))?;
+        let mut form = Form::new()
+            .part("file", part)
+            .text("response_format", "verbose_json")
+            .text("temperature", "0");
+        if let Some(language) = language {
+            form = form.text("language", language.to_string());
+        }
+
+        match self.backend {
+            Backend::OpenAI => {
+                let auth = match (&self.api_key, authorization) {
+                    (Some(key), _) => format!("Bearer {}", key),
+                    (None, Some(header)) => header.to_string(),
+                    (None, None) => return Err("No OpenAI key: set api_key in [transcribe] or send Authorization".to_string()),
+                };
+                let endpoint = self.url.as_deref().unwrap_or("https://api.openai.com/v1/audio/transcriptions");
+                let form = form.text("model", self.model.clone()).text("timestamp_granularities[]", "segment");
+                Ok(client.post(endpoint).header("Authorization", auth).multipart(form))
+            }
+            Backend::WhisperCpp => {
+                let base = self.url.as_deref().unwrap_or("").trim_end_matches('/');
+                Ok(client.post(format!("{}/inference", base)).multipart(form))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Transcript {
+    pub text: String,
+    pub language: Option<String>,
+    pub duration: Option<f64>,
+    pub segments: Vec<Segment>,
+    pub backend: &'static str,
+}
+
+/// Normalize a `verbose_json` answer
+pub fn parse(backend: Backend, body: &Value) -> Transcript {
+    let segments: Vec<Segment> = body["segments"].as_array().into_iter().flatten()
+        .filter_map(|s| Some(Segment {
+            start: s["start"].as_f64()?,
+            end: s["end"].as_f64()?,
+            text: s["text"].as_str()?.trim().to_string(),
+        }))
+        .collect();
+    let text = match body["text"].as_str() {
+        Some(text) => text.trim().to_string(),
+        None => segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" "),
+    };
+    Transcript {
+        text,
+        language: body["language"].as_str().map(str::to_string),
+        duration: body["duration"].as_f64().or_else(|| segments.last().map(|s| s.end)),
+        segments,
+        backend: backend.name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_verbose_json() {
+        let body = json!({
+            "task": "transcribe", "language": "english", "duration": 5.5, "text": " Hello there. General Kenobi.",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 2.1, "text": " Hello there."},
+                {"id": 1, "start": 2.1, "end": 5.5, "text": " General Kenobi."}
+            ]
+        });
+        let transcript = parse(Backend::OpenAI, &body);
+        assert_eq!(transcript.text, "Hello there. General Kenobi.");
+        assert_eq!(transcript.segments[1], Segment { start: 2.1, end: 5.5, text: "General Kenobi.".to_string() });
+
+        // whisper.cpp servers without a top-level text or duration
+        let partial = parse(Backend::WhisperCpp, &json!({"segments": [{"start": 0.0, "end": 1.5, "text": " Hi"}]}));
+        assert_eq!((partial.text.as_str(), partial.duration), ("Hi", Some(1.5)));
+    }
+
+    #[test]
+    fn test_config() {
+        assert_eq!(file_name("audio/wav; codecs=1"), "audio.wav");
+        assert_eq!(Backend::parse("whisper.cpp"), Some(Backend::WhisperCpp));
+        let local = TranscribeConfig { backend: Backend::WhisperCpp, ..Default::default() };
+        assert!(local.validate().unwrap_err().contains("url"));
+        let keyless = TranscribeConfig::default().request(&Client::new(), vec![0], "audio/mpeg", None, None);
+        assert!(keyless.unwrap_err().contains("api_key"));
+    }
+}
@@ -0,0 +1,382 @@
+//! Text to speech behind POST /tts
+//!
+//! Takes `{"text", "voice", "format"}` and answers with audio from the
+//! configured backend: OpenAI's speech API, ElevenLabs, or a local Piper
+//! HTTP server. Backends cap the text per request, so long text is split at
+//! sentence boundaries and the chunks are synthesized in order. MP3 and Ogg
+//! are streamed chunk by chunk (their frames concatenate); WAV is merged
+//! into one file. Formats a backend cannot produce itself (Piper only
+//! speaks WAV, ElevenLabs only MP3 here) are converted with ffmpeg.
+
+use std::process::Stdio;
+
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::metrics::Metrics;
+use crate::upstream::Upstream;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    OpenAI,
+    ElevenLabs,
+    Piper,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::OpenAI => "openai",
+            Backend::ElevenLabs => "elevenlabs",
+            Backend::Piper => "piper",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name.trim().to_lowercase().as_str() {
+            "openai" => Some(Backend::OpenAI),
+            "elevenlabs" | "eleven" => Some(Backend::ElevenLabs),
+            "piper" => Some(Backend::Piper),
+            _ => None,
+        }
+    }
+
+    /// What the backend produces without conversion
+    fn native(self, format: Format) -> Format {
+        match self {
+            Backend::OpenAI => format,
+            Backend::ElevenLabs => Format::Mp3,
+            Backend::Piper => Format::Wav,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Mp3,
+    /// Opus in Ogg
+    Ogg,
+    Wav,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Mp3 => "audio/mpeg",
+            Format::Ogg => "audio/ogg",
+            Format::Wav => "audio/wav",
+        }
+    }
+
+    /// OpenAI's `response_format`
+    fn openai(self) -> &'static str {
+        match self {
+            Format::Mp3 => "mp3",
+            Format::Ogg => "opus",
+            Format::Wav => "wav",
+        }
+    }
+
+    /// ffmpeg output arguments
+    fn ffmpeg(self) -> &'static [&'static str] {
+        match self {
+            Format::Mp3 => &["-f", "mp3", "-codec:a", "libmp3lame", "-b:a", "128k"],
+            Format::Ogg => &["-f", "ogg", "-codec:a", "libopus", "-b:a", "64k"],
+            Format::Wav => &["-f", "wav"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TtsConfig {
+    pub backend: Backend,
+    /// OpenAI or ElevenLabs key
+    pub api_key: Option<String>,
+    /// Piper HTTP server, e.g. `http://127.0.0.1:5000`; for the others,
+    /// overrides the API endpoint
+    pub url: Option<String>,
+    /// Model; the backend's default when unset
+    pub model: Option<String>,
+    /// Voice when the request names none (OpenAI voice name, ElevenLabs
+    /// voice ID, Piper voice)
+    pub voice: Option<String>,
+    /// Most characters sent to the backend at once
+    pub chunk_chars: usize,
+    /// Longest accepted text
+    pub max_chars: usize,
+    /// ffmpeg binary for format conversion
+    pub ffmpeg: String,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        TtsConfig {
+            backend: Backend::OpenAI,
+            api_key: None,
+            url: None,
+            model: None,
+            voice: None,
+            chunk_chars: 4000,
+            max_chars: 100_000,
+            ffmpeg: "ffmpeg".to_string(),
+        }
+    }
+}
+
+/// One request to /tts
+#[derive(Debug, Deserialize)]
+pub struct TtsRequest {
+    pub text: String,
+    pub voice: Option<String>,
+    #[serde(default)]
+    pub format: Format,
+    /// Playback speed, 0.25 to 4 (OpenAI only)
+    pub speed: Option<f64>,
+}
+
+impl TtsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.backend {
+            Backend::Piper if self.url.is_none() => Err("tts backend piper needs url (the Piper HTTP server)".to_string()),
+            Backend::ElevenLabs if self.api_key.is_none() => Err("tts backend elevenlabs needs api_key".to_string()),
+            _ if self.chunk_chars < 100 => Err("tts chunk_chars must be at least 100".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The upstream request for one chunk; `authorization` is the caller's
+    /// header, used for OpenAI when no key is configured
+    pub fn request(
+        &self,
+        client: &Client,
+        chunk: &str,
+        req: &TtsRequest,
+        authorization: Option<&str>,
+    ) -> Result<RequestBuilder, String> {
+        let voice = req.voice.as_deref().or(self.voice.as_deref());
+        let format = self.backend.native(req.format);
+        match self.backend {
+            Backend::OpenAI => {
+                let auth = match (&self.api_key, authorization) {
+                    (Some(key), _) => format!("Bearer {}", key),
+                    (None, Some(header)) => header.to_string(),
+                    (None, None) => return Err("No OpenAI key: set api_key in [tts] or send Authorization".to_string()),
+                };
+                let mut body = serde_json::json!({
+                    "model": self.model.as_deref().unwrap_or("tts-1"),
+                    "input": chunk,
+                    "voice": voice.unwrap_or("alloy"),
+                    "response_format": format.openai(),
+                });
+                if let Some(speed) = req.speed {
+                    body["speed"] = speed.clamp(0.25, 4.0).into();
+                }
+                let endpoint = self.url.as_deref().unwrap_or("https://api.openai.com/v1/audio/speech");
+                Ok(client.post(endpoint).header("Authorization", auth).json(&body))
+            }
+            Backend::ElevenLabs => {
+                // "Rachel", one of the default voices
+                let voice = voice.unwrap_or("21m00Tcm4TlvDq8ikWAM");
+                let base = self.url.as_deref().unwrap_or("https://api.elevenlabs.io").trim_end_matches('/');
+                Ok(client
+                    .post(format!("{}/v1/text-to-speech/{}", base, urlencoding::encode(voice)))
+                    .query(&[("output_format", "mp3_44100_128")])
+                    .header("xi-api-key", self.api_key.as_deref().unwrap_or(""))
+                    .json(&serde_json::json!({
+                        "text": chunk,
+                        "model_id": self.model.as_deref().unwrap_or("eleven_multilingual_v2"),
+                    })))
+            }
+            Backend::Piper => {
+                let mut body = serde_json::json!({ "text": chunk });
+                if let Some(voice) = voice {
+                    body["voice"] = voice.into();
+                }
+                Ok(client.post(self.url.as_deref().unwrap_or("")).json(&body))
+            }
+        }
+    }
+
+    /// Whether the backend's audio has to go through ffmpeg
+    pub fn needs_conversion(&self, format: Format) -> bool {
+        self.backend.native(format) != format
+    }
+
+    /// One chunk as audio in the requested format; errors carry the HTTP
+    /// status to answer with
+    pub async fn synthesize(
+        &self,
+        upstream: &Upstream,
+        metrics: &Metrics,
+        chunk: &str,
+        req: &TtsRequest,
+        authorization: Option<&str>,
+    ) -> Result<Vec<u8>, (u16, String)> {
+        let request = self.request(&upstream.fixed, chunk, req, authorization).map_err(|e| (400, e))?;
+        let response = metrics.upstream("/tts", upstream.send(request)).await
+            .map_err(|e| (502, format!("{} failed: {}", self.backend.name(), crate::upstream::error_chain(&e).join(": "))))?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(|e| (502, e.to_string()))?;
+        if !status.is_success() {
+            // A rejected key, voice or text is the caller's to fix
+            let code = if status.is_client_error() { status.as_u16() } else { 502 };
+            let detail = String::from_utf8_lossy(&body).chars().take(300).collect::<String>();
+            return Err((code, format!("{} answered HTTP {}: {}", self.backend.name(), status.as_u16(), detail)));
+        }
+        if self.needs_conversion(req.format) {
+            return self.convert(body.to_vec(), req.format).await.map_err(|e| (500, e));
+        }
+        Ok(body.to_vec())
+    }
+
+    /// Convert one chunk's audio with ffmpeg
+    pub async fn convert(&self, audio: Vec<u8>, format: Format) -> Result<Vec<u8>, String> {
+        let mut child = tokio::process::Command::new(&self.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-i", "pipe:0"])
+            .args(format.ffmpeg())
+            .arg("pipe:1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Cannot run {} to convert to {:?}: {}", self.ffmpeg, format, e))?;
+        let mut stdin = child.stdin.take().ok_or("ffmpeg has no stdin")?;
+        // Write while ffmpeg's output is read, or a full pipe stalls both
+        let writer = tokio::spawn(async move {
+            let _ = stdin.write_all(&audio).await;
+        });
+        let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+        let _ = writer.await;
+        if !output.status.success() {
+            return Err(format!("ffmpeg failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Split text into chunks of at most `max` characters, preferring sentence
+/// ends, then line breaks, then spaces
+pub fn split_text(text: &str, max: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+    while rest.chars().count() > max {
+        let window: String = rest.chars().take(max).collect();
+        let cut = if rest[window.len()..].starts_with(char::is_whitespace) {
+            window.len()
+        } else {
+            [". ", "! ", "? ", "\n", "; ", ", ", " "].iter()
+                .find_map(|sep| window.rfind(sep).filter(|&i| i > 0).map(|i| i + sep.len()))
+                .unwrap_or(window.len())
+        };
+        chunks.push(rest[..cut].trim().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        chunks.push(rest.to_string());
+    }
+    chunks
+}
+
+/// The `fmt ` and `data` chunks of a RIFF/WAVE file
+fn wav_parts(wav: &[u8]) -> Option<(&[u8], &[u8])> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return None;
+    }
+    let (mut fmt, mut data) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= wav.len() {
+        let id = &wav[pos..pos + 4];
+        let declared = u32::from_le_bytes(wav[pos + 4..pos + 8].try_into().ok()?) as usize;
+        // Streaming writers leave the data size at 0 or u32::MAX
+        let size = declared.min(wav.len() - pos - 8);
+        let size = if id == b"data" && declared == 0 { wav.len() - pos - 8 } else { size };
+        let body = &wav[pos + 8..pos + 8 + size];
+        match id {
+            b"fmt " => fmt = Some(body),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        pos += 8 + size + size % 2;
+    }
+    Some((fmt?, data?))
+}
+
+/// Join WAV files of the same format into one
+pub fn merge_wav(parts: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    let mut fmt: Option<&[u8]> = None;
+    let mut samples = Vec::new();
+    for part in parts {
+        let (part_fmt, data) = wav_parts(part).ok_or("The backend did not return a WAV file")?;
+        if fmt.is_some_and(|f| f != part_fmt) {
+            return Err("WAV chunks differ in format".to_string());
+        }
+        fmt = Some(part_fmt);
+        samples.extend_from_slice(data);
+    }
+    let fmt = fmt.ok_or("No audio")?;
+    let mut wav = Vec::with_capacity(44 + samples.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&((4 + 8 + fmt.len() + 8 + samples.len()) as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    wav.extend_from_slice(fmt);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    wav.extend_from_slice(&samples);
+    Ok(wav)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(samples: &[u8]) -> Vec<u8> {
+        // 16 kHz mono 16-bit PCM
+        let fmt = [1, 0, 1, 0, 0x80, 0x3e, 0, 0, 0, 0x7d, 0, 0, 2, 0, 16, 0];
+        let mut out = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&fmt);
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        out.extend_from_slice(samples);
+        out
+    }
+
+    #[test]
+    fn test_split_text() {
+        let text = "First sentence here. Second one is a bit longer! Third?";
+        assert_eq!(split_text(text, 30), ["First sentence here.", "Second one is a bit longer!", "Third?"]);
+        assert_eq!(split_text("short", 100), ["short"]);
+        assert!(split_text("   ", 100).is_empty());
+        // No separator at all: cut hard
+        assert_eq!(split_text("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        // Multi-byte text splits on characters, not bytes
+        assert_eq!(split_text("çok güzel bir gün", 9), ["çok güzel", "bir gün"]);
+    }
+
+    #[test]
+    fn test_merge_wav() {
+        let merged = merge_wav(&[wav(&[1, 2, 3, 4]), wav(&[5, 6])]).unwrap();
+        let (_, data) = wav_parts(&merged).unwrap();
+        assert_eq!(data, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(u32::from_le_bytes(merged[4..8].try_into().unwrap()) as usize, merged.len() - 8);
+        assert!(merge_wav(&[b"ID3 not a wav".to_vec()]).is_err());
+        assert!(merge_wav(&[]).is_err());
+    }
+
+    #[test]
+    fn test_conversion_needs() {
+        let piper = TtsConfig { backend: Backend::Piper, url: Some("http://127.0.0.1:5000".to_string()), ..Default::default() };
+        assert!(piper.validate().is_ok());
+        assert!(piper.needs_conversion(Format::Mp3) && !piper.needs_conversion(Format::Wav));
+        assert!(!TtsConfig::default().needs_conversion(Format::Ogg));
+        assert!(TtsConfig { backend: Backend::ElevenLabs, ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Shared HTTP clients for upstream requests
+//!
+//! Two clients are built at startup and reused, so connections are pooled
+//! across requests: `targets` for caller-chosen URLs (/proxy and
+//! /proxy-stream), guarded by the target policy, and `fixed` for the
+//! proxy's own endpoints (DuckDuckGo, Ollama, Reddit). Both decode gzip
+//! and deflate. Certificates are verified unless `insecure_tls` is set.
+//! Idempotent requests that fail to connect, time out or get a 502/503/504
+//! are retried with exponential backoff. Configured in `[upstream]` (see
+//! `config`).
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+
+use crate::config::UpstreamConfig;
+use crate::ssrf::TargetPolicy;
+
+/// Delay before the first retry; doubled for each one after
+const BACKOFF: Duration = Duration::from_millis(250);
+
+pub struct Upstream {
+    /// Caller-chosen targets; no overall timeout so streams can run long
+    pub targets: Client,
+    /// The proxy's own search endpoints
+    pub fixed: Client,
+    /// Overall timeout for buffered requests
+    pub timeout: Duration,
+    retries: u32,
+}
+
+/// Methods safe to send twice
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE)
+}
+
+/// An error and its causes, outermost first (reqwest's own message rarely
+/// says what went wrong)
+pub fn error_chain(e: &reqwest::Error) -> Vec<String> {
+    std::iter::successors(Some(e as &dyn std::error::Error), |e| e.source())
+        .map(|e| e.to_string())
+        .collect()
+}
+
+pub fn is_certificate_error(e: &reqwest::Error) -> bool {
+    error_chain(e).iter().any(|cause| cause.to_lowercase().contains("certificate"))
+}
+
+/// Whether a failed attempt is worth repeating
+fn retryable(result: &reqwest::Result<Response>) -> bool {
+    match result {
+        Ok(response) => matches!(
+            response.status(),
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        ),
+        // A refused redirect, a malformed request or a bad certificate fails
+        // the same way every time
+        Err(e) => !e.is_redirect() && !e.is_builder() && !is_certificate_error(e)
+            && (e.is_connect() || e.is_timeout() || e.is_request()),
+    }
+}
+
+impl Upstream {
+    pub fn new(config: &UpstreamConfig, policy: &Arc<TargetPolicy>) -> Result<Self, String> {
+        let connect_timeout = Duration::from_secs(config.connect_timeout_secs);
+        let timeout = Duration::from_secs(config.timeout_secs);
+        let targets = policy.client_builder()
+            .use_native_tls()
+            .danger_accept_invalid_certs(config.insecure_tls)
+            .connect_timeout(connect_timeout)
+            .gzip(true)
+            .deflate(true)
+            .build()
+            .map_err(|e| format!("Cannot build the upstream client: {}", e))?;
+        let fixed = Client::builder()
+            .connect_timeout(connect_timeout)
+            .timeout(timeout)
+            .gzip(true)
+            .deflate(true)
+            .build()
+            .map_err(|e| format!("Cannot build the upstream client: {}", e))?;
+        Ok(Upstream { targets, fixed, timeout, retries: config.retries })
+    }
+
+    /// Send a request, retrying idempotent ones on transient failures
+    pub async fn send(&self, builder: RequestBuilder) -> reqwest::Result<Response> {
+        let (client, request) = builder.build_split();
+        let mut request = request?;
+        let idempotent = is_idempotent(request.method());
+        let mut attempt = 0;
+        loop {
+            let spare = if idempotent && attempt < self.retries { request.try_clone() } else { None };
+            let url = request.url().to_string();
+            let result = client.execute(request).await;
+            let Some(next) = spare.filter(|_| retryable(&result)) else {
+                return result;
+            };
+            let delay = BACKOFF * 2u32.pow(attempt);
+            match &result {
+                Ok(response) => eprintln!("⚠️  {} answered {}; retrying in {:?}", url, response.status().as_u16(), delay),
+                Err(e) => eprintln!("⚠️  {} failed: {}; retrying in {:?}", url, e, delay),
+            }
+            tokio::time::sleep(delay).await;
+            request = next;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_idempotent_methods_retry() {
+        assert!(is_idempotent(&Method::GET) && is_idempotent(&Method::PUT) && is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST) && !is_idempotent(&Method::PATCH));
+    }
+
+    #[tokio::test]
+    async fn test_retries_refused_connections() {
+        // Nothing listens on port 9 of loopback: every attempt is refused
+        let policy = Arc::new(TargetPolicy { allow_private: true, ..Default::default() });
+        let config = UpstreamConfig { retries: 2, ..Default::default() };
+        let upstream = Upstream::new(&config, &policy).unwrap();
+        let started = std::time::Instant::now();
+        let result = upstream.send(upstream.targets.get("http://127.0.0.1:9/")).await;
+        assert!(result.unwrap_err().is_connect());
+        // 250ms + 500ms of backoff
+        assert!(started.elapsed() >= Duration::from_millis(750));
+
+        let started = std::time::Instant::now();
+        assert!(upstream.send(upstream.targets.post("http://127.0.0.1:9/")).await.is_err());
+        assert!(started.elapsed() < Duration::from_millis(250), "POST is not retried");
+    }
+}
@@ -0,0 +1,557 @@
+//! WhatsApp bot on the Cloud API, behind /channel/whatsapp/webhook
+//!
+//! Meta checks the callback URL with a GET carrying `hub.verify_token`,
+//! answered with `hub.challenge` when it matches `verify_token`, and then
+//! posts notifications signed with the app secret in `X-Hub-Signature-256`.
+//! Text messages to `phone_number_id` become `message` events on `/ws`;
+//! taps on reply buttons, list rows and template quick replies become
+//! `callback` events whose `text` is the button's `data`:
+//!
+//! ```json
+//! {"channel": "whatsapp", "type": "message", "data": {"from": "15551234567", "message_id": "wamid.X",
+//!   "name": "Ann", "text": "...", "timestamp": 1700000000, "history": [{"role": "user", "content": "..."}],
+//!   "model": null}}
+//! ```
+//!
+//! The browser answers with POST /channel/whatsapp/send `{"to", "text",
+//! "reply_to", "buttons", "list", "template"}`, sent through the Graph API
+//! with `access_token`. Free-form answers are only accepted within 24 hours
+//! of the user's last message; after that WhatsApp takes templates alone.
+//! `/new` clears a conversation and `/model` picks one of the `[sessions]`
+//! models for it; each number gets the `[sessions]` rate.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::auth::constant_time_eq;
+use crate::metrics::Metrics;
+use crate::realtime::{Channel, Hub};
+use crate::sessions::{self, Sessions};
+use crate::telegram::{chunks, unix_now, Turn};
+
+pub const HOST: &str = "graph.facebook.com";
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+pub const MAX_MESSAGE_CHARS: usize = 4096;
+/// Limits of interactive messages: body text, reply buttons, list rows
+const MAX_BODY_CHARS: usize = 1024;
+const MAX_BUTTONS: usize = 3;
+const MAX_ROWS: usize = 10;
+/// Message IDs remembered to drop redeliveries
+const SEEN_MESSAGES: usize = 500;
+/// Older messages (redelivered after an outage) are not answered
+const MAX_AGE_SECS: i64 = 600;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WhatsAppConfig {
+    /// System user or temporary access token; the channel is off without one
+    pub access_token: Option<String>,
+    /// The business number's ID (not the phone number itself)
+    pub phone_number_id: Option<String>,
+    /// Any string; entered again in the app's webhook settings
+    pub verify_token: Option<String>,
+    /// From the app's Basic settings; checks each notification's signature
+    pub app_secret: Option<String>,
+    /// Numbers (as WhatsApp IDs, digits only) the bot answers; empty answers anyone
+    pub allowed_numbers: Vec<String>,
+    /// Messages per conversation kept as context
+    pub history: usize,
+    /// Sent when no browser is connected to answer
+    pub offline_reply: Option<String>,
+    pub api_version: String,
+}
+
+impl Default for WhatsAppConfig {
+    fn default() -> Self {
+        WhatsAppConfig {
+            access_token: None,
+            phone_number_id: None,
+            verify_token: None,
+            app_secret: None,
+            allowed_numbers: Vec::new(),
+            history: 20,
+            offline_reply: Some("The assistant is offline right now. Please try again later.".to_string()),
+            api_version: "v21.0".to_string(),
+        }
+    }
+}
+
+impl WhatsAppConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.access_token.is_none() {
+            return Ok(());
+        }
+        for (name, value) in [("phone_number_id", &self.phone_number_id), ("verify_token", &self.verify_token), ("app_secret", &self.app_secret)] {
+            if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+                return Err(format!("whatsapp needs {} with access_token", name));
+            }
+        }
+        for number in &self.allowed_numbers {
+            if normalize(number).is_empty() || !normalize(number).chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("whatsapp allowed_numbers are phone numbers with country code, not {:?}", number));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A phone number as WhatsApp IDs write it: digits only
+fn normalize(number: &str) -> String {
+    number.chars().filter(|c| !matches!(c, '+' | ' ' | '-' | '(' | ')')).collect()
+}
+
+/// A message for the bot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incoming {
+    /// The sender's WhatsApp ID, which answers go `to`
+    pub from: String,
+    pub message_id: String,
+    pub name: Option<String>,
+    /// The text, or the `data` of the tapped button or row
+    pub text: String,
+    /// What the tapped button or row said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub timestamp: i64,
+}
+
+impl Incoming {
+    fn tapped(&self) -> bool {
+        self.title.is_some()
+    }
+}
+
+/// The messages for `phone_number_id` in a webhook notification; status
+/// updates, media and other numbers' messages are left out
+pub fn incoming(notification: &Value, phone_number_id: &str) -> Vec<Incoming> {
+    let mut messages = Vec::new();
+    for change in notification["entry"].as_array().into_iter().flatten().flat_map(|entry| entry["changes"].as_array().into_iter().flatten()) {
+        let value = &change["value"];
+        if change["field"] != "messages" || value["metadata"]["phone_number_id"].as_str() != Some(phone_number_id) {
+            continue;
+        }
+        for message in value["messages"].as_array().into_iter().flatten() {
+            let (text, title) = match message["type"].as_str() {
+                Some("text") => (message["text"]["body"].as_str(), None),
+                Some("interactive") => {
+                    let reply = &message["interactive"][message["interactive"]["type"].as_str().unwrap_or("")];
+                    (reply["id"].as_str(), reply["title"].as_str())
+                }
+                // A template's quick reply button
+                Some("button") => (message["button"]["payload"].as_str(), message["button"]["text"].as_str()),
+                _ => (None, None),
+            };
+            let (Some(text), Some(from), Some(id)) = (text, message["from"].as_str(), message["id"].as_str()) else {
+                continue;
+            };
+            let contact = value["contacts"].as_array().into_iter().flatten().find(|c| c["wa_id"].as_str() == Some(from));
+            messages.push(Incoming {
+                from: from.to_string(),
+                message_id: id.to_string(),
+                name: contact.and_then(|c| c["profile"]["name"].as_str()).map(str::to_string),
+                text: text.trim().to_string(),
+                title: title.map(str::to_string),
+                timestamp: message["timestamp"].as_str().and_then(|t| t.parse().ok()).unwrap_or_else(unix_now),
+            });
+        }
+    }
+    messages
+}
+
+/// A reply button, or a list row with its `description`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Button {
+    pub text: String,
+    pub data: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Section {
+    pub title: Option<String>,
+    pub rows: Vec<Button>,
+}
+
+/// A menu behind a button, up to ten rows in all
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct List {
+    /// What the menu's button says
+    pub button: String,
+    pub sections: Vec<Section>,
+}
+
+/// A template approved in the WhatsApp Manager, the only way to write
+/// first or after the 24-hour window
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    pub name: String,
+    /// E.g. `en_US`
+    pub language: String,
+    /// Header, body and button parameters, as the Cloud API takes them
+    #[serde(default)]
+    pub components: Vec<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendRequest {
+    pub to: String,
+    #[serde(default)]
+    pub text: String,
+    /// Message the answer replies to
+    pub reply_to: Option<String>,
+    #[serde(default)]
+    pub buttons: Vec<Button>,
+    pub list: Option<List>,
+    pub template: Option<Template>,
+}
+
+/// Check a title against WhatsApp's limit
+fn title(text: &str, limit: usize, what: &str) -> Result<String, String> {
+    let text = text.trim();
+    match text.is_empty() || text.chars().count() > limit {
+        true => Err(format!("{} must be 1 to {} characters, not {:?}", what, limit, text)),
+        false => Ok(text.to_string()),
+    }
+}
+
+impl SendRequest {
+    /// The Graph API message bodies, in order: text split at 4096
+    /// characters, the last part (up to 1024) carrying the buttons or list
+    pub fn messages(&self) -> Result<Vec<Value>, String> {
+        let to = normalize(&self.to);
+        let message = |kind: &str, content: Value| {
+            json!({ "messaging_product": "whatsapp", "recipient_type": "individual", "to": to, "type": kind, kind: content })
+        };
+        let mut messages = Vec::new();
+        if let Some(template) = &self.template {
+            if !self.text.trim().is_empty() || !self.buttons.is_empty() || self.list.is_some() {
+                return Err("A template carries its own text and buttons; send 'template' alone".to_string());
+            }
+            let name = title(&template.name, 512, "Template name")?;
+            messages.push(message("template", json!({
+                "name": name,
+                "language": { "code": template.language },
+                "components": template.components,
+            })));
+        } else {
+            let mut parts = chunks(&self.text, MAX_MESSAGE_CHARS);
+            let action = match (&self.list, self.buttons.is_empty()) {
+                (Some(_), false) => return Err("Send either 'buttons' or a 'list', not both".to_string()),
+                (Some(list), true) => Some(("list", self.list_action(list)?)),
+                (None, false) => Some(("button", self.button_action()?)),
+                (None, true) => None,
+            };
+            // The interactive part's body is shorter than a text message
+            let body = match action {
+                Some(_) => parts.pop().map(|last| chunks(&last, MAX_BODY_CHARS)).unwrap_or_default(),
+                None => Vec::new(),
+            };
+            parts.extend(body);
+            if parts.is_empty() {
+                return Err("Missing 'text'".to_string());
+            }
+            let last = parts.len() - 1;
+            for (i, part) in parts.into_iter().enumerate() {
+                messages.push(match (&action, i == last) {
+                    (Some((kind, action)), true) => message("interactive", json!({ "type": kind, "body": { "text": part }, "action": action })),
+                    _ => message("text", json!({ "body": part, "preview_url": false })),
+                });
+            }
+        }
+        if let (Some(first), Some(id)) = (messages.first_mut(), &self.reply_to) {
+            first["context"] = json!({ "message_id": id });
+        }
+        Ok(messages)
+    }
+
+    fn button_action(&self) -> Result<Value, String> {
+        if self.buttons.len() > MAX_BUTTONS {
+            return Err(format!("{} buttons; WhatsApp shows at most {}", self.buttons.len(), MAX_BUTTONS));
+        }
+        let buttons = self.buttons.iter()
+            .map(|button| Ok(json!({ "type": "reply", "reply": { "id": title(&button.data, 256, "Button data")?, "title": title(&button.text, 20, "Button text")? } })))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(json!({ "buttons": buttons }))
+    }
+
+    fn list_action(&self, list: &List) -> Result<Value, String> {
+        let rows = list.sections.iter().map(|section| section.rows.len()).sum::<usize>();
+        if rows == 0 || rows > MAX_ROWS {
+            return Err(format!("A list has 1 to {} rows, not {}", MAX_ROWS, rows));
+        }
+        let sections = list.sections.iter()
+            .map(|section| {
+                let rows = section.rows.iter()
+                    .map(|row| {
+                        let mut entry = json!({ "id": title(&row.data, 200, "Row data")?, "title": title(&row.text, 24, "Row text")? });
+                        if let Some(description) = &row.description {
+                            entry["description"] = json!(title(description, 72, "Row description")?);
+                        }
+                        Ok(entry)
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                let mut entry = json!({ "rows": rows });
+                if let Some(heading) = &section.title {
+                    entry["title"] = json!(title(heading, 24, "Section title")?);
+                }
+                Ok(entry)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(json!({ "button": title(&list.button, 20, "List button")?, "sections": sections }))
+    }
+}
+
+/// The bot's settings, the sessions of its conversations, and the
+/// messages already handled
+pub struct WhatsApp {
+    pub config: WhatsAppConfig,
+    sessions: Arc<Sessions>,
+    seen: Mutex<VecDeque<String>>,
+}
+
+impl WhatsApp {
+    pub fn new(config: &WhatsAppConfig, sessions: Arc<Sessions>) -> Self {
+        WhatsApp { config: config.clone(), sessions, seen: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.access_token.is_some()
+    }
+
+    /// The `hub.challenge` to echo if `mode` and `token` are a valid
+    /// subscription check
+    pub fn challenge<'a>(&self, mode: &str, token: &str, challenge: &'a str) -> Option<&'a str> {
+        let expected = self.config.verify_token.as_deref()?;
+        (mode == "subscribe" && constant_time_eq(expected.as_bytes(), token.as_bytes())).then_some(challenge)
+    }
+
+    /// Whether `signature` is `sha256=` and the HMAC-SHA256 of `body` under
+    /// the app secret
+    pub fn verify(&self, body: &[u8], signature: &str) -> bool {
+        let Some(secret) = &self.config.app_secret else {
+            return false;
+        };
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+        let tag = ring::hmac::sign(&key, body);
+        let expected: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+        constant_time_eq(format!("sha256={}", expected).as_bytes(), signature.trim().to_lowercase().as_bytes())
+    }
+
+    pub fn allows(&self, number: &str) -> bool {
+        self.config.allowed_numbers.is_empty() || self.config.allowed_numbers.iter().any(|allowed| normalize(allowed) == number)
+    }
+
+    /// Whether the bot may write to `to`: one of its conversations or an
+    /// allowed number
+    pub fn may_send(&self, to: &str) -> bool {
+        let to = normalize(to);
+        self.config.allowed_numbers.iter().any(|allowed| normalize(allowed) == to) || self.sessions.active(Channel::WhatsApp, &to)
+    }
+
+    fn seen(&self, message_id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.iter().any(|id| id == message_id) {
+            return true;
+        }
+        seen.push_back(message_id.to_string());
+        if seen.len() > SEEN_MESSAGES {
+            seen.pop_front();
+        }
+        false
+    }
+
+    pub fn history(&self, number: &str) -> Vec<Turn> {
+        self.sessions.history(Channel::WhatsApp, number)
+    }
+
+    pub fn remember(&self, number: &str, role: &'static str, content: &str) {
+        self.sessions.remember(Channel::WhatsApp, &normalize(number), role, content, self.config.history);
+    }
+
+    /// Clear the history but keep the conversation answerable
+    fn forget(&self, number: &str) {
+        self.sessions.forget(Channel::WhatsApp, number);
+    }
+
+    /// Post one message body to the number's messages endpoint
+    async fn call(&self, client: &reqwest::Client, body: &Value) -> Result<Value, String> {
+        let token = self.config.access_token.as_deref().ok_or("WhatsApp is not configured")?;
+        let url = format!(
+            "https://{}/{}/{}/messages",
+            HOST, self.config.api_version, self.config.phone_number_id.as_deref().unwrap_or("")
+        );
+        let response = client.post(url).bearer_auth(token).json(body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let reply: Value = response.json().await.map_err(|e| e.to_string())?;
+        match status.is_success() {
+            true => Ok(reply),
+            false => Err(format!(
+                "{} {} (code {})",
+                status.as_u16(),
+                reply["error"]["message"].as_str().unwrap_or("request refused"),
+                reply["error"]["code"].as_i64().unwrap_or(0)
+            )),
+        }
+    }
+
+    /// Send `req`; the answer is the number of messages sent
+    pub async fn send(&self, client: &reqwest::Client, req: &SendRequest) -> Result<usize, String> {
+        let messages = req.messages()?;
+        for message in &messages {
+            self.call(client, message).await?;
+        }
+        Ok(messages.len())
+    }
+
+    /// Reply to `message` with plain text, logging failures
+    async fn reply(&self, client: &reqwest::Client, metrics: &Metrics, message: &Incoming, text: &str) {
+        let req = SendRequest { to: message.from.clone(), text: text.to_string(), ..Default::default() };
+        if let Err(e) = metrics.upstream("whatsapp:messages", self.send(client, &req)).await {
+            eprintln!("❌ WhatsApp message: {}", e);
+        }
+    }
+
+    /// Relay the messages of one notification to the browsers on /ws, or
+    /// answer them here
+    pub async fn handle(&self, notification: &Value, hub: &Hub, client: &reqwest::Client, metrics: &Metrics) {
+        let phone_number_id = self.config.phone_number_id.as_deref().unwrap_or("");
+        for message in incoming(notification, phone_number_id) {
+            if self.seen(&message.message_id) {
+                continue;
+            }
+            if !self.allows(&message.from) {
+                eprintln!("⛔ WhatsApp: ignored {} (not in allowed_numbers)", message.from);
+                continue;
+            }
+            if unix_now() - message.timestamp > MAX_AGE_SECS {
+                eprintln!("⏭️  WhatsApp: skipped a message from {} sent {}s ago", message.from, unix_now() - message.timestamp);
+                continue;
+            }
+            if let Err(wait) = self.sessions.spend(Channel::WhatsApp, &message.from) {
+                eprintln!("🐢 Rate limit for WhatsApp {}: retry in {}s", message.from, sessions::wait_secs(wait));
+                if self.sessions.notice(Channel::WhatsApp, &message.from) {
+                    self.reply(client, metrics, &message, &sessions::slow_down(wait)).await;
+                }
+                continue;
+            }
+            if !message.tapped() && matches!(message.text.as_str(), "/new" | "/reset") {
+                self.forget(&message.from);
+                self.reply(client, metrics, &message, "Started a new conversation.").await;
+                continue;
+            }
+            if let Some(args) = sessions::model_args(&message.text).filter(|_| !message.tapped()) {
+                let answer = self.sessions.model_command(Channel::WhatsApp, &message.from, &self.sessions.config.models, args);
+                self.reply(client, metrics, &message, &answer).await;
+                continue;
+            }
+            if hub.listeners(Channel::WhatsApp) == 0 {
+                eprintln!("→ WhatsApp message from {} with no browser connected", message.from);
+                if let Some(offline) = &self.config.offline_reply {
+                    self.reply(client, metrics, &message, offline).await;
+                }
+                continue;
+            }
+
+            eprintln!("→ WhatsApp message from {}", message.from);
+            // Blue ticks while the agent works
+            let read = json!({ "messaging_product": "whatsapp", "status": "read", "message_id": message.message_id });
+            let _ = self.call(client, &read).await;
+            let mut data = json!(message);
+            data["history"] = json!(self.history(&message.from));
+            data["model"] = json!(self.sessions.model(Channel::WhatsApp, &message.from));
+            self.remember(&message.from, "user", message.title.as_deref().unwrap_or(&message.text));
+            hub.relay(Channel::WhatsApp, if message.tapped() { "callback" } else { "message" }, data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(message: Value) -> Value {
+        json!({"object": "whatsapp_business_account", "entry": [{"id": "1", "changes": [{"field": "messages", "value": {
+            "messaging_product": "whatsapp", "metadata": {"phone_number_id": "100"},
+            "contacts": [{"profile": {"name": "Ann"}, "wa_id": "15551234567"}], "messages": [message]}}]}]})
+    }
+
+    #[test]
+    fn test_incoming() {
+        let text = notification(json!({"from": "15551234567", "id": "wamid.1", "timestamp": "1700000000", "type": "text", "text": {"body": " hi "}}));
+        let messages = incoming(&text, "100");
+        assert_eq!((messages[0].text.as_str(), messages[0].name.as_deref(), messages[0].timestamp), ("hi", Some("Ann"), 1700000000));
+        assert!(incoming(&text, "200").is_empty(), "another number's message");
+
+        let tap = notification(json!({"from": "15551234567", "id": "wamid.2", "type": "interactive",
+            "interactive": {"type": "list_reply", "list_reply": {"id": "approve:action_1", "title": "Approve"}}}));
+        let message = &incoming(&tap, "100")[0];
+        assert_eq!((message.text.as_str(), message.title.as_deref()), ("approve:action_1", Some("Approve")));
+        let quick = notification(json!({"from": "15551234567", "id": "wamid.3", "type": "button", "button": {"payload": "yes", "text": "Yes"}}));
+        assert!(incoming(&quick, "100")[0].tapped());
+        let image = notification(json!({"from": "15551234567", "id": "wamid.4", "type": "image", "image": {"id": "m1"}}));
+        assert!(incoming(&image, "100").is_empty());
+    }
+
+    #[test]
+    fn test_messages() {
+        let button = |text: &str, data: &str| Button { text: text.to_string(), data: data.to_string(), description: None };
+        let req = SendRequest {
+            to: "+1 555 123 4567".to_string(),
+            text: "x".repeat(6000),
+            reply_to: Some("wamid.1".to_string()),
+            buttons: vec![button("Approve", "approve:1"), button("Deny", "deny:1")],
+            ..Default::default()
+        };
+        let messages = req.messages().unwrap();
+        assert_eq!(messages.len(), 3, "4096 and 1024 as text, then 880 as the body of the buttons");
+        assert_eq!((messages[0]["to"].as_str(), messages[0]["context"]["message_id"].as_str()), (Some("15551234567"), Some("wamid.1")));
+        assert_eq!(messages[2]["interactive"]["action"]["buttons"][1]["reply"]["id"], "deny:1");
+
+        let list = List { button: "Options".to_string(), sections: vec![Section { title: None, rows: vec![button("One", "1")] }] };
+        let both = SendRequest { list: Some(list.clone()), ..req };
+        assert!(both.messages().unwrap_err().contains("not both"));
+        let menu = SendRequest { to: "1".to_string(), text: "Pick".to_string(), list: Some(list), ..Default::default() };
+        assert_eq!(menu.messages().unwrap()[0]["interactive"]["action"]["sections"][0]["rows"][0]["title"], "One");
+        let long = SendRequest { to: "1".to_string(), text: "Hi".to_string(), buttons: vec![button("Far too long a label", "1"), button("x", "2")], ..Default::default() };
+        assert!(long.messages().is_ok());
+        let longer = SendRequest { buttons: vec![button("Far too long a label!", "1")], ..long };
+        assert!(longer.messages().unwrap_err().contains("1 to 20"));
+
+        let template = Template { name: "daily_digest".to_string(), language: "en_US".to_string(), components: Vec::new() };
+        let digest = SendRequest { to: "1".to_string(), template: Some(template), ..Default::default() };
+        assert_eq!(digest.messages().unwrap()[0]["template"]["language"]["code"], "en_US");
+    }
+
+    #[test]
+    fn test_verify_and_config() {
+        let config = WhatsAppConfig {
+            access_token: Some("EAAG".to_string()),
+            phone_number_id: Some("100".to_string()),
+            verify_token: Some("check-me".to_string()),
+            app_secret: Some("s3cret".to_string()),
+            allowed_numbers: vec!["+1 (555) 123-4567".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(WhatsAppConfig { app_secret: None, ..config.clone() }.validate().unwrap_err().contains("app_secret"));
+        let whatsapp = WhatsApp::new(&config, Default::default());
+        assert_eq!(whatsapp.challenge("subscribe", "check-me", "42"), Some("42"));
+        assert_eq!(whatsapp.challenge("subscribe", "guess", "42"), None);
+
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"s3cret");
+        let signature = format!("sha256={}", ring::hmac::sign(&key, b"{}").as_ref().iter().map(|b| format!("{:02x}", b)).collect::<String>());
+        assert!(whatsapp.verify(b"{}", &signature) && !whatsapp.verify(b"{ }", &signature));
+
+        assert!(whatsapp.allows("15551234567") && !whatsapp.allows("15550000000"));
+        assert!(whatsapp.may_send("+15551234567") && !whatsapp.may_send("15550000000"));
+        assert!(!whatsapp.seen("wamid.1") && whatsapp.seen("wamid.1"));
+    }
+}
@@ -0,0 +1,181 @@
+//! YouTube captions behind GET /youtube/transcript?id=...
+//!
+//! The watch page embeds the player configuration, which lists the
+//! caption tracks (uploaded and auto-generated) with their timedtext URLs.
+//! The proxy picks the track for the requested language, fetches it and
+//! answers with the spoken text:
+//!
+//! ```json
+//! {"id": "dQw4w9WgXcQ", "title": "...", "language": "en", "generated": false,
+//!  "text": "...", "segments": [{"start": 0.0, "duration": 2.5, "text": "..."}],
+//!  "languages": ["en", "de"]}
+//! ```
+
+use roxmltree::Document;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::search::plain;
+
+pub const HOST: &str = "www.youtube.com";
+
+/// The 11-character video ID in a bare ID or any common YouTube URL
+pub fn video_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let valid = |id: &str| id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid(input) {
+        return Some(input.to_string());
+    }
+    let url = reqwest::Url::parse(input).ok()?;
+    let host = url.host_str()?.trim_start_matches("www.").trim_start_matches("m.");
+    let candidate = match host {
+        "youtu.be" => url.path_segments()?.next().map(str::to_string),
+        "youtube.com" | "music.youtube.com" | "youtube-nocookie.com" => {
+            let mut segments = url.path_segments()?;
+            match segments.next() {
+                Some("watch") => url.query_pairs().find(|(k, _)| k == "v").map(|(_, v)| v.into_owned()),
+                Some("shorts" | "embed" | "live" | "v") => segments.next().map(str::to_string),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    candidate.filter(|id| valid(id))
+}
+
+pub fn watch_url(id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={}&hl=en", id)
+}
+
+/// The `ytInitialPlayerResponse` object in a watch page
+pub fn player_response(html: &str) -> Option<Value> {
+    let start = html.find("ytInitialPlayerResponse = ")? + "ytInitialPlayerResponse = ".len();
+    // Trailing script follows the object; take the first complete value
+    serde_json::Deserializer::from_str(&html[start..]).into_iter::<Value>().next()?.ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub language: String,
+    pub name: String,
+    /// Automatic speech recognition rather than uploaded captions
+    pub generated: bool,
+    pub url: String,
+}
+
+/// Caption tracks offered by the player
+pub fn tracks(player: &Value) -> Vec<Track> {
+    player["captions"]["playerCaptionsTracklistRenderer"]["captionTracks"].as_array().into_iter().flatten()
+        .filter_map(|track| Some(Track {
+            language: track["languageCode"].as_str()?.to_string(),
+            name: track["name"]["simpleText"].as_str()
+                .or_else(|| track["name"]["runs"][0]["text"].as_str())
+                .unwrap_or("")
+                .to_string(),
+            generated: track["kind"].as_str() == Some("asr"),
+            url: track["baseUrl"].as_str()?.to_string(),
+        }))
+        .collect()
+}
+
+/// The track for `language` ("en" also matches "en-GB"), uploaded captions
+/// before generated ones; without a language, the first uploaded track
+pub fn pick<'a>(tracks: &'a [Track], language: Option<&str>) -> Option<&'a Track> {
+    let matches = |track: &&Track| match language {
+        Some(language) => {
+            let language = language.to_lowercase();
+            let code = track.language.to_lowercase();
+            code == language || code.split('-').next() == Some(language.as_str())
+        }
+        None => true,
+    };
+    tracks.iter().filter(matches).find(|t| !t.generated)
+        .or_else(|| tracks.iter().find(matches))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    pub start: f64,
+    pub duration: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Transcript {
+    pub id: String,
+    pub title: Option<String>,
+    pub language: String,
+    pub generated: bool,
+    pub text: String,
+    pub segments: Vec<Segment>,
+    /// Every caption language the video has
+    pub languages: Vec<String>,
+}
+
+/// Parse timedtext XML: the classic `<text start dur>` format or srv3's
+/// `<p t d>` in milliseconds
+pub fn parse_captions(xml: &str) -> Result<Vec<Segment>, String> {
+    let doc = Document::parse(xml).map_err(|e| format!("Unreadable captions: {}", e))?;
+    let seconds = |value: Option<&str>, scale: f64| value.and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0) / scale;
+    let segments = doc.descendants()
+        .filter_map(|node| {
+            let (start, duration) = match node.tag_name().name() {
+                "text" => (seconds(node.attribute("start"), 1.0), seconds(node.attribute("dur"), 1.0)),
+                "p" => (seconds(node.attribute("t"), 1000.0), seconds(node.attribute("d"), 1000.0)),
+                _ => return None,
+            };
+            let raw: String = node.descendants().filter(|d| d.is_text()).filter_map(|d| d.text()).collect();
+            // Text arrives escaped twice ("&amp;#39;")
+            let text = plain(&raw);
+            (!text.is_empty()).then_some(Segment { start, duration, text })
+        })
+        .collect();
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_video_id() {
+        for input in [
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc",
+            "https://m.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(video_id(input).as_deref(), Some("dQw4w9WgXcQ"), "{}", input);
+        }
+        assert_eq!(video_id("https://example.com/watch?v=dQw4w9WgXcQ"), None);
+        assert_eq!(video_id("not a video"), None);
+    }
+
+    #[test]
+    fn test_tracks_and_captions() {
+        let html = r#"<script>var ytInitialPlayerResponse = {"videoDetails": {"title": "Talk"}, "captions": {"playerCaptionsTracklistRenderer": {"captionTracks": [
+            {"baseUrl": "https://www.youtube.com/api/timedtext?v=x&lang=en&kind=asr", "languageCode": "en", "kind": "asr", "name": {"runs": [{"text": "English (auto-generated)"}]}},
+            {"baseUrl": "https://www.youtube.com/api/timedtext?v=x&lang=en-GB", "languageCode": "en-GB", "name": {"simpleText": "English (UK)"}},
+            {"baseUrl": "https://www.youtube.com/api/timedtext?v=x&lang=de", "languageCode": "de", "name": {"simpleText": "Deutsch"}}
+        ]}}};var meta = {};</script>"#;
+        let player = player_response(html).unwrap();
+        assert_eq!(player["videoDetails"]["title"], json!("Talk"));
+        let tracks = tracks(&player);
+        assert_eq!(pick(&tracks, Some("en")).unwrap().language, "en-GB", "uploaded before generated");
+        assert_eq!(pick(&tracks, Some("DE")).unwrap().language, "de");
+        assert_eq!(pick(&tracks, None).unwrap().language, "en-GB");
+        assert!(pick(&tracks, Some("fr")).is_none());
+
+        let classic = r#"<?xml version="1.0" encoding="utf-8" ?><transcript>
+            <text start="0.5" dur="2.1">It&amp;#39;s &amp;quot;fine&amp;quot;</text><text start="2.6" dur="1">
+            second</text></transcript>"#;
+        let segments = parse_captions(classic).unwrap();
+        assert_eq!(segments[0], Segment { start: 0.5, duration: 2.1, text: "It's \"fine\"".to_string() });
+        assert_eq!(segments[1].text, "second");
+
+        let srv3 = r#"<timedtext format="3"><body><p t="1500" d="2000"><s>Hello</s><s t="400"> there</s></p><p t="4000" d="10"> </p></body></timedtext>"#;
+        assert_eq!(parse_captions(srv3).unwrap(), vec![Segment { start: 1.5, duration: 2.0, text: "Hello there".to_string() }]);
+    }
+}
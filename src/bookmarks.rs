@@ -0,0 +1,308 @@
+//! Bookmarks imported from the browser for `import_bookmarks`
+//!
+//! The assistant never reads the browser's bookmarks or history itself:
+//! the user exports them (the bookmarks HTML every browser writes, or the
+//! JSON of Chrome's `Bookmarks` file or a Firefox backup), attaches the
+//! file and asks for the import, which waits for their approval. The
+//! bookmarks become a document of the `bookmarks` collection, a few to a
+//! chunk with their folder, tags and date. They are stored without
+//! embeddings, so nothing of them leaves the browser, and `ask_document`
+//! finds them by keywords ("that article I bookmarked about X").
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::sanitize::decode_entities;
+
+/// Collection imports go to unless another is named
+pub const COLLECTION: &str = "bookmarks";
+
+/// Bookmarks per document; a larger export is stored in parts
+pub const PART_SIZE: usize = 1000;
+
+/// Most characters of bookmark entries in one chunk
+const CHUNK_CHARS: usize = 1200;
+
+/// Seconds from 1601, where Chrome counts from, to 1970
+const WINDOWS_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// One bookmark of an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    /// Folders it is in, from the top down
+    pub folder: Vec<String>,
+    pub tags: Vec<String>,
+    /// When it was added, in seconds since 1970
+    pub added: Option<i64>,
+}
+
+impl Bookmark {
+    /// The bookmark as the text that is searched
+    pub fn entry(&self) -> String {
+        let mut lines = vec![match self.title.is_empty() {
+            true => self.url.clone(),
+            false => self.title.clone(),
+        }];
+        lines.push(self.url.clone());
+        if !self.folder.is_empty() {
+            lines.push(format!("Folder: {}", self.folder.join(" / ")));
+        }
+        if !self.tags.is_empty() {
+            lines.push(format!("Tags: {}", self.tags.join(", ")));
+        }
+        if let Some(date) = self.added.and_then(|secs| chrono::DateTime::from_timestamp(secs, 0)) {
+            lines.push(format!("Added: {}", date.format("%Y-%m-%d")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// The web bookmarks of an export, HTML or JSON, each URL once
+pub fn parse(text: &str) -> Result<Vec<Bookmark>> {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let mut bookmarks = Vec::new();
+    if text.starts_with('{') || text.starts_with('[') {
+        let value: Value = serde_json::from_str(text)
+            .map_err(|e| Error::new(format!("Invalid bookmarks JSON: {}", e)))?;
+        walk_json(&value, &mut Vec::new(), &mut bookmarks);
+    } else {
+        parse_html(text, &mut bookmarks);
+    }
+    let mut seen = HashSet::new();
+    bookmarks.retain(|b| seen.insert(b.url.clone()));
+    match bookmarks.is_empty() {
+        true => Err(Error::new("No bookmarks found: export them from the browser as an HTML or JSON file")),
+        false => Ok(bookmarks),
+    }
+}
+
+/// The entries of `bookmarks`, a few to a chunk
+pub fn chunks(bookmarks: &[Bookmark]) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for entry in bookmarks.iter().map(Bookmark::entry) {
+        if !current.is_empty() && current.chars().count() + entry.chars().count() + 2 > CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(&entry);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// The document name of part `part` (from 1) of `parts` of an import
+pub fn part_name(file: &str, part: usize, parts: usize) -> String {
+    match parts {
+        1 => file.to_string(),
+        _ => format!("{} (part {} of {})", file, part, parts),
+    }
+}
+
+/// Whether `document` is (a part of) the import of `file`
+pub fn is_import_of(document: &str, file: &str) -> bool {
+    document == file || document.strip_prefix(file).is_some_and(|rest| rest.starts_with(" (part "))
+}
+
+fn is_web(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// The text of an HTML fragment, entities decoded and spaces collapsed
+fn text(html: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+    decode_entities(&tag.replace_all(html, " ")).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect()
+}
+
+/// The Netscape bookmarks format: `<DT><A HREF=…>` links in `<DL>` lists,
+/// each list after the `<H3>` naming its folder
+fn parse_html(html: &str, out: &mut Vec<Bookmark>) {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let token = TOKEN.get_or_init(|| Regex::new(r"(?is)<h3\b[^>]*>(.*?)</h3>|<a\s([^>]*)>(.*?)</a>|</dl\s*>|<dl\b[^>]*>").unwrap());
+    let attribute = ATTRIBUTE.get_or_init(|| Regex::new(r#"(?is)([a-z_]+)\s*=\s*"([^"]*)""#).unwrap());
+
+    // The folder of each open list; the outermost has none
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut heading: Option<String> = None;
+    for c in token.captures_iter(html) {
+        if let Some(name) = c.get(1) {
+            heading = Some(text(name.as_str()));
+        } else if let Some(attributes) = c.get(2) {
+            let attributes: Vec<(String, String)> = attribute.captures_iter(attributes.as_str())
+                .map(|a| (a[1].to_lowercase(), decode_entities(&a[2])))
+                .collect();
+            let get = |key: &str| attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.trim());
+            let Some(url) = get("href").filter(|url| is_web(url)) else { continue };
+            out.push(Bookmark {
+                title: text(&c[3]),
+                url: url.to_string(),
+                folder: folders.iter().flatten().cloned().collect(),
+                tags: get("tags").map(split_tags).unwrap_or_default(),
+                added: get("add_date").and_then(|d| d.parse().ok()),
+            });
+        } else if c[0].starts_with("</") {
+            folders.pop();
+        } else {
+            folders.push(heading.take());
+        }
+    }
+}
+
+/// Chrome's `Bookmarks` (`roots` of folders with `children`, links with
+/// `url`), a Firefox backup (`children`, links with `uri`), or a plain
+/// list of `{title, url}`
+fn walk_json(value: &Value, folder: &mut Vec<String>, out: &mut Vec<Bookmark>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| walk_json(item, folder, out)),
+        Value::Object(object) => {
+            let title = ["name", "title"].iter()
+                .find_map(|key| object.get(*key)?.as_str())
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            if let Some(url) = ["url", "uri", "href"].iter().find_map(|key| object.get(*key)?.as_str()) {
+                if is_web(url) {
+                    let tags = match object.get("tags") {
+                        Some(Value::String(tags)) => split_tags(tags),
+                        Some(Value::Array(tags)) => tags.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+                        _ => Vec::new(),
+                    };
+                    out.push(Bookmark { title, url: url.to_string(), folder: folder.clone(), tags, added: json_added(object) });
+                }
+                return;
+            }
+            if let Some(Value::Object(roots)) = object.get("roots") {
+                roots.values().for_each(|root| walk_json(root, folder, out));
+                return;
+            }
+            if let Some(children) = object.get("children") {
+                let named = !title.is_empty();
+                if named {
+                    folder.push(title);
+                }
+                walk_json(children, folder, out);
+                if named {
+                    folder.pop();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// When a JSON bookmark was added, in seconds since 1970
+fn json_added(object: &serde_json::Map<String, Value>) -> Option<i64> {
+    // Chrome: microseconds since 1601, as a string
+    if let Some(micros) = object.get("date_added").and_then(|d| d.as_str()?.parse::<i64>().ok()) {
+        return Some(micros / 1_000_000 - WINDOWS_EPOCH_OFFSET);
+    }
+    // Firefox: microseconds since 1970
+    if let Some(micros) = object.get("dateAdded").and_then(Value::as_i64) {
+        return Some(micros / 1_000_000);
+    }
+    object.get("added").and_then(Value::as_i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_html() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://doc.rust-lang.org/book/" ADD_DATE="1709251200" TAGS="rust, learning">The Rust Book</A>
+        <DT><H3>Reading &amp; Notes</H3>
+        <DL><p>
+            <DT><A HREF="https://example.com/wasm?a=1&amp;b=2">WebAssembly &lt;GC&gt; explained</A>
+            <DD>A long read
+        </DL><p>
+        <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+    </DL><p>
+    <DT><A HREF="https://news.ycombinator.com/">HN</A>
+    <DT><A HREF="https://doc.rust-lang.org/book/">Rust Book again</A>
+</DL><p>"#;
+        let bookmarks = parse(html).unwrap();
+        assert_eq!(bookmarks.len(), 3);
+        assert_eq!(bookmarks[0], Bookmark {
+            title: "The Rust Book".to_string(),
+            url: "https://doc.rust-lang.org/book/".to_string(),
+            folder: vec!["Bookmarks bar".to_string()],
+            tags: vec!["rust".to_string(), "learning".to_string()],
+            added: Some(1709251200),
+        });
+        assert_eq!((bookmarks[1].title.as_str(), bookmarks[1].url.as_str()), ("WebAssembly <GC> explained", "https://example.com/wasm?a=1&b=2"));
+        assert_eq!(bookmarks[1].folder, ["Bookmarks bar", "Reading & Notes"]);
+        assert!(bookmarks[2].folder.is_empty());
+
+        assert_eq!(bookmarks[0].entry(), "The Rust Book\nhttps://doc.rust-lang.org/book/\nFolder: Bookmarks bar\nTags: rust, learning\nAdded: 2024-03-01");
+        assert_eq!(parse("<html><body>No links</body></html>").unwrap_err().message(),
+            "No bookmarks found: export them from the browser as an HTML or JSON file");
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let chrome = r#"{"checksum": "x", "roots": {
+            "bookmark_bar": {"name": "Bookmarks bar", "type": "folder", "children": [
+                {"name": "Rust", "type": "folder", "children": [
+                    {"name": "Tokio tutorial", "type": "url", "url": "https://tokio.rs/tokio/tutorial", "date_added": "13350643200000000"}
+                ]}
+            ]},
+            "other": {"name": "Other bookmarks", "type": "folder", "children": [{"name": "Settings", "type": "url", "url": "chrome://settings"}]}
+        }, "version": 1}"#;
+        let bookmarks = parse(chrome).unwrap();
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks[0].folder, ["Bookmarks bar", "Rust"]);
+        assert_eq!(bookmarks[0].added, Some(1706169600));
+
+        let firefox = r#"{"title": "", "root": "placesRoot", "children": [
+            {"title": "menu", "children": [
+                {"title": "MDN", "uri": "https://developer.mozilla.org/", "dateAdded": 1700000000000000, "tags": "docs,web"},
+                {"title": "Recent", "uri": "place:sort=8"}
+            ]}
+        ]}"#;
+        let bookmarks = parse(firefox).unwrap();
+        assert_eq!((bookmarks[0].folder.clone(), bookmarks[0].tags.clone(), bookmarks[0].added), (vec!["menu".to_string()], vec!["docs".to_string(), "web".to_string()], Some(1700000000)));
+
+        assert_eq!(parse(r#"[{"title": "A", "url": "https://a.example"}]"#).unwrap()[0].title, "A");
+        assert!(parse("{not json").unwrap_err().message().starts_with("Invalid bookmarks JSON"));
+    }
+
+    #[test]
+    fn test_chunks_and_parts() {
+        let bookmarks: Vec<Bookmark> = (0..40)
+            .map(|i| Bookmark { title: format!("Article {}", i), url: format!("https://example.com/{}", i), folder: vec![], tags: vec![], added: None })
+            .collect();
+        let chunks = chunks(&bookmarks);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.chars().count() <= CHUNK_CHARS));
+        assert!(chunks[0].starts_with("Article 0\nhttps://example.com/0\n\nArticle 1\n"));
+        assert_eq!(chunks.concat().matches("https://").count(), 40);
+
+        assert_eq!(part_name("bookmarks.html", 1, 1), "bookmarks.html");
+        assert_eq!(part_name("bookmarks.html", 2, 3), "bookmarks.html (part 2 of 3)");
+        assert!(is_import_of("bookmarks.html (part 2 of 3)", "bookmarks.html"));
+        assert!(!is_import_of("bookmarks.html.bak", "bookmarks.html"));
+    }
+}
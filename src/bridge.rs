@@ -0,0 +1,242 @@
+//! Channel bridge: the agent as the brain of the proxy's bots
+//!
+//! The proxy holds the bots' credentials and queues the messages they
+//! receive; `GET /inbox` hands each one to a single poller. The bridge
+//! takes them, runs each through the agent, with the user's own provider,
+//! keys and the permission profile of who wrote (`telegram:<user>`), and
+//! posts the answer to the channel's send route. Tool calls waiting for
+//! approval come back as Approve / Deny buttons where the channel has them,
+//! and a pressed button (`approve:<id>`) decides the action and resumes.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::agent::{Agent, ToolRunner};
+use crate::chat::Message;
+use crate::config::proxy_endpoint;
+use crate::error::{Error, Result};
+use crate::platform::{self, HttpRequest};
+use crate::tools::get_tool_definitions;
+
+/// Channels the bridge can answer
+pub const CHANNELS: [&str; 7] = ["bluesky", "discord", "email", "mastodon", "slack", "telegram", "whatsapp"];
+
+/// A message queued by one of the proxy's bots
+#[derive(Debug, Clone, Deserialize)]
+pub struct Inbound {
+    pub channel: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub data: Value,
+}
+
+impl Inbound {
+    fn field(&self, name: &str) -> String {
+        match &self.data[name] {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        }
+    }
+
+    /// Who wrote, for permission profiles: "telegram:<user>"
+    pub fn subject(&self) -> String {
+        let who = match self.channel.as_str() {
+            "telegram" if !self.data["user_id"].is_null() => self.field("user_id"),
+            "telegram" => self.field("chat_id"),
+            "whatsapp" | "email" => self.field("from"),
+            "mastodon" => self.field("acct"),
+            "bluesky" => self.field("handle"),
+            _ => self.field("user_id"),
+        };
+        format!("{}:{}", self.channel, who)
+    }
+
+    /// The conversation's recent turns, as the proxy keeps them
+    pub fn history(&self) -> Vec<Message> {
+        serde_json::from_value(self.data["history"].clone()).unwrap_or_default()
+    }
+
+    /// The model picked with /model, if any
+    pub fn model(&self) -> Option<String> {
+        self.data["model"].as_str().map(str::to_string)
+    }
+
+    /// A pressed Approve / Deny button: (approved, action id)
+    pub fn decision(&self) -> Option<(bool, &str)> {
+        let text = self.data["text"].as_str()?.trim();
+        let (verdict, id) = text.split_once(':')?;
+        if id.is_empty() || id.contains(char::is_whitespace) {
+            return None;
+        }
+        match verdict {
+            "approve" => Some((true, id)),
+            "deny" => Some((false, id)),
+            _ => None,
+        }
+    }
+
+    /// What the agent is asked
+    pub fn prompt(&self) -> String {
+        let text = self.field("text");
+        if self.channel != "email" {
+            return text;
+        }
+        let mut lines = vec![
+            format!("Email from {} <{}>", self.field("name"), self.field("from")),
+            format!("Subject: {}", self.field("subject")),
+        ];
+        let attachments: Vec<&str> = self.data["attachments"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        if !attachments.is_empty() {
+            lines.push(format!("Attachments: {}", attachments.join(", ")));
+        }
+        lines.push(String::new());
+        lines.push(text);
+        lines.join("\n")
+    }
+
+    /// The send route and body answering with `text`; `pending` are the ids
+    /// of actions awaiting approval
+    pub fn reply(&self, text: &str, pending: &[String]) -> Result<(&'static str, Value)> {
+        let data = &self.data;
+        let reply = match self.channel.as_str() {
+            "telegram" => ("/channel/telegram/send", json!({
+                "chat_id": data["chat_id"], "text": text, "reply_to": data["message_id"], "format": "markdown",
+            })),
+            // Slash commands carry an interaction_id; the answer replaces their placeholder
+            "discord" => ("/channel/discord/send", json!({
+                "channel_id": data["channel_id"], "text": text, "reply_to": data["message_id"],
+                "interaction_id": data["interaction_id"],
+            })),
+            "slack" => ("/channel/slack/send", json!({
+                "channel": data["channel"], "text": text, "thread_ts": data["thread_ts"],
+                "command_id": data["command_id"], "format": "markdown",
+                "buttons": pending.iter().take(12).flat_map(|id| [
+                    json!({ "text": format!("Approve {}", id).chars().take(75).collect::<String>(), "data": format!("approve:{}", id), "style": "primary" }),
+                    json!({ "text": "Deny", "data": format!("deny:{}", id), "style": "danger" }),
+                ]).collect::<Vec<_>>(),
+            })),
+            // WhatsApp shows three buttons at most: enough for one action
+            "whatsapp" => ("/channel/whatsapp/send", json!({
+                "to": data["from"], "text": text, "reply_to": data["message_id"],
+                "buttons": pending.iter().take(1).flat_map(|id| [
+                    json!({ "text": "Approve", "data": format!("approve:{}", id) }),
+                    json!({ "text": "Deny", "data": format!("deny:{}", id) }),
+                ]).collect::<Vec<_>>(),
+            })),
+            "mastodon" => ("/channel/mastodon/send", json!({ "text": text, "reply_to": data["status_id"] })),
+            "bluesky" => ("/channel/bluesky/send", json!({ "text": text, "reply_to": data["uri"] })),
+            "email" => ("/channel/email/reply", json!({ "reply_to": data["id"], "text": text })),
+            other => return Err(Error::new(format!("Cannot answer {} messages", other))),
+        };
+        Ok(reply)
+    }
+}
+
+/// Take the queued messages of `channels`, waiting up to `wait_secs` for one
+pub async fn poll(proxy: &str, channels: &[String], wait_secs: u32) -> Result<Vec<Inbound>> {
+    let url = format!(
+        "{}?channels={}&wait={}",
+        proxy_endpoint(proxy, "/inbox"),
+        urlencoding::encode(&channels.join(",")),
+        wait_secs
+    );
+    let response = platform::fetch(HttpRequest::get(&url)).await?;
+    let body: Value = response.json().unwrap_or_default();
+    if !response.ok() {
+        return Err(Error::new(format!(
+            "Inbox: {}",
+            body["error"].as_str().map(str::to_string).unwrap_or_else(|| format!("HTTP {}", response.status))
+        )));
+    }
+    serde_json::from_value(body["events"].clone()).map_err(|e| Error::new(format!("Inbox: {}", e)))
+}
+
+/// Answer one message with `agent`, whose subject and model are already
+/// set, and post the answer back
+pub async fn answer(message: &Inbound, agent: &Agent, tools: &dyn ToolRunner, system_prompt: &str) -> Result<()> {
+    let run = |prompt: String| async move {
+        let mut messages = vec![Message::system(system_prompt)];
+        messages.extend(message.history());
+        messages.push(Message::user(&prompt));
+        agent.run_turn(&mut messages, tools).await.map(|turn| turn.response)
+    };
+    let reply = if let Some((approved, id)) = message.decision() {
+        let decided = match approved {
+            true => agent.security.borrow_mut().approve_action(id),
+            false => agent.security.borrow_mut().deny_action(id),
+        };
+        match decided {
+            Ok(()) => run(format!("The user {} action {}.", if approved { "approved" } else { "denied" }, id)).await,
+            Err(e) => Err(e),
+        }
+    } else if message.data["command"] == "tools" {
+        Ok(get_tool_definitions().iter()
+            .map(|tool| format!("- `{}`: {}", tool.name, tool.description))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    } else {
+        run(message.prompt()).await
+    };
+    let text = reply.unwrap_or_else(|e| format!("Sorry, something went wrong: {}", e.message()));
+
+    let pending: Vec<String> = agent.security.borrow().pending_actions().into_iter().map(|(id, _)| id).collect();
+    let (path, body) = message.reply(&text, &pending)?;
+    let response = platform::fetch(HttpRequest::post_json(&proxy_endpoint(&agent.config.proxy_url, path), &body)).await?;
+    if !response.ok() {
+        let body: Value = response.json().unwrap_or_default();
+        return Err(Error::new(format!(
+            "{} reply failed: {}",
+            message.channel,
+            body["error"].as_str().map(str::to_string).unwrap_or_else(|| format!("HTTP {}", response.status))
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inbound(channel: &str, data: Value) -> Inbound {
+        serde_json::from_value(json!({ "channel": channel, "type": "message", "data": data })).unwrap()
+    }
+
+    #[test]
+    fn test_messages() {
+        let telegram = inbound("telegram", json!({
+            "chat_id": 42, "message_id": 7, "user_id": 42, "text": "approve:a1b2",
+            "history": [{"role": "user", "content": "scan it"}, {"role": "assistant", "content": "Approve?"}],
+            "model": "gpt-4o",
+        }));
+        assert_eq!(telegram.subject(), "telegram:42");
+        assert_eq!(telegram.history().len(), 2);
+        assert_eq!(telegram.model().as_deref(), Some("gpt-4o"));
+        assert_eq!(telegram.decision(), Some((true, "a1b2")));
+        assert_eq!(inbound("telegram", json!({ "text": "deny: later" })).decision(), None);
+
+        let email = inbound("email", json!({
+            "id": "<m1@x>", "from": "ann@example.com", "name": "Ann", "subject": "Invoice",
+            "attachments": ["a.pdf"], "text": "Paid?",
+        }));
+        assert_eq!(email.subject(), "email:ann@example.com");
+        assert_eq!(email.prompt(), "Email from Ann <ann@example.com>\nSubject: Invoice\nAttachments: a.pdf\n\nPaid?");
+    }
+
+    #[test]
+    fn test_replies() {
+        let slack = inbound("slack", json!({ "channel": "C1", "thread_ts": "1.1", "user_id": "U1" }));
+        let (path, body) = slack.reply("Done", &["x1".to_string()]).unwrap();
+        assert_eq!(path, "/channel/slack/send");
+        assert_eq!(body["buttons"][0]["data"], "approve:x1");
+        assert_eq!(body["thread_ts"], "1.1");
+
+        let whatsapp = inbound("whatsapp", json!({ "from": "15551234567", "message_id": "wamid.X" }));
+        let (_, body) = whatsapp.reply("Done", &["x1".to_string(), "x2".to_string()]).unwrap();
+        assert_eq!(body["buttons"].as_array().unwrap().len(), 2, "one action at most");
+
+        let (path, body) = inbound("email", json!({ "id": "<m1@x>" })).reply("Yes", &[]).unwrap();
+        assert_eq!((path, &body["reply_to"]), ("/channel/email/reply", &json!("<m1@x>")));
+        assert!(inbound("irc", json!({})).reply("hi", &[]).is_err());
+    }
+}
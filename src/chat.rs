@@ -52,6 +52,17 @@ impl Message {
 pub struct Chat {
     /// Messages in chronological order
     pub messages: Vec<Message>,
+    /// Bumped when the conversation is cleared or replaced, so a turn
+    /// handing back what it was lent does not bring the old one back
+    #[serde(skip)]
+    generation: u64,
+}
+
+/// A conversation lent to a turn by `Chat::lend`
+#[derive(Debug)]
+pub struct Lent {
+    pub messages: Vec<Message>,
+    generation: u64,
 }
 
 impl Chat {
@@ -59,6 +70,29 @@ impl Chat {
     pub fn with_system_prompt(prompt: &str) -> Self {
         Chat {
             messages: vec![Message::system(prompt)],
+            generation: 0,
+        }
+    }
+
+    /// Take the conversation for a turn to work on without copying it;
+    /// the chat is empty until `give_back`
+    pub fn lend(&mut self) -> Lent {
+        Lent { messages: std::mem::take(&mut self.messages), generation: self.generation }
+    }
+
+    /// Take back what `lend` gave out, unless the conversation was cleared
+    /// or replaced meanwhile. A system prompt set meanwhile wins, and
+    /// messages added meanwhile follow.
+    pub fn give_back(&mut self, lent: Lent) {
+        if lent.generation != self.generation {
+            return;
+        }
+        let added = std::mem::replace(&mut self.messages, lent.messages);
+        for message in added {
+            match message.role {
+                Role::System => self.set_system_prompt(&message.content),
+                _ => self.messages.push(message),
+            }
         }
     }
 
@@ -79,8 +113,73 @@ impl Chat {
         messages
     }
 
+    /// Replace the system message, keeping the rest of the conversation
+    pub fn set_system_prompt(&mut self, prompt: &str) {
+        match self.messages.iter_mut().find(|m| m.role == Role::System) {
+            Some(system) => system.content = prompt.to_string(),
+            None => self.messages.insert(0, Message::system(prompt)),
+        }
+    }
+
     /// Clear all messages except system
     pub fn clear(&mut self, system_prompt: &str) {
         self.messages = vec![Message::system(system_prompt)];
+        self.generation += 1;
+    }
+
+    /// Replace the conversation after the system message; system messages
+    /// in `messages` are dropped
+    pub fn replace_conversation(&mut self, messages: Vec<Message>) {
+        self.messages.retain(|m| m.role == Role::System);
+        self.messages.extend(messages.into_iter().filter(|m| m.role != Role::System));
+        self.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_system_prompt_keeps_history() {
+        let mut chat = Chat::with_system_prompt("old");
+        chat.add_user("hi");
+        chat.add_assistant("hello");
+        chat.set_system_prompt("new");
+        assert_eq!(chat.messages.len(), 3);
+        assert_eq!((chat.messages[0].role.clone(), chat.messages[0].content.as_str()), (Role::System, "new"));
+        
+        let mut chat = Chat { messages: vec![Message::user("hi")], generation: 0 };
+        chat.set_system_prompt("new");
+        assert_eq!(chat.messages[0].role, Role::System);
+    }
+
+    #[test]
+    fn test_replace_conversation() {
+        let mut chat = Chat::with_system_prompt("prompt");
+        chat.add_user("old");
+        chat.replace_conversation(vec![Message::system("injected"), Message::user("hi"), Message::assistant("hello")]);
+        let contents: Vec<&str> = chat.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["prompt", "hi", "hello"]);
+    }
+
+    #[test]
+    fn test_lend_and_give_back() {
+        let mut chat = Chat::with_system_prompt("prompt");
+        chat.add_user("hi");
+        let lent = chat.lend();
+        assert!(chat.messages.is_empty());
+        chat.set_system_prompt("new prompt");
+        chat.add_user("meanwhile");
+        chat.give_back(lent);
+        let contents: Vec<&str> = chat.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["new prompt", "hi", "meanwhile"]);
+
+        let lent = chat.lend();
+        chat.clear("prompt");
+        chat.add_user("fresh start");
+        chat.give_back(lent);
+        let contents: Vec<&str> = chat.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["prompt", "fresh start"], "a cleared chat stays cleared");
     }
 }
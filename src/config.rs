@@ -1,8 +1,23 @@
 //! Configuration module for claWasm
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::logger::Level;
+use crate::platform::storage;
+use crate::providers::AVAILABLE_PROVIDERS;
+use crate::reasoning::ReasoningMode;
+use crate::tool_grammar::ToolFormat;
+
+/// localStorage key holding a user-set system prompt
+pub const SYSTEM_PROMPT_KEY: &str = "clawasm_system_prompt";
+
+/// Where the CORS proxy (src/bin/proxy) listens by default
+pub const DEFAULT_PROXY_URL: &str = "http://localhost:3000";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// AI Provider settings
     pub provider: ProviderConfig,
@@ -12,9 +27,131 @@ pub struct Config {
     pub max_tokens: u32,
     /// Temperature for response generation
     pub temperature: f32,
+    /// Nucleus sampling cutoff (0.0 to 1.0)
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// Penalize tokens by how often they already appeared (-2.0 to 2.0)
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    /// Penalize tokens that already appeared at all (-2.0 to 2.0)
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    /// Stop generating at any of these sequences (at most 4)
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Seed for reproducible sampling, where the provider supports it
+    #[serde(default)]
+    pub seed: Option<u32>,
+    /// Base URL of the CORS proxy used for every outbound fetch
+    #[serde(default = "default_proxy_url")]
+    pub proxy_url: String,
+    /// Shared secret the proxy expects (`CLAWASM_PROXY_TOKEN` on its side)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_token: Option<String>,
+    /// Least severe log level shown and kept: error, warn, info or debug
+    #[serde(default)]
+    pub log_level: Level,
+    /// Knowledge retrieved into every user message
+    #[serde(default)]
+    pub rag: RagConfig,
+    /// Hold a screen wake lock and a worker heartbeat while a turn runs, so
+    /// long research or scan turns survive background-tab throttling
+    #[serde(default)]
+    pub keep_awake: bool,
+    /// Monthly token and cost limits, keyed by provider name
+    #[serde(default)]
+    pub budgets: BTreeMap<String, Budget>,
+    /// What happens to reasoning models' thinking: strip, log or expose
+    #[serde(default)]
+    pub reasoning: ReasoningMode,
+    /// How the model writes tool calls: "loose" ```tool blocks, or the
+    /// validated "strict" grammar for models that garble them
+    #[serde(default)]
+    pub tool_format: ToolFormat,
+    /// Offer the tools that read, click and fill the embedding page
+    /// (read_page, query_selector_text, click_element, fill_form); each
+    /// call still needs the user's approval
+    #[serde(default)]
+    pub page_tools: bool,
+}
+
+/// RAG mode: before each user message reaches the model, the passages of
+/// ingested documents (and memories) that best match it are added to it with
+/// source markers, so answers draw on them without a tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RagConfig {
+    pub enabled: bool,
+    /// Document collections to search, each with its settings; empty for all
+    pub collections: Vec<String>,
+    /// Search the agent's memories too
+    pub memory: bool,
+    /// Passages added per message (1 to 20)
+    pub top_k: usize,
+    /// Passages scoring less are left out (0.0 to 1.0)
+    pub min_score: f32,
+    /// Characters of retrieved text added per message at most
+    pub max_chars: usize,
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        RagConfig {
+            enabled: false,
+            collections: Vec::new(),
+            memory: true,
+            top_k: 4,
+            min_score: 0.2,
+            max_chars: 6000,
+        }
+    }
+}
+
+/// A monthly limit on one provider's usage (see `usage`); cost is
+/// counted at the prices given here, in whatever currency they are in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Budget {
+    /// Input plus output tokens per calendar month (UTC)
+    pub monthly_tokens: Option<u64>,
+    /// Spend per calendar month
+    pub monthly_cost: Option<f64>,
+    /// Price of a million input tokens
+    pub input_price: f64,
+    /// Price of a million output tokens
+    pub output_price: f64,
+    /// Alert once this share of a limit is used (0.0 to 1.0)
+    pub warn_at: f32,
+    /// Refuse requests once a limit is reached, rather than only alerting
+    pub block: bool,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            monthly_tokens: None,
+            monthly_cost: None,
+            input_price: 0.0,
+            output_price: 0.0,
+            warn_at: 0.8,
+            block: true,
+        }
+    }
+}
+
+fn default_proxy_url() -> String {
+    DEFAULT_PROXY_URL.to_string()
+}
+
+/// Full URL of a proxy route, e.g. ("http://host:8080/", "/proxy") -> "http://host:8080/proxy"
+pub fn proxy_endpoint(proxy: &str, path: &str) -> String {
+    let base = proxy.trim().trim_end_matches('/');
+    let base = if base.is_empty() { DEFAULT_PROXY_URL } else { base };
+    format!("{}{}", base, path)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ProviderConfig {
     /// Active provider name (openai, anthropic, ollama, etc.)
     pub active: String,
@@ -25,6 +162,47 @@ pub struct ProviderConfig {
     pub base_url: Option<String>,
     /// Model to use
     pub model: String,
+    /// Remembered settings of the other providers, keyed by provider name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProviderSettings>,
+}
+
+/// Settings kept for one provider while another one is active
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    pub base_url: Option<String>,
+    pub model: String,
+}
+
+impl ProviderConfig {
+    /// Make `name` the active provider, remembering the current one's settings
+    ///
+    /// A provider used for the first time starts without key or base URL and
+    /// with its default model.
+    pub fn switch_to(&mut self, name: &str) {
+        if name == self.active {
+            return;
+        }
+        let current = ProviderSettings {
+            api_key: self.api_key.take(),
+            base_url: self.base_url.take(),
+            model: std::mem::take(&mut self.model),
+        };
+        let previous = std::mem::replace(&mut self.active, name.to_string());
+        self.profiles.insert(previous, current);
+        
+        match self.profiles.remove(name) {
+            Some(saved) => {
+                self.api_key = saved.api_key;
+                self.base_url = saved.base_url;
+                self.model = saved.model;
+            }
+            None => self.model = crate::providers::default_model(name).to_string(),
+        }
+    }
 }
 
 impl Default for Config {
@@ -35,12 +213,287 @@ impl Default for Config {
                 api_key: None,
                 base_url: None,
                 model: "gpt-4o-mini".to_string(),
+                profiles: BTreeMap::new(),
             },
-            system_prompt: "You are claWasm, a helpful AI assistant running entirely in the browser. \
+            system_prompt: "You are claWasm, a helpful AI assistant running entirely in the browser as WebAssembly (WASM). \
                 You are fast, private, and ready to help with any task."
                 .to_string(),
             max_tokens: 8192,
             temperature: 0.7,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: Vec::new(),
+            seed: None,
+            proxy_url: default_proxy_url(),
+            proxy_token: None,
+            log_level: Level::Info,
+            rag: RagConfig::default(),
+            keep_awake: false,
+            budgets: BTreeMap::new(),
+            reasoning: ReasoningMode::Strip,
+            tool_format: ToolFormat::Loose,
+            page_tools: false,
+        }
+    }
+}
+
+/// The saved system prompt, if the user set one
+pub fn load_system_prompt() -> Option<String> {
+    storage::get(SYSTEM_PROMPT_KEY).ok()?
+}
+
+/// Save the system prompt for later sessions (None forgets it)
+pub fn save_system_prompt(prompt: Option<&str>) -> crate::error::Result<()> {
+    match prompt {
+        Some(prompt) => storage::set(SYSTEM_PROMPT_KEY, prompt),
+        None => storage::remove(SYSTEM_PROMPT_KEY),
+    }
+}
+
+/// A problem with one config field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// Dotted path, e.g. "provider.base_url"
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError { field: field.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// One-line summary of config errors for JS callers
+pub fn describe_errors(errors: &[FieldError]) -> String {
+    let list: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    format!("Config error: {}", list.join("; "))
+}
+
+impl Config {
+    /// Parse and validate a complete config
+    pub fn from_json(json: &str) -> Result<Config, Vec<FieldError>> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| vec![FieldError::new("config", format!("invalid JSON: {}", e))])?;
+        Self::from_value(value)
+    }
+
+    /// Apply a JSON merge patch (RFC 7396) and validate the result
+    ///
+    /// Only the fields present in the patch change; `null` removes optional
+    /// fields such as `provider.base_url`.
+    pub fn patched(&self, patch_json: &str) -> Result<Config, Vec<FieldError>> {
+        let patch: serde_json::Value = serde_json::from_str(patch_json)
+            .map_err(|e| vec![FieldError::new("config", format!("invalid JSON: {}", e))])?;
+        if !patch.is_object() {
+            return Err(vec![FieldError::new("config", "expected a JSON object")]);
+        }
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| vec![FieldError::new("config", e.to_string())])?;
+        merge_patch(&mut value, &patch);
+        Self::from_value(value)
+    }
+
+    fn from_value(value: serde_json::Value) -> Result<Config, Vec<FieldError>> {
+        let config: Config = serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            let field = if path == "." { "config".to_string() } else { path };
+            vec![FieldError::new(&field, e.into_inner().to_string())]
+        })?;
+        let errors = config.validate();
+        if errors.is_empty() { Ok(config) } else { Err(errors) }
+    }
+
+    /// Semantic checks serde cannot express
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if !AVAILABLE_PROVIDERS.contains(&self.provider.active.as_str()) {
+            errors.push(FieldError::new("provider.active", format!(
+                "unknown provider \"{}\" (expected one of {})", self.provider.active, AVAILABLE_PROVIDERS.join(", "))));
+        }
+        if self.provider.model.trim().is_empty() {
+            errors.push(FieldError::new("provider.model", "must not be empty"));
+        }
+        if let Some(base_url) = &self.provider.base_url {
+            check_url(&mut errors, "provider.base_url", base_url);
+        }
+        for (name, settings) in &self.provider.profiles {
+            if !AVAILABLE_PROVIDERS.contains(&name.as_str()) {
+                errors.push(FieldError::new(&format!("provider.profiles.{}", name), "unknown provider"));
+            }
+            if let Some(base_url) = &settings.base_url {
+                check_url(&mut errors, &format!("provider.profiles.{}.base_url", name), base_url);
+            }
+        }
+        if self.max_tokens == 0 {
+            errors.push(FieldError::new("max_tokens", "must be at least 1"));
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            errors.push(FieldError::new("temperature", format!("{} is out of range (0.0 to 2.0)", self.temperature)));
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                errors.push(FieldError::new("top_p", format!("{} is out of range (0.0 to 1.0)", top_p)));
+            }
+        }
+        for (field, penalty) in [("frequency_penalty", self.frequency_penalty), ("presence_penalty", self.presence_penalty)] {
+            if let Some(penalty) = penalty {
+                if !(-2.0..=2.0).contains(&penalty) {
+                    errors.push(FieldError::new(field, format!("{} is out of range (-2.0 to 2.0)", penalty)));
+                }
+            }
+        }
+        if self.stop.len() > 4 {
+            errors.push(FieldError::new("stop", "at most 4 stop sequences are supported"));
+        }
+        if self.stop.iter().any(|s| s.is_empty()) {
+            errors.push(FieldError::new("stop", "stop sequences must not be empty"));
         }
+        check_url(&mut errors, "proxy_url", &self.proxy_url);
+        if !(1..=20).contains(&self.rag.top_k) {
+            errors.push(FieldError::new("rag.top_k", format!("{} is out of range (1 to 20)", self.rag.top_k)));
+        }
+        if !(0.0..=1.0).contains(&self.rag.min_score) {
+            errors.push(FieldError::new("rag.min_score", format!("{} is out of range (0.0 to 1.0)", self.rag.min_score)));
+        }
+        if self.rag.max_chars < 200 {
+            errors.push(FieldError::new("rag.max_chars", "must be at least 200"));
+        }
+        for (name, budget) in &self.budgets {
+            let field = |f: &str| format!("budgets.{}.{}", name, f);
+            if !AVAILABLE_PROVIDERS.contains(&name.as_str()) {
+                errors.push(FieldError::new(&format!("budgets.{}", name), "unknown provider"));
+            }
+            if budget.monthly_tokens == Some(0) {
+                errors.push(FieldError::new(&field("monthly_tokens"), "must be at least 1"));
+            }
+            if budget.monthly_cost.is_some_and(|cost| cost <= 0.0) {
+                errors.push(FieldError::new(&field("monthly_cost"), "must be more than 0"));
+            }
+            if budget.monthly_cost.is_some() && budget.input_price == 0.0 && budget.output_price == 0.0 {
+                errors.push(FieldError::new(&field("monthly_cost"), "needs input_price or output_price to count cost"));
+            }
+            for (name, price) in [("input_price", budget.input_price), ("output_price", budget.output_price)] {
+                if price < 0.0 {
+                    errors.push(FieldError::new(&field(name), "must not be negative"));
+                }
+            }
+            if !(0.0..=1.0).contains(&budget.warn_at) {
+                errors.push(FieldError::new(&field("warn_at"), format!("{} is out of range (0.0 to 1.0)", budget.warn_at)));
+            }
+        }
+        errors
+    }
+}
+
+fn check_url(errors: &mut Vec<FieldError>, field: &str, value: &str) {
+    match url::Url::parse(value) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        Ok(url) => errors.push(FieldError::new(field, format!("unsupported scheme \"{}\" (use http or https)", url.scheme()))),
+        Err(e) => errors.push(FieldError::new(field, format!("malformed URL \"{}\": {}", value, e))),
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7396) to `target`
+pub fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let Some(patch) = patch.as_object() else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::json!({});
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switch_remembers_provider_settings() {
+        let mut provider = Config::default().provider;
+        provider.api_key = Some("sk-openai".to_string());
+        
+        provider.switch_to("ollama");
+        assert_eq!((provider.api_key.as_deref(), provider.model.as_str()), (None, "llama3.2"));
+        provider.model = "qwen2.5:14b".to_string();
+        provider.base_url = Some("http://gpu-box:11434".to_string());
+        
+        provider.switch_to("openai");
+        assert_eq!((provider.api_key.as_deref(), provider.model.as_str()), (Some("sk-openai"), "gpt-4o-mini"));
+        
+        provider.switch_to("ollama");
+        assert_eq!(provider.model, "qwen2.5:14b");
+        assert_eq!(provider.base_url.as_deref(), Some("http://gpu-box:11434"));
+        assert!(provider.profiles.contains_key("openai") && !provider.profiles.contains_key("ollama"));
+    }
+
+    #[test]
+    fn test_proxy_endpoint() {
+        assert_eq!(proxy_endpoint("https://proxy.example.com/", "/proxy"), "https://proxy.example.com/proxy");
+        assert_eq!(proxy_endpoint("", "/search"), "http://localhost:3000/search");
+        let config: Config = serde_json::from_str(r#"{"provider": {"active": "openai", "model": "m", "base_url": null},
+            "system_prompt": "", "max_tokens": 1, "temperature": 0.5}"#).unwrap();
+        assert_eq!(config.proxy_url, DEFAULT_PROXY_URL);
+    }
+
+    #[test]
+    fn test_patch_and_validation_errors() {
+        let config = Config::default();
+        let patched = config.patched(r#"{"temperature": 1.2, "provider": {"model": "gpt-4o"}}"#).unwrap();
+        assert_eq!((patched.temperature, patched.provider.model.as_str()), (1.2, "gpt-4o"));
+        assert_eq!(patched.provider.active, "openai");
+        
+        let errors = config.patched(r#"{"temperature": 3, "provider": {"active": "openia", "base_url": "not a url"}}"#).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["provider.active", "provider.base_url", "temperature"]);
+        assert!(errors[0].message.contains("expected one of openai"));
+        
+        let errors = config.patched(r#"{"provider": {"modle": "x"}}"#).unwrap_err();
+        assert_eq!(errors[0].field, "provider.modle");
+        let errors = config.patched(r#"{"top_p": 1.5, "presence_penalty": -3, "stop": ["", "a", "b", "c", "d"]}"#).unwrap_err();
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(), vec!["top_p", "presence_penalty", "stop", "stop"]);
+        let errors = config.patched(r#"{"max_tokens": "lots"}"#).unwrap_err();
+        assert_eq!(errors[0].field, "max_tokens");
+        assert_eq!(config.patched(r#"{"log_level": "debug"}"#).unwrap().log_level, Level::Debug);
+        assert_eq!(config.patched(r#"{"log_level": "verbose"}"#).unwrap_err()[0].field, "log_level");
+        assert!(config.patched(r#"{"keep_awake": true}"#).unwrap().keep_awake);
+        assert_eq!(config.patched(r#"{"reasoning": "expose"}"#).unwrap().reasoning, ReasoningMode::Expose);
+        assert_eq!(config.patched(r#"{"reasoning": "show"}"#).unwrap_err()[0].field, "reasoning");
+        assert_eq!(config.patched(r#"{"tool_format": "strict"}"#).unwrap().tool_format, ToolFormat::Strict);
+        assert_eq!(config.patched(r#"{"tool_format": "xml"}"#).unwrap_err()[0].field, "tool_format");
+        assert!(config.patched(r#"{"page_tools": true}"#).unwrap().page_tools);
+        let budgets = config.patched(r#"{"budgets": {"openai": {"monthly_tokens": 2000000}}}"#).unwrap().budgets;
+        assert_eq!((budgets["openai"].monthly_tokens, budgets["openai"].block), (Some(2_000_000), true));
+        let errors = config.patched(r#"{"budgets": {"opnai": {"monthly_cost": 5, "warn_at": 80}}}"#).unwrap_err();
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+            vec!["budgets.opnai", "budgets.opnai.monthly_cost", "budgets.opnai.warn_at"]);
+        let rag = config.patched(r#"{"rag": {"enabled": true, "collections": ["security notes"]}}"#).unwrap().rag;
+        assert_eq!((rag.enabled, rag.top_k), (true, 4));
+        let errors = config.patched(r#"{"rag": {"top_k": 0, "min_score": 2}}"#).unwrap_err();
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(), vec!["rag.top_k", "rag.min_score"]);
+        assert_eq!(Config::from_json("{}").unwrap_err()[0].field, "config");
+    }
+
+    #[test]
+    fn test_merge_patch_removes_nulls() {
+        let mut target = serde_json::json!({"a": {"b": 1, "c": 2}, "d": 3});
+        merge_patch(&mut target, &serde_json::json!({"a": {"b": null, "e": 4}, "d": [1]}));
+        assert_eq!(target, serde_json::json!({"a": {"c": 2, "e": 4}, "d": [1]}));
     }
 }
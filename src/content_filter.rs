@@ -0,0 +1,211 @@
+//! Content safety filters for claWasm
+//!
+//! Pre-send filters check the user's message before it reaches the provider;
+//! post-receive filters check the model's reply before it reaches the user.
+//! Matches come from a term blocklist, regex rules, and optionally the
+//! OpenAI moderation API (called through the local proxy in the browser). The policy
+//! decides whether a match blocks, warns, or annotates.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{self, AuditKind};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::logger;
+use crate::platform::{self, HttpRequest};
+
+/// What to do when a filter matches
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterPolicy {
+    /// Refuse to send the message / withhold the reply
+    Block,
+    /// Let it through, log a warning and audit entry
+    #[default]
+    Warn,
+    /// Let it through with a visible note prepended
+    Annotate,
+}
+
+/// Where a filter runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterStage {
+    PreSend,
+    PostReceive,
+}
+
+impl FilterStage {
+    fn name(self) -> &'static str {
+        match self {
+            FilterStage::PreSend => "pre_send",
+            FilterStage::PostReceive => "post_receive",
+        }
+    }
+}
+
+/// Content filter configuration (part of SecurityConfig)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentFilterConfig {
+    /// Check user messages before they are sent
+    pub pre_send: bool,
+    /// Check model replies before they are shown
+    pub post_receive: bool,
+    /// Case-insensitive terms that trigger the filter
+    pub blocklist: Vec<String>,
+    /// Regex rules that trigger the filter
+    pub regex_rules: Vec<String>,
+    /// Also ask the OpenAI moderation API (needs the OpenAI provider key)
+    pub moderation: bool,
+    pub policy: FilterPolicy,
+}
+
+impl ContentFilterConfig {
+    /// Compile the regex rules, reporting the first invalid one
+    pub fn compile_rules(&self) -> Result<Vec<Regex>, String> {
+        self.regex_rules.iter()
+            .map(|r| Regex::new(r).map_err(|e| format!("Invalid content filter rule '{}': {}", r, e)))
+            .collect()
+    }
+
+    fn enabled_for(&self, stage: FilterStage) -> bool {
+        match stage {
+            FilterStage::PreSend => self.pre_send,
+            FilterStage::PostReceive => self.post_receive,
+        }
+    }
+}
+
+/// Run the filter for a stage and apply the policy
+///
+/// Returns the (possibly annotated) text. A blocked pre-send message is an
+/// error; a blocked reply is replaced by a notice.
+pub async fn apply(
+    stage: FilterStage,
+    text: &str,
+    filter: &ContentFilterConfig,
+    config: &Config,
+) -> Result<String> {
+    if !filter.enabled_for(stage) {
+        return Ok(text.to_string());
+    }
+
+    let rules = filter.compile_rules().map_err(Error::new)?;
+    let mut reasons = local_matches(text, &filter.blocklist, &rules);
+
+    if filter.moderation {
+        match moderate(text, config).await {
+            Ok(categories) => reasons.extend(categories.into_iter().map(|c| format!("moderation: {}", c))),
+            Err(e) => logger::warn("content_filter", &format!("Moderation check failed: {}", e)),
+        }
+    }
+
+    if reasons.is_empty() {
+        return Ok(text.to_string());
+    }
+
+    let outcome = match filter.policy {
+        FilterPolicy::Block => "block",
+        FilterPolicy::Warn => "warn",
+        FilterPolicy::Annotate => "annotate",
+    };
+    audit::record(
+        AuditKind::SecurityDecision,
+        &format!("content_filter:{}", stage.name()),
+        outcome,
+        &reasons.join(", "),
+    );
+    if filter.policy == FilterPolicy::Warn {
+        logger::warn("content_filter", &format!("Content filter ({}): {}", stage.name(), reasons.join(", ")));
+    }
+
+    enforce(stage, filter.policy, text, &reasons).map_err(Error::new)
+}
+
+/// Blocklist terms and regex rules matching the text
+pub fn local_matches(text: &str, blocklist: &[String], rules: &[Regex]) -> Vec<String> {
+    let lower = text.to_lowercase();
+    let mut reasons: Vec<String> = blocklist.iter()
+        .filter(|term| !term.trim().is_empty() && lower.contains(&term.to_lowercase()))
+        .map(|term| format!("blocked term '{}'", term))
+        .collect();
+    reasons.extend(rules.iter()
+        .filter(|re| re.is_match(text))
+        .map(|re| format!("rule '{}'", re.as_str())));
+    reasons
+}
+
+/// Apply a policy to matched text
+fn enforce(stage: FilterStage, policy: FilterPolicy, text: &str, reasons: &[String]) -> Result<String, String> {
+    let summary = reasons.join(", ");
+    match (policy, stage) {
+        (FilterPolicy::Block, FilterStage::PreSend) => {
+            Err(format!("Message blocked by content filter ({})", summary))
+        }
+        (FilterPolicy::Block, FilterStage::PostReceive) => {
+            Ok(format!("⚠️ Response withheld by content filter ({}).", summary))
+        }
+        (FilterPolicy::Annotate, _) => Ok(format!("⚠️ Content filter: {}\n\n{}", summary, text)),
+        (FilterPolicy::Warn, _) => Ok(text.to_string()),
+    }
+}
+
+const MODERATION_URL: &str = "https://api.openai.com/v1/moderations";
+
+/// Flagged categories from the OpenAI moderation API
+async fn moderate(text: &str, config: &Config) -> Result<Vec<String>> {
+    if config.provider.active != "openai" {
+        return Err(Error::new("Moderation requires the OpenAI provider"));
+    }
+    let api_key = config.provider.api_key.as_ref()
+        .ok_or_else(|| Error::new("API key not set for moderation"))?;
+
+    let request = HttpRequest::post_json(MODERATION_URL, &serde_json::json!({ "input": text }))
+        .header("Authorization", &format!("Bearer {}", api_key));
+    let response = platform::fetch_cross_origin(request, &config.proxy_url).await?;
+
+    if !response.ok() {
+        return Err(Error::new(format!("Moderation API error: {}", response.status)));
+    }
+
+    Ok(flagged_categories(&response.json()?))
+}
+
+/// Categories marked true in a moderation response
+fn flagged_categories(json: &serde_json::Value) -> Vec<String> {
+    json["results"].as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|r| r["categories"].as_object())
+        .flat_map(|cats| cats.iter().filter(|(_, v)| v.as_bool() == Some(true)).map(|(k, _)| k.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_matches() {
+        let rules = vec![Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap()];
+        let reasons = local_matches("My SSN is 123-45-6789, Project X", &["project x".to_string()], &rules);
+        assert_eq!(reasons.len(), 2);
+        assert!(local_matches("hello", &["".to_string()], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_enforce_policies() {
+        let reasons = vec!["blocked term 'x'".to_string()];
+        assert!(enforce(FilterStage::PreSend, FilterPolicy::Block, "hi", &reasons).is_err());
+        assert!(enforce(FilterStage::PostReceive, FilterPolicy::Block, "hi", &reasons).unwrap().contains("withheld"));
+        assert_eq!(enforce(FilterStage::PreSend, FilterPolicy::Warn, "hi", &reasons).unwrap(), "hi");
+        assert!(enforce(FilterStage::PostReceive, FilterPolicy::Annotate, "hi", &reasons).unwrap().ends_with("\n\nhi"));
+    }
+
+    #[test]
+    fn test_flagged_categories() {
+        let json = serde_json::json!({"results": [{"flagged": true, "categories": {"violence": true, "hate": false}}]});
+        assert_eq!(flagged_categories(&json), vec!["violence".to_string()]);
+    }
+}
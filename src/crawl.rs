@@ -0,0 +1,348 @@
+//! Site-scoped crawling for the `crawl_site` tool
+//!
+//! The crawl starts at one page and follows links on the same site
+//! (`www.` aside), breadth first, up to a depth and a page budget. Paths
+//! robots.txt disallows for us (the `clawasm` group, or `*`) are skipped,
+//! with the longest matching rule deciding as RFC 9309 has it. Fetching
+//! happens in the tools through the proxy; this module keeps the frontier,
+//! pulls links and titles out of HTML and renders the site map.
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::OnceLock;
+
+/// The user-agent token robots.txt groups are matched against
+pub const CRAWLER_AGENT: &str = "clawasm";
+
+/// Extensions of files that are not pages
+const SKIPPED_EXTENSIONS: [&str; 16] = [
+    "jpg", "jpeg", "png", "gif", "webp", "svg", "ico", "pdf", "zip", "gz", "mp3", "mp4", "webm", "css", "js", "woff2",
+];
+
+/// A robots.txt rule: (allowed, path pattern)
+type Rule = (bool, String);
+
+/// The robots.txt rules that apply to one user agent
+#[derive(Debug, Default, PartialEq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+}
+
+impl Robots {
+    /// The rules of the group naming `agent`, or else of `*`
+    pub fn parse(text: &str, agent: &str) -> Self {
+        let mut groups: Vec<(Vec<String>, Vec<Rule>)> = Vec::new();
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((field, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+            match field.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push((Vec::new(), Vec::new()));
+                    }
+                    in_agents = true;
+                    if let Some((agents, _)) = groups.last_mut() {
+                        agents.push(value.to_lowercase());
+                    }
+                }
+                field @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty Disallow allows everything
+                    match groups.last_mut() {
+                        Some((_, rules)) if !value.is_empty() => rules.push((field == "allow", value.to_string())),
+                        _ => {}
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        let agent = agent.to_lowercase();
+        let name = match groups.iter().any(|(agents, _)| agents.contains(&agent)) {
+            true => agent.as_str(),
+            false => "*",
+        };
+        let rules = groups.into_iter()
+            .filter(|(agents, _)| agents.iter().any(|a| a == name))
+            .flat_map(|(_, rules)| rules)
+            .collect();
+        Robots { rules }
+    }
+
+    /// Whether `path` (with its query) may be crawled
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules.iter()
+            .filter(|(_, pattern)| rule_matches(pattern, path))
+            .max_by_key(|(allowed, pattern)| (pattern.len(), *allowed))
+            .is_none_or(|(allowed, _)| *allowed)
+    }
+}
+
+/// Whether a robots.txt path pattern (`*` for any run, `$` for the end)
+/// matches `path`
+fn rule_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// The host two URLs are on the same site by: lowercase, without `www.`
+fn site(url: &url::Url) -> String {
+    let host = url.host_str().unwrap_or("").to_lowercase();
+    host.strip_prefix("www.").unwrap_or(&host).to_string()
+}
+
+/// Absolute http(s) URLs of the links in `html`, resolved against `base`,
+/// without fragments, in page order and without repeats
+pub fn extract_links(html: &str, base: &url::Url) -> Vec<url::Url> {
+    static HREF: OnceLock<Regex> = OnceLock::new();
+    static BASE: OnceLock<Regex> = OnceLock::new();
+    let href = HREF.get_or_init(|| Regex::new(r#"(?is)<a\b[^>]*?\shref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap());
+    let base_tag = BASE.get_or_init(|| Regex::new(r#"(?is)<base\b[^>]*?\shref\s*=\s*["']([^"']+)["']"#).unwrap());
+    let base = base_tag.captures(html)
+        .and_then(|c| base.join(c[1].trim()).ok())
+        .unwrap_or_else(|| base.clone());
+
+    let mut seen = BTreeSet::new();
+    let mut links = Vec::new();
+    for c in href.captures_iter(html) {
+        let value = c.get(1).or(c.get(2)).or(c.get(3)).map(|m| m.as_str().trim()).unwrap_or("");
+        let value = value.replace("&amp;", "&");
+        let Ok(mut link) = base.join(&value) else { continue };
+        if !matches!(link.scheme(), "http" | "https") {
+            continue;
+        }
+        link.set_fragment(None);
+        if seen.insert(link.to_string()) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// The text of the page's `<title>`
+pub fn page_title(html: &str) -> Option<String> {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let title = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+    let text = title.captures(html)?[1].split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then(|| text.replace("&amp;", "&").replace("&#39;", "'").replace("&quot;", "\""))
+}
+
+/// One crawled page
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawlPage {
+    pub url: String,
+    /// Links followed from the start page to reach it
+    pub depth: usize,
+    pub status: u16,
+    pub title: Option<String>,
+    /// Extracted text, cut to the crawl's limit
+    pub text: String,
+    /// Same-site pages it links to
+    pub links: Vec<String>,
+    /// Links to other sites
+    pub external_links: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The crawl frontier and what it found
+#[derive(Debug, Serialize)]
+pub struct SiteCrawl {
+    pub start: String,
+    pub max_depth: usize,
+    pub max_pages: usize,
+    pub pages: Vec<CrawlPage>,
+    /// URLs robots.txt kept us from
+    pub disallowed: Vec<String>,
+    /// Whether pages were left when the budget ran out
+    pub truncated: bool,
+    #[serde(skip)]
+    site: String,
+    #[serde(skip)]
+    robots: Robots,
+    #[serde(skip)]
+    queue: VecDeque<(url::Url, usize)>,
+    #[serde(skip)]
+    seen: BTreeSet<String>,
+}
+
+impl SiteCrawl {
+    pub fn new(start: &str, max_depth: usize, max_pages: usize, robots: Robots) -> Result<Self, String> {
+        let start = url::Url::parse(start.trim()).map_err(|e| format!("Invalid URL '{}': {}", start, e))?;
+        if !matches!(start.scheme(), "http" | "https") {
+            return Err(format!("Cannot crawl {} URLs", start.scheme()));
+        }
+        let mut crawl = SiteCrawl {
+            start: start.to_string(),
+            max_depth,
+            max_pages: max_pages.max(1),
+            pages: Vec::new(),
+            disallowed: Vec::new(),
+            truncated: false,
+            site: site(&start),
+            robots,
+            queue: VecDeque::new(),
+            seen: BTreeSet::new(),
+        };
+        crawl.enqueue(start, 0);
+        Ok(crawl)
+    }
+
+    fn enqueue(&mut self, url: url::Url, depth: usize) {
+        if !self.seen.insert(url.as_str().trim_end_matches('/').to_string()) {
+            return;
+        }
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        if !self.robots.allows(&path) {
+            self.disallowed.push(url.to_string());
+            return;
+        }
+        self.queue.push_back((url, depth));
+    }
+
+    /// The next page to fetch and its depth, or `None` when the crawl is
+    /// done or out of budget
+    pub fn next_page(&mut self) -> Option<(String, usize)> {
+        if self.pages.len() >= self.max_pages {
+            self.truncated = !self.queue.is_empty();
+            return None;
+        }
+        self.queue.pop_front().map(|(url, depth)| (url.to_string(), depth))
+    }
+
+    /// Record a fetched page and queue its same-site links
+    pub fn add_page(&mut self, url: &str, depth: usize, status: u16, html: &str, text: String) {
+        let Ok(base) = url::Url::parse(url) else { return };
+        let (internal, external): (Vec<url::Url>, Vec<url::Url>) = extract_links(html, &base).into_iter().partition(|link| site(link) == self.site);
+        let internal: Vec<url::Url> = internal.into_iter().filter(|link| !is_file(link)).collect();
+        if depth < self.max_depth {
+            for link in &internal {
+                self.enqueue(link.clone(), depth + 1);
+            }
+        }
+        self.pages.push(CrawlPage {
+            url: url.to_string(),
+            depth,
+            status,
+            title: page_title(html),
+            text,
+            links: internal.iter().map(url::Url::to_string).collect(),
+            external_links: external.len(),
+            error: None,
+        });
+    }
+
+    /// Record a page that could not be fetched
+    pub fn add_error(&mut self, url: &str, depth: usize, status: u16, error: &str) {
+        self.pages.push(CrawlPage {
+            url: url.to_string(),
+            depth,
+            status,
+            title: None,
+            text: String::new(),
+            links: Vec::new(),
+            external_links: 0,
+            error: Some(error.to_string()),
+        });
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let failed = self.pages.iter().filter(|p| p.error.is_some()).count();
+        let mut out = format!(
+            "# Site Crawl: {}\n\n{} pages crawled ({} failed), depth up to {}, {} skipped by robots.txt{}\n\n## Pages\n",
+            self.start,
+            self.pages.len(),
+            failed,
+            self.max_depth,
+            self.disallowed.len(),
+            if self.truncated { format!(", stopped at the {}-page budget", self.max_pages) } else { String::new() }
+        );
+        for page in &self.pages {
+            let title = page.title.as_deref().unwrap_or("(untitled)");
+            out.push_str(&format!("\n### {}\n{} · HTTP {} · depth {} · {} links ({} external)\n", title, page.url, page.status, page.depth, page.links.len(), page.external_links));
+            match &page.error {
+                Some(error) => out.push_str(&format!("⚠️ {}\n", error)),
+                None if !page.text.is_empty() => out.push_str(&format!("{}\n", page.text)),
+                None => {}
+            }
+        }
+        if !self.disallowed.is_empty() {
+            out.push_str("\n## Disallowed by robots.txt\n\n");
+            for url in &self.disallowed {
+                out.push_str(&format!("- {}\n", url));
+            }
+        }
+        out
+    }
+}
+
+/// Whether a link points at a file rather than a page
+fn is_file(url: &url::Url) -> bool {
+    let path = url.path().to_lowercase();
+    path.rsplit_once('.').is_some_and(|(_, ext)| SKIPPED_EXTENSIONS.contains(&ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_robots() {
+        let text = "User-agent: Googlebot\nDisallow: /\n\nUser-agent: *\nUser-agent: other\nDisallow: /private\nAllow: /private/docs\nDisallow: /*.php$\nDisallow:\n";
+        let robots = Robots::parse(text, CRAWLER_AGENT);
+        assert!(robots.allows("/about"));
+        assert!(!robots.allows("/private/keys"));
+        assert!(robots.allows("/private/docs/a"), "the longer Allow wins");
+        assert!(!robots.allows("/index.php") && robots.allows("/index.php?x=1"));
+
+        let ours = Robots::parse("User-agent: *\nDisallow: /\n\nUser-agent: ClaWasm\nDisallow: /tmp\n", CRAWLER_AGENT);
+        assert!(ours.allows("/about") && !ours.allows("/tmp/x"), "our own group replaces *");
+        assert!(Robots::parse("", CRAWLER_AGENT).allows("/anything"));
+    }
+
+    #[test]
+    fn test_crawl() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /admin\n", CRAWLER_AGENT);
+        let mut crawl = SiteCrawl::new("https://example.com/", 1, 3, robots).unwrap();
+        assert_eq!(crawl.next_page(), Some(("https://example.com/".to_string(), 0)));
+        let html = r#"<html><title>Home &amp; Away</title><body>
+            <a href="/docs#intro">Docs</a> <a href='https://www.example.com/blog'>Blog</a>
+            <a href="/admin">Admin</a> <a href="/logo.png">Logo</a> <a href="mailto:a@example.com">Mail</a>
+            <a href="https://other.org/">Other</a> <a href=/docs>Again</a> <a href="/">Home</a></body></html>"#;
+        crawl.add_page("https://example.com/", 0, 200, html, "Home".to_string());
+        assert_eq!(crawl.pages[0].title.as_deref(), Some("Home & Away"));
+        assert_eq!(crawl.pages[0].links, ["https://example.com/docs", "https://www.example.com/blog", "https://example.com/admin", "https://example.com/"]);
+        assert_eq!(crawl.pages[0].external_links, 1);
+        assert_eq!(crawl.disallowed, ["https://example.com/admin"]);
+
+        let (docs, depth) = crawl.next_page().unwrap();
+        crawl.add_page(&docs, depth, 200, r#"<a href="/deeper">Deeper</a>"#, String::new());
+        assert!(crawl.queue.iter().all(|(url, _)| url.path() != "/deeper"), "past the depth");
+        let (blog, depth) = crawl.next_page().unwrap();
+        crawl.add_error(&blog, depth, 500, "HTTP 500");
+        assert_eq!(crawl.next_page(), None);
+        assert!(!crawl.truncated);
+        assert!(crawl.to_markdown().contains("3 pages crawled (1 failed), depth up to 1, 1 skipped by robots.txt\n"));
+    }
+}
@@ -0,0 +1,339 @@
+//! Connectivity diagnostics for claWasm
+//!
+//! `diagnose()` runs every check and reports them together, so the UI can
+//! say which piece is broken when "nothing works": the proxy, the provider
+//! credentials, or browser storage. Checks never fail the call itself.
+
+use serde::Serialize;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::config::{proxy_endpoint, Config};
+use crate::platform::{self, HttpRequest};
+use crate::providers::Provider;
+use crate::tools;
+
+/// Browsers cap localStorage around 5 MB per origin
+const LOCAL_STORAGE_LIMIT: usize = 5 * 1024 * 1024;
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+    Skipped,
+}
+
+/// Result of one diagnostic check
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+impl Check {
+    fn new(name: &str, status: Status, detail: impl Into<String>) -> Self {
+        Check { name: name.to_string(), status, detail: detail.into(), hint: None, latency_ms: None }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Everything `diagnose()` found
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// True when no check failed (warnings are allowed)
+    pub ok: bool,
+    pub checks: Vec<Check>,
+    pub checked_at: String,
+}
+
+impl Report {
+    pub fn new(checks: Vec<Check>) -> Self {
+        Report {
+            ok: checks.iter().all(|c| c.status != Status::Fail),
+            checks,
+            checked_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Cheapest authenticated request that proves a provider's credentials work
+#[derive(Debug, PartialEq)]
+pub struct Probe {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// Sent through the proxy, like chat requests to this provider
+    pub via_proxy: bool,
+}
+
+/// Model-listing request for the configured provider, or why none can be made
+pub fn provider_probe(config: &Config) -> Result<Probe, String> {
+    let provider = Provider::from_name(&config.provider.active, config.provider.base_url.as_deref());
+    let key = config.provider.api_key.as_deref().filter(|k| !k.trim().is_empty());
+    let bearer = |key: &str| vec![("Authorization".to_string(), format!("Bearer {}", key))];
+    match provider {
+        Provider::OpenAI { base_url } => Ok(Probe {
+            url: format!("{}/models", base_url.trim_end_matches('/')),
+            headers: bearer(key.ok_or("API key not set")?),
+            via_proxy: false,
+        }),
+        Provider::Anthropic => Ok(Probe {
+            url: "https://api.anthropic.com/v1/models".to_string(),
+            headers: vec![
+                ("x-api-key".to_string(), key.ok_or("API key not set")?.to_string()),
+                ("anthropic-version".to_string(), "2023-06-01".to_string()),
+            ],
+            via_proxy: false,
+        }),
+        Provider::Ollama { base_url, .. } => {
+            let cloud = base_url.contains("ollama.com");
+            Ok(Probe {
+                url: format!("{}/api/tags", base_url.trim_end_matches('/')),
+                headers: match key {
+                    Some(key) => bearer(key),
+                    None if cloud => return Err("API key not set".to_string()),
+                    None => Vec::new(),
+                },
+                via_proxy: cloud,
+            })
+        }
+    }
+}
+
+/// Model names in a model-listing response (OpenAI/Anthropic `data`, Ollama `models`)
+pub fn listed_models(json: &serde_json::Value) -> Vec<String> {
+    let data = json["data"].as_array().into_iter().flatten()
+        .filter_map(|m| m["id"].as_str());
+    let ollama = json["models"].as_array().into_iter().flatten()
+        .filter_map(|m| m["name"].as_str().or_else(|| m["model"].as_str()));
+    data.chain(ollama).map(|s| s.to_string()).collect()
+}
+
+/// Judge a provider probe response
+pub fn provider_check(status: u16, body: &str, config: &Config) -> Check {
+    let name = "provider";
+    let provider = &config.provider.active;
+    match status {
+        200..=299 => {
+            let models = serde_json::from_str(body).map(|json| listed_models(&json)).unwrap_or_default();
+            let normalize = |m: &str| m.trim_end_matches(":latest").trim_end_matches(":cloud").to_string();
+            let wanted = normalize(&config.provider.model);
+            if models.is_empty() || models.iter().any(|m| normalize(m) == wanted) {
+                Check::new(name, Status::Ok, format!("{} accepted the credentials", provider))
+            } else {
+                Check::new(name, Status::Warn, format!("{} is reachable but does not list model \"{}\"", provider, config.provider.model))
+                    .hint(format!("Pick one of: {}", models.iter().take(10).cloned().collect::<Vec<_>>().join(", ")))
+            }
+        }
+        401 | 403 => Check::new(name, Status::Fail, format!("{} rejected the API key (HTTP {})", provider, status))
+            .hint("Check the API key in Settings; it may be revoked or belong to another provider."),
+        404 => Check::new(name, Status::Warn, format!("{} is reachable but has no model listing endpoint (HTTP 404)", provider))
+            .hint("Credentials could not be verified; try sending a message."),
+        429 => Check::new(name, Status::Warn, format!("{} is rate limiting or out of quota (HTTP 429)", provider))
+            .hint("Wait a moment or check the account's billing and limits."),
+        _ => Check::new(name, Status::Fail, format!("{} answered HTTP {}", provider, status))
+            .hint("The provider may be down; check its status page or the base URL."),
+    }
+}
+
+/// Judge storage usage reported by navigator.storage.estimate()
+pub fn quota_check(usage: f64, quota: f64) -> Check {
+    let name = "storage_quota";
+    if quota <= 0.0 {
+        return Check::new(name, Status::Skipped, "Browser did not report a storage quota");
+    }
+    let percent = usage / quota * 100.0;
+    let detail = format!("{:.1} MB of {:.0} MB used ({:.0}%)", usage / 1e6, quota / 1e6, percent);
+    match percent {
+        p if p >= 95.0 => Check::new(name, Status::Fail, detail).hint("Storage is almost full; delete files or old sessions."),
+        p if p >= 80.0 => Check::new(name, Status::Warn, detail).hint("Storage is filling up; consider deleting files or old sessions."),
+        _ => Check::new(name, Status::Ok, detail),
+    }
+}
+
+/// Judge localStorage, which has its own much smaller limit
+pub fn local_storage_check(bytes: usize, writable: bool) -> Check {
+    let name = "local_storage";
+    if !writable {
+        return Check::new(name, Status::Fail, "localStorage is not writable")
+            .hint("Private browsing or a full quota blocks saving settings, sessions and memory.");
+    }
+    let percent = bytes as f64 / LOCAL_STORAGE_LIMIT as f64 * 100.0;
+    let detail = format!("~{:.0} KB used of ~5 MB ({:.0}%)", bytes as f64 / 1024.0, percent);
+    if percent >= 80.0 {
+        Check::new(name, Status::Warn, detail).hint("Delete saved files or old sessions before writes start failing.")
+    } else {
+        Check::new(name, Status::Ok, detail)
+    }
+}
+
+/// Run all checks against the given configuration
+pub async fn diagnose(config: &Config) -> Report {
+    let mut checks = vec![check_proxy(&config.proxy_url).await];
+    let proxy_up = checks[0].status == Status::Ok;
+    checks.push(check_provider(config, proxy_up).await);
+    checks.push(check_local_storage());
+    checks.push(check_quota().await);
+    Report::new(checks)
+}
+
+async fn check_proxy(proxy: &str) -> Check {
+    let started = js_sys::Date::now();
+    let mut check = match get(&proxy_endpoint(proxy, "/health"), &[]).await {
+        Ok((200, _)) => Check::new("proxy", Status::Ok, format!("Proxy at {} is running", proxy)),
+        // Older proxies have no /health route but still answer
+        Ok((404, _)) => Check::new("proxy", Status::Ok, format!("Proxy at {} is running (no /health route; consider updating it)", proxy)),
+        Ok((401, _)) => Check::new("proxy", Status::Fail, format!("Proxy at {} requires a token", proxy))
+            .hint("Set the proxy token (setProxyToken or \"proxy_token\" in the config) to the proxy's CLAWASM_PROXY_TOKEN."),
+        Ok((status, _)) => Check::new("proxy", Status::Fail, format!("Proxy at {} answered HTTP {}", proxy, status))
+            .hint("Check that the Proxy URL in Settings points at the claWasm proxy."),
+        Err(e) => Check::new("proxy", Status::Fail, format!("Proxy at {} is unreachable: {}", proxy, js_error(&e)))
+            .hint("Start it with `cargo run --bin proxy --features proxy`, or fix the Proxy URL in Settings."),
+    };
+    check.latency_ms = Some((js_sys::Date::now() - started) as u64);
+    check
+}
+
+async fn check_provider(config: &Config, proxy_up: bool) -> Check {
+    let probe = match provider_probe(config) {
+        Ok(probe) => probe,
+        Err(reason) => return Check::new("provider", Status::Fail, format!("{}: {}", config.provider.active, reason))
+            .hint("Enter an API key in Settings."),
+    };
+    if probe.via_proxy && !proxy_up {
+        return Check::new("provider", Status::Skipped, format!("{} is reached through the proxy, which is down", config.provider.active));
+    }
+    
+    let started = js_sys::Date::now();
+    let result = if probe.via_proxy {
+        let headers: serde_json::Map<String, serde_json::Value> = probe.headers.iter()
+            .map(|(k, v)| (k.clone(), serde_json::json!(v)))
+            .collect();
+        tools::fetch_via_proxy(&probe.url, "GET", serde_json::Value::Object(headers), &config.proxy_url).await
+            .map(|r| (r.status, r.body))
+    } else {
+        get(&probe.url, &probe.headers).await
+    };
+    let mut check = match result {
+        Ok((status, body)) => provider_check(status, &body, config),
+        Err(e) => Check::new("provider", Status::Fail, format!("{} is unreachable: {}", config.provider.active, js_error(&e)))
+            .hint(if config.provider.active == "ollama" {
+                "Start Ollama (`ollama serve`) and allow browser origins with OLLAMA_ORIGINS=*."
+            } else {
+                "Check the network, the base URL, and that the provider allows browser (CORS) requests."
+            }),
+    };
+    check.latency_ms = Some((js_sys::Date::now() - started) as u64);
+    check
+}
+
+fn check_local_storage() -> Check {
+    let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) else {
+        return local_storage_check(0, false);
+    };
+    let writable = storage.set_item("clawasm_diagnose_probe", "1").is_ok()
+        && storage.remove_item("clawasm_diagnose_probe").is_ok();
+    let length = storage.length().unwrap_or(0);
+    // UTF-16: two bytes per character of every key and value
+    let bytes: usize = (0..length)
+        .filter_map(|i| storage.key(i).ok().flatten())
+        .map(|key| (key.len() + storage.get_item(&key).ok().flatten().map_or(0, |v| v.len())) * 2)
+        .sum();
+    local_storage_check(bytes, writable)
+}
+
+async fn check_quota() -> Check {
+    let Some(window) = web_sys::window() else {
+        return quota_check(0.0, 0.0);
+    };
+    let estimate = match window.navigator().storage().estimate() {
+        Ok(promise) => JsFuture::from(promise).await,
+        Err(e) => Err(e),
+    };
+    match estimate {
+        Ok(estimate) => {
+            let estimate: web_sys::StorageEstimate = estimate.unchecked_into();
+            quota_check(estimate.get_usage().unwrap_or(0.0), estimate.get_quota().unwrap_or(0.0))
+        }
+        Err(e) => Check::new("storage_quota", Status::Skipped, format!("Storage estimate unavailable: {}", js_error(&e))),
+    }
+}
+
+/// Direct GET, returning status and body
+async fn get(url: &str, headers: &[(String, String)]) -> Result<(u16, String), JsValue> {
+    let mut request = HttpRequest::get(url);
+    request.headers.extend_from_slice(headers);
+    let response = platform::fetch(request).await?;
+    Ok((response.status, response.body))
+}
+
+fn js_error(e: &JsValue) -> String {
+    e.as_string()
+        .or_else(|| js_sys::Reflect::get(e, &JsValue::from_str("message")).ok().and_then(|m| m.as_string()))
+        .unwrap_or_else(|| "network error".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(active: &str, key: Option<&str>, model: &str) -> Config {
+        let mut config = Config::default();
+        config.provider.active = active.to_string();
+        config.provider.api_key = key.map(|k| k.to_string());
+        config.provider.model = model.to_string();
+        config
+    }
+
+    #[test]
+    fn test_provider_probe_per_provider() {
+        let probe = provider_probe(&config("groq", Some("gsk"), "m")).unwrap();
+        assert_eq!(probe.url, "https://api.groq.com/openai/v1/models");
+        assert_eq!(probe.headers, vec![("Authorization".to_string(), "Bearer gsk".to_string())]);
+        
+        assert_eq!(provider_probe(&config("anthropic", None, "m")).unwrap_err(), "API key not set");
+        
+        let local = provider_probe(&config("ollama", None, "llama3.2")).unwrap();
+        assert_eq!((local.url.as_str(), local.via_proxy), ("http://localhost:11434/api/tags", false));
+        assert!(provider_probe(&config("ollama_cloud", Some("k"), "m")).unwrap().via_proxy);
+    }
+
+    #[test]
+    fn test_provider_check_statuses() {
+        let ollama = config("ollama", None, "llama3.2");
+        let tags = r#"{"models": [{"name": "llama3.2:latest"}, {"name": "qwen2.5:14b"}]}"#;
+        assert_eq!(provider_check(200, tags, &ollama).status, Status::Ok);
+        
+        let missing = provider_check(200, tags, &config("ollama", None, "mistral"));
+        assert_eq!(missing.status, Status::Warn);
+        assert!(missing.hint.unwrap().contains("qwen2.5:14b"));
+        
+        assert_eq!(provider_check(401, "", &ollama).status, Status::Fail);
+        assert_eq!(provider_check(429, "", &ollama).status, Status::Warn);
+    }
+
+    #[test]
+    fn test_storage_checks() {
+        assert_eq!(quota_check(10e6, 100e6).status, Status::Ok);
+        assert_eq!(quota_check(85e6, 100e6).status, Status::Warn);
+        assert_eq!(quota_check(99e6, 100e6).status, Status::Fail);
+        assert_eq!(quota_check(1.0, 0.0).status, Status::Skipped);
+        assert_eq!(local_storage_check(0, false).status, Status::Fail);
+        assert_eq!(local_storage_check(4_500_000, true).status, Status::Warn);
+        
+        let report = Report::new(vec![quota_check(85e6, 100e6), local_storage_check(10, true)]);
+        assert!(report.ok);
+    }
+}
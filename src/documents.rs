@@ -0,0 +1,522 @@
+//! Question answering over documents, on the memory system
+//!
+//! `ingest_document` cuts a text (pasted, fetched or picked in the UI) into
+//! overlapping chunks of a few paragraphs and stores each, embedded, as a
+//! memory entry in the "document" namespace, tagged with the document's
+//! name, source and chunk number. Document chunks are kept apart from the
+//! assistant's memories: never recalled as memories, counted against
+//! `max_entries` or consolidated. `ask_document` ranks the chunks against a
+//! question with the memory system's hybrid search and returns the best as
+//! numbered passages to answer from and cite.
+//!
+//! Documents live in named collections ("project docs", "security notes"),
+//! each with its own retrieval settings and stored, like the chunks, as a
+//! memory entry in its own namespace. Documents ingested without one go to
+//! the `default` collection, which always exists.
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+use crate::error::{Error, Result};
+use crate::memory::{MemoryEntry, MemorySystem};
+
+/// Memory namespace of document chunks
+pub const NAMESPACE: &str = "document";
+/// Memory namespace of collection definitions
+const COLLECTION_NAMESPACE: &str = "collection";
+/// The collection of documents ingested without one
+pub const DEFAULT_COLLECTION: &str = "default";
+/// Target chunk size, and how much of the previous chunk each repeats
+const CHUNK_CHARS: usize = 1200;
+const OVERLAP_CHARS: usize = 200;
+/// Largest document, in chunks (about 300 pages of text)
+pub const MAX_CHUNKS: usize = 400;
+
+thread_local! {
+    /// Files picked in the UI, waiting for `ingest_document`
+    static UPLOADS: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Keep a picked file's text for `ingest_document` to take by name
+pub fn attach(name: &str, text: &str) {
+    UPLOADS.with(|u| u.borrow_mut().insert(name.to_string(), text.to_string()));
+}
+
+/// Take the text of an attached file
+pub fn take_upload(name: &str) -> Option<String> {
+    UPLOADS.with(|u| u.borrow_mut().remove(name))
+}
+
+/// Names of the attached files not ingested yet
+pub fn uploads() -> Vec<String> {
+    UPLOADS.with(|u| u.borrow().keys().cloned().collect())
+}
+
+/// Cut `text` into chunks of about `CHUNK_CHARS`, on paragraph and
+/// sentence boundaries, each starting with the end of the one before
+pub fn chunk(text: &str) -> Vec<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    for paragraph in text.split("\n\n").map(|p| p.split_whitespace().collect::<Vec<_>>().join(" ")) {
+        for sentence in paragraph.split_inclusive(['.', '!', '?']).map(str::trim).filter(|s| !s.is_empty()) {
+            // A run-on "sentence" (a table, a log) is cut where it must be
+            let chars: Vec<char> = sentence.chars().collect();
+            pieces.extend(chars.chunks(CHUNK_CHARS).map(|c| c.iter().collect::<String>()));
+        }
+    }
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut size = 0;
+    for piece in &pieces {
+        let len = piece.chars().count();
+        if size + len > CHUNK_CHARS && size > OVERLAP_CHARS {
+            chunks.push(current.join(" "));
+            // Carry the last pieces, up to the overlap, into the next chunk
+            let mut carried = 0;
+            let keep = current.iter().rev()
+                .take_while(|p| {
+                    carried += p.chars().count();
+                    carried <= OVERLAP_CHARS
+                })
+                .count();
+            current.drain(..current.len() - keep);
+            size = current.iter().map(|p| p.chars().count() + 1).sum();
+        }
+        current.push(piece);
+        size += len + 1;
+    }
+    if !current.is_empty() {
+        chunks.push(current.join(" "));
+    }
+    chunks
+}
+
+/// A named corpus of documents ("project docs", "security notes") with its
+/// own retrieval settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Collection {
+    pub name: String,
+    pub description: String,
+    pub settings: CollectionSettings,
+    pub created_at: i64,
+}
+
+/// How `ask_document` searches a collection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectionSettings {
+    /// Passages returned when the question does not say (1 to 20)
+    pub limit: usize,
+    /// Passages scoring less are left out (0 to 1)
+    pub min_score: f32,
+    /// Weight of embedding similarity against keyword overlap (0 to 1);
+    /// the memory settings' weights when unset
+    pub vector_weight: Option<f32>,
+}
+
+impl Default for CollectionSettings {
+    fn default() -> Self {
+        CollectionSettings { limit: 5, min_score: 0.0, vector_weight: None }
+    }
+}
+
+impl CollectionSettings {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=20).contains(&self.limit) {
+            return Err(Error::new("limit must be between 1 and 20"));
+        }
+        if !(0.0..=1.0).contains(&self.min_score) {
+            return Err(Error::new("min_score must be between 0 and 1"));
+        }
+        if self.vector_weight.is_some_and(|w| !(0.0..=1.0).contains(&w)) {
+            return Err(Error::new("vector_weight must be between 0 and 1"));
+        }
+        Ok(())
+    }
+}
+
+/// The collection an entry belongs to; documents from before collections
+/// are in the default one
+fn collection_of(entry: &MemoryEntry) -> &str {
+    entry.metadata["collection"].as_str().unwrap_or(DEFAULT_COLLECTION)
+}
+
+/// The collections in `memory`, by name; the default one is always there
+pub fn collections(memory: &MemorySystem) -> Vec<Collection> {
+    let mut collections: BTreeMap<String, Collection> = memory.get_all().iter()
+        .filter(|e| e.namespace() == Some(COLLECTION_NAMESPACE))
+        .filter_map(|e| serde_json::from_value::<Collection>(e.metadata["collection"].clone()).ok())
+        .map(|c| (c.name.clone(), c))
+        .collect();
+    collections.entry(DEFAULT_COLLECTION.to_string()).or_insert_with(|| Collection {
+        name: DEFAULT_COLLECTION.to_string(),
+        description: "Documents ingested without a collection".to_string(),
+        settings: CollectionSettings::default(),
+        created_at: 0,
+    });
+    collections.into_values().collect()
+}
+
+/// Collection `name`, or an error listing the collections there are
+pub fn collection(memory: &MemorySystem, name: &str) -> Result<Collection> {
+    let all = collections(memory);
+    all.iter().find(|c| c.name == name).cloned().ok_or_else(|| Error::new(format!(
+        "No collection named '{}'. Collections: {}",
+        name, all.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+    )))
+}
+
+/// Store `collection`, replacing the stored entry of that name
+fn store_collection(memory: &mut MemorySystem, collection: &Collection) -> Result<()> {
+    let stored: Vec<String> = memory.get_all().iter()
+        .filter(|e| e.namespace() == Some(COLLECTION_NAMESPACE) && e.metadata["collection"]["name"] == collection.name.as_str())
+        .map(|e| e.id.clone())
+        .collect();
+    memory.delete_many(&stored)?;
+    let metadata = serde_json::json!({ "namespace": COLLECTION_NAMESPACE, "collection": collection });
+    memory.insert(&format!("Document collection {}: {}", collection.name, collection.description), None, metadata)?;
+    Ok(())
+}
+
+/// Create an empty collection
+pub fn create_collection(memory: &Rc<RefCell<MemorySystem>>, name: &str, description: &str, settings: CollectionSettings) -> Result<Collection> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(Error::new("A collection name has 1 to 64 characters"));
+    }
+    settings.validate()?;
+    let mut memory = memory.borrow_mut();
+    memory.ensure_loaded()?;
+    if collections(&memory).iter().any(|c| c.name == name) {
+        return Err(Error::new(format!("Collection '{}' already exists", name)));
+    }
+    let collection = Collection {
+        name: name.to_string(),
+        description: description.trim().to_string(),
+        settings,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    store_collection(&mut memory, &collection)?;
+    Ok(collection)
+}
+
+/// Change a collection's retrieval settings, and its description if given
+pub fn configure_collection(memory: &Rc<RefCell<MemorySystem>>, name: &str, description: Option<&str>, settings: CollectionSettings) -> Result<Collection> {
+    settings.validate()?;
+    let mut memory = memory.borrow_mut();
+    memory.ensure_loaded()?;
+    let mut collection = collection(&memory, name)?;
+    if let Some(description) = description {
+        collection.description = description.trim().to_string();
+    }
+    collection.settings = settings;
+    store_collection(&mut memory, &collection)?;
+    Ok(collection)
+}
+
+/// Delete a collection and its documents, returning how many chunks went;
+/// the default collection is emptied and its settings reset
+pub fn delete_collection(memory: &Rc<RefCell<MemorySystem>>, name: &str) -> Result<usize> {
+    let mut memory = memory.borrow_mut();
+    memory.ensure_loaded()?;
+    collection(&memory, name)?;
+    let chunks: Vec<String> = memory.get_all().iter()
+        .filter(|e| e.namespace() == Some(NAMESPACE) && collection_of(e) == name)
+        .map(|e| e.id.clone())
+        .collect();
+    let stored: Vec<String> = memory.get_all().iter()
+        .filter(|e| e.namespace() == Some(COLLECTION_NAMESPACE) && e.metadata["collection"]["name"] == name)
+        .map(|e| e.id.clone())
+        .collect();
+    memory.delete_many(&stored)?;
+    memory.delete_many(&chunks)
+}
+
+/// A collection with what it holds
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionStats {
+    #[serde(flatten)]
+    pub collection: Collection,
+    pub documents: usize,
+    pub chunks: usize,
+    pub chars: usize,
+    /// Chunks with an embedding (the rest match by keywords only)
+    pub embedded: usize,
+}
+
+/// Every collection with its counts
+pub fn collection_stats(memory: &MemorySystem) -> Vec<CollectionStats> {
+    collections(memory).into_iter()
+        .map(|collection| {
+            let chunks: Vec<&MemoryEntry> = memory.get_all().iter()
+                .filter(|e| e.namespace() == Some(NAMESPACE) && collection_of(e) == collection.name)
+                .collect();
+            let documents: BTreeSet<&str> = chunks.iter().filter_map(|e| e.metadata["document"].as_str()).collect();
+            CollectionStats {
+                documents: documents.len(),
+                chunks: chunks.len(),
+                chars: chunks.iter().map(|e| e.content.chars().count()).sum(),
+                embedded: chunks.iter().filter(|e| e.embedding.is_some()).count(),
+                collection,
+            }
+        })
+        .collect()
+}
+
+/// An ingested document
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentInfo {
+    pub collection: String,
+    pub name: String,
+    pub source: String,
+    pub chunks: usize,
+    pub ingested_at: i64,
+}
+
+/// The documents in `memory`, or in one collection, by collection and name
+pub fn list(memory: &MemorySystem, collection: Option<&str>) -> Vec<DocumentInfo> {
+    let mut documents: BTreeMap<(String, String), DocumentInfo> = BTreeMap::new();
+    let chunks = memory.get_all().iter()
+        .filter(|e| e.namespace() == Some(NAMESPACE))
+        .filter(|e| collection.is_none_or(|c| collection_of(e) == c));
+    for entry in chunks {
+        let name = entry.metadata["document"].as_str().unwrap_or("").to_string();
+        let info = documents.entry((collection_of(entry).to_string(), name.clone())).or_insert_with(|| DocumentInfo {
+            collection: collection_of(entry).to_string(),
+            name,
+            source: entry.metadata["source"].as_str().unwrap_or("").to_string(),
+            chunks: 0,
+            ingested_at: entry.created_at,
+        });
+        info.chunks += 1;
+    }
+    documents.into_values().collect()
+}
+
+/// Delete the chunks of document `name` in `collection`, returning how
+/// many there were
+pub fn remove(memory: &Rc<RefCell<MemorySystem>>, collection: &str, name: &str) -> Result<usize> {
+    let mut memory = memory.borrow_mut();
+    memory.ensure_loaded()?;
+    let ids: Vec<String> = memory.get_all().iter()
+        .filter(|e| e.namespace() == Some(NAMESPACE) && collection_of(e) == collection && e.metadata["document"] == name)
+        .map(|e| e.id.clone())
+        .collect();
+    memory.delete_many(&ids)
+}
+
+/// Chunk, embed and store `text` as document `name` of `collection`,
+/// replacing a document of that name there; returns the number of chunks
+pub async fn ingest(memory: &Rc<RefCell<MemorySystem>>, collection: &str, name: &str, source: &str, text: &str) -> Result<usize> {
+    ingest_chunks(memory, collection, name, source, &chunk(text), true).await
+}
+
+/// Store `chunks` as the document `name` of `collection`, replacing any
+/// document of that name; without `embed` they are found by keywords only
+/// and never sent to an embedding API
+pub async fn ingest_chunks(
+    memory: &Rc<RefCell<MemorySystem>>,
+    collection: &str,
+    name: &str,
+    source: &str,
+    chunks: &[String],
+    embed: bool,
+) -> Result<usize> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::new("A document needs a name"));
+    }
+    {
+        let mut memory = memory.borrow_mut();
+        memory.ensure_loaded()?;
+        self::collection(&memory, collection)?;
+    }
+    if chunks.is_empty() {
+        return Err(Error::new(format!("{} has no text", name)));
+    }
+    if chunks.len() > MAX_CHUNKS {
+        return Err(Error::new(format!(
+            "{} is too long: {} chunks, at most {}. Split it into parts.",
+            name, chunks.len(), MAX_CHUNKS
+        )));
+    }
+
+    remove(memory, collection, name)?;
+    let embedder = memory.borrow().embedder();
+    let total = chunks.len();
+    for (i, text) in chunks.iter().enumerate() {
+        let embedding = match embed {
+            true => embedder.embed(text).await.ok(),
+            false => None,
+        };
+        let metadata = serde_json::json!({
+            "namespace": NAMESPACE,
+            "collection": collection,
+            "document": name,
+            "source": source,
+            "chunk": i + 1,
+            "chunks": total,
+        });
+        memory.borrow_mut().insert(text, embedding, metadata)?;
+    }
+    Ok(total)
+}
+
+/// A chunk retrieved for a question
+#[derive(Debug, Clone, Serialize)]
+pub struct Passage {
+    pub collection: String,
+    pub document: String,
+    pub source: String,
+    pub chunk: u64,
+    pub chunks: u64,
+    pub text: String,
+    pub score: f32,
+}
+
+impl Passage {
+    /// Where the passage comes from, e.g. "notes / login.md, part 2/5 (upload)"
+    pub fn label(&self) -> String {
+        format!(
+            "{}{}, part {}/{}{}",
+            if self.collection == DEFAULT_COLLECTION { String::new() } else { format!("{} / ", self.collection) },
+            self.document,
+            self.chunk,
+            self.chunks,
+            if self.source.is_empty() { String::new() } else { format!(" ({})", self.source) },
+        )
+    }
+}
+
+/// What to search for a question
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scope<'a> {
+    /// One collection, searched with its settings; all with the defaults
+    pub collection: Option<&'a str>,
+    pub document: Option<&'a str>,
+    /// Passages wanted, overriding the collection's `limit`
+    pub limit: Option<usize>,
+}
+
+/// The chunks in `scope` that best answer `question`
+pub async fn search(memory: &Rc<RefCell<MemorySystem>>, question: &str, scope: Scope<'_>) -> Result<Vec<Passage>> {
+    let (embedder, settings) = {
+        let mut memory = memory.borrow_mut();
+        memory.ensure_loaded()?;
+        let settings = match scope.collection {
+            Some(name) => collection(&memory, name)?.settings,
+            None => CollectionSettings::default(),
+        };
+        (memory.embedder(), settings)
+    };
+    let query_embedding = embedder.embed(question).await.ok();
+    let limit = scope.limit.unwrap_or(settings.limit);
+    let weights = settings.vector_weight.map(|w| (w, 1.0 - w));
+    let results = memory.borrow_mut().rank_where(question, query_embedding.as_deref(), limit, weights, |entry| {
+        entry.namespace() == Some(NAMESPACE)
+            && scope.collection.is_none_or(|name| collection_of(entry) == name)
+            && scope.document.is_none_or(|name| entry.metadata["document"] == name)
+    });
+    Ok(results.into_iter()
+        .filter(|r| r.score >= settings.min_score)
+        .map(|r| Passage {
+            collection: collection_of(&r.entry).to_string(),
+            document: r.entry.metadata["document"].as_str().unwrap_or("").to_string(),
+            source: r.entry.metadata["source"].as_str().unwrap_or("").to_string(),
+            chunk: r.entry.metadata["chunk"].as_u64().unwrap_or(0),
+            chunks: r.entry.metadata["chunks"].as_u64().unwrap_or(0),
+            text: r.entry.content,
+            score: r.score,
+        })
+        .collect())
+}
+
+/// Numbered passages with a closing instruction to answer only from them
+pub fn cite(question: &str, passages: &[Passage]) -> String {
+    let mut out = format!("Passages for \"{}\":\n", question);
+    for (i, passage) in passages.iter().enumerate() {
+        out.push_str(&format!("\n[{}] {}\n{}\n", i + 1, passage.label(), passage.text));
+    }
+    out.push_str("\nAnswer from these passages only, citing them as [n]. If they do not answer the question, say so.");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{EmbeddingProvider, MemoryBackend, MemoryConfig};
+    use crate::platform::transport::tests::block_on;
+
+    #[test]
+    fn test_chunk() {
+        assert!(chunk(" \n\n ").is_empty());
+        let sentence = "The quarterly report covers revenue, churn and hiring in detail. ";
+        let text = format!("{}\n\n{}", sentence.repeat(30), sentence.repeat(30));
+        let chunks = chunk(&text);
+        assert!(chunks.len() >= 3);
+        assert!(chunks.iter().all(|c| c.chars().count() <= CHUNK_CHARS + OVERLAP_CHARS));
+        assert!(chunks[1].starts_with("The quarterly"), "chunks start on a sentence");
+
+        let run_on = "x".repeat(CHUNK_CHARS * 2 + 10);
+        assert_eq!(chunk(&run_on).len(), 3);
+    }
+
+    #[test]
+    fn test_namespace_kept_apart() {
+        let memory = Rc::new(RefCell::new(MemorySystem::new(MemoryConfig {
+            backend: MemoryBackend::None,
+            embedding_provider: EmbeddingProvider::Local,
+            max_entries: 2,
+            ..MemoryConfig::default()
+        })));
+        let text = "Invoices are due within thirty days of delivery.\n\nLate invoices accrue two percent interest per month.";
+        assert_eq!(block_on(ingest(&memory, DEFAULT_COLLECTION, "terms.txt", "upload", text)).unwrap(), 1);
+        block_on(ingest(&memory, DEFAULT_COLLECTION, "terms.txt", "upload", text)).unwrap();
+        memory.borrow_mut().insert("The user likes invoices paid early", None, serde_json::json!({})).unwrap();
+        memory.borrow_mut().insert("The user's name is Ann", None, serde_json::json!({})).unwrap();
+
+        let docs = list(&memory.borrow(), None);
+        assert_eq!((docs.len(), docs[0].chunks), (1, 1), "re-ingesting replaces");
+        assert_eq!(memory.borrow_mut().rank("invoices interest", None, 5).len(), 2, "memories only");
+        let passages = block_on(search(&memory, "invoice interest", Scope::default())).unwrap();
+        assert_eq!(passages.len(), 1);
+        assert!(cite("invoice interest", &passages).contains("[1] terms.txt, part 1/1 (upload)\nInvoices are due"));
+        assert_eq!(remove(&memory, DEFAULT_COLLECTION, "terms.txt").unwrap(), 1);
+        assert!(list(&memory.borrow(), None).is_empty());
+    }
+
+    #[test]
+    fn test_collections() {
+        let memory = Rc::new(RefCell::new(MemorySystem::new(MemoryConfig {
+            backend: MemoryBackend::None,
+            embedding_provider: EmbeddingProvider::Local,
+            ..MemoryConfig::default()
+        })));
+        let strict = CollectionSettings { limit: 1, min_score: 0.2, vector_weight: Some(0.0) };
+        create_collection(&memory, "security notes", "Pentest findings", strict.clone()).unwrap();
+        assert!(create_collection(&memory, "security notes", "", CollectionSettings::default()).is_err());
+        assert!(create_collection(&memory, "x", "", CollectionSettings { limit: 0, ..Default::default() }).is_err());
+        assert!(block_on(ingest(&memory, "project docs", "a.md", "", "Some text here.")).is_err(), "no such collection");
+
+        block_on(ingest(&memory, "security notes", "login.md", "", "The login form allows SQL injection through the user field.")).unwrap();
+        block_on(ingest(&memory, "security notes", "tls.md", "", "The TLS certificate expires next month on the API host.")).unwrap();
+        block_on(ingest(&memory, DEFAULT_COLLECTION, "login.md", "", "Login with your SSO account.")).unwrap();
+
+        let stats = collection_stats(&memory.borrow());
+        assert_eq!(stats.iter().map(|s| (s.collection.name.as_str(), s.documents)).collect::<Vec<_>>(), [("default", 1), ("security notes", 2)]);
+        let scope = Scope { collection: Some("security notes"), ..Scope::default() };
+        let passages = block_on(search(&memory, "login injection", scope)).unwrap();
+        assert_eq!(passages.iter().map(|p| p.document.as_str()).collect::<Vec<_>>(), ["login.md"], "the collection's limit");
+        assert!(cite("q", &passages).contains("[1] security notes / login.md, part 1/1\n"));
+        assert!(block_on(search(&memory, "unrelated words", scope)).unwrap().is_empty(), "under min_score");
+
+        let loose = CollectionSettings { limit: 3, ..strict };
+        assert_eq!(configure_collection(&memory, "security notes", None, loose).unwrap().description, "Pentest findings");
+        assert_eq!(block_on(search(&memory, "login injection", scope)).unwrap().len(), 1, "tls.md scores under min_score");
+        assert_eq!(delete_collection(&memory, "security notes").unwrap(), 2);
+        assert_eq!(collections(&memory.borrow()).len(), 1);
+        assert_eq!(list(&memory.borrow(), None).len(), 1, "the default collection's document stays");
+    }
+}
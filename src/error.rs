@@ -0,0 +1,59 @@
+//! Error type shared by the browser and native builds
+//!
+//! Core modules return `Error` so they compile without wasm-bindgen; the
+//! browser bindings convert it to a `JsValue` at the boundary.
+
+use std::fmt;
+
+/// A human-readable error message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn new(message: impl Into<String>) -> Self {
+        Error(message.into())
+    }
+
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error(message.to_string())
+    }
+}
+
+#[cfg(feature = "web")]
+impl From<wasm_bindgen::JsValue> for Error {
+    fn from(value: wasm_bindgen::JsValue) -> Self {
+        match value.as_string() {
+            Some(message) => Error(message),
+            None => Error(format!("{:?}", value)),
+        }
+    }
+}
+
+#[cfg(feature = "web")]
+impl From<Error> for wasm_bindgen::JsValue {
+    fn from(error: Error) -> Self {
+        wasm_bindgen::JsValue::from_str(&error.0)
+    }
+}
@@ -0,0 +1,443 @@
+//! File store: the PDFs and audio tools create
+//!
+//! A file's metadata is stored under its ID ("pdf_…" or "audio_…") and its
+//! bytes in chunks of 256 KiB, base64, under "<id>_data_0", "<id>_data_1"…
+//! A manifest under "<id>_data" gives the size and the SHA-256 of the file
+//! and of each chunk: a single item would exceed what browsers allow per
+//! value for anything but short audio, and the hashes catch a chunk lost or
+//! damaged in storage. Chunks can be read one at a time, so a reader can
+//! stop and resume where it left off. Files from before chunking keep the
+//! whole base64 under "<id>_data" and are read as one unchecked chunk.
+//! Every item is sealed by the vault when it is on; `clawasm_files` lists
+//! the IDs.
+//!
+//! `create_pdf` (in JavaScript, then `adopt`), `export_conversation` and
+//! `text_to_speech` write files; this module lets host UIs list, read,
+//! rename and delete them, see how much space they take and clear out old
+//! ones.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::platform::storage;
+use crate::vault;
+
+/// Storage key of the file ID list
+pub const FILES_INDEX_KEY: &str = "clawasm_files";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Pdf,
+    Audio,
+}
+
+impl FileKind {
+    pub const ALL: [FileKind; 2] = [FileKind::Pdf, FileKind::Audio];
+
+    /// The kind of file `id` names, from its prefix
+    pub fn of(id: &str) -> Option<FileKind> {
+        if id.starts_with("pdf_") {
+            Some(FileKind::Pdf)
+        } else if id.starts_with("audio_") {
+            Some(FileKind::Audio)
+        } else {
+            None
+        }
+    }
+
+    /// MIME type of `filename` when of this kind
+    pub fn mime(self, filename: &str) -> &'static str {
+        match self {
+            FileKind::Pdf => "application/pdf",
+            FileKind::Audio => match filename.rsplit('.').next() {
+                Some("ogg") => "audio/ogg",
+                Some("wav") => "audio/wav",
+                _ => "audio/mpeg",
+            },
+        }
+    }
+}
+
+/// Bytes per stored chunk
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// A file as listed for a downloads panel
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoredFile {
+    pub id: String,
+    pub kind: FileKind,
+    pub filename: String,
+    /// A PDF's title, or the start of the text spoken in an audio file
+    pub description: String,
+    /// RFC 3339
+    pub created_at: String,
+    /// Size of the file itself, in bytes
+    pub size: usize,
+    /// Characters it takes in storage, metadata included
+    pub stored: usize,
+}
+
+/// Space taken by the files of one kind
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Usage {
+    pub kind: FileKind,
+    pub files: usize,
+    pub size: usize,
+    pub stored: usize,
+}
+
+/// Where a file's bytes are, stored under "<id>_data"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Manifest {
+    size: usize,
+    sha256: String,
+    /// SHA-256 of each chunk, in order
+    chunks: Vec<String>,
+}
+
+fn data_key(id: &str) -> String {
+    format!("{}_data", id)
+}
+
+fn chunk_key(id: &str, index: usize) -> String {
+    format!("{}_data_{}", id, index)
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Bytes encoded by `base64` of this length
+fn decoded_len(base64: &str) -> usize {
+    let padding = base64.bytes().rev().take_while(|&b| b == b'=').count();
+    (base64.len() / 4 * 3).saturating_sub(padding)
+}
+
+fn decode(id: &str, base64: &str) -> Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD.decode(base64.trim())
+        .map_err(|e| Error::new(format!("File {} is damaged: {}", id, e)))
+}
+
+/// How file `id`'s bytes are stored
+enum Data {
+    Chunked(Manifest),
+    /// The whole base64, as files were stored before chunking
+    Single(String),
+}
+
+impl Data {
+    fn size(&self) -> usize {
+        match self {
+            Data::Chunked(manifest) => manifest.size,
+            Data::Single(base64) => decoded_len(base64),
+        }
+    }
+
+    fn chunks(&self) -> usize {
+        match self {
+            Data::Chunked(manifest) => manifest.chunks.len(),
+            Data::Single(_) => 1,
+        }
+    }
+}
+
+/// File `id`'s data item, opened, with its stored length
+fn read_data(id: &str) -> Result<(Data, usize)> {
+    let stored = storage::get(&data_key(id))?.unwrap_or_default();
+    Ok((parse_data(id, vault::open(&stored)?)?, stored.len()))
+}
+
+fn parse_data(id: &str, opened: String) -> Result<Data> {
+    // Base64 never starts with "{"
+    if opened.starts_with('{') {
+        let manifest = serde_json::from_str(&opened).map_err(|e| Error::new(format!("File {} is damaged: {}", id, e)))?;
+        Ok(Data::Chunked(manifest))
+    } else {
+        Ok(Data::Single(opened))
+    }
+}
+
+/// The listing of file `id` from its metadata and size
+fn describe(id: &str, metadata: &str, size: usize, stored: usize) -> Option<StoredFile> {
+    let kind = FileKind::of(id)?;
+    let meta: serde_json::Value = serde_json::from_str(metadata).ok()?;
+    let description = match kind {
+        FileKind::Pdf => meta["title"].as_str().unwrap_or("").to_string(),
+        FileKind::Audio => {
+            let text = meta["text"].as_str().unwrap_or("");
+            let preview: String = text.chars().take(80).collect();
+            if preview.len() < text.len() { format!("{}…", preview) } else { preview }
+        }
+    };
+    Some(StoredFile {
+        id: id.to_string(),
+        kind,
+        filename: meta["filename"].as_str().unwrap_or(id).to_string(),
+        description,
+        created_at: meta["created_at"].as_str().unwrap_or("").to_string(),
+        size,
+        stored,
+    })
+}
+
+/// IDs of the stored files, oldest first
+pub fn index() -> Result<Vec<String>> {
+    Ok(storage::get(FILES_INDEX_KEY)?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default())
+}
+
+fn write_index(ids: &[String]) -> Result<()> {
+    storage::set(FILES_INDEX_KEY, &serde_json::to_string(ids).unwrap())
+}
+
+/// Store a file: `metadata` must have at least `filename` and `created_at`
+pub fn save(id: &str, metadata: &impl Serialize, bytes: &[u8]) -> Result<()> {
+    let json = serde_json::to_string(metadata).map_err(|e| Error::new(format!("Serialization error: {}", e)))?;
+    let previous = read_data(id).map(|(data, _)| data.chunks()).unwrap_or(0);
+
+    // Chunks first and the manifest last, so a failed write (out of quota)
+    // never leaves a manifest pointing at missing chunks
+    let mut hashes = Vec::new();
+    for (index, chunk) in bytes.chunks(CHUNK_SIZE).enumerate() {
+        let base64 = base64::engine::general_purpose::STANDARD.encode(chunk);
+        if let Err(e) = storage::set(&chunk_key(id, index), &vault::seal(&base64)?) {
+            for written in 0..index {
+                let _ = storage::remove(&chunk_key(id, written));
+            }
+            return Err(Error::new(format!("Cannot store {} ({} bytes): {}", id, bytes.len(), e)));
+        }
+        hashes.push(sha256(chunk));
+    }
+    let manifest = Manifest { size: bytes.len(), sha256: sha256(bytes), chunks: hashes };
+    storage::set(&data_key(id), &vault::seal(&serde_json::to_string(&manifest).unwrap())?)?;
+    for stale in manifest.chunks.len()..previous {
+        storage::remove(&chunk_key(id, stale))?;
+    }
+    storage::set(id, &vault::seal(&json)?)?;
+
+    let mut ids = index()?;
+    if !ids.iter().any(|i| i == id) {
+        ids.push(id.to_string());
+        write_index(&ids)?;
+    }
+    Ok(())
+}
+
+/// Store the file `create_pdf`'s JavaScript wrote in one plaintext item
+/// again in chunks, sealed
+pub fn adopt(id: &str) -> Result<()> {
+    let metadata = storage::get(id)?.ok_or_else(|| Error::new(format!("File not found: {}", id)))?;
+    let metadata: serde_json::Value = serde_json::from_str(&vault::open(&metadata)?)
+        .map_err(|e| Error::new(format!("File {} is damaged: {}", id, e)))?;
+    let bytes = match read_data(id)?.0 {
+        Data::Single(base64) => decode(id, &base64)?,
+        Data::Chunked(_) => return Ok(()),
+    };
+    save(id, &metadata, &bytes)
+}
+
+/// File `id`'s listing and data
+fn load(id: &str) -> Result<(StoredFile, Data)> {
+    let not_found = || Error::new(format!("File not found: {}", id));
+    let metadata = storage::get(id)?.ok_or_else(not_found)?;
+    let (data, data_stored) = read_data(id)?;
+    let chunks_stored: usize = match &data {
+        Data::Chunked(manifest) => (0..manifest.chunks.len())
+            .map(|index| storage::get(&chunk_key(id, index)).ok().flatten().map_or(0, |c| c.len()))
+            .sum(),
+        Data::Single(_) => 0,
+    };
+    let stored = metadata.chars().count() + data_stored + chunks_stored;
+    let file = describe(id, &vault::open(&metadata)?, data.size(), stored).ok_or_else(not_found)?;
+    Ok((file, data))
+}
+
+/// Storage keys holding file `id`, for the vault to seal
+pub fn storage_keys(id: &str) -> Vec<String> {
+    let chunks = match read_data(id) {
+        Ok((Data::Chunked(manifest), _)) => manifest.chunks.len(),
+        _ => 0,
+    };
+    let mut keys = vec![id.to_string(), data_key(id)];
+    keys.extend((0..chunks).map(|index| chunk_key(id, index)));
+    keys
+}
+
+/// Number of chunks file `id` is read in
+pub fn chunk_count(id: &str) -> Result<usize> {
+    Ok(read_data(id)?.0.chunks())
+}
+
+fn chunk_of(id: &str, data: &Data, index: usize) -> Result<Vec<u8>> {
+    match data {
+        Data::Chunked(manifest) => {
+            let expected = manifest.chunks.get(index)
+                .ok_or_else(|| Error::new(format!("File {} has {} chunks, not {}", id, manifest.chunks.len(), index + 1)))?;
+            let stored = storage::get(&chunk_key(id, index))?
+                .ok_or_else(|| Error::new(format!("File {} is damaged: chunk {} is missing", id, index)))?;
+            let bytes = decode(id, &vault::open(&stored)?)?;
+            if sha256(&bytes) != *expected {
+                return Err(Error::new(format!("File {} is damaged: chunk {} fails its checksum", id, index)));
+            }
+            Ok(bytes)
+        }
+        Data::Single(base64) if index == 0 => decode(id, base64),
+        Data::Single(_) => Err(Error::new(format!("File {} has 1 chunk, not {}", id, index + 1))),
+    }
+}
+
+/// Chunk `index` of file `id`, checked against its hash
+///
+/// Reading chunk by chunk keeps memory flat for long audio, and a reader
+/// interrupted at chunk n resumes there.
+pub fn read_chunk(id: &str, index: usize) -> Result<Vec<u8>> {
+    chunk_of(id, &read_data(id)?.0, index)
+}
+
+/// Every stored file, newest first; entries whose metadata is gone are skipped
+pub fn list() -> Result<Vec<StoredFile>> {
+    let mut files: Vec<StoredFile> = index()?.iter().filter_map(|id| load(id).ok()).map(|(file, _)| file).collect();
+    files.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(files)
+}
+
+/// File `id`'s listing and bytes, checked against the file's hash
+pub fn get(id: &str) -> Result<(StoredFile, Vec<u8>)> {
+    let (file, data) = load(id)?;
+    let mut bytes = Vec::with_capacity(file.size);
+    for index in 0..data.chunks() {
+        bytes.extend(chunk_of(id, &data, index)?);
+    }
+    if let Data::Chunked(manifest) = &data {
+        if bytes.len() != manifest.size || sha256(&bytes) != manifest.sha256 {
+            return Err(Error::new(format!("File {} is damaged: its contents fail the checksum", id)));
+        }
+    }
+    Ok((file, bytes))
+}
+
+/// Give file `id` a new download name, keeping its extension
+pub fn rename(id: &str, filename: &str) -> Result<StoredFile> {
+    let name: String = filename.trim()
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    if name.is_empty() {
+        return Err(Error::new("The file name is empty"));
+    }
+    let metadata = storage::get(id)?.ok_or_else(|| Error::new(format!("File not found: {}", id)))?;
+    let mut meta: serde_json::Value = serde_json::from_str(&vault::open(&metadata)?)
+        .map_err(|e| Error::new(format!("File {} is damaged: {}", id, e)))?;
+    let current = meta["filename"].as_str().unwrap_or("");
+    let extension = current.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+    let name = if extension.is_empty() || name.to_lowercase().ends_with(&format!(".{}", extension.to_lowercase())) {
+        name
+    } else {
+        format!("{}.{}", name, extension)
+    };
+    meta["filename"] = serde_json::Value::String(name);
+    storage::set(id, &vault::seal(&meta.to_string())?)?;
+    Ok(load(id)?.0)
+}
+
+/// Delete file `id`, returning whether it existed
+pub fn delete(id: &str) -> Result<bool> {
+    let mut ids = index()?;
+    let listed = ids.iter().any(|i| i == id);
+    let existed = listed || storage::get(id)?.is_some();
+    for key in storage_keys(id) {
+        storage::remove(&key)?;
+    }
+    if listed {
+        ids.retain(|i| i != id);
+        write_index(&ids)?;
+    }
+    Ok(existed)
+}
+
+/// Totals per kind of file
+pub fn summarize(files: &[StoredFile]) -> Vec<Usage> {
+    FileKind::ALL.iter()
+        .map(|&kind| {
+            let of_kind = files.iter().filter(|f| f.kind == kind);
+            Usage {
+                kind,
+                files: of_kind.clone().count(),
+                size: of_kind.clone().map(|f| f.size).sum(),
+                stored: of_kind.map(|f| f.stored).sum(),
+            }
+        })
+        .collect()
+}
+
+/// Space taken by stored files, per kind
+pub fn usage() -> Result<Vec<Usage>> {
+    Ok(summarize(&list()?))
+}
+
+/// Files created before `cutoff`; those with an unreadable date are kept
+pub fn older_than(files: &[StoredFile], cutoff: chrono::DateTime<chrono::Utc>) -> Vec<String> {
+    files.iter()
+        .filter(|f| chrono::DateTime::parse_from_rfc3339(&f.created_at).map(|t| t < cutoff).unwrap_or(false))
+        .map(|f| f.id.clone())
+        .collect()
+}
+
+/// Delete the files created more than `days` days ago, returning their IDs
+pub fn cleanup(days: u32) -> Result<Vec<String>> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
+    let expired = older_than(&list()?, cutoff);
+    for id in &expired {
+        delete(id)?;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pdf(id: &str, created_at: &str) -> StoredFile {
+        let metadata = serde_json::json!({"id": id, "title": "Report", "filename": "Report.pdf", "created_at": created_at});
+        describe(id, &metadata.to_string(), 9, 100).unwrap()
+    }
+
+    #[test]
+    fn test_describe() {
+        let file = pdf("pdf_1", "2026-10-01T10:00:00+00:00");
+        assert_eq!((file.kind, file.filename.as_str(), file.description.as_str(), file.size), (FileKind::Pdf, "Report.pdf", "Report", 9));
+        let audio = describe("audio_2", &serde_json::json!({"text": "x".repeat(100), "filename": "speech.ogg"}).to_string(), 0, 10).unwrap();
+        assert_eq!((audio.description.chars().count(), audio.kind.mime(&audio.filename)), (81, "audio/ogg"));
+        assert!(describe("note_3", "{}", 0, 0).is_none());
+        assert_eq!(decoded_len("YQ=="), 1);
+    }
+
+    #[test]
+    fn test_usage_and_age() {
+        let files = vec![pdf("pdf_1", "2026-09-01T10:00:00+00:00"), pdf("pdf_2", "2026-10-10T10:00:00Z"), pdf("pdf_3", "yesterday")];
+        let usage = summarize(&files);
+        assert_eq!((usage[0].files, usage[0].size, usage[0].stored), (3, 27, 300));
+        assert_eq!(usage[1].files, 0);
+        let cutoff = chrono::DateTime::parse_from_rfc3339("2026-10-01T00:00:00Z").unwrap().to_utc();
+        assert_eq!(older_than(&files, cutoff), vec!["pdf_1".to_string()]);
+    }
+
+    #[test]
+    fn test_data_formats() {
+        let manifest = Manifest { size: 3, sha256: sha256(b"abc"), chunks: vec![sha256(b"abc")] };
+        let Data::Chunked(parsed) = parse_data("pdf_1", serde_json::to_string(&manifest).unwrap()).unwrap() else {
+            panic!("manifest read as base64");
+        };
+        assert_eq!(parsed, manifest);
+        assert_eq!(parsed.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        let legacy = parse_data("pdf_2", "YWJj".to_string()).unwrap();
+        assert_eq!((legacy.size(), legacy.chunks()), (3, 1));
+        assert_eq!(chunk_of("pdf_2", &legacy, 0).unwrap(), b"abc");
+        assert!(chunk_of("pdf_2", &legacy, 1).is_err());
+        assert!(parse_data("pdf_3", "{not json".to_string()).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Filling forms on the embedding page for the `fill_form` tool
+//!
+//! The model maps what it learned in the conversation onto a form as
+//! `{selector: value}`. Every field is found and checked before anything
+//! is written: that it exists, can be filled, and takes the value (a
+//! checkbox a yes or no, a select one of its options). A call is a dry run
+//! unless it says `"dry_run": false`, and answers with what would change,
+//! field by field, for the model to show the user; writing is a page tool
+//! call like the others and waits for the user's approval. A form with any
+//! problem is left as it is, and password, file and hidden inputs are never
+//! filled. The DOM side lives in the browser tools.
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Most fields filled in one call
+pub const MAX_FIELDS: usize = 50;
+
+/// Input types that are never filled
+const REFUSED_TYPES: [&str; 3] = ["password", "file", "hidden"];
+
+/// The kind of control a field is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control {
+    /// A text-like `<input>` or a `<textarea>`
+    Text,
+    /// A checkbox or radio button
+    Check,
+    /// A `<select>`, with the value and text of each option
+    Select(Vec<(String, String)>),
+}
+
+/// A field's planned change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub selector: String,
+    /// `<input#email>` or the like
+    pub label: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// The `fields` argument as (selector, value) pairs, in the order given
+pub fn parse_fields(args: &Value) -> Result<Vec<(String, Value)>> {
+    let fields = args["fields"].as_object()
+        .ok_or_else(|| Error::new("'fields' must map CSS selectors to values"))?;
+    if fields.is_empty() {
+        return Err(Error::new("'fields' is empty: nothing to fill"));
+    }
+    if fields.len() > MAX_FIELDS {
+        return Err(Error::new(format!("{} fields are more than the {} one call fills", fields.len(), MAX_FIELDS)));
+    }
+    Ok(fields.iter().map(|(selector, value)| (selector.clone(), value.clone())).collect())
+}
+
+/// The control an element of `tag` (and, for inputs, `input_type`) is,
+/// unless it is one that is never filled; selects are `Select` with their
+/// options, which the caller reads
+pub fn control(tag: &str, input_type: &str) -> Result<Control> {
+    match tag.to_lowercase().as_str() {
+        "textarea" => Ok(Control::Text),
+        "select" => Ok(Control::Select(Vec::new())),
+        "input" => match input_type.to_lowercase().as_str() {
+            "checkbox" | "radio" => Ok(Control::Check),
+            kind if REFUSED_TYPES.contains(&kind) => Err(Error::new(format!("{} inputs are never filled", kind))),
+            "submit" | "button" | "reset" | "image" => Err(Error::new("is a button, not a field")),
+            _ => Ok(Control::Text),
+        },
+        other => Err(Error::new(format!("is a <{}>, not a form field", other))),
+    }
+}
+
+/// What `value` sets a `control` to: the text, "checked" or "unchecked",
+/// or the value of the option it names (by value or text)
+pub fn new_value(control: &Control, value: &Value) -> Result<String> {
+    match (control, value) {
+        (Control::Text, Value::String(text)) => Ok(text.clone()),
+        (Control::Text, Value::Number(n)) => Ok(n.to_string()),
+        (Control::Check, Value::Bool(on)) => Ok(checked(*on).to_string()),
+        (Control::Check, Value::String(text)) => match text.trim().to_lowercase().as_str() {
+            "true" | "yes" | "on" | "checked" => Ok(checked(true).to_string()),
+            "false" | "no" | "off" | "unchecked" => Ok(checked(false).to_string()),
+            _ => Err(Error::new(format!("takes true or false, not \"{}\"", text))),
+        },
+        (Control::Select(options), Value::String(_) | Value::Number(_)) => {
+            let wanted = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+            options.iter()
+                .find(|(v, _)| *v == wanted)
+                .or_else(|| options.iter().find(|(v, text)| v.eq_ignore_ascii_case(&wanted) || text.trim().eq_ignore_ascii_case(wanted.trim())))
+                .map(|(v, _)| v.clone())
+                .ok_or_else(|| {
+                    let names: Vec<String> = options.iter().map(|(v, text)| match v == text {
+                        true => format!("\"{}\"", v),
+                        false => format!("\"{}\" ({})", v, text.trim()),
+                    }).collect();
+                    Error::new(format!("has no option \"{}\"; the options are {}", wanted, names.join(", ")))
+                })
+        }
+        (Control::Check, _) => Err(Error::new("takes true or false")),
+        _ => Err(Error::new("takes a string or a number")),
+    }
+}
+
+/// How a checkable control's state reads in a preview
+pub fn checked(on: bool) -> &'static str {
+    match on {
+        true => "checked",
+        false => "unchecked",
+    }
+}
+
+/// The answer of a call: the changes, or the problems that kept the form
+/// as it was
+pub fn report(changes: &[Change], problems: &[String], written: bool) -> String {
+    if !problems.is_empty() {
+        return format!(
+            "Nothing was filled, as {} of the {} field(s) can't be:\n- {}",
+            problems.len(), changes.len() + problems.len(), problems.join("\n- ")
+        );
+    }
+    let lines: Vec<String> = changes.iter()
+        .map(|c| match c.from == c.to {
+            true => format!("- {} {}: \"{}\" (unchanged)", c.selector, c.label, c.to),
+            false => format!("- {} {}: \"{}\" -> \"{}\"", c.selector, c.label, c.from, c.to),
+        })
+        .collect();
+    match written {
+        true => format!("Filled {} field(s):\n{}", changes.len(), lines.join("\n")),
+        false => format!(
+            "Dry run, nothing was written. Filling would set:\n{}\n\n\
+            Show the user these values; to write them, call fill_form again with the same fields and \"dry_run\": false, \
+            which waits for their approval.",
+            lines.join("\n")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_values() {
+        assert_eq!(control("INPUT", "email").unwrap(), Control::Text);
+        assert_eq!(control("input", "checkbox").unwrap(), Control::Check);
+        assert_eq!(control("input", "password").unwrap_err().message(), "password inputs are never filled");
+        assert!(control("div", "").unwrap_err().message().contains("not a form field"));
+
+        assert_eq!(new_value(&Control::Text, &json!(42)).unwrap(), "42");
+        assert_eq!(new_value(&Control::Check, &json!("yes")).unwrap(), "checked");
+        assert!(new_value(&Control::Check, &json!("maybe")).is_err());
+        assert!(new_value(&Control::Text, &json!(null)).is_err());
+
+        let country = Control::Select(vec![("de".into(), "Germany".into()), ("tr".into(), "Türkiye".into())]);
+        assert_eq!(new_value(&country, &json!("tr")).unwrap(), "tr");
+        assert_eq!(new_value(&country, &json!("germany")).unwrap(), "de");
+        assert_eq!(new_value(&country, &json!("France")).unwrap_err().message(),
+            "has no option \"France\"; the options are \"de\" (Germany), \"tr\" (Türkiye)");
+    }
+
+    #[test]
+    fn test_fields_and_report() {
+        let fields = parse_fields(&json!({"fields": {"#name": "Ada", "#terms": true}})).unwrap();
+        assert_eq!(fields.len(), 2);
+        assert!(parse_fields(&json!({"fields": {}})).is_err());
+        assert!(parse_fields(&json!({"fields": ["#name"]})).is_err());
+
+        let changes = vec![
+            Change { selector: "#name".into(), label: "<input#name>".into(), from: "".into(), to: "Ada".into() },
+            Change { selector: "#terms".into(), label: "<input#terms>".into(), from: "checked".into(), to: "checked".into() },
+        ];
+        let preview = report(&changes, &[], false);
+        assert!(preview.starts_with("Dry run, nothing was written. Filling would set:\n- #name <input#name>: \"\" -> \"Ada\"\n- #terms <input#terms>: \"checked\" (unchanged)\n"));
+        assert!(report(&changes, &[], true).starts_with("Filled 2 field(s):"));
+        assert_eq!(report(&changes[..1], &["#card: password inputs are never filled".to_string()], false),
+            "Nothing was filled, as 1 of the 2 field(s) can't be:\n- #card: password inputs are never filled");
+    }
+}
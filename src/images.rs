@@ -0,0 +1,216 @@
+//! Images for generated PDFs
+//!
+//! `create_pdf` places the images of its content and `images` argument in
+//! the document. They are fetched here as bytes, through the proxy's
+//! `/proxy` with `response_format: "base64"` (so no text decoding touches
+//! them), or taken from `data:` URIs, then checked and made small enough to
+//! embed with the `image` crate: anything larger than `MAX_DIMENSION` is
+//! resized, and GIF and WebP, which PDFs can't hold, are converted to PNG
+//! or JPEG. Other formats (SVG, HTML) are refused with a message saying so.
+
+use std::io::Cursor;
+
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+use crate::config::proxy_endpoint;
+use crate::error::{Error, Result};
+use crate::platform::{self, HttpRequest};
+
+/// Longest side, in pixels, of an image after preparation
+pub const MAX_DIMENSION: u32 = 1600;
+/// Largest image fetched, in bytes
+pub const MAX_BYTES: usize = 8 * 1024 * 1024;
+/// Longest side of an image that is decoded at all
+const MAX_SOURCE_DIMENSION: u32 = 16_384;
+/// Quality of re-encoded JPEGs
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpeg",
+        }
+    }
+}
+
+/// An image ready to embed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// The bytes at `url`: a `data:` URI, or anything the proxy can fetch
+pub async fn fetch(proxy: &str, url: &str) -> Result<Vec<u8>> {
+    if let Some(data) = url.strip_prefix("data:") {
+        let (meta, payload) = data.split_once(',').ok_or_else(|| Error::new("Malformed data URI"))?;
+        if !meta.ends_with(";base64") {
+            return Err(Error::new("Only base64 data URIs are supported"));
+        }
+        return base64::engine::general_purpose::STANDARD.decode(payload.trim())
+            .map_err(|e| Error::new(format!("Malformed data URI: {}", e)));
+    }
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(Error::new(format!("Cannot fetch '{}': use an http(s) URL or a data URI", url)));
+    }
+
+    let envelope = serde_json::json!({"url": url, "method": "GET", "response_format": "base64"});
+    let response = platform::fetch(HttpRequest::post_json(&proxy_endpoint(proxy, "/proxy"), &envelope)).await?;
+    let reply: serde_json::Value = serde_json::from_str(&response.body)
+        .map_err(|_| Error::new(format!("Proxy error ({}): {}", response.status, response.body.chars().take(200).collect::<String>())))?;
+    if !response.ok() || reply["status"].as_u64().is_some_and(|status| status >= 400) {
+        return Err(Error::new(format!(
+            "Fetching {} failed: {}",
+            url,
+            reply["error"].as_str().map(str::to_string).unwrap_or_else(|| format!("HTTP {}", reply["status"]))
+        )));
+    }
+    if reply["size"].as_u64().unwrap_or(0) as usize > MAX_BYTES {
+        return Err(Error::new(format!("{} is larger than {} MB", url, MAX_BYTES / 1024 / 1024)));
+    }
+    base64::engine::general_purpose::STANDARD.decode(reply["body"].as_str().unwrap_or(""))
+        .map_err(|e| Error::new(format!("Proxy sent a damaged body for {}: {}", url, e)))
+}
+
+/// Check `bytes` and bring them within `max_dimension` pixels as a PNG or
+/// JPEG
+///
+/// PNG and JPEG within bounds are embedded as they are. Anything larger,
+/// and every GIF (its first frame) and WebP, is decoded, resized and
+/// encoded again: as PNG when it has transparency, as JPEG otherwise.
+pub fn prepare(bytes: Vec<u8>, max_dimension: u32) -> Result<Image> {
+    let format = match image::guess_format(&bytes) {
+        Ok(image::ImageFormat::Png) => Some(Format::Png),
+        Ok(image::ImageFormat::Jpeg) => Some(Format::Jpeg),
+        Ok(image::ImageFormat::Gif | image::ImageFormat::WebP) => None,
+        _ => {
+            let kind = if bytes.iter().take(256).any(|&b| b == b'<') { "SVG or HTML" } else { "an unknown format" };
+            return Err(Error::new(format!("The image is {}: only PNG, JPEG, GIF and WebP can be embedded", kind)));
+        }
+    };
+    let (width, height) = reader(&bytes)?.into_dimensions().map_err(damaged)?;
+    if let Some(format) = format.filter(|_| width.max(height) <= max_dimension) {
+        return Ok(Image { format, width, height, bytes });
+    }
+
+    let mut decoded = reader(&bytes)?.decode().map_err(damaged)?;
+    if width.max(height) > max_dimension {
+        decoded = decoded.resize(max_dimension, max_dimension, FilterType::Triangle);
+    }
+    encode(decoded)
+}
+
+/// A reader of `bytes` bounded so a small file can't claim a huge canvas
+fn reader(bytes: &[u8]) -> Result<image::ImageReader<Cursor<&[u8]>>> {
+    let mut reader = image::ImageReader::new(Cursor::new(bytes)).with_guessed_format()
+        .map_err(|e| Error::new(format!("Unreadable image: {}", e)))?;
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    reader.limits(limits);
+    Ok(reader)
+}
+
+fn damaged(e: image::ImageError) -> Error {
+    Error::new(format!("Damaged image: {}", e))
+}
+
+/// PNG when any pixel is see-through, JPEG otherwise
+fn encode(image: DynamicImage) -> Result<Image> {
+    let (width, height) = (image.width(), image.height());
+    let transparent = image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p[3] < 255);
+    let mut bytes = Vec::new();
+    let format = if transparent {
+        image.to_rgba8().write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .map_err(|e| Error::new(format!("Encoding the image failed: {}", e)))?;
+        Format::Png
+    } else {
+        JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).encode_image(&image.to_rgb8())
+            .map_err(|e| Error::new(format!("Encoding the image failed: {}", e)))?;
+        Format::Jpeg
+    };
+    Ok(Image { format, width, height, bytes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `image` encoded as `format`
+    fn encoded(image: DynamicImage, format: image::ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+        bytes
+    }
+
+    /// A 40x20 image, left half red and right half half-transparent blue
+    fn halves() -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(40, 20, |x, _| match x < 20 {
+            true => image::Rgba([255, 0, 0, 255]),
+            false => image::Rgba([0, 0, 255, 128]),
+        }))
+    }
+
+    #[test]
+    fn test_png_downscaled() {
+        let png = encoded(halves(), image::ImageFormat::Png);
+        let small = prepare(png.clone(), 10).unwrap();
+        assert_eq!((small.format, small.width, small.height), (Format::Png, 10, 5));
+        let decoded = image::load_from_memory(&small.bytes).unwrap().to_rgba8();
+        assert_eq!((decoded.get_pixel(0, 0).0, decoded.get_pixel(9, 4).0), ([255, 0, 0, 255], [0, 0, 255, 128]));
+        // Within bounds: the same bytes
+        assert_eq!(prepare(png.clone(), 40).unwrap().bytes, png);
+    }
+
+    #[test]
+    fn test_large_jpeg_comes_out_smaller() {
+        let photo = DynamicImage::ImageRgb8(image::RgbImage::from_fn(1200, 800, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])));
+        let jpeg = encoded(photo, image::ImageFormat::Jpeg);
+        let small = prepare(jpeg.clone(), 300).unwrap();
+        assert_eq!((small.format, small.width, small.height), (Format::Jpeg, 300, 200));
+        assert!(small.bytes.len() < jpeg.len() / 4, "{} bytes from {}", small.bytes.len(), jpeg.len());
+        assert_eq!(image::guess_format(&small.bytes).unwrap(), image::ImageFormat::Jpeg);
+        assert_eq!(prepare(jpeg.clone(), 1600).unwrap().bytes, jpeg);
+    }
+
+    #[test]
+    fn test_gif_and_webp_converted() {
+        let opaque = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(64, 32, image::Rgba([0, 128, 0, 255])));
+        let gif = prepare(encoded(opaque, image::ImageFormat::Gif), 16).unwrap();
+        assert_eq!((gif.format, gif.width, gif.height), (Format::Jpeg, 16, 8));
+        let webp = prepare(encoded(halves(), image::ImageFormat::WebP), 100).unwrap();
+        assert_eq!((webp.format, webp.width, webp.height), (Format::Png, 40, 20), "transparency kept, size too");
+
+        assert!(prepare(b"<svg xmlns=...>".to_vec(), 100).unwrap_err().to_string().contains("SVG"));
+        assert!(prepare(b"\x89PNG\r\n\x1a\n".to_vec(), 100).unwrap_err().to_string().contains("Damaged"));
+    }
+
+    #[test]
+    fn test_fetch_through_proxy() {
+        use crate::platform::transport::{self, tests::block_on, MockTransport};
+        use std::rc::Rc;
+
+        let mock = Rc::new(MockTransport::new());
+        mock.respond_json("http://proxy/proxy", &serde_json::json!({"status": 200, "content_type": "image/png", "size": 3, "body": "AAEC"}));
+        mock.respond_json("http://proxy/proxy", &serde_json::json!({"status": 404, "content_type": "text/html", "size": 0, "body": ""}));
+        transport::set_transport(mock.clone());
+        assert_eq!(block_on(fetch("http://proxy", "https://e.com/a.png")).unwrap(), vec![0, 1, 2]);
+        assert!(block_on(fetch("http://proxy", "https://e.com/b.png")).unwrap_err().to_string().contains("HTTP 404"));
+        transport::reset_transport();
+        assert!(mock.requests()[0].body.as_deref().unwrap().contains("\"response_format\":\"base64\""));
+        assert_eq!(block_on(fetch("http://proxy", "data:image/png;base64,AAEC")).unwrap(), vec![0, 1, 2]);
+        assert!(block_on(fetch("http://proxy", "file:///etc/passwd")).is_err());
+    }
+}
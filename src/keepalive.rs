@@ -0,0 +1,195 @@
+//! Keeping the tab awake through long turns
+//!
+//! Browsers throttle background tabs (timers drop to about once a minute)
+//! and a sleeping screen can freeze the page, which stalls a multi-minute
+//! research or scan turn. With `keep_awake` on, a running turn holds a
+//! `Hold`. The first hold asks for a screen wake lock, asks again whenever
+//! the tab becomes visible (browsers release it on hide), and starts a Web
+//! Worker whose heartbeat keeps the page's event loop busy. Dropping the
+//! last hold releases both. Either part may be missing, e.g. no Wake Lock
+//! API or a CSP without `worker-src blob:`; the turn then runs as before.
+
+use std::cell::RefCell;
+
+use js_sys::{Array, Function, Promise, Reflect};
+use serde::Serialize;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, Url, Worker};
+
+use crate::logger;
+
+/// Milliseconds between worker heartbeats
+const HEARTBEAT_MS: u32 = 2000;
+
+#[derive(Default)]
+struct State {
+    holds: usize,
+    /// The `WakeLockSentinel` while the lock is held
+    sentinel: Option<JsValue>,
+    worker: Option<(Worker, String)>,
+    ticks: u64,
+    on_tick: Option<Closure<dyn FnMut()>>,
+    on_visible: Option<Closure<dyn FnMut()>>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+/// What is currently keeping the tab awake
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub active: bool,
+    pub wake_lock: bool,
+    pub heartbeat: bool,
+    /// Heartbeats received since the first hold
+    pub ticks: u64,
+}
+
+/// Keeps the tab awake until dropped
+pub struct Hold(());
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        let last = STATE.with(|s| {
+            let mut state = s.borrow_mut();
+            state.holds = state.holds.saturating_sub(1);
+            state.holds == 0
+        });
+        if last {
+            stop();
+        }
+    }
+}
+
+/// Keep the tab awake until the returned `Hold` is dropped
+pub fn hold() -> Hold {
+    let first = STATE.with(|s| {
+        let mut state = s.borrow_mut();
+        state.holds += 1;
+        state.holds == 1
+    });
+    if first {
+        start();
+    }
+    Hold(())
+}
+
+/// Whether a hold is active and what it managed to start
+pub fn status() -> Status {
+    STATE.with(|s| {
+        let state = s.borrow();
+        Status {
+            active: state.holds > 0,
+            wake_lock: state.sentinel.as_ref().is_some_and(|sentinel| !released(sentinel)),
+            heartbeat: state.worker.is_some(),
+            ticks: state.ticks,
+        }
+    })
+}
+
+fn start() {
+    STATE.with(|s| s.borrow_mut().ticks = 0);
+    request_wake_lock();
+    match start_worker() {
+        Ok(worker) => STATE.with(|s| s.borrow_mut().worker = Some(worker)),
+        Err(e) => logger::info("keepalive", &format!("No heartbeat worker: {:?}", e)),
+    }
+    if let Some(document) = web_sys::window().and_then(|w| w.document()) {
+        let on_visible = Closure::<dyn FnMut()>::new(|| {
+            let hidden = web_sys::window().and_then(|w| w.document()).is_none_or(|d| d.hidden());
+            let held = STATE.with(|s| s.borrow().sentinel.as_ref().is_some_and(|sentinel| !released(sentinel)));
+            if !hidden && !held {
+                request_wake_lock();
+            }
+        });
+        let _ = document.add_event_listener_with_callback("visibilitychange", on_visible.as_ref().unchecked_ref());
+        STATE.with(|s| s.borrow_mut().on_visible = Some(on_visible));
+    }
+}
+
+fn stop() {
+    let (sentinel, worker, on_tick, on_visible) = STATE.with(|s| {
+        let mut state = s.borrow_mut();
+        (state.sentinel.take(), state.worker.take(), state.on_tick.take(), state.on_visible.take())
+    });
+    if let Some(sentinel) = sentinel {
+        release(&sentinel);
+    }
+    if let Some((worker, url)) = worker {
+        worker.terminate();
+        worker.set_onmessage(None);
+        let _ = Url::revoke_object_url(&url);
+    }
+    drop(on_tick);
+    if let (Some(on_visible), Some(document)) = (on_visible, web_sys::window().and_then(|w| w.document())) {
+        let _ = document.remove_event_listener_with_callback("visibilitychange", on_visible.as_ref().unchecked_ref());
+    }
+}
+
+/// `navigator.wakeLock.request("screen")`, read through Reflect because
+/// web-sys only has the Wake Lock API behind its unstable flag
+fn request_wake_lock() {
+    let Some(window) = web_sys::window() else { return };
+    let wake_lock = Reflect::get(&window.navigator(), &JsValue::from_str("wakeLock")).unwrap_or(JsValue::UNDEFINED);
+    let Ok(request) = Reflect::get(&wake_lock, &JsValue::from_str("request")).and_then(|f| f.dyn_into::<Function>()) else {
+        logger::info("keepalive", "Wake Lock API not available");
+        return;
+    };
+    let Ok(promise) = request.call1(&wake_lock, &JsValue::from_str("screen")).and_then(|p| p.dyn_into::<Promise>()) else {
+        return;
+    };
+    wasm_bindgen_futures::spawn_local(async move {
+        match JsFuture::from(promise).await {
+            Ok(sentinel) => {
+                // The turn may have ended while the request was pending
+                let stale = STATE.with(|s| {
+                    let mut state = s.borrow_mut();
+                    if state.holds == 0 {
+                        return Some(sentinel);
+                    }
+                    state.sentinel.replace(sentinel)
+                });
+                if let Some(stale) = stale {
+                    release(&stale);
+                }
+            }
+            // Refused while the tab is hidden; retried when it is shown
+            Err(e) => logger::info("keepalive", &format!("Wake lock refused: {:?}", e)),
+        }
+    });
+}
+
+fn released(sentinel: &JsValue) -> bool {
+    Reflect::get(sentinel, &JsValue::from_str("released")).ok().and_then(|r| r.as_bool()).unwrap_or(true)
+}
+
+fn release(sentinel: &JsValue) {
+    if let Ok(release) = Reflect::get(sentinel, &JsValue::from_str("release")).and_then(|f| f.dyn_into::<Function>()) {
+        let _ = release.call0(sentinel);
+    }
+}
+
+/// A worker posting a message every `HEARTBEAT_MS`; worker timers are not
+/// throttled like the page's, and each message wakes the page
+fn start_worker() -> Result<(Worker, String), JsValue> {
+    let script = format!("setInterval(() => postMessage(0), {});", HEARTBEAT_MS);
+    let options = BlobPropertyBag::new();
+    options.set_type("text/javascript");
+    let blob = Blob::new_with_str_sequence_and_options(&Array::of1(&JsValue::from_str(&script)), &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let worker = match Worker::new(&url) {
+        Ok(worker) => worker,
+        Err(e) => {
+            let _ = Url::revoke_object_url(&url);
+            return Err(e);
+        }
+    };
+    let on_tick = Closure::<dyn FnMut()>::new(|| STATE.with(|s| s.borrow_mut().ticks += 1));
+    worker.set_onmessage(Some(on_tick.as_ref().unchecked_ref()));
+    STATE.with(|s| s.borrow_mut().on_tick = Some(on_tick));
+    Ok((worker, url))
+}
@@ -0,0 +1,194 @@
+//! Leveled logging for claWasm
+//!
+//! Modules log through `error`/`warn`/`info`/`debug` with a short module
+//! tag. Records at or above the configured level go to the console (stderr
+//! natively) and into an in-memory ring buffer the UI can read back with
+//! `getLogs()`. The level comes from `log_level` in the config; `debug`
+//! records are dropped unless it asks for them.
+
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use crate::platform;
+
+/// Records kept in the ring buffer; the oldest are dropped first
+const MAX_RECORDS: usize = 500;
+
+/// Log severity, most severe first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Level> {
+        match name.trim().to_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// One log line
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    /// Unix time in milliseconds
+    pub timestamp: i64,
+    pub level: Level,
+    pub module: String,
+    pub message: String,
+}
+
+/// Which records `records` returns
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogFilter {
+    /// Least severe level to include
+    pub level: Option<Level>,
+    pub module: Option<String>,
+    /// Only the newest `limit` records
+    pub limit: Option<usize>,
+}
+
+thread_local! {
+    static LEVEL: Cell<Level> = const { Cell::new(Level::Info) };
+    static RECORDS: RefCell<VecDeque<LogRecord>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Least severe level that is still logged
+pub fn set_level(level: Level) {
+    LEVEL.with(|l| l.set(level));
+}
+
+pub fn level() -> Level {
+    LEVEL.with(|l| l.get())
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+/// Log `message` under `module` if `level` is enabled
+pub fn log(level: Level, module: &str, message: &str) {
+    if !enabled(level) {
+        return;
+    }
+    platform::log(level, &format!("[{}] {}", module, message));
+    push(LogRecord {
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        level,
+        module: module.to_string(),
+        message: message.to_string(),
+    });
+}
+
+pub fn error(module: &str, message: &str) {
+    log(Level::Error, module, message);
+}
+
+pub fn warn(module: &str, message: &str) {
+    log(Level::Warn, module, message);
+}
+
+pub fn info(module: &str, message: &str) {
+    log(Level::Info, module, message);
+}
+
+pub fn debug(module: &str, message: &str) {
+    log(Level::Debug, module, message);
+}
+
+fn push(record: LogRecord) {
+    RECORDS.with(|records| {
+        let mut records = records.borrow_mut();
+        records.push_back(record);
+        while records.len() > MAX_RECORDS {
+            records.pop_front();
+        }
+    });
+}
+
+/// Buffered records matching a filter, oldest first
+pub fn records(filter: &LogFilter) -> Vec<LogRecord> {
+    let mut matching: Vec<LogRecord> = RECORDS.with(|records| {
+        records.borrow().iter()
+            .filter(|r| filter.level.is_none_or(|level| r.level <= level))
+            .filter(|r| filter.module.as_deref().is_none_or(|module| r.module == module))
+            .cloned()
+            .collect()
+    });
+    if let Some(limit) = filter.limit {
+        let skip = matching.len().saturating_sub(limit);
+        matching.drain(..skip);
+    }
+    matching
+}
+
+/// Empty the ring buffer
+pub fn clear() {
+    RECORDS.with(|records| records.borrow_mut().clear());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: Level, module: &str, message: &str) -> LogRecord {
+        LogRecord { timestamp: 0, level, module: module.to_string(), message: message.to_string() }
+    }
+
+    #[test]
+    fn test_level_gate_and_filters() {
+        clear();
+        set_level(Level::Info);
+        // Below the level: neither printed nor buffered
+        debug("agent", "context trimmed");
+        assert!(records(&LogFilter::default()).is_empty());
+        assert!(enabled(Level::Warn) && !enabled(Level::Debug));
+
+        push(record(Level::Warn, "agent", "incomplete JSON"));
+        push(record(Level::Info, "audit", "rotated"));
+        push(record(Level::Error, "agent", "consolidation failed"));
+
+        let agent = records(&LogFilter { module: Some("agent".to_string()), ..Default::default() });
+        assert_eq!(agent.len(), 2);
+        let warnings = records(&LogFilter { level: Some(Level::Warn), ..Default::default() });
+        assert_eq!(warnings.iter().map(|r| r.message.as_str()).collect::<Vec<_>>(), ["incomplete JSON", "consolidation failed"]);
+        let newest = records(&LogFilter { limit: Some(1), ..Default::default() });
+        assert_eq!(newest[0].level, Level::Error);
+
+        for i in 0..MAX_RECORDS {
+            push(record(Level::Info, "test", &i.to_string()));
+        }
+        let all = records(&LogFilter::default());
+        assert_eq!(all.len(), MAX_RECORDS);
+        assert_eq!(all[0].message, "0");
+        clear();
+    }
+
+    #[test]
+    fn test_level_names() {
+        assert_eq!(Level::parse("WARNING"), Some(Level::Warn));
+        assert_eq!(Level::parse("trace"), None);
+        assert_eq!(serde_json::to_string(&Level::Debug).unwrap(), "\"debug\"");
+        assert!(Level::Error < Level::Debug);
+    }
+}
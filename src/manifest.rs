@@ -0,0 +1,269 @@
+//! Dependency manifest parsing for claWasm
+//!
+//! Reads package.json, Cargo.toml and requirements.txt into a flat list of
+//! dependencies for bulk OSV lookups. Version requirements are reduced to
+//! the lowest version they allow, since that is what an install could
+//! resolve to.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Supported manifest formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManifestKind {
+    PackageJson,
+    CargoToml,
+    Requirements,
+}
+
+impl ManifestKind {
+    /// Pick the format from a filename, falling back to sniffing the content
+    pub fn detect(filename: Option<&str>, content: &str) -> Option<Self> {
+        let name = filename.unwrap_or("").to_lowercase();
+        if name.ends_with("package.json") {
+            return Some(ManifestKind::PackageJson);
+        }
+        if name.ends_with("cargo.toml") {
+            return Some(ManifestKind::CargoToml);
+        }
+        if name.ends_with(".txt") || name.contains("requirements") {
+            return Some(ManifestKind::Requirements);
+        }
+        let trimmed = content.trim_start();
+        if trimmed.starts_with('{') {
+            Some(ManifestKind::PackageJson)
+        } else if content.contains("[dependencies]") || content.contains("[package]") || content.contains("[workspace") {
+            Some(ManifestKind::CargoToml)
+        } else if content.lines().any(|l| l.contains("==") || l.contains(">=")) {
+            Some(ManifestKind::Requirements)
+        } else {
+            None
+        }
+    }
+
+    /// OSV ecosystem name
+    pub fn ecosystem(self) -> &'static str {
+        match self {
+            ManifestKind::PackageJson => "npm",
+            ManifestKind::CargoToml => "crates.io",
+            ManifestKind::Requirements => "PyPI",
+        }
+    }
+}
+
+/// A dependency declared in a manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    /// Concrete version used for the lookup
+    pub version: String,
+    /// Requirement as written in the manifest
+    pub requirement: String,
+    pub dev: bool,
+}
+
+/// Parsed manifest
+#[derive(Debug, Default)]
+pub struct Manifest {
+    pub dependencies: Vec<Dependency>,
+    /// Dependencies without a usable version (git, path, unpinned)
+    pub skipped: Vec<String>,
+}
+
+/// Parse a manifest of the given kind
+pub fn parse(kind: ManifestKind, content: &str) -> Result<Manifest, String> {
+    match kind {
+        ManifestKind::PackageJson => parse_package_json(content),
+        ManifestKind::CargoToml => Ok(parse_cargo_toml(content)),
+        ManifestKind::Requirements => Ok(parse_requirements(content)),
+    }
+}
+
+/// Lowest concrete version allowed by a requirement ("^4.17.1" -> "4.17.1")
+fn base_version(requirement: &str) -> Option<String> {
+    static VERSION: OnceLock<Regex> = OnceLock::new();
+    let version = VERSION.get_or_init(|| Regex::new(r"\d+(?:\.\d+){0,2}(?:-[0-9A-Za-z.]+)?").unwrap());
+    let requirement = requirement.trim();
+    if requirement.contains(':') || requirement.contains('/') {
+        // git+https://, file:, workspace:, npm: aliases, paths
+        return None;
+    }
+    version.find(requirement).map(|m| {
+        let mut v = m.as_str().to_string();
+        // Cargo and npm treat "1.2" as "1.2.0"
+        while v.split('-').next().unwrap_or("").matches('.').count() < 2 {
+            v = match v.split_once('-') {
+                Some((core, pre)) => format!("{}.0-{}", core, pre),
+                None => format!("{}.0", v),
+            };
+        }
+        v
+    })
+}
+
+fn push(manifest: &mut Manifest, name: &str, requirement: &str, dev: bool) {
+    match base_version(requirement) {
+        Some(version) => manifest.dependencies.push(Dependency {
+            name: name.to_string(),
+            version,
+            requirement: requirement.to_string(),
+            dev,
+        }),
+        None => manifest.skipped.push(format!("{} ({})", name, if requirement.is_empty() { "no version" } else { requirement })),
+    }
+}
+
+fn parse_package_json(content: &str) -> Result<Manifest, String> {
+    let json: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid package.json: {}", e))?;
+    let mut manifest = Manifest::default();
+    for (section, dev) in [("dependencies", false), ("devDependencies", true), ("optionalDependencies", false), ("peerDependencies", false)] {
+        for (name, requirement) in json[section].as_object().into_iter().flatten() {
+            push(&mut manifest, name, requirement.as_str().unwrap_or(""), dev);
+        }
+    }
+    Ok(manifest)
+}
+
+/// Minimal Cargo.toml reader covering the dependency table forms Cargo accepts
+fn parse_cargo_toml(content: &str) -> Manifest {
+    static INLINE_VERSION: OnceLock<Regex> = OnceLock::new();
+    let inline_version = INLINE_VERSION.get_or_init(|| Regex::new(r#"version\s*=\s*"([^"]*)""#).unwrap());
+
+    let mut manifest = Manifest::default();
+    // (dev, table-per-dependency name) for the current section
+    let mut section: Option<(bool, Option<String>)> = None;
+    let mut table_version: Option<String> = None;
+
+    let flush = |manifest: &mut Manifest, section: &Option<(bool, Option<String>)>, version: &mut Option<String>| {
+        if let Some((dev, Some(name))) = section {
+            push(manifest, name, version.as_deref().unwrap_or(""), *dev);
+        }
+        *version = None;
+    };
+
+    for line in content.lines() {
+        let line = line.split(" #").next().unwrap_or("").trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            flush(&mut manifest, &section, &mut table_version);
+            let header = line.trim_matches(|c| c == '[' || c == ']').trim();
+            let (table, name) = match header.rsplit_once('.') {
+                Some((table, name)) if !header.ends_with("dependencies") => (table, Some(name.trim_matches('"').to_string())),
+                _ => (header, None),
+            };
+            section = if table.ends_with("dependencies") {
+                Some((table.ends_with("dev-dependencies"), name))
+            } else {
+                None
+            };
+            continue;
+        }
+        let Some((dev, table_name)) = &section else { continue };
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let (key, value) = (key.trim().trim_matches('"'), value.trim());
+        if table_name.is_some() {
+            if key == "version" {
+                table_version = Some(value.trim_matches('"').to_string());
+            }
+            continue;
+        }
+        if value.starts_with('"') {
+            push(&mut manifest, key, value.trim_matches('"'), *dev);
+        } else if value.starts_with('{') {
+            let requirement = inline_version.captures(value).map(|c| c[1].to_string()).unwrap_or_default();
+            if value.contains("workspace = true") || value.contains("workspace=true") {
+                manifest.skipped.push(format!("{} (workspace)", key));
+            } else {
+                push(&mut manifest, key, &requirement, *dev);
+            }
+        }
+    }
+    flush(&mut manifest, &section, &mut table_version);
+    manifest
+}
+
+fn parse_requirements(content: &str) -> Manifest {
+    static REQUIREMENT: OnceLock<Regex> = OnceLock::new();
+    let requirement = REQUIREMENT.get_or_init(|| {
+        Regex::new(r"^([A-Za-z0-9][A-Za-z0-9._-]*)\s*(?:\[[^\]]*\])?\s*(.*)$").unwrap()
+    });
+
+    let mut manifest = Manifest::default();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").split(';').next().unwrap_or("").trim();
+        if line.is_empty() || line.starts_with('-') || line.contains("://") {
+            continue;
+        }
+        let Some(c) = requirement.captures(line) else { continue };
+        let spec = c[2].trim();
+        // Only exact pins and lower bounds say which version gets installed
+        let usable = spec.starts_with("==") || spec.starts_with(">=") || spec.starts_with("~=");
+        push(&mut manifest, &c[1], if usable { spec } else { "" }, false);
+    }
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(ManifestKind::detect(Some("web/package.json"), ""), Some(ManifestKind::PackageJson));
+        assert_eq!(ManifestKind::detect(None, "[package]\nname = \"x\""), Some(ManifestKind::CargoToml));
+        assert_eq!(ManifestKind::detect(None, "django==3.2.1\n"), Some(ManifestKind::Requirements));
+        assert_eq!(ManifestKind::detect(None, "hello"), None);
+    }
+
+    #[test]
+    fn test_parse_package_json() {
+        let manifest = parse(ManifestKind::PackageJson, r#"{
+            "dependencies": {"lodash": "^4.17.15", "left-pad": "git+https://github.com/x/left-pad.git", "react": "16"},
+            "devDependencies": {"jest": "~26.6.3"}
+        }"#).unwrap();
+        let versions: Vec<(&str, &str, bool)> = manifest.dependencies.iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.dev))
+            .collect();
+        assert_eq!(versions, vec![("lodash", "4.17.15", false), ("react", "16.0.0", false), ("jest", "26.6.3", true)]);
+        assert_eq!(manifest.skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_cargo_toml() {
+        let manifest = parse(ManifestKind::CargoToml, r#"
+[package]
+name = "demo"
+version = "9.9.9"
+
+[dependencies]
+serde = { version = "1.0.100", features = ["derive"] }
+regex = "1" # comment
+local = { path = "../local" }
+
+[dependencies.tokio]
+version = "1.2"
+features = ["full"]
+
+[dev-dependencies]
+wasm-bindgen-test = "0.3.13"
+"#).unwrap();
+        let versions: Vec<(&str, &str, bool)> = manifest.dependencies.iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.dev))
+            .collect();
+        assert_eq!(versions, vec![
+            ("serde", "1.0.100", false), ("regex", "1.0.0", false),
+            ("tokio", "1.2.0", false), ("wasm-bindgen-test", "0.3.13", true),
+        ]);
+        assert_eq!(manifest.skipped, vec!["local (no version)"]);
+    }
+
+    #[test]
+    fn test_parse_requirements() {
+        let manifest = parse(ManifestKind::Requirements, "# deps\nDjango==3.2.1\nrequests[socks]>=2.20 ; python_version > '3'\nflask\n-r other.txt\n").unwrap();
+        assert_eq!(manifest.dependencies.iter().map(|d| d.version.as_str()).collect::<Vec<_>>(), vec!["3.2.1", "2.20.0"]);
+        assert_eq!(manifest.skipped, vec!["flask (no version)"]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Markdown rendering, on pulldown-cmark
+//!
+//! Model output is CommonMark with the GitHub extensions (tables,
+//! strikethrough, task lists, footnotes). `to_html` renders it for email
+//! and the chat view; raw HTML in it goes through the sanitizer, so harmless
+//! tags render and anything able to run script is dropped. `to_blocks` flattens it for layouts without
+//! HTML (the PDF export, `create_pdf`): headings, paragraphs, list items
+//! with their depth, quotes, code, tables, rules and images, with inline
+//! markup reduced to plain text and links keeping their URL.
+
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use crate::sanitize;
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES
+}
+
+/// `markdown` as an HTML fragment, passed through `sanitize::clean`
+pub fn to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(markdown, options()));
+    sanitize::clean(&html)
+}
+
+/// A piece of a document laid out on its own
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading { level: u8, text: String },
+    Paragraph(String),
+    /// A list item; `depth` is 0 at the top level and `marker` is "•" or
+    /// "3.", empty for text continuing an item after a nested list
+    Item { depth: usize, marker: String, text: String },
+    Quote(String),
+    Code { language: String, lines: Vec<String> },
+    Table { header: Vec<String>, rows: Vec<Vec<String>> },
+    Rule,
+    /// An image, placed after the block it appeared in
+    Image { alt: String, url: String },
+}
+
+/// `markdown` as plain-text blocks
+pub fn to_blocks(markdown: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    // Next number of each open list, None for bullets
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut item: Option<String> = None;
+    let mut quotes = 0;
+    let mut code: Option<String> = None;
+    let mut cells: Vec<String> = Vec::new();
+    let mut table: Option<(Vec<String>, Vec<Vec<String>>)> = None;
+    // Where the text of each open link or image starts, and its target
+    let mut links: Vec<(usize, String)> = Vec::new();
+    let mut images: Vec<Block> = Vec::new();
+
+    let flush_item = |item: &mut Option<String>, text: &mut String, blocks: &mut Vec<Block>, depth: usize| {
+        let body = std::mem::take(text).trim().to_string();
+        let marker = item.take();
+        if !body.is_empty() {
+            blocks.push(Block::Item { depth, marker: marker.unwrap_or_default(), text: body });
+        }
+    };
+
+    for event in Parser::new_ext(markdown, options()) {
+        let depth = lists.len().saturating_sub(1);
+        match event {
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() {
+                    flush_item(&mut item, &mut text, &mut blocks, depth);
+                }
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                flush_item(&mut item, &mut text, &mut blocks, depth);
+                item = Some(match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}.", *number - 1)
+                    }
+                    _ => "•".to_string(),
+                });
+            }
+            Event::End(TagEnd::Item) => flush_item(&mut item, &mut text, &mut blocks, depth),
+            Event::TaskListMarker(done) => {
+                item = Some(if done { "☑" } else { "☐" }.to_string());
+            }
+            Event::Start(Tag::BlockQuote(_)) => quotes += 1,
+            Event::End(TagEnd::BlockQuote(_)) => quotes -= 1,
+            Event::End(TagEnd::Paragraph) => {
+                if !lists.is_empty() {
+                    // Paragraphs of a loose item run together
+                    text.push(' ');
+                } else if !text.trim().is_empty() {
+                    let body = std::mem::take(&mut text).trim().to_string();
+                    blocks.push(if quotes > 0 { Block::Quote(body) } else { Block::Paragraph(body) });
+                }
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                let level = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    HeadingLevel::H3 => 3,
+                    HeadingLevel::H4 => 4,
+                    HeadingLevel::H5 => 5,
+                    HeadingLevel::H6 => 6,
+                };
+                blocks.push(Block::Heading { level, text: std::mem::take(&mut text).trim().to_string() });
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                if !lists.is_empty() {
+                    flush_item(&mut item, &mut text, &mut blocks, depth);
+                }
+                code = Some(match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                });
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                let body = std::mem::take(&mut text);
+                blocks.push(Block::Code {
+                    language: code.take().unwrap_or_default(),
+                    lines: body.strip_suffix('\n').unwrap_or(&body).lines().map(str::to_string).collect(),
+                });
+            }
+            Event::Start(Tag::Table(_)) => table = Some((Vec::new(), Vec::new())),
+            Event::End(TagEnd::TableCell) => cells.push(std::mem::take(&mut text).trim().to_string()),
+            Event::End(TagEnd::TableHead) => {
+                if let Some((header, _)) = table.as_mut() {
+                    *header = std::mem::take(&mut cells);
+                }
+            }
+            Event::End(TagEnd::TableRow) => {
+                if let Some((_, rows)) = table.as_mut() {
+                    rows.push(std::mem::take(&mut cells));
+                }
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some((header, rows)) = table.take() {
+                    blocks.push(Block::Table { header, rows });
+                }
+            }
+            Event::Start(Tag::Link { dest_url, .. }) | Event::Start(Tag::Image { dest_url, .. }) => {
+                links.push((text.len(), dest_url.to_string()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((start, url)) = links.pop() {
+                    let alt = text[start..].trim().to_string();
+                    text.truncate(start);
+                    images.push(Block::Image { alt, url });
+                }
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((start, url)) = links.pop() {
+                    let label = text[start..].trim();
+                    if label.is_empty() {
+                        text.truncate(start);
+                        text.push_str(&url);
+                    } else if label != url && !url.starts_with('#') {
+                        text.push_str(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::Rule => blocks.push(Block::Rule),
+            Event::Text(t) | Event::Code(t) | Event::Html(t) | Event::InlineHtml(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::FootnoteReference(name) => text.push_str(&format!("[^{}]", name)),
+            _ => {}
+        }
+        // Images wait for the end of the block holding them
+        if !images.is_empty() && text.trim().is_empty() && table.is_none() && code.is_none() {
+            blocks.append(&mut images);
+        }
+    }
+    if !text.trim().is_empty() {
+        blocks.push(Block::Paragraph(text.trim().to_string()));
+    }
+    blocks.append(&mut images);
+    blocks
+}
+
+/// `markdown` as plain text: "#" headings, indented list items, "|"
+/// separated table rows, code as it is and images as `![alt](url)` lines
+pub fn to_text(markdown: &str) -> String {
+    let blocks: Vec<String> = to_blocks(markdown).into_iter()
+        .map(|block| match block {
+            Block::Heading { level, text } => format!("{} {}", "#".repeat(level.min(3) as usize), text),
+            Block::Paragraph(text) | Block::Quote(text) => text,
+            Block::Item { depth, marker, text } => {
+                format!("{}{}{}", "  ".repeat(depth), if marker.is_empty() { String::new() } else { format!("{} ", marker) }, text)
+            }
+            Block::Code { lines, .. } => lines.join("\n"),
+            Block::Table { header, rows } => std::iter::once(header).chain(rows)
+                .map(|row| row.join(" | "))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Rule => "———".to_string(),
+            Block::Image { alt, url } => format!("![{}]({})", alt, url),
+        })
+        .collect();
+    blocks.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        let html = to_html("- one\n- two\n  1. nested\n\n| a | b |\n|---|---|\n| *x* | ~~y~~ |\n\n[doc](https://e.com) ![cat](c.png)");
+        assert!(html.contains("<ul>\n<li>one</li>\n<li>two\n<ol>\n<li>nested</li>\n</ol>\n</li>\n</ul>"), "{}", html);
+        assert!(html.contains("<td><em>x</em></td>") && html.contains("<del>y</del>"));
+        assert!(html.contains("<a href=\"https://e.com\" rel=\"noopener noreferrer\">doc</a> <img src=\"c.png\" alt=\"cat\">"));
+    }
+
+    #[test]
+    fn test_to_html_is_safe() {
+        let html = to_html("<script>alert(1)</script>\n\nhi <b onclick=x>there</b> [x](javascript:alert(1))");
+        assert!(!html.contains("script") && !html.contains("onclick"), "{}", html);
+        assert!(html.contains("hi <b>there</b> <a rel=\"noopener noreferrer\">x</a>"), "{}", html);
+    }
+
+    #[test]
+    fn test_to_blocks() {
+        let markdown = "# Plan\nFirst **bold** line\ncontinues [here](https://a.io).\n\n- one\n  - inner\n- [x] done\n\n3. third\n4. fourth\n\n> quoted\n\n```rust\nfn main() {}\n```\n| a | b |\n|---|---|\n| 1 | 2 |\n\n---";
+        assert_eq!(to_blocks(markdown), vec![
+            Block::Heading { level: 1, text: "Plan".to_string() },
+            Block::Paragraph("First bold line continues here (https://a.io).".to_string()),
+            Block::Item { depth: 0, marker: "•".to_string(), text: "one".to_string() },
+            Block::Item { depth: 1, marker: "•".to_string(), text: "inner".to_string() },
+            Block::Item { depth: 0, marker: "☑".to_string(), text: "done".to_string() },
+            Block::Item { depth: 0, marker: "3.".to_string(), text: "third".to_string() },
+            Block::Item { depth: 0, marker: "4.".to_string(), text: "fourth".to_string() },
+            Block::Quote("quoted".to_string()),
+            Block::Code { language: "rust".to_string(), lines: vec!["fn main() {}".to_string()] },
+            Block::Table { header: vec!["a".to_string(), "b".to_string()], rows: vec![vec!["1".to_string(), "2".to_string()]] },
+            Block::Rule,
+        ]);
+        assert_eq!(to_text("## T\n\n- a\n  - b"), "## T\n\n• a\n\n  • b");
+    }
+
+    #[test]
+    fn test_images_follow_their_block() {
+        assert_eq!(to_blocks("Intro ![chart](https://e.com/c.png) below\n\n![logo](l.png)\n\n- item ![x](x.jpg)"), vec![
+            Block::Paragraph("Intro  below".to_string()),
+            Block::Image { alt: "chart".to_string(), url: "https://e.com/c.png".to_string() },
+            Block::Image { alt: "logo".to_string(), url: "l.png".to_string() },
+            Block::Item { depth: 0, marker: "•".to_string(), text: "item".to_string() },
+            Block::Image { alt: "x".to_string(), url: "x.jpg".to_string() },
+        ]);
+        assert_eq!(to_text("![a b](u.png)"), "![a b](u.png)");
+    }
+}
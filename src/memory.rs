@@ -3,24 +3,80 @@
 //! Inspired by ZeroClaw's memory system with hybrid search capabilities.
 
 use serde::{Deserialize, Serialize};
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{Headers, Request, RequestInit, Response};
-use wasm_bindgen::JsCast;
-use js_sys::{Array, Object, Reflect};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::chat::Message;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::platform::{self, storage, HttpRequest};
+use crate::providers::ChatModel;
+use crate::reasoning;
+use crate::security::SecurityConfig;
+use crate::vault;
 
 /// Memory entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryEntry {
     pub id: String,
     pub content: String,
+    #[serde(default)]
     pub embedding: Option<Vec<f32>>,
+    #[serde(default)]
     pub metadata: serde_json::Value,
     pub created_at: i64,
+    #[serde(default)]
     pub accessed_at: i64,
+    #[serde(default)]
     pub access_count: u32,
 }
 
+impl MemoryEntry {
+    /// The namespace in `metadata` (e.g. "document"); entries in one are
+    /// kept apart from the assistant's own memories: not recalled, counted
+    /// against `max_entries` or consolidated
+    pub fn namespace(&self) -> Option<&str> {
+        self.metadata.get("namespace").and_then(|n| n.as_str())
+    }
+}
+
+/// Current version of the memory export format
+pub const MEMORY_EXPORT_VERSION: u32 = 1;
+
+/// Portable memory export (backup / transfer between browsers)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryExport {
+    pub version: u32,
+    pub exported_at: i64,
+    pub entries: Vec<MemoryEntry>,
+}
+
+/// Result of a memory import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportStats {
+    /// Entries added to memory
+    pub imported: usize,
+    /// Entries skipped as duplicates
+    pub skipped: usize,
+    /// Entries whose ID clashed with different content and got a new ID
+    pub renamed: usize,
+}
+
+/// Memory statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub count: usize,
+    pub max_entries: usize,
+    /// Entries with a stored embedding
+    pub with_embeddings: usize,
+    /// Summary entries produced by consolidation
+    pub consolidated: usize,
+    pub total_chars: usize,
+    pub oldest: Option<i64>,
+    pub newest: Option<i64>,
+    pub last_consolidation: Option<i64>,
+}
+
 /// Memory search result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemorySearchResult {
@@ -39,6 +95,7 @@ pub enum MemoryBackend {
 
 /// Memory configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MemoryConfig {
     pub backend: MemoryBackend,
     pub auto_save: bool,
@@ -46,6 +103,16 @@ pub struct MemoryConfig {
     pub vector_weight: f32,
     pub keyword_weight: f32,
     pub max_entries: usize,
+    /// Entries not accessed for this long become consolidation candidates
+    pub ttl_secs: Option<i64>,
+    /// Half-life of an entry's importance since its last access
+    pub decay_half_life_secs: i64,
+    /// Expired entries below this importance are consolidated
+    pub min_importance: f32,
+    /// Maximum entries folded into one summary
+    pub consolidation_batch: usize,
+    /// Minimum time between automatic consolidation runs
+    pub consolidation_interval_secs: i64,
 }
 
 impl Default for MemoryConfig {
@@ -57,6 +124,11 @@ impl Default for MemoryConfig {
             vector_weight: 0.7,
             keyword_weight: 0.3,
             max_entries: 1000,
+            ttl_secs: Some(90 * 24 * 3600),
+            decay_half_life_secs: 30 * 24 * 3600,
+            min_importance: 0.5,
+            consolidation_batch: 10,
+            consolidation_interval_secs: 3600,
         }
     }
 }
@@ -77,6 +149,7 @@ pub struct MemorySystem {
     config: MemoryConfig,
     entries: Vec<MemoryEntry>,
     api_key: Option<String>,
+    last_consolidation: i64,
 }
 
 impl MemorySystem {
@@ -86,6 +159,7 @@ impl MemorySystem {
             config,
             entries: Vec::new(),
             api_key: None,
+            last_consolidation: 0,
         }
     }
 
@@ -95,17 +169,16 @@ impl MemorySystem {
     }
 
     /// Save a memory entry
-    pub async fn save(&mut self, content: &str, metadata: serde_json::Value) -> Result<String, JsValue> {
+    pub async fn save(&mut self, content: &str, metadata: serde_json::Value) -> Result<String> {
+        let embedding = self.embedder().embed(content).await.ok();
+        self.insert(content, embedding, metadata)
+    }
+
+    /// Store a memory entry with an already computed embedding
+    pub fn insert(&mut self, content: &str, embedding: Option<Vec<f32>>, metadata: serde_json::Value) -> Result<String> {
         let id = generate_id();
         let now = chrono::Utc::now().timestamp();
         
-        // Get embedding
-        let embedding = if self.config.embedding_provider != EmbeddingProvider::None {
-            self.get_embedding(content).await.ok()
-        } else {
-            None
-        };
-        
         let entry = MemoryEntry {
             id: id.clone(),
             content: content.to_string(),
@@ -116,52 +189,63 @@ impl MemorySystem {
             access_count: 0,
         };
         
-        // Check max entries
-        if self.entries.len() >= self.config.max_entries {
-            // Remove oldest accessed entry
-            self.entries.sort_by_key(|e| e.accessed_at);
-            self.entries.remove(0);
+        self.ensure_loaded()?;
+        
+        // Make room by consolidating rather than dropping entries
+        if entry.namespace().is_none() && self.general_count() >= self.config.max_entries {
+            self.enforce_capacity(self.config.max_entries.saturating_sub(1))?;
         }
         
         self.entries.push(entry.clone());
         
         // Persist to IndexedDB
         if self.config.backend == MemoryBackend::IndexedDB {
-            self.persist_to_indexeddb(&entry).await?;
+            self.write_entry(&entry)?;
         }
         
         Ok(id)
     }
 
     /// Recall memories by search query
-    pub async fn recall(&mut self, query: &str, limit: usize) -> Result<Vec<MemorySearchResult>, JsValue> {
-        if self.entries.is_empty() {
-            // Load from IndexedDB
-            self.load_from_indexeddb().await?;
-        }
-        
-        let query_embedding = if self.config.embedding_provider != EmbeddingProvider::None {
-            self.get_embedding(query).await.ok()
-        } else {
-            None
-        };
-        
+    pub async fn recall(&mut self, query: &str, limit: usize) -> Result<Vec<MemorySearchResult>> {
+        self.ensure_loaded()?;
+        let query_embedding = self.embedder().embed(query).await.ok();
+        Ok(self.rank(query, query_embedding.as_deref(), limit))
+    }
+
+    /// Score entries against a query and its embedding, updating access stats
+    pub fn rank(&mut self, query: &str, query_embedding: Option<&[f32]>, limit: usize) -> Vec<MemorySearchResult> {
+        self.rank_where(query, query_embedding, limit, None, |entry| entry.namespace().is_none())
+    }
+
+    /// `rank` over the entries `include` accepts, namespaced or not, with
+    /// (vector, keyword) `weights` in place of the configured ones if given
+    pub fn rank_where(
+        &mut self,
+        query: &str,
+        query_embedding: Option<&[f32]>,
+        limit: usize,
+        weights: Option<(f32, f32)>,
+        include: impl Fn(&MemoryEntry) -> bool,
+    ) -> Vec<MemorySearchResult> {
         let query_keywords = extract_keywords(query);
+        let (vector_weight, keyword_weight) = weights.unwrap_or((self.config.vector_weight, self.config.keyword_weight));
         
         let mut results: Vec<MemorySearchResult> = self.entries.iter()
+            .filter(|entry| include(entry))
             .map(|entry| {
                 let mut score = 0.0;
                 
                 // Vector similarity
-                if let (Some(q_emb), Some(e_emb)) = (&query_embedding, &entry.embedding) {
+                if let (Some(q_emb), Some(e_emb)) = (query_embedding, &entry.embedding) {
                     let vector_score = cosine_similarity(q_emb, e_emb);
-                    score += vector_score * self.config.vector_weight;
+                    score += vector_score * vector_weight;
                 }
                 
                 // Keyword matching
                 let entry_keywords = extract_keywords(&entry.content);
                 let keyword_score = jaccard_similarity(&query_keywords, &entry_keywords);
-                score += keyword_score * self.config.keyword_weight;
+                score += keyword_score * keyword_weight;
                 
                 // Boost by access count
                 score *= 1.0 + (entry.access_count as f32 * 0.01);
@@ -184,191 +268,538 @@ impl MemorySystem {
             }
         }
         
-        Ok(results.into_iter().take(limit).collect())
+        results.into_iter().take(limit).collect()
     }
 
-    /// Get embedding from provider
-    async fn get_embedding(&self, text: &str) -> Result<Vec<f32>, JsValue> {
-        match self.config.embedding_provider {
-            EmbeddingProvider::OpenAI => self.get_openai_embedding(text).await,
-            EmbeddingProvider::Local => self.get_local_embedding(text),
-            EmbeddingProvider::None => Err(JsValue::from_str("No embedding provider configured")),
+    /// Embedding client for the configured provider
+    ///
+    /// Owns its settings, so it can be awaited without borrowing the memory system.
+    pub fn embedder(&self) -> Embedder {
+        Embedder {
+            provider: self.config.embedding_provider.clone(),
+            api_key: self.api_key.clone(),
         }
     }
 
-    /// Get embedding from OpenAI
-    async fn get_openai_embedding(&self, text: &str) -> Result<Vec<f32>, JsValue> {
-        let api_key = self.api_key.as_ref()
-            .ok_or_else(|| JsValue::from_str("API key not set for embeddings"))?;
+    /// Write a single entry and its index record to storage
+    fn write_entry(&self, entry: &MemoryEntry) -> Result<()> {
+        let key = format!("memory_{}", entry.id);
+        let value = serde_json::to_string(entry)
+            .map_err(|e| Error::new(format!("Serialize error: {}", e)))?;
         
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
+        storage::set(&key, &vault::seal(&value)?)?;
         
-        let headers = Headers::new()?;
-        headers.set("Content-Type", "application/json")?;
-        headers.set("Authorization", &format!("Bearer {}", api_key))?;
+        // Store index
+        let mut ids = read_index();
+        if !ids.contains(&entry.id) {
+            ids.push(entry.id.clone());
+            storage::set("memory_index", &serde_json::to_string(&ids).unwrap())?;
+        }
         
-        let body = serde_json::json!({
-            "input": text,
-            "model": "text-embedding-3-small",
-        });
+        Ok(())
+    }
+
+    /// Read all stored entries into memory
+    fn read_entries(&mut self) -> Result<()> {
+        self.entries.clear();
+        
+        for id in read_index() {
+            let key = format!("memory_{}", id);
+            if let Some(stored) = storage::get(&key).ok().flatten() {
+                let json = vault::open(&stored)?;
+                if let Ok(entry) = serde_json::from_str::<MemoryEntry>(&json) {
+                    self.entries.push(entry);
+                }
+            }
+        }
         
-        let request_init = RequestInit::new();
-        request_init.set_method("POST");
-        request_init.set_headers(headers.as_ref());
-        request_init.set_body(&JsValue::from_str(&serde_json::to_string(&body).unwrap()));
+        Ok(())
+    }
+
+    /// Delete several entries, writing the index once; returns how many existed
+    pub fn delete_many(&mut self, ids: &[String]) -> Result<usize> {
+        self.ensure_loaded()?;
+        let before = self.entries.len();
+        self.entries.retain(|e| !ids.contains(&e.id));
+        let deleted = before - self.entries.len();
+        if self.config.backend == MemoryBackend::IndexedDB {
+            for id in ids {
+                storage::remove(&format!("memory_{}", id))?;
+            }
+            let mut index = read_index();
+            index.retain(|i| !ids.contains(i));
+            storage::set("memory_index", &serde_json::to_string(&index).unwrap())?;
+        }
+        Ok(deleted)
+    }
+
+    /// Delete a memory entry, returns whether it existed
+    pub fn delete(&mut self, id: &str) -> Result<bool> {
+        self.ensure_loaded()?;
+        let existed = self.entries.iter().any(|e| e.id == id);
+        self.remove_entry(id)?;
+        Ok(existed)
+    }
+
+    /// Clear all memories
+    pub fn clear(&mut self) -> Result<()> {
+        self.entries.clear();
+        if self.config.backend != MemoryBackend::IndexedDB {
+            return Ok(());
+        }
         
-        let request = Request::new_with_str_and_init(
-            "https://api.openai.com/v1/embeddings",
-            &request_init,
-        )?;
+        // Remove all memory entries
+        for id in read_index() {
+            storage::remove(&format!("memory_{}", id))?;
+        }
         
-        let response = JsFuture::from(window.fetch_with_request(&request)).await?;
-        let response: Response = response.dyn_into()?;
+        // Clear index
+        storage::remove("memory_index")?;
         
-        if !response.ok() {
-            return Err(JsValue::from_str(&format!("Embedding API error: {}", response.status())));
+        Ok(())
+    }
+
+    /// Summary statistics for a memory browser
+    pub fn stats(&mut self) -> Result<MemoryStats> {
+        self.ensure_loaded()?;
+        Ok(MemoryStats {
+            count: self.entries.len(),
+            max_entries: self.config.max_entries,
+            with_embeddings: self.entries.iter().filter(|e| e.embedding.is_some()).count(),
+            consolidated: self.entries.iter()
+                .filter(|e| e.metadata.get("type").and_then(|t| t.as_str()) == Some("consolidated"))
+                .count(),
+            total_chars: self.entries.iter().map(|e| e.content.chars().count()).sum(),
+            oldest: self.entries.iter().map(|e| e.created_at).min(),
+            newest: self.entries.iter().map(|e| e.created_at).max(),
+            last_consolidation: (self.last_consolidation > 0).then_some(self.last_consolidation),
+        })
+    }
+
+    /// Get all memories
+    pub fn get_all(&self) -> &[MemoryEntry] {
+        &self.entries
+    }
+
+    /// Export all memories as portable, versioned JSON
+    pub fn export_json(&mut self, include_embeddings: bool) -> Result<String> {
+        if self.entries.is_empty() && self.config.backend == MemoryBackend::IndexedDB {
+            self.read_entries()?;
         }
         
-        let json = JsFuture::from(response.json()?).await?;
-        let result: EmbeddingResponse = serde_wasm_bindgen::from_value(json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        let entries = self.entries.iter()
+            .cloned()
+            .map(|mut e| {
+                if !include_embeddings {
+                    e.embedding = None;
+                }
+                e
+            })
+            .collect();
         
-        Ok(result.data[0].embedding.clone())
+        let export = MemoryExport {
+            version: MEMORY_EXPORT_VERSION,
+            exported_at: chrono::Utc::now().timestamp(),
+            entries,
+        };
+        
+        serde_json::to_string(&export)
+            .map_err(|e| Error::new(format!("Serialize error: {}", e)))
     }
 
-    /// Get local embedding (simple hash-based, not real embeddings)
-    fn get_local_embedding(&self, text: &str) -> Result<Vec<f32>, JsValue> {
-        // Simple TF-IDF style local embedding (384 dimensions)
-        let text_lower = text.to_lowercase();
-        let words: Vec<&str> = text_lower.split_whitespace().collect();
-        let mut embedding = vec![0.0f32; 384];
+    /// Import memories from an export, skipping duplicates
+    pub fn import_json(&mut self, json: &str) -> Result<ImportStats> {
+        let export = parse_memory_export(json).map_err(Error::new)?;
         
-        for (i, word) in words.iter().enumerate() {
-            let hash = hash_word(word);
-            let idx = hash % 384;
-            embedding[idx] += 1.0 / (1.0 + i as f32); // Position weighting
+        if self.entries.is_empty() && self.config.backend == MemoryBackend::IndexedDB {
+            self.read_entries()?;
         }
         
-        // Normalize
-        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-        if norm > 0.0 {
-            for e in embedding.iter_mut() {
-                *e /= norm;
+        let before = self.entries.len();
+        let stats = merge_entries(&mut self.entries, export.entries);
+        
+        if self.config.backend == MemoryBackend::IndexedDB {
+            for entry in &self.entries[before..] {
+                self.write_entry(entry)?;
             }
         }
         
-        Ok(embedding)
+        // Imports obey the same cap as saves
+        self.enforce_capacity(self.config.max_entries)?;
+        
+        Ok(stats)
+    }
+
+    /// Drop the in-memory copy of all entries (e.g. when storage is locked)
+    pub fn unload(&mut self) {
+        self.entries.clear();
     }
 
-    /// Persist entry to IndexedDB
-    async fn persist_to_indexeddb(&self, entry: &MemoryEntry) -> Result<(), JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
+    /// Load entries from storage if not loaded yet
+    pub fn ensure_loaded(&mut self) -> Result<()> {
+        if self.entries.is_empty() && self.config.backend == MemoryBackend::IndexedDB {
+            self.read_entries()?;
+        }
+        Ok(())
+    }
+
+    /// Importance of an entry: access count decayed by time since last access
+    pub fn importance(&self, entry: &MemoryEntry, now: i64) -> f32 {
+        entry_importance(entry, now, self.config.decay_half_life_secs)
+    }
+
+    /// Expired, low-importance entries to fold into a summary (at most one batch)
+    pub fn consolidation_candidates(&self, now: i64) -> Vec<MemoryEntry> {
+        let ttl = match self.config.ttl_secs {
+            Some(ttl) => ttl,
+            None => return Vec::new(),
+        };
         
-        // Use localStorage as fallback (IndexedDB requires more complex setup)
-        let storage = window.local_storage()?.ok_or_else(|| JsValue::from_str("No localStorage"))?;
+        let mut candidates: Vec<&MemoryEntry> = self.entries.iter()
+            .filter(|e| e.namespace().is_none())
+            .filter(|e| now - e.accessed_at > ttl)
+            .filter(|e| self.importance(e, now) < self.config.min_importance)
+            .collect();
+        candidates.sort_by_key(|e| e.accessed_at);
         
-        let key = format!("memory_{}", entry.id);
-        let value = serde_json::to_string(entry)
-            .map_err(|e| JsValue::from_str(&format!("Serialize error: {}", e)))?;
+        candidates.into_iter()
+            .take(self.config.consolidation_batch.max(2))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether an automatic consolidation run should happen now
+    pub fn is_consolidation_due(&self, now: i64) -> bool {
+        now - self.last_consolidation >= self.config.consolidation_interval_secs
+            && self.consolidation_candidates(now).len() >= 2
+    }
+
+    /// Replace the given entries with a single summary entry
+    pub fn apply_consolidation(&mut self, ids: &[String], summary: &str) -> Result<String> {
+        let now = chrono::Utc::now().timestamp();
+        let sources: Vec<&MemoryEntry> = self.entries.iter()
+            .filter(|e| ids.contains(&e.id))
+            .collect();
         
-        storage.set_item(&key, &value)?;
+        let access_count = sources.iter().map(|e| e.access_count).sum();
+        let created_at = sources.iter().map(|e| e.created_at).min().unwrap_or(now);
+        let embedding = if self.config.embedding_provider == EmbeddingProvider::Local {
+            Some(local_embedding(summary))
+        } else {
+            None
+        };
         
-        // Store index
-        let mut ids: Vec<String> = storage.get_item("memory_index")
-            .ok()
-            .flatten()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
+        let entry = MemoryEntry {
+            id: generate_id(),
+            content: summary.to_string(),
+            embedding,
+            metadata: serde_json::json!({
+                "type": "consolidated",
+                "source_ids": ids,
+                "consolidated_at": now,
+            }),
+            created_at,
+            accessed_at: now,
+            access_count,
+        };
         
-        if !ids.contains(&entry.id) {
-            ids.push(entry.id.clone());
-            storage.set_item("memory_index", &serde_json::to_string(&ids).unwrap())?;
+        for id in ids {
+            self.remove_entry(id)?;
+        }
+        if self.config.backend == MemoryBackend::IndexedDB {
+            self.write_entry(&entry)?;
         }
+        let id = entry.id.clone();
+        self.entries.push(entry);
+        self.last_consolidation = now;
         
-        Ok(())
+        Ok(id)
     }
 
-    /// Load entries from IndexedDB
-    async fn load_from_indexeddb(&mut self) -> Result<(), JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        let storage = window.local_storage()?.ok_or_else(|| JsValue::from_str("No localStorage"))?;
-        
-        let ids: Vec<String> = storage.get_item("memory_index")
-            .ok()
-            .flatten()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
-        
-        self.entries.clear();
+    /// Consolidate lowest-importance entries locally until at most `limit` remain
+    fn enforce_capacity(&mut self, limit: usize) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
         
-        for id in ids {
-            let key = format!("memory_{}", id);
-            if let Some(json) = storage.get_item(&key).ok().flatten() {
-                if let Ok(entry) = serde_json::from_str::<MemoryEntry>(&json) {
-                    self.entries.push(entry);
+        while self.general_count() > limit {
+            let excess = self.general_count() - limit;
+            let mut ranked: Vec<&MemoryEntry> = self.entries.iter().filter(|e| e.namespace().is_none()).collect();
+            ranked.sort_by(|a, b| self.importance(a, now)
+                .partial_cmp(&self.importance(b, now))
+                .unwrap_or(std::cmp::Ordering::Equal));
+            
+            // Folding n entries into one frees n - 1 slots
+            let batch = (excess + 1).clamp(2, self.config.consolidation_batch.max(2));
+            let group: Vec<MemoryEntry> = ranked.into_iter().take(batch).cloned().collect();
+            
+            if group.len() < 2 {
+                if let Some(entry) = group.first() {
+                    let id = entry.id.clone();
+                    self.remove_entry(&id)?;
                 }
+                break;
             }
+            
+            let ids: Vec<String> = group.iter().map(|e| e.id.clone()).collect();
+            self.apply_consolidation(&ids, &extractive_summary(&group))?;
         }
         
         Ok(())
     }
 
-    /// Delete a memory entry
-    pub async fn delete(&mut self, id: &str) -> Result<bool, JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        let storage = window.local_storage()?.ok_or_else(|| JsValue::from_str("No localStorage"))?;
-        
-        // Remove from entries
+    /// Entries outside any namespace
+    fn general_count(&self) -> usize {
+        self.entries.iter().filter(|e| e.namespace().is_none()).count()
+    }
+
+    /// Remove an entry from memory and storage (sync)
+    fn remove_entry(&mut self, id: &str) -> Result<()> {
         self.entries.retain(|e| e.id != id);
+        if self.config.backend != MemoryBackend::IndexedDB {
+            return Ok(());
+        }
         
-        // Remove from storage
-        let key = format!("memory_{}", id);
-        storage.remove_item(&key)?;
-        
-        // Update index
-        let mut ids: Vec<String> = storage.get_item("memory_index")
-            .ok()
-            .flatten()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
+        storage::remove(&format!("memory_{}", id))?;
         
+        let mut ids = read_index();
         ids.retain(|i| i != id);
-        storage.set_item("memory_index", &serde_json::to_string(&ids).unwrap())?;
+        storage::set("memory_index", &serde_json::to_string(&ids).unwrap())?;
         
-        Ok(true)
+        Ok(())
     }
+}
 
-    /// Clear all memories
-    pub async fn clear(&mut self) -> Result<(), JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        let storage = window.local_storage()?.ok_or_else(|| JsValue::from_str("No localStorage"))?;
+/// Embedding client detached from the memory system
+#[derive(Debug, Clone)]
+pub struct Embedder {
+    provider: EmbeddingProvider,
+    api_key: Option<String>,
+}
+
+impl Embedder {
+    /// Embed text with the configured provider
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        match self.provider {
+            EmbeddingProvider::OpenAI => self.openai(text).await,
+            EmbeddingProvider::Local => Ok(local_embedding(text)),
+            EmbeddingProvider::None => Err(Error::new("No embedding provider configured")),
+        }
+    }
+
+    /// Get embedding from OpenAI
+    async fn openai(&self, text: &str) -> Result<Vec<f32>> {
+        let api_key = self.api_key.as_ref()
+            .ok_or_else(|| Error::new("API key not set for embeddings"))?;
         
-        // Get all memory IDs
-        let ids: Vec<String> = storage.get_item("memory_index")
-            .ok()
-            .flatten()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
+        let body = serde_json::json!({
+            "input": text,
+            "model": "text-embedding-3-small",
+        });
         
-        // Remove all memory entries
-        for id in &ids {
-            let key = format!("memory_{}", id);
-            storage.remove_item(&key)?;
-        }
+        let request = HttpRequest::post_json("https://api.openai.com/v1/embeddings", &body)
+            .header("Authorization", &format!("Bearer {}", api_key));
+        let response = platform::fetch(request).await?;
         
-        // Clear index
-        storage.remove_item("memory_index")?;
+        if !response.ok() {
+            return Err(Error::new(format!("Embedding API error: {}", response.status)));
+        }
         
-        // Clear in-memory entries
-        self.entries.clear();
+        let result: EmbeddingResponse = response.json()?;
         
-        Ok(())
+        Ok(result.data[0].embedding.clone())
     }
+}
 
-    /// Get all memories
-    pub fn get_all(&self) -> &[MemoryEntry] {
-        &self.entries
+/// Get local embedding (simple hash-based, not real embeddings)
+fn local_embedding(text: &str) -> Vec<f32> {
+    // Simple TF-IDF style local embedding (384 dimensions)
+    let text_lower = text.to_lowercase();
+    let words: Vec<&str> = text_lower.split_whitespace().collect();
+    let mut embedding = vec![0.0f32; 384];
+
+    for (i, word) in words.iter().enumerate() {
+        let hash = hash_word(word);
+        let idx = hash % 384;
+        embedding[idx] += 1.0 / (1.0 + i as f32); // Position weighting
+    }
+
+    // Normalize
+    let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for e in embedding.iter_mut() {
+            *e /= norm;
+        }
+    }
+
+    embedding
+}
+
+/// Save a memory on a shared memory system
+///
+/// The embedding is fetched before borrowing, so the system stays usable
+/// while the request is in flight.
+pub async fn save_shared(
+    memory: &Rc<RefCell<MemorySystem>>,
+    content: &str,
+    metadata: serde_json::Value,
+) -> Result<String> {
+    let embedder = memory.borrow().embedder();
+    let embedding = embedder.embed(content).await.ok();
+    memory.borrow_mut().insert(content, embedding, metadata)
+}
+
+/// Recall memories from a shared memory system
+pub async fn recall_shared(
+    memory: &Rc<RefCell<MemorySystem>>,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<MemorySearchResult>> {
+    let embedder = {
+        let mut memory = memory.borrow_mut();
+        memory.ensure_loaded()?;
+        memory.embedder()
+    };
+    let query_embedding = embedder.embed(query).await.ok();
+    Ok(memory.borrow_mut().rank(query, query_embedding.as_deref(), limit))
+}
+
+/// Consolidate expired memories, summarizing them with the provider
+///
+/// Falls back to a local extractive summary if the provider call fails.
+/// Returns the ID of the new summary entry, if anything was consolidated.
+pub async fn consolidate_with_provider(
+    memory: &Rc<RefCell<MemorySystem>>,
+    provider: &dyn ChatModel,
+    config: &Config,
+    security: &SecurityConfig,
+) -> Result<Option<String>> {
+    let now = chrono::Utc::now().timestamp();
+    let batch = {
+        let mut memory = memory.borrow_mut();
+        memory.ensure_loaded()?;
+        memory.consolidation_candidates(now)
+    };
+    
+    if batch.len() < 2 {
+        return Ok(None);
     }
+    
+    let mut messages = vec![
+        Message::system("You condense an assistant's long-term memory. Merge the notes below into one short factual summary that keeps names, dates, preferences, and decisions. Reply with the summary only."),
+        Message::user(&batch.iter()
+            .map(|e| format!("- {}", e.content))
+            .collect::<Vec<_>>()
+            .join("\n")),
+    ];
+    
+    security.redact_messages(&mut messages);
+    let summary = match provider.complete(&messages, config).await {
+        Ok(text) if !reasoning::strip(&text).is_empty() => reasoning::strip(&text),
+        _ => extractive_summary(&batch),
+    };
+    
+    let ids: Vec<String> = batch.iter().map(|e| e.id.clone()).collect();
+    let id = memory.borrow_mut().apply_consolidation(&ids, &summary)?;
+    Ok(Some(id))
+}
+
+/// IDs of the entries in storage
+fn read_index() -> Vec<String> {
+    storage::get("memory_index")
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Importance score: (1 + access count), halved every half-life since last access
+fn entry_importance(entry: &MemoryEntry, now: i64, half_life_secs: i64) -> f32 {
+    let idle = (now - entry.accessed_at).max(0) as f32;
+    let half_life = half_life_secs.max(1) as f32;
+    (1.0 + entry.access_count as f32) * 0.5f32.powf(idle / half_life)
+}
+
+/// Summarize entries without a model: first sentence of each, oldest first
+fn extractive_summary(entries: &[MemoryEntry]) -> String {
+    let mut sorted: Vec<&MemoryEntry> = entries.iter().collect();
+    sorted.sort_by_key(|e| e.created_at);
+    
+    let lines: Vec<String> = sorted.iter()
+        .map(|e| {
+            let content = e.content.trim();
+            let first = content.split_inclusive(['.', '!', '?', '\n'])
+                .next()
+                .unwrap_or(content)
+                .trim();
+            format!("- {}", first.chars().take(200).collect::<String>())
+        })
+        .collect();
+    
+    format!("Consolidated memories:\n{}", lines.join("\n"))
+}
+
+/// Parse a memory export, migrating older formats to the current version
+fn parse_memory_export(json: &str) -> Result<MemoryExport, String> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("Invalid memory export: {}", e))?;
+    
+    // Version 0: a bare array of entries (raw localStorage dump)
+    if value.is_array() {
+        let entries: Vec<MemoryEntry> = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid memory entries: {}", e))?;
+        return Ok(migrate_export(MemoryExport { version: 0, exported_at: 0, entries }));
+    }
+    
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > MEMORY_EXPORT_VERSION {
+        return Err(format!(
+            "Memory export version {} is newer than supported version {}",
+            version, MEMORY_EXPORT_VERSION
+        ));
+    }
+    
+    let entries: Vec<MemoryEntry> = serde_json::from_value(
+        value.get("entries").cloned().unwrap_or(serde_json::json!([]))
+    ).map_err(|e| format!("Invalid memory entries: {}", e))?;
+    let exported_at = value.get("exported_at").and_then(|v| v.as_i64()).unwrap_or(0);
+    
+    Ok(migrate_export(MemoryExport { version, exported_at, entries }))
+}
+
+/// Bring an older export up to the current format
+fn migrate_export(mut export: MemoryExport) -> MemoryExport {
+    if export.version < 1 {
+        // v0 dumps may lack access stats
+        for entry in export.entries.iter_mut() {
+            if entry.accessed_at == 0 {
+                entry.accessed_at = entry.created_at;
+            }
+            if entry.metadata.is_null() {
+                entry.metadata = serde_json::json!({});
+            }
+        }
+    }
+    export.version = MEMORY_EXPORT_VERSION;
+    export
+}
+
+/// Merge imported entries into existing ones, deduplicating by ID and content
+fn merge_entries(existing: &mut Vec<MemoryEntry>, incoming: Vec<MemoryEntry>) -> ImportStats {
+    let mut stats = ImportStats::default();
+    
+    for mut entry in incoming {
+        if existing.iter().any(|e| e.content == entry.content) {
+            stats.skipped += 1;
+            continue;
+        }
+        if existing.iter().any(|e| e.id == entry.id) {
+            entry.id = generate_id();
+            stats.renamed += 1;
+        }
+        existing.push(entry);
+        stats.imported += 1;
+    }
+    
+    stats
 }
 
 // Response types
@@ -385,12 +816,13 @@ struct EmbeddingData {
 // Helper functions
 
 fn generate_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
-    format!("mem_{}", timestamp)
+    use std::sync::atomic::{AtomicU32, Ordering};
+    static SEQ: AtomicU32 = AtomicU32::new(0);
+    
+    // chrono works on wasm32 (SystemTime does not); the sequence keeps
+    // IDs unique when several entries are created in the same millisecond
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    format!("mem_{}_{}", timestamp, SEQ.fetch_add(1, Ordering::Relaxed))
 }
 
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
@@ -453,3 +885,70 @@ fn hash_word(word: &str) -> usize {
     }
     hash
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, content: &str) -> MemoryEntry {
+        MemoryEntry {
+            id: id.to_string(),
+            content: content.to_string(),
+            embedding: None,
+            metadata: serde_json::json!({}),
+            created_at: 100,
+            accessed_at: 100,
+            access_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_legacy_array_export() {
+        let json = r#"[{"id": "mem_1", "content": "hello", "created_at": 42}]"#;
+        let export = parse_memory_export(json).unwrap();
+        assert_eq!(export.version, MEMORY_EXPORT_VERSION);
+        assert_eq!(export.entries.len(), 1);
+        assert_eq!(export.entries[0].accessed_at, 42);
+    }
+
+    #[test]
+    fn test_reject_newer_export_version() {
+        let json = r#"{"version": 99, "exported_at": 0, "entries": []}"#;
+        assert!(parse_memory_export(json).is_err());
+    }
+
+    #[test]
+    fn test_importance_decays() {
+        let mut e = entry("mem_1", "alpha");
+        e.access_count = 3;
+        let fresh = entry_importance(&e, 100, 1000);
+        let old = entry_importance(&e, 1100, 1000);
+        assert!((fresh - 4.0).abs() < 1e-6);
+        assert!((old - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_extractive_summary_takes_first_sentences() {
+        let mut a = entry("mem_1", "User likes tea. Also coffee sometimes.");
+        a.created_at = 1;
+        let b = entry("mem_2", "Project deadline is Friday");
+        let summary = extractive_summary(&[b, a]);
+        assert_eq!(summary, "Consolidated memories:\n- User likes tea.\n- Project deadline is Friday");
+    }
+
+    #[test]
+    fn test_merge_deduplicates() {
+        let mut existing = vec![entry("mem_1", "alpha")];
+        let incoming = vec![
+            entry("mem_2", "alpha"),
+            entry("mem_1", "beta"),
+            entry("mem_3", "gamma"),
+        ];
+        let stats = merge_entries(&mut existing, incoming);
+        assert_eq!(stats.imported, 2);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.renamed, 1);
+        assert_eq!(existing.len(), 3);
+        assert_ne!(existing[1].id, "mem_1");
+    }
+}
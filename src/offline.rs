@@ -0,0 +1,163 @@
+//! Offline request queue
+//!
+//! While the browser reports no connection, `platform::fetch` does not fail
+//! tool and provider requests: each one waits here and goes out, in the
+//! order it came, once connectivity returns (`set_online(true)`, driven by
+//! the window's `online` event). A request that fails because the
+//! connection dropped under it waits and is replayed the same way, up to
+//! `MAX_REPLAYS` times. The page, wasm bundle and fonts are cached for
+//! offline startup by the service worker in web/sw.js.
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::platform::HttpRequest;
+
+/// Requests waiting at once; more fail right away
+pub const MAX_QUEUED: usize = 100;
+
+/// Times one request is replayed after the connection dropped under it
+pub const MAX_REPLAYS: u32 = 3;
+
+/// A request waiting for the connection
+#[derive(Debug, Clone, Serialize)]
+pub struct Queued {
+    pub id: u64,
+    pub method: String,
+    /// Without the query, which may carry keys
+    pub url: String,
+    pub queued_at: String,
+}
+
+struct Waiting {
+    entry: Queued,
+    waker: Option<Waker>,
+}
+
+thread_local! {
+    static ONLINE: Cell<bool> = const { Cell::new(true) };
+    static QUEUE: RefCell<Vec<Waiting>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<u64> = const { Cell::new(1) };
+}
+
+/// True while the browser reports no connection
+pub fn is_offline() -> bool {
+    !ONLINE.with(Cell::get)
+}
+
+/// Record a connectivity change; coming online releases every queued request
+pub fn set_online(online: bool) {
+    ONLINE.with(|o| o.set(online));
+    if online {
+        let wakers: Vec<Waker> = QUEUE.with(|q| q.borrow_mut().iter_mut().filter_map(|w| w.waker.take()).collect());
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Requests waiting for the connection, oldest first
+pub fn queued() -> Vec<Queued> {
+    QUEUE.with(|q| q.borrow().iter().map(|w| w.entry.clone()).collect())
+}
+
+fn display_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed.set_query(None);
+            parsed.set_fragment(None);
+            parsed.to_string()
+        }
+        Err(_) => url.split(['?', '#']).next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Wait until the browser is online, queued behind earlier requests
+pub async fn wait_online(request: &HttpRequest) -> Result<()> {
+    if !is_offline() {
+        return Ok(());
+    }
+    let id = QUEUE.with(|q| {
+        let mut queue = q.borrow_mut();
+        if queue.len() >= MAX_QUEUED {
+            return Err(Error::new(format!("Offline: {} requests are already waiting for the connection", MAX_QUEUED)));
+        }
+        let id = NEXT_ID.with(|n| n.replace(n.get() + 1));
+        queue.push(Waiting {
+            entry: Queued {
+                id,
+                method: request.method.clone(),
+                url: display_url(&request.url),
+                queued_at: chrono::Utc::now().to_rfc3339(),
+            },
+            waker: None,
+        });
+        Ok(id)
+    })?;
+    WaitOnline { id }.await;
+    Ok(())
+}
+
+/// Pending until online; leaves the queue when done or dropped
+struct WaitOnline {
+    id: u64,
+}
+
+impl Future for WaitOnline {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if !is_offline() {
+            return Poll::Ready(());
+        }
+        QUEUE.with(|q| {
+            if let Some(waiting) = q.borrow_mut().iter_mut().find(|w| w.entry.id == self.id) {
+                waiting.waker = Some(cx.waker().clone());
+            }
+        });
+        Poll::Pending
+    }
+}
+
+impl Drop for WaitOnline {
+    fn drop(&mut self) {
+        QUEUE.with(|q| q.borrow_mut().retain(|w| w.entry.id != self.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::{self, MockTransport};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_requests_wait_for_the_connection() {
+        let mock = Rc::new(MockTransport::new());
+        mock.respond_json("https://api.example.com/", &serde_json::json!({"ok": true}));
+        platform::set_transport(mock.clone());
+        set_online(false);
+
+        let mut first = Box::pin(platform::fetch(HttpRequest::get("https://api.example.com/v1?key=secret")));
+        let mut second = Box::pin(platform::fetch(HttpRequest::post_json("https://api.example.com/v2", &serde_json::json!({}))));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(first.as_mut().poll(&mut cx).is_pending() && second.as_mut().poll(&mut cx).is_pending());
+        let waiting = queued();
+        assert_eq!(waiting.iter().map(|q| (q.method.as_str(), q.url.as_str())).collect::<Vec<_>>(),
+            vec![("GET", "https://api.example.com/v1"), ("POST", "https://api.example.com/v2")]);
+        assert!(mock.requests().is_empty());
+
+        // A dropped request leaves the queue
+        drop(second);
+        assert_eq!(queued().len(), 1);
+
+        set_online(true);
+        assert!(matches!(first.as_mut().poll(&mut cx), Poll::Ready(Ok(ref r)) if r.ok()));
+        assert!(queued().is_empty());
+        assert_eq!(mock.requests().len(), 1);
+        platform::reset_transport();
+    }
+}
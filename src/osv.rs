@@ -0,0 +1,215 @@
+//! OSV (Open Source Vulnerabilities) helpers for claWasm
+//!
+//! Request bodies and response parsing for api.osv.dev. Network calls go
+//! through the proxy in tools.rs; this module ranks advisories using the
+//! same severity scale as the scan report.
+
+use serde::Serialize;
+
+use crate::scan_report::Severity;
+
+/// OSV single-package query endpoint
+pub const QUERY_URL: &str = "https://api.osv.dev/v1/query";
+/// OSV batch query endpoint (IDs only, up to 1000 queries)
+pub const BATCH_URL: &str = "https://api.osv.dev/v1/querybatch";
+/// Maximum queries per batch request
+pub const MAX_BATCH: usize = 1000;
+/// Proxy endpoint that runs a batch and fetches the advisories in one go
+pub const PROXY_BATCH_PATH: &str = "/osv/batch";
+
+/// URL of the full record for one vulnerability
+pub fn vuln_url(id: &str) -> String {
+    format!("https://api.osv.dev/v1/vulns/{}", id)
+}
+
+/// A known vulnerability affecting a package version
+#[derive(Debug, Clone, Serialize)]
+pub struct Vulnerability {
+    pub id: String,
+    pub summary: String,
+    pub severity: Severity,
+    /// CVE and other identifiers for the same issue
+    pub aliases: Vec<String>,
+    /// Versions that fix it, when the advisory lists them
+    pub fixed: Vec<String>,
+}
+
+/// Body for a single-package query
+pub fn query_body(package: &str, ecosystem: &str, version: Option<&str>) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "package": { "name": package, "ecosystem": ecosystem }
+    });
+    if let Some(version) = version {
+        body["version"] = serde_json::json!(version);
+    }
+    body
+}
+
+/// Body for a batch query of (package, ecosystem, version) triples
+pub fn batch_body<'a>(queries: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>) -> serde_json::Value {
+    let queries: Vec<serde_json::Value> = queries.into_iter()
+        .map(|(package, ecosystem, version)| query_body(package, ecosystem, Some(version)))
+        .collect();
+    serde_json::json!({ "queries": queries })
+}
+
+/// Body for the proxy's /osv/batch
+pub fn proxy_batch_body<'a>(packages: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>) -> serde_json::Value {
+    let packages: Vec<serde_json::Value> = packages.into_iter()
+        .map(|(name, ecosystem, version)| serde_json::json!({ "name": name, "ecosystem": ecosystem, "version": version }))
+        .collect();
+    serde_json::json!({ "packages": packages })
+}
+
+/// Vulnerabilities per package in an /osv/batch response, in request
+/// order, plus how many advisories came without details
+pub fn parse_proxy_batch(json: &serde_json::Value) -> (Vec<Vec<Vulnerability>>, usize) {
+    let missing: Vec<&str> = json["missing"].as_array().into_iter().flatten()
+        .filter_map(|id| id.as_str())
+        .collect();
+    let found = json["results"].as_array().into_iter().flatten()
+        .map(|result| result["vulns"].as_array().into_iter().flatten()
+            .map(|vuln| match vuln["id"].as_str() {
+                Some(id) if missing.contains(&id) => undetailed(id),
+                _ => parse_vuln(vuln),
+            })
+            .collect())
+        .collect();
+    (found, missing.len())
+}
+
+/// Placeholder for an advisory whose record could not be fetched
+pub fn undetailed(id: &str) -> Vulnerability {
+    Vulnerability {
+        id: id.to_string(),
+        summary: "Details not fetched".to_string(),
+        severity: Severity::Medium,
+        aliases: Vec::new(),
+        fixed: Vec::new(),
+    }
+}
+
+/// Vulnerability IDs per query in a batch response, in query order
+pub fn parse_batch(json: &serde_json::Value) -> Vec<Vec<String>> {
+    json["results"].as_array()
+        .into_iter()
+        .flatten()
+        .map(|result| result["vulns"].as_array().into_iter().flatten()
+            .filter_map(|v| v["id"].as_str().map(|s| s.to_string()))
+            .collect())
+        .collect()
+}
+
+/// Vulnerabilities in a query response, most severe first
+pub fn parse_vulns(json: &serde_json::Value) -> Vec<Vulnerability> {
+    let mut vulns: Vec<Vulnerability> = json["vulns"].as_array()
+        .into_iter()
+        .flatten()
+        .map(parse_vuln)
+        .collect();
+    vulns.sort_by_key(|v| std::cmp::Reverse(v.severity));
+    vulns
+}
+
+/// Parse one OSV vulnerability record
+pub fn parse_vuln(vuln: &serde_json::Value) -> Vulnerability {
+    let strings = |value: &serde_json::Value| -> Vec<String> {
+        value.as_array().into_iter().flatten()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect()
+    };
+    let mut fixed: Vec<String> = vuln["affected"].as_array().into_iter().flatten()
+        .flat_map(|a| a["ranges"].as_array().into_iter().flatten())
+        .flat_map(|r| r["events"].as_array().into_iter().flatten())
+        .filter_map(|e| e["fixed"].as_str().map(|s| s.to_string()))
+        .collect();
+    fixed.dedup();
+
+    Vulnerability {
+        id: vuln["id"].as_str().unwrap_or("Unknown").to_string(),
+        summary: vuln["summary"].as_str()
+            .or_else(|| vuln["details"].as_str().and_then(|d| d.lines().next()))
+            .unwrap_or("No description")
+            .to_string(),
+        severity: severity_of(vuln),
+        aliases: strings(&vuln["aliases"]),
+        fixed,
+    }
+}
+
+/// Advisory severity, from the database label or a numeric CVSS score
+fn severity_of(vuln: &serde_json::Value) -> Severity {
+    let label = vuln["database_specific"]["severity"].as_str()
+        .or_else(|| vuln["affected"][0]["ecosystem_specific"]["severity"].as_str());
+    if let Some(label) = label {
+        return match label.to_uppercase().as_str() {
+            "CRITICAL" => Severity::Critical,
+            "HIGH" => Severity::High,
+            "MODERATE" | "MEDIUM" => Severity::Medium,
+            "LOW" => Severity::Low,
+            _ => Severity::Medium,
+        };
+    }
+    let score = vuln["severity"].as_array().into_iter().flatten()
+        .filter_map(|s| s["score"].as_f64().or_else(|| s["score"].as_str().and_then(|s| s.parse().ok())))
+        .fold(None, |max: Option<f64>, s| Some(max.map_or(s, |m| m.max(s))));
+    match score {
+        Some(s) if s >= 9.0 => Severity::Critical,
+        Some(s) if s >= 7.0 => Severity::High,
+        Some(s) if s >= 4.0 => Severity::Medium,
+        Some(_) => Severity::Low,
+        // Unrated advisories are still worth a look
+        None => Severity::Medium,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vulns_ranks_by_severity() {
+        let json = serde_json::json!({"vulns": [
+            {"id": "GHSA-low", "summary": "minor", "database_specific": {"severity": "LOW"}},
+            {"id": "GHSA-high", "aliases": ["CVE-2020-11022"], "database_specific": {"severity": "MODERATE"},
+             "affected": [{"ranges": [{"events": [{"introduced": "1.2"}, {"fixed": "3.5.0"}]}]}]},
+            {"id": "OSV-score", "summary": "scored", "severity": [{"type": "CVSS_V3", "score": "9.8"}]}
+        ]});
+        let vulns = parse_vulns(&json);
+        assert_eq!(vulns.iter().map(|v| v.id.as_str()).collect::<Vec<_>>(), vec!["OSV-score", "GHSA-high", "GHSA-low"]);
+        assert_eq!(vulns[1].fixed, vec!["3.5.0"]);
+        assert_eq!(vulns[1].aliases, vec!["CVE-2020-11022"]);
+        assert!(parse_vulns(&serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_batch_round_trip() {
+        let body = batch_body([("lodash", "npm", "4.17.15"), ("django", "PyPI", "3.2.1")]);
+        assert_eq!(body["queries"][1]["package"]["ecosystem"], "PyPI");
+        assert_eq!(body["queries"][0]["version"], "4.17.15");
+
+        let response = serde_json::json!({"results": [
+            {"vulns": [{"id": "GHSA-1", "modified": "2024-01-01T00:00:00Z"}, {"id": "GHSA-2"}]},
+            {}
+        ]});
+        assert_eq!(parse_batch(&response), vec![vec!["GHSA-1".to_string(), "GHSA-2".to_string()], vec![]]);
+    }
+
+    #[test]
+    fn test_proxy_batch() {
+        let body = proxy_batch_body([("serde", "crates.io", "1.0.0")]);
+        assert_eq!(body["packages"][0], serde_json::json!({"name": "serde", "ecosystem": "crates.io", "version": "1.0.0"}));
+
+        let response = serde_json::json!({"cached": 0, "missing": ["GHSA-2"], "results": [
+            {"name": "lodash", "ecosystem": "npm", "version": "4.17.15", "vulns": [
+                {"id": "GHSA-1", "summary": "Prototype pollution", "database_specific": {"severity": "HIGH"}},
+                {"id": "GHSA-2"}
+            ]},
+            {"name": "left-pad", "ecosystem": "npm", "version": "1.3.0", "vulns": []}
+        ]});
+        let (found, undetailed) = parse_proxy_batch(&response);
+        assert_eq!((found.len(), undetailed), (2, 1));
+        assert_eq!(found[0][0].severity, Severity::High);
+        assert_eq!(found[0][1].summary, "Details not fetched");
+    }
+}
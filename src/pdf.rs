@@ -0,0 +1,283 @@
+//! Minimal PDF writer
+//!
+//! Enough of PDF 1.4 for generated reports: A4 pages of text in the four
+//! standard fonts every viewer has (Helvetica regular, bold and oblique, and
+//! Courier), filled rectangles and RGB colors. The standard fonts need no
+//! embedding but only cover WinAnsi (Latin-1 plus typographic quotes and
+//! dashes); other letters are transliterated where that is common (Turkish
+//! ğ → g) and shown as "?" otherwise. Content streams are left uncompressed.
+
+/// A4 in points
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+/// RGB, each from 0.0 to 1.0
+pub type Color = (f32, f32, f32);
+
+pub const BLACK: Color = (0.0, 0.0, 0.0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+    Italic,
+    Mono,
+}
+
+impl Font {
+    const ALL: [Font; 4] = [Font::Regular, Font::Bold, Font::Italic, Font::Mono];
+
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Italic => "F3",
+            Font::Mono => "F4",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+            Font::Italic => "Helvetica-Oblique",
+            Font::Mono => "Courier",
+        }
+    }
+}
+
+/// Helvetica advance widths of ' ' to '~', in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Width of `text` set in `font` at `size` points
+pub fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    let units: f32 = text.chars()
+        .map(|c| match font {
+            Font::Mono => 600.0,
+            _ => {
+                let width = match c {
+                    ' '..='~' => HELVETICA_WIDTHS[c as usize - 32] as f32,
+                    _ => 556.0,
+                };
+                // Bold runs about 8% wider than regular
+                if font == Font::Bold { width * 1.08 } else { width }
+            }
+        })
+        .sum();
+    units * size / 1000.0
+}
+
+/// Break `text` into lines no wider than `width`, at spaces where possible
+pub fn wrap(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if text_width(&candidate, font, size) <= width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        // A word wider than the line (a URL, a hash) is cut anywhere
+        for c in word.chars() {
+            line.push(c);
+            if text_width(&line, font, size) > width && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `c` in WinAnsiEncoding, if it has a code there
+fn win_ansi(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        '\u{a0}'..='\u{ff}' => Some(c as u32 as u8),
+        '€' => Some(0x80),
+        '‚' => Some(0x82),
+        '„' => Some(0x84),
+        '…' => Some(0x85),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        '™' => Some(0x99),
+        _ => None,
+    }
+}
+
+/// `text` as a PDF string literal in WinAnsiEncoding
+fn literal(text: &str) -> String {
+    let mut out = String::from("(");
+    for c in text.chars() {
+        let c = match c {
+            '\t' => ' ',
+            'ı' => 'i',
+            'İ' => 'I',
+            'ğ' => 'g',
+            'Ğ' => 'G',
+            'ş' => 's',
+            'Ş' => 'S',
+            '→' => '>',
+            '←' => '<',
+            c => c,
+        };
+        match win_ansi(c) {
+            Some(b'(') => out.push_str("\\("),
+            Some(b')') => out.push_str("\\)"),
+            Some(b'\\') => out.push_str("\\\\"),
+            Some(byte) if byte < 0x80 => out.push(byte as char),
+            Some(byte) => out.push_str(&format!("\\{:03o}", byte)),
+            // Emoji and other pictographs carry no text worth a "?"
+            None if (c as u32) >= 0x1F000 || ('\u{2600}'..='\u{27BF}').contains(&c) || c == '\u{fe0f}' => {}
+            None if c.is_control() => {}
+            None => out.push('?'),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// A document being drawn, page by page; y runs up from the bottom edge
+#[derive(Debug, Clone)]
+pub struct PdfDocument {
+    title: String,
+    pages: Vec<String>,
+}
+
+impl PdfDocument {
+    pub fn new(title: &str) -> Self {
+        PdfDocument { title: title.to_string(), pages: Vec::new() }
+    }
+
+    /// Start a page, returning its index
+    pub fn add_page(&mut self) -> usize {
+        self.pages.push(String::new());
+        self.pages.len() - 1
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Draw `text` on one line with its baseline at (x, y)
+    #[allow(clippy::too_many_arguments)]
+    pub fn text(&mut self, page: usize, x: f32, y: f32, font: Font, size: f32, color: Color, text: &str) {
+        let stream = &mut self.pages[page];
+        stream.push_str(&format!(
+            "BT {:.3} {:.3} {:.3} rg /{} {:.1} Tf {:.2} {:.2} Td {} Tj ET\n",
+            color.0, color.1, color.2, font.resource(), size, x, y, literal(text)
+        ));
+    }
+
+    /// Fill the rectangle with lower left corner (x, y)
+    pub fn rect(&mut self, page: usize, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let stream = &mut self.pages[page];
+        stream.push_str(&format!(
+            "{:.3} {:.3} {:.3} rg {:.2} {:.2} {:.2} {:.2} re f\n",
+            color.0, color.1, color.2, x, y, width, height
+        ));
+    }
+
+    /// The finished file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let font_ids = 4..4 + Font::ALL.len();
+        let first_page = font_ids.end;
+        let mut objects: Vec<String> = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..self.pages.len()).map(|i| format!("{} 0 R", first_page + 2 * i)).collect::<Vec<_>>().join(" "),
+                self.pages.len()
+            ),
+            format!("<< /Title {} /Producer (claWasm) >>", literal(&self.title)),
+        ];
+        for font in Font::ALL {
+            objects.push(format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                font.base_font()
+            ));
+        }
+        let fonts: String = Font::ALL.iter().zip(font_ids)
+            .map(|(font, id)| format!("/{} {} 0 R ", font.resource(), id))
+            .collect();
+        for (i, stream) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R /Resources << /Font << {}>> >> >>",
+                PAGE_WIDTH, PAGE_HEIGHT, first_page + 2 * i + 1, fonts
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}endstream", stream.len(), stream));
+        }
+
+        let mut out = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+        }
+        let xref = out.len();
+        out.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+        for offset in offsets {
+            out.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        out.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1, xref
+        ));
+        out.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let lines = wrap("The quick brown fox jumps over the lazy dog", Font::Regular, 10.0, 100.0);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| text_width(l, Font::Regular, 10.0) <= 100.0));
+        assert_eq!(lines.join(" "), "The quick brown fox jumps over the lazy dog");
+        assert_eq!(wrap(&"x".repeat(30), Font::Mono, 10.0, 60.0), vec!["x".repeat(10); 3]);
+        assert_eq!(wrap("", Font::Regular, 10.0, 60.0), vec![String::new()]);
+    }
+
+    #[test]
+    fn test_literal_encoding() {
+        assert_eq!(literal("a (b) \\ c"), "(a \\(b\\) \\\\ c)");
+        assert_eq!(literal("Çağrı — ok 🤖"), "(\\307agri \\227 ok )");
+    }
+
+    #[test]
+    fn test_xref_offsets() {
+        let mut doc = PdfDocument::new("Report");
+        let page = doc.add_page();
+        doc.text(page, 50.0, 800.0, Font::Bold, 12.0, BLACK, "Hello");
+        let bytes = doc.to_bytes();
+        let text = String::from_utf8(bytes).unwrap();
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(text[xref..].starts_with("xref\n0 10\n"));
+        // Every offset in the table points at its object
+        for (i, entry) in text[xref..].lines().skip(3).take(9).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", i + 1)), "object {}", i + 1);
+        }
+    }
+}
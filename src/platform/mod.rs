@@ -0,0 +1,124 @@
+//! Platform services for claWasm
+//!
+//! The agent core reaches the outside world only through this module:
+//! key-value storage, HTTP, randomness and logging. With the `web` feature
+//! (the default) they are backed by localStorage, `fetch` and Web Crypto;
+//! with `native` by a JSON file under `~/.clawasm`, reqwest and the OS
+//! random source. `web` wins when both are enabled. HTTP goes through a
+//! swappable `HttpTransport` (see `transport`).
+
+use crate::error::{Error, Result};
+
+#[cfg(feature = "web")]
+mod web;
+#[cfg(feature = "web")]
+pub use web::{fill_random, log, storage, watch_connectivity, WebFetch, CORS_PROXY_REQUIRED};
+
+#[cfg(all(feature = "native", not(feature = "web")))]
+mod native;
+#[cfg(all(feature = "native", not(feature = "web")))]
+pub use native::{fill_random, log, storage, ReqwestTransport, CORS_PROXY_REQUIRED};
+
+pub mod transport;
+pub use transport::{fetch, set_transport, reset_transport, set_proxy_token, proxy_token_for, HttpTransport, MockTransport, ProxyTransport};
+
+#[cfg(not(any(feature = "web", feature = "native")))]
+compile_error!("claWasm needs the `web` (browser) or `native` feature");
+
+/// An outgoing HTTP request
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+impl HttpRequest {
+    pub fn get(url: &str) -> Self {
+        HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// POST with a JSON body
+    pub fn post_json(url: &str, body: &serde_json::Value) -> Self {
+        HttpRequest {
+            method: "POST".to_string(),
+            url: url.to_string(),
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: Some(body.to_string()),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// A response with its body read as text
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Whether the status is 2xx
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Case-insensitive header lookup
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Deserialize the body as JSON
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.body).map_err(|e| Error::new(format!("Parse error: {}", e)))
+    }
+}
+
+/// Send a request to a third-party API
+///
+/// In the browser the same-origin policy forces these through the local
+/// proxy at `proxy_url`; natively they go straight out.
+pub async fn fetch_cross_origin(request: HttpRequest, proxy_url: &str) -> Result<HttpResponse> {
+    if CORS_PROXY_REQUIRED {
+        let proxy = ProxyTransport::new(proxy_url, transport::transport());
+        proxy.send(request).await
+    } else {
+        fetch(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_helpers() {
+        let response = HttpResponse {
+            status: 201,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: r#"{"ok": true}"#.to_string(),
+        };
+        assert!(response.ok());
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        assert_eq!(response.json::<serde_json::Value>().unwrap()["ok"], true);
+        assert!(response.json::<Vec<u8>>().is_err());
+
+        let request = HttpRequest::post_json("https://example.com", &serde_json::json!({"a": 1}))
+            .header("Authorization", "Bearer k");
+        assert_eq!(request.headers.len(), 2);
+        assert_eq!(request.body.as_deref(), Some(r#"{"a":1}"#));
+    }
+}
@@ -0,0 +1,140 @@
+//! Native platform: a JSON key-value file, reqwest, the OS RNG and stderr
+//!
+//! Storage lives in `$CLAWASM_HOME/storage.json` (default `~/.clawasm`) and
+//! uses the same keys as the browser's localStorage, so the vault, memory
+//! and audit log behave identically in both builds.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use super::transport::{HttpFuture, HttpTransport};
+use super::{HttpRequest, HttpResponse};
+use crate::error::{Error, Result};
+use crate::logger::Level;
+
+/// No same-origin policy outside the browser; APIs are called directly
+pub const CORS_PROXY_REQUIRED: bool = false;
+
+/// File-backed key-value storage
+pub mod storage {
+    use super::*;
+
+    static CACHE: Mutex<Option<BTreeMap<String, String>>> = Mutex::new(None);
+
+    /// Directory holding claWasm state
+    pub fn home() -> PathBuf {
+        if let Some(dir) = std::env::var_os("CLAWASM_HOME") {
+            return PathBuf::from(dir);
+        }
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+        home.join(".clawasm")
+    }
+
+    fn path() -> PathBuf {
+        home().join("storage.json")
+    }
+
+    fn with_items<T>(f: impl FnOnce(&mut BTreeMap<String, String>) -> Result<T>) -> Result<T> {
+        let mut cache = CACHE.lock().map_err(|_| Error::new("Storage lock poisoned"))?;
+        if cache.is_none() {
+            let items = match std::fs::read_to_string(path()) {
+                Ok(json) => serde_json::from_str(&json)
+                    .map_err(|e| Error::new(format!("Corrupt storage file: {}", e)))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(e) => return Err(Error::new(format!("Cannot read storage: {}", e))),
+            };
+            *cache = Some(items);
+        }
+        f(cache.as_mut().expect("storage cache loaded above"))
+    }
+
+    fn persist(items: &BTreeMap<String, String>) -> Result<()> {
+        let path = path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| Error::new(format!("Cannot create {}: {}", dir.display(), e)))?;
+        }
+        let json = serde_json::to_string_pretty(items)
+            .map_err(|e| Error::new(format!("Serialize error: {}", e)))?;
+        // Write then rename so a crash never leaves a half-written file
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| Error::new(format!("Cannot write storage: {}", e)))?;
+        std::fs::rename(&tmp, &path).map_err(|e| Error::new(format!("Cannot write storage: {}", e)))
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>> {
+        with_items(|items| Ok(items.get(key).cloned()))
+    }
+
+    pub fn set(key: &str, value: &str) -> Result<()> {
+        with_items(|items| {
+            items.insert(key.to_string(), value.to_string());
+            persist(items)
+        })
+    }
+
+    pub fn remove(key: &str) -> Result<()> {
+        with_items(|items| match items.remove(key) {
+            Some(_) => persist(items),
+            None => Ok(()),
+        })
+    }
+
+    pub fn keys() -> Result<Vec<String>> {
+        with_items(|items| Ok(items.keys().cloned().collect()))
+    }
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(concat!("claWasm/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("default reqwest client")
+    })
+}
+
+/// Sends requests with a shared reqwest client
+pub struct ReqwestTransport;
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> HttpFuture<'_> {
+        Box::pin(fetch(request))
+    }
+}
+
+async fn fetch(request: HttpRequest) -> Result<HttpResponse> {
+    let method = reqwest::Method::from_bytes(request.method.as_bytes())
+        .map_err(|_| Error::new(format!("Invalid HTTP method: {}", request.method)))?;
+    let mut builder = client().request(method, &request.url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = request.body {
+        builder = builder.body(body);
+    }
+
+    let response = builder.send().await
+        .map_err(|e| Error::new(format!("Request to {} failed: {}", request.url, e)))?;
+    let status = response.status().as_u16();
+    let headers = response.headers().iter()
+        .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.to_string(), v.to_string())))
+        .collect();
+    let body = response.text().await
+        .map_err(|e| Error::new(format!("Cannot read response: {}", e)))?;
+    Ok(HttpResponse { status, headers, body })
+}
+
+/// Fill a buffer from the operating system's random source
+pub fn fill_random(buf: &mut [u8]) -> Result<()> {
+    getrandom::getrandom(buf).map_err(|e| Error::new(format!("No random source: {}", e)))
+}
+
+/// Write a line to stderr, prefixed with its level
+pub fn log(level: Level, message: &str) {
+    eprintln!("{:<5} {}", level.name().to_uppercase(), message);
+}
@@ -0,0 +1,366 @@
+//! HTTP transports
+//!
+//! Every request the core makes goes through `platform::fetch`, which hands
+//! it to the current `HttpTransport`: `WebFetch` in the browser, reqwest
+//! natively, a `MockTransport` in tests. Wrapping the default in a
+//! `ProxyTransport` routes everything through the local CORS proxy; the
+//! choice can change at runtime with `set_transport`. When the proxy needs
+//! a token (`set_proxy_token`), requests addressed to it carry it in
+//! `X-Proxy-Token`; requests to anywhere else never see it.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use super::{HttpRequest, HttpResponse};
+use crate::config::proxy_endpoint;
+use crate::error::{Error, Result};
+use crate::offline;
+use crate::scan_report::HeaderMap;
+
+/// Header the proxy checks against `CLAWASM_PROXY_TOKEN`
+pub const PROXY_TOKEN_HEADER: &str = "X-Proxy-Token";
+
+/// Future returned by an `HttpTransport`
+pub type HttpFuture<'a> = Pin<Box<dyn Future<Output = Result<HttpResponse>> + 'a>>;
+
+/// Sends HTTP requests
+pub trait HttpTransport {
+    fn send(&self, request: HttpRequest) -> HttpFuture<'_>;
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<dyn HttpTransport>>> = const { RefCell::new(None) };
+    /// Proxy base URL and the token it expects
+    static PROXY_TOKEN: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+fn default_transport() -> Rc<dyn HttpTransport> {
+    #[cfg(feature = "web")]
+    return Rc::new(super::WebFetch);
+    #[cfg(all(feature = "native", not(feature = "web")))]
+    return Rc::new(super::ReqwestTransport);
+}
+
+/// The transport `fetch` uses
+pub fn transport() -> Rc<dyn HttpTransport> {
+    CURRENT.with(|current| current.borrow().clone()).unwrap_or_else(default_transport)
+}
+
+/// Replace the transport for every later request
+pub fn set_transport(transport: Rc<dyn HttpTransport>) {
+    CURRENT.with(|current| *current.borrow_mut() = Some(transport));
+}
+
+/// Go back to the platform's own transport
+pub fn reset_transport() {
+    CURRENT.with(|current| *current.borrow_mut() = None);
+}
+
+/// Send `token` to the proxy at `proxy_url` from now on (None stops)
+pub fn set_proxy_token(proxy_url: &str, token: Option<&str>) {
+    let auth = token.filter(|t| !t.is_empty())
+        .map(|t| (proxy_url.trim_end_matches('/').to_string(), t.to_string()));
+    PROXY_TOKEN.with(|current| *current.borrow_mut() = auth);
+}
+
+/// The token header for `url`, if it points at the proxy that has one
+pub fn proxy_token_for(url: &str) -> Option<(&'static str, String)> {
+    PROXY_TOKEN.with(|current| {
+        current.borrow().as_ref()
+            .filter(|(base, _)| is_under(url, base))
+            .map(|(_, token)| (PROXY_TOKEN_HEADER, token.clone()))
+    })
+}
+
+/// Whether `url` is on the origin of `base` (scheme, host and port alike,
+/// no userinfo) at or below its path; a string prefix would also match
+/// `https://proxy.example.com.evil.net`
+fn is_under(url: &str, base: &str) -> bool {
+    let (Ok(url), Ok(base)) = (url::Url::parse(url), url::Url::parse(base)) else { return false };
+    let same_origin = url.scheme() == base.scheme()
+        && url.host_str() == base.host_str()
+        && url.port_or_known_default() == base.port_or_known_default()
+        && url.username().is_empty()
+        && url.password().is_none();
+    let base_path = base.path().trim_end_matches('/');
+    let path = url.path();
+    same_origin && (path == base_path || path.strip_prefix(base_path).is_some_and(|rest| rest.starts_with('/')))
+}
+
+fn with_proxy_token(request: HttpRequest) -> HttpRequest {
+    match proxy_token_for(&request.url) {
+        Some((name, token)) => request.header(name, &token),
+        None => request,
+    }
+}
+
+/// Send a request with the current transport
+///
+/// Offline, the request waits in the `offline` queue until the connection
+/// is back, and is queued again if the connection drops while it is sent.
+pub async fn fetch(request: HttpRequest) -> Result<HttpResponse> {
+    let transport = transport();
+    let request = with_proxy_token(request);
+    let mut replays = 0;
+    loop {
+        offline::wait_online(&request).await?;
+        match transport.send(request.clone()).await {
+            Err(_) if offline::is_offline() && replays < offline::MAX_REPLAYS => replays += 1,
+            result => return result,
+        }
+    }
+}
+
+/// Sends requests through the local proxy's `/proxy` endpoint
+///
+/// The target's status and body come back as-is; its headers arrive in
+/// `X-Upstream-Headers` and are restored on the response. Requests already
+/// addressed to the proxy (`/search`, `/tls-info`, ...) pass through. A
+/// `Cache-Control: no-cache` request also skips the proxy's cache.
+pub struct ProxyTransport {
+    proxy_url: String,
+    inner: Rc<dyn HttpTransport>,
+}
+
+impl ProxyTransport {
+    pub fn new(proxy_url: &str, inner: Rc<dyn HttpTransport>) -> Self {
+        ProxyTransport { proxy_url: proxy_url.trim_end_matches('/').to_string(), inner }
+    }
+
+    async fn forward(&self, request: HttpRequest) -> Result<HttpResponse> {
+        if is_under(&request.url, &self.proxy_url) {
+            return self.inner.send(with_proxy_token(request)).await;
+        }
+
+        let no_cache = request.headers.iter()
+            .any(|(name, value)| name.eq_ignore_ascii_case("cache-control") && value.contains("no-cache"));
+        let headers: serde_json::Map<String, serde_json::Value> = request.headers.into_iter()
+            .map(|(name, value)| (name, serde_json::Value::String(value)))
+            .collect();
+        let envelope = serde_json::json!({
+            "url": request.url,
+            "method": request.method,
+            "headers": headers,
+            "body": request.body,
+        });
+        let mut request = HttpRequest::post_json(&proxy_endpoint(&self.proxy_url, "/proxy"), &envelope);
+        if no_cache {
+            request = request.header("Cache-Control", "no-cache");
+        }
+        let response = self.inner.send(with_proxy_token(request)).await?;
+
+        let upstream = response.header("x-upstream-headers")
+            .and_then(|encoded| urlencoding::decode(encoded).ok())
+            .and_then(|json| serde_json::from_str::<HeaderMap>(&json).ok());
+        let headers = match upstream {
+            Some(map) => map.into_iter()
+                .flat_map(|(name, values)| values.into_iter().map(move |value| (name.to_lowercase(), value)))
+                .collect(),
+            // The proxy itself failed; its body says why
+            None if !response.ok() => {
+                return Err(Error::new(format!("Proxy error ({}): {}", response.status, response.body)));
+            }
+            None => {
+                return Err(Error::new("Proxy did not return upstream headers. Update the proxy server (cargo run --bin proxy --features proxy)"));
+            }
+        };
+        Ok(HttpResponse { status: response.status, headers, body: response.body })
+    }
+}
+
+impl HttpTransport for ProxyTransport {
+    fn send(&self, request: HttpRequest) -> HttpFuture<'_> {
+        Box::pin(self.forward(request))
+    }
+}
+
+/// Scripted transport for tests
+///
+/// Answers each request with the first route whose URL prefix matches and
+/// records every request it sees. Unmatched requests fail.
+#[derive(Default)]
+pub struct MockTransport {
+    routes: RefCell<Vec<(String, VecDeque<HttpResponse>)>>,
+    requests: RefCell<Vec<HttpRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests to URLs starting with `url_prefix`
+    ///
+    /// Several responses for the same prefix are used in order; the last
+    /// one repeats.
+    pub fn respond(&self, url_prefix: &str, response: HttpResponse) -> &Self {
+        let mut routes = self.routes.borrow_mut();
+        match routes.iter_mut().find(|(prefix, _)| prefix == url_prefix) {
+            Some((_, queue)) => queue.push_back(response),
+            None => routes.push((url_prefix.to_string(), VecDeque::from([response]))),
+        }
+        self
+    }
+
+    /// Answer with a 200 and a JSON body
+    pub fn respond_json(&self, url_prefix: &str, body: &serde_json::Value) -> &Self {
+        self.respond(url_prefix, HttpResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        })
+    }
+
+    /// Requests sent so far, oldest first
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.borrow().clone()
+    }
+
+    fn answer(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let mut routes = self.routes.borrow_mut();
+        let (_, queue) = routes.iter_mut()
+            .find(|(prefix, _)| request.url.starts_with(prefix.as_str()))
+            .ok_or_else(|| Error::new(format!("No mock response for {} {}", request.method, request.url)))?;
+        let response = if queue.len() > 1 { queue.pop_front() } else { queue.front().cloned() };
+        Ok(response.expect("routes always hold a response"))
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn send(&self, request: HttpRequest) -> HttpFuture<'_> {
+        let response = self.answer(&request);
+        self.requests.borrow_mut().push(request);
+        Box::pin(async move { response })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Waker};
+
+    /// Drive a future that never waits (everything here is mocked)
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("mocked future is pending"),
+        }
+    }
+
+    #[test]
+    fn test_mock_answers_by_prefix_in_order() {
+        let mock = MockTransport::new();
+        mock.respond("https://api.test/a", HttpResponse { status: 500, headers: vec![], body: "first".to_string() })
+            .respond("https://api.test/a", HttpResponse { status: 200, headers: vec![], body: "then".to_string() });
+
+        let bodies: Vec<String> = (0..3)
+            .map(|_| block_on(mock.send(HttpRequest::get("https://api.test/a?x=1"))).unwrap().body)
+            .collect();
+        assert_eq!(bodies, ["first", "then", "then"]);
+        assert!(block_on(mock.send(HttpRequest::get("https://other.test"))).is_err());
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_proxy_wraps_requests_and_restores_headers() {
+        let mock = Rc::new(MockTransport::new());
+        let upstream = urlencoding::encode(r#"{"Server":["nginx"],"Set-Cookie":["a=1","b=2"]}"#).into_owned();
+        mock.respond("http://localhost:3000/proxy", HttpResponse {
+            status: 404,
+            headers: vec![("X-Upstream-Headers".to_string(), upstream)],
+            body: "missing".to_string(),
+        });
+        mock.respond("http://localhost:3000/search", HttpResponse { status: 200, headers: vec![], body: "{}".to_string() });
+        let proxy = ProxyTransport::new("http://localhost:3000/", mock.clone());
+
+        let request = HttpRequest::get("https://example.com/page")
+            .header("Origin", "https://evil.test")
+            .header("Cache-Control", "no-cache");
+        let response = block_on(proxy.send(request)).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.header("server"), Some("nginx"));
+        assert_eq!(response.headers.iter().filter(|(k, _)| k == "set-cookie").count(), 2);
+
+        let sent = &mock.requests()[0];
+        assert_eq!(sent.method, "POST");
+        let envelope: serde_json::Value = serde_json::from_str(sent.body.as_deref().unwrap()).unwrap();
+        assert_eq!(envelope["url"], "https://example.com/page");
+        assert_eq!(envelope["method"], "GET");
+        assert_eq!(envelope["headers"]["Origin"], "https://evil.test");
+        assert!(sent.headers.contains(&("Cache-Control".to_string(), "no-cache".to_string())), "skips the proxy cache");
+
+        // Proxy endpoints are not wrapped again
+        block_on(proxy.send(HttpRequest::get("http://localhost:3000/search?q=x"))).unwrap();
+        assert_eq!(mock.requests()[1].url, "http://localhost:3000/search?q=x");
+    }
+
+    #[test]
+    fn test_proxy_failure_without_upstream_headers() {
+        let mock = Rc::new(MockTransport::new());
+        mock.respond("http://localhost:3000/proxy", HttpResponse { status: 500, headers: vec![], body: "Proxy error: dns".to_string() });
+        let proxy = ProxyTransport::new("http://localhost:3000", mock);
+        let error = block_on(proxy.send(HttpRequest::get("https://nowhere.test"))).unwrap_err();
+        assert!(error.message().contains("Proxy error (500): Proxy error: dns"));
+    }
+
+    #[test]
+    fn test_proxy_token_only_goes_to_the_proxy() {
+        let mock = Rc::new(MockTransport::new());
+        mock.respond("http://localhost:3000/proxy", HttpResponse {
+            status: 200,
+            headers: vec![("X-Upstream-Headers".to_string(), "%7B%7D".to_string())],
+            body: String::new(),
+        });
+        mock.respond("https://api.test", HttpResponse { status: 200, headers: vec![], body: String::new() });
+        set_proxy_token("http://localhost:3000/", Some("s3cret"));
+        set_transport(mock.clone());
+
+        block_on(ProxyTransport::new("http://localhost:3000", mock.clone()).send(HttpRequest::get("https://example.com"))).unwrap();
+        block_on(fetch(HttpRequest::get("https://api.test/v1"))).unwrap();
+        reset_transport();
+        set_proxy_token("http://localhost:3000", None);
+
+        let requests = mock.requests();
+        let token = |request: &HttpRequest| request.headers.iter()
+            .find(|(name, _)| name == PROXY_TOKEN_HEADER)
+            .map(|(_, value)| value.clone());
+        assert_eq!(token(&requests[0]).as_deref(), Some("s3cret"));
+        assert_eq!(token(&requests[1]), None);
+        assert!(proxy_token_for("http://localhost:3000/search").is_none());
+    }
+
+    #[test]
+    fn test_proxy_token_needs_the_exact_origin() {
+        set_proxy_token("https://proxy.example.com", Some("tok"));
+        let sent = |url: &str| proxy_token_for(url).is_some();
+        assert!(sent("https://proxy.example.com/paste/x"));
+        assert!(sent("https://proxy.example.com:443/search?q=a"));
+        assert!(!sent("https://proxy.example.com.evil.net/paste/x"), "look-alike host");
+        assert!(!sent("https://proxy.example.comevil.net/"));
+        assert!(!sent("https://proxy.example.com:4443/paste/x"), "other port");
+        assert!(!sent("http://proxy.example.com/paste/x"), "other scheme");
+        assert!(!sent("https://proxy.example.com:80@evil.net/paste/x"), "userinfo");
+        assert!(!sent("https://user@proxy.example.com/paste/x"));
+
+        set_proxy_token("http://localhost:3000/api/", Some("tok"));
+        assert!(sent("http://localhost:3000/api") && sent("http://localhost:3000/api/search"));
+        assert!(!sent("http://localhost:3000/apix/search"), "path boundary");
+        assert!(!sent("http://localhost:3000/search"));
+        set_proxy_token("http://localhost:3000", None);
+    }
+
+    #[test]
+    fn test_fetch_uses_the_installed_transport() {
+        let mock = Rc::new(MockTransport::new());
+        mock.respond_json("https://api.test", &serde_json::json!({"ok": true}));
+        set_transport(mock.clone());
+        let response = block_on(fetch(HttpRequest::get("https://api.test/v1")));
+        reset_transport();
+        assert_eq!(response.unwrap().json::<serde_json::Value>().unwrap()["ok"], true);
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
@@ -0,0 +1,129 @@
+//! Browser platform: localStorage, fetch, Web Crypto and the console
+
+use std::cell::Cell;
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
+
+use super::transport::{HttpFuture, HttpTransport};
+use super::{HttpRequest, HttpResponse};
+use crate::error::{Error, Result};
+use crate::logger::Level;
+use crate::offline;
+
+/// Cross-origin APIs must go through the local proxy
+pub const CORS_PROXY_REQUIRED: bool = true;
+
+/// localStorage access
+pub mod storage {
+    use crate::error::{Error, Result};
+
+    fn local_storage() -> Result<web_sys::Storage> {
+        let window = web_sys::window().ok_or_else(|| Error::new("No window"))?;
+        window.local_storage()?.ok_or_else(|| Error::new("No localStorage"))
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>> {
+        Ok(local_storage()?.get_item(key)?)
+    }
+
+    pub fn set(key: &str, value: &str) -> Result<()> {
+        Ok(local_storage()?.set_item(key, value)?)
+    }
+
+    pub fn remove(key: &str) -> Result<()> {
+        Ok(local_storage()?.remove_item(key)?)
+    }
+
+    pub fn keys() -> Result<Vec<String>> {
+        let storage = local_storage()?;
+        let mut keys = Vec::new();
+        for i in 0..storage.length()? {
+            keys.extend(storage.key(i)?);
+        }
+        Ok(keys)
+    }
+}
+
+/// Sends requests with `window.fetch` (CORS mode)
+pub struct WebFetch;
+
+impl HttpTransport for WebFetch {
+    fn send(&self, request: HttpRequest) -> HttpFuture<'_> {
+        Box::pin(fetch(request))
+    }
+}
+
+async fn fetch(request: HttpRequest) -> Result<HttpResponse> {
+    let window = web_sys::window().ok_or_else(|| Error::new("No window"))?;
+
+    let headers = Headers::new()?;
+    for (name, value) in &request.headers {
+        headers.set(name, value)?;
+    }
+    let init = RequestInit::new();
+    init.set_method(&request.method);
+    init.set_headers(headers.as_ref());
+    init.set_mode(RequestMode::Cors);
+    if let Some(body) = &request.body {
+        init.set_body(&JsValue::from_str(body));
+    }
+
+    let js_request = Request::new_with_str_and_init(&request.url, &init)?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&js_request)).await?.dyn_into()?;
+
+    let mut response_headers = Vec::new();
+    if let Some(entries) = js_sys::try_iter(response.headers().as_ref())? {
+        for entry in entries {
+            let pair: js_sys::Array = entry?.dyn_into()?;
+            if let (Some(name), Some(value)) = (pair.get(0).as_string(), pair.get(1).as_string()) {
+                response_headers.push((name, value));
+            }
+        }
+    }
+    let body = JsFuture::from(response.text()?).await?.as_string().unwrap_or_default();
+
+    Ok(HttpResponse {
+        status: response.status(),
+        headers: response_headers,
+        body,
+    })
+}
+
+/// Track `navigator.onLine` and the window's `online`/`offline` events in
+/// `offline::set_online`; calls after the first do nothing
+pub fn watch_connectivity() {
+    thread_local! {
+        static WATCHING: Cell<bool> = const { Cell::new(false) };
+    }
+    let Some(window) = web_sys::window() else { return };
+    if WATCHING.with(|w| w.replace(true)) {
+        return;
+    }
+    offline::set_online(window.navigator().on_line());
+    for (event, online) in [("online", true), ("offline", false)] {
+        let listener = Closure::<dyn FnMut()>::new(move || offline::set_online(online));
+        let _ = window.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref());
+        listener.forget();
+    }
+}
+
+/// Fill a buffer from `crypto.getRandomValues`
+pub fn fill_random(buf: &mut [u8]) -> Result<()> {
+    let window = web_sys::window().ok_or_else(|| Error::new("No window"))?;
+    window.crypto()?.get_random_values_with_u8_array(buf)?;
+    Ok(())
+}
+
+/// Write a line to the browser console at the matching console level
+pub fn log(level: Level, message: &str) {
+    let message = JsValue::from_str(message);
+    match level {
+        Level::Error => web_sys::console::error_1(&message),
+        Level::Warn => web_sys::console::warn_1(&message),
+        Level::Info => web_sys::console::info_1(&message),
+        Level::Debug => web_sys::console::debug_1(&message),
+    }
+}
@@ -0,0 +1,340 @@
+//! Tools from WebAssembly plugins
+//!
+//! A plugin is a WebAssembly module, fetched from a URL, that brings one or
+//! more tools. It runs sandboxed: it may import nothing, so all it can do
+//! is compute on the arguments it is given, with no way to the network,
+//! the page or storage, and its answers are capped, as is its memory
+//! through the maximum the module must declare. Installed plugins are
+//! remembered with the SHA-256 of their module, and one whose module
+//! changed since is refused on the next start until it is installed
+//! again. A plugin that never returns still blocks the page, as calls run
+//! on the main thread.
+//!
+//! The interface, in the plugin's exports:
+//!
+//! ```text
+//! memory                                    the plugin's memory
+//! alloc(len: i32) -> i32                    room for `len` bytes the host writes
+//! manifest() -> i32                         the manifest, as a buffer
+//! call(name, name_len, args, args_len) -> i32
+//!                                           run a tool on its JSON arguments
+//! ```
+//!
+//! A buffer is a little-endian u32 length followed by that many bytes of
+//! UTF-8. The manifest is `{"name", "version", "tools": [{"name",
+//! "description", "parameters", "tier"}]}` and a call answers
+//! `{"ok": result}` or `{"error": message}`. The host never frees
+//! anything, so a plugin can reuse its buffers from call to call.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::platform::storage;
+use crate::tools::registry;
+use crate::tools::ToolDefinition;
+
+/// localStorage key of the installed plugins
+pub const PLUGINS_KEY: &str = "clawasm_plugins";
+
+/// Largest module accepted
+pub const MAX_MODULE_BYTES: usize = 8 << 20;
+/// Most memory a plugin may grow to
+pub const MAX_MEMORY_BYTES: usize = 64 << 20;
+/// Size of a WebAssembly memory page
+const PAGE_BYTES: u64 = 64 << 10;
+/// Largest manifest or answer read back
+pub const MAX_OUTPUT_BYTES: usize = 1 << 20;
+
+/// Exports every plugin has, with their kinds
+pub const EXPORTS: [(&str, &str); 4] = [
+    ("memory", "memory"),
+    ("alloc", "function"),
+    ("manifest", "function"),
+    ("call", "function"),
+];
+
+/// What a plugin says it brings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    pub tools: Vec<ToolDefinition>,
+}
+
+/// A plugin as remembered between sessions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    /// Of the module installed, hex
+    pub sha256: String,
+    pub tools: Vec<String>,
+}
+
+/// Refuse URLs a module shouldn't come from: plain HTTP except to this
+/// machine
+pub fn check_url(url: &str) -> Result<()> {
+    let parsed = url::Url::parse(url).map_err(|e| Error::new(format!("Invalid plugin URL '{}': {}", url, e)))?;
+    let local = matches!(parsed.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    match parsed.scheme() {
+        "https" => Ok(()),
+        "http" if local => Ok(()),
+        _ => Err(Error::new(format!("Plugins are only installed over HTTPS, not from '{}'", url))),
+    }
+}
+
+/// Check a fetched module: its size, that it is WebAssembly at all, that
+/// its memory can't grow past the cap and, given one, its SHA-256; the
+/// module's SHA-256
+///
+/// The browser grows a memory up to the maximum the module declares, even
+/// in the middle of a call, so the maximum is what is checked.
+pub fn check_module(bytes: &[u8], sha256: Option<&str>) -> Result<String> {
+    if bytes.len() > MAX_MODULE_BYTES {
+        return Err(Error::new(format!("The plugin is {} bytes, more than the {} allowed", bytes.len(), MAX_MODULE_BYTES)));
+    }
+    if !bytes.starts_with(b"\0asm\x01\0\0\0") {
+        return Err(Error::new("Not a WebAssembly module (version 1)"));
+    }
+    for maximum in memory_maximums(bytes)? {
+        match maximum {
+            None => return Err(Error::new(format!(
+                "The plugin's memory has no maximum, so it could grow past the {} bytes allowed", MAX_MEMORY_BYTES))),
+            Some(pages) if pages.saturating_mul(PAGE_BYTES) > MAX_MEMORY_BYTES as u64 => return Err(Error::new(format!(
+                "The plugin's memory may grow to {} pages, more than the {} bytes allowed", pages, MAX_MEMORY_BYTES))),
+            Some(_) => {}
+        }
+    }
+    let actual = format!("{:x}", Sha256::digest(bytes));
+    match sha256.map(str::trim) {
+        Some(expected) if !expected.eq_ignore_ascii_case(&actual) => Err(Error::new(format!(
+            "The plugin's SHA-256 is {}, not the {} expected", actual, expected))),
+        _ => Ok(actual),
+    }
+}
+
+/// The maximum, in pages, of each memory the module defines, from its
+/// memory section
+fn memory_maximums(bytes: &[u8]) -> Result<Vec<Option<u64>>> {
+    let malformed = || Error::new("The plugin's sections are malformed");
+    let mut at = 8;
+    let mut maximums = Vec::new();
+    while at < bytes.len() {
+        let id = bytes[at];
+        at += 1;
+        let size = leb128(bytes, &mut at).ok_or_else(malformed)?;
+        let end = usize::try_from(size).ok().and_then(|size| at.checked_add(size)).filter(|end| *end <= bytes.len()).ok_or_else(malformed)?;
+        if id == 5 {
+            let section = &bytes[..end];
+            for _ in 0..leb128(section, &mut at).ok_or_else(malformed)? {
+                let flags = *section.get(at).ok_or_else(malformed)?;
+                at += 1;
+                let _minimum = leb128(section, &mut at).ok_or_else(malformed)?;
+                match flags {
+                    0 => maximums.push(None),
+                    1 => maximums.push(Some(leb128(section, &mut at).ok_or_else(malformed)?)),
+                    _ => return Err(Error::new("Plugins can't have shared or 64-bit memories")),
+                }
+            }
+        }
+        at = end;
+    }
+    Ok(maximums)
+}
+
+/// The unsigned LEB128 number at `at`, moving past it
+fn leb128(bytes: &[u8], at: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*at)?;
+        *at += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Check a compiled module's imports (`module.name`) and exports (name and
+/// kind) against the plugin interface
+pub fn check_interface(imports: &[String], exports: &[(String, String)]) -> Result<()> {
+    if !imports.is_empty() {
+        return Err(Error::new(format!("Plugins can't import anything, but this one imports {}", imports.join(", "))));
+    }
+    let missing: Vec<String> = EXPORTS.iter()
+        .filter(|(name, kind)| !exports.iter().any(|(n, k)| n == name && k == kind))
+        .map(|(name, kind)| format!("{} ({})", name, kind))
+        .collect();
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(Error::new(format!("The plugin lacks the export{} {}",
+            if missing.len() == 1 { "" } else { "s" }, missing.join(", ")))),
+    }
+}
+
+/// A plugin's manifest, every tool checked
+pub fn parse_manifest(json: &str) -> Result<Manifest> {
+    let mut manifest: Manifest = serde_json::from_str(json)
+        .map_err(|e| Error::new(format!("Invalid plugin manifest: {}", e)))?;
+    if !registry::valid_name(&manifest.name) {
+        return Err(Error::new(format!("Invalid plugin name '{}'", manifest.name)));
+    }
+    if manifest.tools.is_empty() {
+        return Err(Error::new(format!("Plugin '{}' brings no tools", manifest.name)));
+    }
+    manifest.tools = std::mem::take(&mut manifest.tools).into_iter()
+        .map(registry::check_external)
+        .collect::<Result<_>>()
+        .map_err(|e| Error::new(format!("Plugin '{}': {}", manifest.name, e.message())))?;
+    for (i, tool) in manifest.tools.iter().enumerate() {
+        if manifest.tools[..i].iter().any(|t| t.name == tool.name) {
+            return Err(Error::new(format!("Plugin '{}' has two tools called '{}'", manifest.name, tool.name)));
+        }
+    }
+    Ok(manifest)
+}
+
+/// Where the bytes of the buffer at `ptr` lie, given the length in its
+/// first four bytes
+pub fn output_span(memory_len: usize, ptr: usize, header: [u8; 4]) -> Result<Range<usize>> {
+    let len = u32::from_le_bytes(header) as usize;
+    if len > MAX_OUTPUT_BYTES {
+        return Err(Error::new(format!("The plugin answered {} bytes, more than the {} allowed", len, MAX_OUTPUT_BYTES)));
+    }
+    let start = ptr + 4;
+    match start.checked_add(len).filter(|end| *end <= memory_len) {
+        Some(end) => Ok(start..end),
+        None => Err(Error::new("The plugin answered with a buffer outside its memory")),
+    }
+}
+
+/// The result of a call from its answer
+pub fn parse_answer(tool: &str, bytes: &[u8]) -> Result<String> {
+    let answer: Value = std::str::from_utf8(bytes).ok()
+        .and_then(|text| serde_json::from_str(text).ok())
+        .ok_or_else(|| Error::new(format!("Tool '{}' answered with something other than JSON", tool)))?;
+    match (&answer["ok"], &answer["error"]) {
+        (_, Value::String(error)) => Err(Error::new(format!("Tool '{}' failed: {}", tool, error))),
+        (Value::String(result), _) => Ok(result.clone()),
+        (Value::Null, _) => Err(Error::new(format!("Tool '{}' answered with neither \"ok\" nor \"error\"", tool))),
+        (result, _) => Ok(result.to_string()),
+    }
+}
+
+/// The installed plugins
+pub fn load() -> Result<Vec<InstalledPlugin>> {
+    match storage::get(PLUGINS_KEY)? {
+        Some(json) => serde_json::from_str(&json).map_err(|e| Error::new(format!("Corrupt plugin list: {}", e))),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn save(plugins: &[InstalledPlugin]) -> Result<()> {
+    let json = serde_json::to_string(plugins).map_err(|e| Error::new(format!("Serialization error: {}", e)))?;
+    storage::set(PLUGINS_KEY, &json)
+}
+
+/// Remember `plugin`, in place of an earlier install of the same name
+pub fn remember(plugin: InstalledPlugin) -> Result<()> {
+    let mut plugins = load()?;
+    match plugins.iter_mut().find(|p| p.name == plugin.name) {
+        Some(existing) => *existing = plugin,
+        None => plugins.push(plugin),
+    }
+    save(&plugins)
+}
+
+/// Forget the plugin called `name`, returning what was remembered of it
+pub fn forget(name: &str) -> Result<Option<InstalledPlugin>> {
+    let mut plugins = load()?;
+    let Some(at) = plugins.iter().position(|p| p.name == name) else {
+        return Ok(None);
+    };
+    let plugin = plugins.remove(at);
+    save(&plugins)?;
+    Ok(Some(plugin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        assert!(check_url("https://plugins.example.com/qr.wasm").is_ok());
+        assert!(check_url("http://localhost:8080/qr.wasm").is_ok());
+        assert!(check_url("http://example.com/qr.wasm").is_err());
+        assert!(check_url("file:///tmp/qr.wasm").is_err());
+
+        let module = b"\0asm\x01\0\0\0";
+        let sha256 = check_module(module, None).unwrap();
+        assert_eq!(check_module(module, Some(&sha256.to_uppercase())).unwrap(), sha256);
+        assert!(check_module(module, Some("00")).unwrap_err().message().contains("not the 00 expected"));
+        assert!(check_module(b"<html>", None).is_err());
+        assert!(check_module(b"\0asm\x01\0\0\0\x05\x09", None).unwrap_err().message().contains("malformed"));
+
+        let exports: Vec<(String, String)> = EXPORTS.iter().map(|(n, k)| (n.to_string(), k.to_string())).collect();
+        assert!(check_interface(&[], &exports).is_ok());
+        assert_eq!(check_interface(&["env.fetch".to_string()], &exports).unwrap_err().message(),
+            "Plugins can't import anything, but this one imports env.fetch");
+        assert_eq!(check_interface(&[], &exports[1..]).unwrap_err().message(), "The plugin lacks the export memory (memory)");
+    }
+
+    /// A module whose function grows its memory by 2048 pages, 128 MiB,
+    /// with the memory `limits` given
+    fn growing_module(limits: &[u8]) -> Vec<u8> {
+        let mut module = b"\0asm\x01\0\0\0".to_vec();
+        module.extend([1, 5, 1, 0x60, 0, 1, 0x7f]);
+        module.extend([3, 2, 1, 0]);
+        module.extend([5, limits.len() as u8 + 1, 1]);
+        module.extend(limits);
+        module.extend([10, 9, 1, 7, 0, 0x41, 0x80, 0x10, 0x40, 0, 0x0b]);
+        module
+    }
+
+    #[test]
+    fn test_memory_maximum() {
+        assert!(check_module(&growing_module(&[0, 1]), None).unwrap_err().message().contains("has no maximum"));
+        assert!(check_module(&growing_module(&[1, 1, 0x81, 0x08]), None).unwrap_err().message()
+            .contains("may grow to 1025 pages"));
+        assert!(check_module(&growing_module(&[3, 1, 1]), None).is_err());
+        assert!(check_module(&growing_module(&[1, 1, 0x80, 0x08]), None).is_ok());
+        assert_eq!(memory_maximums(&growing_module(&[1, 1, 0x80, 0x08])).unwrap(), vec![Some(1024)]);
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(r#"{"name": "qr", "version": "1.0.0", "tools": [
+            {"name": "make_qr", "description": "Encode text as a QR code", "parameters": {"properties": {"text": {"type": "string"}}, "required": ["text"]}},
+            {"name": "qr_version", "description": "Smallest QR version for a length"}
+        ]}"#).unwrap();
+        assert_eq!(manifest.tools[0].parameters["type"], "object");
+        assert_eq!(manifest.tools[1].parameters, serde_json::json!({"type": "object", "properties": {}}));
+
+        let error = |json: &str| parse_manifest(json).unwrap_err().message().to_string();
+        assert_eq!(error(r#"{"name": "qr", "tools": []}"#), "Plugin 'qr' brings no tools");
+        assert!(error(r#"{"name": "qr", "tools": [{"name": "a b", "description": "x"}]}"#).starts_with("Plugin 'qr': Invalid tool name 'a b'"));
+        assert!(error(r#"{"name": "qr", "tools": [{"name": "a", "description": "x"}, {"name": "a", "description": "y"}]}"#).contains("two tools called 'a'"));
+        assert!(error(r#"{"name": "qr", "tools": [{"name": "a", "description": "x", "parameters": {"type": "string"}}]}"#).contains("must be an object schema"));
+    }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(output_span(100, 10, 5u32.to_le_bytes()).unwrap(), 14..19);
+        assert!(output_span(100, 90, 20u32.to_le_bytes()).is_err());
+        assert!(output_span(usize::MAX, 0, u32::MAX.to_le_bytes()).is_err());
+
+        assert_eq!(parse_answer("t", br#"{"ok": "done"}"#).unwrap(), "done");
+        assert_eq!(parse_answer("t", br#"{"ok": {"n": 1}}"#).unwrap(), r#"{"n":1}"#);
+        assert_eq!(parse_answer("t", br#"{"error": "bad input"}"#).unwrap_err().message(), "Tool 't' failed: bad input");
+        assert!(parse_answer("t", b"\xff").is_err());
+        assert!(parse_answer("t", b"{}").is_err());
+    }
+}
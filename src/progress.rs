@@ -0,0 +1,69 @@
+//! Progress of long-running tools for the UI
+//!
+//! A tool that takes a while (deep research reads a dozen pages) reports
+//! what it is doing, e.g. "Reading source 3/8", so the UI can show more
+//! than a spinner. The web build forwards reports to the callback given to
+//! `ClaWasm.onProgress()`; with no listener they only reach the debug log.
+
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::logger;
+
+/// One progress report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    /// The tool reporting
+    pub tool: String,
+    pub message: String,
+    /// Current step and step count, when the work is counted
+    pub step: Option<usize>,
+    pub total: Option<usize>,
+}
+
+pub type Listener = Rc<dyn Fn(&Progress)>;
+
+thread_local! {
+    static LISTENER: RefCell<Option<Listener>> = const { RefCell::new(None) };
+}
+
+/// Send reports to `listener`, or nowhere with `None`
+pub fn set_listener(listener: Option<Listener>) {
+    LISTENER.with(|l| *l.borrow_mut() = listener);
+}
+
+/// Report `message` from `tool`, as step `step` of `total` when given
+pub fn report(tool: &str, message: &str, step: Option<(usize, usize)>) {
+    logger::debug(tool, message);
+    let progress = Progress {
+        tool: tool.to_string(),
+        message: message.to_string(),
+        step: step.map(|(step, _)| step),
+        total: step.map(|(_, total)| total),
+    };
+    // Cloned out so a listener may replace itself
+    let listener = LISTENER.with(|l| l.borrow().clone());
+    if let Some(listener) = listener {
+        listener(&progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listener() {
+        let seen: Rc<RefCell<Vec<Progress>>> = Rc::default();
+        let sink = seen.clone();
+        set_listener(Some(Rc::new(move |p: &Progress| sink.borrow_mut().push(p.clone()))));
+        report("research", "Reading source 3/8", Some((3, 8)));
+        set_listener(None);
+        report("research", "Done", None);
+
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 1);
+        assert_eq!((seen[0].step, seen[0].total), (Some(3), Some(8)));
+    }
+}
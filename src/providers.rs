@@ -5,12 +5,15 @@
 use crate::chat::{Message, Role};
 use crate::config::Config;
 use serde::Deserialize;
-use std::collections::HashMap;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
-use wasm_bindgen::JsCast;
+use std::future::Future;
+use std::pin::Pin;
 
+use crate::audit::{self, AuditKind};
+use crate::error::{Error, Result};
+use crate::logger;
+use crate::platform::{self, HttpRequest};
+use crate::reasoning;
+use crate::usage::{self, Tokens};
 use crate::tools::get_tools_openai_format;
 
 pub const AVAILABLE_PROVIDERS: &[&str] = &[
@@ -23,6 +26,90 @@ pub const AVAILABLE_PROVIDERS: &[&str] = &[
     "custom",
 ];
 
+/// Model a provider starts with before the user picks one
+pub fn default_model(name: &str) -> &'static str {
+    match name {
+        "anthropic" => "claude-3-haiku-20240307",
+        "ollama" => "llama3.2",
+        "ollama_cloud" => "glm-5:cloud",
+        "groq" => "llama-3.1-70b-versatile",
+        "together" => "meta-llama/Llama-3-70b-chat-hf",
+        _ => "gpt-4o-mini",
+    }
+}
+
+/// Request format a sampling setting has to be expressed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiStyle {
+    /// OpenAI chat completions (also Groq, Together, Ollama's /v1)
+    OpenAI,
+    Anthropic,
+    /// Ollama /api/chat, where sampling goes under "options"
+    OllamaNative,
+}
+
+/// Sampling parameters from the config in the shape each API expects
+///
+/// Settings an API has no equivalent for are left out rather than sent
+/// and rejected.
+pub fn sampling_params(style: ApiStyle, config: &Config) -> serde_json::Map<String, serde_json::Value> {
+    let mut params = serde_json::Map::new();
+    let mut set = |key: &str, value: serde_json::Value| {
+        params.insert(key.to_string(), value);
+    };
+    match style {
+        ApiStyle::OpenAI | ApiStyle::OllamaNative => {
+            let native = style == ApiStyle::OllamaNative;
+            set(if native { "num_predict" } else { "max_tokens" }, config.max_tokens.into());
+            set("temperature", config.temperature.into());
+            if let Some(top_p) = config.top_p {
+                set("top_p", top_p.into());
+            }
+            if let Some(penalty) = config.frequency_penalty {
+                set("frequency_penalty", penalty.into());
+            }
+            if let Some(penalty) = config.presence_penalty {
+                set("presence_penalty", penalty.into());
+            }
+            if !config.stop.is_empty() {
+                set("stop", config.stop.clone().into());
+            }
+            if let Some(seed) = config.seed {
+                set("seed", seed.into());
+            }
+        }
+        ApiStyle::Anthropic => {
+            set("max_tokens", config.max_tokens.into());
+            // Anthropic accepts 0.0 to 1.0
+            set("temperature", config.temperature.min(1.0).into());
+            if let Some(top_p) = config.top_p {
+                set("top_p", top_p.into());
+            }
+            if !config.stop.is_empty() {
+                set("stop_sequences", config.stop.clone().into());
+            }
+        }
+    }
+    params
+}
+
+/// Future returned by a `ChatModel`
+pub type ChatFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + 'a>>;
+
+/// Something that answers a conversation
+///
+/// `Provider` is the only production implementation; the seam exists so
+/// the agent loop can run against a scripted model in tests.
+pub trait ChatModel {
+    fn complete<'a>(&'a self, messages: &'a [Message], config: &'a Config) -> ChatFuture<'a>;
+}
+
+impl ChatModel for Provider {
+    fn complete<'a>(&'a self, messages: &'a [Message], config: &'a Config) -> ChatFuture<'a> {
+        Box::pin(self.chat(messages, config))
+    }
+}
+
 /// Provider enum (simpler than trait for WASM)
 #[derive(Debug, Clone)]
 pub enum Provider {
@@ -60,25 +147,40 @@ impl Provider {
     }
 
     /// Send a chat completion request
-    pub async fn chat(&self, messages: &[Message], config: &Config) -> Result<String, JsValue> {
-        match self {
+    ///
+    /// Refused once the provider's blocking budget is used up; the tokens of
+    /// every reply are added to its monthly usage.
+    pub async fn chat(&self, messages: &[Message], config: &Config) -> Result<String> {
+        let provider = config.provider.active.as_str();
+        usage::check(provider, config)?;
+        let result = match self {
             Provider::OpenAI { base_url } => self.chat_openai(messages, config, base_url).await,
             Provider::Anthropic => self.chat_anthropic(messages, config).await,
             Provider::Ollama { base_url, .. } => self.chat_ollama(messages, config, base_url).await,
+        };
+        
+        let subject = format!("{}/{}", provider, config.provider.model);
+        let details = format!("{} messages", messages.len());
+        match &result {
+            Ok(_) => audit::record(AuditKind::ProviderRequest, &subject, "ok", &details),
+            Err(e) => audit::record(AuditKind::ProviderRequest, &subject, "error", &format!("{}: {}", details, e)),
+        }
+        let (reply, tokens) = result?;
+        let recorded = match tokens {
+            Some(tokens) => usage::record(provider, tokens, false, config),
+            None => usage::record(provider, Tokens::estimate(messages, &reply), true, config),
+        };
+        if let Err(e) = recorded {
+            logger::error("usage", &format!("Could not record usage: {}", e));
         }
+        Ok(reply)
     }
 
-    async fn chat_openai(&self, messages: &[Message], config: &Config, base_url: &str) -> Result<String, JsValue> {
+    async fn chat_openai(&self, messages: &[Message], config: &Config, base_url: &str) -> Result<(String, Option<Tokens>)> {
         let api_key = config.provider.api_key.as_ref()
-            .ok_or_else(|| JsValue::from_str("API key not set"))?;
+            .ok_or_else(|| Error::new("API key not set"))?;
         
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        
-        let headers = Headers::new()?;
-        headers.set("Content-Type", "application/json")?;
-        headers.set("Authorization", &format!("Bearer {}", api_key))?;
-        
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "model": config.provider.model,
             "messages": messages.iter().map(|m| serde_json::json!({
                 "role": match m.role {
@@ -88,47 +190,27 @@ impl Provider {
                 },
                 "content": m.content,
             })).collect::<Vec<_>>(),
-            "max_tokens": config.max_tokens,
-            "temperature": config.temperature,
             "tools": get_tools_openai_format(),
         });
+        body.as_object_mut().unwrap().extend(sampling_params(ApiStyle::OpenAI, config));
         
-        let mut request_init = RequestInit::new();
-        request_init.method("POST");
-        request_init.headers(headers.as_ref());
-        request_init.body(Some(&JsValue::from_str(&serde_json::to_string(&body).unwrap())));
-        request_init.mode(RequestMode::Cors);
-        
-        let request = Request::new_with_str_and_init(
-            &format!("{}/chat/completions", base_url),
-            &request_init,
-        )?;
-        
-        let response = JsFuture::from(window.fetch_with_request(&request)).await?;
-        let response: Response = response.dyn_into()?;
+        let request = HttpRequest::post_json(&format!("{}/chat/completions", base_url), &body)
+            .header("Authorization", &format!("Bearer {}", api_key));
+        let response = platform::fetch(request).await?;
         
         if !response.ok() {
-            let error_text = JsFuture::from(response.text()?).await?;
-            return Err(JsValue::from_str(&format!("API error: {}", error_text.as_string().unwrap_or_default())));
+            return Err(Error::new(format!("API error: {}", response.body)));
         }
         
-        let json = JsFuture::from(response.json()?).await?;
-        let result: OpenAIResponse = serde_wasm_bindgen::from_value(json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        let result: OpenAIResponse = response.json()?;
         
-        Ok(result.choices[0].message.content.clone())
+        let message = &result.choices[0].message;
+        Ok((reasoning::wrap(message.thinking(), &message.content), result.usage.map(Tokens::from)))
     }
 
-    async fn chat_anthropic(&self, messages: &[Message], config: &Config) -> Result<String, JsValue> {
+    async fn chat_anthropic(&self, messages: &[Message], config: &Config) -> Result<(String, Option<Tokens>)> {
         let api_key = config.provider.api_key.as_ref()
-            .ok_or_else(|| JsValue::from_str("API key not set"))?;
-        
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        
-        let headers = Headers::new()?;
-        headers.set("Content-Type", "application/json")?;
-        headers.set("x-api-key", api_key)?;
-        headers.set("anthropic-version", "2023-06-01")?;
+            .ok_or_else(|| Error::new("API key not set"))?;
         
         // Extract system prompt and other messages
         let system_prompt: String = messages
@@ -151,69 +233,46 @@ impl Provider {
             }))
             .collect();
         
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "model": config.provider.model,
-            "max_tokens": config.max_tokens,
             "system": system_prompt,
             "messages": anthropic_messages,
         });
+        body.as_object_mut().unwrap().extend(sampling_params(ApiStyle::Anthropic, config));
         
-        let mut request_init = RequestInit::new();
-        request_init.method("POST");
-        request_init.headers(headers.as_ref());
-        request_init.body(Some(&JsValue::from_str(&serde_json::to_string(&body).unwrap())));
-        request_init.mode(RequestMode::Cors);
-        
-        let request = Request::new_with_str_and_init(
-            "https://api.anthropic.com/v1/messages",
-            &request_init,
-        )?;
-        
-        let response = JsFuture::from(window.fetch_with_request(&request)).await?;
-        let response: Response = response.dyn_into()?;
+        let request = HttpRequest::post_json("https://api.anthropic.com/v1/messages", &body)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01");
+        let response = platform::fetch(request).await?;
         
         if !response.ok() {
-            let error_text = JsFuture::from(response.text()?).await?;
-            return Err(JsValue::from_str(&format!("API error: {}", error_text.as_string().unwrap_or_default())));
+            return Err(Error::new(format!("API error: {}", response.body)));
         }
         
-        let json = JsFuture::from(response.json()?).await?;
-        let result: AnthropicResponse = serde_wasm_bindgen::from_value(json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        let result: AnthropicResponse = response.json()?;
         
-        let content = result.content
-            .into_iter()
-            .filter_map(|c| if c.content_type == "text" { Some(c.text) } else { None })
+        let content = result.content.iter()
+            .filter(|c| c.content_type == "text")
+            .map(|c| c.text.as_str())
+            .collect::<String>();
+        let thinking = result.content.iter()
+            .filter_map(|c| c.thinking.as_deref())
             .collect::<Vec<_>>()
-            .join("");
+            .join("\n\n");
+        let content = reasoning::wrap(Some(&thinking), &content);
+        let tokens = result.usage.map(|u| Tokens { input: u.input_tokens, output: u.output_tokens });
         
-        Ok(content)
+        Ok((content, tokens))
     }
 
-    async fn chat_ollama(&self, messages: &[Message], config: &Config, base_url: &str) -> Result<String, JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        
+    async fn chat_ollama(&self, messages: &[Message], config: &Config, base_url: &str) -> Result<(String, Option<Tokens>)> {
         // Normalize model name (remove :cloud suffix if present)
         let model = config.provider.model.replace(":cloud", "");
         
-        // Check if this is Ollama Cloud - route through proxy for CORS
+        // Ollama Cloud is cross-origin; a local Ollama allows the page's origin
         let is_ollama_cloud = base_url.contains("ollama.com");
         
-        let endpoint = if is_ollama_cloud {
-            // Use proxy for Ollama Cloud
-            "http://localhost:3000/proxy".to_string()
-        } else {
-            // Direct connection for local Ollama
-            format!("{}/v1/chat/completions", base_url)
-        };
-        
-        let actual_url = if is_ollama_cloud {
-            format!("{}/v1/chat/completions", base_url)
-        } else {
-            endpoint.clone()
-        };
-        
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "model": model,
             "messages": messages.iter().map(|m| serde_json::json!({
                 "role": match m.role {
@@ -226,49 +285,21 @@ impl Provider {
             "stream": false,
             "tools": get_tools_openai_format(),
         });
+        body.as_object_mut().unwrap().extend(sampling_params(ApiStyle::OpenAI, config));
         
-        let headers = Headers::new()?;
-        headers.set("Content-Type", "application/json")?;
-        
-        // Add API key if available
+        let mut request = HttpRequest::post_json(&format!("{}/v1/chat/completions", base_url), &body);
         if let Some(ref api_key) = config.provider.api_key {
-            headers.set("Authorization", &format!("Bearer {}", api_key))?;
+            request = request.header("Authorization", &format!("Bearer {}", api_key));
         }
-        
-        let mut request_init = RequestInit::new();
-        request_init.set_method("POST");
-        request_init.set_headers(headers.as_ref());
-        
-        // For Ollama Cloud via proxy, wrap the request
-        let request_body = if is_ollama_cloud {
-            let mut proxy_headers = HashMap::new();
-            proxy_headers.insert("Content-Type".to_string(), "application/json".to_string());
-            if let Some(ref api_key) = config.provider.api_key {
-                proxy_headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
-            }
-            
-            serde_json::json!({
-                "url": actual_url,
-                "method": "POST",
-                "headers": proxy_headers,
-                "body": serde_json::to_string(&body).unwrap()
-            })
+        let response = if is_ollama_cloud {
+            platform::fetch_cross_origin(request, &config.proxy_url).await?
         } else {
-            body.clone()
+            platform::fetch(request).await?
         };
         
-        request_init.set_body(&JsValue::from_str(&serde_json::to_string(&request_body).unwrap()));
-        request_init.set_mode(RequestMode::Cors);
-        
-        let request = Request::new_with_str_and_init(&endpoint, &request_init)?;
-        
-        let response = JsFuture::from(window.fetch_with_request(&request)).await?;
-        let response: Response = response.dyn_into()?;
-        
         if !response.ok() {
-            let status = response.status();
-            let error_text = JsFuture::from(response.text()?).await?;
-            let error_str = error_text.as_string().unwrap_or_default();
+            let status = response.status;
+            let error_str = &response.body;
             
             // If OpenAI-compatible fails for local Ollama, try native API
             if !is_ollama_cloud && (error_str.contains("404") || error_str.contains("Not Found")) {
@@ -277,12 +308,12 @@ impl Provider {
             
             // Clear error for unauthorized
             if status == 401 || error_str.contains("unauthorized") || error_str.contains("Unauthorized") {
-                return Err(JsValue::from_str(
+                return Err(Error::new(
                     "Ollama Cloud API key required. Go to Settings and enter your Ollama Cloud API key."
                 ));
             }
             
-            return Err(JsValue::from_str(&format!(
+            return Err(Error::new(format!(
                 "Ollama error ({}): {}. Make sure {} is running",
                 status,
                 error_str,
@@ -291,9 +322,8 @@ impl Provider {
         }
         
         // Parse OpenAI-compatible response
-        let json = JsFuture::from(response.json()?).await?;
-        let result: OpenAIResponse = serde_wasm_bindgen::from_value(json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        let result: OpenAIResponse = response.json()?;
+        let tokens = result.usage.map(Tokens::from);
         
         let message = &result.choices[0].message;
         
@@ -304,20 +334,19 @@ impl Provider {
                 // Return as JSON string that parse_tool_call can find
                 let args: serde_json::Value = serde_json::from_str(&tc.function.arguments)
                     .unwrap_or(serde_json::json!({}));
-                return Ok(serde_json::to_string(&serde_json::json!({
+                let call = serde_json::to_string(&serde_json::json!({
                     "name": tc.function.name,
                     "arguments": args
-                })).unwrap_or_else(|_| message.content.clone()));
+                })).unwrap_or_else(|_| message.content.clone());
+                return Ok((reasoning::wrap(message.thinking(), &call), tokens));
             }
         }
         
-        Ok(message.content.clone())
+        Ok((reasoning::wrap(message.thinking(), &message.content), tokens))
     }
     
     /// Fallback to native Ollama API if OpenAI-compatible fails
-    async fn chat_ollama_native(&self, messages: &[Message], config: &Config, base_url: &str) -> Result<String, JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-        
+    async fn chat_ollama_native(&self, messages: &[Message], config: &Config, base_url: &str) -> Result<(String, Option<Tokens>)> {
         let model = config.provider.model.replace(":cloud", "");
         
         let body = serde_json::json!({
@@ -331,38 +360,26 @@ impl Provider {
                 "content": m.content,
             })).collect::<Vec<_>>(),
             "stream": false,
+            "options": sampling_params(ApiStyle::OllamaNative, config),
         });
         
-        let headers = Headers::new()?;
-        headers.set("Content-Type", "application/json")?;
-        
-        let mut request_init = RequestInit::new();
-        request_init.method("POST");
-        request_init.headers(headers.as_ref());
-        request_init.body(Some(&JsValue::from_str(&serde_json::to_string(&body).unwrap())));
-        request_init.mode(RequestMode::Cors);
-        
-        let request = Request::new_with_str_and_init(
-            &format!("{}/api/chat", base_url),
-            &request_init,
-        )?;
-        
-        let response = JsFuture::from(window.fetch_with_request(&request)).await?;
-        let response: Response = response.dyn_into()?;
+        let request = HttpRequest::post_json(&format!("{}/api/chat", base_url), &body);
+        let response = platform::fetch(request).await?;
         
         if !response.ok() {
-            let error_text = JsFuture::from(response.text()?).await?;
-            return Err(JsValue::from_str(&format!(
+            return Err(Error::new(format!(
                 "Ollama native error: {}. Make sure Ollama is running (ollama serve)",
-                error_text.as_string().unwrap_or_default()
+                response.body
             )));
         }
         
-        let json = JsFuture::from(response.json()?).await?;
-        let result: OllamaResponse = serde_wasm_bindgen::from_value(json)
-            .map_err(|e| JsValue::from_str(&format!("Parse error: {}", e)))?;
+        let result: OllamaResponse = response.json()?;
+        let tokens = match (result.prompt_eval_count, result.eval_count) {
+            (None, None) => None,
+            (input, output) => Some(Tokens { input: input.unwrap_or(0), output: output.unwrap_or(0) }),
+        };
         
-        Ok(result.message.content)
+        Ok((reasoning::wrap(result.message.thinking.as_deref(), &result.message.content), tokens))
     }
 }
 
@@ -370,6 +387,22 @@ impl Provider {
 #[derive(Debug, Deserialize)]
 struct OpenAIResponse {
     choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+impl From<OpenAIUsage> for Tokens {
+    fn from(usage: OpenAIUsage) -> Self {
+        Tokens { input: usage.prompt_tokens, output: usage.completion_tokens }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -381,12 +414,21 @@ struct OpenAIChoice {
 struct OpenAIMessage {
     #[serde(default)]
     content: String,
+    /// Thinking of reasoning models, under either name
     #[serde(default)]
     reasoning: Option<String>,
     #[serde(default)]
+    reasoning_content: Option<String>,
+    #[serde(default)]
     tool_calls: Option<Vec<ToolCall>>,
 }
 
+impl OpenAIMessage {
+    fn thinking(&self) -> Option<&str> {
+        self.reasoning.as_deref().or(self.reasoning_content.as_deref())
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 struct ToolCall {
     #[serde(default)]
@@ -407,21 +449,99 @@ struct ToolCallFunction {
 #[derive(Debug, Deserialize)]
 struct AnthropicResponse {
     content: Vec<AnthropicContent>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
 }
 
 #[derive(Debug, Deserialize)]
 struct AnthropicContent {
     #[serde(rename = "type")]
     content_type: String,
+    #[serde(default)]
     text: String,
+    /// Set on "thinking" blocks
+    #[serde(default)]
+    thinking: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OllamaResponse {
     message: OllamaMessage,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
 struct OllamaMessage {
     content: String,
+    #[serde(default)]
+    thinking: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_params_per_api() {
+        let config = Config {
+            temperature: 1.5,
+            top_p: Some(0.9),
+            presence_penalty: Some(0.5),
+            stop: vec!["END".to_string()],
+            seed: Some(42),
+            ..Config::default()
+        };
+        
+        let openai = serde_json::Value::Object(sampling_params(ApiStyle::OpenAI, &config));
+        assert_eq!(openai["seed"], 42);
+        assert_eq!(openai["stop"], serde_json::json!(["END"]));
+        assert!(openai.get("frequency_penalty").is_none());
+        
+        let anthropic = serde_json::Value::Object(sampling_params(ApiStyle::Anthropic, &config));
+        assert_eq!(anthropic["temperature"], 1.0);
+        assert_eq!(anthropic["stop_sequences"], serde_json::json!(["END"]));
+        assert!(anthropic.get("seed").is_none() && anthropic.get("presence_penalty").is_none());
+        
+        let ollama = serde_json::Value::Object(sampling_params(ApiStyle::OllamaNative, &config));
+        assert_eq!(ollama["num_predict"], 8192);
+        assert!(ollama.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn test_openai_request_and_reply_over_mock_transport() {
+        use crate::platform::transport::tests::block_on;
+        use crate::platform::{reset_transport, set_transport, MockTransport};
+        use std::rc::Rc;
+
+        let mock = Rc::new(MockTransport::new());
+        mock.respond_json("https://api.test/v1/chat/completions", &serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "pong" } }],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15 }
+        }));
+        set_transport(mock.clone());
+
+        let mut config = Config::default();
+        config.provider.api_key = Some("sk-test".to_string());
+        let provider = Provider::from_name("openai", Some("https://api.test/v1"));
+        let reply = block_on(provider.chat_openai(&[Message::user("ping")], &config, "https://api.test/v1"));
+        reset_transport();
+
+        assert_eq!(reply.unwrap(), ("pong".to_string(), Some(Tokens { input: 12, output: 3 })));
+        let request = &mock.requests()[0];
+        assert_eq!(request.headers.iter().find(|(k, _)| k == "Authorization").map(|(_, v)| v.as_str()), Some("Bearer sk-test"));
+        let body: serde_json::Value = serde_json::from_str(request.body.as_deref().unwrap()).unwrap();
+        assert_eq!(body["messages"][0]["content"], "ping");
+        assert_eq!(body["model"], config.provider.model);
+    }
 }
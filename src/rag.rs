@@ -0,0 +1,133 @@
+//! RAG mode: retrieved knowledge added to each user message
+//!
+//! With `rag.enabled`, the agent searches ingested documents (the configured
+//! collections, each with its retrieval settings) and memories for the
+//! user's message before asking the model, and puts the best passages in
+//! front of it, numbered with their sources. The model answers from them
+//! without having to call `ask_document` or `memory_recall` first. Only the
+//! copy sent to the provider changes; the chat history keeps what the user
+//! wrote.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::chat::{Message, Role};
+use crate::config::RagConfig;
+use crate::documents::{self, Scope};
+use crate::error::Result;
+use crate::memory::{self, MemorySystem};
+
+/// A passage found for a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Retrieved {
+    /// Source marker, e.g. "notes / login.md, part 2/5" or "memory, 2026-10-01"
+    pub label: String,
+    pub text: String,
+    pub score: f32,
+}
+
+/// The best `top_k` passages for `query` from the configured collections
+/// and memories, best first
+pub async fn retrieve(memory: &Rc<RefCell<MemorySystem>>, config: &RagConfig, query: &str) -> Result<Vec<Retrieved>> {
+    let mut found = Vec::new();
+    let limit = Some(config.top_k);
+    let scopes: Vec<Scope> = if config.collections.is_empty() {
+        vec![Scope { limit, ..Scope::default() }]
+    } else {
+        config.collections.iter()
+            .map(|c| Scope { collection: Some(c.as_str()), limit, ..Scope::default() })
+            .collect()
+    };
+    for scope in scopes {
+        found.extend(documents::search(memory, query, scope).await?.into_iter().map(|p| Retrieved {
+            label: p.label(),
+            text: p.text,
+            score: p.score,
+        }));
+    }
+    if config.memory {
+        found.extend(memory::recall_shared(memory, query, config.top_k).await?.into_iter().map(|r| Retrieved {
+            label: format!("memory, {}", chrono::DateTime::from_timestamp(r.entry.created_at, 0)
+                .map(|t| t.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()),
+            text: r.entry.content,
+            score: r.score,
+        }));
+    }
+
+    found.retain(|r| r.score >= config.min_score);
+    found.sort_by(|a, b| b.score.total_cmp(&a.score));
+    found.truncate(config.top_k);
+    Ok(found)
+}
+
+/// `message` preceded by the numbered passages that fit in `max_chars`
+pub fn augment(message: &str, retrieved: &[Retrieved], max_chars: usize) -> String {
+    let mut context = String::new();
+    let mut used = 0;
+    for (i, passage) in retrieved.iter().enumerate() {
+        let entry = format!("[{}] {}\n{}\n\n", i + 1, passage.label, passage.text);
+        used += entry.chars().count();
+        if used > max_chars && i > 0 {
+            break;
+        }
+        context.push_str(&entry);
+    }
+    format!(
+        "Knowledge retrieved for this message. Cite it as [n] where you use it; ignore what does not apply.\n\n{}---\n\n{}",
+        context, message
+    )
+}
+
+/// Add retrieved knowledge to the last message, if it is the user's,
+/// returning the number of passages added
+pub async fn inject(memory: &Rc<RefCell<MemorySystem>>, config: &RagConfig, messages: &mut [Message]) -> Result<usize> {
+    let Some(last) = messages.last_mut().filter(|m| matches!(m.role, Role::User) && !m.content.trim().is_empty()) else {
+        return Ok(0);
+    };
+    let retrieved = retrieve(memory, config, &last.content).await?;
+    if !retrieved.is_empty() {
+        last.content = augment(&last.content, &retrieved, config.max_chars);
+    }
+    Ok(retrieved.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{EmbeddingProvider, MemoryBackend, MemoryConfig};
+    use crate::platform::transport::tests::block_on;
+
+    #[test]
+    fn test_inject() {
+        let memory = Rc::new(RefCell::new(MemorySystem::new(MemoryConfig {
+            backend: MemoryBackend::None,
+            embedding_provider: EmbeddingProvider::Local,
+            ..MemoryConfig::default()
+        })));
+        block_on(documents::ingest(&memory, documents::DEFAULT_COLLECTION, "terms.txt", "upload",
+            "Invoices are due within thirty days. Late invoices accrue two percent interest per month.")).unwrap();
+        block_on(memory::save_shared(&memory, "The user pays invoices when they are due, by bank transfer", serde_json::json!({}))).unwrap();
+        block_on(memory::save_shared(&memory, "The user's cat is called Miso", serde_json::json!({}))).unwrap();
+        let config = RagConfig { enabled: true, ..RagConfig::default() };
+
+        let mut messages = vec![Message::system("persona"), Message::user("When are invoices due?")];
+        assert_eq!(block_on(inject(&memory, &config, &mut messages)).unwrap(), 2);
+        let content = &messages[1].content;
+        assert!(content.contains("] terms.txt, part 1/1 (upload)\nInvoices are due"), "{}", content);
+        assert!(content.contains("] memory, ") && !content.contains("Miso"));
+        assert!(content.ends_with("---\n\nWhen are invoices due?"));
+
+        let config = RagConfig { collections: vec!["missing".to_string()], ..config };
+        let mut messages = vec![Message::user("When are invoices due?")];
+        assert!(block_on(inject(&memory, &config, &mut messages)).unwrap_err().to_string().contains("No collection named 'missing'"));
+        assert_eq!(messages[0].content, "When are invoices due?");
+    }
+
+    #[test]
+    fn test_augment_budget() {
+        let passage = |label: &str| Retrieved { label: label.to_string(), text: "x".repeat(300), score: 0.5 };
+        let out = augment("question", &[passage("a"), passage("b")], 400);
+        assert!(out.contains("[1] a") && !out.contains("[2] b"));
+    }
+}
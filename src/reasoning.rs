@@ -0,0 +1,111 @@
+//! Reasoning ("thinking") text of models
+//!
+//! Reasoning models send their thinking apart from the answer: a
+//! `reasoning` or `reasoning_content` field (OpenAI-compatible servers,
+//! Ollama, Groq), `thinking` blocks (Anthropic, Ollama's native API) or
+//! `<think>` tags inside the content (DeepSeek-R1, Qwen and other local
+//! models). Providers put all of them into one leading `<think>` block;
+//! the agent takes it back out with `split` before the reply is searched
+//! for tool calls or shown, and `ReasoningMode` decides what happens to it.
+
+use serde::{Deserialize, Serialize};
+
+/// What happens to a model's reasoning
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningMode {
+    /// Dropped
+    #[default]
+    Strip,
+    /// Dropped after going to the log
+    Log,
+    /// Returned next to the answer in verbose results
+    Expose,
+}
+
+/// Opening and closing tags models wrap their thinking in
+const TAGS: [(&str, &str); 2] = [("<think>", "</think>"), ("<thinking>", "</thinking>")];
+
+/// `content` with `reasoning` in front of it as a `<think>` block
+pub fn wrap(reasoning: Option<&str>, content: &str) -> String {
+    match reasoning.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(reasoning) => format!("<think>\n{}\n</think>\n{}", reasoning, content),
+        None => content.to_string(),
+    }
+}
+
+/// The earliest of `tags` in `text`: its position and which one
+fn find_first(text: &str, tags: impl Iterator<Item = &'static str>) -> Option<(usize, &'static str)> {
+    tags.filter_map(|tag| text.find(tag).map(|at| (at, tag))).min_by_key(|(at, _)| *at)
+}
+
+/// A reply's answer and its thinking, if any
+///
+/// Every tagged block comes out. A block left open (a truncated reply)
+/// runs to the end, and text before a closing tag with no opening one
+/// (chat templates that put `<think>` in the prompt) counts as thinking.
+pub fn split(text: &str) -> (String, Option<String>) {
+    let mut answer = String::new();
+    let mut thoughts: Vec<&str> = Vec::new();
+    let mut rest = text;
+    loop {
+        let open = find_first(rest, TAGS.iter().map(|(open, _)| *open));
+        let close = find_first(rest, TAGS.iter().map(|(_, close)| *close));
+        match (open, close) {
+            (_, Some((at, tag))) if open.is_none_or(|(open_at, _)| at < open_at) => {
+                if answer.trim().is_empty() && thoughts.is_empty() {
+                    thoughts.push(&rest[..at]);
+                } else {
+                    answer.push_str(&rest[..at]);
+                }
+                rest = &rest[at + tag.len()..];
+            }
+            (Some((at, tag)), _) => {
+                answer.push_str(&rest[..at]);
+                let inner = &rest[at + tag.len()..];
+                let close = TAGS.iter().find(|(open, _)| *open == tag).map(|(_, close)| *close).unwrap_or_default();
+                match inner.find(close) {
+                    Some(end) => {
+                        thoughts.push(&inner[..end]);
+                        rest = &inner[end + close.len()..];
+                    }
+                    None => {
+                        thoughts.push(inner);
+                        rest = "";
+                    }
+                }
+            }
+            _ => {
+                answer.push_str(rest);
+                break;
+            }
+        }
+    }
+    let thinking: Vec<&str> = thoughts.iter().map(|t| t.trim()).filter(|t| !t.is_empty()).collect();
+    let thinking = (!thinking.is_empty()).then(|| thinking.join("\n\n"));
+    (answer.trim().to_string(), thinking)
+}
+
+/// `text` without its thinking
+pub fn strip(text: &str) -> String {
+    split(text).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("<think>\nThe user wants 6*7.\n</think>\n\nIt is 42."),
+            ("It is 42.".to_string(), Some("The user wants 6*7.".to_string())));
+        assert_eq!(split(&wrap(Some("plan"), "{\"name\": \"calculate\"}")),
+            ("{\"name\": \"calculate\"}".to_string(), Some("plan".to_string())));
+        assert_eq!(split("Plain answer."), ("Plain answer.".to_string(), None));
+        // Opening tag in the prompt, unclosed block at the end, <thinking>
+        assert_eq!(split("weighing options</think>A <thinking>hmm</thinking>B <think>cut off"),
+            ("A B".to_string(), Some("weighing options\n\nhmm\n\ncut off".to_string())));
+        assert_eq!(split("<think></think>Hi"), ("Hi".to_string(), None));
+        assert_eq!(wrap(Some("  "), "Hi"), "Hi");
+    }
+}
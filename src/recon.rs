@@ -0,0 +1,365 @@
+//! Passive reconnaissance parsers for claWasm
+//!
+//! Pure parsing for the recon scanners: certificate transparency results
+//! from crt.sh, robots.txt, sitemaps, security.txt and front-end library
+//! fingerprints. Fetching happens in tools.rs through the proxy; this
+//! module only turns responses into deduplicated, sorted findings.
+
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+/// Path fragments worth a closer look when they show up in robots.txt
+const INTERESTING_PATH_HINTS: &[&str] = &[
+    "admin", "login", "backup", "config", "private", "internal", "secret",
+    ".git", ".env", "debug", "test", "staging", "dev", "api", "upload",
+    "sql", "db", "dump", "cgi-bin", "wp-admin", "phpmyadmin", "console",
+];
+
+/// Known front-end libraries: (display name, npm package, URL token, banner regex)
+///
+/// The URL token is matched in script paths like `jquery-3.4.1.min.js`,
+/// `jquery@3.4.1/dist/...` or `/jquery/3.4.1/...`; the banner is matched in
+/// the license comment at the top of the file.
+const JS_LIBRARIES: &[(&str, &str, &str, &str)] = &[
+    ("jQuery", "jquery", "jquery", r"jQuery (?:JavaScript Library )?v(\d+\.\d+\.\d+)"),
+    ("jQuery UI", "jquery-ui", "jquery-ui", r"jQuery UI - v(\d+\.\d+\.\d+)"),
+    ("Bootstrap", "bootstrap", "bootstrap", r"Bootstrap v(\d+\.\d+\.\d+)"),
+    ("AngularJS", "angular", "angular", r"AngularJS v(\d+\.\d+\.\d+)"),
+    ("Vue.js", "vue", "vue", r"Vue\.js v(\d+\.\d+\.\d+)"),
+    ("React", "react", "react", r"React v(\d+\.\d+\.\d+)"),
+    ("Lodash", "lodash", "lodash", r"(?i)lodash (\d+\.\d+\.\d+)"),
+    ("Underscore.js", "underscore", "underscore", r"Underscore\.js (\d+\.\d+\.\d+)"),
+    ("Moment.js", "moment", "moment", r"moment\.js\s*(?://!\s*)?version : (\d+\.\d+\.\d+)"),
+    ("Handlebars", "handlebars", "handlebars", r"(?i)handlebars v(\d+\.\d+\.\d+)"),
+    ("DOMPurify", "dompurify", "purify", r"DOMPurify (\d+\.\d+\.\d+)"),
+    ("Knockout", "knockout", "knockout", r"Knockout JavaScript library v(\d+\.\d+\.\d+)"),
+];
+
+/// A front-end library detected on a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsLibrary {
+    pub name: &'static str,
+    /// npm package name, for OSV lookups
+    pub package: &'static str,
+    pub version: String,
+    /// Where it was detected (script URL or "inline")
+    pub source: String,
+}
+
+struct LibraryPattern {
+    name: &'static str,
+    package: &'static str,
+    url: Regex,
+    banner: Regex,
+}
+
+fn library_patterns() -> &'static [LibraryPattern] {
+    static PATTERNS: OnceLock<Vec<LibraryPattern>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        JS_LIBRARIES.iter().map(|(name, package, token, banner)| LibraryPattern {
+            name,
+            package,
+            url: Regex::new(&format!(
+                r"(?i)(?:^|[/._-]){}(?:\.min)?(?:\.js)?(?:[-@/._]v?|\?(?:ver|v)=)(\d+\.\d+(?:\.\d+)?)",
+                regex::escape(token)
+            )).unwrap(),
+            banner: Regex::new(banner).unwrap(),
+        }).collect()
+    })
+}
+
+/// Absolute URLs of external scripts on a page
+pub fn script_sources(html: &str, base: &str) -> Vec<String> {
+    static SRC: OnceLock<Regex> = OnceLock::new();
+    let src = SRC.get_or_init(|| Regex::new(r#"(?is)<script[^>]*?\ssrc\s*=\s*["']([^"']+)["']"#).unwrap());
+    let base = url::Url::parse(base).ok();
+    let mut seen = BTreeSet::new();
+    src.captures_iter(html)
+        .filter_map(|c| {
+            let raw = c[1].trim().replace("&amp;", "&");
+            match &base {
+                Some(base) => base.join(&raw).ok().map(|u| u.to_string()),
+                None => Some(raw),
+            }
+        })
+        .filter(|u| seen.insert(u.clone()))
+        .collect()
+}
+
+/// Library and version encoded in a script URL
+pub fn detect_library_from_url(url: &str) -> Option<JsLibrary> {
+    let path = url.rsplit_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    // Longer tokens first so "jquery-ui-1.12.1" isn't read as jQuery
+    let mut patterns: Vec<&LibraryPattern> = library_patterns().iter().collect();
+    patterns.sort_by_key(|p| std::cmp::Reverse(p.package.len()));
+    patterns.into_iter().find_map(|p| {
+        p.url.captures(path).map(|c| JsLibrary {
+            name: p.name,
+            package: p.package,
+            version: c[1].to_string(),
+            source: url.to_string(),
+        })
+    })
+}
+
+/// Libraries announced by license banners in script text
+pub fn detect_libraries_in_text(text: &str, source: &str) -> Vec<JsLibrary> {
+    library_patterns().iter()
+        .filter_map(|p| p.banner.captures(text).map(|c| JsLibrary {
+            name: p.name,
+            package: p.package,
+            version: c[1].to_string(),
+            source: source.to_string(),
+        }))
+        .collect()
+}
+
+/// Framework and CMS hints in page markup that don't carry a library version
+pub fn page_hints(html: &str) -> Vec<String> {
+    static GENERATOR: OnceLock<Regex> = OnceLock::new();
+    let generator = GENERATOR.get_or_init(|| {
+        Regex::new(r#"(?i)<meta[^>]+name\s*=\s*["']generator["'][^>]+content\s*=\s*["']([^"']+)["']"#).unwrap()
+    });
+    let mut hints: Vec<String> = generator.captures_iter(html)
+        .map(|c| format!("Generator: {}", &c[1]))
+        .collect();
+    let markers = [
+        ("ng-version=", "Angular (rendered with ng-version)"),
+        ("ng-app", "AngularJS (ng-app attribute)"),
+        ("__NEXT_DATA__", "Next.js"),
+        ("__NUXT__", "Nuxt"),
+        ("data-reactroot", "React (data-reactroot)"),
+        ("data-v-", "Vue.js (scoped style attributes)"),
+        ("/wp-content/", "WordPress"),
+    ];
+    hints.extend(markers.iter()
+        .filter(|(marker, _)| html.contains(marker))
+        .map(|(_, hint)| hint.to_string()));
+    hints
+}
+
+/// Normalize a domain argument (strip scheme, path, port, wildcard and case)
+pub fn normalize_domain(input: &str) -> String {
+    let host = input.trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let host = host.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once(':').map(|(h, _)| h).unwrap_or(host);
+    host.trim_start_matches("*.").trim_end_matches('.').to_lowercase()
+}
+
+/// Subdomains of `domain` listed in a crt.sh JSON response
+///
+/// Each certificate's `name_value` may hold several newline-separated names;
+/// wildcards are reduced to their base name and names outside the domain
+/// are dropped.
+pub fn parse_crtsh(json: &str, domain: &str) -> Result<Vec<String>, String> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| format!("Unexpected crt.sh response: {}", e))?;
+    let domain = normalize_domain(domain);
+    let suffix = format!(".{}", domain);
+
+    let names: BTreeSet<String> = entries.iter()
+        .filter_map(|e| e["name_value"].as_str())
+        .flat_map(|names| names.lines())
+        .map(normalize_domain)
+        .filter(|name| !name.contains(char::is_whitespace) && !name.contains('@'))
+        .filter(|name| name == &domain || name.ends_with(&suffix))
+        .collect();
+    Ok(names.into_iter().collect())
+}
+
+/// Scheme and host of a URL, e.g. "https://example.com"
+pub fn origin(url: &str) -> Result<String, String> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed.origin().ascii_serialization()),
+        other => Err(format!("Unsupported scheme '{}'", other)),
+    }
+}
+
+/// Rules and sitemap references from a robots.txt file
+#[derive(Debug, Default, PartialEq)]
+pub struct RobotsTxt {
+    pub disallowed: Vec<String>,
+    pub allowed: Vec<String>,
+    pub sitemaps: Vec<String>,
+}
+
+impl RobotsTxt {
+    /// Disallowed paths whose names suggest sensitive areas
+    pub fn interesting(&self) -> Vec<&str> {
+        self.disallowed.iter()
+            .map(|p| p.as_str())
+            .filter(|p| {
+                let lower = p.to_lowercase();
+                INTERESTING_PATH_HINTS.iter().any(|hint| lower.contains(hint))
+            })
+            .collect()
+    }
+}
+
+/// Parse robots.txt, collecting rules across all user-agent groups
+pub fn parse_robots(text: &str) -> RobotsTxt {
+    let mut robots = RobotsTxt::default();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((field, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let target = match field.trim().to_lowercase().as_str() {
+            "disallow" => &mut robots.disallowed,
+            "allow" => &mut robots.allowed,
+            "sitemap" => &mut robots.sitemaps,
+            _ => continue,
+        };
+        if !target.iter().any(|v| v == value) {
+            target.push(value.to_string());
+        }
+    }
+    robots
+}
+
+/// URLs listed in a sitemap or sitemap index
+pub fn parse_sitemap(xml: &str) -> Vec<String> {
+    static LOC: OnceLock<Regex> = OnceLock::new();
+    let loc = LOC.get_or_init(|| Regex::new(r"(?is)<loc>\s*(?:<!\[CDATA\[)?(.*?)(?:\]\]>)?\s*</loc>").unwrap());
+    let mut seen = BTreeSet::new();
+    loc.captures_iter(xml)
+        .map(|c| c[1].trim().replace("&amp;", "&"))
+        .filter(|u| seen.insert(u.clone()))
+        .collect()
+}
+
+/// Fields of a security.txt file (RFC 9116)
+#[derive(Debug, Default)]
+pub struct SecurityTxt {
+    /// (field, value) pairs in file order
+    pub fields: Vec<(String, String)>,
+    /// Whether the file is PGP clear-signed
+    pub signed: bool,
+}
+
+impl SecurityTxt {
+    pub fn get(&self, name: &str) -> Vec<&str> {
+        self.fields.iter()
+            .filter(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Problems against RFC 9116, given the current time
+    pub fn problems(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.get("Contact").is_empty() {
+            problems.push("Missing required Contact field".to_string());
+        }
+        match self.get("Expires").first() {
+            None => problems.push("Missing required Expires field".to_string()),
+            Some(expires) => match chrono::DateTime::parse_from_rfc3339(expires) {
+                Ok(date) if date < now => problems.push(format!("Expired on {}", expires)),
+                Ok(_) => {}
+                Err(_) => problems.push(format!("Expires is not an RFC 3339 date: {}", expires)),
+            },
+        }
+        problems
+    }
+}
+
+/// Parse security.txt, skipping comments and PGP armor
+pub fn parse_security_txt(text: &str) -> SecurityTxt {
+    let signed = text.contains("-----BEGIN PGP SIGNED MESSAGE-----");
+    let mut fields = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("-----BEGIN PGP SIGNATURE") {
+            break;
+        }
+        if line.starts_with('#') || line.starts_with("-----") || line.starts_with("Hash:") {
+            continue;
+        }
+        if let Some((field, value)) = line.split_once(':') {
+            let field = field.trim();
+            if !field.is_empty() && field.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                fields.push((field.to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    SecurityTxt { fields, signed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_domain() {
+        assert_eq!(normalize_domain("https://Example.com:8443/path?q=1"), "example.com");
+        assert_eq!(normalize_domain("*.example.com."), "example.com");
+    }
+
+    #[test]
+    fn test_parse_crtsh_dedupes_and_filters() {
+        let json = r#"[
+            {"name_value": "example.com\nwww.example.com"},
+            {"name_value": "*.api.example.com"},
+            {"name_value": "WWW.example.com"},
+            {"name_value": "admin@example.com"},
+            {"name_value": "notexample.com"}
+        ]"#;
+        let names = parse_crtsh(json, "example.com").unwrap();
+        assert_eq!(names, vec!["api.example.com", "example.com", "www.example.com"]);
+        assert!(parse_crtsh("<html>", "example.com").is_err());
+    }
+
+    #[test]
+    fn test_parse_robots() {
+        let robots = parse_robots("User-agent: *\nDisallow: /admin/ # staff\nDisallow: /search\nAllow: /public\nDisallow:\nSitemap: https://example.com/sitemap.xml\n\nUser-agent: bot\nDisallow: /admin/\n");
+        assert_eq!(robots.disallowed, vec!["/admin/", "/search"]);
+        assert_eq!(robots.allowed, vec!["/public"]);
+        assert_eq!(robots.sitemaps, vec!["https://example.com/sitemap.xml"]);
+        assert_eq!(robots.interesting(), vec!["/admin/"]);
+    }
+
+    #[test]
+    fn test_parse_sitemap() {
+        let xml = "<urlset><url><loc>https://example.com/a?x=1&amp;y=2</loc></url><url><loc><![CDATA[https://example.com/b]]></loc></url><url><loc>https://example.com/b</loc></url></urlset>";
+        assert_eq!(parse_sitemap(xml), vec!["https://example.com/a?x=1&y=2", "https://example.com/b"]);
+    }
+
+    #[test]
+    fn test_security_txt_problems() {
+        let now = chrono::DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let txt = parse_security_txt("# comment\nContact: mailto:security@example.com\nExpires: 2025-12-31T23:59:59Z\nPolicy: https://example.com/policy\n");
+        assert_eq!(txt.get("contact"), vec!["mailto:security@example.com"]);
+        assert_eq!(txt.problems(now), vec!["Expired on 2025-12-31T23:59:59Z".to_string()]);
+        assert_eq!(parse_security_txt("Policy: x").problems(now).len(), 2);
+    }
+
+    #[test]
+    fn test_detect_library_from_url() {
+        let lib = detect_library_from_url("https://code.jquery.com/jquery-3.4.1.min.js").unwrap();
+        assert_eq!((lib.name, lib.version.as_str()), ("jQuery", "3.4.1"));
+        let lib = detect_library_from_url("https://cdn.example.com/ajax/libs/jquery-ui/1.12.1/jquery-ui.min.js").unwrap();
+        assert_eq!(lib.package, "jquery-ui");
+        let lib = detect_library_from_url("https://example.com/wp-includes/js/jquery/jquery.min.js?ver=3.6.0").unwrap();
+        assert_eq!(lib.version, "3.6.0");
+        assert!(detect_library_from_url("https://example.com/app.bundle.js").is_none());
+        assert!(detect_library_from_url("https://example.com/revue-3.0.0.js").is_none());
+    }
+
+    #[test]
+    fn test_detect_libraries_in_text_and_sources() {
+        let libs = detect_libraries_in_text("/*! jQuery v1.12.4 | (c) jQuery Foundation */", "inline");
+        assert_eq!(libs[0].version, "1.12.4");
+        let html = r#"<script src="/js/app.js"></script><script async src='https://cdn.example.com/vue@2.6.10'></script><script>var x;</script>"#;
+        assert_eq!(script_sources(html, "https://example.com/page"), vec![
+            "https://example.com/js/app.js", "https://cdn.example.com/vue@2.6.10",
+        ]);
+        assert_eq!(page_hints(r#"<meta name="generator" content="WordPress 5.8"><div ng-app>"#),
+            vec!["Generator: WordPress 5.8", "AngularJS (ng-app attribute)"]);
+    }
+}
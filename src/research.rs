@@ -0,0 +1,519 @@
+//! Research planning and citation tracking for the `research` tool
+//!
+//! A topic is searched as several sub-queries (given by the agent, or
+//! planned from the topic), the results are merged into one list of
+//! numbered sources with duplicate URLs folded together, and the pages
+//! fetched contribute the sentences that best match the queries as
+//! quotes. Each source is scored for relevance by how many of the
+//! research terms it covers and summarized, by the provider when the tool
+//! runs inside the agent or else by picking its best sentences; sources
+//! below the cut are dropped and the rest renumbered. Deep research runs
+//! in rounds: what the first round found suggests follow-up queries (asked
+//! of the provider, or else the terms the sources share that no query
+//! used yet), searched and read in the next. The report renders
+//! as Markdown with `[n]` markers and a References section that
+//! `create_pdf` takes as it is, or as JSON.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::chat::Message;
+use crate::config::Config;
+use crate::providers::ChatModel;
+use crate::reasoning;
+use crate::security::SecurityConfig;
+
+/// How much a depth searches and reads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Depth {
+    pub queries: usize,
+    /// Pages fetched for quotes in the first round; later rounds read half
+    pub pages: usize,
+    pub results_per_query: usize,
+    /// Search rounds, each after the first refining on what was found
+    pub rounds: usize,
+    /// Follow-up queries per refining round
+    pub follow_ups: usize,
+}
+
+impl Depth {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "quick" => Depth { queries: 2, pages: 3, results_per_query: 5, rounds: 1, follow_ups: 2 },
+            "deep" => Depth { queries: 6, pages: 10, results_per_query: 10, rounds: 3, follow_ups: 3 },
+            _ => Depth { queries: 4, pages: 6, results_per_query: 8, rounds: 1, follow_ups: 3 },
+        }
+    }
+
+    /// Pages read in round `round` (from 0)
+    pub fn pages_in(&self, round: usize) -> usize {
+        match round {
+            0 => self.pages,
+            _ => self.pages.div_ceil(2),
+        }
+    }
+}
+
+/// Most search rounds a research may ask for
+pub const MAX_ROUNDS: usize = 5;
+
+/// Angles searched besides the topic itself when the agent gave no sub-queries
+const ANGLES: [&str; 6] = ["overview", "latest developments", "statistics and data", "criticism and limitations", "expert analysis", "case studies"];
+
+/// Quotes kept per source
+const QUOTES_PER_SOURCE: usize = 2;
+const MAX_QUOTE_CHARS: usize = 300;
+/// Sentences in an extractive summary
+const SUMMARY_SENTENCES: usize = 3;
+/// Page text sent to the provider for a summary
+const MAX_SUMMARY_INPUT_CHARS: usize = 6000;
+/// Sources scoring less are dropped by default
+pub const DEFAULT_MIN_RELEVANCE: f32 = 0.25;
+/// The provider's answer for a page with nothing on the topic
+const NOT_RELEVANT: &str = "NOT RELEVANT";
+/// The provider's answer when no follow-up search is needed
+const DONE: &str = "DONE";
+/// Common long words that make poor follow-up queries
+const STOPWORDS: [&str; 24] = [
+    "about", "after", "also", "been", "because", "before", "being", "between", "could", "from", "have", "more",
+    "most", "other", "should", "some", "than", "that", "their", "there", "these", "they", "this", "which",
+];
+
+/// The sub-queries to search: the topic first, then `given` or planned
+/// angles, without repeats, `depth.queries` in all
+pub fn plan_queries(topic: &str, given: &[String], depth: Depth) -> Vec<String> {
+    let topic = topic.trim();
+    let planned: Vec<String> = match given.is_empty() {
+        true => ANGLES.iter().map(|angle| format!("{} {}", topic, angle)).collect(),
+        false => given.to_vec(),
+    };
+    let mut queries: Vec<String> = Vec::new();
+    for query in std::iter::once(topic.to_string()).chain(planned) {
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !query.is_empty() && !queries.iter().any(|q| q.eq_ignore_ascii_case(&query)) {
+            queries.push(query);
+        }
+    }
+    queries.truncate(depth.queries.max(1));
+    queries
+}
+
+/// The URL two results are the same source by: no scheme, `www.`,
+/// fragment, tracking parameters or trailing slash, and a lowercase host
+pub fn canonical_url(raw: &str) -> String {
+    let Ok(mut url) = url::Url::parse(raw.trim()) else {
+        return raw.trim().trim_end_matches('/').to_string();
+    };
+    url.set_fragment(None);
+    let kept: Vec<(String, String)> = url.query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !matches!(key.as_ref(), "ref" | "fbclid" | "gclid"))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.set_query(None);
+    if !kept.is_empty() {
+        url.query_pairs_mut().extend_pairs(kept);
+    }
+    let host = url.host_str().unwrap_or("").to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let query = url.query().map(|q| format!("?{}", q)).unwrap_or_default();
+    format!("{}{}{}{}", host, port, url.path().trim_end_matches('/'), query)
+}
+
+/// One source, numbered in the order it was found
+#[derive(Debug, Clone, Serialize)]
+pub struct Source {
+    pub id: usize,
+    pub url: String,
+    pub title: String,
+    /// The search engine's snippet
+    pub snippet: String,
+    /// Sentences of the page that match the queries
+    pub quotes: Vec<String>,
+    /// The sub-queries that found it
+    pub queries: Vec<String>,
+    /// Share of the research terms the page (or, unread, the title and
+    /// snippet) covers, from 0 to 1
+    pub relevance: f32,
+    /// What the page says about the topic; empty when it was not read
+    pub summary: String,
+    /// Whether the page was fetched
+    pub read: bool,
+}
+
+impl Source {
+    /// A Markdown reference line: `[1] Title. https://...`
+    pub fn reference(&self) -> String {
+        match self.title.is_empty() {
+            true => format!("[{}] {}", self.id, self.url),
+            false => format!("[{}] {}. {}", self.id, self.title, self.url),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResearchReport {
+    pub topic: String,
+    pub depth: String,
+    pub queries: Vec<String>,
+    pub sources: Vec<Source>,
+    /// Results that pointed at a source already listed
+    pub duplicates: usize,
+    /// Sources dropped as irrelevant
+    pub dropped: Vec<String>,
+    /// Search rounds run
+    pub rounds: usize,
+}
+
+impl ResearchReport {
+    pub fn new(topic: &str, depth: &str, queries: Vec<String>) -> Self {
+        ResearchReport { topic: topic.trim().to_string(), depth: depth.to_string(), queries, sources: Vec::new(), duplicates: 0, dropped: Vec::new(), rounds: 0 }
+    }
+
+    /// Merge the proxy's `/search` answer for `query` (`{"results": [{"title",
+    /// "url", "snippet"}]}`), keeping at most `limit` of its results
+    pub fn add_results(&mut self, query: &str, answer: &serde_json::Value, limit: usize) {
+        let results = answer["results"].as_array().into_iter().flatten().take(limit);
+        for result in results {
+            let Some(url) = result["url"].as_str().filter(|u| u.starts_with("http")) else {
+                continue;
+            };
+            let key = canonical_url(url);
+            if let Some(source) = self.sources.iter_mut().find(|s| canonical_url(&s.url) == key) {
+                self.duplicates += 1;
+                if !source.queries.iter().any(|q| q == query) {
+                    source.queries.push(query.to_string());
+                }
+                continue;
+            }
+            self.sources.push(Source {
+                id: self.sources.len() + 1,
+                url: url.to_string(),
+                title: result["title"].as_str().unwrap_or("").trim().to_string(),
+                snippet: result["snippet"].as_str().unwrap_or("").trim().to_string(),
+                quotes: Vec::new(),
+                queries: vec![query.to_string()],
+                relevance: 0.0,
+                summary: String::new(),
+                read: false,
+            });
+        }
+    }
+
+    /// Add follow-up queries, returning those not searched yet
+    pub fn add_queries(&mut self, queries: Vec<String>) -> Vec<String> {
+        let mut added = Vec::new();
+        for query in queries {
+            let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !query.is_empty() && !self.queries.iter().any(|q| q.eq_ignore_ascii_case(&query)) {
+                self.queries.push(query.clone());
+                added.push(query);
+            }
+        }
+        added
+    }
+
+    /// Unread sources to fetch, those found by the most sub-queries first
+    pub fn to_read(&self, pages: usize) -> Vec<usize> {
+        let mut ids: Vec<&Source> = self.sources.iter().filter(|s| !s.read).collect();
+        ids.sort_by_key(|s| (std::cmp::Reverse(s.queries.len()), s.id));
+        ids.into_iter().take(pages).map(|s| s.id).collect()
+    }
+
+    /// Quote, score and summarize the page text of source `id`
+    pub fn add_page(&mut self, id: usize, text: &str) {
+        let terms = terms(&self.topic, &self.queries);
+        if let Some(source) = self.sources.iter_mut().find(|s| s.id == id) {
+            source.quotes = quotes(text, &terms, QUOTES_PER_SOURCE);
+            source.relevance = relevance(text, &self.topic, &self.queries);
+            source.summary = quotes(text, &terms, SUMMARY_SENTENCES).join(" ");
+            source.read = true;
+        }
+    }
+
+    /// Replace the extractive summary of source `id` with the provider's;
+    /// a page the provider found nothing relevant in scores 0
+    pub fn set_summary(&mut self, id: usize, summary: &str) {
+        if let Some(source) = self.sources.iter_mut().find(|s| s.id == id) {
+            match summary.trim() {
+                s if s.trim_end_matches('.').eq_ignore_ascii_case(NOT_RELEVANT) => source.relevance = 0.0,
+                s if !s.is_empty() => source.summary = s.to_string(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Up to `max` follow-up queries from the pages read: the topic with each
+    /// of the words most sources share that no query has used
+    pub fn follow_ups(&self, max: usize) -> Vec<String> {
+        let used = terms(&self.topic, &self.queries);
+        let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for source in self.sources.iter().filter(|s| s.read) {
+            let text = format!("{} {}", source.summary, source.quotes.join(" ")).to_lowercase();
+            let mut words: Vec<&str> = text.split(|c: char| !c.is_alphanumeric())
+                .filter(|w| w.chars().count() >= 5 && !w.chars().all(|c| c.is_ascii_digit()))
+                .filter(|w| !STOPWORDS.contains(w) && !used.iter().any(|u| u == w))
+                .collect();
+            for word in &words {
+                counts.entry(word.to_string()).or_default().1 += 1;
+            }
+            words.sort_unstable();
+            words.dedup();
+            for word in words {
+                counts.entry(word.to_string()).or_default().0 += 1;
+            }
+        }
+        let mut ranked: Vec<(String, (usize, usize))> = counts.into_iter().filter(|(_, (sources, _))| *sources >= 2).collect();
+        ranked.sort_by_key(|(word, (sources, uses))| (std::cmp::Reverse(*sources), std::cmp::Reverse(*uses), word.clone()));
+        ranked.into_iter().take(max).map(|(word, _)| format!("{} {}", self.topic, word)).collect()
+    }
+
+    /// What the pages read say, one `[n] summary` line each, for the provider
+    fn findings(&self) -> String {
+        self.sources.iter()
+            .filter(|s| s.read && !s.summary.is_empty())
+            .map(|s| format!("[{}] {}", s.id, s.summary))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Drop the sources scoring under `min` and renumber the rest; unread
+    /// sources are scored on their title and snippet
+    pub fn filter(&mut self, min: f32) {
+        for source in self.sources.iter_mut().filter(|s| !s.read) {
+            source.relevance = relevance(&format!("{} {}", source.title, source.snippet), &self.topic, &self.queries);
+        }
+        let (kept, dropped): (Vec<Source>, Vec<Source>) = std::mem::take(&mut self.sources).into_iter().partition(|s| s.relevance >= min);
+        self.dropped.extend(dropped.into_iter().map(|s| s.url));
+        self.sources = kept;
+        for (i, source) in self.sources.iter_mut().enumerate() {
+            source.id = i + 1;
+        }
+    }
+
+    /// The numbered references, one per line
+    pub fn references(&self) -> String {
+        self.sources.iter().map(Source::reference).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Research Report: {}\n\nDepth: {}{} · {} queries · {} sources ({} duplicate results merged, {} irrelevant dropped)\n\n## Queries\n\n",
+            self.topic,
+            self.depth,
+            if self.rounds > 1 { format!(" ({} rounds)", self.rounds) } else { String::new() },
+            self.queries.len(),
+            self.sources.len(),
+            self.duplicates,
+            self.dropped.len()
+        );
+        for (i, query) in self.queries.iter().enumerate() {
+            out.push_str(&format!("{}. {}\n", i + 1, query));
+        }
+        out.push_str("\n## Sources\n");
+        for source in &self.sources {
+            let title = if source.title.is_empty() { &source.url } else { &source.title };
+            out.push_str(&format!("\n### [{}] {}\n{} · relevance {:.2}\n", source.id, title, source.url, source.relevance));
+            if !source.summary.is_empty() {
+                out.push_str(&format!("{}\n", source.summary));
+            } else if !source.snippet.is_empty() {
+                out.push_str(&format!("{}\n", source.snippet));
+            }
+            for quote in &source.quotes {
+                out.push_str(&format!("> \"{}\" [{}]\n", quote, source.id));
+            }
+        }
+        if self.sources.is_empty() {
+            out.push_str("\nNo sources found.\n");
+        } else {
+            out.push_str(&format!("\n## References\n\n{}\n", self.references()));
+        }
+        out
+    }
+}
+
+/// Summarizes sources with the provider the agent runs on
+pub struct Summarizer<'a> {
+    pub model: &'a dyn ChatModel,
+    pub config: &'a Config,
+    pub policy: &'a SecurityConfig,
+}
+
+impl Summarizer<'_> {
+    /// Two or three sentences on what `text` says about `topic`, or
+    /// "NOT RELEVANT"; `None` when the provider failed
+    pub async fn summarize(&self, topic: &str, text: &str) -> Option<String> {
+        let text: String = text.chars().take(MAX_SUMMARY_INPUT_CHARS).collect();
+        let mut messages = vec![
+            Message::system(&format!(
+                "You summarize a web page for research on \"{}\". In two or three sentences, state only what the page says about that topic, with its figures, names and dates. If it says nothing about the topic, reply {} and nothing else.",
+                topic, NOT_RELEVANT
+            )),
+            Message::user(&text),
+        ];
+        self.policy.redact_messages(&mut messages);
+        match self.model.complete(&messages, self.config).await {
+            Ok(summary) => Some(reasoning::strip(&summary)).filter(|s| !s.is_empty()),
+            Err(_) => None,
+        }
+    }
+
+    /// Up to `max` searches filling the gaps in what `report` found; empty
+    /// when the provider sees none, `None` when it failed
+    pub async fn follow_ups(&self, report: &ResearchReport, max: usize) -> Option<Vec<String>> {
+        let mut messages = vec![
+            Message::system(&format!(
+                "You plan web searches for research on \"{}\". Given the queries already searched and what the sources said, reply with up to {} new search queries that would fill the most important gaps, one per line, nothing else. If nothing important is missing, reply {}.",
+                report.topic, max, DONE
+            )),
+            Message::user(&format!("Queries searched:\n{}\n\nFindings:\n{}", report.queries.join("\n"), report.findings())),
+        ];
+        self.policy.redact_messages(&mut messages);
+        let answer = self.model.complete(&messages, self.config).await.ok()?;
+        Some(parse_queries(&reasoning::strip(&answer), max))
+    }
+}
+
+/// The queries of a provider's one-per-line answer, without list markers
+/// or quotes
+fn parse_queries(answer: &str, max: usize) -> Vec<String> {
+    if answer.trim().trim_end_matches('.').eq_ignore_ascii_case(DONE) {
+        return Vec::new();
+    }
+    answer.lines()
+        .map(|line| line.trim().trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•')).trim())
+        .map(|line| line.trim_matches(|c| c == '"' || c == '\'').trim().to_string())
+        .filter(|line| !line.is_empty() && line.chars().count() <= 200)
+        .take(max)
+        .collect()
+}
+
+/// How much of the research `text` covers: the topic's terms weigh three
+/// times the sub-queries', which no single page is expected to cover all of
+fn relevance(text: &str, topic: &str, queries: &[String]) -> f32 {
+    let lower = text.to_lowercase();
+    let share = |terms: Vec<String>| match terms.len() {
+        0 => 1.0,
+        n => terms.iter().filter(|term| lower.contains(term.as_str())).count() as f32 / n as f32,
+    };
+    0.75 * share(terms(topic, &[])) + 0.25 * share(terms(topic, queries))
+}
+
+/// Lowercase words of at least four letters in the topic and queries
+fn terms(topic: &str, queries: &[String]) -> Vec<String> {
+    let mut terms: Vec<String> = std::iter::once(topic)
+        .chain(queries.iter().map(String::as_str))
+        .flat_map(|text| text.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.chars().count() >= 4)
+        .map(str::to_lowercase)
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// The `max` sentences of `text` matching the most `terms`, in page order
+fn quotes(text: &str, terms: &[String], max: usize) -> Vec<String> {
+    let sentences: Vec<&str> = text
+        .split_inclusive(['.', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|s| (40..=MAX_QUOTE_CHARS).contains(&s.chars().count()))
+        .collect();
+    let mut scored: Vec<(usize, usize)> = sentences.iter().enumerate()
+        .map(|(i, sentence)| {
+            let lower = sentence.to_lowercase();
+            (terms.iter().filter(|term| lower.contains(term.as_str())).count(), i)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), i));
+    let mut picked: Vec<usize> = scored.into_iter().take(max).map(|(_, i)| i).collect();
+    picked.sort_unstable();
+    picked.dedup_by_key(|i| sentences[*i]);
+    picked.into_iter().map(|i| sentences[i].to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_plan_and_merge() {
+        let depth = Depth::from_name("quick");
+        assert_eq!(plan_queries(" rust  wasm ", &[], depth), ["rust wasm", "rust wasm overview"]);
+        let given = vec!["Rust WASM".to_string(), "wasm gc proposal".to_string()];
+        assert_eq!(plan_queries("rust wasm", &given, Depth::from_name("normal")), ["rust wasm", "wasm gc proposal"]);
+
+        assert_eq!(canonical_url("https://www.Example.com/a/?utm_source=x&id=2#top"), "example.com/a?id=2");
+        assert_eq!(canonical_url("http://example.com/a"), canonical_url("https://example.com/a/"));
+
+        let mut report = ResearchReport::new("rust wasm", "quick", vec!["rust wasm".to_string(), "wasm gc".to_string()]);
+        report.add_results("rust wasm", &json!({"results": [
+            {"title": "Rust and WebAssembly", "url": "https://rustwasm.github.io/", "snippet": "Book"},
+            {"title": "MDN", "url": "https://developer.mozilla.org/en-US/docs/WebAssembly"},
+        ]}), 10);
+        report.add_results("wasm gc", &json!({"results": [
+            {"title": "Rust and WebAssembly", "url": "https://rustwasm.github.io?utm_medium=feed"},
+            {"title": "", "url": "javascript:alert(1)"},
+        ]}), 10);
+        assert_eq!(report.sources.len(), 2);
+        assert_eq!((report.duplicates, report.sources[0].queries.len()), (1, 2));
+        assert_eq!(report.to_read(1), [1], "found by both queries");
+    }
+
+    #[test]
+    fn test_quotes_and_references() {
+        let mut report = ResearchReport::new("rust wasm", "quick", vec!["rust wasm".to_string()]);
+        report.add_results("rust wasm", &json!({"results": [{"title": "Guide", "url": "https://example.com/guide"}]}), 10);
+        report.add_page(1, "Cookies keep this site running smoothly for everyone. \
+            Rust compiles to WASM with wasm-bindgen and wasm-pack. Short one. \
+            The toolchain targets wasm32-unknown-unknown by default today.");
+        assert_eq!(report.sources[0].quotes, ["Rust compiles to WASM with wasm-bindgen and wasm-pack.", "The toolchain targets wasm32-unknown-unknown by default today."]);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("> \"Rust compiles to WASM with wasm-bindgen and wasm-pack.\" [1]"));
+        assert!(markdown.ends_with("## References\n\n[1] Guide. https://example.com/guide\n"));
+    }
+
+    #[test]
+    fn test_relevance_filter() {
+        let mut report = ResearchReport::new("rust wasm", "quick", vec!["rust wasm".to_string(), "rust wasm benchmarks".to_string()]);
+        report.add_results("rust wasm", &json!({"results": [
+            {"title": "Recipes", "url": "https://example.com/cake"},
+            {"title": "Rust WASM benchmarks", "url": "https://example.com/bench", "snippet": "Numbers"},
+            {"title": "Rust and WASM", "url": "https://example.com/guide"},
+            {"title": "Rust news", "url": "https://example.com/news"},
+        ]}), 10);
+        report.add_page(1, "Whisk the eggs and sugar until the batter turns pale and thick.");
+        report.add_page(3, "Rust compiles to WASM with wasm-bindgen, and the output stays small.");
+        assert!(report.sources[0].relevance < 0.25 && report.sources[2].relevance > 0.7);
+        assert_eq!(report.sources[2].summary, "Rust compiles to WASM with wasm-bindgen, and the output stays small.");
+        report.add_page(4, "Rust 1.80 ships with improved WASM support and faster builds overall.");
+        report.set_summary(4, "NOT RELEVANT.");
+        report.set_summary(3, "A guide to building WASM with Rust.");
+
+        report.filter(DEFAULT_MIN_RELEVANCE);
+        assert_eq!(report.dropped, ["https://example.com/cake", "https://example.com/news"]);
+        assert_eq!(report.sources.iter().map(|s| (s.id, s.url.as_str())).collect::<Vec<_>>(), [(1, "https://example.com/bench"), (2, "https://example.com/guide")]);
+        assert!(report.to_markdown().contains("https://example.com/guide · relevance 0.92\nA guide to building WASM with Rust.\n"));
+    }
+
+    #[test]
+    fn test_follow_ups() {
+        let mut report = ResearchReport::new("rust wasm", "deep", vec!["rust wasm".to_string()]);
+        report.add_results("rust wasm", &json!({"results": [
+            {"title": "A", "url": "https://example.com/a"},
+            {"title": "B", "url": "https://example.com/b"},
+        ]}), 10);
+        report.add_page(1, "Rust WASM binaries shrink with wasm-opt and a smaller allocator today.");
+        report.add_page(2, "Teams pick a smaller allocator to cut Rust WASM startup and binary size.");
+        assert!(report.to_read(5).is_empty(), "both read");
+        assert_eq!(report.follow_ups(1), ["rust wasm allocator"]);
+
+        assert_eq!(report.add_queries(vec!["Rust  WASM".to_string(), "rust wasm allocator".to_string()]), ["rust wasm allocator"]);
+        assert_eq!(report.follow_ups(3), ["rust wasm smaller"], "allocator is searched now");
+        assert_eq!(parse_queries("1. \"wasm gc\"\n- component model\n\n* wasi preview 2", 2), ["wasm gc", "component model"]);
+        assert!(parse_queries("DONE.", 3).is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+//! HTML sanitizer for model output and fetched content
+//!
+//! Anything HTML that came from a model or a page passes through `clean`
+//! before it is shown with `innerHTML` or sent as an email. It works from an
+//! allowlist, like ammonia: formatting, lists, tables, links and images are
+//! kept with a few harmless attributes; script-bearing elements (`script`,
+//! `style`, `iframe`, `svg`, forms...) are dropped with their content; any
+//! other tag is dropped but its text kept. Links and image sources must be
+//! http(s), mailto or relative, and links open with `rel="noopener
+//! noreferrer"`. The output is well formed: stray closing tags are dropped
+//! and open ones closed at the end.
+
+/// Elements kept, with the attributes each may keep
+const ALLOWED: &[(&str, &[&str])] = &[
+    ("a", &["href", "title"]),
+    ("b", &[]), ("strong", &[]), ("i", &[]), ("em", &[]), ("u", &[]), ("s", &[]), ("del", &[]),
+    ("sup", &["id"]), ("sub", &[]), ("mark", &[]), ("small", &[]), ("kbd", &[]),
+    ("code", &["class"]), ("pre", &[]), ("p", &[]), ("br", &[]), ("hr", &[]), ("span", &[]), ("div", &["id"]),
+    ("h1", &[]), ("h2", &[]), ("h3", &[]), ("h4", &[]), ("h5", &[]), ("h6", &[]),
+    ("ul", &[]), ("ol", &["start"]), ("li", &[]), ("blockquote", &[]), ("dl", &[]), ("dt", &[]), ("dd", &[]),
+    ("table", &[]), ("thead", &[]), ("tbody", &[]), ("tr", &[]), ("th", &["style", "colspan", "rowspan"]),
+    ("td", &["style", "colspan", "rowspan"]),
+    ("img", &["src", "alt", "title", "width", "height"]),
+    ("input", &["type", "checked", "disabled"]),
+];
+
+/// Elements dropped together with everything inside them
+const DROPPED: &[&str] = &[
+    "script", "style", "iframe", "frame", "frameset", "object", "embed", "applet", "noscript", "noembed",
+    "template", "textarea", "select", "option", "button", "form", "svg", "math", "title", "head", "xmp",
+];
+
+/// Elements without content or closing tag
+const VOID: &[&str] = &["br", "hr", "img", "input"];
+
+fn allowed_attributes(tag: &str) -> Option<&'static [&'static str]> {
+    ALLOWED.iter().find(|(name, _)| *name == tag).map(|(_, attributes)| *attributes)
+}
+
+/// Escape text for an HTML text node or quoted attribute value
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode the character references in `text`; unknown ones stay as they are
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let end = rest.find(';').filter(|&end| end <= 10);
+        let decoded = end.and_then(|end| {
+            let name = &rest[1..end];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => {
+                    let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                        u32::from_str_radix(hex, 16).ok()
+                    } else {
+                        name.strip_prefix('#').and_then(|n| n.parse().ok())
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether `url` (decoded) is safe to link to or load
+fn safe_url(url: &str, image: bool) -> bool {
+    // Browsers ignore whitespace and control characters inside the scheme
+    let compact: String = url.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect::<String>().to_ascii_lowercase();
+    match compact.find(':') {
+        // No scheme, or a colon after the path starts: relative
+        None => true,
+        Some(colon) if compact[..colon].contains(['/', '?', '#']) => true,
+        Some(colon) => match &compact[..colon] {
+            "http" | "https" => true,
+            "mailto" => !image,
+            "data" => image && ["data:image/png", "data:image/jpeg", "data:image/gif", "data:image/webp"]
+                .iter().any(|prefix| compact.starts_with(prefix)),
+            _ => false,
+        },
+    }
+}
+
+/// Whether attribute `name` of `tag` may keep `value` (decoded)
+fn allowed_value(tag: &str, name: &str, value: &str) -> bool {
+    match name {
+        "href" => safe_url(value, false),
+        "src" => safe_url(value, true),
+        "style" => {
+            // Table cell alignment as the Markdown renderer writes it
+            let value = value.trim().trim_end_matches(';').to_ascii_lowercase();
+            matches!(value.split_once(':').map(|(k, v)| (k.trim(), v.trim())),
+                Some(("text-align", "left" | "right" | "center")))
+        }
+        "class" => value.split_whitespace().all(|class| {
+            class.starts_with("language-") && class.chars().all(|c| c.is_ascii_alphanumeric() || "-_+#".contains(c))
+        }),
+        "type" => tag == "input" && value.eq_ignore_ascii_case("checkbox"),
+        "start" | "colspan" | "rowspan" | "width" | "height" => !value.is_empty() && value.len() <= 5 && value.chars().all(|c| c.is_ascii_digit()),
+        "id" => value.chars().all(|c| c.is_ascii_alphanumeric() || "-_:.".contains(c)),
+        _ => true,
+    }
+}
+
+/// A tag as found in the input
+struct Tag {
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, String)>,
+}
+
+/// Parse the tag starting at `input[0] == '<'`, returning it and its length
+fn parse_tag(input: &str) -> Option<(Tag, usize)> {
+    let bytes = input.as_bytes();
+    let mut i = 1;
+    let closing = bytes.get(i) == Some(&b'/');
+    if closing {
+        i += 1;
+    }
+    let name_start = i;
+    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+        i += 1;
+    }
+    if i == name_start || !bytes[name_start].is_ascii_alphabetic() {
+        return None;
+    }
+    let name = input[name_start..i].to_ascii_lowercase();
+    let mut attributes = Vec::new();
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        match bytes.get(i) {
+            None => return None,
+            Some(b'>') => return Some((Tag { name, closing, attributes }, i + 1)),
+            _ => {}
+        }
+        let attribute_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"/>=".contains(&bytes[i]) {
+            i += 1;
+        }
+        let attribute = input[attribute_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let mut value = String::new();
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let end = input[i + 1..].find(quote as char)? + i + 1;
+                    value = input[i + 1..end].to_string();
+                    i = end + 1;
+                }
+                _ => {
+                    let value_start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    value = input[value_start..i].to_string();
+                }
+            }
+        }
+        if !attribute.is_empty() {
+            attributes.push((attribute, decode_entities(&value)));
+        }
+    }
+}
+
+/// `html` with only allowlisted elements and attributes left
+pub fn clean(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<String> = Vec::new();
+    // Inside a dropped element: its name and nesting depth
+    let mut dropping: Option<(String, usize)> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            if dropping.is_none() {
+                out.push_str(&escape(&decode_entities(rest)));
+            }
+            break;
+        };
+        if dropping.is_none() {
+            out.push_str(&escape(&decode_entities(&rest[..lt])));
+        }
+        rest = &rest[lt..];
+
+        // Comments, doctypes and processing instructions go entirely
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+            continue;
+        }
+        let Some((tag, length)) = parse_tag(rest) else {
+            // A lone "<" is text
+            if dropping.is_none() {
+                out.push_str("&lt;");
+            }
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[length..];
+
+        if let Some((name, depth)) = dropping.as_mut() {
+            if *name == tag.name {
+                if tag.closing {
+                    *depth -= 1;
+                } else {
+                    *depth += 1;
+                }
+                if *depth == 0 {
+                    dropping = None;
+                }
+            }
+            continue;
+        }
+        if DROPPED.contains(&tag.name.as_str()) {
+            if !tag.closing {
+                dropping = Some((tag.name, 1));
+            }
+            continue;
+        }
+        let Some(allowed) = allowed_attributes(&tag.name) else {
+            continue;
+        };
+
+        if tag.closing {
+            if let Some(position) = open.iter().rposition(|name| *name == tag.name) {
+                for name in open.drain(position..).rev() {
+                    out.push_str(&format!("</{}>", name));
+                }
+            }
+            continue;
+        }
+        out.push('<');
+        out.push_str(&tag.name);
+        for (name, value) in &tag.attributes {
+            if allowed.contains(&name.as_str()) && allowed_value(&tag.name, name, value) {
+                out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+            }
+        }
+        if tag.name == "a" {
+            out.push_str(" rel=\"noopener noreferrer\"");
+        }
+        if tag.name == "input" && !tag.attributes.iter().any(|(name, _)| name == "disabled") {
+            out.push_str(" disabled=\"\"");
+        }
+        out.push('>');
+        if !VOID.contains(&tag.name.as_str()) {
+            open.push(tag.name);
+        }
+    }
+    for name in open.into_iter().rev() {
+        out.push_str(&format!("</{}>", name));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_vectors_removed() {
+        let html = r#"<p onclick="x()">Hi <script>alert(1)</script><b>there</b></p><img src=x onerror=alert(1)><a href="jav&#x61;script:alert(1)">a</a><a href=" JavaScript:alert(1)">b</a><svg><script>x</script><g onload=y></g></svg><iframe src="https://e.com"></iframe><!-- <script> -->"#;
+        assert_eq!(clean(html), concat!(
+            "<p>Hi <b>there</b></p><img src=\"x\">",
+            "<a rel=\"noopener noreferrer\">a</a><a rel=\"noopener noreferrer\">b</a>"
+        ));
+    }
+
+    #[test]
+    fn test_allowed_markup_kept() {
+        let html = "<h2>T</h2><ul><li>a &amp; b</li></ul><table><tr><td style=\"text-align: right\">1</td><td style=\"color:red\">2</td></tr></table><pre><code class=\"language-rust\">x &lt; y</code></pre><a href=\"https://e.com/?a=1&amp;b=2\" target=_blank>e</a><input type=\"checkbox\" checked>";
+        assert_eq!(clean(html), concat!(
+            "<h2>T</h2><ul><li>a &amp; b</li></ul><table><tr><td style=\"text-align: right\">1</td><td>2</td></tr></table>",
+            "<pre><code class=\"language-rust\">x &lt; y</code></pre>",
+            "<a href=\"https://e.com/?a=1&amp;b=2\" rel=\"noopener noreferrer\">e</a><input type=\"checkbox\" checked=\"\" disabled=\"\">"
+        ));
+    }
+
+    #[test]
+    fn test_output_well_formed() {
+        assert_eq!(clean("<b><i>x</b> 1 < 2 </i></p><unknown>y</unknown><em>open"), "<b><i>x</i></b> 1 &lt; 2 y<em>open</em>");
+        assert_eq!(clean("<script>never closed <b>x</b>"), "");
+        assert_eq!(clean("a <3 b > c"), "a &lt;3 b &gt; c");
+    }
+}
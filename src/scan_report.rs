@@ -0,0 +1,894 @@
+//! Unified security scan report for claWasm
+//!
+//! Checks run by `scan_full` against a single fetched response (headers,
+//! cookies, TLS, CORS, XSS indicators, mixed content, exposed secrets), aggregated into a
+//! severity-weighted score and rendered as a pentest-style Markdown report
+//! that create_pdf can turn into a document.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::secrets::{self, Confidence};
+
+/// Response headers: lowercase name to all values
+pub type HeaderMap = HashMap<String, Vec<String>>;
+
+/// Finding severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Points deducted from the 100-point score
+    fn weight(self) -> u32 {
+        match self {
+            Severity::Info => 0,
+            Severity::Low => 3,
+            Severity::Medium => 7,
+            Severity::High => 15,
+            Severity::Critical => 25,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "ℹ️ Info",
+            Severity::Low => "🟡 Low",
+            Severity::Medium => "🟠 Medium",
+            Severity::High => "🔴 High",
+            Severity::Critical => "🟣 Critical",
+        }
+    }
+}
+
+/// A single issue found by a check
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// Check that produced it: headers, cookies, tls, cors, xss, secrets
+    pub category: String,
+    pub severity: Severity,
+    pub title: String,
+    pub detail: String,
+    pub recommendation: String,
+}
+
+impl Finding {
+    fn new(category: &str, severity: Severity, title: &str, detail: &str, recommendation: &str) -> Self {
+        Finding {
+            category: category.to_string(),
+            severity,
+            title: title.to_string(),
+            detail: detail.to_string(),
+            recommendation: recommendation.to_string(),
+        }
+    }
+}
+
+/// Aggregated scan report
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanReport {
+    pub target: String,
+    /// Unix timestamp (seconds)
+    pub scanned_at: i64,
+    /// 0-100, higher is better
+    pub score: u32,
+    pub grade: String,
+    pub categories: Vec<String>,
+    pub findings: Vec<Finding>,
+    /// Checks that could not run, with the reason
+    pub errors: Vec<String>,
+}
+
+impl ScanReport {
+    /// Build a report, ordering findings by severity
+    pub fn new(target: &str, scanned_at: i64, categories: &[&str], mut findings: Vec<Finding>, errors: Vec<String>) -> Self {
+        findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+        let score = score(&findings);
+        ScanReport {
+            target: target.to_string(),
+            scanned_at,
+            score,
+            grade: grade(score).to_string(),
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            findings,
+            errors,
+        }
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|f| f.severity == severity).count()
+    }
+
+    /// Pentest-style Markdown document
+    pub fn to_markdown(&self) -> String {
+        let date = chrono::DateTime::from_timestamp(self.scanned_at, 0)
+            .map(|d| d.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default();
+
+        let mut md = format!("# Security Assessment: {}\n\n", self.target);
+        md.push_str(&format!("**Date:** {}  \n**Score:** {}/100 (Grade {})  \n**Checks:** {}\n\n",
+            date, self.score, self.grade, self.categories.join(", ")));
+
+        md.push_str("## Executive Summary\n\n");
+        md.push_str("| Severity | Count |\n|---|---|\n");
+        for severity in [Severity::Critical, Severity::High, Severity::Medium, Severity::Low, Severity::Info] {
+            md.push_str(&format!("| {} | {} |\n", severity.label(), self.count(severity)));
+        }
+
+        md.push_str("\n## Findings\n\n");
+        if self.findings.is_empty() {
+            md.push_str("No issues found by the automated checks.\n");
+        }
+        for (i, f) in self.findings.iter().enumerate() {
+            md.push_str(&format!("### {}. {} — {}\n\n", i + 1, f.title, f.severity.label()));
+            md.push_str(&format!("- **Category:** {}\n- **Details:** {}\n- **Recommendation:** {}\n\n",
+                f.category, f.detail, f.recommendation));
+        }
+
+        if !self.errors.is_empty() {
+            md.push_str("## Checks Not Completed\n\n");
+            for e in &self.errors {
+                md.push_str(&format!("- {}\n", e));
+            }
+            md.push('\n');
+        }
+
+        md.push_str("## Scope and Limitations\n\n");
+        md.push_str("Automated, non-intrusive checks of a single response. Results should be confirmed manually; \
+            absence of findings does not mean absence of vulnerabilities.\n");
+        md
+    }
+}
+
+/// 100 minus severity weights, floored at 0
+pub fn score(findings: &[Finding]) -> u32 {
+    100u32.saturating_sub(findings.iter().map(|f| f.severity.weight()).sum())
+}
+
+pub fn grade(score: u32) -> &'static str {
+    match score {
+        90..=100 => "A",
+        75..=89 => "B",
+        60..=74 => "C",
+        40..=59 => "D",
+        _ => "F",
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.first()).map(|s| s.as_str())
+}
+
+/// Missing security headers and information disclosure
+pub fn check_headers(headers: &HeaderMap) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let csp = header(headers, "content-security-policy");
+
+    match csp {
+        None => findings.push(Finding::new("headers", Severity::Medium, "Missing Content-Security-Policy",
+            "No CSP header; injected scripts run unrestricted.",
+            "Define a Content-Security-Policy, starting with default-src 'self'.")),
+        Some(policy) => findings.extend(analyze_csp(policy)),
+    }
+    findings.extend(check_clickjacking(headers, false).findings);
+    if header(headers, "x-content-type-options").is_none_or(|v| !v.eq_ignore_ascii_case("nosniff")) {
+        findings.push(Finding::new("headers", Severity::Low, "Missing X-Content-Type-Options",
+            "Browsers may MIME-sniff responses.",
+            "Set X-Content-Type-Options: nosniff."));
+    }
+    if header(headers, "referrer-policy").is_none() {
+        findings.push(Finding::new("headers", Severity::Low, "Missing Referrer-Policy",
+            "Full URLs may leak to third parties via the Referer header.",
+            "Set Referrer-Policy: strict-origin-when-cross-origin."));
+    }
+    if header(headers, "permissions-policy").is_none() {
+        findings.push(Finding::new("headers", Severity::Info, "Missing Permissions-Policy",
+            "Powerful browser features are not explicitly restricted.",
+            "Set a Permissions-Policy disabling unused features."));
+    }
+    for name in ["server", "x-powered-by", "x-aspnet-version"] {
+        if let Some(value) = header(headers, name) {
+            if value.chars().any(|c| c.is_ascii_digit()) {
+                findings.push(Finding::new("headers", Severity::Low, "Version disclosure",
+                    &format!("{} header reveals '{}'.", name, value),
+                    "Remove or genericize version information in response headers."));
+            }
+        }
+    }
+    findings
+}
+
+/// Script hosts that serve JSONP endpoints or arbitrary packages, which
+/// let an attacker run their own code under an allowlist that includes them
+const CSP_BYPASS_HOSTS: &[&str] = &[
+    "ajax.googleapis.com", "www.google.com", "accounts.google.com",
+    "cdnjs.cloudflare.com", "cdn.jsdelivr.net", "unpkg.com",
+    "raw.githubusercontent.com", "*.googleusercontent.com", "*.cloudfront.net",
+    "*.amazonaws.com", "*.herokuapp.com", "*.firebaseapp.com", "*.appspot.com",
+];
+
+/// Parse a policy into directive name -> sources (lowercase, first occurrence wins)
+pub fn parse_csp(policy: &str) -> Vec<(String, Vec<String>)> {
+    let mut directives: Vec<(String, Vec<String>)> = Vec::new();
+    for directive in policy.split(';') {
+        let mut tokens = directive.split_whitespace();
+        let Some(name) = tokens.next() else { continue };
+        let name = name.to_lowercase();
+        if directives.iter().any(|(n, _)| n == &name) {
+            continue;
+        }
+        directives.push((name, tokens.map(|t| t.to_lowercase()).collect()));
+    }
+    directives
+}
+
+/// Weak directives in a Content-Security-Policy
+pub fn analyze_csp(policy: &str) -> Vec<Finding> {
+    let directives = parse_csp(policy);
+    let get = |name: &str| directives.iter().find(|(n, _)| n == name).map(|(_, sources)| sources);
+    let default_src = get("default-src");
+    let mut findings = Vec::new();
+    
+    match get("script-src").or(default_src) {
+        None => findings.push(Finding::new("csp", Severity::High, "CSP does not restrict scripts",
+            "Neither script-src nor default-src is set, so any script source is allowed.",
+            "Add script-src (or default-src) 'self', ideally with nonces.")),
+        Some(script) => {
+            let has = |s: &str| script.iter().any(|x| x == s);
+            let nonce_or_hash = script.iter().any(|s| s.starts_with("'nonce-") || s.starts_with("'sha"));
+            let strict_dynamic = has("'strict-dynamic'");
+            
+            if has("'unsafe-inline'") && !nonce_or_hash {
+                findings.push(Finding::new("csp", Severity::High, "script-src allows 'unsafe-inline'",
+                    "Inline scripts and event handlers run, so CSP gives no XSS protection.",
+                    "Remove 'unsafe-inline' and use nonces or hashes for inline scripts."));
+            }
+            if has("'unsafe-eval'") {
+                findings.push(Finding::new("csp", Severity::Medium, "script-src allows 'unsafe-eval'",
+                    "eval(), new Function() and string timers are permitted.",
+                    "Remove 'unsafe-eval' and refactor code that evaluates strings."));
+            }
+            if !strict_dynamic {
+                let wildcards: Vec<&String> = script.iter()
+                    .filter(|s| matches!(s.as_str(), "*" | "http:" | "https:" | "data:" | "blob:" | "http://*" | "https://*"))
+                    .collect();
+                if !wildcards.is_empty() {
+                    findings.push(Finding::new("csp", Severity::High, "Wildcard script sources",
+                        &format!("script-src allows {}, so scripts can load from attacker-controlled hosts.",
+                            wildcards.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")),
+                        "List specific hosts, or use nonces with 'strict-dynamic'."));
+                }
+                let bypassable: Vec<&String> = script.iter()
+                    .filter(|s| {
+                        let host = s.trim_start_matches("https://").trim_start_matches("http://");
+                        let host = host.split('/').next().unwrap_or(host);
+                        CSP_BYPASS_HOSTS.contains(&host)
+                    })
+                    .collect();
+                if !bypassable.is_empty() {
+                    findings.push(Finding::new("csp", Severity::Medium, "Script allowlist includes bypassable hosts",
+                        &format!("{} host JSONP endpoints or arbitrary user content.",
+                            bypassable.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")),
+                        "Prefer nonce-based CSP with 'strict-dynamic' over host allowlists."));
+                }
+            }
+            if script.iter().any(|s| s.starts_with("http://")) {
+                findings.push(Finding::new("csp", Severity::Low, "Insecure script sources",
+                    "script-src allows http:// hosts, which can be hijacked in transit.",
+                    "Use https:// sources only."));
+            }
+        }
+    }
+    
+    match get("object-src").or(default_src) {
+        None => findings.push(Finding::new("csp", Severity::Medium, "object-src not restricted",
+            "Plugins (<object>/<embed>) can load from anywhere and bypass script-src.",
+            "Set object-src 'none'.")),
+        Some(object) if object.iter().any(|s| s == "*" || s == "data:" || s == "http:" || s == "https:") => {
+            findings.push(Finding::new("csp", Severity::Medium, "Permissive object-src",
+                &format!("object-src allows {}.", object.join(" ")),
+                "Set object-src 'none'."));
+        }
+        _ => {}
+    }
+    if get("base-uri").is_none() {
+        findings.push(Finding::new("csp", Severity::Low, "Missing base-uri",
+            "An injected <base> tag can redirect relative script URLs.",
+            "Set base-uri 'none' or 'self'."));
+    }
+    match get("frame-ancestors") {
+        None => findings.push(Finding::new("csp", Severity::Low, "Missing frame-ancestors",
+            "The policy does not control who may frame the page; X-Frame-Options is the legacy fallback.",
+            "Set frame-ancestors 'none' (or 'self').")),
+        Some(ancestors) if ancestors.iter().any(|s| s == "*" || s == "https:" || s == "http:") => {
+            findings.push(Finding::new("csp", Severity::Medium, "Permissive frame-ancestors",
+                &format!("frame-ancestors {} lets any site frame the page.", ancestors.join(" ")),
+                "Set frame-ancestors 'none' or list trusted origins."));
+        }
+        _ => {}
+    }
+    if get("form-action").is_none() {
+        findings.push(Finding::new("csp", Severity::Info, "Missing form-action",
+            "Injected forms can submit data to any origin (form-action does not fall back to default-src).",
+            "Set form-action 'self'."));
+    }
+    if get("report-uri").is_none() && get("report-to").is_none() {
+        findings.push(Finding::new("csp", Severity::Info, "No CSP violation reporting",
+            "Violations are not reported, so attacks and breakage go unnoticed.",
+            "Add report-to (or report-uri) pointing at a collection endpoint."));
+    }
+    findings
+}
+
+/// Handshake details from the proxy's /tls-info endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsInfo {
+    pub host: String,
+    pub protocol: Option<String>,
+    pub cipher: Option<String>,
+    /// Protocol version -> accepted by the server
+    #[serde(default)]
+    pub protocols: BTreeMap<String, bool>,
+    pub trusted: bool,
+    pub verification_error: Option<String>,
+    /// Leaf first
+    #[serde(default)]
+    pub chain: Vec<CertInfo>,
+}
+
+/// One certificate of the presented chain
+#[derive(Debug, Clone, Deserialize)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// Unix timestamps (seconds)
+    pub not_before: i64,
+    pub not_after: i64,
+    #[serde(default)]
+    pub san: Vec<String>,
+    pub signature_algorithm: String,
+    pub key: String,
+    #[serde(default)]
+    pub self_signed: bool,
+}
+
+/// Whether a certificate name (possibly a wildcard) covers a host
+fn name_matches(pattern: &str, host: &str) -> bool {
+    let (pattern, host) = (pattern.to_lowercase(), host.to_lowercase());
+    match pattern.strip_prefix("*.") {
+        Some(base) => host.split_once('.').is_some_and(|(_, rest)| rest == base),
+        None => pattern == host,
+    }
+}
+
+/// Certificate and protocol findings from a server-side handshake
+pub fn check_tls_info(info: &TlsInfo, now: i64) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    
+    if !info.trusted {
+        findings.push(Finding::new("tls", Severity::High, "Untrusted certificate",
+            info.verification_error.as_deref().unwrap_or("The chain does not validate against public roots."),
+            "Install a certificate from a publicly trusted CA with the full intermediate chain."));
+    }
+    
+    if let Some(leaf) = info.chain.first() {
+        let days_left = (leaf.not_after - now) / 86_400;
+        if leaf.not_after < now {
+            findings.push(Finding::new("tls", Severity::Critical, "Certificate expired",
+                &format!("Expired {} days ago.", -days_left),
+                "Renew the certificate and automate renewal (e.g. ACME)."));
+        } else if days_left < 7 {
+            findings.push(Finding::new("tls", Severity::High, "Certificate about to expire",
+                &format!("Expires in {} days.", days_left),
+                "Renew the certificate now and automate renewal."));
+        } else if days_left < 30 {
+            findings.push(Finding::new("tls", Severity::Medium, "Certificate expires soon",
+                &format!("Expires in {} days.", days_left),
+                "Renew the certificate and automate renewal."));
+        }
+        if leaf.not_before > now {
+            findings.push(Finding::new("tls", Severity::High, "Certificate not yet valid",
+                "The notBefore date is in the future.", "Check the certificate dates and server clock."));
+        }
+        if !leaf.san.is_empty() && !leaf.san.iter().any(|name| name_matches(name, &info.host)) {
+            findings.push(Finding::new("tls", Severity::High, "Hostname mismatch",
+                &format!("{} is not in the certificate names: {}.", info.host, leaf.san.join(", ")),
+                "Issue a certificate that covers this hostname."));
+        }
+        if leaf.self_signed {
+            findings.push(Finding::new("tls", Severity::Medium, "Self-signed certificate",
+                &format!("Subject and issuer are both {}.", leaf.subject),
+                "Use a certificate issued by a trusted CA."));
+        }
+        let key_bits = leaf.key.split_whitespace().nth(1).and_then(|b| b.parse::<u32>().ok());
+        if leaf.key.starts_with("RSA") && key_bits.is_some_and(|b| b < 2048) {
+            findings.push(Finding::new("tls", Severity::High, "Weak RSA key",
+                &format!("The certificate key is {}.", leaf.key),
+                "Use RSA 2048+ or an ECDSA P-256 key."));
+        }
+    }
+    
+    // Root certificates are trusted by identity, so only leaf and intermediates count
+    for cert in info.chain.iter().filter(|c| !c.self_signed || info.chain.len() == 1) {
+        let algorithm = cert.signature_algorithm.to_lowercase();
+        if algorithm.contains("sha1") || algorithm.contains("md5") {
+            findings.push(Finding::new("tls", Severity::High, "Weak certificate signature",
+                &format!("{} is signed with {}.", cert.subject, cert.signature_algorithm),
+                "Reissue the certificate with a SHA-256 (or stronger) signature."));
+        }
+    }
+    
+    let legacy: Vec<&str> = ["TLSv1.0", "TLSv1.1"].into_iter()
+        .filter(|v| info.protocols.get(*v).copied().unwrap_or(false))
+        .collect();
+    if !legacy.is_empty() {
+        findings.push(Finding::new("tls", Severity::Medium, "Legacy TLS versions enabled",
+            &format!("The server accepts {}.", legacy.join(" and ")),
+            "Disable TLS 1.0 and 1.1; support only TLS 1.2 and 1.3."));
+    }
+    if info.protocols.get("TLSv1.3") == Some(&false) {
+        findings.push(Finding::new("tls", Severity::Low, "TLS 1.3 not supported",
+            "Only older protocol versions are offered.",
+            "Enable TLS 1.3 for faster, more secure handshakes."));
+    }
+    findings
+}
+
+/// Result of a framing-protection analysis
+#[derive(Debug, Clone, Serialize)]
+pub struct ClickjackingAnalysis {
+    /// Whether browsers will refuse to frame the page cross-origin
+    pub protected: bool,
+    /// Which header provides the protection, if any
+    pub protection: Option<String>,
+    pub findings: Vec<Finding>,
+}
+
+/// Evaluate X-Frame-Options and CSP frame-ancestors
+///
+/// `sensitive` raises the severity when the page has forms or password fields.
+pub fn check_clickjacking(headers: &HeaderMap, sensitive: bool) -> ClickjackingAnalysis {
+    let mut findings = Vec::new();
+    let mut protection = None;
+    
+    // CSP frame-ancestors takes precedence over X-Frame-Options in modern browsers
+    let ancestors = headers.get("content-security-policy").into_iter().flatten()
+        .flat_map(|csp| csp.split(';'))
+        .map(|d| d.trim())
+        .find_map(|d| d.strip_prefix("frame-ancestors"))
+        .map(|sources| sources.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>());
+    
+    if let Some(sources) = &ancestors {
+        if sources.iter().any(|s| s == "*" || s == "https:" || s == "http:") {
+            findings.push(Finding::new("clickjacking", Severity::Medium, "Permissive frame-ancestors",
+                &format!("frame-ancestors {} lets any site frame the page.", sources.join(" ")),
+                "Use frame-ancestors 'none' or list only trusted origins."));
+        } else {
+            protection = Some(format!("CSP frame-ancestors {}", sources.join(" ")));
+        }
+    }
+    
+    let xfo: Vec<String> = headers.get("x-frame-options").into_iter().flatten()
+        .map(|v| v.trim().to_uppercase())
+        .collect();
+    if xfo.len() > 1 && xfo.iter().any(|v| v != &xfo[0]) {
+        findings.push(Finding::new("clickjacking", Severity::Low, "Conflicting X-Frame-Options",
+            &format!("Multiple values sent: {}. Browsers may ignore the header.", xfo.join(", ")),
+            "Send a single X-Frame-Options header."));
+    }
+    match xfo.first().map(|v| v.as_str()) {
+        Some("DENY") | Some("SAMEORIGIN") => {
+            protection.get_or_insert_with(|| format!("X-Frame-Options {}", xfo[0]));
+        }
+        Some(v) if v.starts_with("ALLOW-FROM") && protection.is_none() => {
+            findings.push(Finding::new("clickjacking", Severity::Medium, "Obsolete X-Frame-Options ALLOW-FROM",
+                "ALLOW-FROM is ignored by modern browsers, so the page can be framed by anyone.",
+                "Replace it with CSP frame-ancestors listing the allowed origin."));
+        }
+        Some(v) if protection.is_none() => {
+            findings.push(Finding::new("clickjacking", Severity::Medium, "Invalid X-Frame-Options",
+                &format!("Unrecognized value '{}' is ignored by browsers.", v),
+                "Use X-Frame-Options: DENY or SAMEORIGIN."));
+        }
+        _ => {}
+    }
+    
+    let report_only = headers.get("content-security-policy-report-only").into_iter().flatten()
+        .any(|csp| csp.contains("frame-ancestors"));
+    if protection.is_none() && report_only {
+        findings.push(Finding::new("clickjacking", Severity::Info, "frame-ancestors only in report-only mode",
+            "Content-Security-Policy-Report-Only does not block framing.",
+            "Move frame-ancestors into the enforced Content-Security-Policy header."));
+    }
+    
+    if protection.is_none() && !findings.iter().any(|f| f.severity >= Severity::Medium) {
+        let severity = if sensitive { Severity::High } else { Severity::Medium };
+        findings.push(Finding::new("clickjacking", severity, "No clickjacking protection",
+            "Neither X-Frame-Options nor CSP frame-ancestors is set.",
+            "Set X-Frame-Options: DENY or CSP frame-ancestors 'none'."));
+    }
+    
+    ClickjackingAnalysis {
+        protected: protection.is_some(),
+        protection,
+        findings,
+    }
+}
+
+/// Set-Cookie flags
+pub fn check_cookies(headers: &HeaderMap, https: bool) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for cookie in headers.get("set-cookie").into_iter().flatten() {
+        let name = cookie.split('=').next().unwrap_or("").trim();
+        let attrs = cookie.to_lowercase();
+        let mut missing = Vec::new();
+        if https && !attrs.contains("secure") {
+            missing.push("Secure");
+        }
+        if !attrs.contains("httponly") {
+            missing.push("HttpOnly");
+        }
+        if !attrs.contains("samesite") {
+            missing.push("SameSite");
+        }
+        if missing.is_empty() {
+            continue;
+        }
+        let sensitive = ["sess", "auth", "token", "sid", "jwt"].iter().any(|s| name.to_lowercase().contains(s));
+        let severity = if sensitive { Severity::Medium } else { Severity::Low };
+        findings.push(Finding::new("cookies", severity, &format!("Cookie '{}' missing flags", name),
+            &format!("Missing: {}.", missing.join(", ")),
+            "Set Secure, HttpOnly, and SameSite=Lax (or Strict) on cookies."));
+    }
+    findings
+}
+
+/// Transport security: HTTPS and HSTS
+pub fn check_tls(url: &str, headers: &HeaderMap) -> Vec<Finding> {
+    if !url.starts_with("https://") {
+        return vec![Finding::new("tls", Severity::High, "Served over plain HTTP",
+            "Traffic can be read and modified in transit.",
+            "Serve the site over HTTPS and redirect HTTP to HTTPS.")];
+    }
+    match header(headers, "strict-transport-security") {
+        None => vec![Finding::new("tls", Severity::Medium, "HSTS not enabled",
+            "Browsers may be downgraded to HTTP on first visit.",
+            "Set Strict-Transport-Security: max-age=31536000; includeSubDomains.")],
+        Some(value) => {
+            let max_age = value.split(';')
+                .find_map(|p| p.trim().strip_prefix("max-age="))
+                .and_then(|v| v.trim_matches('"').parse::<u64>().ok())
+                .unwrap_or(0);
+            if max_age < 15_552_000 {
+                vec![Finding::new("tls", Severity::Low, "Short HSTS max-age",
+                    &format!("max-age is {} seconds.", max_age),
+                    "Use a max-age of at least 180 days (15552000).")]
+            } else {
+                vec![]
+            }
+        }
+    }
+}
+
+/// CORS response to a request sent with `Origin: <probe_origin>`
+pub fn check_cors(headers: &HeaderMap, probe_origin: &str) -> Vec<Finding> {
+    let acao = match header(headers, "access-control-allow-origin") {
+        Some(v) => v.trim(),
+        None => return vec![],
+    };
+    let credentials = header(headers, "access-control-allow-credentials")
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+
+    if acao == probe_origin || acao == "null" {
+        let severity = if credentials { Severity::Critical } else { Severity::High };
+        vec![Finding::new("cors", severity, "CORS reflects arbitrary origins",
+            &format!("Origin '{}' was allowed{}.", probe_origin, if credentials { " with credentials" } else { "" }),
+            "Validate Origin against an explicit allowlist.")]
+    } else if acao == "*" {
+        vec![Finding::new("cors", Severity::Low, "CORS allows any origin",
+            "Access-Control-Allow-Origin: * exposes responses to every site.",
+            "Restrict Access-Control-Allow-Origin unless the resource is truly public.")]
+    } else {
+        vec![]
+    }
+}
+
+/// Passive XSS indicators in the page source
+pub fn check_xss(body: &str) -> Vec<Finding> {
+    let lower = body.to_lowercase();
+    let indicators = [
+        ("document.write(", "document.write usage"),
+        ("eval(", "eval() usage"),
+        (".innerhtml", "innerHTML assignment"),
+        ("javascript:", "javascript: URLs"),
+    ];
+    let found: Vec<&str> = indicators.iter()
+        .filter(|(p, _)| lower.contains(p))
+        .map(|(_, d)| *d)
+        .collect();
+    let inline_handlers = ["onerror=", "onload=", "onclick=", "onmouseover="].iter()
+        .filter(|p| lower.contains(*p))
+        .count();
+
+    let mut findings = Vec::new();
+    if !found.is_empty() {
+        findings.push(Finding::new("xss", Severity::Low, "DOM XSS sinks in page source",
+            &format!("Found: {}.", found.join(", ")),
+            "Avoid dangerous sinks; use textContent and safe templating."));
+    }
+    if inline_handlers > 0 {
+        findings.push(Finding::new("xss", Severity::Info, "Inline event handlers",
+            &format!("{} kinds of inline handlers found; these prevent a strict CSP.", inline_handlers),
+            "Move handlers to external scripts so CSP can forbid inline code."));
+    }
+    findings
+}
+
+/// An http:// subresource on an HTTPS page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MixedResource {
+    /// Element that loads it, e.g. "script", "img", "form"
+    pub element: String,
+    pub url: String,
+    /// Active content (scripts, frames, styles) can rewrite the page; browsers block it
+    pub active: bool,
+}
+
+/// Insecure subresources referenced by page markup
+pub fn find_mixed_content(body: &str) -> Vec<MixedResource> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    static CSS_URL: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(
+        r"(?is)<(script|iframe|frame|img|audio|video|source|track|embed|object|link|form|input)\b[^>]*>"
+    ).unwrap());
+    let attr = ATTR.get_or_init(|| Regex::new(
+        r#"(?is)\s(src|href|data|action|srcset|poster)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#
+    ).unwrap());
+    let css_url = CSS_URL.get_or_init(|| Regex::new(r#"(?i)url\(\s*["']?(http://[^"')\s]+)"#).unwrap());
+
+    let mut found: Vec<MixedResource> = Vec::new();
+    let mut push = |element: &str, url: &str, active: bool| {
+        if url.to_lowercase().starts_with("http://") && !found.iter().any(|r| r.url == url && r.element == element) {
+            found.push(MixedResource { element: element.to_string(), url: url.to_string(), active });
+        }
+    };
+
+    for t in tag.captures_iter(body) {
+        let element = t[1].to_lowercase();
+        let markup = t[0].to_lowercase();
+        let active = match element.as_str() {
+            "script" | "iframe" | "frame" | "embed" | "object" | "form" => true,
+            // Stylesheets and preloads are active; icons and other links are passive
+            "link" => !markup.contains("icon") && (markup.contains("stylesheet") || markup.contains("preload") || markup.contains("import")),
+            _ => false,
+        };
+        if element == "link" && !active && !markup.contains("icon") {
+            // Plain navigation links (canonical, alternate) load nothing
+            continue;
+        }
+        for a in attr.captures_iter(&t[0]) {
+            let value = a.get(2).or(a.get(3)).or(a.get(4)).map(|m| m.as_str().trim()).unwrap_or("");
+            if a[1].eq_ignore_ascii_case("srcset") {
+                for candidate in value.split(',') {
+                    push(&element, candidate.split_whitespace().next().unwrap_or(""), active);
+                }
+            } else {
+                push(&element, value, active);
+            }
+        }
+    }
+    for c in css_url.captures_iter(body) {
+        push("css url()", &c[1], false);
+    }
+    found
+}
+
+/// Mixed content findings for an HTTPS page
+pub fn check_mixed_content(url: &str, headers: &HeaderMap, body: &str) -> Vec<Finding> {
+    if !url.starts_with("https://") {
+        return Vec::new();
+    }
+    let resources = find_mixed_content(body);
+    if resources.is_empty() {
+        return Vec::new();
+    }
+    let upgraded = header(headers, "content-security-policy")
+        .is_some_and(|csp| csp.contains("upgrade-insecure-requests"));
+    let list = |items: Vec<&MixedResource>| items.iter().take(5)
+        .map(|r| format!("<{}> {}", r.element, r.url))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut findings = Vec::new();
+    let forms: Vec<&MixedResource> = resources.iter().filter(|r| r.element == "form").collect();
+    let active: Vec<&MixedResource> = resources.iter().filter(|r| r.active && r.element != "form").collect();
+    let passive: Vec<&MixedResource> = resources.iter().filter(|r| !r.active).collect();
+    let fix = "Serve every subresource over HTTPS, or add CSP upgrade-insecure-requests.";
+
+    if !forms.is_empty() {
+        findings.push(Finding::new("mixed_content", Severity::High, "Form submits over HTTP",
+            &format!("{} form(s) post to plain HTTP: {}.", forms.len(), list(forms)),
+            "Point form actions at HTTPS endpoints."));
+    }
+    if !active.is_empty() {
+        let severity = if upgraded { Severity::Info } else { Severity::Medium };
+        findings.push(Finding::new("mixed_content", severity, "Active mixed content",
+            &format!("{} script/frame/style resource(s) load over HTTP and will be blocked or hijackable: {}.", active.len(), list(active)),
+            fix));
+    }
+    if !passive.is_empty() {
+        let severity = if upgraded { Severity::Info } else { Severity::Low };
+        findings.push(Finding::new("mixed_content", severity, "Passive mixed content",
+            &format!("{} image/media resource(s) load over HTTP and can be spoofed: {}.", passive.len(), list(passive)),
+            fix));
+    }
+    findings
+}
+
+/// Secrets exposed in the page source
+pub fn check_secrets(body: &str) -> Vec<Finding> {
+    secrets::scan(body).into_iter()
+        .filter(|s| s.confidence > Confidence::Low)
+        .map(|s| {
+            let severity = if s.confidence == Confidence::High { Severity::High } else { Severity::Medium };
+            Finding::new("secrets", severity, &format!("Exposed {}", s.label),
+                &format!("Line {}, column {}: {}.", s.line, s.column, s.preview),
+                "Remove the secret from client-side code and rotate it.")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.entry(k.to_string()).or_default().push(v.to_string());
+        }
+        map
+    }
+
+    #[test]
+    fn test_score_and_grade() {
+        let findings = vec![
+            Finding::new("cors", Severity::Critical, "a", "", ""),
+            Finding::new("tls", Severity::Medium, "b", "", ""),
+        ];
+        assert_eq!(score(&findings), 68);
+        assert_eq!(grade(68), "C");
+        assert_eq!(score(&vec![Finding::new("x", Severity::Critical, "", "", ""); 5]), 0);
+    }
+
+    #[test]
+    fn test_hardened_site_has_no_header_findings() {
+        let h = headers(&[
+            ("content-security-policy", "default-src 'self'; object-src 'none'; base-uri 'none'; frame-ancestors 'none'; form-action 'self'; report-to csp"),
+            ("x-content-type-options", "nosniff"),
+            ("referrer-policy", "no-referrer"),
+            ("permissions-policy", "camera=()"),
+            ("strict-transport-security", "max-age=31536000"),
+            ("set-cookie", "sid=1; Secure; HttpOnly; SameSite=Lax"),
+        ]);
+        assert!(check_headers(&h).is_empty());
+        assert!(check_tls("https://example.com", &h).is_empty());
+        assert!(check_cookies(&h, true).is_empty());
+    }
+
+    #[test]
+    fn test_analyze_csp() {
+        let titles = |policy: &str| -> Vec<String> { analyze_csp(policy).into_iter().map(|f| f.title).collect() };
+        
+        let strict = "default-src 'self'; script-src 'nonce-abc' 'strict-dynamic' https: 'unsafe-inline'; object-src 'none'; \
+            base-uri 'none'; frame-ancestors 'none'; form-action 'self'; report-to csp";
+        assert!(titles(strict).is_empty());
+        
+        let weak = "script-src 'self' 'unsafe-inline' 'unsafe-eval' * cdn.jsdelivr.net; frame-ancestors *";
+        assert_eq!(titles(weak), vec![
+            "script-src allows 'unsafe-inline'", "script-src allows 'unsafe-eval'", "Wildcard script sources",
+            "Script allowlist includes bypassable hosts", "object-src not restricted", "Missing base-uri",
+            "Permissive frame-ancestors", "Missing form-action", "No CSP violation reporting",
+        ]);
+        assert_eq!(titles("upgrade-insecure-requests")[0], "CSP does not restrict scripts");
+    }
+
+    fn tls_info(json: serde_json::Value) -> TlsInfo {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_check_tls_info() {
+        let now = 1_800_000_000;
+        let healthy = tls_info(serde_json::json!({
+            "host": "www.example.com", "protocol": "TLSv1.3", "cipher": "TLS13_AES_128_GCM_SHA256",
+            "protocols": {"TLSv1.0": false, "TLSv1.1": false, "TLSv1.2": true, "TLSv1.3": true},
+            "trusted": true, "verification_error": null,
+            "chain": [
+                {"subject": "CN=www.example.com", "issuer": "CN=CA", "not_before": now - 86_400, "not_after": now + 90 * 86_400,
+                 "san": ["*.example.com"], "signature_algorithm": "sha256WithRSAEncryption", "key": "RSA 2048", "self_signed": false}
+            ]
+        }));
+        assert!(check_tls_info(&healthy, now).is_empty());
+
+        let broken = tls_info(serde_json::json!({
+            "host": "example.com", "protocols": {"TLSv1.0": true, "TLSv1.3": false},
+            "trusted": false, "verification_error": "expired",
+            "chain": [
+                {"subject": "CN=other.com", "issuer": "CN=other.com", "not_before": 0, "not_after": now - 86_400,
+                 "san": ["other.com"], "signature_algorithm": "sha1WithRSAEncryption", "key": "RSA 1024", "self_signed": true}
+            ]
+        }));
+        let titles: Vec<String> = check_tls_info(&broken, now).into_iter().map(|f| f.title).collect();
+        assert_eq!(titles, vec![
+            "Untrusted certificate", "Certificate expired", "Hostname mismatch", "Self-signed certificate",
+            "Weak RSA key", "Weak certificate signature", "Legacy TLS versions enabled", "TLS 1.3 not supported",
+        ]);
+    }
+
+    #[test]
+    fn test_mixed_content() {
+        let html = r#"<script src="http://cdn.example.com/a.js"></script>
+            <img src='http://img.example.com/x.png' srcset="https://ok/1.png 1x, http://img.example.com/2.png 2x">
+            <link rel="canonical" href="http://example.com/"><link rel="stylesheet" href=http://example.com/s.css>
+            <form action="http://example.com/login"><div style="background: url('http://example.com/bg.png')">"#;
+        let found = find_mixed_content(html);
+        assert_eq!(found.iter().filter(|r| r.active).count(), 3);
+        assert_eq!(found.iter().filter(|r| !r.active).count(), 3);
+        assert!(!found.iter().any(|r| r.url == "http://example.com/"));
+
+        let findings = check_mixed_content("https://example.com", &headers(&[]), html);
+        assert_eq!(findings.iter().map(|f| f.severity).collect::<Vec<_>>(), vec![Severity::High, Severity::Medium, Severity::Low]);
+        assert!(check_mixed_content("http://example.com", &headers(&[]), html).is_empty());
+    }
+
+    #[test]
+    fn test_clickjacking_analysis() {
+        let open = check_clickjacking(&headers(&[]), true);
+        assert!(!open.protected);
+        assert_eq!(open.findings[0].severity, Severity::High);
+        
+        let xfo = check_clickjacking(&headers(&[("x-frame-options", "sameorigin")]), false);
+        assert!(xfo.protected && xfo.findings.is_empty());
+        
+        let allow_from = check_clickjacking(&headers(&[("x-frame-options", "ALLOW-FROM https://a.example")]), false);
+        assert!(!allow_from.protected);
+        assert_eq!(allow_from.findings.len(), 1);
+        
+        // frame-ancestors wins over a broken X-Frame-Options
+        let csp = check_clickjacking(&headers(&[
+            ("content-security-policy", "default-src 'self'; frame-ancestors 'none'"),
+            ("x-frame-options", "ALLOW-FROM https://a.example"),
+        ]), false);
+        assert!(csp.protected && csp.findings.is_empty());
+    }
+
+    #[test]
+    fn test_cors_reflection_with_credentials_is_critical() {
+        let h = headers(&[
+            ("access-control-allow-origin", "https://evil.example"),
+            ("access-control-allow-credentials", "true"),
+        ]);
+        let findings = check_cors(&h, "https://evil.example");
+        assert_eq!(findings[0].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn test_report_markdown() {
+        let findings = vec![Finding::new("cookies", Severity::Low, "Cookie 'x' missing flags", "Missing: HttpOnly.", "Set flags.")];
+        let report = ScanReport::new("https://example.com", 0, &["cookies"], findings, vec![]);
+        let md = report.to_markdown();
+        assert!(md.starts_with("# Security Assessment: https://example.com"));
+        assert!(md.contains("**Score:** 97/100 (Grade A)"));
+        assert!(md.contains("### 1. Cookie 'x' missing flags"));
+    }
+}
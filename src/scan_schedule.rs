@@ -0,0 +1,261 @@
+//! Recurring security scans for claWasm
+//!
+//! Targets registered here are re-scanned with scan_full whenever
+//! `runDueScans` finds them due. Each run is diffed against the previous
+//! one; new findings are alerted through the audit log and, if configured,
+//! a Slack/Discord-compatible webhook or a channel named in the proxy
+//! config (whose credentials then never reach the browser).
+
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+use crate::error::{Error, Result};
+use crate::platform::storage;
+use crate::scan_report::{Finding, ScanReport, Severity};
+use crate::vault;
+
+/// localStorage key holding scheduled scans
+pub const SCAN_SCHEDULE_KEY: &str = "clawasm_scan_schedule";
+/// Shortest allowed interval between runs of one target
+pub const MIN_INTERVAL_MINUTES: u32 = 15;
+
+/// Identity of a finding for drift comparison
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindingKey {
+    pub category: String,
+    pub severity: Severity,
+    pub title: String,
+}
+
+impl From<&Finding> for FindingKey {
+    fn from(finding: &Finding) -> Self {
+        FindingKey {
+            category: finding.category.clone(),
+            severity: finding.severity,
+            title: finding.title.clone(),
+        }
+    }
+}
+
+/// A target scanned on a fixed interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledScan {
+    pub id: String,
+    pub target: String,
+    pub interval_minutes: u32,
+    /// Slack/Discord-style webhook notified when new findings appear
+    #[serde(default)]
+    pub alert_webhook: Option<String>,
+    /// Channel instance of the proxy config notified the same way
+    #[serde(default)]
+    pub alert_channel: Option<String>,
+    /// Only alert on new findings at or above this severity
+    #[serde(default = "default_alert_severity")]
+    pub alert_severity: Severity,
+    /// Unix timestamps (seconds)
+    pub created_at: i64,
+    #[serde(default)]
+    pub last_run: Option<i64>,
+    #[serde(default)]
+    pub last_score: Option<u32>,
+    #[serde(default)]
+    pub last_findings: Vec<FindingKey>,
+}
+
+fn default_alert_severity() -> Severity {
+    Severity::Low
+}
+
+/// Options accepted by `scheduleScan`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScheduleOptions {
+    pub alert_webhook: Option<String>,
+    pub alert_channel: Option<String>,
+    pub alert_severity: Option<Severity>,
+}
+
+/// Difference between two runs of the same target
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    pub schedule_id: String,
+    pub target: String,
+    pub previous_score: Option<u32>,
+    pub score: u32,
+    pub new_findings: Vec<FindingKey>,
+    pub resolved: Vec<FindingKey>,
+    /// Whether this run should alert (new findings at the alert severity)
+    pub alert: bool,
+}
+
+impl ScheduledScan {
+    pub fn new(target: &str, interval_minutes: u32, options: ScheduleOptions, now: i64) -> Self {
+        ScheduledScan {
+            id: format!("scan_{}", chrono::Utc::now().timestamp_millis()),
+            target: target.to_string(),
+            interval_minutes: interval_minutes.max(MIN_INTERVAL_MINUTES),
+            alert_webhook: options.alert_webhook.filter(|w| !w.trim().is_empty()),
+            alert_channel: options.alert_channel.filter(|c| !c.trim().is_empty()),
+            alert_severity: options.alert_severity.unwrap_or_else(default_alert_severity),
+            created_at: now,
+            last_run: None,
+            last_score: None,
+            last_findings: Vec::new(),
+        }
+    }
+
+    pub fn is_due(&self, now: i64) -> bool {
+        self.last_run.is_none_or(|last| now - last >= i64::from(self.interval_minutes) * 60)
+    }
+
+    /// Record a finished run and return its drift against the previous one
+    ///
+    /// The first run sets the baseline and never alerts.
+    pub fn record_run(&mut self, report: &ScanReport, now: i64) -> Drift {
+        let current: Vec<FindingKey> = report.findings.iter().map(FindingKey::from).collect();
+        let baseline = self.last_run.is_none();
+        let new_findings: Vec<FindingKey> = current.iter()
+            .filter(|f| !self.last_findings.contains(f))
+            .cloned()
+            .collect();
+        let resolved: Vec<FindingKey> = self.last_findings.iter()
+            .filter(|f| !current.contains(f))
+            .cloned()
+            .collect();
+        let drift = Drift {
+            schedule_id: self.id.clone(),
+            target: self.target.clone(),
+            previous_score: self.last_score,
+            score: report.score,
+            alert: !baseline && new_findings.iter().any(|f| f.severity >= self.alert_severity),
+            new_findings: if baseline { Vec::new() } else { new_findings },
+            resolved,
+        };
+        self.last_run = Some(now);
+        self.last_score = Some(report.score);
+        self.last_findings = current;
+        drift
+    }
+}
+
+impl Drift {
+    /// Notification level: critical when a new finding is high or worse
+    pub fn level(&self) -> &'static str {
+        match self.new_findings.iter().any(|f| f.severity >= Severity::High) {
+            true => "critical",
+            false => "warning",
+        }
+    }
+
+    /// Human-readable alert text
+    pub fn message(&self) -> String {
+        let mut lines = vec![format!(
+            "🔔 Scheduled scan of {}: {} new issue(s), score {} → {}",
+            self.target,
+            self.new_findings.len(),
+            self.previous_score.map(|s| s.to_string()).unwrap_or_else(|| "-".to_string()),
+            self.score
+        )];
+        lines.extend(self.new_findings.iter().map(|f| format!("- {} [{}] {}", f.severity.label(), f.category, f.title)));
+        if !self.resolved.is_empty() {
+            lines.push(format!("Resolved: {}", self.resolved.iter().map(|f| f.title.as_str()).collect::<Vec<_>>().join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+thread_local! {
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Held while a batch of due scans runs; overlapping ticks are skipped
+pub struct RunGuard(());
+
+impl RunGuard {
+    pub fn acquire() -> Option<Self> {
+        RUNNING.with(|running| (!running.replace(true)).then_some(RunGuard(())))
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        RUNNING.with(|running| running.set(false));
+    }
+}
+
+/// Load scheduled scans
+pub fn load() -> Result<Vec<ScheduledScan>> {
+    match storage::get(SCAN_SCHEDULE_KEY)? {
+        Some(stored) => serde_json::from_str(&vault::open(&stored)?)
+            .map_err(|e| Error::new(format!("Corrupt scan schedule: {}", e))),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Save scheduled scans
+pub fn save(scans: &[ScheduledScan]) -> Result<()> {
+    let json = serde_json::to_string(scans)
+        .map_err(|e| Error::new(format!("Serialize error: {}", e)))?;
+    storage::set(SCAN_SCHEDULE_KEY, &vault::seal(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> ScheduledScan {
+        ScheduledScan {
+            id: "scan_1".to_string(),
+            target: "https://example.com".to_string(),
+            interval_minutes: 60,
+            alert_webhook: None,
+            alert_channel: None,
+            alert_severity: Severity::Medium,
+            created_at: 0,
+            last_run: None,
+            last_score: None,
+            last_findings: Vec::new(),
+        }
+    }
+
+    fn report(findings: &[(Severity, &str)]) -> ScanReport {
+        let findings = findings.iter().map(|(severity, title)| Finding {
+            category: "headers".to_string(),
+            severity: *severity,
+            title: title.to_string(),
+            detail: String::new(),
+            recommendation: String::new(),
+        }).collect();
+        ScanReport::new("https://example.com", 0, &["headers"], findings, Vec::new())
+    }
+
+    #[test]
+    fn test_due_and_drift() {
+        let mut scan = schedule();
+        assert!(scan.is_due(0));
+
+        let first = scan.record_run(&report(&[(Severity::Low, "Missing Referrer-Policy")]), 1000);
+        assert!(!first.alert && first.new_findings.is_empty());
+        assert!(!scan.is_due(1000 + 59 * 60));
+        assert!(scan.is_due(1000 + 60 * 60));
+
+        // A new Low finding is below the alert threshold
+        let second = scan.record_run(&report(&[(Severity::Low, "Missing Referrer-Policy"), (Severity::Low, "Short HSTS max-age")]), 5000);
+        assert_eq!(second.new_findings.len(), 1);
+        assert!(!second.alert);
+
+        let third = scan.record_run(&report(&[(Severity::High, "Untrusted certificate")]), 9000);
+        assert!(third.alert);
+        assert_eq!(third.resolved.len(), 2);
+        assert!(third.message().contains("Untrusted certificate"));
+        assert_eq!(third.level(), "critical");
+    }
+
+    #[test]
+    fn test_run_guard_blocks_overlap() {
+        let guard = RunGuard::acquire().unwrap();
+        assert!(RunGuard::acquire().is_none());
+        drop(guard);
+        assert!(RunGuard::acquire().is_some());
+    }
+}
@@ -0,0 +1,190 @@
+//! Sharing a conversation as an encrypted link
+//!
+//! `export` seals a saved session with a fresh AES-256-GCM key, stores the
+//! ciphertext through the proxy's `/paste` and answers with a link that
+//! carries the paste id and the key in its fragment:
+//! `https://app.example/#share=<id>&key=<key>`. Browsers never send a
+//! fragment, so neither the proxy nor the server of the page sees the key,
+//! and the conversation only exists in plaintext in the browsers of the
+//! people holding the link. `import` takes such a link, fetches the
+//! ciphertext from the configured proxy and opens it again as a session in
+//! the web UI's format. A link never picks the host it is fetched from: it
+//! comes from whoever sent it, and the proxy token must not follow it. So
+//! sender and reader share through the same proxy, typically the one
+//! serving the page. Whoever has the link can read the conversation until
+//! the proxy forgets it (after a week by default).
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD};
+use serde_json::Value;
+
+use crate::config::proxy_endpoint;
+use crate::error::{Error, Result};
+use crate::platform::{self, HttpRequest};
+
+/// Fields of a session that are shared; ids and local state stay behind
+const SHARED_FIELDS: [&str; 5] = ["title", "provider", "model", "createdAt", "messages"];
+
+/// Where a shared conversation is and how to open it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareLink {
+    /// Paste id at the proxy
+    pub id: String,
+    /// 32-byte key, base64url
+    pub key: String,
+}
+
+impl ShareLink {
+    /// `base` (the page, without its fragment) with the share in the fragment
+    pub fn to_url(&self, base: &str) -> String {
+        let base = base.split('#').next().unwrap_or_default();
+        format!("{}#share={}&key={}", base, self.id, self.key)
+    }
+
+    /// A link made by `to_url`, or just its fragment; anything else in it
+    /// is ignored
+    pub fn parse(link: &str) -> Result<ShareLink> {
+        let fragment = link.rsplit_once('#').map_or(link, |(_, fragment)| fragment);
+        let mut share = ShareLink { id: String::new(), key: String::new() };
+        for (name, value) in fragment.split('&').filter_map(|pair| pair.split_once('=')) {
+            match name {
+                "share" => share.id = value.to_string(),
+                "key" => share.key = value.to_string(),
+                _ => {}
+            }
+        }
+        if share.id.is_empty() || !share.id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(Error::new("Not a share link: it has no valid #share= id"));
+        }
+        if share.key.is_empty() {
+            return Err(Error::new("The share link has no key; was it cut off?"));
+        }
+        Ok(share)
+    }
+}
+
+/// The parts of `session` that are shared, as JSON
+fn shared_session(session: &Value) -> Value {
+    let fields = SHARED_FIELDS.iter()
+        .filter_map(|field| session.get(*field).map(|value| (field.to_string(), value.clone())))
+        .collect();
+    Value::Object(fields)
+}
+
+/// base64(iv || ciphertext || tag)
+fn seal_with(key: &[u8; 32], iv: &[u8; 12], plaintext: &str) -> String {
+    let cipher = Aes256Gcm::new(key.into());
+    let ciphertext = cipher.encrypt(Nonce::from_slice(iv), plaintext.as_bytes())
+        .expect("AES-GCM encryption cannot fail for in-memory buffers");
+    let mut payload = iv.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    BASE64.encode(payload)
+}
+
+fn open_with(key: &str, data: &str) -> Result<String> {
+    let key: [u8; 32] = URL_SAFE_NO_PAD.decode(key).ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| Error::new("The share link's key is damaged"))?;
+    let payload = BASE64.decode(data.trim())
+        .map_err(|e| Error::new(format!("The shared conversation is corrupt: {}", e)))?;
+    if payload.len() < 12 + 16 {
+        return Err(Error::new("The shared conversation is corrupt: too short"));
+    }
+    let (iv, ciphertext) = payload.split_at(12);
+    let plaintext = Aes256Gcm::new((&key).into()).decrypt(Nonce::from_slice(iv), ciphertext)
+        .map_err(|_| Error::new("The shared conversation can't be decrypted: the key doesn't match"))?;
+    String::from_utf8(plaintext).map_err(|e| Error::new(format!("The shared conversation is not UTF-8: {}", e)))
+}
+
+/// The error the proxy gave, or the status
+fn proxy_error(response: &platform::HttpResponse) -> String {
+    serde_json::from_str::<Value>(&response.body).ok()
+        .and_then(|v| v["error"].as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("HTTP {}", response.status))
+}
+
+/// Encrypt `session`, store it at the proxy and answer with its link on
+/// `base` (the page the link should open)
+pub async fn export(session: &Value, proxy_url: &str, base: &str) -> Result<String> {
+    let mut key = [0u8; 32];
+    let mut iv = [0u8; 12];
+    platform::fill_random(&mut key)?;
+    platform::fill_random(&mut iv)?;
+    let data = seal_with(&key, &iv, &shared_session(session).to_string());
+
+    let endpoint = proxy_endpoint(proxy_url, "/paste");
+    let response = platform::fetch(HttpRequest::post_json(&endpoint, &serde_json::json!({ "data": data }))).await?;
+    if !response.ok() {
+        return Err(Error::new(format!("The proxy did not keep the share: {}. Is it up to date?", proxy_error(&response))));
+    }
+    let id = response.json::<Value>()?["id"].as_str()
+        .ok_or_else(|| Error::new("The proxy answered without a share id"))?
+        .to_string();
+    Ok(ShareLink { id, key: URL_SAFE_NO_PAD.encode(key) }.to_url(base))
+}
+
+/// The session a share link points to, fetched from `proxy_url` (never a
+/// host named by the link) and decrypted
+pub async fn import(link: &str, proxy_url: &str) -> Result<Value> {
+    let share = ShareLink::parse(link)?;
+    let response = platform::fetch(HttpRequest::get(&proxy_endpoint(proxy_url, &format!("/paste/{}", share.id)))).await?;
+    if !response.ok() {
+        return Err(Error::new(format!("The shared conversation is not available: {}", proxy_error(&response))));
+    }
+    let data = response.json::<Value>()?["data"].as_str().unwrap_or_default().to_string();
+    let session: Value = serde_json::from_str(&open_with(&share.key, &data)?)
+        .map_err(|e| Error::new(format!("The shared conversation is corrupt: {}", e)))?;
+    if !session["messages"].is_array() {
+        return Err(Error::new("The shared conversation has no messages"));
+    }
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::transport::tests::block_on;
+    use crate::platform::MockTransport;
+    use serde_json::json;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_links() {
+        let share = ShareLink { id: "0a1b".into(), key: "k-y_".into() };
+        let url = share.to_url("https://app.example/index.html#old");
+        assert_eq!(url, "https://app.example/index.html#share=0a1b&key=k-y_");
+        assert_eq!(ShareLink::parse(&url).unwrap(), share);
+        assert_eq!(ShareLink::parse("share=0a1b&key=k-y_&via=https%3A%2F%2Fevil.example").unwrap(), share);
+
+        assert!(ShareLink::parse("https://app.example/").unwrap_err().message().contains("Not a share link"));
+        assert!(ShareLink::parse("#share=../x&key=k").is_err());
+        assert!(ShareLink::parse("#share=0a1b").unwrap_err().message().contains("no key"));
+    }
+
+    #[test]
+    fn test_import_decrypts_what_was_sealed() {
+        let session = json!({"id": "session_1", "title": "Trip", "messages": [{"role": "user", "content": "Lisbon in May?"}]});
+        let shared = shared_session(&session);
+        assert_eq!(shared, json!({"title": "Trip", "messages": [{"role": "user", "content": "Lisbon in May?"}]}));
+
+        let key = [9u8; 32];
+        let data = seal_with(&key, &[3u8; 12], &shared.to_string());
+        assert!(!data.contains("Lisbon") && !BASE64.decode(&data).unwrap().windows(6).any(|w| w == b"Lisbon"));
+
+        let mock = Rc::new(MockTransport::new());
+        mock.respond_json("http://localhost:3000/paste/0a1b", &json!({"data": data}));
+        platform::set_transport(mock.clone());
+        let link = format!("{}&via=https%3A%2F%2Fevil.example", ShareLink { id: "0a1b".into(), key: URL_SAFE_NO_PAD.encode(key) }
+            .to_url("https://app.example/"));
+        let opened = block_on(import(&link, "http://localhost:3000")).unwrap();
+        let wrong = ShareLink { key: URL_SAFE_NO_PAD.encode([1u8; 32]), ..ShareLink::parse(&link).unwrap() };
+        let refused = block_on(import(&wrong.to_url("https://app.example/"), "http://localhost:3000"));
+        platform::reset_transport();
+
+        assert_eq!(opened, shared);
+        assert!(refused.unwrap_err().message().contains("key doesn't match"));
+        assert!(mock.requests().iter().all(|r| r.method == "GET" && r.url == "http://localhost:3000/paste/0a1b"), "only the configured proxy");
+    }
+}
@@ -0,0 +1,207 @@
+//! Strict tool-call grammar for models without native tool calling
+//!
+//! With `tool_format: "strict"` the system prompt asks for calls in one
+//! exact shape,
+//!
+//! ```text
+//! <<<CALL
+//! {"name": "web_search", "arguments": {"query": "rust wasm"}}
+//! CALL>>>
+//! ```
+//!
+//! and `parse` accepts nothing else: each block holds one JSON object with
+//! a string `name` and an object `arguments`, checked against the tool's
+//! parameters. A reply that breaks the grammar comes back as every problem
+//! found, which the agent sends to the model asking for a corrected reply
+//! (up to `MAX_REASKS` times a turn) instead of guessing what was meant.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::{self, ToolCall};
+use crate::tool_xml;
+use crate::tools::schema::{self, Unknown};
+use crate::tools::ToolDefinition;
+
+/// How the model is asked to write tool calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolFormat {
+    /// ```tool blocks, read leniently along with bare JSON and XML calls
+    #[default]
+    Loose,
+    /// `<<<CALL` blocks only, validated, with malformed calls sent back
+    Strict,
+}
+
+/// Opens a call block, on a line of its own
+pub const OPEN: &str = "<<<CALL";
+/// Closes a call block
+pub const CLOSE: &str = "CALL>>>";
+
+/// Corrections asked for in one turn before a malformed reply is taken as
+/// the answer
+pub const MAX_REASKS: usize = 2;
+
+/// The system prompt's part on calling tools
+pub const INSTRUCTIONS: &str = "To use a tool, write one block per call in exactly this form:\n\
+    <<<CALL\n\
+    {\"name\": \"tool_name\", \"arguments\": {\"parameter\": \"value\"}}\n\
+    CALL>>>\n\n\
+    Each block holds a single JSON object with exactly two keys: \"name\", one of the tools above, \
+    and \"arguments\", an object with the tool's parameters ({} when it takes none). \
+    Use double quotes, and no comments or trailing commas. Do not use ```tool blocks or bare JSON. \
+    A reply without blocks is your final answer.";
+
+/// The calls in `text`, or every way it breaks the grammar
+pub fn parse(text: &str, definitions: &[ToolDefinition]) -> Result<Vec<ToolCall>, Vec<String>> {
+    let mut calls = Vec::new();
+    let mut errors = Vec::new();
+    let mut rest = text;
+    let mut blocks = 0;
+    while let Some(start) = rest.find(OPEN) {
+        blocks += 1;
+        let body = &rest[start + OPEN.len()..];
+        let Some(end) = body.find(CLOSE) else {
+            errors.push(format!("Call {}: no {} line closes it", blocks, CLOSE));
+            break;
+        };
+        let result = match body[..end].contains(OPEN) {
+            true => Err(format!("a new {} starts before {} closes this one", OPEN, CLOSE)),
+            false => read_call(body[..end].trim(), definitions),
+        };
+        match result {
+            Ok(call) => calls.push(call),
+            Err(e) => errors.push(format!("Call {}: {}", blocks, e)),
+        }
+        rest = &body[end + CLOSE.len()..];
+    }
+    if blocks == 0 {
+        // Calls in the loose formats: the model forgot the grammar
+        let known = |call: &ToolCall| definitions.iter().any(|d| d.name == call.name);
+        let marked = text.contains("```tool") || tool_xml::has_markup(text);
+        if let Some(call) = agent::parse_all_tool_calls(text).into_iter().find(|call| marked || known(call)) {
+            errors.push(format!("the call to '{}' is not in a {} block", call.name, OPEN));
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(calls),
+        false => Err(errors),
+    }
+}
+
+/// The message asking for a corrected reply
+pub fn reask(errors: &[String]) -> String {
+    format!(
+        "Your last reply broke the tool call format, so no tool was run:\n- {}\n\n\
+        Reply again, either with a final answer and no {} blocks, or with each call corrected and written as\n\
+        {}\n{{\"name\": \"tool_name\", \"arguments\": {{...}}}}\n{}",
+        errors.join("\n- "), OPEN, OPEN, CLOSE
+    )
+}
+
+/// `text` without its call blocks
+pub fn strip(text: &str) -> String {
+    let mut kept = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+        kept.push_str(&rest[..start]);
+        let body = &rest[start + OPEN.len()..];
+        rest = body.find(CLOSE).map(|end| &body[end + CLOSE.len()..]).unwrap_or("");
+    }
+    kept.push_str(rest);
+    kept.trim().to_string()
+}
+
+/// One block's call, checked against the tool's parameters
+fn read_call(json: &str, definitions: &[ToolDefinition]) -> Result<ToolCall, String> {
+    if json.is_empty() {
+        return Err("the block is empty".to_string());
+    }
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("invalid JSON ({})", e))?;
+    let Value::Object(mut object) = value else {
+        return Err("expected a JSON object".to_string());
+    };
+    let name = match object.remove("name") {
+        Some(Value::String(name)) if !name.trim().is_empty() => name,
+        Some(_) => return Err("\"name\" must be a tool name in quotes".to_string()),
+        None => return Err("\"name\" is missing".to_string()),
+    };
+    let arguments = match object.remove("arguments") {
+        Some(Value::Object(arguments)) => arguments,
+        Some(_) => return Err(format!("\"arguments\" of '{}' must be an object", name)),
+        None => return Err(format!("\"arguments\" of '{}' is missing (use {{}} for none)", name)),
+    };
+    let mut problems: Vec<String> = object.keys()
+        .map(|key| format!("unexpected key \"{}\" (only \"name\" and \"arguments\" belong at the top)", key))
+        .collect();
+    // Tools of the user's own have no definition here; the runner checks those
+    if let Some(definition) = definitions.iter().find(|d| d.name == name) {
+        let violations = schema::validate(&definition.parameters, &Value::Object(arguments.clone()), Unknown::Reject);
+        if !violations.is_empty() {
+            problems.extend(violations.iter().map(|v| v.to_string()));
+            problems.push(format!("parameters: {}", schema::signature(&definition.parameters)));
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(ToolCall { name, arguments: Value::Object(arguments) }),
+        false => Err(format!("'{}': {}", name, problems.join("; "))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::builtin_definitions;
+
+    fn block(json: &str) -> String {
+        format!("{}\n{}\n{}", OPEN, json, CLOSE)
+    }
+
+    #[test]
+    fn test_parse_valid_calls() {
+        let definitions = builtin_definitions();
+        let text = format!("Searching both.\n{}\n{}",
+            block(r#"{"name": "web_search", "arguments": {"query": "rust"}}"#),
+            block(r#"{"name": "reddit_search", "arguments": {"query": "wasm", "limit": 5}}"#));
+        let calls = parse(&text, &definitions).unwrap();
+        assert_eq!(calls.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["web_search", "reddit_search"]);
+        assert_eq!(calls[1].arguments["limit"], 5);
+        assert_eq!(strip(&text), "Searching both.");
+
+        // No blocks and nothing call-like: a final answer
+        assert!(parse("The answer is 42. Config: {\"name\": \"Bob\"}", &definitions).unwrap().is_empty());
+        // Tools without a definition here are left to the runner
+        assert_eq!(parse(&block(r#"{"name": "my_tool", "arguments": {"x": 1}}"#), &definitions).unwrap()[0].name, "my_tool");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let definitions = builtin_definitions();
+        let errors = |text: &str| parse(text, &definitions).unwrap_err();
+
+        assert!(errors(&block(r#"{"name": "web_search", "arguments": {"query": "x",}}"#))[0].starts_with("Call 1: invalid JSON"));
+        assert_eq!(errors(&block(r#"{"name": "web_search"}"#)), ["Call 1: \"arguments\" of 'web_search' is missing (use {} for none)"]);
+        assert_eq!(errors(&block(r#"{"name": "web_search", "query": "x", "arguments": {}}"#)),
+            ["Call 1: 'web_search': unexpected key \"query\" (only \"name\" and \"arguments\" belong at the top); query: is required; parameters: query (string, required)"]);
+        assert_eq!(errors(&block(r#"{"name": "reddit_search", "arguments": {"query": "x", "limit": "5", "sort": "new"}}"#)),
+            ["Call 1: 'reddit_search': limit: must be an integer, not a string; sort: is not a parameter of this tool; \
+            parameters: limit (integer), query (string, required), subreddit (string)"]);
+        assert_eq!(errors(&block(r#"{"name": "research", "arguments": {"topic": "x", "depth": "extreme"}}"#)),
+            ["Call 1: 'research': depth: must be one of \"quick\", \"normal\", \"deep\", not \"extreme\"; parameters: depth (\"quick\" | \"normal\" | \"deep\"), \
+            format (\"markdown\" | \"json\"), min_relevance (number), queries (array of string), rounds (integer), \
+            summarize (\"provider\" | \"extractive\"), topic (string, required)"]);
+
+        // Every block is checked; an unclosed one ends the reply
+        let text = format!("{}\n{}\n{{\"name\": \"calculate\"", block(r#"{"name": "get_current_time", "arguments": {}}"#), OPEN);
+        assert_eq!(errors(&text), ["Call 2: no CALL>>> line closes it"]);
+        assert_eq!(strip(&text), "");
+
+        // The loose formats are refused, naming the call
+        assert_eq!(errors("```tool\n{\"name\": \"calculate\", \"arguments\": {\"expression\": \"1+1\"}}\n```"),
+            ["the call to 'calculate' is not in a <<<CALL block"]);
+        assert_eq!(errors("{\"name\": \"web_search\", \"query\": \"x\"}").len(), 1);
+
+        assert!(reask(&["Call 1: invalid JSON".to_string()]).contains("- Call 1: invalid JSON\n"));
+    }
+}
@@ -0,0 +1,328 @@
+//! XML tool calls of GLM and other local models
+//!
+//! GLM-4.5 and later call tools as
+//!
+//! ```text
+//! <tool_call>web_search
+//! <arg_key>query</arg_key>
+//! <arg_value>rust wasm</arg_value>
+//! </tool_call>
+//! ```
+//!
+//! with string values written as they are and other values as JSON. Served
+//! through chat templates that lose part of the markup, or cut off at a stop
+//! token, the same call arrives in variants: closing tags missing, the name
+//! given as a `name` argument, the arguments as a JSON object in front of
+//! the tags, or a JSON call (`{"name": ..., "arguments": ...}`) inside
+//! `<tool_call>` as Hermes-style templates write it. `parse` reads all of
+//! them; the corpus in tests/fixtures/glm_tool_calls.json pins what it
+//! makes of each.
+
+use serde_json::{Map, Value};
+
+use crate::agent::ToolCall;
+use crate::logger;
+use crate::tools::{self, ToolDefinition};
+
+const OPEN: &str = "<tool_call>";
+const CLOSE: &str = "</tool_call>";
+const KEY: &str = "<arg_key>";
+const KEY_END: &str = "</arg_key>";
+const VALUE: &str = "<arg_value>";
+const VALUE_END: &str = "</arg_value>";
+
+/// Longest tool name accepted
+const MAX_NAME_CHARS: usize = 64;
+
+/// Whether `text` has tool call markup worth parsing
+pub fn has_markup(text: &str) -> bool {
+    text.contains(OPEN) || text.contains(KEY)
+}
+
+/// Every call in `text`, in order
+pub fn parse(text: &str) -> Vec<ToolCall> {
+    let definitions = tools::get_tool_definitions();
+    blocks(text)
+        .into_iter()
+        .filter_map(|(_, body)| {
+            let call = parse_block(body, &definitions);
+            if call.is_none() {
+                logger::debug("agent", &format!("Unreadable tool call: {}", body.trim().chars().take(200).collect::<String>()));
+            }
+            call
+        })
+        .collect()
+}
+
+/// `text` without its tool call blocks
+pub fn strip(text: &str) -> String {
+    let mut kept = String::new();
+    let mut last = 0;
+    for (whole, _) in blocks(text) {
+        kept.push_str(&text[last..whole.0]);
+        last = whole.1;
+    }
+    kept.push_str(&text[last..]);
+    kept.trim().to_string()
+}
+
+/// The blocks of `text`: their span, tags included, and their body
+///
+/// A block ends at its closing tag, the next opening one or the end of the
+/// text. Argument tags with no opening tag at all (templates that put it
+/// in the prompt) make one block of everything up to the first closing tag.
+fn blocks(text: &str) -> Vec<((usize, usize), &str)> {
+    if !text.contains(OPEN) {
+        if !text.contains(KEY) {
+            return Vec::new();
+        }
+        let end = text.find(CLOSE).unwrap_or(text.len());
+        let whole = text.find(CLOSE).map(|end| end + CLOSE.len()).unwrap_or(text.len());
+        return vec![((0, whole), &text[..end])];
+    }
+    let mut blocks = Vec::new();
+    let mut at = 0;
+    while let Some(start) = text[at..].find(OPEN).map(|start| at + start) {
+        let body_start = start + OPEN.len();
+        let body = &text[body_start..];
+        let end = [body.find(CLOSE), body.find(OPEN)].into_iter().flatten().min().unwrap_or(body.len());
+        let whole = match body[end..].starts_with(CLOSE) {
+            true => body_start + end + CLOSE.len(),
+            false => body_start + end,
+        };
+        blocks.push(((start, whole), &body[..end]));
+        at = whole;
+    }
+    blocks
+}
+
+fn parse_block(body: &str, definitions: &[ToolDefinition]) -> Option<ToolCall> {
+    let (head, tags) = body.split_at(body.find(KEY).unwrap_or(body.len()));
+    let mut name = None;
+    let mut arguments = Map::new();
+
+    // A JSON call, or arguments in JSON ahead of the tags
+    let (json, outside) = leading_json(head);
+    if let Some(Value::Object(object)) = json {
+        let (json_name, json_arguments) = json_call(object);
+        name = json_name;
+        arguments = json_arguments;
+    }
+    // Otherwise the name is what precedes the tags, on its own line
+    if name.is_none() {
+        let outside = &outside[..outside.find('<').unwrap_or(outside.len())];
+        name = outside.lines().map(str::trim).rfind(|line| !line.is_empty()).map(str::to_string);
+    }
+
+    // Or, failing that, a `name` argument
+    let pairs = pairs(tags);
+    let named_by_argument = name.as_deref().is_none_or(|name| !valid_name(name));
+    if named_by_argument {
+        name = pairs.iter().find(|(key, _)| *key == "name").map(|(_, value)| value.trim().to_string());
+    }
+    let name = name.filter(|name| valid_name(name))?;
+    let definition = definitions.iter().find(|d| d.name == name);
+    for (key, raw) in pairs {
+        match key {
+            "name" if named_by_argument => {}
+            "arguments" | "parameters" if declared_type(definition, key).is_none() => match serde_json::from_str(raw.trim()) {
+                Ok(Value::Object(object)) => arguments.extend(object),
+                _ => {
+                    arguments.insert(key.to_string(), typed(definition, key, raw));
+                }
+            },
+            _ => {
+                arguments.insert(key.to_string(), typed(definition, key, raw));
+            }
+        }
+    }
+    Some(ToolCall { name, arguments: Value::Object(arguments) })
+}
+
+/// The first JSON object in `text`, and the text around it
+fn leading_json(text: &str) -> (Option<Value>, String) {
+    let Some(start) = text.find('{') else {
+        return (None, text.to_string());
+    };
+    let mut stream = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>();
+    match stream.next() {
+        Some(Ok(value)) => {
+            let end = start + stream.byte_offset();
+            (Some(value), format!("{}{}", &text[..start], &text[end..]))
+        }
+        _ => (None, text.to_string()),
+    }
+}
+
+/// Name and arguments of a JSON call: `{"name", "arguments"}`, with
+/// `parameters` for `arguments`, OpenAI's `{"function": {...}}` wrapping,
+/// arguments as a JSON string, or just the arguments
+fn json_call(mut object: Map<String, Value>) -> (Option<String>, Map<String, Value>) {
+    if let Some(Value::Object(function)) = object.remove("function") {
+        object = function;
+    }
+    let name = match object.remove("name") {
+        Some(Value::String(name)) => Some(name),
+        _ => None,
+    };
+    let arguments = object.remove("arguments").or_else(|| object.remove("parameters"));
+    let arguments = match arguments {
+        Some(Value::String(json)) => serde_json::from_str(&json).ok(),
+        Some(Value::Object(arguments)) => Some(arguments),
+        _ => None,
+    };
+    (name, arguments.unwrap_or(object))
+}
+
+/// `(key, value)` pairs of the argument tags, tolerating missing closing
+/// tags; a key with no value is dropped
+fn pairs(mut rest: &str) -> Vec<(&str, &str)> {
+    let mut pairs = Vec::new();
+    while let Some(start) = rest.find(KEY) {
+        let after = &rest[start + KEY.len()..];
+        let key_end = [after.find(KEY_END), after.find(VALUE), after.find(KEY)].into_iter().flatten().min().unwrap_or(after.len());
+        let key = after[..key_end].trim();
+        let tail = &after[key_end..];
+        let tail = tail.strip_prefix(KEY_END).unwrap_or(tail);
+        match tail.trim_start().strip_prefix(VALUE) {
+            Some(value) => {
+                let end = [value.find(VALUE_END), value.find(KEY)].into_iter().flatten().min().unwrap_or(value.len());
+                if !key.is_empty() {
+                    pairs.push((key, trim_newlines(&value[..end])));
+                }
+                rest = &value[end..];
+            }
+            None => rest = tail,
+        }
+    }
+    pairs
+}
+
+/// `value` without the line breaks the tags are written on; other
+/// whitespace may belong to it (code, file contents)
+fn trim_newlines(value: &str) -> &str {
+    let value = value.strip_prefix("\r\n").or_else(|| value.strip_prefix('\n')).unwrap_or(value);
+    value.strip_suffix("\r\n").or_else(|| value.strip_suffix('\n')).unwrap_or(value)
+}
+
+fn declared_type<'a>(definition: Option<&'a ToolDefinition>, key: &str) -> Option<&'a str> {
+    definition?.parameters["properties"][key]["type"].as_str()
+}
+
+/// An argument value: as written for string parameters, parsed when it is
+/// JSON other than a string otherwise
+fn typed(definition: Option<&ToolDefinition>, key: &str, raw: &str) -> Value {
+    if declared_type(definition, key) == Some("string") {
+        return Value::String(raw.to_string());
+    }
+    match serde_json::from_str::<Value>(raw.trim()) {
+        Ok(value) if !value.is_string() => value,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && name.len() <= MAX_NAME_CHARS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// One model output of the corpus and the calls it holds
+    #[derive(Deserialize)]
+    struct Fixture {
+        source: String,
+        output: String,
+        calls: Vec<ToolCall>,
+        /// What is left for a best-effort answer
+        #[serde(default)]
+        text: Option<String>,
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let fixtures: Vec<Fixture> = serde_json::from_str(include_str!("../tests/fixtures/glm_tool_calls.json")).unwrap();
+        for fixture in fixtures {
+            let calls: Vec<(String, Value)> = parse(&fixture.output).into_iter().map(|c| (c.name, c.arguments)).collect();
+            let expected: Vec<(String, Value)> = fixture.calls.into_iter().map(|c| (c.name, c.arguments)).collect();
+            assert_eq!(calls, expected, "{}", fixture.source);
+            if let Some(text) = fixture.text {
+                assert_eq!(strip(&fixture.output), text, "{}", fixture.source);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Written the way GLM's chat template writes a call
+        fn render(name: &str, arguments: &[(String, Value)]) -> String {
+            let mut out = format!("{}{}\n", OPEN, name);
+            for (key, value) in arguments {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                out.push_str(&format!("{}{}{}\n{}{}{}\n", KEY, key, KEY_END, VALUE, value, VALUE_END));
+            }
+            out + CLOSE
+        }
+
+        fn argument() -> impl Strategy<Value = Value> {
+            prop_oneof![
+                // Text that reads as plain text, not as JSON or markup
+                "[a-zA-Z][a-zA-Z0-9 .,:/?=&_-]{0,40}|[a-z][a-z ]{0,20}\n[a-z0-9 ]{1,20}"
+                    .prop_filter("reads as JSON", |s| serde_json::from_str::<Value>(s.trim()).is_err())
+                    .prop_map(Value::String),
+                any::<i32>().prop_map(Value::from),
+                any::<bool>().prop_map(Value::from),
+                prop::collection::vec("[a-z]{1,8}", 0..4).prop_map(Value::from),
+            ]
+        }
+
+        fn call() -> impl Strategy<Value = (String, Vec<(String, Value)>)> {
+            // Not the name of a built-in tool, whose parameter types apply
+            ("x_[a-z_]{0,20}", prop::collection::btree_map("[a-z][a-z_]{0,12}", argument(), 0..5))
+                .prop_map(|(name, arguments)| (name, arguments.into_iter().collect()))
+        }
+
+        proptest! {
+            #[test]
+            fn rendered_calls_parse_back(calls in prop::collection::vec(call(), 1..4), prose in "[a-zA-Z ,.]{0,40}") {
+                let text = format!("{}\n{}", prose, calls.iter().map(|(name, args)| render(name, args)).collect::<Vec<_>>().join("\n"));
+                let parsed = parse(&text);
+                prop_assert_eq!(parsed.len(), calls.len());
+                for (parsed, (name, arguments)) in parsed.iter().zip(&calls) {
+                    prop_assert_eq!(&parsed.name, name);
+                    prop_assert_eq!(&parsed.arguments, &Value::Object(arguments.iter().cloned().collect()));
+                }
+                prop_assert_eq!(strip(&text), prose.trim());
+            }
+
+            #[test]
+            fn truncated_calls_keep_what_arrived(call in call(), cut in 0usize..400) {
+                // Cut off anywhere, as by a stop token or the output limit
+                let text = render(&call.0, &call.1);
+                let cut = text.char_indices().map(|(i, _)| i).nth(cut).unwrap_or(text.len());
+                for parsed in parse(&text[..cut]) {
+                    prop_assert!(call.0.starts_with(&parsed.name));
+                }
+            }
+
+            #[test]
+            fn never_panics(text in "(<tool_call>|</tool_call>|<arg_key>|</arg_key>|<arg_value>|</arg_value>|[{}\"a-z:\n ]){0,30}") {
+                for parsed in parse(&text) {
+                    prop_assert!(valid_name(&parsed.name));
+                }
+                strip(&text);
+            }
+        }
+    }
+}
@@ -0,0 +1,1410 @@
+//! Tools module for claWasm - Skills and function calling
+//!
+//! Tool definitions and safety tiers are shared by every build (the
+//! catalog), and calls are checked against the definitions' parameter
+//! schemas (`schema`). Each build registers the tools it runs in the
+//! `registry`: the implementations in `browser` need the browser (and the
+//! local proxy), while `native` runs a subset directly over HTTP.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::platform::storage;
+use crate::scan_report::{self, HeaderMap};
+use crate::secrets::{self, Confidence};
+use crate::vault;
+
+pub mod registry;
+pub mod schema;
+
+pub use registry::{Tool, ToolCategory, ToolContext, ToolEnv, ToolRegistry};
+
+#[cfg(feature = "web")]
+mod browser;
+#[cfg(feature = "web")]
+pub mod plugin;
+#[cfg(feature = "web")]
+pub use browser::{execute_tool, execute_tool_with, fetch_via_proxy, run_full_scan, send_notification, send_webhook, set_page_tools, JsTool, ProxiedResponse};
+
+#[cfg(all(feature = "native", not(feature = "web")))]
+mod native;
+#[cfg(all(feature = "native", not(feature = "web")))]
+pub use native::{execute_tool, NativeTools};
+
+/// Tool safety tier
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolTier {
+    /// Reads or creates data without destroying anything
+    #[default]
+    ReadOnly,
+    /// Deletes data, changes the assistant itself, or sends externally;
+    /// always requires explicit approval
+    Mutating,
+    /// Reads or operates the page the assistant is embedded in; always
+    /// requires explicit approval
+    Page,
+}
+
+/// Tool definition for AI function calling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+    #[serde(default)]
+    pub tier: ToolTier,
+}
+
+/// Tool execution result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResult {
+    pub name: String,
+    pub result: String,
+    pub success: bool,
+}
+
+/// Definitions of the tools this build runs, and of the page's own
+pub fn get_tool_definitions() -> Vec<ToolDefinition> {
+    registry::with(ToolRegistry::definitions)
+}
+
+/// Definitions of every built-in tool, whichever builds run it
+pub fn builtin_definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "web_search".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Search the web for current information. Returns search results with titles, URLs, and snippets.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query"
+                    }
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: "reddit_search".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Search Reddit for posts and discussions. Returns post titles, content, scores, and URLs.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query for Reddit posts"
+                    },
+                    "subreddit": {
+                        "type": "string",
+                        "description": "Optional subreddit to search in (without r/ prefix)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of results (default: 10)"
+                    }
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: "image_search".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Search for freely licensed images (Openverse, Wikimedia Commons). Returns direct image URLs with titles, dimensions, licenses and source pages. Use this to find images for PDFs or research.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query for images"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of images to return (default: 5)"
+                    }
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: "get_current_time".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Get the current date and time".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "calculate".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Perform a mathematical calculation. Supports basic arithmetic, powers, and common functions.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "Mathematical expression to evaluate (e.g., '2+2', 'sqrt(16)', 'sin(3.14)')"
+                    }
+                },
+                "required": ["expression"]
+            }),
+        },
+        ToolDefinition {
+            name: "fetch_url".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Fetch and extract text content from a URL".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The URL to fetch content from"
+                    },
+                    "render": {
+                        "type": "boolean",
+                        "description": "Run the page's JavaScript in a headless browser first (default: only when the static HTML is an empty app shell)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "read_feed".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Read an RSS or Atom feed: the latest items with title, link, date and a short summary".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL of the feed"
+                    },
+                    "count": {
+                        "type": "integer",
+                        "description": "Number of items (default: 10, max: 100)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "youtube_transcript".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Get the transcript (captions) of a YouTube video, to summarize or quote it without watching".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "video": {
+                        "type": "string",
+                        "description": "YouTube video URL or 11-character video ID"
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Caption language code, e.g. en, de (default: the video's own captions)"
+                    },
+                    "timestamps": {
+                        "type": "boolean",
+                        "description": "Include [mm:ss] timestamps per caption line (default: false)"
+                    }
+                },
+                "required": ["video"]
+            }),
+        },
+        ToolDefinition {
+            name: "save_note".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Save a note to browser local storage for later retrieval".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "title": {
+                        "type": "string",
+                        "description": "Note title"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "Note content"
+                    }
+                },
+                "required": ["title", "content"]
+            }),
+        },
+        ToolDefinition {
+            name: "read_notes".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Read all saved notes from browser local storage".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "create_pdf".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Create a PDF document with text content and optional images. Returns a downloadable file ID. Images (PNG or JPEG) can be URLs or base64 data URIs, given in `images` (placed after the content) or as Markdown images in the content (placed where they appear).".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "title": {
+                        "type": "string",
+                        "description": "PDF document title"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "PDF content (markdown format supported)"
+                    },
+                    "filename": {
+                        "type": "string",
+                        "description": "Optional filename for the PDF (without .pdf extension)"
+                    },
+                    "images": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "url": {"type": "string", "description": "PNG or JPEG image URL, or base64 data URI"},
+                                "caption": {"type": "string", "description": "Optional image caption"},
+                                "width": {"type": "number", "description": "Image width in mm (default: 170)"},
+                                "height": {"type": "number", "description": "Image height in mm (auto if not set)"}
+                            }
+                        },
+                        "description": "Array of images to include in the PDF"
+                    }
+                },
+                "required": ["title", "content"]
+            }),
+        },
+        ToolDefinition {
+            name: "download_file".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Trigger download of a previously created file (PDF or Audio). Returns download status.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "file_id": {
+                        "type": "string",
+                        "description": "The file ID returned from create_pdf or text_to_speech"
+                    }
+                },
+                "required": ["file_id"]
+            }),
+        },
+        ToolDefinition {
+            name: "send_email".to_string(),
+            tier: ToolTier::Mutating,
+            description: "Send an email through the proxy's SMTP server, e.g. to deliver a report or briefing. Can attach files created with create_pdf or text_to_speech.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "to": {
+                        "type": "string",
+                        "description": "Recipient address; separate several with commas"
+                    },
+                    "cc": {
+                        "type": "string",
+                        "description": "Optional CC addresses, comma-separated"
+                    },
+                    "subject": {
+                        "type": "string",
+                        "description": "Subject line"
+                    },
+                    "body": {
+                        "type": "string",
+                        "description": "The message, in Markdown; sent as is and as HTML rendered from it"
+                    },
+                    "html": {
+                        "type": "string",
+                        "description": "Optional HTML version of the message, instead of the one rendered from body; sanitized before sending"
+                    },
+                    "attachments": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "File IDs from create_pdf or text_to_speech to attach"
+                    }
+                },
+                "required": ["to", "subject", "body"]
+            }),
+        },
+        ToolDefinition {
+            name: "publish_post".to_string(),
+            tier: ToolTier::Mutating,
+            description: "Publish a post on the proxy's Mastodon or Bluesky account, e.g. a summary or a daily digest. Long text becomes a thread.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "network": {
+                        "type": "string",
+                        "enum": ["mastodon", "bluesky"],
+                        "description": "Where to post"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "The post; split into a thread past 500 (Mastodon) or 300 (Bluesky) characters"
+                    },
+                    "visibility": {
+                        "type": "string",
+                        "enum": ["public", "unlisted", "private"],
+                        "description": "Mastodon only: who sees the post (the proxy's default when omitted)"
+                    },
+                    "content_warning": {
+                        "type": "string",
+                        "description": "Mastodon only: a warning shown before the text"
+                    }
+                },
+                "required": ["network", "text"]
+            }),
+        },
+        ToolDefinition {
+            name: "send_notification".to_string(),
+            tier: ToolTier::Mutating,
+            description: "Send a notification to channels configured on the proxy (Telegram chats, Slack or Discord channels, webhooks), e.g. scan results or a finished task. The proxy holds the credentials; channels are referred to by id.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "channels": {
+                        "type": "string",
+                        "description": "Channel ids from the proxy config, comma-separated, or * for all of them"
+                    },
+                    "title": {
+                        "type": "string",
+                        "description": "Optional headline"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "The notification text"
+                    },
+                    "level": {
+                        "type": "string",
+                        "enum": ["info", "success", "warning", "critical"],
+                        "description": "How urgent it is (default: info)"
+                    }
+                },
+                "required": ["channels", "message"]
+            }),
+        },
+        ToolDefinition {
+            name: "list_files".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "List all previously created files (PDFs, audio files) that can be downloaded. Use this to see available files and their IDs.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        ToolDefinition {
+            name: "get_conversation".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Get the current conversation history as text. Use this when the user asks to create a PDF or summary of the current discussion - you can use the conversation content directly instead of doing new research.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "format": {
+                        "type": "string",
+                        "description": "Output format: 'text' (plain text), 'markdown' (formatted), or 'summary' (brief summary)"
+                    }
+                },
+                "required": []
+            }),
+        },
+        ToolDefinition {
+            name: "export_conversation".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Export the current conversation as a styled PDF (role labels, code blocks, an appendix of tool calls) in one step. Returns a downloadable file ID. Use this, not get_conversation and create_pdf, when the user asks for the chat as a PDF.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "template": {
+                        "type": "string",
+                        "enum": ["default", "compact", "monochrome"],
+                        "description": "Page style: 'default' (colored), 'compact' (smaller type, for long chats) or 'monochrome' (for printing)"
+                    },
+                    "session": {
+                        "type": "string",
+                        "description": "Conversation ID (default: the current one)"
+                    }
+                },
+                "required": []
+            }),
+        },
+        // Self-evolving tools
+        ToolDefinition {
+            name: "create_tool".to_string(),
+            tier: ToolTier::Mutating,
+            description: "Create a new custom tool with JavaScript code. The tool will be saved and can be used immediately. Use this to extend your own capabilities!".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Tool name (lowercase, underscores allowed)"
+                    },
+                    "description": {
+                        "type": "string",
+                        "description": "What this tool does"
+                    },
+                    "parameters_schema": {
+                        "type": "object",
+                        "description": "JSON schema for tool parameters"
+                    },
+                    "code": {
+                        "type": "string",
+                        "description": "JavaScript code. Use 'args' for parameters. Return a string result. Example: 'return args.query.toUpperCase();'"
+                    }
+                },
+                "required": ["name", "description", "parameters_schema", "code"]
+            }),
+        },
+        ToolDefinition {
+            name: "list_custom_tools".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "List all custom tools created by the AI".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "research".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Deep research on a topic. Searches several sub-queries, merges duplicate sources, reads and summarizes the pages, drops sources with little on the topic, and returns a report of numbered sources with quotes and a References section to cite as [n] (and append to create_pdf content).".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "topic": {
+                        "type": "string",
+                        "description": "The topic to research"
+                    },
+                    "queries": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Sub-questions to search besides the topic; planned from the topic when omitted"
+                    },
+                    "depth": {
+                        "type": "string",
+                        "enum": ["quick", "normal", "deep"],
+                        "description": "Research depth: how many queries and pages (default: normal); deep runs three rounds, each searching follow-ups to what the last found"
+                    },
+                    "rounds": {
+                        "type": "integer",
+                        "description": "Search rounds, 1 to 5, overriding the depth's"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["markdown", "json"],
+                        "description": "markdown (default) or the structured report as JSON"
+                    },
+                    "summarize": {
+                        "type": "string",
+                        "enum": ["provider", "extractive"],
+                        "description": "Summarize each page with the model (default) or by picking its best sentences, which sends nothing to the provider"
+                    },
+                    "min_relevance": {
+                        "type": "number",
+                        "description": "Drop sources covering less of the topic than this, from 0 to 1 (default: 0.25)"
+                    }
+                },
+                "required": ["topic"]
+            }),
+        },
+        ToolDefinition {
+            name: "crawl_site".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Crawl a website from one page, following same-site links breadth first within robots.txt, and return a map of the pages (title, status, depth, links) with their text. For site audits and answering questions from a site's documentation.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The page to start from"
+                    },
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Links to follow away from the start page, 0 to 5 (default: 2)"
+                    },
+                    "max_pages": {
+                        "type": "integer",
+                        "description": "Pages to fetch at most, 1 to 100 (default: 20)"
+                    },
+                    "max_chars": {
+                        "type": "integer",
+                        "description": "Text kept per page (default: 1500)"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["markdown", "json"],
+                        "description": "markdown (default) or the site map as JSON"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "ingest_document".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Add a document to the assistant's document library for questions with ask_document: pasted text (with a name), a URL, or a file the user attached. It is split into chunks and embedded; a document of the same name is replaced.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "The document's text, when pasted"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "A page or text file to fetch"
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "The name of a file the user attached"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "What to call the document (required for text; defaults to the URL or file name)"
+                    },
+                    "collection": {
+                        "type": "string",
+                        "description": "The collection to add it to (default: \"default\")"
+                    }
+                },
+                "required": []
+            }),
+        },
+        ToolDefinition {
+            name: "ask_document".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Find the passages of ingested documents that answer a question, numbered to cite as [n]. Answer from them only.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "question": {
+                        "type": "string",
+                        "description": "The question to answer"
+                    },
+                    "document": {
+                        "type": "string",
+                        "description": "Search only this document (default: all)"
+                    },
+                    "collection": {
+                        "type": "string",
+                        "description": "Search only this collection, with its retrieval settings (default: all)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Passages to return, 1 to 20 (default: the collection's, or 5)"
+                    }
+                },
+                "required": ["question"]
+            }),
+        },
+        ToolDefinition {
+            name: "import_bookmarks".to_string(),
+            tier: ToolTier::Mutating,
+            description: "Import a bookmarks file the user exported from their browser (HTML or JSON) and attached, so their bookmarks can be searched with ask_document and collection 'bookmarks'. Only when the user asks for it; the bookmarks stay in the browser.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "Name of the attached bookmarks file"
+                    },
+                    "collection": {
+                        "type": "string",
+                        "description": "Collection to import into (default: 'bookmarks', created when missing)"
+                    }
+                },
+                "required": ["file"]
+            }),
+        },
+        ToolDefinition {
+            name: "delete_tool".to_string(),
+            tier: ToolTier::Mutating,
+            description: "Delete a custom tool by name".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the tool to delete"
+                    }
+                },
+                "required": ["name"]
+            }),
+        },
+        // Security & Vulnerability Scanners
+        ToolDefinition {
+            name: "scan_xss".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Scan a URL or HTML content for XSS (Cross-Site Scripting) vulnerabilities. Tests for common injection points and sanitization issues.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL to scan for XSS vulnerabilities"
+                    },
+                    "html": {
+                        "type": "string",
+                        "description": "HTML content to scan (alternative to URL)"
+                    },
+                    "render": {
+                        "type": "boolean",
+                        "description": "Scan the DOM after JavaScript ran, via a headless browser (default: only when the static HTML is an empty app shell)"
+                    }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "scan_sqli".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Scan a URL for SQL Injection vulnerabilities. Tests common injection patterns and reports potential risks.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL with parameters to test for SQL injection"
+                    },
+                    "param": {
+                        "type": "string",
+                        "description": "Specific parameter to test (optional, tests all if not specified)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_headers".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Check security headers of a URL. Analyzes HTTP headers for security best practices (CSP, HSTS, X-Frame-Options, etc.) and parses the CSP for weak directives.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL to check security headers"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_ssl".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Check SSL/TLS configuration of a domain via a server-side handshake: certificate chain, expiry, hostname, trust, negotiated cipher and supported protocol versions.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "domain": {
+                        "type": "string",
+                        "description": "Domain to check SSL/TLS configuration"
+                    },
+                    "port": {
+                        "type": "integer",
+                        "description": "TLS port (default: 443)"
+                    }
+                },
+                "required": ["domain"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_deps".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Scan package dependencies for known vulnerabilities. Checks against CVE database for outdated or vulnerable packages.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "package": {
+                        "type": "string",
+                        "description": "Package name to check (e.g., 'lodash', 'express')"
+                    },
+                    "version": {
+                        "type": "string",
+                        "description": "Package version (optional)"
+                    },
+                    "ecosystem": {
+                        "type": "string",
+                        "description": "Package ecosystem: npm, pip, cargo, maven (default: npm)"
+                    }
+                },
+                "required": ["package"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_secrets".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Scan code or text for exposed secrets (API keys, tokens, passwords). Detects patterns for AWS keys, GitHub tokens, JWTs, etc.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "code": {
+                        "type": "string",
+                        "description": "Code or text to scan for secrets"
+                    }
+                },
+                "required": ["code"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_cors".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Check CORS (Cross-Origin Resource Sharing) configuration of a URL. Tests for misconfigurations that could allow unauthorized access.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL to check CORS configuration"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_clickjacking".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Check whether a URL can be framed by other sites (X-Frame-Options / CSP frame-ancestors) and produce a proof-of-concept iframe test page.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL to test"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_subdomains".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Enumerate subdomains of a domain from certificate transparency logs (crt.sh), optionally checking which ones respond.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "domain": {
+                        "type": "string",
+                        "description": "Domain to enumerate, e.g. example.com"
+                    },
+                    "probe": {
+                        "type": "boolean",
+                        "description": "Send a HEAD request to each subdomain to check liveness (default: false)"
+                    },
+                    "max_probe": {
+                        "type": "integer",
+                        "description": "Maximum subdomains to probe (default: 20, max: 50)"
+                    }
+                },
+                "required": ["domain"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_discovery".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Reconnaissance of a site's robots.txt, sitemap.xml and security.txt: disallowed paths worth reviewing, listed URLs, and security contact/policy details.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "Any URL on the target site"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_jslibs".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Fingerprint front-end JavaScript libraries and versions on a page (script URLs, license banners, framework hints) and check them against the OSV vulnerability database.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "Page URL to fingerprint"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_mixed_content".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Find http:// subresources (scripts, styles, frames, images, media, form actions) on an HTTPS page and classify them as active or passive mixed content.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "HTTPS page URL to check"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_manifest".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Check every dependency in a package.json, Cargo.toml or requirements.txt against the OSV vulnerability database in one batch and return a ranked vulnerability table.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "description": "Full text of the manifest file"
+                    },
+                    "filename": {
+                        "type": "string",
+                        "description": "File name, e.g. package.json, Cargo.toml or requirements.txt (detected from content if omitted)"
+                    },
+                    "include_dev": {
+                        "type": "boolean",
+                        "description": "Also check dev dependencies (default: true)"
+                    }
+                },
+                "required": ["content"]
+            }),
+        },
+        ToolDefinition {
+            name: "scan_full".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Run headers, cookies, TLS, CORS, XSS, mixed content and secrets checks against one URL and produce a scored pentest-style report. Pass the report to create_pdf to export it.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "Target URL"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["markdown", "json"],
+                        "description": "Report format (default: markdown)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        // Audio & Media Tools
+        ToolDefinition {
+            name: "text_to_speech".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Convert text to speech audio file and download it. Creates an MP3 (or Ogg/WAV) file through the proxy's TTS backend (OpenAI, ElevenLabs or Piper); long texts are fine. The language follows the text.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "The text to convert to speech"
+                    },
+                    "lang": {
+                        "type": "string",
+                        "description": "Language code of the text, kept with the saved file: tr (Turkish), en (English), de (German), etc. Default: en"
+                    },
+                    "voice": {
+                        "type": "string",
+                        "description": "Voice name for the backend, e.g. alloy, nova, onyx for OpenAI (default: the proxy's)"
+                    },
+                    "format": {
+                        "type": "string",
+                        "description": "mp3 (default), ogg or wav"
+                    },
+                    "filename": {
+                        "type": "string",
+                        "description": "Filename for the audio file (without .mp3 extension)"
+                    }
+                },
+                "required": ["text"]
+            }),
+        },
+        ToolDefinition {
+            name: "transcribe_audio".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Transcribe speech in an audio or video file (mp3, wav, ogg, webm, m4a) to text with Whisper, via the proxy. Optionally with timestamps per segment.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "URL of the audio file"
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "ISO-639-1 language of the speech, e.g. en, tr (default: detected)"
+                    },
+                    "timestamps": {
+                        "type": "boolean",
+                        "description": "Include [mm:ss - mm:ss] timestamps per segment (default: false)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        },
+        ToolDefinition {
+            name: "speak".to_string(),
+            tier: ToolTier::ReadOnly,
+            description: "Speak text aloud using browser's built-in speech synthesis. Does NOT create a file, just speaks the text. Use text_to_speech if you need a downloadable audio file.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "The text to speak aloud"
+                    },
+                    "lang": {
+                        "type": "string",
+                        "description": "Language code (e.g., 'tr-TR', 'en-US'). Default: tr-TR"
+                    },
+                    "rate": {
+                        "type": "number",
+                        "description": "Speech rate (0.1 to 10, default: 1)"
+                    }
+                },
+                "required": ["text"]
+            }),
+        },
+        // Page tools, registered only while `page_tools` is on
+        ToolDefinition {
+            name: "read_page".to_string(),
+            tier: ToolTier::Page,
+            description: "Read the text of the page the user is viewing (the app you are embedded in), or of one part of it. Use this when the user asks about 'this page'.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "selector": {
+                        "type": "string",
+                        "description": "CSS selector of the part to read (default: the whole page)"
+                    },
+                    "max_chars": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Most characters to return (default: 8000)"
+                    }
+                },
+                "required": []
+            }),
+        },
+        ToolDefinition {
+            name: "query_selector_text".to_string(),
+            tier: ToolTier::Page,
+            description: "List the elements of the user's page that match a CSS selector, with their text, e.g. to find a button or a table row.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "selector": {
+                        "type": "string",
+                        "description": "CSS selector, e.g. 'button', 'nav a' or '#orders tr'"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 100,
+                        "description": "Most elements to list (default: 20)"
+                    }
+                },
+                "required": ["selector"]
+            }),
+        },
+        ToolDefinition {
+            name: "click_element".to_string(),
+            tier: ToolTier::Page,
+            description: "Click an element of the user's page, to guide them through the app. Find it with query_selector_text first.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "selector": {
+                        "type": "string",
+                        "description": "CSS selector of the element"
+                    },
+                    "index": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Which of the matching elements, from 0 (default: 0)"
+                    }
+                },
+                "required": ["selector"]
+            }),
+        },
+        ToolDefinition {
+            name: "fill_form".to_string(),
+            tier: ToolTier::Page,
+            description: "Fill in a form on the user's page with values from the conversation. Without \"dry_run\": false it only previews what each field would be set to; show the user the preview, then call again with \"dry_run\": false to write the values. Password, file and hidden fields are never filled.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "fields": {
+                        "type": "object",
+                        "description": "CSS selector of each field, mapped to its value: text, a number, true/false for checkboxes, or an option's value or text for selects"
+                    },
+                    "form": {
+                        "type": "string",
+                        "description": "CSS selector of the form the fields' selectors are within (default: the whole page)"
+                    },
+                    "dry_run": {
+                        "type": "boolean",
+                        "description": "Only preview the changes (default: true)"
+                    }
+                },
+                "required": ["fields"]
+            }),
+        },
+    ]
+}
+
+/// Get tools in OpenAI function format
+pub fn get_tools_openai_format() -> Vec<serde_json::Value> {
+    get_tool_definitions()
+        .into_iter()
+        .map(|t| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": t.name,
+                    "description": t.description,
+                    "parameters": t.parameters
+                }
+            })
+        })
+        .collect()
+}
+
+/// Safety tier of a tool (unknown and custom tools default to read-only)
+pub fn tool_tier(name: &str) -> ToolTier {
+    registry::get(name)
+        .map(|t| t.definition().tier)
+        .unwrap_or_default()
+}
+
+/// Format a search response: the proxy's normalized `results`, or a raw
+/// DuckDuckGo Instant Answer (native tools and older proxies)
+pub fn search_summary(query: &str, ddg: &serde_json::Value) -> String {
+    let mut results: Vec<String> = Vec::new();
+    
+    if let Some(normalized) = ddg["results"].as_array() {
+        for (i, result) in normalized.iter().enumerate() {
+            let title = result["title"].as_str().unwrap_or("");
+            let url = result["url"].as_str().unwrap_or("");
+            let snippet = result["snippet"].as_str().unwrap_or("");
+            results.push(format!("{}. **{}**\n{}\n{}", i + 1, title, url, snippet).trim_end().to_string());
+        }
+        if results.is_empty() {
+            return format!("No results found for: {}", query);
+        }
+        let backend = ddg["backend"].as_str().unwrap_or("web");
+        return format!("Search results for '{}' ({}):\n\n{}", query, backend, results.join("\n\n"));
+    }
+    
+    // DuckDuckGo Abstract (top result)
+    if let Some(abstract_text) = ddg["Abstract"].as_str() {
+        if !abstract_text.is_empty() {
+            let source = ddg["AbstractSource"].as_str().unwrap_or("");
+            let url = ddg["AbstractURL"].as_str().unwrap_or("");
+            results.push(format!("**{}**\n{}\n{}", source, abstract_text, url));
+        }
+    }
+    
+    // Related topics
+    if let Some(topics) = ddg["RelatedTopics"].as_array() {
+        for topic in topics.iter().take(8) {
+            if let (Some(text), Some(url)) = (
+                topic["Text"].as_str(),
+                topic["FirstURL"].as_str()
+            ) {
+                if !text.is_empty() {
+                    results.push(format!("• {}\n  {}", text, url));
+                }
+            }
+        }
+    }
+    
+    if results.is_empty() {
+        return format!("No results found for: {}", query);
+    }
+    
+    format!("Search results for '{}':\n\n{}", query, results.join("\n\n"))
+}
+
+/// Simple math expression evaluator
+pub fn evaluate_math(expr: &str) -> Result<f64, String> {
+    let expr = expr.trim();
+    
+    // Handle basic operations
+    // This is a simplified evaluator - for production use a proper math parser
+    
+    // Try to parse as a simple number first
+    if let Ok(n) = expr.parse::<f64>() {
+        return Ok(n);
+    }
+    
+    // Handle basic arithmetic
+    let expr = expr.replace(" ", "");
+    
+    // Addition
+    if let Some(pos) = expr.find('+') {
+        if pos > 0 {
+            let left = evaluate_math(&expr[..pos])?;
+            let right = evaluate_math(&expr[pos+1..])?;
+            return Ok(left + right);
+        }
+    }
+    
+    // Subtraction (not at start)
+    if let Some(pos) = expr[1..].find('-') {
+        let pos = pos + 1;
+        let left = evaluate_math(&expr[..pos])?;
+        let right = evaluate_math(&expr[pos+1..])?;
+        return Ok(left - right);
+    }
+    
+    // Multiplication
+    if let Some(pos) = expr.find('*') {
+        let left = evaluate_math(&expr[..pos])?;
+        let right = evaluate_math(&expr[pos+1..])?;
+        return Ok(left * right);
+    }
+    
+    // Division
+    if let Some(pos) = expr.find('/') {
+        let left = evaluate_math(&expr[..pos])?;
+        let right = evaluate_math(&expr[pos+1..])?;
+        if right == 0.0 {
+            return Err("Division by zero".to_string());
+        }
+        return Ok(left / right);
+    }
+    
+    // Power
+    if let Some(pos) = expr.find('^') {
+        let left = evaluate_math(&expr[..pos])?;
+        let right = evaluate_math(&expr[pos+1..])?;
+        return Ok(left.powf(right));
+    }
+    
+    // Functions
+    if expr.starts_with("sqrt(") && expr.ends_with(')') {
+        let inner = &expr[5..expr.len()-1];
+        let val = evaluate_math(inner)?;
+        return Ok(val.sqrt());
+    }
+    
+    if expr.starts_with("sin(") && expr.ends_with(')') {
+        let inner = &expr[4..expr.len()-1];
+        let val = evaluate_math(inner)?;
+        return Ok(val.sin());
+    }
+    
+    if expr.starts_with("cos(") && expr.ends_with(')') {
+        let inner = &expr[4..expr.len()-1];
+        let val = evaluate_math(inner)?;
+        return Ok(val.cos());
+    }
+    
+    if expr.starts_with("tan(") && expr.ends_with(')') {
+        let inner = &expr[4..expr.len()-1];
+        let val = evaluate_math(inner)?;
+        return Ok(val.tan());
+    }
+    
+    if expr.starts_with("abs(") && expr.ends_with(')') {
+        let inner = &expr[4..expr.len()-1];
+        let val = evaluate_math(inner)?;
+        return Ok(val.abs());
+    }
+    
+    if expr.starts_with("log(") && expr.ends_with(')') {
+        let inner = &expr[4..expr.len()-1];
+        let val = evaluate_math(inner)?;
+        return Ok(val.ln());
+    }
+    
+    // Handle parentheses
+    if expr.starts_with('(') && expr.ends_with(')') {
+        return evaluate_math(&expr[1..expr.len()-1]);
+    }
+    
+    Err(format!("Cannot evaluate: {}", expr))
+}
+
+/// Whether static HTML looks like a single-page app shell that only fills
+/// in once its scripts run (little text, some scripts)
+pub fn looks_unrendered(html: &str) -> bool {
+    html.to_lowercase().contains("<script") && remove_html_tags(html).trim().chars().count() < 200
+}
+
+/// Text read off the page: each line trimmed, blank lines dropped, and cut
+/// to `max_chars`
+pub fn page_text(text: &str, max_chars: usize) -> String {
+    let text = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join("\n");
+    match text.chars().count() > max_chars {
+        true => format!("{}...(truncated)", text.chars().take(max_chars).collect::<String>()),
+        false => text,
+    }
+}
+
+/// Simple HTML tag removal
+pub fn remove_html_tags(html: &str) -> String {
+    let mut result = String::new();
+    let mut in_tag = false;
+    
+    for c in html.chars() {
+        if c == '<' {
+            in_tag = true;
+        } else if c == '>' {
+            in_tag = false;
+            result.push(' ');
+        } else if !in_tag {
+            result.push(c);
+        }
+    }
+    
+    // Clean up whitespace
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A note saved by save_note
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Note {
+    title: String,
+    content: String,
+    created_at: String,
+}
+
+/// localStorage key holding notes
+const NOTES_KEY: &str = "clawasm_notes";
+
+/// Append a note to storage
+pub fn save_note(title: &str, content: &str) -> Result<String> {
+    // Get existing notes
+    let notes_json = vault::open(&storage::get(NOTES_KEY)?.unwrap_or_default())?;
+    let mut notes: Vec<Note> = if notes_json.is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(&notes_json).unwrap_or_default()
+    };
+    
+    // Add new note
+    notes.push(Note {
+        title: title.to_string(),
+        content: content.to_string(),
+        created_at: chrono::Local::now().to_rfc3339(),
+    });
+    
+    // Save
+    let notes_json = serde_json::to_string(&notes)
+        .map_err(|e| Error::new(format!("Serialization error: {}", e)))?;
+    storage::set(NOTES_KEY, &vault::seal(&notes_json)?)?;
+    
+    Ok(format!("Note '{}' saved successfully", title))
+}
+
+/// All saved notes, formatted for the model
+pub fn read_notes() -> Result<String> {
+    let notes_json = vault::open(&storage::get(NOTES_KEY)?.unwrap_or_default())?;
+    
+    if notes_json.is_empty() {
+        return Ok("No notes found".to_string());
+    }
+    
+    let notes: Vec<Note> = serde_json::from_str(&notes_json)
+        .map_err(|e| Error::new(format!("Parse error: {}", e)))?;
+    
+    if notes.is_empty() {
+        return Ok("No notes found".to_string());
+    }
+    
+    let result: Vec<String> = notes.iter().map(|n| {
+        format!("Title: {}\nContent: {}\nCreated: {}", n.title, n.content, n.created_at)
+    }).collect();
+    
+    Ok(result.join("\n\n---\n\n"))
+}
+
+/// Security headers report for a response's headers
+pub fn headers_report(url: &str, response_headers: &HeaderMap) -> String {
+    let mut findings: Vec<String> = Vec::new();
+    let mut score = 0;
+    
+    // Security headers to check
+    let security_headers = [
+        ("content-security-policy", "Content-Security-Policy (CSP)", 20),
+        ("strict-transport-security", "Strict-Transport-Security (HSTS)", 15),
+        ("x-frame-options", "X-Frame-Options", 10),
+        ("x-content-type-options", "X-Content-Type-Options", 10),
+        ("x-xss-protection", "X-XSS-Protection", 10),
+        ("referrer-policy", "Referrer-Policy", 5),
+        ("permissions-policy", "Permissions-Policy", 10),
+        ("cross-origin-opener-policy", "Cross-Origin-Opener-Policy", 5),
+        ("cross-origin-resource-policy", "Cross-Origin-Resource-Policy", 5),
+    ];
+    
+    for (header_name, display_name, points) in &security_headers {
+        if response_headers.contains_key(*header_name) {
+            findings.push(format!("✅ {}: Present", display_name));
+            score += points;
+        } else {
+            findings.push(format!("❌ {}: Missing", display_name));
+        }
+    }
+    
+    // Check for insecure headers
+    if response_headers.contains_key("server") {
+        findings.push("⚠️ Server header exposed - Consider removing or obscuring".to_string());
+    }
+    if response_headers.contains_key("x-powered-by") {
+        findings.push("⚠️ X-Powered-By header exposed - Remove this header".to_string());
+    }
+    
+    // A present-but-weak CSP is worth little, so its points depend on the analysis
+    let csp_section = match response_headers.get("content-security-policy").and_then(|v| v.first()) {
+        Some(policy) => {
+            let issues = scan_report::analyze_csp(policy);
+            let serious = issues.iter().filter(|f| f.severity >= scan_report::Severity::Medium).count();
+            score -= (serious as i32 * 5).min(15);
+            let lines: Vec<String> = issues.iter()
+                .map(|f| format!("{} {}: {} Fix: {}", f.severity.label(), f.title, f.detail, f.recommendation))
+                .collect();
+            format!("\n\nCSP Analysis:\nPolicy: {}\n{}", policy,
+                if lines.is_empty() { "✅ No weak directives found".to_string() } else { lines.join("\n") })
+        }
+        None => String::new(),
+    };
+    
+    let grade = if score >= 80 { "A" } else if score >= 60 { "B" } else if score >= 40 { "C" } else if score >= 20 { "D" } else { "F" };
+    
+    format!("🔒 Security Headers Scan Results\n\nURL: {}\n\nSecurity Score: {}/100 (Grade: {})\n\nHeaders Analysis:\n{}{}\n\nRecommendations:\n- Implement CSP to prevent XSS\n- Enable HSTS for HTTPS enforcement\n- Set X-Frame-Options to prevent clickjacking\n- Remove server version disclosure", 
+        url, score, grade, findings.join("\n"), csp_section)
+}
+
+/// Secret scan report for a piece of code
+pub fn secrets_report(code: &str) -> String {
+    let findings = secrets::scan(code);
+    let high = findings.iter().filter(|f| f.confidence == Confidence::High).count();
+    
+    let lines: Vec<String> = findings.iter()
+        .map(|f| {
+            let icon = match f.confidence {
+                Confidence::High => "🔴",
+                Confidence::Medium => "🟠",
+                Confidence::Low => "🟡",
+            };
+            format!(
+                "{} Line {}, col {}: {} ({:?} confidence, entropy {:.1}) → {}",
+                icon, f.line, f.column, f.label, f.confidence, f.entropy, f.preview
+            )
+        })
+        .collect();
+    
+    let result = if findings.is_empty() {
+        "✅ Secret Scan Results\n\nNo obvious secrets detected in the provided code.\n\nNote: This is a pattern-based scan. Always review code manually and use tools like git-secrets, truffleHog, or gitleaks for comprehensive scanning.".to_string()
+    } else {
+        format!("🔴 Secret Scan Results\n\n⚠️ {} POTENTIAL SECRETS DETECTED ({} high confidence)\n\n{}\n\n⚠️ IMMEDIATE ACTIONS:\n1. Rotate any exposed credentials\n2. Remove secrets from code\n3. Use environment variables or secret managers\n4. Add secrets to .gitignore\n5. Review git history for accidental commits", findings.len(), high, lines.join("\n"))
+    };
+    
+    result
+}
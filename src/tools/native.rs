@@ -0,0 +1,125 @@
+//! Native tool execution
+//!
+//! Outside the browser there is no same-origin policy, so tools call the
+//! target APIs directly through `platform::fetch` instead of the local
+//! proxy. Tools that need a DOM or the proxy's extra endpoints are only
+//! available in the browser build.
+
+use crate::agent::{ToolFuture, ToolRunner};
+use crate::audit::{self, AuditKind};
+use crate::error::{Error, Result};
+use crate::platform::{self, HttpRequest};
+use crate::scan_report::HeaderMap;
+
+use super::{builtin_definitions, evaluate_math, headers_report, read_notes, registry, remove_html_tags, save_note, schema, search_summary, secrets_report};
+use super::{ToolCategory, ToolEnv, ToolRegistry};
+
+/// Runs tool calls for the native agent loop
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NativeTools;
+
+impl ToolRunner for NativeTools {
+    fn run<'a>(&'a self, name: &'a str, args: &'a serde_json::Value) -> ToolFuture<'a> {
+        Box::pin(execute_tool(name, args))
+    }
+}
+
+/// Execute a tool by name, recording the call in the audit log
+pub async fn execute_tool(name: &str, args: &serde_json::Value) -> Result<String> {
+    let result = match schema::check_call(name, args) {
+        Ok(()) => match registry::get(name) {
+            Some(tool) => tool.execute(args, &ToolEnv::default()).await,
+            None if builtin_definitions().iter().any(|t| t.name == name) => {
+                Err(Error::new(format!("Tool '{}' is only available in the browser build", name)))
+            }
+            None => Err(Error::new(format!("Unknown tool: {}", name))),
+        },
+        Err(e) => Err(e),
+    };
+    match &result {
+        Ok(_) => audit::record(AuditKind::ToolCall, name, "ok", &args.to_string()),
+        Err(e) => audit::record(AuditKind::ToolCall, name, "error", &format!("{} -> {}", args, e)),
+    }
+    result
+}
+
+/// Register the tools this build can run, by category
+pub fn register_builtins(registry: &mut ToolRegistry) {
+    registry.register_builtins(ToolCategory::Search, &[
+        ("web_search", |args, _| Box::pin(web_search(args))),
+        ("fetch_url", |args, _| Box::pin(fetch_url(args))),
+    ]);
+    registry.register_builtins(ToolCategory::Documents, &[
+        ("save_note", |args, _| Box::pin(async move { save_note(required(args, "title")?, required(args, "content")?) })),
+        ("read_notes", |_, _| Box::pin(async { read_notes() })),
+    ]);
+    registry.register_builtins(ToolCategory::Security, &[
+        ("scan_headers", |args, _| Box::pin(scan_headers(args))),
+        ("scan_secrets", |args, _| Box::pin(async move { Ok(secrets_report(required(args, "code")?)) })),
+    ]);
+    registry.register_builtins(ToolCategory::Other, &[
+        ("get_current_time", |_, _| Box::pin(async {
+            Ok(format!("Current date and time: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S %Z")))
+        })),
+        ("calculate", |args, _| Box::pin(async move {
+            let expression = required(args, "expression")?;
+            Ok(format!("Result: {}", evaluate_math(expression)?))
+        })),
+    ]);
+}
+
+fn required<'a>(args: &'a serde_json::Value, key: &str) -> Result<&'a str> {
+    args[key].as_str().ok_or_else(|| Error::new(format!("Missing '{}' parameter", key)))
+}
+
+async fn web_search(args: &serde_json::Value) -> Result<String> {
+    let query = required(args, "query")?;
+    let url = format!(
+        "https://api.duckduckgo.com/?q={}&format=json&no_html=1&skip_disambig=1",
+        urlencoding::encode(query)
+    );
+    let response = platform::fetch(HttpRequest::get(&url)).await?;
+    if !response.ok() {
+        return Err(Error::new(format!("Search failed: {}", response.status)));
+    }
+    Ok(search_summary(query, &response.json()?))
+}
+
+async fn fetch_url(args: &serde_json::Value) -> Result<String> {
+    let url = required(args, "url")?;
+    let response = platform::fetch(HttpRequest::get(url)).await?;
+    if !response.ok() {
+        return Err(Error::new(format!("Fetch failed: {}", response.status)));
+    }
+
+    // Limit to first 3000 characters (UTF-8 safe)
+    let text = remove_html_tags(&response.body);
+    if text.chars().count() > 3000 {
+        Ok(format!("{}...(truncated)", text.chars().take(3000).collect::<String>()))
+    } else {
+        Ok(text)
+    }
+}
+
+async fn scan_headers(args: &serde_json::Value) -> Result<String> {
+    let url = required(args, "url")?;
+    let request = HttpRequest { method: "HEAD".to_string(), ..HttpRequest::get(url) };
+    let response = platform::fetch(request).await?;
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in response.headers {
+        headers.entry(name.to_ascii_lowercase()).or_default().push(value);
+    }
+    Ok(headers_report(url, &headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_holds_the_native_tools() {
+        let names = registry::with(|r| r.names().iter().map(|n| n.to_string()).collect::<Vec<_>>());
+        assert_eq!(names, ["web_search", "fetch_url", "save_note", "read_notes", "scan_headers", "scan_secrets", "get_current_time", "calculate"]);
+    }
+}
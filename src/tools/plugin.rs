@@ -0,0 +1,218 @@
+//! WebAssembly plugins in the browser: fetching, instantiating and calling
+//! them (see `crate::plugins` for the interface and the checks)
+
+use std::rc::Rc;
+
+use js_sys::{Array, Function, Object, Reflect, Uint8Array, WebAssembly};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+
+use crate::agent::ToolFuture;
+use crate::error::{Error, Result};
+use crate::logger;
+use crate::plugins::{self, InstalledPlugin, MAX_MEMORY_BYTES};
+
+use super::{registry, Tool, ToolCategory, ToolDefinition, ToolEnv};
+
+/// An instantiated plugin
+struct Instance {
+    name: String,
+    memory: WebAssembly::Memory,
+    alloc: Function,
+    manifest: Function,
+    call: Function,
+}
+
+impl Instance {
+    fn new(name: &str, instance: &WebAssembly::Instance) -> Result<Self> {
+        let exports = instance.exports();
+        let export = |key: &str| Reflect::get(&exports, &JsValue::from_str(key));
+        Ok(Instance {
+            name: name.to_string(),
+            memory: export("memory")?.dyn_into()?,
+            alloc: export("alloc")?.dyn_into()?,
+            manifest: export("manifest")?.dyn_into()?,
+            call: export("call")?.dyn_into()?,
+        })
+    }
+
+    /// A view of the memory as it is now; growing it detaches older views
+    ///
+    /// `plugins::check_module` already refused memories that may grow past
+    /// the cap, so the check here only backs that up.
+    fn view(&self) -> Result<Uint8Array> {
+        let view = Uint8Array::new(&self.memory.buffer());
+        if view.length() as usize > MAX_MEMORY_BYTES {
+            return Err(Error::new(format!("Plugin '{}' grew its memory past the {} bytes allowed", self.name, MAX_MEMORY_BYTES)));
+        }
+        Ok(view)
+    }
+
+    /// Copy `bytes` into the plugin; where they went
+    fn write(&self, bytes: &[u8]) -> Result<(u32, u32)> {
+        let ptr = pointer(self.alloc.call1(&JsValue::NULL, &JsValue::from(bytes.len() as u32))?)?;
+        let view = self.view()?;
+        if ptr as usize + bytes.len() > view.length() as usize {
+            return Err(Error::new(format!("Plugin '{}' allocated outside its memory", self.name)));
+        }
+        view.subarray(ptr, ptr + bytes.len() as u32).copy_from(bytes);
+        Ok((ptr, bytes.len() as u32))
+    }
+
+    /// The buffer at `ptr`
+    fn read(&self, ptr: u32) -> Result<Vec<u8>> {
+        let view = self.view()?;
+        let len = view.length() as usize;
+        if ptr as usize + 4 > len {
+            return Err(Error::new("The plugin answered with a buffer outside its memory"));
+        }
+        let mut header = [0u8; 4];
+        view.subarray(ptr, ptr + 4).copy_to(&mut header);
+        let span = plugins::output_span(len, ptr as usize, header)?;
+        Ok(view.subarray(span.start as u32, span.end as u32).to_vec())
+    }
+
+    fn manifest(&self) -> Result<Vec<u8>> {
+        let ptr = pointer(self.manifest.call0(&JsValue::NULL)?)?;
+        self.read(ptr)
+    }
+
+    fn run(&self, tool: &str, args: &serde_json::Value) -> Result<String> {
+        let (name, name_len) = self.write(tool.as_bytes())?;
+        let (args, args_len) = self.write(args.to_string().as_bytes())?;
+        let call_args = Array::of4(&name.into(), &name_len.into(), &args.into(), &args_len.into());
+        let ptr = self.call.apply(&JsValue::NULL, &call_args)
+            .map_err(|e| Error::new(format!("Plugin '{}' crashed in '{}': {:?}", self.name, tool, e)))?;
+        plugins::parse_answer(tool, &self.read(pointer(ptr)?)?)
+    }
+}
+
+/// An i32 the plugin returned, as the address it is
+fn pointer(value: JsValue) -> Result<u32> {
+    value.as_f64()
+        .map(|n| n as i32 as u32)
+        .ok_or_else(|| Error::new("The plugin returned something other than an address"))
+}
+
+/// One of a plugin's tools
+struct PluginTool {
+    definition: ToolDefinition,
+    instance: Rc<Instance>,
+}
+
+impl Tool for PluginTool {
+    fn definition(&self) -> &ToolDefinition {
+        &self.definition
+    }
+
+    fn category(&self) -> ToolCategory {
+        ToolCategory::Plugin
+    }
+
+    fn execute<'a>(&'a self, args: &'a serde_json::Value, _env: &'a ToolEnv<'a>) -> ToolFuture<'a> {
+        Box::pin(async move { self.instance.run(&self.definition.name, args) })
+    }
+}
+
+async fn fetch_module(url: &str) -> Result<Vec<u8>> {
+    let window = web_sys::window().ok_or_else(|| Error::new("No window"))?;
+    let response: Response = JsFuture::from(window.fetch_with_str(url)).await?.dyn_into()?;
+    if !response.ok() {
+        return Err(Error::new(format!("Fetching the plugin failed: {}", response.status())));
+    }
+    let buffer = JsFuture::from(response.array_buffer()?).await?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// `module.name` of each import, or `name` and kind of each export
+fn descriptors(list: Array, with_module: bool) -> Vec<(String, String)> {
+    list.iter()
+        .map(|entry| {
+            let field = |key: &str| Reflect::get(&entry, &JsValue::from_str(key)).ok().and_then(|v| v.as_string()).unwrap_or_default();
+            match with_module {
+                true => (format!("{}.{}", field("module"), field("name")), field("kind")),
+                false => (field("name"), field("kind")),
+            }
+        })
+        .collect()
+}
+
+/// Fetch, check and instantiate the plugin at `url` and register its tools,
+/// in place of an earlier install of the same plugin
+///
+/// With `sha256`, a module of any other hash is refused.
+pub async fn install(url: &str, sha256: Option<&str>) -> Result<InstalledPlugin> {
+    plugins::check_url(url)?;
+    let bytes = fetch_module(url).await?;
+    let hash = plugins::check_module(&bytes, sha256)?;
+
+    let module: WebAssembly::Module = JsFuture::from(WebAssembly::compile(&Uint8Array::from(&bytes[..])))
+        .await
+        .map_err(|e| Error::new(format!("The plugin doesn't compile: {:?}", e)))?
+        .dyn_into()?;
+    let imports: Vec<String> = descriptors(WebAssembly::Module::imports(&module), true).into_iter().map(|(name, _)| name).collect();
+    plugins::check_interface(&imports, &descriptors(WebAssembly::Module::exports(&module), false))?;
+    let instance: WebAssembly::Instance = JsFuture::from(WebAssembly::instantiate_module(&module, &Object::new()))
+        .await
+        .map_err(|e| Error::new(format!("The plugin doesn't start: {:?}", e)))?
+        .dyn_into()?;
+
+    let instance = Instance::new(url, &instance)?;
+    let manifest = plugins::parse_manifest(&String::from_utf8_lossy(&instance.manifest()?))?;
+    let instance = Rc::new(Instance { name: manifest.name.clone(), ..instance });
+
+    let previous = plugins::load()?.into_iter().find(|p| p.name == manifest.name);
+    let taken: Vec<&str> = manifest.tools.iter()
+        .map(|t| t.name.as_str())
+        .filter(|name| registry::get(name).is_some() && !previous.as_ref().is_some_and(|p| p.tools.iter().any(|t| t == name)))
+        .collect();
+    if !taken.is_empty() {
+        return Err(Error::new(format!("Plugin '{}' brings tools that already exist: {}", manifest.name, taken.join(", "))));
+    }
+    if let Some(previous) = &previous {
+        unregister(previous);
+    }
+    for definition in &manifest.tools {
+        registry::register_external(Rc::new(PluginTool { definition: definition.clone(), instance: instance.clone() }))?;
+    }
+
+    let installed = InstalledPlugin {
+        name: manifest.name,
+        version: manifest.version,
+        url: url.to_string(),
+        sha256: hash,
+        tools: manifest.tools.into_iter().map(|t| t.name).collect(),
+    };
+    plugins::remember(installed.clone())?;
+    logger::info("plugins", &format!("Installed plugin '{}' with {}", installed.name, installed.tools.join(", ")));
+    Ok(installed)
+}
+
+fn unregister(plugin: &InstalledPlugin) {
+    for tool in &plugin.tools {
+        if registry::get(tool).is_some_and(|t| t.category() == ToolCategory::Plugin) {
+            let _ = registry::unregister_external(tool);
+        }
+    }
+}
+
+/// Remove the plugin called `name` and its tools
+pub fn uninstall(name: &str) -> Result<()> {
+    let plugin = plugins::forget(name)?.ok_or_else(|| Error::new(format!("No plugin '{}' is installed", name)))?;
+    unregister(&plugin);
+    Ok(())
+}
+
+/// Install the remembered plugins again, each only if its module is the
+/// one installed; how many were
+pub async fn restore() -> Result<usize> {
+    let mut restored = 0;
+    for plugin in plugins::load()? {
+        match install(&plugin.url, Some(&plugin.sha256)).await {
+            Ok(_) => restored += 1,
+            Err(e) => logger::warn("plugins", &format!("Plugin '{}' not loaded: {}", plugin.name, e)),
+        }
+    }
+    Ok(restored)
+}
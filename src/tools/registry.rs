@@ -0,0 +1,413 @@
+//! The tools a build can run, by name
+//!
+//! Every tool is a `Tool`: its definition, a category and a way to run it.
+//! Each build registers its built-ins here by category (`browser` and
+//! `native`), with the definitions from the shared catalog, and the page
+//! can add its own at runtime with `ClaWasm.registerJsTool()` or from
+//! WebAssembly plugins (`crate::plugins`). Whatever is registered is what
+//! the model is told about (`catalog`), what providers are offered, and
+//! what calls are validated and run against.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde_json::Value;
+
+use crate::agent::ToolFuture;
+use crate::error::{Error, Result};
+use crate::memory::MemorySystem;
+use crate::research;
+
+use super::{builtin_definitions, ToolDefinition, ToolTier};
+
+/// Longest name of a tool from outside the build
+const MAX_NAME_CHARS: usize = 64;
+
+/// Groups tools in the system prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ToolCategory {
+    Search,
+    Documents,
+    Security,
+    Custom,
+    Media,
+    Other,
+    /// From a WebAssembly plugin
+    Plugin,
+    /// Registered by the page
+    Host,
+    /// Work on the page's own DOM; only while `page_tools` is on
+    Page,
+}
+
+impl ToolCategory {
+    const ALL: [ToolCategory; 9] = [
+        ToolCategory::Search,
+        ToolCategory::Documents,
+        ToolCategory::Security,
+        ToolCategory::Custom,
+        ToolCategory::Media,
+        ToolCategory::Other,
+        ToolCategory::Plugin,
+        ToolCategory::Host,
+        ToolCategory::Page,
+    ];
+
+    /// Whether tools of the category come with the build
+    pub fn is_builtin(self) -> bool {
+        !matches!(self, ToolCategory::Plugin | ToolCategory::Host)
+    }
+
+    /// Heading of the category's tools in the system prompt
+    pub fn heading(self) -> &'static str {
+        match self {
+            ToolCategory::Search => "🔍 Arama ve Araştırma",
+            ToolCategory::Documents => "📄 Belge ve Not",
+            ToolCategory::Security => "🔒 Güvenlik ve Zafiyet Tarama",
+            ToolCategory::Custom => "🔧 Özel Araçlar",
+            ToolCategory::Media => "🎧 Ses ve Medya",
+            ToolCategory::Other => "⚡ Diğer",
+            ToolCategory::Plugin => "🧱 Eklentiler",
+            ToolCategory::Host => "🧩 Sayfa Araçları",
+            ToolCategory::Page => "🖱️ Sayfa Etkileşimi",
+        }
+    }
+}
+
+/// What tools run by the agent can use besides the proxy
+pub struct ToolContext<'a> {
+    /// Summarizes research sources with the agent's provider
+    pub summarizer: research::Summarizer<'a>,
+    /// Holds ingested documents
+    pub memory: &'a Rc<RefCell<MemorySystem>>,
+}
+
+/// What a running tool gets besides its arguments
+#[derive(Default)]
+pub struct ToolEnv<'a> {
+    /// Base URL of the local proxy (unused by the native tools)
+    pub proxy: &'a str,
+    /// The agent's summarizer and memory; absent for direct calls
+    pub context: Option<&'a ToolContext<'a>>,
+}
+
+/// A tool the model can call
+pub trait Tool {
+    fn definition(&self) -> &ToolDefinition;
+    fn category(&self) -> ToolCategory;
+    /// Run with arguments already checked against the definition
+    fn execute<'a>(&'a self, args: &'a Value, env: &'a ToolEnv<'a>) -> ToolFuture<'a>;
+}
+
+/// How a built-in tool runs
+pub type BuiltinFn = for<'a> fn(&'a Value, &'a ToolEnv<'a>) -> ToolFuture<'a>;
+
+/// A built-in tool: its catalog definition and the function that runs it
+struct Builtin {
+    definition: ToolDefinition,
+    category: ToolCategory,
+    run: BuiltinFn,
+}
+
+impl Tool for Builtin {
+    fn definition(&self) -> &ToolDefinition {
+        &self.definition
+    }
+
+    fn category(&self) -> ToolCategory {
+        self.category
+    }
+
+    fn execute<'a>(&'a self, args: &'a Value, env: &'a ToolEnv<'a>) -> ToolFuture<'a> {
+        (self.run)(args, env)
+    }
+}
+
+/// Tools by name, in the order they were registered
+#[derive(Default, Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Rc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// The built-in tools of this build
+    pub fn builtin() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = ToolRegistry::default();
+        #[cfg(feature = "web")]
+        super::browser::register_builtins(&mut registry);
+        #[cfg(all(feature = "native", not(feature = "web")))]
+        super::native::register_builtins(&mut registry);
+        registry
+    }
+
+    /// Register built-in tools of `category`, each with its definition
+    /// from the catalog
+    ///
+    /// # Panics
+    /// When a name is not in the catalog
+    pub fn register_builtins(&mut self, category: ToolCategory, tools: &[(&str, BuiltinFn)]) {
+        let catalog = builtin_definitions();
+        for (name, run) in tools {
+            let definition = catalog.iter().find(|d| d.name == *name).cloned()
+                .unwrap_or_else(|| panic!("Built-in tool '{}' is not in the catalog", name));
+            self.register(Rc::new(Builtin { definition, category, run: *run }));
+        }
+    }
+
+    /// Add `tool`, in place of any tool of the same name
+    pub fn register(&mut self, tool: Rc<dyn Tool>) {
+        match self.tools.iter_mut().find(|t| t.definition().name == tool.definition().name) {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// Remove the tool called `name`; whether there was one
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let before = self.tools.len();
+        self.tools.retain(|t| t.definition().name != name);
+        self.tools.len() < before
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Tool>> {
+        self.tools.iter().find(|t| t.definition().name == name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.definition().name.as_str()).collect()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|t| t.definition().clone()).collect()
+    }
+
+    /// The tools for the system prompt: a Markdown list under a heading per
+    /// category
+    pub fn catalog(&self) -> String {
+        let mut catalog = String::new();
+        for category in ToolCategory::ALL {
+            let tools: Vec<&Rc<dyn Tool>> = self.tools.iter().filter(|t| t.category() == category).collect();
+            if tools.is_empty() {
+                continue;
+            }
+            catalog.push_str(&format!("\n## {}\n", category.heading()));
+            for tool in tools {
+                let definition = tool.definition();
+                catalog.push_str(&format!("- **{}**: {}\n", definition.name, definition.description));
+            }
+        }
+        catalog
+    }
+}
+
+thread_local! {
+    static REGISTRY: RefCell<ToolRegistry> = RefCell::new(ToolRegistry::builtin());
+}
+
+/// Read the registry
+pub fn with<T>(f: impl FnOnce(&ToolRegistry) -> T) -> T {
+    REGISTRY.with(|r| f(&r.borrow()))
+}
+
+/// The registered tool called `name`
+pub fn get(name: &str) -> Option<Rc<dyn Tool>> {
+    with(|r| r.get(name))
+}
+
+/// Add a tool from outside the build (the page's or a plugin's), in place
+/// of an earlier one of the same name; built-in tools can't be replaced
+pub fn register_external(tool: Rc<dyn Tool>) -> Result<()> {
+    let name = tool.definition().name.clone();
+    REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        if registry.get(&name).is_some_and(|t| t.category().is_builtin()) {
+            return Err(Error::new(format!("'{}' is a built-in tool", name)));
+        }
+        registry.register(tool);
+        Ok(())
+    })
+}
+
+/// Remove a tool from outside the build
+pub fn unregister_external(name: &str) -> Result<()> {
+    REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        match registry.get(name).map(|t| t.category()) {
+            Some(category) if !category.is_builtin() => {
+                registry.unregister(name);
+                Ok(())
+            }
+            Some(_) => Err(Error::new(format!("'{}' is a built-in tool", name))),
+            None => Err(Error::new(format!("No tool '{}' is registered", name))),
+        }
+    })
+}
+
+/// Add built-in tools that are off by default (the page tools), in place
+/// of any tools of their names, or remove them again
+pub fn set_builtins(category: ToolCategory, tools: &[(&str, BuiltinFn)], on: bool) {
+    REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        match on {
+            true => registry.register_builtins(category, tools),
+            false => tools.iter().for_each(|(name, _)| {
+                registry.unregister(name);
+            }),
+        }
+    })
+}
+
+/// Whether `name` can name a tool from outside the build
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_NAME_CHARS
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A tool from outside the build, checked: a usable name, a description,
+/// and arguments that are an object (none when left out)
+pub fn check_external(mut definition: ToolDefinition) -> Result<ToolDefinition> {
+    let name = &definition.name;
+    if !valid_name(name) {
+        return Err(Error::new(format!(
+            "Invalid tool name '{}': use up to {} letters, digits, '_' or '-'", name, MAX_NAME_CHARS)));
+    }
+    if definition.description.trim().is_empty() {
+        return Err(Error::new(format!("'{}' needs a \"description\" of the tool", name)));
+    }
+    match &mut definition.parameters {
+        Value::Null => definition.parameters = serde_json::json!({"type": "object", "properties": {}}),
+        Value::Object(parameters) if parameters.get("type").is_none_or(|t| t == "object") => {
+            parameters.insert("type".to_string(), Value::from("object"));
+        }
+        _ => return Err(Error::new(format!("The arguments of '{}' must be an object schema", name))),
+    }
+    Ok(definition)
+}
+
+/// The definition of a page's tool from the JSON Schema of its arguments,
+/// whose `description` describes the tool; an optional `tier` of
+/// `"mutating"` makes every call need approval
+pub fn host_definition(name: &str, schema: &str) -> Result<ToolDefinition> {
+    let mut parameters: Value = serde_json::from_str(schema)
+        .map_err(|e| Error::new(format!("Invalid schema for '{}': {}", name, e)))?;
+    let Value::Object(object) = &mut parameters else {
+        return Err(Error::new(format!("Invalid schema for '{}': expected a JSON object", name)));
+    };
+    let description = match object.remove("description") {
+        Some(Value::String(description)) => description,
+        _ => String::new(),
+    };
+    let tier = parse_tier(name, object.remove("tier"))?;
+    check_external(ToolDefinition { name: name.to_string(), description, parameters, tier })
+}
+
+/// The definition of a page's tool from `{"description", "parameters",
+/// "tier"}`
+pub fn host_tool(name: &str, spec: &str) -> Result<ToolDefinition> {
+    let mut spec: serde_json::Map<String, Value> = serde_json::from_str(spec)
+        .map_err(|e| Error::new(format!("Invalid tool '{}': {}", name, e)))?;
+    let description = spec.remove("description").and_then(|d| d.as_str().map(str::to_string)).unwrap_or_default();
+    let tier = parse_tier(name, spec.remove("tier"))?;
+    let parameters = spec.remove("parameters").unwrap_or_default();
+    check_external(ToolDefinition { name: name.to_string(), description, parameters, tier })
+}
+
+fn parse_tier(name: &str, tier: Option<Value>) -> Result<ToolTier> {
+    match tier {
+        Some(tier) => serde_json::from_value(tier)
+            .map_err(|_| Error::new(format!("Invalid tier for '{}': use \"read_only\", \"mutating\" or \"page\"", name))),
+        None => Ok(ToolTier::ReadOnly),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo(ToolDefinition);
+
+    impl Tool for Echo {
+        fn definition(&self) -> &ToolDefinition {
+            &self.0
+        }
+
+        fn category(&self) -> ToolCategory {
+            ToolCategory::Host
+        }
+
+        fn execute<'a>(&'a self, args: &'a Value, _env: &'a ToolEnv<'a>) -> ToolFuture<'a> {
+            Box::pin(async move { Ok(args.to_string()) })
+        }
+    }
+
+    #[test]
+    fn test_builtin_registry() {
+        let registry = ToolRegistry::builtin();
+        let names = registry.names();
+        for name in ["web_search", "calculate", "fetch_url", "scan_headers"] {
+            assert!(names.contains(&name), "{} is not registered", name);
+        }
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+
+        let catalog = registry.catalog();
+        assert!(catalog.starts_with("\n## 🔍 Arama ve Araştırma\n- **web_search**: "));
+        assert!(catalog.contains("- **calculate**: Perform a mathematical calculation."));
+    }
+
+    #[test]
+    fn test_page_tools_are_off_by_default() {
+        assert!(get("read_page").is_none());
+        let run: BuiltinFn = |args, _| Box::pin(async move { Ok(args.to_string()) });
+        set_builtins(ToolCategory::Page, &[("read_page", run)], true);
+        assert_eq!(get("read_page").unwrap().definition().tier, ToolTier::Page);
+        assert!(with(|r| r.catalog()).ends_with("\n## 🖱️ Sayfa Etkileşimi\n- **read_page**: Read the text of the page the user is viewing \
+            (the app you are embedded in), or of one part of it. Use this when the user asks about 'this page'.\n"));
+        // Built-ins now, so the page can't replace them
+        assert!(unregister_external("read_page").is_err());
+        set_builtins(ToolCategory::Page, &[("read_page", run)], false);
+        assert!(get("read_page").is_none());
+    }
+
+    #[test]
+    fn test_host_tools() {
+        let definition = host_definition("lookup_order", r#"{
+            "description": "Look up an order",
+            "properties": {"id": {"type": "string"}},
+            "required": ["id"],
+            "tier": "mutating"
+        }"#).unwrap();
+        assert_eq!(definition.description, "Look up an order");
+        assert_eq!(definition.tier, ToolTier::Mutating);
+        assert_eq!(definition.parameters, serde_json::json!({"type": "object", "properties": {"id": {"type": "string"}}, "required": ["id"]}));
+
+        assert!(host_definition("bad name", r#"{"description": "x"}"#).unwrap_err().message().starts_with("Invalid tool name"));
+        assert!(host_definition("t", r#"{"type": "object"}"#).unwrap_err().message().contains("needs a \"description\""));
+        assert!(host_definition("t", r#"{"description": "x", "type": "string"}"#).is_err());
+        assert!(host_definition("t", r#"{"description": "x", "tier": "admin"}"#).is_err());
+
+        let todo = host_tool("add_todo", r#"{"description": "Add a to-do", "parameters": {"properties": {"title": {"type": "string"}}}}"#).unwrap();
+        assert_eq!((todo.tier, &todo.parameters["type"]), (ToolTier::ReadOnly, &Value::from("object")));
+        assert_eq!(host_tool("go", r#"{"description": "Navigate"}"#).unwrap().parameters, serde_json::json!({"type": "object", "properties": {}}));
+        assert_eq!(host_tool("go", r#"{"parameters": {}}"#).unwrap_err().message(), "'go' needs a \"description\" of the tool");
+        assert!(host_tool("go", r#"{"description": "x", "parameters": []}"#).unwrap_err().message().contains("must be an object schema"));
+
+        let mut registry = ToolRegistry::builtin();
+        let count = registry.names().len();
+        registry.register(Rc::new(Echo(definition.clone())));
+        registry.register(Rc::new(Echo(ToolDefinition { description: "Newer".to_string(), ..definition })));
+        assert_eq!(registry.names().len(), count + 1);
+        assert!(registry.catalog().ends_with("\n## 🧩 Sayfa Araçları\n- **lookup_order**: Newer\n"));
+        assert!(registry.unregister("lookup_order"));
+        assert!(!registry.unregister("lookup_order"));
+
+        // The shared registry keeps built-ins from being replaced
+        let web_search = get("web_search").unwrap().definition().clone();
+        assert_eq!(register_external(Rc::new(Echo(web_search))).unwrap_err().message(), "'web_search' is a built-in tool");
+        assert!(unregister_external("web_search").is_err());
+        assert!(unregister_external("nothing").is_err());
+    }
+}
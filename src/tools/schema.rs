@@ -0,0 +1,251 @@
+//! Tool arguments checked against the tools' JSON schemas
+//!
+//! Before a tool runs, its arguments are checked against the parameters of
+//! its definition, in the part of JSON Schema the definitions use: `type`,
+//! `required`, `properties`, `items`, `enum`, `minimum`/`maximum` and
+//! `additionalProperties`. A bad call then fails with every problem named
+//! and the parameters spelled out, which the model can correct in one go,
+//! instead of with the first "Missing 'query' parameter" the tool trips
+//! over. Optional arguments given as `null` count as left out.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+use super::registry;
+
+/// Arguments a schema does not list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unknown {
+    /// Ignored, unless the schema sets `additionalProperties: false`
+    Allow,
+    /// Violations
+    Reject,
+}
+
+/// One way arguments break a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Where, e.g. `limit` or `images[0].url`; empty for the arguments
+    /// as a whole
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.is_empty() {
+            true => write!(f, "arguments: {}", self.message),
+            false => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Every way `value` breaks `schema`
+pub fn validate(schema: &Value, value: &Value, unknown: Unknown) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    check(schema, value, "", unknown, &mut violations);
+    violations
+}
+
+/// Check a call of a registered tool; tools without a definition (made
+/// with `create_tool`) pass, as their runner checks them
+pub fn check_call(name: &str, arguments: &Value) -> Result<()> {
+    let Some(tool) = registry::get(name) else {
+        return Ok(());
+    };
+    let definition = tool.definition();
+    let violations = validate(&definition.parameters, arguments, Unknown::Allow);
+    match violations.is_empty() {
+        true => Ok(()),
+        false => Err(Error::new(describe(name, &violations, &definition.parameters))),
+    }
+}
+
+/// The error for a call that broke its tool's schema
+pub fn describe(tool: &str, violations: &[Violation], schema: &Value) -> String {
+    let lines: Vec<String> = violations.iter().map(|v| format!("- {}", v)).collect();
+    format!("Invalid arguments for '{}':\n{}\nParameters: {}", tool, lines.join("\n"), signature(schema))
+}
+
+/// The parameters of `schema` in one line:
+/// `limit (integer), query (string, required)`
+pub fn signature(schema: &Value) -> String {
+    let Some(properties) = schema["properties"].as_object().filter(|p| !p.is_empty()) else {
+        return "none".to_string();
+    };
+    let required = |key: &str| schema["required"].as_array().is_some_and(|r| r.iter().any(|k| k == key));
+    properties.iter()
+        .map(|(key, property)| {
+            let kind = kind(property);
+            match required(key) {
+                true => format!("{} ({}, required)", key, kind),
+                false => format!("{} ({})", key, kind),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn kind(schema: &Value) -> String {
+    if let Some(options) = schema["enum"].as_array() {
+        return options.iter().map(Value::to_string).collect::<Vec<_>>().join(" | ");
+    }
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(kind) => vec![kind.as_str()],
+        Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => vec!["any"],
+    };
+    match (types.as_slice(), &schema["items"]) {
+        (["array"], items) if !items.is_null() => format!("array of {}", kind(items)),
+        _ => types.join(" or "),
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, unknown: Unknown, violations: &mut Vec<Violation>) {
+    let mut violation = |message: String| violations.push(Violation { path: path.to_string(), message });
+
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(kind) => vec![kind.as_str()],
+        Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|kind| has_type(value, kind)) {
+        let expected: Vec<String> = types.iter().map(|kind| format!("{} {}", article(kind), kind)).collect();
+        violation(format!("must be {}, not {}", expected.join(" or "), describe_value(value)));
+        return;
+    }
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            violation(format!("must be one of {}, not {}", options.join(", "), value));
+            return;
+        }
+    }
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema["minimum"].as_f64().filter(|minimum| number < *minimum) {
+            violation(format!("must be at least {}, not {}", minimum, value));
+        }
+        if let Some(maximum) = schema["maximum"].as_f64().filter(|maximum| number > *maximum) {
+            violation(format!("must be at most {}, not {}", maximum, value));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let required: Vec<&str> = schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+            for key in &required {
+                if object.get(*key).is_none_or(Value::is_null) {
+                    violations.push(Violation { path: join(path, key), message: "is required".to_string() });
+                }
+            }
+            let properties = schema["properties"].as_object();
+            let closed = unknown == Unknown::Reject || schema["additionalProperties"] == Value::Bool(false);
+            for (key, item) in object {
+                match properties.and_then(|p| p.get(key)) {
+                    _ if item.is_null() && !required.contains(&key.as_str()) => {}
+                    Some(property) => check(property, item, &join(path, key), unknown, violations),
+                    None if closed && properties.is_some() => {
+                        violations.push(Violation { path: join(path, key), message: "is not a parameter of this tool".to_string() });
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) if schema["items"].is_object() => {
+            for (i, item) in items.iter().enumerate() {
+                check(&schema["items"], item, &format!("{}[{}]", path, i), unknown, violations);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", path, key),
+    }
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn describe_value(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_f64() => "a fractional number",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn article(kind: &str) -> &'static str {
+    match kind.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        true => "an",
+        false => "a",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string"},
+                "limit": {"type": "integer", "minimum": 1},
+                "depth": {"type": "string", "enum": ["quick", "deep"]},
+                "images": {"type": "array", "items": {"type": "object", "properties": {"url": {"type": "string"}}, "required": ["url"]}}
+            },
+            "required": ["query"]
+        });
+        assert!(validate(&schema, &json!({"query": "x", "limit": 3, "images": [{"url": "u"}]}), Unknown::Reject).is_empty());
+        // Optional nulls count as left out; unknown arguments pass unless rejected
+        assert!(validate(&schema, &json!({"query": "x", "depth": null, "extra": 1}), Unknown::Allow).is_empty());
+
+        let violations: Vec<String> = validate(&schema, &json!({"limit": "5", "depth": "medium", "images": [{"width": 3}], "extra": 1}), Unknown::Reject)
+            .iter().map(Violation::to_string).collect();
+        assert_eq!(violations, [
+            "query: is required",
+            "depth: must be one of \"quick\", \"deep\", not \"medium\"",
+            "extra: is not a parameter of this tool",
+            "images[0].url: is required",
+            "images[0].width: is not a parameter of this tool",
+            "limit: must be an integer, not a string",
+        ]);
+        assert_eq!(validate(&schema, &json!({"query": "x", "limit": 0}), Unknown::Allow)[0].to_string(), "limit: must be at least 1, not 0");
+        assert_eq!(validate(&schema, &json!("x"), Unknown::Allow)[0].to_string(), "arguments: must be an object, not a string");
+
+        assert_eq!(signature(&schema), "depth (\"quick\" | \"deep\"), images (array of object), limit (integer), query (string, required)");
+        assert_eq!(signature(&json!({"type": "object", "properties": {}})), "none");
+    }
+
+    #[test]
+    fn test_check_call() {
+        assert!(check_call("web_search", &json!({"query": "rust"})).is_ok());
+        assert!(check_call("my_own_tool", &json!({"anything": 1})).is_ok());
+        assert_eq!(check_call("calculate", &json!({"expression": 5})).unwrap_err().message(),
+            "Invalid arguments for 'calculate':\n- expression: must be a string, not an integer\n\
+            Parameters: expression (string, required)");
+    }
+}
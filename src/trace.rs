@@ -0,0 +1,308 @@
+//! Conversation tracing for claWasm
+//!
+//! When enabled, the agent loop records a span per turn, iteration,
+//! provider call and tool execution, so slow or looping conversations can
+//! be inspected afterwards. Spans stay in memory (capped) and export as
+//! plain JSON or as OTLP/JSON for an OpenTelemetry collector. Tracing is
+//! off by default and costs nothing then.
+
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::error::{Error, Result};
+use crate::platform::{self, HttpRequest};
+
+/// Maximum number of finished spans kept; the oldest are dropped first
+const MAX_SPANS: usize = 2000;
+/// Service name reported to collectors
+const SERVICE_NAME: &str = "clawasm";
+
+/// Span outcome
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "code", content = "message", rename_all = "lowercase")]
+pub enum SpanStatus {
+    Unset,
+    Ok,
+    Error(String),
+}
+
+/// A finished span
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    /// Unix time in nanoseconds
+    pub start_ns: i64,
+    pub end_ns: i64,
+    pub attributes: BTreeMap<String, serde_json::Value>,
+    pub status: SpanStatus,
+}
+
+impl Span {
+    pub fn duration_ms(&self) -> f64 {
+        (self.end_ns - self.start_ns) as f64 / 1_000_000.0
+    }
+}
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static SPANS: RefCell<VecDeque<Span>> = const { RefCell::new(VecDeque::new()) };
+    static ID_STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Turn span recording on or off
+pub fn set_enabled(enabled: bool) {
+    ENABLED.with(|e| e.set(enabled));
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+/// Finished spans, oldest first
+pub fn spans() -> Vec<Span> {
+    SPANS.with(|spans| spans.borrow().iter().cloned().collect())
+}
+
+/// Drop every recorded span
+pub fn clear() {
+    SPANS.with(|spans| spans.borrow_mut().clear());
+}
+
+fn now_ns() -> i64 {
+    let now = chrono::Utc::now();
+    now.timestamp_nanos_opt().unwrap_or_else(|| now.timestamp_millis() * 1_000_000)
+}
+
+/// Random-looking hex ID of `bytes` bytes
+///
+/// Trace IDs only need to be unique, so a clock-seeded xorshift is enough
+/// and keeps tracing usable where no secure RNG is reachable.
+fn new_id(bytes: usize) -> String {
+    ID_STATE.with(|state| {
+        let mut x = state.get();
+        if x == 0 {
+            x = (now_ns() as u64) | 1;
+        }
+        let mut id = String::with_capacity(bytes * 2);
+        while id.len() < bytes * 2 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            id.push_str(&format!("{:016x}", x));
+        }
+        state.set(x);
+        id.truncate(bytes * 2);
+        id
+    })
+}
+
+/// An open span; recorded when dropped
+///
+/// Inert when tracing was off at creation, so instrumented code can call
+/// it unconditionally.
+pub struct SpanHandle(Option<Span>);
+
+impl SpanHandle {
+    /// Start a new trace
+    pub fn root(name: &str) -> Self {
+        Self::start(name, new_id(16), None)
+    }
+
+    /// Start a span under this one
+    pub fn child(&self, name: &str) -> Self {
+        match &self.0 {
+            Some(parent) => Self::start(name, parent.trace_id.clone(), Some(parent.span_id.clone())),
+            None => SpanHandle(None),
+        }
+    }
+
+    fn start(name: &str, trace_id: String, parent_span_id: Option<String>) -> Self {
+        if !is_enabled() {
+            return SpanHandle(None);
+        }
+        SpanHandle(Some(Span {
+            trace_id,
+            span_id: new_id(8),
+            parent_span_id,
+            name: name.to_string(),
+            start_ns: now_ns(),
+            end_ns: 0,
+            attributes: BTreeMap::new(),
+            status: SpanStatus::Unset,
+        }))
+    }
+
+    pub fn attr(&mut self, key: &str, value: impl Into<serde_json::Value>) {
+        if let Some(span) = &mut self.0 {
+            span.attributes.insert(key.to_string(), value.into());
+        }
+    }
+
+    /// Set the status from a result
+    pub fn finish<T>(&mut self, result: &Result<T>) {
+        if let Some(span) = &mut self.0 {
+            span.status = match result {
+                Ok(_) => SpanStatus::Ok,
+                Err(e) => SpanStatus::Error(e.to_string()),
+            };
+        }
+    }
+}
+
+impl Drop for SpanHandle {
+    fn drop(&mut self) {
+        if let Some(mut span) = self.0.take() {
+            span.end_ns = now_ns();
+            SPANS.with(|spans| {
+                let mut spans = spans.borrow_mut();
+                spans.push_back(span);
+                while spans.len() > MAX_SPANS {
+                    spans.pop_front();
+                }
+            });
+        }
+    }
+}
+
+/// OTLP attribute value
+fn otlp_value(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Bool(b) => serde_json::json!({ "boolValue": b }),
+        serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => serde_json::json!({ "intValue": n.to_string() }),
+        serde_json::Value::Number(n) => serde_json::json!({ "doubleValue": n.as_f64() }),
+        serde_json::Value::String(s) => serde_json::json!({ "stringValue": s }),
+        other => serde_json::json!({ "stringValue": other.to_string() }),
+    }
+}
+
+/// Spans as an OTLP/JSON `ExportTraceServiceRequest`
+pub fn to_otlp(spans: &[Span]) -> serde_json::Value {
+    let spans: Vec<serde_json::Value> = spans.iter()
+        .map(|span| {
+            let (code, message) = match &span.status {
+                SpanStatus::Unset => (0, String::new()),
+                SpanStatus::Ok => (1, String::new()),
+                SpanStatus::Error(message) => (2, message.clone()),
+            };
+            serde_json::json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "parentSpanId": span.parent_span_id.clone().unwrap_or_default(),
+                "name": span.name,
+                // SPAN_KIND_INTERNAL
+                "kind": 1,
+                "startTimeUnixNano": span.start_ns.to_string(),
+                "endTimeUnixNano": span.end_ns.to_string(),
+                "attributes": span.attributes.iter()
+                    .map(|(key, value)| serde_json::json!({ "key": key, "value": otlp_value(value) }))
+                    .collect::<Vec<_>>(),
+                "status": { "code": code, "message": message },
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": SERVICE_NAME } }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": SERVICE_NAME, "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// Send every recorded span to an OTLP/HTTP collector
+///
+/// `endpoint` is the collector base URL (e.g. `http://localhost:4318`);
+/// `/v1/traces` is appended unless already present. Browsers reach it
+/// through the proxy. Returns the number of spans sent.
+pub async fn export_otlp(endpoint: &str, proxy_url: &str) -> Result<usize> {
+    let spans = spans();
+    if spans.is_empty() {
+        return Ok(0);
+    }
+    let endpoint = endpoint.trim_end_matches('/');
+    let url = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+
+    let response = platform::fetch_cross_origin(HttpRequest::post_json(&url, &to_otlp(&spans)), proxy_url).await?;
+    if !response.ok() {
+        return Err(Error::new(format!("Trace export failed ({}): {}", response.status, response.body)));
+    }
+    Ok(spans.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_record_only_when_enabled() {
+        clear();
+        set_enabled(false);
+        drop(SpanHandle::root("ignored"));
+        assert!(spans().is_empty());
+
+        set_enabled(true);
+        {
+            let mut turn = SpanHandle::root("agent.turn");
+            turn.attr("model", "gpt-4o-mini");
+            let mut tool = turn.child("tool.execute");
+            tool.attr("tool", "calculate");
+            tool.finish::<()>(&Err(Error::new("boom")));
+        }
+        set_enabled(false);
+
+        let spans = spans();
+        assert_eq!(spans.len(), 2);
+        let (tool, turn) = (&spans[0], &spans[1]);
+        assert_eq!(tool.trace_id, turn.trace_id);
+        assert_eq!(tool.parent_span_id.as_deref(), Some(turn.span_id.as_str()));
+        assert_eq!(turn.trace_id.len(), 32);
+        assert_eq!(turn.span_id.len(), 16);
+        assert_ne!(tool.span_id, turn.span_id);
+        assert_eq!(tool.status, SpanStatus::Error("boom".to_string()));
+        assert!(turn.end_ns >= turn.start_ns);
+        clear();
+    }
+
+    #[test]
+    fn test_otlp_shape() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert("iteration".to_string(), serde_json::json!(3));
+        attributes.insert("tool".to_string(), serde_json::json!("fetch_url"));
+        attributes.insert("repeated".to_string(), serde_json::json!(true));
+        let span = Span {
+            trace_id: "a".repeat(32),
+            span_id: "b".repeat(16),
+            parent_span_id: None,
+            name: "tool.execute".to_string(),
+            start_ns: 1_000,
+            end_ns: 2_000_000,
+            attributes,
+            status: SpanStatus::Ok,
+        };
+
+        let otlp = to_otlp(std::slice::from_ref(&span));
+        let exported = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(exported["startTimeUnixNano"], "1000");
+        assert_eq!(exported["status"]["code"], 1);
+        assert_eq!(exported["parentSpanId"], "");
+        let attrs = exported["attributes"].as_array().unwrap();
+        assert!(attrs.contains(&serde_json::json!({ "key": "iteration", "value": { "intValue": "3" } })));
+        assert!(attrs.contains(&serde_json::json!({ "key": "repeated", "value": { "boolValue": true } })));
+        assert!((span.duration_ms() - 1.999).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,427 @@
+//! Conversation export: chat sessions as styled PDF documents
+//!
+//! The web UI keeps its sessions in storage under `clawasm_sessions`, each
+//! with `{title, provider, model, createdAt, messages: [{role, content,
+//! time, toolCalls}]}`. `render` lays one out as a PDF: a header, every
+//! message under a role label with a colored rule and its Markdown laid
+//! out (headings, nested lists, tables, quotes and code blocks set apart),
+//! and an appendix listing the arguments of each tool call, referenced from
+//! its message as [T1], [T2].
+
+use crate::agent::ToolCall;
+use crate::error::{Error, Result};
+use crate::markdown::{self, Block};
+use crate::pdf::{self, Color, Font, PdfDocument, PAGE_HEIGHT, PAGE_WIDTH};
+use crate::platform::storage;
+
+/// Storage key of the web UI's sessions
+pub const SESSIONS_KEY: &str = "clawasm_sessions";
+/// Storage key of the session open in the web UI
+pub const ACTIVE_SESSION_KEY: &str = "clawasm_active_session";
+
+/// Characters of tool arguments shown in the appendix
+const MAX_ARGUMENT_CHARS: usize = 2000;
+
+/// A conversation to export
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub title: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub created_at: Option<String>,
+    pub messages: Vec<TranscriptMessage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranscriptMessage {
+    pub role: String,
+    pub content: String,
+    pub time: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// Session `id` as the web UI stores it, or the active one
+pub fn load_session(id: Option<&str>) -> Result<serde_json::Value> {
+    let mut sessions: serde_json::Value = storage::get(SESSIONS_KEY)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    let id = match id.filter(|id| !id.is_empty()) {
+        Some(id) => id.to_string(),
+        None => storage::get(ACTIVE_SESSION_KEY)?
+            .ok_or_else(|| Error::new("No active conversation to export"))?,
+    };
+    sessions.get_mut(&id)
+        .map(serde_json::Value::take)
+        .ok_or_else(|| Error::new(format!("No conversation with ID '{}'", id)))
+}
+
+impl Transcript {
+    /// A session as the web UI stores it
+    pub fn from_session(session: &serde_json::Value) -> Transcript {
+        let text = |key: &str| session[key].as_str().filter(|s| !s.is_empty()).map(str::to_string);
+        let messages = session["messages"].as_array().map(Vec::as_slice).unwrap_or_default().iter()
+            .map(|m| TranscriptMessage {
+                role: m["role"].as_str().unwrap_or("unknown").to_string(),
+                content: strip_tool_call_html(m["content"].as_str().unwrap_or("")).to_string(),
+                time: m["time"].as_str().map(str::to_string),
+                tool_calls: serde_json::from_value(m["toolCalls"].clone()).unwrap_or_default(),
+            })
+            .filter(|m| !m.content.trim().is_empty() || !m.tool_calls.is_empty())
+            .collect();
+        Transcript {
+            title: text("title").unwrap_or_else(|| "Conversation".to_string()),
+            provider: text("provider"),
+            model: text("model"),
+            created_at: text("createdAt"),
+            messages,
+        }
+    }
+
+    /// Session `id` from storage, or the active one
+    pub fn load(id: Option<&str>) -> Result<Transcript> {
+        Ok(Transcript::from_session(&load_session(id)?))
+    }
+
+    /// A file name for the export, from the title
+    pub fn filename(&self) -> String {
+        let name: String = self.title.chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        format!("{}.pdf", name.trim_matches('_').chars().take(60).collect::<String>())
+    }
+}
+
+/// Sessions saved in verbose mode by older versions of the web UI have the
+/// tool-call list, as HTML, in front of the reply; the export lists the
+/// calls in its appendix instead
+fn strip_tool_call_html(content: &str) -> &str {
+    if content.starts_with("<div") && content.contains("🔧 Tool Calls:") {
+        if let Some(end) = content.find("</div></div>") {
+            return &content[end + "</div></div>".len()..];
+        }
+    }
+    content
+}
+
+/// Page styles to export with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// Colored role labels and rules
+    Default,
+    /// Smaller type and spacing, for long conversations
+    Compact,
+    /// Black and gray only, for printing
+    Monochrome,
+}
+
+impl Template {
+    pub const NAMES: [&'static str; 3] = ["default", "compact", "monochrome"];
+
+    /// The template called `name`, the default one when `None`
+    pub fn parse(name: Option<&str>) -> Result<Template> {
+        match name.map(|n| n.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("default") => Ok(Template::Default),
+            Some("compact") => Ok(Template::Compact),
+            Some("monochrome") | Some("print") => Ok(Template::Monochrome),
+            Some(other) => Err(Error::new(format!(
+                "Unknown template '{}' (expected one of {})", other, Template::NAMES.join(", ")
+            ))),
+        }
+    }
+
+    fn style(self) -> Style {
+        let colored = Style {
+            body: 10.5,
+            code: 8.5,
+            gap: 4.0,
+            user: (0.13, 0.55, 0.33),
+            assistant: (0.16, 0.38, 0.75),
+            other: (0.45, 0.45, 0.45),
+            text: (0.1, 0.1, 0.12),
+            muted: (0.45, 0.45, 0.5),
+            code_background: (0.95, 0.95, 0.96),
+        };
+        match self {
+            Template::Default => colored,
+            Template::Compact => Style { body: 9.0, code: 7.5, gap: 2.5, ..colored },
+            Template::Monochrome => Style {
+                user: (0.2, 0.2, 0.2),
+                assistant: (0.0, 0.0, 0.0),
+                other: (0.5, 0.5, 0.5),
+                text: pdf::BLACK,
+                muted: (0.4, 0.4, 0.4),
+                code_background: (0.92, 0.92, 0.92),
+                ..colored
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    body: f32,
+    code: f32,
+    /// Space between paragraphs
+    gap: f32,
+    user: Color,
+    assistant: Color,
+    other: Color,
+    text: Color,
+    muted: Color,
+    code_background: Color,
+}
+
+impl Style {
+    fn role_color(&self, role: &str) -> Color {
+        match role {
+            "user" => self.user,
+            "assistant" => self.assistant,
+            _ => self.other,
+        }
+    }
+}
+
+const MARGIN: f32 = 50.0;
+/// Message text starts this far right of the margin, after the role rule
+const INDENT: f32 = 12.0;
+
+/// Where the next line goes
+struct Cursor {
+    doc: PdfDocument,
+    page: usize,
+    y: f32,
+    style: Style,
+}
+
+impl Cursor {
+    /// Move down `height`, starting a page if it does not fit
+    fn advance(&mut self, height: f32) -> f32 {
+        if self.y - height < MARGIN + 20.0 {
+            self.page = self.doc.add_page();
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        self.y -= height;
+        self.y
+    }
+
+    /// Lines of message text with the role's rule beside them
+    fn lines(&mut self, lines: &[String], x: f32, font: Font, size: f32, color: Color, rule: Color) {
+        let height = size * 1.35;
+        for line in lines {
+            let y = self.advance(height);
+            self.doc.rect(self.page, MARGIN, y - size * 0.35, 2.0, height, rule);
+            self.doc.text(self.page, x, y, font, size, color, line);
+        }
+    }
+
+    fn code(&mut self, language: &str, lines: &[String], rule: Color) {
+        let style = self.style;
+        let x = MARGIN + INDENT;
+        let width = PAGE_WIDTH - MARGIN - x;
+        if !language.is_empty() {
+            self.lines(&[language.to_string()], x + 4.0, Font::Italic, style.code, style.muted, rule);
+        }
+        let height = style.code * 1.4;
+        let columns = ((width - 8.0) / (style.code * 0.6)).floor().max(1.0) as usize;
+        for line in lines {
+            let chars: Vec<char> = line.chars().collect();
+            // Hard-wrap at the column count; an empty line still gets its shading
+            let parts: Vec<String> = if chars.is_empty() {
+                vec![String::new()]
+            } else {
+                chars.chunks(columns).map(|c| c.iter().collect()).collect()
+            };
+            for part in parts {
+                let y = self.advance(height);
+                self.doc.rect(self.page, MARGIN, y - style.code * 0.4, 2.0, height, rule);
+                self.doc.rect(self.page, x, y - style.code * 0.4, width, height, style.code_background);
+                self.doc.text(self.page, x + 4.0, y, Font::Mono, style.code, style.text, &part);
+            }
+        }
+        self.y -= style.gap;
+    }
+}
+
+fn role_label(role: &str) -> String {
+    match role {
+        "user" => "You".to_string(),
+        "assistant" => "Assistant".to_string(),
+        "system" => "System".to_string(),
+        other => {
+            let mut chars = other.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+    }
+}
+
+/// `transcript` as a PDF file in `template`'s style
+pub fn render(transcript: &Transcript, template: Template) -> Vec<u8> {
+    let style = template.style();
+    let mut doc = PdfDocument::new(&transcript.title);
+    let page = doc.add_page();
+    let mut cursor = Cursor { doc, page, y: PAGE_HEIGHT - MARGIN, style };
+    let text_width = PAGE_WIDTH - 2.0 * MARGIN;
+
+    // Header
+    for line in pdf::wrap(&transcript.title, Font::Bold, 20.0, text_width) {
+        let y = cursor.advance(24.0);
+        cursor.doc.text(cursor.page, MARGIN, y, Font::Bold, 20.0, style.text, &line);
+    }
+    let mut details = vec![format!("Exported {}", chrono::Utc::now().format("%Y-%m-%d %H:%M UTC"))];
+    match (&transcript.provider, &transcript.model) {
+        (Some(provider), Some(model)) => details.push(format!("{} / {}", provider, model)),
+        (provider, model) => details.extend(provider.iter().chain(model).cloned()),
+    }
+    details.push(format!("{} messages", transcript.messages.len()));
+    let y = cursor.advance(16.0);
+    cursor.doc.text(cursor.page, MARGIN, y, Font::Regular, 9.0, style.muted, &details.join(" · "));
+    let y = cursor.advance(10.0);
+    cursor.doc.rect(cursor.page, MARGIN, y, text_width, 0.8, style.muted);
+
+    let mut appendix: Vec<(usize, &ToolCall)> = Vec::new();
+    let body_x = MARGIN + INDENT;
+    let body_width = PAGE_WIDTH - MARGIN - body_x;
+    for (number, message) in transcript.messages.iter().enumerate() {
+        let rule = style.role_color(&message.role);
+        cursor.y -= style.gap * 3.0;
+        let y = cursor.advance(style.body * 1.6);
+        cursor.doc.rect(cursor.page, MARGIN, y - 1.0, 7.0, 7.0, rule);
+        let label = role_label(&message.role);
+        cursor.doc.text(cursor.page, body_x, y, Font::Bold, style.body, rule, &label);
+        if let Some(time) = &message.time {
+            let x = body_x + pdf::text_width(&label, Font::Bold, style.body) + 8.0;
+            cursor.doc.text(cursor.page, x, y, Font::Regular, style.body - 2.0, style.muted, time);
+        }
+
+        for block in markdown::to_blocks(&message.content) {
+            match block {
+                Block::Heading { level, text } => {
+                    cursor.y -= style.gap;
+                    let size = style.body + if level <= 2 { 2.5 } else { 1.0 };
+                    let lines = pdf::wrap(&text, Font::Bold, size, body_width);
+                    cursor.lines(&lines, body_x, Font::Bold, size, style.text, rule);
+                }
+                Block::Paragraph(text) => {
+                    let lines = pdf::wrap(&text, Font::Regular, style.body, body_width);
+                    cursor.lines(&lines, body_x, Font::Regular, style.body, style.text, rule);
+                    cursor.y -= style.gap;
+                }
+                Block::Quote(text) => {
+                    let lines = pdf::wrap(&text, Font::Italic, style.body, body_width - 12.0);
+                    cursor.lines(&lines, body_x + 12.0, Font::Italic, style.body, style.muted, rule);
+                    cursor.y -= style.gap;
+                }
+                Block::Item { depth, marker, text } => {
+                    let x = body_x + 12.0 * (depth + 1) as f32;
+                    let mut lines = pdf::wrap(&text, Font::Regular, style.body, PAGE_WIDTH - MARGIN - x);
+                    cursor.lines(&lines[..1], x, Font::Regular, style.body, style.text, rule);
+                    // The standard fonts have no ballot boxes
+                    let marker = match marker.as_str() {
+                        "☑" => "[x]",
+                        "☐" => "[ ]",
+                        marker => marker,
+                    };
+                    let marker_x = x - 4.0 - pdf::text_width(marker, Font::Regular, style.body);
+                    cursor.doc.text(cursor.page, marker_x, cursor.y, Font::Regular, style.body, style.text, marker);
+                    lines.remove(0);
+                    cursor.lines(&lines, x, Font::Regular, style.body, style.text, rule);
+                }
+                Block::Code { language, lines } => cursor.code(&language, &lines, rule),
+                Block::Table { header, rows } => {
+                    let lines = pdf::wrap(&header.join("  |  "), Font::Bold, style.body, body_width);
+                    cursor.lines(&lines, body_x, Font::Bold, style.body, style.text, rule);
+                    cursor.doc.rect(cursor.page, body_x, cursor.y - 3.0, body_width, 0.5, style.muted);
+                    for row in rows {
+                        let lines = pdf::wrap(&row.join("  |  "), Font::Regular, style.body, body_width);
+                        cursor.lines(&lines, body_x, Font::Regular, style.body, style.text, rule);
+                    }
+                    cursor.y -= style.gap;
+                }
+                // The export draws no images; it names them
+                Block::Image { alt, url } => {
+                    let label = if alt.is_empty() { format!("[Image: {}]", url) } else { format!("[Image: {}] {}", alt, url) };
+                    let lines = pdf::wrap(&label, Font::Italic, style.body, body_width);
+                    cursor.lines(&lines, body_x, Font::Italic, style.body, style.muted, rule);
+                    cursor.y -= style.gap;
+                }
+                Block::Rule => {
+                    let y = cursor.advance(style.gap * 2.0);
+                    cursor.doc.rect(cursor.page, body_x, y + style.gap, body_width, 0.5, style.muted);
+                }
+            }
+        }
+
+        if !message.tool_calls.is_empty() {
+            let references: Vec<String> = message.tool_calls.iter()
+                .map(|call| {
+                    appendix.push((number + 1, call));
+                    format!("[T{}] {}", appendix.len(), call.name)
+                })
+                .collect();
+            let lines = pdf::wrap(&format!("Tools used: {}", references.join(", ")), Font::Italic, style.body - 1.0, body_width);
+            cursor.lines(&lines, body_x, Font::Italic, style.body - 1.0, style.muted, rule);
+        }
+    }
+
+    if !appendix.is_empty() {
+        cursor.page = cursor.doc.add_page();
+        cursor.y = PAGE_HEIGHT - MARGIN;
+        let y = cursor.advance(20.0);
+        cursor.doc.text(cursor.page, MARGIN, y, Font::Bold, 15.0, style.text, "Appendix: tool calls");
+        for (i, (message, call)) in appendix.iter().enumerate() {
+            cursor.y -= style.gap * 2.0;
+            let y = cursor.advance(style.body * 1.5);
+            let heading = format!("[T{}] {} (message {})", i + 1, call.name, message);
+            cursor.doc.text(cursor.page, MARGIN, y, Font::Bold, style.body, style.text, &heading);
+            let mut arguments = serde_json::to_string_pretty(&call.arguments).unwrap_or_default();
+            if arguments.chars().count() > MAX_ARGUMENT_CHARS {
+                arguments = format!("{}…", arguments.chars().take(MAX_ARGUMENT_CHARS).collect::<String>());
+            }
+            let lines: Vec<String> = arguments.lines().map(str::to_string).collect();
+            cursor.code("", &lines, style.code_background);
+        }
+    }
+
+    // Footers, now that the page count is known
+    let mut doc = cursor.doc;
+    let pages = doc.page_count();
+    for page in 0..pages {
+        let footer = format!("{} · page {} of {}", transcript.title, page + 1, pages);
+        let footer: String = footer.chars().take(110).collect();
+        doc.text(page, MARGIN, MARGIN - 20.0, Font::Regular, 8.0, style.muted, &footer);
+    }
+    doc.to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_session() {
+        let session = serde_json::json!({
+            "title": "Rust (help)",
+            "provider": "openai",
+            "model": "gpt-4o-mini",
+            "messages": [
+                {"role": "user", "content": "Show me a loop", "time": "10:01:02"},
+                {"role": "assistant", "content": "<div style=\"x\"><strong>🔧 Tool Calls:</strong><br><div>web_search</div></div>Here:\n```rust\nfor i in 0..3 {}\n```\n1. first\n   - [ ] nested",
+                 "toolCalls": [{"name": "web_search", "arguments": {"query": "rust loops"}}]},
+                {"role": "system", "content": "  "}
+            ]
+        });
+        let transcript = Transcript::from_session(&session);
+        assert_eq!(transcript.messages.len(), 2, "empty messages are skipped");
+        assert!(transcript.messages[1].content.starts_with("Here:"), "the verbose tool-call HTML is dropped");
+        assert_eq!(transcript.filename(), "Rust__help.pdf");
+
+        let pdf = String::from_utf8(render(&transcript, Template::Default)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4") && pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("(Tools used: [T1] web_search)"));
+        assert!(pdf.contains("(1.) Tj") && pdf.contains("([ ]) Tj") && pdf.contains("(nested) Tj"));
+        assert!(pdf.contains("(Appendix: tool calls)") && pdf.contains("(  \"query\": \"rust loops\")"));
+        assert!(pdf.contains("/Count 2"), "the appendix starts a page");
+        assert!(pdf.contains("(Rust \\(help\\) \\267 page 2 of 2)"));
+        assert!(Template::parse(Some("fancy")).is_err());
+    }
+}
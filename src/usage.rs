@@ -0,0 +1,280 @@
+//! Token usage and monthly budgets
+//!
+//! Every provider reply adds its token counts (estimated when the provider
+//! reports none) and their cost at the budget's prices to that provider's
+//! total for the month, kept in localStorage so it survives reloads. With a
+//! `budgets` entry for the provider, using `warn_at` of a limit raises a
+//! warning alert and reaching it an exceeded alert; a blocking budget then
+//! refuses further requests until the next month or a higher limit, so a
+//! runaway tool loop stops instead of running up a bill. Alerts go to the
+//! log and to the callback given to `ClaWasm.onBudgetAlert()`.
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::chat::Message;
+use crate::config::{Budget, Config};
+use crate::error::{Error, Result};
+use crate::logger;
+use crate::platform::storage;
+
+/// localStorage key of the usage ledger
+pub const USAGE_KEY: &str = "clawasm_usage";
+
+/// Months of usage kept, the current one included
+const MONTHS_KEPT: usize = 12;
+
+/// Tokens of one request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tokens {
+    pub input: u64,
+    pub output: u64,
+}
+
+impl Tokens {
+    /// About four characters per token, for providers that report no usage
+    pub fn estimate(messages: &[Message], reply: &str) -> Self {
+        let chars: usize = messages.iter().map(|m| m.content.chars().count()).sum();
+        Tokens { input: chars.div_ceil(4) as u64, output: reply.chars().count().div_ceil(4) as u64 }
+    }
+}
+
+/// One provider's usage in one month
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Consumption {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub requests: u64,
+    /// Requests whose tokens were estimated
+    pub estimated: u64,
+    /// At the budget's prices when the request was made
+    pub cost: f64,
+}
+
+impl Consumption {
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(&mut self, tokens: Tokens, estimated: bool, budget: Option<&Budget>) {
+        self.input_tokens += tokens.input;
+        self.output_tokens += tokens.output;
+        self.requests += 1;
+        self.estimated += estimated as u64;
+        if let Some(budget) = budget {
+            self.cost += (tokens.input as f64 * budget.input_price + tokens.output as f64 * budget.output_price) / 1_000_000.0;
+        }
+    }
+}
+
+/// Usage by month ("2026-10") and provider
+pub type Ledger = BTreeMap<String, BTreeMap<String, Consumption>>;
+
+/// The current month, as ledger key
+pub fn this_month() -> String {
+    chrono::Utc::now().format("%Y-%m").to_string()
+}
+
+pub fn load() -> Result<Ledger> {
+    match storage::get(USAGE_KEY)? {
+        Some(json) => serde_json::from_str(&json).map_err(|e| Error::new(format!("Corrupt usage ledger: {}", e))),
+        None => Ok(Ledger::new()),
+    }
+}
+
+fn save(ledger: &Ledger) -> Result<()> {
+    let json = serde_json::to_string(ledger).map_err(|e| Error::new(format!("Serialization error: {}", e)))?;
+    storage::set(USAGE_KEY, &json)
+}
+
+/// `provider`'s usage this month
+pub fn current(provider: &str) -> Result<Consumption> {
+    Ok(load()?.remove(&this_month()).and_then(|mut month| month.remove(provider)).unwrap_or_default())
+}
+
+/// Forget this month's usage of `provider`, or of every provider
+pub fn reset(provider: Option<&str>) -> Result<()> {
+    let mut ledger = load()?;
+    match provider {
+        Some(provider) => {
+            if let Some(month) = ledger.get_mut(&this_month()) {
+                month.remove(provider);
+            }
+        }
+        None => {
+            ledger.remove(&this_month());
+        }
+    }
+    save(&ledger)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertLevel {
+    /// `warn_at` of a limit used
+    Warning,
+    /// A limit reached
+    Exceeded,
+    /// A request refused by a blocking budget
+    Blocked,
+}
+
+/// A budget event for the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub provider: String,
+    pub level: AlertLevel,
+    pub month: String,
+    pub used_tokens: u64,
+    pub limit_tokens: Option<u64>,
+    pub cost: f64,
+    pub limit_cost: Option<f64>,
+    pub message: String,
+}
+
+impl Alert {
+    fn new(provider: &str, level: AlertLevel, used: &Consumption, budget: &Budget) -> Self {
+        let mut limits = Vec::new();
+        if let Some(limit) = budget.monthly_tokens {
+            limits.push(format!("{} of {} tokens", used.tokens(), limit));
+        }
+        if let Some(limit) = budget.monthly_cost {
+            limits.push(format!("{:.2} of {:.2} spent", used.cost, limit));
+        }
+        let message = match level {
+            AlertLevel::Warning => format!("{} has used {:.0}% of its monthly budget ({})",
+                provider, share(budget, used).unwrap_or_default() * 100.0, limits.join(", ")),
+            AlertLevel::Exceeded if budget.block => format!("{} reached its monthly budget ({}); further requests are blocked until next month or a higher limit",
+                provider, limits.join(", ")),
+            AlertLevel::Exceeded => format!("{} is over its monthly budget ({})", provider, limits.join(", ")),
+            AlertLevel::Blocked => format!("Monthly budget for {} is used up ({}). Raise budgets.{} in the config or wait for next month",
+                provider, limits.join(", "), provider),
+        };
+        Alert {
+            provider: provider.to_string(),
+            level,
+            month: this_month(),
+            used_tokens: used.tokens(),
+            limit_tokens: budget.monthly_tokens,
+            cost: used.cost,
+            limit_cost: budget.monthly_cost,
+            message,
+        }
+    }
+}
+
+pub type Listener = Rc<dyn Fn(&Alert)>;
+
+thread_local! {
+    static LISTENER: RefCell<Option<Listener>> = const { RefCell::new(None) };
+}
+
+/// Send alerts to `listener`, or nowhere with `None`
+pub fn set_listener(listener: Option<Listener>) {
+    LISTENER.with(|l| *l.borrow_mut() = listener);
+}
+
+fn emit(alert: &Alert) {
+    logger::warn("usage", &alert.message);
+    let listener = LISTENER.with(|l| l.borrow().clone());
+    if let Some(listener) = listener {
+        listener(alert);
+    }
+}
+
+/// Share of the nearest limit used (1.0 = reached), None without limits
+fn share(budget: &Budget, used: &Consumption) -> Option<f64> {
+    let tokens = budget.monthly_tokens.map(|limit| used.tokens() as f64 / limit.max(1) as f64);
+    let cost = budget.monthly_cost.map(|limit| used.cost / limit);
+    match (tokens, cost) {
+        (Some(t), Some(c)) => Some(t.max(c)),
+        (t, c) => t.or(c),
+    }
+}
+
+/// The alert due going from `before` to `after`, if a threshold was crossed
+fn crossed(budget: &Budget, before: &Consumption, after: &Consumption) -> Option<AlertLevel> {
+    let (before, after) = (share(budget, before).unwrap_or_default(), share(budget, after)?);
+    let warn_at = budget.warn_at as f64;
+    if after >= 1.0 && before < 1.0 {
+        Some(AlertLevel::Exceeded)
+    } else if after >= warn_at && before < warn_at && after < 1.0 {
+        Some(AlertLevel::Warning)
+    } else {
+        None
+    }
+}
+
+/// Refuse a request to `provider` once a blocking budget is used up
+pub fn check(provider: &str, config: &Config) -> Result<()> {
+    let Some(budget) = config.budgets.get(provider).filter(|b| b.block) else {
+        return Ok(());
+    };
+    let used = current(provider)?;
+    if share(budget, &used).is_some_and(|share| share >= 1.0) {
+        let alert = Alert::new(provider, AlertLevel::Blocked, &used, budget);
+        emit(&alert);
+        return Err(Error::new(alert.message));
+    }
+    Ok(())
+}
+
+/// Add a reply's tokens to `provider`'s month, alerting on crossed thresholds
+pub fn record(provider: &str, tokens: Tokens, estimated: bool, config: &Config) -> Result<()> {
+    let budget = config.budgets.get(provider);
+    let mut ledger = load()?;
+    let month = this_month();
+    let used = ledger.entry(month).or_default().entry(provider.to_string()).or_default();
+    let before = used.clone();
+    used.add(tokens, estimated, budget);
+    let after = used.clone();
+    while ledger.len() > MONTHS_KEPT {
+        ledger.pop_first();
+    }
+    save(&ledger)?;
+
+    if let Some(budget) = budget {
+        if let Some(level) = crossed(budget, &before, &after) {
+            emit(&Alert::new(provider, level, &after, budget));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_and_thresholds() {
+        let budget = Budget { monthly_tokens: Some(1000), monthly_cost: Some(1.0), input_price: 100.0, output_price: 400.0, ..Budget::default() };
+        let mut used = Consumption::default();
+        used.add(Tokens { input: 500, output: 100 }, false, Some(&budget));
+        assert_eq!(used.tokens(), 600);
+        assert!((used.cost - 0.09).abs() < 1e-9);
+        assert_eq!(share(&budget, &used), Some(0.6));
+
+        let before = used.clone();
+        used.add(Tokens { input: 200, output: 50 }, true, Some(&budget));
+        assert_eq!(crossed(&budget, &before, &used), Some(AlertLevel::Warning));
+        let before = used.clone();
+        used.add(Tokens { input: 100, output: 0 }, false, Some(&budget));
+        assert_eq!(crossed(&budget, &before, &used), None);
+        let before = used.clone();
+        used.add(Tokens { input: 100, output: 0 }, false, Some(&budget));
+        assert_eq!(crossed(&budget, &before, &used), Some(AlertLevel::Exceeded));
+        assert_eq!((used.requests, used.estimated), (4, 1));
+
+        // Without limits nothing is ever crossed
+        assert_eq!(crossed(&Budget::default(), &before, &used), None);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let tokens = Tokens::estimate(&[Message::system("abcd"), Message::user("hello")], "hi");
+        assert_eq!(tokens, Tokens { input: 3, output: 1 });
+    }
+}
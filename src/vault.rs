@@ -0,0 +1,231 @@
+//! Encrypted storage vault for claWasm
+//!
+//! Optional encryption at rest for memory entries, notes, stored files,
+//! and the audit log.
+//! Uses AES-256-GCM with a PBKDF2-SHA256 key derived from a user passphrase.
+//! Envelopes follow the Web Crypto AES-GCM layout (12-byte IV, then
+//! ciphertext || tag) and salts/IVs come from `crypto.getRandomValues`
+//! (the OS random source in the native build); the cipher runs in Rust so
+//! storage reads and writes stay synchronous.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::error::{Error, Result};
+use crate::platform::{self, storage};
+
+/// localStorage key holding the vault metadata (salt, KDF params, check value)
+const VAULT_KEY: &str = "clawasm_vault";
+/// Prefix marking an encrypted value
+const ENVELOPE_PREFIX: &str = "enc:v1:";
+/// Known plaintext used to verify a passphrase
+const CHECK_PLAINTEXT: &str = "clawasm-vault-check";
+/// PBKDF2 iteration count for new vaults
+const KDF_ITERATIONS: u32 = 310_000;
+
+/// Vault metadata persisted in localStorage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultMeta {
+    version: u32,
+    salt: String,
+    iterations: u32,
+    check: String,
+}
+
+thread_local! {
+    /// Derived key while the vault is unlocked
+    static KEY: RefCell<Option<[u8; 32]>> = const { RefCell::new(None) };
+}
+
+/// Whether encryption has been enabled in this browser
+pub fn is_enabled() -> bool {
+    storage::get(VAULT_KEY).ok().flatten().is_some()
+}
+
+/// Whether the key is currently held in memory
+pub fn is_unlocked() -> bool {
+    KEY.with(|k| k.borrow().is_some())
+}
+
+/// Unlock the vault, creating it on first use
+///
+/// Enabling encryption re-encrypts existing plaintext data. Returns `true`
+/// if a new vault was created.
+///
+/// The vault metadata is written before any data is sealed, so a failure
+/// partway leaves a mix of plaintext and ciphertext the passphrase still
+/// opens, never ciphertext under a key that was only in memory. Every
+/// unlock seals what is still plaintext, finishing an interrupted run.
+pub fn unlock(passphrase: &str) -> Result<bool> {
+    if passphrase.is_empty() {
+        return Err(Error::new("Passphrase must not be empty"));
+    }
+
+    match storage::get(VAULT_KEY)? {
+        Some(json) => {
+            let meta: VaultMeta = serde_json::from_str(&json)
+                .map_err(|e| Error::new(format!("Corrupt vault metadata: {}", e)))?;
+            let salt = BASE64.decode(&meta.salt)
+                .map_err(|e| Error::new(format!("Corrupt vault salt: {}", e)))?;
+            let key = derive_key(passphrase, &salt, meta.iterations);
+            match decrypt_with(&key, &meta.check) {
+                Ok(check) if check == CHECK_PLAINTEXT => {}
+                _ => return Err(Error::new("Wrong passphrase")),
+            }
+            KEY.with(|k| *k.borrow_mut() = Some(key));
+            seal_existing()?;
+            Ok(false)
+        }
+        None => {
+            let salt = random_bytes::<16>()?;
+            let key = derive_key(passphrase, &salt, KDF_ITERATIONS);
+            let meta = VaultMeta {
+                version: 1,
+                salt: BASE64.encode(salt),
+                iterations: KDF_ITERATIONS,
+                check: encrypt_with(&key, &random_bytes::<12>()?, CHECK_PLAINTEXT),
+            };
+            // The key must be recoverable before anything is sealed with it
+            storage::set(VAULT_KEY, &serde_json::to_string(&meta).unwrap())?;
+            KEY.with(|k| *k.borrow_mut() = Some(key));
+            seal_existing()?;
+            Ok(true)
+        }
+    }
+}
+
+/// Encrypt every protected item still stored as plaintext
+fn seal_existing() -> Result<()> {
+    for item in protected_keys() {
+        seal_item(&item)?;
+    }
+    Ok(())
+}
+
+/// Forget the key; encrypted data is unreadable until the next unlock
+pub fn lock() {
+    KEY.with(|k| *k.borrow_mut() = None);
+}
+
+/// Encrypt a value for storage (passthrough when encryption is not enabled)
+pub fn seal(plaintext: &str) -> Result<String> {
+    let key = KEY.with(|k| *k.borrow());
+    match key {
+        Some(key) => Ok(encrypt_with(&key, &random_bytes::<12>()?, plaintext)),
+        None if is_enabled() => Err(Error::new("Storage is locked. Unlock it with your passphrase first.")),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+/// Decrypt a stored value (plaintext values are returned unchanged)
+pub fn open(stored: &str) -> Result<String> {
+    if !stored.starts_with(ENVELOPE_PREFIX) {
+        return Ok(stored.to_string());
+    }
+    let key = KEY.with(|k| *k.borrow())
+        .ok_or_else(|| Error::new("Storage is locked. Unlock it with your passphrase first."))?;
+    decrypt_with(&key, stored).map_err(Error::new)
+}
+
+/// Encrypt a storage item in place (used for data written by the JS side)
+pub fn seal_item(item: &str) -> Result<()> {
+    if !is_enabled() {
+        return Ok(());
+    }
+    if let Some(value) = storage::get(item)? {
+        if !value.starts_with(ENVELOPE_PREFIX) {
+            storage::set(item, &seal(&value)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Storage keys holding sensitive data: memories, notes, files, and the audit log
+fn protected_keys() -> Vec<String> {
+    let read_index = |key: &str| -> Vec<String> {
+        storage::get(key)
+            .ok()
+            .flatten()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    };
+
+    let mut keys = vec![
+        "clawasm_notes".to_string(),
+        crate::scan_schedule::SCAN_SCHEDULE_KEY.to_string(),
+    ];
+    keys.extend(crate::audit::storage_keys());
+    keys.extend(read_index("memory_index").into_iter().map(|id| format!("memory_{}", id)));
+    for file_id in read_index(crate::files::FILES_INDEX_KEY) {
+        keys.extend(crate::files::storage_keys(&file_id));
+    }
+    keys
+}
+
+/// Random bytes from the platform CSPRNG
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    platform::fill_random(&mut buf)?;
+    Ok(buf)
+}
+
+/// PBKDF2-HMAC-SHA256 key derivation
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+/// Encrypt into an `enc:v1:` envelope: base64(iv || ciphertext || tag)
+fn encrypt_with(key: &[u8; 32], iv: &[u8; 12], plaintext: &str) -> String {
+    let cipher = Aes256Gcm::new(key.into());
+    let ciphertext = cipher.encrypt(Nonce::from_slice(iv), plaintext.as_bytes())
+        .expect("AES-GCM encryption cannot fail for in-memory buffers");
+
+    let mut payload = iv.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    format!("{}{}", ENVELOPE_PREFIX, BASE64.encode(payload))
+}
+
+/// Decrypt an `enc:v1:` envelope
+fn decrypt_with(key: &[u8; 32], envelope: &str) -> Result<String, String> {
+    let encoded = envelope.strip_prefix(ENVELOPE_PREFIX)
+        .ok_or_else(|| "Not an encrypted value".to_string())?;
+    let payload = BASE64.decode(encoded)
+        .map_err(|e| format!("Corrupt encrypted value: {}", e))?;
+    if payload.len() < 12 + 16 {
+        return Err("Corrupt encrypted value: too short".to_string());
+    }
+
+    let (iv, ciphertext) = payload.split_at(12);
+    let cipher = Aes256Gcm::new(key.into());
+    let plaintext = cipher.decrypt(Nonce::from_slice(iv), ciphertext)
+        .map_err(|_| "Decryption failed (wrong key or tampered data)".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| format!("Decrypted value is not UTF-8: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let key = derive_key("correct horse", b"0123456789abcdef", 1000);
+        let sealed = encrypt_with(&key, &[7u8; 12], "secret note");
+        assert!(sealed.starts_with(ENVELOPE_PREFIX));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(decrypt_with(&key, &sealed).unwrap(), "secret note");
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let key = derive_key("correct horse", b"0123456789abcdef", 1000);
+        let other = derive_key("battery staple", b"0123456789abcdef", 1000);
+        let sealed = encrypt_with(&key, &[1u8; 12], "secret note");
+        assert!(decrypt_with(&other, &sealed).is_err());
+    }
+}
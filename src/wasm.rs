@@ -0,0 +1,1513 @@
+//! JavaScript bindings for claWasm
+//!
+//! `ClaWasm` is the object the web UI talks to. Conversation state lives
+//! here; each turn is handed to the shared agent loop with a tool runner
+//! that executes tools in the browser. `ChannelBridge` answers the proxy's
+//! bots with the same agent.
+
+use wasm_bindgen::prelude::*;
+use js_sys::Promise;
+use wasm_bindgen_futures::future_to_promise;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::agent::{Agent, ToolFuture, ToolRunner};
+use crate::config::{self, Config};
+use crate::chat::{Chat, Message};
+use crate::providers::Provider;
+use crate::tools::{get_tool_definitions, execute_tool, execute_tool_with, JsTool, ToolContext};
+use crate::memory::{self, MemorySystem, MemoryConfig};
+use crate::security::{SecurityManager, SecurityConfig, PermissionProfile};
+use crate::audit::{self, AuditFilter, AuditKind};
+use crate::{bridge, diagnostics, documents, files, keepalive, logger, markdown, offline, platform, plugins, progress, providers, research, sanitize, scan_schedule, share, tools, trace, transcript, usage, vault};
+use crate::logger::LogFilter;
+use crate::platform::{ProxyTransport, WebFetch};
+use crate::scan_schedule::{ScheduledScan, ScheduleOptions};
+use crate::transcript::{Template, Transcript};
+use crate::tool_grammar::{self, ToolFormat};
+
+/// Runs tools in the browser, through the configured proxy; `research`
+/// summarizes its sources with the agent's provider, and the document
+/// tools keep documents in its memory
+struct BrowserTools {
+    agent: Agent,
+}
+
+impl ToolRunner for BrowserTools {
+    fn run<'a>(&'a self, name: &'a str, args: &'a serde_json::Value) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let policy = self.agent.security.borrow().get_config().clone();
+            let context = ToolContext {
+                summarizer: research::Summarizer { model: &*self.agent.provider, config: &self.agent.config, policy: &policy },
+                memory: &self.agent.memory,
+            };
+            Ok(execute_tool_with(name, args, &self.agent.config.proxy_url, Some(&context)).await?)
+        })
+    }
+}
+
+/// How the system prompt asks for tool calls in the loose format
+const LOOSE_TOOL_INSTRUCTIONS: &str = "To use a tool, respond with a JSON object in this format:\n\
+    ```tool\n{\"name\": \"tool_name\", \"arguments\": {...}}\n```\n\n\
+    Or simply: {\"name\": \"tool_name\", \"query\": \"...\", ...}";
+
+/// Apply the settings that live outside the instance: log level and the
+/// token sent to the proxy
+fn apply_global_settings(config: &Config) {
+    logger::set_level(config.log_level);
+    platform::set_proxy_token(&config.proxy_url, config.proxy_token.as_deref());
+    tools::set_page_tools(config.page_tools);
+}
+
+/// Collection settings from JSON, the defaults when absent
+fn parse_collection_settings(json: Option<&str>) -> Result<documents::CollectionSettings, JsValue> {
+    match json {
+        Some(json) => serde_json::from_str(json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid collection settings: {}", e))),
+        None => Ok(documents::CollectionSettings::default()),
+    }
+}
+
+/// Initialize the claWasm WASM module
+#[wasm_bindgen]
+pub fn init() {
+    console_error_panic_hook::set_once();
+    platform::watch_connectivity();
+}
+
+/// claWasm - Main entry point for the AI assistant
+#[wasm_bindgen]
+pub struct ClaWasm {
+    /// Lent to a running turn, which hands it back when done
+    chat: Rc<RefCell<Chat>>,
+    /// Shared with running turns; setters copy it only while one is running
+    config: Rc<Config>,
+    provider: Provider,
+    memory: Rc<RefCell<MemorySystem>>,
+    security: Rc<RefCell<SecurityManager>>,
+    /// Who this instance is talking to, for permission profiles
+    subject: Option<String>,
+    /// What the embedding app says about where the user is, for the prompt
+    app_context: Option<String>,
+}
+
+#[wasm_bindgen]
+impl ClaWasm {
+    /// Create a new claWasm instance
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ClaWasm {
+        init();
+        let mut config = Config::default();
+        if let Some(prompt) = config::load_system_prompt() {
+            config.system_prompt = prompt;
+        }
+        apply_global_settings(&config);
+        let chat = Chat::with_system_prompt(&Self::build_system_prompt(&config.system_prompt, config.tool_format));
+        let provider = Provider::from_name(&config.provider.active, config.provider.base_url.as_deref());
+        let memory = Rc::new(RefCell::new(MemorySystem::new(MemoryConfig::default())));
+        let security = Rc::new(RefCell::new(SecurityManager::new(SecurityConfig::load())));
+        ClaWasm { chat: Rc::new(RefCell::new(chat)), config: Rc::new(config), provider, memory, security, subject: None, app_context: None }
+    }
+
+    /// Build system prompt with tools info
+    ///
+    /// `persona` (config.system_prompt) opens the prompt; tool instructions follow.
+    fn build_system_prompt(persona: &str, format: ToolFormat) -> String {
+        let categorized = tools::registry::with(|r| r.catalog());
+
+        format!(
+            "{}\n\n\
+            You have access to the following tools:{}\n\n\
+            {}\n\n\
+            After using a tool, you will receive its result and can continue helping the user.\n\n\
+            CRITICAL RULES:\n\
+            1. When asked about your tools/capabilities, ALWAYS list ALL tools including the security scanners (scan_xss, scan_sqli, scan_headers, scan_ssl, scan_deps, scan_secrets, scan_cors, scan_full, scan_clickjacking, scan_subdomains, scan_discovery, scan_jslibs, scan_mixed_content, scan_manifest)\n\
+            2. When asked about security, vulnerabilities, or code analysis, ALWAYS use the scan_* tools\n\
+            3. NEVER skip or hide tools from the user - show everything available!\n\
+            4. When asked to create a PDF of the current discussion/conversation, use export_conversation. DO NOT start new research!\n\
+            5. When asked to summarize the chat, use get_conversation tool - do not redo the work.\n\
+            6. Only run scan_* tools against targets the user owns or is authorized to test. If a scan is refused as out of scope, explain why and ask the user to confirm authorization - never try to work around it.\n\n\
+            ⚠️ WASM LIMITATIONS:\n\
+            Since I run entirely in the browser as WASM, I have certain limitations:\n\
+            - I cannot access the file system directly (only browser storage/localStorage)\n\
+            - I cannot make direct API calls to external services (I go through a CORS proxy, localhost:3000 by default)\n\
+            - I cannot record audio directly, but I can use text_to_speech tool to generate downloadable MP3s\n\
+            - I cannot execute system commands\n\
+            - Custom tools via create_tool are limited to JavaScript browser APIs\n\n\
+            When you ask for something I cannot do directly, I will:\n\
+            1. Explain my WASM limitations clearly\n\
+            2. Propose alternative solutions using available tools\n\
+            3. If needed, suggest workarounds or external services that could help\n\n\
+            For example: If you want downloadable audio, I use text_to_speech (the proxy's TTS backend) instead of browser speechSynthesis which only speaks but doesn't create files.",
+            persona.trim(),
+            categorized,
+            match format {
+                ToolFormat::Loose => LOOSE_TOOL_INSTRUCTIONS,
+                ToolFormat::Strict => tool_grammar::INSTRUCTIONS,
+            }
+        )
+    }
+
+    /// This instance's system prompt: the tools' and the app's context
+    fn system_prompt(&self) -> String {
+        let prompt = Self::build_system_prompt(&self.config.system_prompt, self.config.tool_format);
+        match &self.app_context {
+            Some(context) => format!("{}\n\n## App context\n{}", prompt, context),
+            None => prompt,
+        }
+    }
+
+    /// Create with custom configuration
+    #[wasm_bindgen(js_name = "withConfig")]
+    pub fn with_config(config_json: &str) -> Result<ClaWasm, JsValue> {
+        init();
+        let config = Config::from_json(config_json)
+            .map_err(|errors| JsValue::from_str(&config::describe_errors(&errors)))?;
+        apply_global_settings(&config);
+        let chat = Chat::with_system_prompt(&Self::build_system_prompt(&config.system_prompt, config.tool_format));
+        let provider = Provider::from_name(&config.provider.active, config.provider.base_url.as_deref());
+        let memory = Rc::new(RefCell::new(MemorySystem::new(MemoryConfig::default())));
+        let security = Rc::new(RefCell::new(SecurityManager::new(SecurityConfig::load())));
+        Ok(ClaWasm { chat: Rc::new(RefCell::new(chat)), config: Rc::new(config), provider, memory, security, subject: None, app_context: None })
+    }
+
+    /// Send a message and get a response (returns Promise)
+    #[wasm_bindgen]
+    pub fn chat(&mut self, message: &str) -> Promise {
+        self.chat_verbose(message, false)
+    }
+
+    /// Send a message and get a response with optional verbose mode
+    ///
+    /// Verbose replies that used tools or came with exposed reasoning are
+    /// JSON `{response, toolCalls: [{name, arguments}], reasoning: [text]}`.
+    #[wasm_bindgen(js_name = "chatVerbose")]
+    pub fn chat_verbose(&mut self, message: &str, verbose: bool) -> Promise {
+        let mut lent = {
+            let mut chat = self.chat.borrow_mut();
+            // Tools may have come or gone since the last message
+            chat.set_system_prompt(&self.system_prompt());
+            chat.add_user(message);
+            // The turn works on the history itself and hands it back
+            chat.lend()
+        };
+        let chat = self.chat.clone();
+        let agent = Agent {
+            config: self.config.clone(),
+            provider: Rc::new(self.provider.clone()),
+            memory: self.memory_handle(),
+            security: self.security.clone(),
+            subject: self.subject.clone(),
+        };
+        let tools = BrowserTools { agent: agent.clone() };
+        let keep_awake = self.config.keep_awake;
+        
+        let future = async move {
+            let _awake = keep_awake.then(keepalive::hold);
+            let turn = agent.run_turn(&mut lent.messages, &tools).await;
+            chat.borrow_mut().give_back(lent);
+            let turn = turn?;
+            // After the answer, not before it
+            wasm_bindgen_futures::spawn_local(async move { agent.consolidate_if_due().await });
+            
+            // Return result based on verbose mode
+            if verbose && !(turn.tool_calls.is_empty() && turn.reasoning.is_empty()) {
+                let result = serde_json::json!({
+                    "response": turn.response,
+                    "toolCalls": turn.tool_calls.iter().map(|t| serde_json::json!({
+                        "name": t.name,
+                        "arguments": t.arguments
+                    })).collect::<Vec<_>>(),
+                    "reasoning": turn.reasoning,
+                });
+                Ok(JsValue::from_str(&serde_json::to_string(&result).unwrap()))
+            } else {
+                Ok(JsValue::from_str(&turn.response))
+            }
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Get available tools
+    #[wasm_bindgen(js_name = "getTools")]
+    pub fn get_tools() -> Vec<JsValue> {
+        get_tool_definitions()
+            .iter()
+            .map(|t| {
+                JsValue::from_str(&serde_json::to_string(t).unwrap_or_default())
+            })
+            .collect()
+    }
+
+    /// Execute a tool directly
+    ///
+    /// `proxy_url` overrides the default proxy (http://localhost:3000) for this call.
+    #[wasm_bindgen(js_name = "executeTool")]
+    pub fn execute_tool_direct(name: &str, args_json: &str, proxy_url: Option<String>) -> Promise {
+        let name = name.to_string();
+        let args: serde_json::Value = serde_json::from_str(args_json)
+            .unwrap_or(serde_json::json!({}));
+        let proxy = proxy_url.unwrap_or_else(|| config::DEFAULT_PROXY_URL.to_string());
+        
+        let future = async move {
+            let result = execute_tool(&name, &args, &proxy).await?;
+            Ok(JsValue::from_str(&result))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Add a tool the model can call, run by a function of the page's
+    ///
+    /// `schema` is the JSON Schema of the arguments; its `description`
+    /// tells the model what the tool does, and `"tier": "mutating"` makes
+    /// every call wait for approval. `callback` gets the arguments object
+    /// and returns the result (a string, or anything JSON can hold) or a
+    /// Promise of it. A tool of the same name registered before is
+    /// replaced; built-in tools can't be. Tools are shared by every
+    /// instance, and this one's system prompt lists the tool right away.
+    #[wasm_bindgen(js_name = "registerJsTool")]
+    pub fn register_js_tool(&mut self, name: &str, schema: &str, callback: js_sys::Function) -> Result<(), JsValue> {
+        let definition = tools::registry::host_definition(name, schema)?;
+        tools::registry::register_external(Rc::new(JsTool { definition, callback }))?;
+        self.chat.borrow_mut().set_system_prompt(&self.system_prompt());
+        Ok(())
+    }
+
+    /// Expose functions of the embedding app to the assistant, as an
+    /// in-app copilot
+    ///
+    /// `tools` maps names to `{description, parameters, tier, handler}`:
+    /// `parameters` is the JSON Schema of the arguments, `tier:
+    /// "mutating"` makes calls wait for approval, and `handler` works as
+    /// `registerJsTool`'s callback. Either every tool is registered or,
+    /// when one is invalid, none is.
+    ///
+    /// ```js
+    /// assistant.registerHostTools({
+    ///     add_todo: { description: 'Add a to-do', parameters: { properties: { title: { type: 'string' } } },
+    ///                 handler: ({ title }) => todos.add(title) },
+    /// });
+    /// ```
+    #[wasm_bindgen(js_name = "registerHostTools")]
+    pub fn register_host_tools(&mut self, tools: &js_sys::Object) -> Result<(), JsValue> {
+        let mut checked = Vec::new();
+        for entry in js_sys::Object::entries(tools).iter() {
+            let entry: js_sys::Array = entry.dyn_into()?;
+            let (name, spec) = (entry.get(0).as_string().unwrap_or_default(), entry.get(1));
+            if tools::registry::get(&name).is_some_and(|t| t.category().is_builtin()) {
+                return Err(JsValue::from_str(&format!("'{}' is a built-in tool", name)));
+            }
+            let callback: js_sys::Function = js_sys::Reflect::get(&spec, &JsValue::from_str("handler"))?
+                .dyn_into()
+                .map_err(|_| JsValue::from_str(&format!("Tool '{}' needs a handler function", name)))?;
+            // The handler is left out of the JSON
+            let spec = js_sys::JSON::stringify(&spec)?.as_string().unwrap_or_default();
+            checked.push(JsTool { definition: tools::registry::host_tool(&name, &spec)?, callback });
+        }
+        for tool in checked {
+            tools::registry::register_external(Rc::new(tool))?;
+        }
+        self.chat.borrow_mut().set_system_prompt(&self.system_prompt());
+        Ok(())
+    }
+
+    /// Tell the assistant what the user is looking at in the embedding app
+    /// (e.g. "Page: /projects/42, 3 open to-dos"), so it can act on it;
+    /// `null` clears it. It goes into the system prompt.
+    #[wasm_bindgen(js_name = "setAppContext")]
+    pub fn set_app_context(&mut self, context: Option<String>) {
+        self.app_context = context.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+        self.chat.borrow_mut().set_system_prompt(&self.system_prompt());
+    }
+
+    /// Remove a tool added with `registerJsTool` or `registerHostTools`
+    #[wasm_bindgen(js_name = "unregisterJsTool")]
+    pub fn unregister_js_tool(&mut self, name: &str) -> Result<(), JsValue> {
+        tools::registry::unregister_external(name)?;
+        self.chat.borrow_mut().set_system_prompt(&self.system_prompt());
+        Ok(())
+    }
+
+    /// Install a WebAssembly plugin's tools
+    ///
+    /// The module at `url` (HTTPS, or HTTP to localhost) must import
+    /// nothing and export the plugin interface described in the README;
+    /// with `sha256` (hex), a module of any other hash is refused.
+    /// Installed plugins are remembered and come back with
+    /// `restorePlugins()`. Resolves to JSON `{name, version, url, sha256,
+    /// tools}`.
+    #[wasm_bindgen(js_name = "installPlugin")]
+    pub fn install_plugin(url: String, sha256: Option<String>) -> Promise {
+        future_to_promise(async move {
+            let installed = tools::plugin::install(&url, sha256.as_deref()).await?;
+            Ok(JsValue::from_str(&serde_json::to_string(&installed).unwrap_or_default()))
+        })
+    }
+
+    /// Remove an installed plugin and its tools
+    #[wasm_bindgen(js_name = "uninstallPlugin")]
+    pub fn uninstall_plugin(name: &str) -> Result<(), JsValue> {
+        Ok(tools::plugin::uninstall(name)?)
+    }
+
+    /// Installed plugins as JSON
+    #[wasm_bindgen(js_name = "listPlugins")]
+    pub fn list_plugins() -> Result<String, JsValue> {
+        serde_json::to_string(&plugins::load()?)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Load the installed plugins again, e.g. at startup; each comes back
+    /// only if its module is unchanged. Resolves to how many did.
+    #[wasm_bindgen(js_name = "restorePlugins")]
+    pub fn restore_plugins() -> Promise {
+        future_to_promise(async move {
+            Ok(JsValue::from(tools::plugin::restore().await? as u32))
+        })
+    }
+
+    /// Get chat history as JSON
+    #[wasm_bindgen(js_name = "getHistory")]
+    pub fn get_history(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.chat.borrow().messages)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Restore a conversation, e.g. a channel's recent turns
+    ///
+    /// Takes a JSON array of `{"role": "user"|"assistant", "content"}` as
+    /// returned by `getHistory`; the current system prompt stays.
+    #[wasm_bindgen(js_name = "setHistory")]
+    pub fn set_history(&mut self, history_json: &str) -> Result<(), JsValue> {
+        let messages: Vec<Message> = serde_json::from_str(history_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid history: {}", e)))?;
+        self.chat.borrow_mut().replace_conversation(messages);
+        Ok(())
+    }
+
+    /// Clear chat history
+    #[wasm_bindgen(js_name = "clearHistory")]
+    pub fn clear_history(&mut self) {
+        self.chat.borrow_mut().clear(&self.system_prompt());
+    }
+
+    /// Replace the system prompt without losing the conversation
+    ///
+    /// The system message is rewritten in place and the prompt is saved, so
+    /// every session (they rebuild their context on switch) and later page
+    /// loads use it. An empty text restores the default prompt.
+    #[wasm_bindgen(js_name = "setSystemPrompt")]
+    pub fn set_system_prompt(&mut self, text: &str) -> Result<(), JsValue> {
+        let text = text.trim();
+        Rc::make_mut(&mut self.config).system_prompt = if text.is_empty() {
+            Config::default().system_prompt
+        } else {
+            text.to_string()
+        };
+        config::save_system_prompt((!text.is_empty()).then_some(text))?;
+        self.chat.borrow_mut().set_system_prompt(&self.system_prompt());
+        Ok(())
+    }
+
+    /// The current system prompt (without the generated tool instructions)
+    #[wasm_bindgen(js_name = "getSystemPrompt")]
+    pub fn get_system_prompt(&self) -> String {
+        self.config.system_prompt.clone()
+    }
+
+    /// Set the AI provider
+    ///
+    /// The previous provider's key, model and base URL are remembered and
+    /// restored when switching back; `api_key` overrides the remembered key.
+    #[wasm_bindgen(js_name = "setProvider")]
+    pub fn set_provider(&mut self, name: &str, api_key: Option<String>) -> Result<(), JsValue> {
+        let config = Rc::make_mut(&mut self.config);
+        config.provider.switch_to(name);
+        if api_key.is_some() {
+            config.provider.api_key = api_key;
+        }
+        self.provider = Provider::from_name(name, self.config.provider.base_url.as_deref());
+        Ok(())
+    }
+
+    /// Check the proxy, provider credentials and storage (returns Promise)
+    ///
+    /// Resolves to a JSON report: `{ok, checked_at, checks: [{name, status,
+    /// detail, hint?, latency_ms?}]}` where status is ok, warn, fail or skipped.
+    /// Meant for the UI to show when requests keep failing.
+    #[wasm_bindgen]
+    pub fn diagnose(&self) -> Promise {
+        let config = self.config.clone();
+        
+        let future = async move {
+            let report = diagnostics::diagnose(&config).await;
+            serde_json::to_string(&report)
+                .map(|json| JsValue::from_str(&json))
+                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Get available providers
+    #[wasm_bindgen(js_name = "getProviders")]
+    pub fn get_providers() -> Vec<JsValue> {
+        providers::AVAILABLE_PROVIDERS
+            .iter()
+            .map(|s| JsValue::from_str(s))
+            .collect()
+    }
+
+    /// Apply a validated merge patch built on the Rust side
+    fn patch_config(&mut self, patch: serde_json::Value) -> Result<(), JsValue> {
+        self.update_config(&patch.to_string())
+    }
+
+    /// Validate a config patch without applying it
+    ///
+    /// Returns a JSON array of `{field, message}` errors, empty when the
+    /// patch would be accepted by `updateConfig`.
+    #[wasm_bindgen(js_name = "validateConfig")]
+    pub fn validate_config(&self, config_json: &str) -> Result<String, JsValue> {
+        let errors = self.config.patched(config_json).err().unwrap_or_default();
+        serde_json::to_string(&errors)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Get configuration as JSON
+    #[wasm_bindgen(js_name = "getConfig")]
+    pub fn get_config(&self) -> Result<String, JsValue> {
+        serde_json::to_string(self.config.as_ref())
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Update configuration
+    ///
+    /// `config_json` is a JSON merge patch: only the fields it contains change
+    /// (`{"temperature": 0.2}`), and `null` clears optional fields. Errors name
+    /// every offending field, e.g. "Config error: temperature: 3 is out of range".
+    #[wasm_bindgen(js_name = "updateConfig")]
+    pub fn update_config(&mut self, config_json: &str) -> Result<(), JsValue> {
+        self.config = self.config.patched(config_json)
+            .map(Rc::new)
+            .map_err(|errors| JsValue::from_str(&config::describe_errors(&errors)))?;
+        apply_global_settings(&self.config);
+        self.chat.borrow_mut().set_system_prompt(&self.system_prompt());
+        self.provider = Provider::from_name(&self.config.provider.active, self.config.provider.base_url.as_deref());
+        Ok(())
+    }
+
+    /// Set API key
+    #[wasm_bindgen(js_name = "setApiKey")]
+    pub fn set_api_key(&mut self, api_key: String) {
+        Rc::make_mut(&mut self.config).provider.api_key = Some(api_key);
+    }
+
+    /// Set model
+    #[wasm_bindgen(js_name = "setModel")]
+    pub fn set_model(&mut self, model: String) {
+        Rc::make_mut(&mut self.config).provider.model = model;
+    }
+
+    /// Set the sampling temperature (0.0 to 2.0)
+    #[wasm_bindgen(js_name = "setTemperature")]
+    pub fn set_temperature(&mut self, temperature: f32) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "temperature": temperature }))
+    }
+
+    /// Set nucleus sampling (0.0 to 1.0, None for the provider default)
+    #[wasm_bindgen(js_name = "setTopP")]
+    pub fn set_top_p(&mut self, top_p: Option<f32>) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "top_p": top_p }))
+    }
+
+    /// Set the frequency penalty (-2.0 to 2.0, None for the provider default)
+    #[wasm_bindgen(js_name = "setFrequencyPenalty")]
+    pub fn set_frequency_penalty(&mut self, penalty: Option<f32>) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "frequency_penalty": penalty }))
+    }
+
+    /// Set the presence penalty (-2.0 to 2.0, None for the provider default)
+    #[wasm_bindgen(js_name = "setPresencePenalty")]
+    pub fn set_presence_penalty(&mut self, penalty: Option<f32>) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "presence_penalty": penalty }))
+    }
+
+    /// Set stop sequences (at most 4, empty to clear)
+    #[wasm_bindgen(js_name = "setStopSequences")]
+    pub fn set_stop_sequences(&mut self, stop: Vec<String>) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "stop": stop }))
+    }
+
+    /// Set the sampling seed (None for random sampling)
+    #[wasm_bindgen(js_name = "setSeed")]
+    pub fn set_seed(&mut self, seed: Option<u32>) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "seed": seed }))
+    }
+
+    /// Set the log level: "error", "warn", "info" (the default) or "debug"
+    #[wasm_bindgen(js_name = "setLogLevel")]
+    pub fn set_log_level(&mut self, level: &str) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "log_level": level }))
+    }
+
+    /// What happens to a reasoning model's thinking: "strip" (the default),
+    /// "log", or "expose" it in verbose results
+    #[wasm_bindgen(js_name = "setReasoning")]
+    pub fn set_reasoning(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "reasoning": mode }))
+    }
+
+    /// Keep the screen awake and the tab unthrottled while a turn or
+    /// scheduled scan runs (a wake lock plus a Web Worker heartbeat)
+    #[wasm_bindgen(js_name = "setKeepAwake")]
+    pub fn set_keep_awake(&mut self, on: bool) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "keep_awake": on }))
+    }
+
+    /// Let the assistant read, click and fill the page it is embedded in
+    /// (read_page, query_selector_text, click_element, fill_form), each
+    /// call only after the user approves it
+    #[wasm_bindgen(js_name = "setPageTools")]
+    pub fn set_page_tools(&mut self, on: bool) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "page_tools": on }))
+    }
+
+    /// What is keeping the tab awake right now, as JSON
+    /// `{active, wakeLock, heartbeat, ticks}`
+    #[wasm_bindgen(js_name = "keepAwakeStatus")]
+    pub fn keep_awake_status() -> String {
+        serde_json::to_string(&keepalive::status()).unwrap_or_default()
+    }
+
+    /// True while the browser has no connection; requests made meanwhile
+    /// wait and are sent when it returns
+    #[wasm_bindgen(js_name = "isOffline")]
+    pub fn is_offline() -> bool {
+        offline::is_offline()
+    }
+
+    /// Requests waiting for the connection, as JSON
+    /// `[{id, method, url, queued_at}]` (URLs without their query)
+    #[wasm_bindgen(js_name = "offlineQueue")]
+    pub fn offline_queue() -> String {
+        serde_json::to_string(&offline::queued()).unwrap_or_default()
+    }
+
+    /// Register the service worker that caches the app for offline startup
+    /// (web/sw.js); resolves to its scope
+    #[wasm_bindgen(js_name = "registerServiceWorker")]
+    pub fn register_service_worker(script_url: String) -> Promise {
+        future_to_promise(async move {
+            let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
+            let container = js_sys::Reflect::get(&window.navigator(), &JsValue::from_str("serviceWorker"))?;
+            if container.is_undefined() {
+                return Err(JsValue::from_str("Service workers are not available (they need HTTPS or localhost)"));
+            }
+            let registration = wasm_bindgen_futures::JsFuture::from(window.navigator().service_worker().register(&script_url)).await?;
+            js_sys::Reflect::get(&registration, &JsValue::from_str("scope"))
+        })
+    }
+
+    /// Set the CORS proxy base URL used for all fetches (None restores http://localhost:3000)
+    #[wasm_bindgen(js_name = "setProxyUrl")]
+    pub fn set_proxy_url(&mut self, proxy_url: Option<String>) {
+        Rc::make_mut(&mut self.config).proxy_url = proxy_url
+            .map(|u| u.trim().trim_end_matches('/').to_string())
+            .filter(|u| !u.is_empty())
+            .unwrap_or_else(|| config::DEFAULT_PROXY_URL.to_string());
+        apply_global_settings(&self.config);
+    }
+
+    /// Set the token the proxy requires (its `CLAWASM_PROXY_TOKEN`; None to stop sending one)
+    #[wasm_bindgen(js_name = "setProxyToken")]
+    pub fn set_proxy_token(&mut self, token: Option<String>) -> Result<(), JsValue> {
+        self.patch_config(serde_json::json!({ "proxy_token": token }))
+    }
+
+    /// Choose how requests leave the page: "direct" (the default; only
+    /// CORS-restricted APIs use the proxy) or "proxy" (everything goes
+    /// through the proxy at the current proxy URL)
+    #[wasm_bindgen(js_name = "setTransport")]
+    pub fn set_transport(&self, mode: &str) -> Result<(), JsValue> {
+        match mode {
+            "direct" => platform::reset_transport(),
+            "proxy" => platform::set_transport(Rc::new(ProxyTransport::new(&self.config.proxy_url, Rc::new(WebFetch)))),
+            _ => return Err(JsValue::from_str(&format!("Unknown transport '{}': use \"direct\" or \"proxy\"", mode))),
+        }
+        Ok(())
+    }
+
+    /// Set the base URL of the active provider (None restores its default endpoint)
+    #[wasm_bindgen(js_name = "setBaseUrl")]
+    pub fn set_base_url(&mut self, base_url: Option<String>) {
+        Rc::make_mut(&mut self.config).provider.base_url = base_url.filter(|u| !u.trim().is_empty());
+        self.provider = Provider::from_name(&self.config.provider.active, self.config.provider.base_url.as_deref());
+    }
+
+    /// Save a memory entry, resolves to its ID (returns Promise)
+    ///
+    /// `metadata_json` is an optional JSON object stored with the entry.
+    #[wasm_bindgen(js_name = "saveMemory")]
+    pub fn save_memory(&self, content: String, metadata_json: Option<String>) -> Promise {
+        let memory = self.memory_handle();
+        
+        let future = async move {
+            let metadata = match metadata_json {
+                Some(json) => serde_json::from_str(&json)
+                    .map_err(|e| JsValue::from_str(&format!("Invalid metadata: {}", e)))?,
+                None => serde_json::json!({}),
+            };
+            let id = memory::save_shared(&memory, &content, metadata).await?;
+            Ok(JsValue::from_str(&id))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Search memories, resolves to a JSON array of results (returns Promise)
+    #[wasm_bindgen(js_name = "recallMemory")]
+    pub fn recall_memory(&self, query: String, limit: Option<usize>) -> Promise {
+        let memory = self.memory_handle();
+        
+        let future = async move {
+            let results = memory::recall_shared(&memory, &query, limit.unwrap_or(5)).await?;
+            let results: Vec<serde_json::Value> = results.into_iter()
+                .map(|r| serde_json::json!({
+                    "id": r.entry.id,
+                    "content": r.entry.content,
+                    "metadata": r.entry.metadata,
+                    "created_at": r.entry.created_at,
+                    "accessed_at": r.entry.accessed_at,
+                    "access_count": r.entry.access_count,
+                    "score": r.score,
+                }))
+                .collect();
+            serde_json::to_string(&results)
+                .map(|json| JsValue::from_str(&json))
+                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Delete a memory entry, resolves to whether it existed (returns Promise)
+    #[wasm_bindgen(js_name = "deleteMemory")]
+    pub fn delete_memory(&self, id: String) -> Promise {
+        let memory = self.memory.clone();
+        
+        let future = async move {
+            let existed = memory.borrow_mut().delete(&id)?;
+            Ok(JsValue::from_bool(existed))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Delete all memories (returns Promise)
+    #[wasm_bindgen(js_name = "clearMemory")]
+    pub fn clear_memory(&self) -> Promise {
+        let memory = self.memory.clone();
+        
+        let future = async move {
+            memory.borrow_mut().clear()?;
+            Ok(JsValue::UNDEFINED)
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Get memory statistics as JSON (returns Promise)
+    #[wasm_bindgen(js_name = "getMemoryStats")]
+    pub fn get_memory_stats(&self) -> Promise {
+        let memory = self.memory.clone();
+        
+        let future = async move {
+            let stats = memory.borrow_mut().stats()?;
+            serde_json::to_string(&stats)
+                .map(|json| JsValue::from_str(&json))
+                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Keep a file the user picked (its text, e.g. from `file.text()`) for
+    /// the agent's `ingest_document` to take by name
+    #[wasm_bindgen(js_name = "attachFile")]
+    pub fn attach_file(name: &str, text: &str) {
+        documents::attach(name, text);
+    }
+
+    /// A saved conversation (default: the open one) as a PDF file, styled
+    /// with `template`: "default", "compact" or "monochrome"
+    #[wasm_bindgen(js_name = "exportConversationPdf")]
+    pub fn export_conversation_pdf(session_id: Option<String>, template: Option<String>) -> Result<Vec<u8>, JsValue> {
+        let template = Template::parse(template.as_deref())?;
+        let transcript = Transcript::load(session_id.as_deref())?;
+        Ok(transcript::render(&transcript, template))
+    }
+
+    /// Share a saved conversation (default: the open one) as a link,
+    /// resolving to it (returns Promise)
+    ///
+    /// The conversation is encrypted here with a new key and only its
+    /// ciphertext goes to the proxy's `/paste`; the key rides in the link's
+    /// fragment, which browsers never send. The link opens `base_url`
+    /// (default: this page), where `openShareLink` reads it back.
+    #[wasm_bindgen(js_name = "exportShareLink")]
+    pub fn export_share_link(&self, session_id: Option<String>, base_url: Option<String>) -> Promise {
+        let proxy = self.config.proxy_url.clone();
+
+        let future = async move {
+            let session = transcript::load_session(session_id.as_deref())?;
+            let base = match base_url.filter(|b| !b.trim().is_empty()) {
+                Some(base) => base,
+                None => web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?.location().href()?,
+            };
+            Ok(JsValue::from_str(&share::export(&session, &proxy, &base).await?))
+        };
+
+        future_to_promise(future)
+    }
+
+    /// The conversation behind a share link, fetched from the configured
+    /// proxy and decrypted, as JSON in the format of the web UI's sessions
+    /// (returns Promise)
+    #[wasm_bindgen(js_name = "openShareLink")]
+    pub fn open_share_link(&self, link: String) -> Promise {
+        let proxy = self.config.proxy_url.clone();
+
+        let future = async move {
+            let session = share::import(&link, &proxy).await?;
+            Ok(JsValue::from_str(&session.to_string()))
+        };
+
+        future_to_promise(future)
+    }
+
+    /// Markdown (e.g. an assistant reply) as sanitized HTML for the chat view
+    #[wasm_bindgen(js_name = "renderMarkdown")]
+    pub fn render_markdown(text: &str) -> String {
+        markdown::to_html(text)
+    }
+
+    /// `html` with everything outside the sanitizer's allowlist removed
+    #[wasm_bindgen(js_name = "sanitizeHtml")]
+    pub fn sanitize_html(html: &str) -> String {
+        sanitize::clean(html)
+    }
+
+    /// Chunk, embed and store a document for `ask_document` in a collection
+    /// (default: "default"), resolving to the number of chunks (returns Promise)
+    #[wasm_bindgen(js_name = "ingestDocument")]
+    pub fn ingest_document(&self, name: String, text: String, source: Option<String>, collection: Option<String>) -> Promise {
+        let memory = self.memory_handle();
+        
+        let future = async move {
+            let collection = collection.as_deref().unwrap_or(documents::DEFAULT_COLLECTION);
+            let chunks = documents::ingest(&memory, collection, &name, source.as_deref().unwrap_or("pasted text"), &text).await?;
+            Ok(JsValue::from(chunks as u32))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Ingested documents, of one collection or all, as JSON:
+    /// [{collection, name, source, chunks, ingested_at}]
+    #[wasm_bindgen(js_name = "listDocuments")]
+    pub fn list_documents(&self, collection: Option<String>) -> Result<String, JsValue> {
+        self.memory.borrow_mut().ensure_loaded()?;
+        serde_json::to_string(&documents::list(&self.memory.borrow(), collection.as_deref()))
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Delete a document's chunks, returning how many there were
+    #[wasm_bindgen(js_name = "deleteDocument")]
+    pub fn delete_document(&self, name: &str, collection: Option<String>) -> Result<u32, JsValue> {
+        let collection = collection.as_deref().unwrap_or(documents::DEFAULT_COLLECTION);
+        Ok(documents::remove(&self.memory, collection, name)? as u32)
+    }
+
+    /// Create a document collection; `settings_json` holds its retrieval
+    /// settings: {"limit": 5, "min_score": 0.0, "vector_weight": 0.7}
+    #[wasm_bindgen(js_name = "createCollection")]
+    pub fn create_collection(&self, name: &str, description: Option<String>, settings_json: Option<String>) -> Result<String, JsValue> {
+        let settings = parse_collection_settings(settings_json.as_deref())?;
+        let collection = documents::create_collection(&self.memory, name, description.as_deref().unwrap_or(""), settings)?;
+        serde_json::to_string(&collection)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Collections with their settings and counts as JSON:
+    /// [{name, description, settings, created_at, documents, chunks, chars, embedded}]
+    #[wasm_bindgen(js_name = "listCollections")]
+    pub fn list_collections(&self) -> Result<String, JsValue> {
+        self.memory.borrow_mut().ensure_loaded()?;
+        serde_json::to_string(&documents::collection_stats(&self.memory.borrow()))
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Replace a collection's retrieval settings, and its description if given
+    #[wasm_bindgen(js_name = "configureCollection")]
+    pub fn configure_collection(&self, name: &str, settings_json: &str, description: Option<String>) -> Result<String, JsValue> {
+        let settings = parse_collection_settings(Some(settings_json))?;
+        let collection = documents::configure_collection(&self.memory, name, description.as_deref(), settings)?;
+        serde_json::to_string(&collection)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Delete a collection with its documents, returning how many chunks
+    /// went; the default collection is only emptied
+    #[wasm_bindgen(js_name = "deleteCollection")]
+    pub fn delete_collection(&self, name: &str) -> Result<u32, JsValue> {
+        Ok(documents::delete_collection(&self.memory, name)? as u32)
+    }
+
+    /// Shared memory handle, with the OpenAI key passed on for embeddings
+    fn memory_handle(&self) -> Rc<RefCell<MemorySystem>> {
+        if self.config.provider.active == "openai" {
+            if let Some(key) = &self.config.provider.api_key {
+                self.memory.borrow_mut().set_api_key(key.clone());
+            }
+        }
+        self.memory.clone()
+    }
+
+    /// Export security configuration as JSON
+    #[wasm_bindgen(js_name = "exportSecurityConfig")]
+    pub fn export_security_config(&self) -> Result<String, JsValue> {
+        serde_json::to_string(self.security.borrow().get_config())
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Replace the security configuration (missing fields use defaults) and persist it
+    #[wasm_bindgen(js_name = "importSecurityConfig")]
+    pub fn import_security_config(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config = SecurityConfig::from_json(config_json).map_err(|e| JsValue::from_str(&e))?;
+        config.save()?;
+        self.security.borrow_mut().update_config(config);
+        audit::record(AuditKind::SecurityDecision, "security_config", "imported", config_json);
+        Ok(())
+    }
+
+    /// Enable/disable pairing mode
+    #[wasm_bindgen(js_name = "setPairingEnabled")]
+    pub fn set_pairing_enabled(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.security.borrow_mut().set_pairing_enabled(enabled);
+        audit::record(AuditKind::SecurityDecision, "pairing", if enabled { "enabled" } else { "disabled" }, "");
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Enable/disable sandbox mode
+    #[wasm_bindgen(js_name = "setSandboxEnabled")]
+    pub fn set_sandbox_enabled(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.security.borrow_mut().set_sandbox_enabled(enabled);
+        audit::record(AuditKind::SecurityDecision, "sandbox", if enabled { "enabled" } else { "disabled" }, "");
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Add domain to allowlist
+    #[wasm_bindgen(js_name = "allowDomain")]
+    pub fn allow_domain(&mut self, domain: String) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, &domain, "allow", "domain");
+        self.security.borrow_mut().allow_domain(domain);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Block a domain
+    #[wasm_bindgen(js_name = "blockDomain")]
+    pub fn block_domain(&mut self, domain: String) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, &domain, "block", "domain");
+        self.security.borrow_mut().block_domain(domain);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Add tool to allowlist
+    #[wasm_bindgen(js_name = "allowTool")]
+    pub fn allow_tool(&mut self, tool: String) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, &tool, "allow", "tool");
+        self.security.borrow_mut().allow_tool(tool);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Block a tool
+    #[wasm_bindgen(js_name = "blockTool")]
+    pub fn block_tool(&mut self, tool: String) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, &tool, "block", "tool");
+        self.security.borrow_mut().block_tool(tool);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Approve a pending action
+    #[wasm_bindgen(js_name = "approveAction")]
+    pub fn approve_action(&mut self, action_id: &str) -> Result<(), JsValue> {
+        self.security.borrow_mut().approve_action(action_id)?;
+        audit::record(AuditKind::Approval, action_id, "approved", "");
+        Ok(())
+    }
+
+    /// Deny a pending action
+    #[wasm_bindgen(js_name = "denyAction")]
+    pub fn deny_action(&mut self, action_id: &str) -> Result<(), JsValue> {
+        self.security.borrow_mut().deny_action(action_id)?;
+        audit::record(AuditKind::Approval, action_id, "denied", "");
+        Ok(())
+    }
+
+    /// Attach this instance to a session or channel user (e.g. "session:abc",
+    /// "telegram:12345") so its permission profile applies; `null` detaches
+    #[wasm_bindgen(js_name = "setSubject")]
+    pub fn set_subject(&mut self, subject: Option<String>) {
+        self.subject = subject;
+    }
+
+    /// Add or replace a named permission profile
+    ///
+    /// Fields (all optional): allowed_tools, allowed_domains, max_iterations.
+    #[wasm_bindgen(js_name = "setPermissionProfile")]
+    pub fn set_permission_profile(&mut self, name: String, profile_json: &str) -> Result<(), JsValue> {
+        let profile: PermissionProfile = serde_json::from_str(profile_json)
+            .map_err(|e| JsValue::from_str(&format!("Profile error: {}", e)))?;
+        audit::record(AuditKind::SecurityDecision, &name, "profile_set", profile_json);
+        self.security.borrow_mut().set_profile(name, profile);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Remove a permission profile and its bindings
+    #[wasm_bindgen(js_name = "removePermissionProfile")]
+    pub fn remove_permission_profile(&mut self, name: &str) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, name, "profile_removed", "");
+        self.security.borrow_mut().remove_profile(name);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Bind a subject (or a whole channel like "telegram") to a profile; `null` unbinds
+    #[wasm_bindgen(js_name = "bindPermissionProfile")]
+    pub fn bind_permission_profile(&mut self, subject: String, profile: Option<String>) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, &subject, "profile_bound", profile.as_deref().unwrap_or(""));
+        self.security.borrow_mut().bind_profile(subject, profile)?;
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Get actions awaiting approval as JSON: [{"id": ..., "action": ...}]
+    #[wasm_bindgen(js_name = "getPendingActions")]
+    pub fn get_pending_actions(&self) -> Result<String, JsValue> {
+        let pending: Vec<serde_json::Value> = self.security.borrow().pending_actions()
+            .into_iter()
+            .map(|(id, action)| serde_json::json!({ "id": id, "action": action }))
+            .collect();
+        serde_json::to_string(&pending)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Authorize scan_* tools against a domain (and its subdomains)
+    #[wasm_bindgen(js_name = "addScanScope")]
+    pub fn add_scan_scope(&mut self, domain: String) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, &domain, "allow", "scan_scope");
+        self.security.borrow_mut().add_scan_scope(domain);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Remove a domain from the authorized scan scope
+    #[wasm_bindgen(js_name = "removeScanScope")]
+    pub fn remove_scan_scope(&mut self, domain: &str) -> Result<(), JsValue> {
+        audit::record(AuditKind::SecurityDecision, domain, "block", "scan_scope");
+        self.security.borrow_mut().remove_scan_scope(domain);
+        Ok(self.security.borrow().get_config().save()?)
+    }
+
+    /// Register a target for recurring scan_full runs
+    ///
+    /// The target must be inside the scan scope. Options JSON (optional):
+    /// `{"alert_webhook": "https://hooks.slack.com/...", "alert_channel": "ops",
+    /// "alert_severity": "medium"}`, where `alert_channel` is a `[channels.<id>]`
+    /// of the proxy config, so its token stays on the proxy.
+    /// Returns the schedule ID.
+    #[wasm_bindgen(js_name = "scheduleScan")]
+    pub fn schedule_scan(&mut self, target: String, interval_minutes: u32, options_json: Option<String>) -> Result<String, JsValue> {
+        if !self.security.borrow().get_config().in_scan_scope(&target) {
+            return Err(JsValue::from_str(&format!(
+                "'{}' is not in the scan scope. Add it with addScanScope before scheduling scans.", target
+            )));
+        }
+        let options: ScheduleOptions = match options_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid schedule options: {}", e)))?,
+            None => ScheduleOptions::default(),
+        };
+        
+        let scan = ScheduledScan::new(&target, interval_minutes, options, chrono::Utc::now().timestamp());
+        let id = scan.id.clone();
+        let mut scans = scan_schedule::load()?;
+        scans.push(scan);
+        scan_schedule::save(&scans)?;
+        audit::record(AuditKind::ScheduledScan, &target, "scheduled", &format!("{} every {} min", id, interval_minutes));
+        Ok(id)
+    }
+
+    /// Stop a recurring scan, returns whether it existed
+    #[wasm_bindgen(js_name = "unscheduleScan")]
+    pub fn unschedule_scan(&mut self, id: &str) -> Result<bool, JsValue> {
+        let mut scans = scan_schedule::load()?;
+        let before = scans.len();
+        scans.retain(|s| s.id != id);
+        if scans.len() == before {
+            return Ok(false);
+        }
+        scan_schedule::save(&scans)?;
+        audit::record(AuditKind::ScheduledScan, id, "unscheduled", "");
+        Ok(true)
+    }
+
+    /// List recurring scans as JSON (with their last score and findings)
+    #[wasm_bindgen(js_name = "listScheduledScans")]
+    pub fn list_scheduled_scans() -> Result<String, JsValue> {
+        serde_json::to_string(&scan_schedule::load()?)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Run every scheduled scan that is due (returns Promise)
+    ///
+    /// Call this periodically (the web UI does so every minute). Resolves to a
+    /// JSON array of drift results for the scans that ran; entries with
+    /// `alert: true` introduced new findings and were sent to the webhook or
+    /// channel.
+    #[wasm_bindgen(js_name = "runDueScans")]
+    pub fn run_due_scans(&self) -> Promise {
+        let security = self.security.clone();
+        let proxy = self.config.proxy_url.clone();
+        let keep_awake = self.config.keep_awake;
+        
+        let future = async move {
+            let Some(_guard) = scan_schedule::RunGuard::acquire() else {
+                return Ok(JsValue::from_str("[]"));
+            };
+            let _awake = keep_awake.then(keepalive::hold);
+            let now = chrono::Utc::now().timestamp();
+            let due: Vec<ScheduledScan> = scan_schedule::load()?.into_iter()
+                .filter(|s| s.is_due(now))
+                .collect();
+            
+            let mut drifts = Vec::new();
+            for mut scan in due {
+                // Scope may have shrunk since the scan was scheduled
+                if !security.borrow().get_config().in_scan_scope(&scan.target) {
+                    audit::record(AuditKind::ScheduledScan, &scan.target, "deny", "target left the scan scope");
+                    continue;
+                }
+                let report = match tools::run_full_scan(&scan.target, &proxy).await {
+                    Ok(report) => report,
+                    Err(e) => {
+                        audit::record(AuditKind::ScheduledScan, &scan.target, "error", &format!("{:?}", e));
+                        continue;
+                    }
+                };
+                let drift = scan.record_run(&report, chrono::Utc::now().timestamp());
+                
+                if drift.alert {
+                    let message = drift.message();
+                    audit::record(AuditKind::ScheduledScan, &scan.target, "drift", &message);
+                    if let Some(webhook) = &scan.alert_webhook {
+                        if let Err(e) = tools::send_webhook(webhook, &message, &proxy).await {
+                            audit::record(AuditKind::ScheduledScan, &scan.target, "error", &format!("alert webhook: {:?}", e));
+                        }
+                    }
+                    if let Some(channel) = &scan.alert_channel {
+                        let channels = [channel.clone()];
+                        if let Err(e) = tools::send_notification(&channels, None, &message, drift.level(), &proxy).await {
+                            audit::record(AuditKind::ScheduledScan, &scan.target, "error", &format!("alert channel: {:?}", e));
+                        }
+                    }
+                } else {
+                    audit::record(AuditKind::ScheduledScan, &scan.target, "ok", &format!("score {}", drift.score));
+                }
+                
+                // Re-read so schedules changed during the scan aren't clobbered
+                let mut scans = scan_schedule::load()?;
+                if let Some(slot) = scans.iter_mut().find(|s| s.id == scan.id) {
+                    *slot = scan;
+                    scan_schedule::save(&scans)?;
+                }
+                drifts.push(drift);
+            }
+            
+            serde_json::to_string(&drifts)
+                .map(|json| JsValue::from_str(&json))
+                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+        };
+        
+        future_to_promise(future)
+    }
+
+    /// Get audit log entries as JSON, optionally filtered
+    ///
+    /// Filter fields (all optional): kind ("tool_call", "provider_request",
+    /// "security_decision", "approval", "scheduled_scan"), since/until (ms),
+    /// outcome, search, limit.
+    #[wasm_bindgen(js_name = "getAuditLog")]
+    pub fn get_audit_log(filter_json: Option<String>) -> Result<String, JsValue> {
+        let filter: AuditFilter = match filter_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid audit filter: {}", e)))?,
+            None => AuditFilter::default(),
+        };
+        serde_json::to_string(&audit::query(&filter)?)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Export the full audit log as JSON
+    #[wasm_bindgen(js_name = "exportAuditLog")]
+    pub fn export_audit_log() -> Result<String, JsValue> {
+        Ok(audit::export_json()?)
+    }
+
+    /// Recent log records as JSON, oldest first
+    ///
+    /// Filter fields (all optional): level (least severe to include),
+    /// module ("agent", "audit", "content_filter", ...), limit.
+    #[wasm_bindgen(js_name = "getLogs")]
+    pub fn get_logs(filter_json: Option<String>) -> Result<String, JsValue> {
+        let filter: LogFilter = match filter_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Invalid log filter: {}", e)))?,
+            None => LogFilter::default(),
+        };
+        serde_json::to_string(&logger::records(&filter))
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Drop all buffered log records
+    #[wasm_bindgen(js_name = "clearLogs")]
+    pub fn clear_logs() {
+        logger::clear();
+    }
+
+    /// Call `callback` with `{tool, message, step, total}` as long-running
+    /// tools (research) progress; `null` stops
+    #[wasm_bindgen(js_name = "onProgress")]
+    pub fn on_progress(callback: Option<js_sys::Function>) {
+        progress::set_listener(callback.map(|callback| -> progress::Listener {
+            Rc::new(move |report: &progress::Progress| {
+                if let Ok(value) = serde_wasm_bindgen::to_value(report) {
+                    let _ = callback.call1(&JsValue::NULL, &value);
+                }
+            })
+        }));
+    }
+
+    /// Call `callback` with each budget alert: `{provider, level, month,
+    /// used_tokens, limit_tokens, cost, limit_cost, message}` where level is
+    /// "warning", "exceeded" or "blocked"; `null` stops
+    #[wasm_bindgen(js_name = "onBudgetAlert")]
+    pub fn on_budget_alert(callback: Option<js_sys::Function>) {
+        usage::set_listener(callback.map(|callback| -> usage::Listener {
+            Rc::new(move |alert: &usage::Alert| {
+                if let Ok(value) = serde_wasm_bindgen::to_value(alert) {
+                    let _ = callback.call1(&JsValue::NULL, &value);
+                }
+            })
+        }));
+    }
+
+    /// Token usage and cost per provider as JSON, for `month` ("2026-10")
+    /// or the current one: `{month, providers: {name: {input_tokens,
+    /// output_tokens, requests, estimated, cost}}, budgets}`
+    #[wasm_bindgen(js_name = "getUsage")]
+    pub fn get_usage(&self, month: Option<String>) -> Result<String, JsValue> {
+        let month = month.unwrap_or_else(usage::this_month);
+        let providers = usage::load()?.remove(&month).unwrap_or_default();
+        serde_json::to_string(&serde_json::json!({
+            "month": month,
+            "providers": providers,
+            "budgets": self.config.budgets,
+        }))
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Forget this month's usage of `provider`, or of all providers
+    #[wasm_bindgen(js_name = "resetUsage")]
+    pub fn reset_usage(provider: Option<String>) -> Result<(), JsValue> {
+        Ok(usage::reset(provider.as_deref())?)
+    }
+
+    /// Record spans for turns, iterations, provider calls and tools
+    #[wasm_bindgen(js_name = "setTracing")]
+    pub fn set_tracing(enabled: bool) {
+        trace::set_enabled(enabled);
+    }
+
+    /// Recorded spans as JSON; `format` "otlp" gives OTLP/JSON instead
+    #[wasm_bindgen(js_name = "getTrace")]
+    pub fn get_trace(format: Option<String>) -> Result<String, JsValue> {
+        let spans = trace::spans();
+        let json = match format.as_deref() {
+            Some("otlp") => serde_json::to_string(&trace::to_otlp(&spans)),
+            None | Some("json") => serde_json::to_string(&spans),
+            Some(other) => return Err(JsValue::from_str(&format!("Unknown trace format '{}': use \"json\" or \"otlp\"", other))),
+        };
+        json.map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Drop all recorded spans
+    #[wasm_bindgen(js_name = "clearTrace")]
+    pub fn clear_trace() {
+        trace::clear();
+    }
+
+    /// Send recorded spans to an OTLP/HTTP collector through the proxy,
+    /// resolves to the number sent (returns Promise)
+    #[wasm_bindgen(js_name = "exportTrace")]
+    pub fn export_trace(&self, endpoint: &str) -> Promise {
+        let endpoint = endpoint.to_string();
+        let proxy = self.config.proxy_url.clone();
+        future_to_promise(async move {
+            let sent = trace::export_otlp(&endpoint, &proxy).await?;
+            Ok(JsValue::from_f64(sent as f64))
+        })
+    }
+
+    /// Export memory as portable JSON (embeddings omitted unless requested)
+    #[wasm_bindgen(js_name = "exportMemory")]
+    pub fn export_memory(&mut self, include_embeddings: Option<bool>) -> Result<String, JsValue> {
+        Ok(self.memory.borrow_mut().export_json(include_embeddings.unwrap_or(false))?)
+    }
+
+    /// Import memory from an export, returns import stats as JSON
+    #[wasm_bindgen(js_name = "importMemory")]
+    pub fn import_memory(&mut self, json: &str) -> Result<String, JsValue> {
+        let stats = self.memory.borrow_mut().import_json(json)?;
+        serde_json::to_string(&stats)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+
+    /// Unlock encrypted storage (enables encryption on first use)
+    ///
+    /// Returns true if encryption was just enabled.
+    #[wasm_bindgen(js_name = "unlockStorage")]
+    pub fn unlock_storage(&mut self, passphrase: &str) -> Result<bool, JsValue> {
+        let created = vault::unlock(passphrase)?;
+        self.memory.borrow_mut().unload();
+        Ok(created)
+    }
+
+    /// Lock encrypted storage and drop decrypted data from memory
+    #[wasm_bindgen(js_name = "lockStorage")]
+    pub fn lock_storage(&mut self) {
+        vault::lock();
+        self.memory.borrow_mut().unload();
+    }
+
+    /// Check whether encrypted storage is currently locked
+    #[wasm_bindgen(js_name = "isStorageLocked")]
+    pub fn is_storage_locked() -> bool {
+        vault::is_enabled() && !vault::is_unlocked()
+    }
+
+    /// Check whether encryption at rest is enabled
+    #[wasm_bindgen(js_name = "isEncryptionEnabled")]
+    pub fn is_encryption_enabled() -> bool {
+        vault::is_enabled()
+    }
+
+    /// Consolidate expired, rarely used memories into summaries (returns Promise)
+    #[wasm_bindgen(js_name = "consolidateMemory")]
+    pub fn consolidate_memory(&self) -> Promise {
+        let memory = self.memory.clone();
+        let provider = self.provider.clone();
+        let config = self.config.clone();
+        let policy = self.security.borrow().get_config().clone();
+        
+        let future = async move {
+            let id = memory::consolidate_with_provider(&memory, &provider, &config, &policy).await?;
+            Ok(id.map(|id| JsValue::from_str(&id)).unwrap_or(JsValue::NULL))
+        };
+        
+        future_to_promise(future)
+    }
+}
+
+impl Default for ClaWasm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The PDFs and audio tools saved, for a host UI's downloads panel
+#[wasm_bindgen]
+pub struct FileStore {}
+
+#[wasm_bindgen]
+impl FileStore {
+    /// Stored files, newest first, as JSON:
+    /// [{id, kind, filename, description, created_at, size, stored}]
+    #[wasm_bindgen]
+    pub fn list() -> Result<String, JsValue> {
+        Ok(serde_json::to_string(&files::list()?).unwrap())
+    }
+
+    /// A file's contents as a Blob of its MIME type, checked against the
+    /// file's hash
+    #[wasm_bindgen(js_name = "getFile")]
+    pub fn get_file(id: &str) -> Result<web_sys::Blob, JsValue> {
+        let (file, bytes) = files::get(id)?;
+        let options = web_sys::BlobPropertyBag::new();
+        options.set_type(file.kind.mime(&file.filename));
+        web_sys::Blob::new_with_u8_array_sequence_and_options(&js_sys::Array::of1(&js_sys::Uint8Array::from(bytes.as_slice())), &options)
+    }
+
+    /// Number of chunks a file is stored in, for `readChunk`
+    #[wasm_bindgen(js_name = "chunkCount")]
+    pub fn chunk_count(id: &str) -> Result<u32, JsValue> {
+        Ok(files::chunk_count(id)? as u32)
+    }
+
+    /// Chunk `index` of a file (up to 256 KiB), checked against its hash:
+    /// read large files piece by piece, resuming after an interruption
+    #[wasm_bindgen(js_name = "readChunk")]
+    pub fn read_chunk(id: &str, index: u32) -> Result<Vec<u8>, JsValue> {
+        Ok(files::read_chunk(id, index as usize)?)
+    }
+
+    /// Change a file's download name (its extension is kept), returning
+    /// its listing as JSON
+    #[wasm_bindgen(js_name = "renameFile")]
+    pub fn rename_file(id: &str, filename: &str) -> Result<String, JsValue> {
+        Ok(serde_json::to_string(&files::rename(id, filename)?).unwrap())
+    }
+
+    /// Delete a file; false if there was none
+    #[wasm_bindgen(js_name = "deleteFile")]
+    pub fn delete_file(id: &str) -> Result<bool, JsValue> {
+        Ok(files::delete(id)?)
+    }
+
+    /// Space taken per kind of file, as JSON: [{kind, files, size, stored}]
+    /// (`stored` counts characters, against the localStorage quota)
+    #[wasm_bindgen]
+    pub fn usage() -> Result<String, JsValue> {
+        Ok(serde_json::to_string(&files::usage()?).unwrap())
+    }
+
+    /// Delete files created more than `days` days ago, returning their IDs
+    #[wasm_bindgen]
+    pub fn cleanup(days: u32) -> Result<Vec<String>, JsValue> {
+        Ok(files::cleanup(days)?)
+    }
+}
+
+/// Answers the messages the proxy's bots queue, with the browser's agent
+#[wasm_bindgen]
+pub struct ChannelBridge {
+    agent: Agent,
+    system_prompt: String,
+    channels: Vec<String>,
+    running: Rc<Cell<bool>>,
+}
+
+#[wasm_bindgen]
+impl ChannelBridge {
+    /// Answer `channels` (e.g. ["telegram", "slack"]) with `agent`'s provider,
+    /// model, keys and proxy as they are now
+    ///
+    /// The bridge shares the agent's memory but keeps its own pending
+    /// approvals, decided from the channel.
+    #[wasm_bindgen(constructor)]
+    pub fn new(agent: &ClaWasm, channels: Vec<String>) -> Result<ChannelBridge, JsValue> {
+        let channels: Vec<String> = channels.iter().map(|c| c.trim().to_lowercase()).collect();
+        if channels.is_empty() {
+            return Err(JsValue::from_str("No channels to answer"));
+        }
+        if let Some(unknown) = channels.iter().find(|c| !bridge::CHANNELS.contains(&c.as_str())) {
+            return Err(JsValue::from_str(&format!("Unknown channel '{}': use {}", unknown, bridge::CHANNELS.join(", "))));
+        }
+        Ok(ChannelBridge {
+            agent: Agent {
+                config: agent.config.clone(),
+                provider: Rc::new(agent.provider.clone()),
+                memory: agent.memory.clone(),
+                security: Rc::new(RefCell::new(SecurityManager::new(SecurityConfig::load()))),
+                subject: None,
+            },
+            system_prompt: ClaWasm::build_system_prompt(&agent.config.system_prompt, agent.config.tool_format),
+            channels,
+            running: Rc::new(Cell::new(false)),
+        })
+    }
+
+    /// Take the queued messages once, waiting up to `wait_secs` (default 25)
+    /// for one, and answer them; resolves to how many were answered
+    #[wasm_bindgen]
+    pub fn poll(&self, wait_secs: Option<u32>) -> Promise {
+        let (agent, system_prompt, channels) = (self.agent.clone(), self.system_prompt.clone(), self.channels.clone());
+        future_to_promise(async move {
+            let answered = answer_queued(&agent, &system_prompt, &channels, wait_secs.unwrap_or(25)).await?;
+            Ok(JsValue::from(answered))
+        })
+    }
+
+    /// Keep polling until `stop()`; the promise resolves once stopped
+    ///
+    /// A failed poll (the proxy restarting, say) is logged and retried
+    /// after five seconds.
+    #[wasm_bindgen]
+    pub fn start(&self) -> Promise {
+        let (agent, system_prompt, channels) = (self.agent.clone(), self.system_prompt.clone(), self.channels.clone());
+        let running = self.running.clone();
+        running.set(true);
+        future_to_promise(async move {
+            while running.get() {
+                if let Err(e) = answer_queued(&agent, &system_prompt, &channels, 25).await {
+                    logger::warn("bridge", &e.to_string());
+                    sleep(5000).await;
+                }
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Stop after the poll in flight
+    #[wasm_bindgen]
+    pub fn stop(&self) {
+        self.running.set(false);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn running(&self) -> bool {
+        self.running.get()
+    }
+}
+
+/// One poll of the inbox, each message answered as the one who wrote it
+async fn answer_queued(agent: &Agent, system_prompt: &str, channels: &[String], wait_secs: u32) -> crate::error::Result<u32> {
+    let mut answered = 0;
+    for message in bridge::poll(&agent.config.proxy_url, channels, wait_secs).await? {
+        let mut agent = agent.clone();
+        agent.subject = Some(message.subject());
+        // A model picked in the chat with /model wins
+        if let Some(model) = message.model() {
+            Rc::make_mut(&mut agent.config).provider.model = model;
+        }
+        let tools = BrowserTools { agent: agent.clone() };
+        match bridge::answer(&message, &agent, &tools, system_prompt).await {
+            Ok(()) => answered += 1,
+            Err(e) => logger::warn("bridge", &e.to_string()),
+        }
+    }
+    if answered > 0 {
+        let agent = agent.clone();
+        wasm_bindgen_futures::spawn_local(async move { agent.consolidate_if_due().await });
+    }
+    Ok(answered)
+}
+
+async fn sleep(ms: i32) {
+    let promise = Promise::new(&mut |resolve, _| {
+        if let Some(window) = web_sys::window() {
+            let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
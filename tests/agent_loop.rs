@@ -0,0 +1,379 @@
+//! Agent loop tests against a scripted model
+//!
+//! Run in a headless browser with
+//! `wasm-pack test --headless --firefox` (or `--chrome`). On native targets
+//! these only type-check: storage and the audit log need localStorage.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use clawasm::agent::{Agent, ToolFuture, ToolRunner};
+use clawasm::chat::{Message, Role};
+use clawasm::config::Config;
+use clawasm::error::Error;
+use clawasm::memory::{MemoryConfig, MemorySystem};
+use clawasm::providers::{ChatFuture, ChatModel};
+use clawasm::reasoning::ReasoningMode;
+use clawasm::tool_grammar::ToolFormat;
+use clawasm::security::{PermissionProfile, SecurityConfig, SecurityManager};
+use clawasm::trace;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// Replies from a script, then "done"; records every request
+#[derive(Default)]
+struct ScriptedModel {
+    replies: RefCell<VecDeque<String>>,
+    requests: RefCell<Vec<Vec<Message>>>,
+    /// Reply with this forever once the script runs out
+    repeat: Option<String>,
+}
+
+impl ScriptedModel {
+    fn new(replies: &[&str]) -> Self {
+        ScriptedModel {
+            replies: RefCell::new(replies.iter().map(|r| r.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn repeating(reply: &str) -> Self {
+        ScriptedModel { repeat: Some(reply.to_string()), ..Default::default() }
+    }
+}
+
+impl ChatModel for ScriptedModel {
+    fn complete<'a>(&'a self, messages: &'a [Message], _config: &'a Config) -> ChatFuture<'a> {
+        self.requests.borrow_mut().push(messages.to_vec());
+        let reply = self.replies.borrow_mut().pop_front()
+            .or_else(|| self.repeat.clone())
+            .unwrap_or_else(|| "done".to_string());
+        Box::pin(async move { Ok(reply) })
+    }
+}
+
+/// Returns canned results and records what ran
+#[derive(Default)]
+struct RecordingTools {
+    results: HashMap<String, String>,
+    calls: RefCell<Vec<(String, serde_json::Value)>>,
+}
+
+impl RecordingTools {
+    fn with(name: &str, result: &str) -> Self {
+        let mut tools = RecordingTools::default();
+        tools.results.insert(name.to_string(), result.to_string());
+        tools
+    }
+}
+
+impl ToolRunner for RecordingTools {
+    fn run<'a>(&'a self, name: &'a str, args: &'a serde_json::Value) -> ToolFuture<'a> {
+        self.calls.borrow_mut().push((name.to_string(), args.clone()));
+        let result = self.results.get(name).cloned()
+            .ok_or_else(|| Error::new(format!("Unknown tool: {}", name)));
+        Box::pin(async move { result })
+    }
+}
+
+fn agent(model: &Rc<ScriptedModel>, policy: SecurityConfig) -> Agent {
+    Agent {
+        config: Rc::new(Config::default()),
+        provider: model.clone(),
+        memory: Rc::new(RefCell::new(MemorySystem::new(MemoryConfig::default()))),
+        security: Rc::new(RefCell::new(SecurityManager::new(policy))),
+        subject: None,
+    }
+}
+
+fn conversation(user: &str) -> Vec<Message> {
+    vec![Message::system("You are a test assistant."), Message::user(user)]
+}
+
+const CALCULATE: &str = "```tool\n{\"name\": \"calculate\", \"arguments\": {\"expression\": \"6*7\"}}\n```";
+
+#[wasm_bindgen_test]
+async fn answers_without_tools() {
+    let model = Rc::new(ScriptedModel::new(&["Hello!"]));
+    let tools = RecordingTools::default();
+
+    let turn = agent(&model, SecurityConfig::default()).run_turn(&mut conversation("hi"), &tools).await.unwrap();
+
+    assert_eq!(turn.response, "Hello!");
+    assert!(turn.tool_calls.is_empty());
+    assert!(tools.calls.borrow().is_empty());
+    assert_eq!(model.requests.borrow().len(), 1);
+}
+
+#[wasm_bindgen_test]
+async fn feeds_tool_results_back_to_the_model() {
+    let model = Rc::new(ScriptedModel::new(&[CALCULATE, "It is 42."]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+
+    let turn = agent(&model, SecurityConfig::default()).run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    assert_eq!(turn.response, "It is 42.");
+    assert_eq!(turn.tool_calls.len(), 1);
+    assert_eq!(turn.tool_calls[0].name, "calculate");
+    assert_eq!(tools.calls.borrow()[0].1["expression"], "6*7");
+
+    let requests = model.requests.borrow();
+    assert_eq!(requests.len(), 2);
+    let followup = &requests[1];
+    assert_eq!(followup[followup.len() - 2].content, CALCULATE);
+    assert_eq!(followup.last().unwrap().content, "Tool 'calculate' returned:\nResult: 42");
+}
+
+#[wasm_bindgen_test]
+async fn keeps_thinking_out_of_tool_parsing_and_the_answer() {
+    let thinking = format!("<think>Maybe I should send {}? No, I know this.</think>\n42.", CALCULATE);
+    let model = Rc::new(ScriptedModel::new(&[&thinking]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let mut agent = agent(&model, SecurityConfig::default());
+    Rc::make_mut(&mut agent.config).reasoning = ReasoningMode::Expose;
+
+    let turn = agent.run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    assert_eq!(turn.response, "42.");
+    assert!(turn.tool_calls.is_empty() && tools.calls.borrow().is_empty());
+    assert_eq!(turn.reasoning.len(), 1);
+    assert!(turn.reasoning[0].starts_with("Maybe I should send"));
+}
+
+#[wasm_bindgen_test]
+async fn asks_again_for_malformed_strict_calls() {
+    let bad = "<<<CALL\n{\"name\": \"calculate\", \"arguments\": {\"expression\": 42}}\nCALL>>>";
+    let good = "<<<CALL\n{\"name\": \"calculate\", \"arguments\": {\"expression\": \"6*7\"}}\nCALL>>>";
+    let model = Rc::new(ScriptedModel::new(&[bad, good, "It is 42."]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let mut agent = agent(&model, SecurityConfig::default());
+    Rc::make_mut(&mut agent.config).tool_format = ToolFormat::Strict;
+
+    let turn = agent.run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    assert_eq!(turn.response, "It is 42.");
+    assert_eq!(tools.calls.borrow().len(), 1);
+    let requests = model.requests.borrow();
+    assert_eq!(requests.len(), 3);
+    let reask = requests[1].last().unwrap();
+    assert!(matches!(reask.role, Role::User) && reask.content.contains("expression: must be a string, not an integer"), "{}", reask.content);
+}
+
+#[wasm_bindgen_test]
+async fn gives_up_on_strict_calls_after_the_corrections() {
+    let model = Rc::new(ScriptedModel::repeating("Let me check.\n```tool\n{\"name\": \"calculate\", \"arguments\": {}}\n```"));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let mut agent = agent(&model, SecurityConfig::default());
+    Rc::make_mut(&mut agent.config).tool_format = ToolFormat::Strict;
+
+    let turn = agent.run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    // The first reply and two corrections; nothing run, the prose kept
+    assert_eq!(model.requests.borrow().len(), 3);
+    assert!(tools.calls.borrow().is_empty());
+    assert_eq!(turn.response, "Let me check.");
+}
+
+#[wasm_bindgen_test]
+async fn runs_every_call_in_a_reply() {
+    let reply = "```tool\n{\"name\": \"calculate\", \"arguments\": {\"expression\": \"1+1\"}}\n```\n\
+        ```tool\n{\"name\": \"get_current_time\", \"arguments\": {}}\n```";
+    let model = Rc::new(ScriptedModel::new(&[reply, "Both done."]));
+    let mut tools = RecordingTools::with("calculate", "Result: 2");
+    tools.results.insert("get_current_time".to_string(), "noon".to_string());
+
+    let turn = agent(&model, SecurityConfig::default()).run_turn(&mut conversation("both"), &tools).await.unwrap();
+
+    let names: Vec<&str> = turn.tool_calls.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["calculate", "get_current_time"]);
+    let results = &model.requests.borrow()[1];
+    let results = &results.last().unwrap().content;
+    assert!(results.contains("Result: 2") && results.contains("noon"));
+}
+
+#[wasm_bindgen_test]
+async fn reports_tool_errors_to_the_model() {
+    let model = Rc::new(ScriptedModel::new(&[CALCULATE, "Sorry."]));
+    let tools = RecordingTools::default();
+
+    let turn = agent(&model, SecurityConfig::default()).run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    assert_eq!(turn.response, "Sorry.");
+    let followup = &model.requests.borrow()[1];
+    assert!(followup.last().unwrap().content.contains("Error: Unknown tool: calculate"));
+}
+
+#[wasm_bindgen_test]
+async fn stops_at_the_iteration_budget() {
+    let model = Rc::new(ScriptedModel::repeating(CALCULATE));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let mut policy = SecurityConfig::default();
+    policy.profiles.insert("tight".to_string(), PermissionProfile { max_iterations: 3, ..Default::default() });
+    policy.default_profile = Some("tight".to_string());
+
+    let turn = agent(&model, policy).run_turn(&mut conversation("loop"), &tools).await.unwrap();
+
+    assert_eq!(turn.tool_calls.len(), 3);
+    // Identical calls after the first are answered from its result
+    assert_eq!(tools.calls.borrow().len(), 1);
+    // The first request plus one after each iteration
+    assert_eq!(model.requests.borrow().len(), 4);
+    // Still calling tools at the end: a best-effort answer, not raw tool JSON
+    assert!(turn.response.contains("Result: 42") && !turn.response.contains("```tool"), "{}", turn.response);
+}
+
+#[wasm_bindgen_test]
+async fn corrects_and_then_escapes_a_tool_loop() {
+    let model = Rc::new(ScriptedModel::repeating(CALCULATE));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+
+    let turn = agent(&model, SecurityConfig::default()).run_turn(&mut conversation("loop"), &tools).await.unwrap();
+
+    // Two corrections, then the loop is cut short of the 10-iteration budget
+    let requests = model.requests.borrow();
+    assert_eq!(requests.len(), 4);
+    for request in &requests[2..] {
+        let last = request.last().unwrap();
+        assert!(matches!(last.role, Role::System) && last.content.contains("answer the user now"), "{}", last.content);
+        assert_eq!(request.iter().filter(|m| m.content.contains("answer the user now")).count(), 1, "one correction at a time");
+    }
+    assert!(turn.response.starts_with("I kept calling tools without reaching an answer"), "{}", turn.response);
+    assert!(turn.response.contains("**calculate**:\nResult: 42"));
+}
+
+#[wasm_bindgen_test]
+async fn corrects_loops_of_denied_and_failing_calls() {
+    let model = Rc::new(ScriptedModel::repeating(CALCULATE));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let policy = SecurityConfig { blocked_tools: vec!["calculate".to_string()], ..Default::default() };
+
+    agent(&model, policy).run_turn(&mut conversation("loop"), &tools).await.unwrap();
+    assert!(tools.calls.borrow().is_empty());
+    assert_eq!(model.requests.borrow().len(), 4, "cut short like an allowed loop");
+
+    let model = Rc::new(ScriptedModel::repeating(CALCULATE));
+    let failing = RecordingTools::default();
+    agent(&model, SecurityConfig::default()).run_turn(&mut conversation("loop"), &failing).await.unwrap();
+    // Errors may be transient, so each retry runs, but the loop is still cut
+    assert_eq!(failing.calls.borrow().len(), 4);
+    assert_eq!(model.requests.borrow().len(), 4);
+}
+
+#[wasm_bindgen_test]
+async fn hands_the_history_back_as_it_was() {
+    let model = Rc::new(ScriptedModel::new(&[CALCULATE, "It is 42."]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let mut history = conversation("6*7?");
+
+    agent(&model, SecurityConfig::default()).run_turn(&mut history, &tools).await.unwrap();
+
+    let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["You are a test assistant.", "6*7?"]);
+    assert_eq!(model.requests.borrow()[1].len(), 4, "the turn still saw its own messages");
+
+    let calls: Vec<String> = (0..6)
+        .map(|i| format!("```tool\n{{\"name\": \"calculate\", \"arguments\": {{\"expression\": \"{}*7\"}}}}\n```", i))
+        .collect();
+    let model = Rc::new(ScriptedModel::new(&calls.iter().map(String::as_str).collect::<Vec<_>>()));
+    let tools = RecordingTools::with("calculate", &"x".repeat(30_000));
+    agent(&model, SecurityConfig::default()).run_turn(&mut history, &tools).await.unwrap();
+    assert_eq!(history.len(), 2, "trimming works on a copy");
+}
+
+#[wasm_bindgen_test]
+async fn answers_repeated_calls_from_the_first_result() {
+    let other = "```tool\n{\"name\": \"calculate\", \"arguments\": {\"expression\": \"1+1\"}}\n```";
+    let model = Rc::new(ScriptedModel::new(&[CALCULATE, CALCULATE, other, "It is 42."]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+
+    let turn = agent(&model, SecurityConfig::default()).run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    assert_eq!(turn.tool_calls.len(), 3);
+    let ran: Vec<String> = tools.calls.borrow().iter().map(|(_, args)| args["expression"].to_string()).collect();
+    assert_eq!(ran, ["\"6*7\"", "\"1+1\""]);
+    let repeated = &model.requests.borrow()[2];
+    let notice = &repeated.last().unwrap().content;
+    assert!(notice.contains("Already called 'calculate' with these exact arguments") && notice.ends_with("Result: 42"), "{}", notice);
+}
+
+#[wasm_bindgen_test]
+async fn trims_a_growing_context() {
+    let calls: Vec<String> = (0..6)
+        .map(|i| format!("```tool\n{{\"name\": \"calculate\", \"arguments\": {{\"expression\": \"{}*7\"}}}}\n```", i))
+        .collect();
+    let model = Rc::new(ScriptedModel::new(&calls.iter().map(String::as_str).collect::<Vec<_>>()));
+    let tools = RecordingTools::with("calculate", &"x".repeat(30_000));
+
+    agent(&model, SecurityConfig::default()).run_turn(&mut conversation("loop"), &tools).await.unwrap();
+
+    let requests = model.requests.borrow();
+    for request in requests.iter() {
+        let chars: usize = request.iter().map(|m| m.content.chars().count()).sum();
+        assert!(chars <= 100_000, "sent {} chars", chars);
+    }
+    let last = requests.last().unwrap();
+    assert!(matches!(last[0].role, Role::System), "system prompt was trimmed");
+    assert!(!last.iter().any(|m| m.content == "loop"), "oldest messages should go first");
+}
+
+#[wasm_bindgen_test]
+async fn profile_blocks_tools_outside_its_list() {
+    let model = Rc::new(ScriptedModel::new(&[CALCULATE, "Not allowed."]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    let mut policy = SecurityConfig::default();
+    policy.profiles.insert("search".to_string(), PermissionProfile {
+        allowed_tools: vec!["web_search".to_string()],
+        ..Default::default()
+    });
+    policy.default_profile = Some("search".to_string());
+
+    let turn = agent(&model, policy).run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+
+    assert_eq!(turn.tool_calls.len(), 1);
+    assert!(tools.calls.borrow().is_empty(), "denied tool must not run");
+    let followup = &model.requests.borrow()[1];
+    assert!(followup.last().unwrap().content.contains("not permitted"));
+}
+
+#[wasm_bindgen_test]
+async fn out_of_scope_scans_wait_for_approval() {
+    let scan = "```tool\n{\"name\": \"scan_headers\", \"arguments\": {\"url\": \"https://example.com\"}}\n```";
+    let model = Rc::new(ScriptedModel::new(&[scan, "Please approve.", scan, "Scanned."]));
+    let tools = RecordingTools::with("scan_headers", "Grade: A");
+    let agent = agent(&model, SecurityConfig::default());
+
+    let turn = agent.run_turn(&mut conversation("scan example.com"), &tools).await.unwrap();
+    assert_eq!(turn.response, "Please approve.");
+    assert!(tools.calls.borrow().is_empty(), "scan ran before approval");
+
+    let pending = agent.security.borrow().pending_actions();
+    assert_eq!(pending.len(), 1);
+    agent.security.borrow_mut().approve_action(&pending[0].0).unwrap();
+
+    let turn = agent.run_turn(&mut conversation("scan example.com"), &tools).await.unwrap();
+    assert_eq!(turn.response, "Scanned.");
+    assert_eq!(tools.calls.borrow().len(), 1);
+}
+
+#[wasm_bindgen_test]
+async fn traces_turns_iterations_and_tools() {
+    let model = Rc::new(ScriptedModel::new(&[CALCULATE, "It is 42."]));
+    let tools = RecordingTools::with("calculate", "Result: 42");
+    trace::clear();
+    trace::set_enabled(true);
+
+    agent(&model, SecurityConfig::default()).run_turn(&mut conversation("6*7?"), &tools).await.unwrap();
+    trace::set_enabled(false);
+
+    let spans = trace::spans();
+    let names: Vec<&str> = spans.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["provider.chat", "tool.execute", "provider.chat", "agent.iteration", "agent.turn"]);
+    let turn = spans.last().unwrap();
+    assert_eq!(turn.attributes["iterations"], 2);
+    assert!(spans.iter().all(|s| s.trace_id == turn.trace_id));
+    assert_eq!(spans[1].attributes["tool"], "calculate");
+    assert_eq!(spans[1].attributes["decision"], "allow");
+    trace::clear();
+}
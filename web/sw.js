@@ -0,0 +1,75 @@
+// claWasm service worker: keeps the app shell cached for offline startup
+//
+// The page, the wasm bundle and the PDF fonts are cached on install and
+// served network-first, so a new build is picked up whenever the network
+// is there and the last one still starts without it. The pinned CDN
+// scripts are served cache-first. API and proxy requests are not touched:
+// the wasm side queues those while offline (see src/offline.rs).
+
+const CACHE = 'clawasm-shell-v1';
+
+const SHELL = [
+    './',
+    './index.html',
+    './pkg/clawasm.js',
+    './pkg/clawasm_bg.wasm',
+    './fonts/fontkit.umd.js',
+    './fonts/DejaVuSans.ttf',
+];
+
+// Versioned URLs, so a cached copy never goes stale
+const PINNED = [
+    'https://cdnjs.cloudflare.com/ajax/libs/jspdf/2.5.1/jspdf.umd.min.js',
+    'https://unpkg.com/pdf-lib@1.17.1/dist/pdf-lib.min.js',
+];
+
+self.addEventListener('install', event => {
+    event.waitUntil(
+        caches.open(CACHE)
+            .then(cache => cache.addAll(SHELL))
+            .then(() => self.skipWaiting())
+    );
+});
+
+self.addEventListener('activate', event => {
+    event.waitUntil(
+        caches.keys()
+            .then(keys => Promise.all(keys.filter(key => key !== CACHE).map(key => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+const shellUrls = SHELL.map(path => new URL(path, self.registration.scope).href);
+
+async function networkFirst(request) {
+    const cache = await caches.open(CACHE);
+    try {
+        const response = await fetch(request);
+        if (response.ok) cache.put(request, response.clone());
+        return response;
+    } catch (err) {
+        const cached = await cache.match(request, { ignoreSearch: true });
+        if (cached) return cached;
+        throw err;
+    }
+}
+
+async function cacheFirst(request) {
+    const cache = await caches.open(CACHE);
+    const cached = await cache.match(request);
+    if (cached) return cached;
+    const response = await fetch(request);
+    if (response.ok || response.type === 'opaque') cache.put(request, response.clone());
+    return response;
+}
+
+self.addEventListener('fetch', event => {
+    const { request } = event;
+    if (request.method !== 'GET') return;
+    const url = request.url.split('#')[0];
+    if (shellUrls.includes(url.split('?')[0])) {
+        event.respondWith(networkFirst(request));
+    } else if (PINNED.includes(url)) {
+        event.respondWith(cacheFirst(request));
+    }
+});